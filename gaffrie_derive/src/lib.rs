@@ -0,0 +1,242 @@
+//! Derives parsing and drawing of fixed-layout structures for the format explorer
+//!
+//! ```ignore
+//! #[derive(FileFormat)]
+//! #[format(little_endian)]
+//! pub struct FileHeader {
+//!     #[format(meaning = machine_name)]
+//!     pub machine: u16,
+//!     pub section_count: u16,
+//!     #[format(hex)]
+//!     pub symbol_table_offset: u32,
+//!     #[format(flags = FILE_CHARACTERISTICS)]
+//!     pub characteristics: u16,
+//! }
+//! ```
+//!
+//! The fields are stored one after the other in the order they are declared, each parsed with
+//! its `Layout` implementation and shown with its `FileFormatUi` one unless told otherwise.
+//!
+//! On the structure:
+//! - `big_endian` or `little_endian`: the byte order of the fields, by default the one passed
+//!   to `Layout::parse`
+//!
+//! On fields:
+//! - `big_endian` or `little_endian`: the byte order of this field
+//! - `name = "..."`: the name to show instead of the field's
+//! - `hidden`: parsed but not shown, like reserved bytes
+//! - `hex`: the number in hexadecimal, like offsets and addresses
+//! - `meaning = function`: the number with what it means, from a function taking the value
+//! - `flags = NAMES`: the number with the names of its set bits, from `(bit, name)` pairs
+//! - `display = function`: the text from a function taking a reference to the value
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Ident, LitStr, Path, Type};
+
+#[proc_macro_derive(FileFormat, attributes(format))]
+pub fn derive_file_format(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// How a field is shown
+enum Display {
+    /// With its own `FileFormatUi` implementation
+    Default,
+    Hidden,
+    Hex,
+    Meaning(Path),
+    Flags(Path),
+    Function(Path),
+}
+
+struct Field {
+    ident: Ident,
+    ty: Type,
+    name: String,
+    /// Byte order that overrides the structure's, `true` for big endian
+    big_endian: Option<bool>,
+    display: Display,
+}
+
+/// Byte order and display options of a `#[format(...)]` attribute
+#[derive(Default)]
+struct Options {
+    big_endian: Option<bool>,
+    name: Option<String>,
+    display: Option<Display>,
+}
+
+fn parse_options(attributes: &[syn::Attribute], on_field: bool) -> syn::Result<Options> {
+    let mut options = Options::default();
+    for attribute in attributes {
+        if !attribute.path().is_ident("format") {
+            continue;
+        }
+        attribute.parse_nested_meta(|meta| {
+            let path = &meta.path;
+            if path.is_ident("big_endian") || path.is_ident("little_endian") {
+                if options.big_endian.is_some() {
+                    return Err(meta.error("the byte order is already set"));
+                }
+                options.big_endian = Some(path.is_ident("big_endian"));
+                return Ok(());
+            }
+            if !on_field {
+                return Err(meta.error("expected `big_endian` or `little_endian`"));
+            }
+            if path.is_ident("name") {
+                options.name = Some(meta.value()?.parse::<LitStr>()?.value());
+                return Ok(());
+            }
+            let display = if path.is_ident("hidden") {
+                Display::Hidden
+            } else if path.is_ident("hex") {
+                Display::Hex
+            } else if path.is_ident("meaning") {
+                Display::Meaning(meta.value()?.parse()?)
+            } else if path.is_ident("flags") {
+                Display::Flags(meta.value()?.parse()?)
+            } else if path.is_ident("display") {
+                Display::Function(meta.value()?.parse()?)
+            } else {
+                return Err(meta.error("unknown format option"));
+            };
+            if options.display.is_some() {
+                return Err(meta.error("the field already has a way to be shown"));
+            }
+            options.display = Some(display);
+            Ok(())
+        })?;
+    }
+    Ok(options)
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "FileFormat can only be derived for structs",
+        ));
+    };
+    let Fields::Named(named) = &data.fields else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "FileFormat needs the fields to have names",
+        ));
+    };
+    let big_endian = parse_options(&input.attrs, false)?.big_endian;
+    let fields = named
+        .named
+        .iter()
+        .map(|field| {
+            let ident = field.ident.clone().unwrap();
+            let options = parse_options(&field.attrs, true)?;
+            // `type_` and `r#type` are shown as `type`
+            let default_name = ident.to_string();
+            let default_name = default_name.trim_start_matches("r#").trim_end_matches('_');
+            Ok(Field {
+                name: options.name.unwrap_or_else(|| default_name.to_string()),
+                ty: field.ty.clone(),
+                big_endian: options.big_endian,
+                display: options.display.unwrap_or(Display::Default),
+                ident,
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let ident = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let explorer = quote!(crate::tools::format_explorer);
+
+    let sizes = fields.iter().map(|field| {
+        let ty = &field.ty;
+        quote!(<#ty as #explorer::Layout>::SIZE)
+    });
+    // The parameter goes unused when the byte order of every field is fixed
+    let uses_parameter =
+        big_endian.is_none() && fields.iter().any(|field| field.big_endian.is_none());
+    let parameter = if uses_parameter {
+        quote!(big_endian)
+    } else {
+        quote!(_)
+    };
+    let parsers = fields.iter().map(|field| {
+        let (ident, ty) = (&field.ident, &field.ty);
+        let order = match field.big_endian.or(big_endian) {
+            Some(big_endian) => quote!(#big_endian),
+            None => quote!(big_endian),
+        };
+        quote! {
+            let (input, #ident) = <#ty as #explorer::Layout>::parse(input, #order)?;
+        }
+    });
+    let idents = fields.iter().map(|field| &field.ident);
+
+    let field_uis = fields.iter().map(|field| {
+        let (ident, ty, name) = (&field.ident, &field.ty, &field.name);
+        let size = quote!(<#ty as #explorer::Layout>::SIZE);
+        let context = quote!(fields.next(#size));
+        match &field.display {
+            Display::Default => quote! {
+                #explorer::FileFormatUi::ui(&mut self.#ident, ui, #name, &#context);
+            },
+            // Read so the field doesn't look unused
+            Display::Hidden => quote! {
+                let _ = &self.#ident;
+                fields.skip(#size);
+            },
+            Display::Hex => quote! {
+                #context.field(ui, #name, &format!("{:#x}", self.#ident));
+            },
+            Display::Meaning(function) => quote! {
+                #context.enum_field(
+                    ui,
+                    #name,
+                    u64::from(self.#ident),
+                    &#function(self.#ident.into()),
+                );
+            },
+            Display::Flags(names) => quote! {
+                #context.enum_field(
+                    ui,
+                    #name,
+                    u64::from(self.#ident),
+                    &#explorer::formats::flag_names(u64::from(self.#ident), #names),
+                );
+            },
+            Display::Function(function) => quote! {
+                #context.field(ui, #name, &#function(&self.#ident));
+            },
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics #explorer::Layout for #ident #type_generics #where_clause {
+            const SIZE: u64 = 0 #(+ #sizes)*;
+
+            fn parse(input: &[u8], #parameter: bool) -> nom::IResult<&[u8], Self> {
+                #(#parsers)*
+                Ok((input, Self { #(#idents),* }))
+            }
+        }
+
+        impl #impl_generics #explorer::FileFormatUi for #ident #type_generics #where_clause {
+            fn ui(
+                &mut self,
+                ui: &mut egui::Ui,
+                name: &str,
+                context: &#explorer::FormatContext,
+            ) {
+                context.collapsing(ui, name, |ui| {
+                    #[allow(unused_mut, unused_variables)]
+                    let mut fields = context.fields();
+                    #(#field_uis)*
+                });
+            }
+        }
+    })
+}
@@ -0,0 +1,494 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{Receiver, Sender},
+        Arc,
+    },
+};
+
+use egui::mutex::RwLock;
+use memmap2::{Mmap, MmapMut, MmapOptions};
+
+use crate::{
+    error::Error, history::History, tools::format_explorer::SymbolCache, Event, Selection,
+};
+
+/// Replaces `old` bytes at `offset` with `new` ones. Lengths differ for insertions and deletions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Patch {
+    pub offset: usize,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+impl Patch {
+    /// Bytes covered by the patch after it was applied
+    pub fn new_end(&self) -> usize {
+        self.offset + self.new.len()
+    }
+
+    /// Reads a byte as if the patch was already applied on top of `file`
+    pub fn read(&self, file: &[u8], offset: usize) -> Option<u8> {
+        if offset < self.offset {
+            file.get(offset).copied()
+        } else if offset < self.new_end() {
+            Some(self.new[offset - self.offset])
+        } else {
+            file.get(offset - self.new.len() + self.old.len()).copied()
+        }
+    }
+
+    /// Combines a patch applied right after this one into a single patch.
+    /// Returns false if the patches don't touch each other and can't be merged.
+    pub fn merge(&mut self, next: &Patch) -> bool {
+        let self_end = self.new_end();
+        let next_end = next.offset + next.old.len();
+        if next.offset > self_end || next_end < self.offset {
+            return false;
+        }
+        let start = self.offset.min(next.offset);
+        let end = self_end.max(next_end);
+        // Bytes that `next` replaced outside of this patch were never touched by it
+        let prefix = &next.old[..self.offset - start];
+        let suffix = &next.old[next.old.len() - (end - self_end)..];
+        let mut new = [prefix, &self.new, suffix].concat();
+        let replaced = next.offset - start..next.offset - start + next.old.len();
+        new.splice(replaced, next.new.iter().copied());
+        self.old = [prefix, &self.old, suffix].concat();
+        self.new = new;
+        self.offset = start;
+        true
+    }
+
+    pub fn inverse(&self) -> Patch {
+        Patch {
+            offset: self.offset,
+            old: self.new.clone(),
+            new: self.old.clone(),
+        }
+    }
+
+    /// Moves this already applied patch to account for a `later` one applied after it.
+    /// Returns `None` if `later` modified the same bytes.
+    pub fn rebase(&self, later: &Patch) -> Option<Patch> {
+        if later.offset + later.old.len() <= self.offset && later.offset < self.offset {
+            let offset = self.offset + later.new.len() - later.old.len();
+            Some(Patch {
+                offset,
+                ..self.clone()
+            })
+        } else if later.offset >= self.new_end() {
+            Some(self.clone())
+        } else {
+            None
+        }
+    }
+
+    pub fn apply(&self, file: &mut FileData) -> io::Result<()> {
+        let old_range = self.offset..self.offset + self.old.len();
+        if self.old.len() == self.new.len() {
+            let Some(bytes) = file.as_mut() else {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "file is opened read-only",
+                ));
+            };
+            bytes[old_range].copy_from_slice(&self.new);
+            return Ok(());
+        }
+        if !file.is_writable() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "file is opened read-only",
+            ));
+        }
+        // Mappings can't change their size, so the data is moved into a new anonymous one
+        let len = file.len() - self.old.len() + self.new.len();
+        let mut resized = MmapOptions::new().len(len).map_anon()?;
+        resized[..self.offset].copy_from_slice(&file[..self.offset]);
+        resized[self.offset..self.new_end()].copy_from_slice(&self.new);
+        resized[self.new_end()..].copy_from_slice(&file[old_range.end..]);
+        *file = FileData::Private(resized);
+        Ok(())
+    }
+}
+
+/// How a file is mapped into memory
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OpenMode {
+    /// Can't be modified at all
+    #[default]
+    ReadOnly,
+    /// Edits stay in memory until the file is saved
+    CopyOnWrite,
+    /// Edits go straight to the file
+    ReadWrite,
+}
+
+impl OpenMode {
+    pub const ALL: [OpenMode; 3] = [
+        OpenMode::ReadOnly,
+        OpenMode::CopyOnWrite,
+        OpenMode::ReadWrite,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OpenMode::ReadOnly => "Read-only",
+            OpenMode::CopyOnWrite => "Copy-on-write",
+            OpenMode::ReadWrite => "Read-write",
+        }
+    }
+}
+
+/// Bytes of a document
+pub enum FileData {
+    ReadOnly(Mmap),
+    /// Private or anonymous mapping, changes never reach the file by themselves
+    Private(MmapMut),
+    /// Shared mapping of a file, changes are written to the file
+    Shared(MmapMut),
+}
+
+impl Deref for FileData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileData::ReadOnly(map) => map,
+            FileData::Private(map) | FileData::Shared(map) => map,
+        }
+    }
+}
+
+impl FileData {
+    pub fn empty() -> io::Result<Self> {
+        Ok(FileData::Private(MmapOptions::new().map_anon()?))
+    }
+
+    /// Anonymous mapping holding a copy of `bytes`
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let mut map = MmapOptions::new().len(bytes.len()).map_anon()?;
+        map.copy_from_slice(bytes);
+        Ok(FileData::Private(map))
+    }
+
+    pub fn is_writable(&self) -> bool {
+        !matches!(self, FileData::ReadOnly(_))
+    }
+
+    pub fn as_mut(&mut self) -> Option<&mut [u8]> {
+        match self {
+            FileData::ReadOnly(_) => None,
+            FileData::Private(map) | FileData::Shared(map) => Some(map),
+        }
+    }
+}
+
+/// Identifies a document for the whole session, unlike its position in the document list
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DocumentId(usize);
+
+impl DocumentId {
+    fn next() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Shared parts of a document, for tools that work with documents other than their own
+#[derive(Clone)]
+pub struct DocumentHandle {
+    pub id: DocumentId,
+    pub name: String,
+    pub file: Arc<RwLock<FileData>>,
+    pub version: Arc<AtomicUsize>,
+    pub events: Sender<Event>,
+}
+
+pub struct Document {
+    pub id: DocumentId,
+    pub file: Arc<RwLock<FileData>>,
+    /// Bumped before each change to the bytes, so work on them in the background can tell it's
+    /// outdated and let go of the file before the change waits for it
+    pub version: Arc<AtomicUsize>,
+    pub history: Arc<RwLock<History>>,
+    /// Named values of the format the bytes are in, for go-to expressions
+    pub symbols: SymbolCache,
+    /// Tools publish their events here for the app to handle
+    pub events: Sender<Event>,
+    receiver: Receiver<Event>,
+    pub path: Option<PathBuf>,
+    /// Name of a document made from the bytes of another, like an archive entry, until it's
+    /// saved
+    pub label: Option<String>,
+    pub mode: OpenMode,
+    /// Edits were made since the last save
+    pub dirty: bool,
+    pub selection: Selection,
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        let (events, receiver) = std::sync::mpsc::channel();
+        Self {
+            id: DocumentId::next(),
+            file: Arc::new(RwLock::new(FileData::empty().unwrap())),
+            version: Default::default(),
+            history: Default::default(),
+            symbols: Default::default(),
+            events,
+            receiver,
+            path: None,
+            label: None,
+            mode: OpenMode::CopyOnWrite,
+            dirty: false,
+            selection: Selection::default(),
+        }
+    }
+}
+
+impl Document {
+    pub fn map(path: &Path, mode: OpenMode) -> Result<FileData, Error> {
+        Self::map_file(path, mode).map_err(|err| Error::open(path, err))
+    }
+
+    fn map_file(path: &Path, mode: OpenMode) -> io::Result<FileData> {
+        // Safety: the file may still be modified by other processes, there is no way to prevent it
+        unsafe {
+            match mode {
+                OpenMode::ReadOnly => {
+                    let file = File::open(path)?;
+                    MmapOptions::new().map(&file).map(FileData::ReadOnly)
+                }
+                OpenMode::CopyOnWrite => {
+                    let file = File::open(path)?;
+                    MmapOptions::new().map_copy(&file).map(FileData::Private)
+                }
+                OpenMode::ReadWrite => {
+                    let file = File::options().write(true).read(true).open(path)?;
+                    MmapOptions::new().map_mut(&file).map(FileData::Shared)
+                }
+            }
+        }
+    }
+
+    pub fn load(&mut self, path: PathBuf, mode: OpenMode, data: FileData) {
+        self.changing();
+        *self.file.write() = data;
+        self.history.write().clear();
+        self.path = Some(path);
+        self.mode = mode;
+        self.dirty = false;
+        self.selection = Selection::default();
+    }
+
+    /// Fills an untitled document with bytes that don't come from a file of their own
+    pub fn load_derived(&mut self, label: String, data: FileData) {
+        self.changing();
+        *self.file.write() = data;
+        self.history.write().clear();
+        self.label = Some(label);
+        self.selection = Selection::default();
+    }
+
+    /// Untitled and empty, nothing would be lost by loading a file into it
+    pub fn is_blank(&self) -> bool {
+        self.path.is_none() && self.label.is_none() && !self.dirty && self.file.read().is_empty()
+    }
+
+    pub fn handle(&self) -> DocumentHandle {
+        DocumentHandle {
+            id: self.id,
+            name: self.name(),
+            file: self.file.clone(),
+            version: self.version.clone(),
+            events: self.events.clone(),
+        }
+    }
+
+    fn changing(&self) {
+        self.version.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.mode == OpenMode::ReadOnly
+    }
+
+    pub fn next_event(&self) -> Option<Event> {
+        self.receiver.try_recv().ok()
+    }
+
+    pub fn name(&self) -> String {
+        match &self.path {
+            Some(path) => path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.display().to_string()),
+            None => self.label.clone().unwrap_or_else(|| "untitled".to_string()),
+        }
+    }
+
+    /// Applies a new edit and puts it into the history
+    pub fn edit(&mut self, patch: Patch, time: f64) -> Result<(), Error> {
+        self.apply(&patch)?;
+        self.history.write().record(patch, time);
+        Ok(())
+    }
+
+    pub fn undo(&mut self) -> Result<Option<Patch>, Error> {
+        let patch = self.history.write().undo();
+        if let Some(patch) = &patch {
+            self.apply(patch)?;
+        }
+        Ok(patch)
+    }
+
+    pub fn redo(&mut self) -> Result<Option<Patch>, Error> {
+        let patch = self.history.write().redo();
+        if let Some(patch) = &patch {
+            self.apply(patch)?;
+        }
+        Ok(patch)
+    }
+
+    fn apply(&mut self, patch: &Patch) -> Result<(), Error> {
+        self.changing();
+        patch.apply(&mut self.file.write()).map_err(Error::edit)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    pub fn save(&mut self) -> Result<(), Error> {
+        let Some(path) = self.path.clone() else {
+            return Err(Error::save(
+                self.name(),
+                io::Error::new(io::ErrorKind::NotFound, "document has no path, use save as"),
+            ));
+        };
+        if self.is_read_only() {
+            return Err(Error::save(
+                path,
+                io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "file is opened read-only, use save as to make a copy",
+                ),
+            ));
+        }
+        self.save_as(path)
+    }
+
+    pub fn save_as(&mut self, path: PathBuf) -> Result<(), Error> {
+        self.write_to(&path)
+            .map_err(|err| Error::save(path.clone(), err))?;
+        self.path = Some(path);
+        self.dirty = false;
+        Ok(())
+    }
+
+    fn write_to(&mut self, path: &Path) -> io::Result<()> {
+        // The bytes stay the same but may be mapped again
+        self.changing();
+        let mut lock = self.file.write();
+        let same_file = self.path.as_deref() == Some(path);
+        match &*lock {
+            FileData::Shared(map) if same_file => map.flush()?,
+            _ => {
+                // Not truncating until everything is written, as the file may be mapped by us
+                let mut file = File::options()
+                    .write(true)
+                    .create(true)
+                    .truncate(false)
+                    .open(path)?;
+                file.write_all(&lock)?;
+                file.set_len(lock.len() as u64)?;
+                drop(file);
+                *lock = Self::map_file(path, self.mode)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Patch;
+
+    fn patch(offset: usize, old: &[u8], new: &[u8]) -> Patch {
+        Patch {
+            offset,
+            old: old.to_vec(),
+            new: new.to_vec(),
+        }
+    }
+
+    #[test]
+    fn merge_overwrites_in_a_row() {
+        let mut first = patch(0, b"a", b"x");
+        assert!(first.merge(&patch(1, b"b", b"y")));
+        assert_eq!(first, patch(0, b"ab", b"xy"));
+    }
+
+    #[test]
+    fn merge_same_byte_keeps_original() {
+        let mut first = patch(5, b"a", b"x");
+        assert!(first.merge(&patch(5, b"x", b"y")));
+        assert_eq!(first, patch(5, b"a", b"y"));
+    }
+
+    #[test]
+    fn merge_before_start() {
+        let mut first = patch(5, b"a", b"x");
+        assert!(first.merge(&patch(4, b"b", b"y")));
+        assert_eq!(first, patch(4, b"ba", b"yx"));
+    }
+
+    #[test]
+    fn merge_covering_patch() {
+        let mut first = patch(2, b"a", b"x");
+        assert!(first.merge(&patch(1, b"bxc", b"z")));
+        assert_eq!(first, patch(1, b"bac", b"z"));
+    }
+
+    #[test]
+    fn merge_insertion_and_deletion() {
+        let mut first = patch(3, b"", b"x");
+        assert!(first.merge(&patch(4, b"", b"y")));
+        assert_eq!(first, patch(3, b"", b"xy"));
+        assert!(first.merge(&patch(4, b"y", b"")));
+        assert_eq!(first, patch(3, b"", b"x"));
+        assert!(first.merge(&patch(3, b"x", b"")));
+        assert_eq!(first, patch(3, b"", b""));
+    }
+
+    #[test]
+    fn merge_apart() {
+        let mut first = patch(0, b"a", b"x");
+        assert!(!first.merge(&patch(2, b"c", b"z")));
+        assert!(!patch(5, b"a", b"x").merge(&patch(3, b"c", b"z")));
+        assert_eq!(first, patch(0, b"a", b"x"));
+    }
+
+    /// The file after `patch`, read through `Patch::read`
+    fn applied(patch: &Patch, file: &[u8]) -> Vec<u8> {
+        let len = file.len() - patch.old.len() + patch.new.len();
+        (0..len)
+            .map(|offset| patch.read(file, offset).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn merged_patch_reads_like_both() {
+        let file = b"0123456789";
+        let mut first = patch(2, b"2", b"ab");
+        let second = patch(3, b"b3", b"c");
+        let after_both = applied(&second, &applied(&first, file));
+        assert_eq!(after_both, b"01ac456789");
+        assert!(first.merge(&second));
+        assert_eq!(applied(&first, file), after_both);
+    }
+}
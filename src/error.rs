@@ -0,0 +1,94 @@
+use std::{fmt, io, path::PathBuf, sync::Arc};
+
+#[derive(Clone, Debug)]
+pub enum Error {
+    Open {
+        path: PathBuf,
+        source: Arc<io::Error>,
+    },
+    Save {
+        path: PathBuf,
+        source: Arc<io::Error>,
+    },
+    Edit(Arc<io::Error>),
+    Parse {
+        format: &'static str,
+        message: String,
+    },
+}
+
+impl Error {
+    pub fn open(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Error::Open {
+            path: path.into(),
+            source: Arc::new(source),
+        }
+    }
+
+    pub fn save(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Error::Save {
+            path: path.into(),
+            source: Arc::new(source),
+        }
+    }
+
+    pub fn edit(source: io::Error) -> Self {
+        Error::Edit(Arc::new(source))
+    }
+
+    pub fn parse(format: &'static str, message: impl Into<String>) -> Self {
+        Error::Parse {
+            format,
+            message: message.into(),
+        }
+    }
+
+    /// Describes where and why a nom parser failed on `input`
+    pub fn from_nom(
+        format: &'static str,
+        input: &[u8],
+        err: nom::Err<nom::error::Error<&[u8]>>,
+    ) -> Self {
+        let message = match err {
+            nom::Err::Incomplete(_) => "unexpected end of file".to_string(),
+            nom::Err::Error(err) | nom::Err::Failure(err) => {
+                let offset = input.len() - err.input.len();
+                let reason = match &err.code {
+                    nom::error::ErrorKind::Eof => "unexpected end of file",
+                    nom::error::ErrorKind::Switch => "unsupported value",
+                    code => code.description(),
+                };
+                format!("{} at offset {:#x}", reason, offset)
+            }
+        };
+        Self::parse(format, message)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Open { path, source } => {
+                write!(f, "Failed to open {}: {}", path.display(), source)
+            }
+            Error::Save { path, source } => {
+                write!(f, "Failed to save {}: {}", path.display(), source)
+            }
+            Error::Edit(source) => write!(f, "Failed to edit the file: {}", source),
+            Error::Parse { format, message } => {
+                write!(f, "Failed to parse as {}: {}", format, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Open { source, .. } | Error::Save { source, .. } | Error::Edit(source) => {
+                Some(source.as_ref())
+            }
+            Error::Parse { .. } => None,
+        }
+    }
+}
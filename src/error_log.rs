@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use eframe::egui;
+
+use crate::error::Error;
+
+/// How long an error stays on screen as a toast, in seconds
+const TOAST_DURATION: f64 = 5.0;
+
+struct LoggedError {
+    error: Error,
+    /// Time the error happened, in seconds from the app start
+    time: f64,
+    /// The toast was closed before it timed out
+    dismissed: bool,
+}
+
+/// Every error reported during the session, recent ones are also shown as toasts
+#[derive(Default)]
+pub struct ErrorLog {
+    errors: Vec<LoggedError>,
+    /// Whether the log panel is shown
+    pub open: bool,
+}
+
+impl ErrorLog {
+    pub fn push(&mut self, error: Error, time: f64) {
+        log::error!("{}", error);
+        self.errors.push(LoggedError {
+            error,
+            time,
+            dismissed: false,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    pub fn show_toasts(&mut self, ctx: &egui::Context, time: f64) {
+        let mut next_timeout = None::<f64>;
+        egui::Area::new(egui::Id::new("error_toasts"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, [-8.0, -8.0])
+            .order(egui::Order::Foreground)
+            .interactable(true)
+            .show(ctx, |ui| {
+                for logged in &mut self.errors {
+                    let remaining = logged.time + TOAST_DURATION - time;
+                    if logged.dismissed || remaining <= 0.0 {
+                        continue;
+                    }
+                    next_timeout = Some(next_timeout.map_or(remaining, |t| t.min(remaining)));
+                    let response = egui::Frame::popup(ui.style())
+                        .show(ui, |ui| {
+                            ui.set_max_width(320.0);
+                            ui.colored_label(ui.visuals().error_fg_color, logged.error.to_string());
+                        })
+                        .response
+                        .interact(egui::Sense::click())
+                        .on_hover_text("Click to dismiss");
+                    if response.clicked() {
+                        logged.dismissed = true;
+                    }
+                }
+            });
+        // Keep repainting so toasts disappear even when nothing else happens
+        if let Some(timeout) = next_timeout {
+            ctx.request_repaint_after(Duration::from_secs_f64(timeout));
+        }
+    }
+
+    pub fn show_panel(&mut self, ctx: &egui::Context) {
+        if !self.open {
+            return;
+        }
+        egui::TopBottomPanel::bottom("error_log")
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.strong("Errors");
+                    if ui.button("Clear").clicked() {
+                        self.errors.clear();
+                    }
+                    if ui.button("Close").clicked() {
+                        self.open = false;
+                    }
+                });
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, false])
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for logged in &self.errors {
+                            ui.horizontal(|ui| {
+                                ui.weak(format!("{:8.1}s", logged.time));
+                                ui.label(logged.error.to_string());
+                            });
+                        }
+                    });
+            });
+    }
+}
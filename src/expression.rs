@@ -0,0 +1,316 @@
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpressionError(String);
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i128),
+    Name(String),
+    Operator(char),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, ExpressionError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if "+-*/()".contains(c) {
+            tokens.push(Token::Operator(c));
+            chars.next();
+        } else if is_name_char(c) {
+            let mut end = start;
+            while let Some(&(index, c)) = chars.peek() {
+                if !is_name_char(c) {
+                    break;
+                }
+                end = index + c.len_utf8();
+                chars.next();
+            }
+            let word = &text[start..end];
+            if c.is_ascii_digit() {
+                tokens.push(Token::Number(parse_number(word)?));
+            } else {
+                tokens.push(Token::Name(word.to_string()));
+            }
+        } else {
+            return Err(ExpressionError(format!("unexpected '{}'", c)));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Names can be symbols like `_ZN4core3fmt5write17h0123456789abcdefE` or `std::fmt::write`
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_.$:".contains(c)
+}
+
+fn parse_number(word: &str) -> Result<i128, ExpressionError> {
+    let lower = word.to_ascii_lowercase();
+    let (digits, radix) = if let Some(digits) = lower.strip_prefix("0x") {
+        (digits, 16)
+    } else if let Some(digits) = lower.strip_prefix("0o") {
+        (digits, 8)
+    } else if let Some(digits) = lower.strip_prefix("0b") {
+        (digits, 2)
+    } else {
+        (lower.as_str(), 10)
+    };
+    i128::from_str_radix(&digits.replace('_', ""), radix)
+        .map_err(|_| ExpressionError(format!("invalid number '{}'", word)))
+}
+
+/// Recursive descent over the tokens, `lookup` resolves names to their values
+struct Parser<'a, F> {
+    tokens: &'a [Token],
+    position: usize,
+    lookup: F,
+}
+
+impl<F: Fn(&str) -> Option<i128>> Parser<'_, F> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next_operator(&mut self, operators: &str) -> Option<char> {
+        match self.peek() {
+            Some(Token::Operator(c)) if operators.contains(*c) => {
+                let c = *c;
+                self.position += 1;
+                Some(c)
+            }
+            _ => None,
+        }
+    }
+
+    fn sum(&mut self) -> Result<i128, ExpressionError> {
+        let mut value = self.product()?;
+        while let Some(operator) = self.next_operator("+-") {
+            let rhs = self.product()?;
+            value = match operator {
+                '+' => value.checked_add(rhs),
+                _ => value.checked_sub(rhs),
+            }
+            .ok_or_else(|| ExpressionError("overflow".to_string()))?;
+        }
+        Ok(value)
+    }
+
+    fn product(&mut self) -> Result<i128, ExpressionError> {
+        let mut value = self.unary()?;
+        while let Some(operator) = self.next_operator("*/") {
+            let rhs = self.unary()?;
+            value = match operator {
+                '*' => value
+                    .checked_mul(rhs)
+                    .ok_or_else(|| ExpressionError("overflow".to_string()))?,
+                _ => value
+                    .checked_div(rhs)
+                    .ok_or_else(|| ExpressionError("division by zero".to_string()))?,
+            };
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<i128, ExpressionError> {
+        if self.next_operator("-").is_some() {
+            return self
+                .unary()?
+                .checked_neg()
+                .ok_or_else(|| ExpressionError("overflow".to_string()));
+        }
+        match self.tokens.get(self.position).cloned() {
+            Some(Token::Number(value)) => {
+                self.position += 1;
+                Ok(value)
+            }
+            Some(Token::Name(name)) => {
+                self.position += 1;
+                (self.lookup)(&name)
+                    .ok_or_else(|| ExpressionError(format!("unknown name '{}'", name)))
+            }
+            Some(Token::Operator('(')) => {
+                self.position += 1;
+                let value = self.sum()?;
+                match self.next_operator(")") {
+                    Some(_) => Ok(value),
+                    None => Err(ExpressionError("missing ')'".to_string())),
+                }
+            }
+            Some(Token::Operator(c)) => Err(ExpressionError(format!("unexpected '{}'", c))),
+            None => Err(ExpressionError("unexpected end of expression".to_string())),
+        }
+    }
+}
+
+/// Evaluates arithmetic like `0x400 + e_phoff * 2`, looking up names with `lookup`
+pub fn evaluate(
+    text: &str,
+    lookup: impl Fn(&str) -> Option<i128>,
+) -> Result<i128, ExpressionError> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        position: 0,
+        lookup,
+    };
+    let value = parser.sum()?;
+    match parser.peek() {
+        None => Ok(value),
+        Some(_) => Err(ExpressionError(
+            "unexpected input after the expression".to_string(),
+        )),
+    }
+}
+
+/// Evaluates an offset in a file of `len` bytes. `end` is the file length, `cursor` the current
+/// offset, and a leading `+` or `-` makes the expression relative to the cursor.
+pub fn evaluate_offset(
+    text: &str,
+    cursor: usize,
+    len: usize,
+    symbols: &[(String, u64)],
+) -> Result<usize, ExpressionError> {
+    let text = text.trim();
+    let lookup = |name: &str| match name {
+        "end" => Some(len as i128),
+        "cursor" => Some(cursor as i128),
+        _ => symbols
+            .iter()
+            .find(|(symbol, _)| symbol == name)
+            .map(|(_, value)| *value as i128),
+    };
+    let value = if text.starts_with('+') || text.starts_with('-') {
+        (cursor as i128)
+            .checked_add(evaluate(text.trim_start_matches('+'), lookup)?)
+            .ok_or_else(|| ExpressionError(format!("{} overflows", text)))?
+    } else {
+        evaluate(text, lookup)?
+    };
+    if !(0..=len as i128).contains(&value) {
+        return Err(ExpressionError(format!("{} is outside of the file", value)));
+    }
+    Ok(value as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(text: &str) -> Result<i128, ExpressionError> {
+        evaluate(text, |name| match name {
+            "e_phoff" => Some(0x40),
+            "std::fmt::write" => Some(0x1000),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(eval("42"), Ok(42));
+        assert_eq!(eval("0x1F"), Ok(31));
+        assert_eq!(eval("0o17"), Ok(15));
+        assert_eq!(eval("0b1010"), Ok(10));
+        assert_eq!(eval("1_000"), Ok(1000));
+        assert_eq!(eval("0xffff_ffff"), Ok(0xffff_ffff));
+        assert!(eval("0x").is_err());
+        assert!(eval("12ab").is_err());
+        assert!(eval("0b102").is_err());
+    }
+
+    #[test]
+    fn precedence_and_parentheses() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(7));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(9));
+        assert_eq!(eval("10 - 4 - 3"), Ok(3));
+        assert_eq!(eval("100 / 10 / 5"), Ok(2));
+        assert_eq!(eval("7 / 2"), Ok(3));
+        assert_eq!(eval("-3 * -(2 + 1)"), Ok(9));
+        assert_eq!(eval("--5"), Ok(5));
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(eval("0x400 + e_phoff * 2"), Ok(0x480));
+        assert_eq!(eval("std::fmt::write+1"), Ok(0x1001));
+        assert_eq!(
+            eval("missing + 1"),
+            Err(ExpressionError("unknown name 'missing'".to_string()))
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            eval("1 / 0"),
+            Err(ExpressionError("division by zero".to_string()))
+        );
+        assert_eq!(
+            eval("(1 + 2"),
+            Err(ExpressionError("missing ')'".to_string()))
+        );
+        assert_eq!(
+            eval("1 +"),
+            Err(ExpressionError("unexpected end of expression".to_string()))
+        );
+        assert_eq!(
+            eval(""),
+            Err(ExpressionError("unexpected end of expression".to_string()))
+        );
+        assert_eq!(
+            eval("1 2"),
+            Err(ExpressionError(
+                "unexpected input after the expression".to_string()
+            ))
+        );
+        assert_eq!(
+            eval("1 % 2"),
+            Err(ExpressionError("unexpected '%'".to_string()))
+        );
+        assert_eq!(
+            eval("* 2"),
+            Err(ExpressionError("unexpected '*'".to_string()))
+        );
+        assert_eq!(
+            eval("0x7fffffffffffffffffffffffffffffff + 1"),
+            Err(ExpressionError("overflow".to_string()))
+        );
+        assert_eq!(
+            eval("0x7fffffffffffffffffffffffffffffff * 2"),
+            Err(ExpressionError("overflow".to_string()))
+        );
+    }
+
+    #[test]
+    fn offsets() {
+        let symbols = [("main".to_string(), 0x100)];
+        assert_eq!(evaluate_offset("main + 4", 0, 0x200, &symbols), Ok(0x104));
+        assert_eq!(evaluate_offset(" end ", 0, 0x200, &symbols), Ok(0x200));
+        assert_eq!(
+            evaluate_offset("cursor * 2", 0x10, 0x200, &symbols),
+            Ok(0x20)
+        );
+        assert_eq!(evaluate_offset("+0x10", 0x50, 0x200, &symbols), Ok(0x60));
+        assert_eq!(evaluate_offset("-0x10", 0x50, 0x200, &symbols), Ok(0x40));
+        assert_eq!(
+            evaluate_offset("-0x60", 0x50, 0x200, &symbols),
+            Err(ExpressionError("-16 is outside of the file".to_string()))
+        );
+        assert!(evaluate_offset("end + 1", 0, 0x200, &symbols).is_err());
+        assert_eq!(
+            evaluate_offset("+0x7fffffffffffffffffffffffffffffff", 1, 0x200, &symbols),
+            Err(ExpressionError(
+                "+0x7fffffffffffffffffffffffffffffff overflows".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use crate::document::Patch;
+
+/// Edits made within this many seconds of each other, touching adjacent bytes, are undone together
+const GROUP_TIMEOUT: f64 = 1.0;
+
+pub struct Edit {
+    pub patch: Patch,
+    /// Time of the last change merged into this edit, in seconds from the app start
+    pub time: f64,
+}
+
+#[derive(Default)]
+pub struct History {
+    /// Applied edits, oldest first
+    edits: Vec<Edit>,
+    /// Undone edits, the next to be redone is the last one
+    undone: Vec<Edit>,
+    /// Whether the next edit may be merged into the last one
+    grouping: bool,
+}
+
+impl History {
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn edits(&self) -> &[Edit] {
+        &self.edits
+    }
+
+    pub fn undone(&self) -> &[Edit] {
+        &self.undone
+    }
+
+    pub fn record(&mut self, patch: Patch, time: f64) {
+        self.undone.clear();
+        if let Some(last) = self.edits.last_mut() {
+            if self.grouping && time - last.time < GROUP_TIMEOUT && last.patch.merge(&patch) {
+                last.time = time;
+                return;
+            }
+        }
+        self.edits.push(Edit { patch, time });
+        self.grouping = true;
+    }
+
+    /// Returns the patch reverting the last edit
+    pub fn undo(&mut self) -> Option<Patch> {
+        let edit = self.edits.pop()?;
+        let inverse = edit.patch.inverse();
+        self.undone.push(edit);
+        self.grouping = false;
+        Some(inverse)
+    }
+
+    /// Returns the patch reapplying the last undone edit
+    pub fn redo(&mut self) -> Option<Patch> {
+        let edit = self.undone.pop()?;
+        let patch = edit.patch.clone();
+        self.edits.push(edit);
+        self.grouping = false;
+        Some(patch)
+    }
+
+    /// The edit at `index` as it's located in the current file, after all the later edits
+    pub fn rebased(&self, index: usize) -> Option<Patch> {
+        let mut patch = self.edits.get(index)?.patch.clone();
+        for later in &self.edits[index + 1..] {
+            patch = patch.rebase(&later.patch)?;
+        }
+        Some(patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::History;
+    use crate::document::Patch;
+
+    fn patch(offset: usize, old: &[u8], new: &[u8]) -> Patch {
+        Patch {
+            offset,
+            old: old.to_vec(),
+            new: new.to_vec(),
+        }
+    }
+
+    #[test]
+    fn rebase_after_later_insertion_before() {
+        let edit = patch(10, b"a", b"x");
+        assert_eq!(
+            edit.rebase(&patch(2, b"", b"123")),
+            Some(patch(13, b"a", b"x"))
+        );
+        assert_eq!(
+            edit.rebase(&patch(2, b"12", b"")),
+            Some(patch(8, b"a", b"x"))
+        );
+        // A deletion ending right where the edit starts
+        assert_eq!(
+            edit.rebase(&patch(8, b"12", b"")),
+            Some(patch(8, b"a", b"x"))
+        );
+    }
+
+    #[test]
+    fn rebase_after_later_edit_after() {
+        let edit = patch(10, b"a", b"xy");
+        assert_eq!(edit.rebase(&patch(12, b"b", b"")), Some(edit.clone()));
+        assert_eq!(edit.rebase(&patch(50, b"", b"z")), Some(edit.clone()));
+    }
+
+    #[test]
+    fn rebase_after_overlapping_edit() {
+        let edit = patch(10, b"ab", b"xy");
+        assert_eq!(edit.rebase(&patch(11, b"y", b"z")), None);
+        assert_eq!(edit.rebase(&patch(9, b"0x", b"")), None);
+        assert_eq!(edit.rebase(&patch(10, b"", b"z")), None);
+    }
+
+    #[test]
+    fn rebased_through_history() {
+        let mut history = History::default();
+        history.record(patch(10, b"a", b"x"), 0.0);
+        history.record(patch(0, b"", b"12"), 10.0);
+        history.record(patch(20, b"b", b"y"), 20.0);
+        assert_eq!(history.rebased(0), Some(patch(12, b"a", b"x")));
+        assert_eq!(history.rebased(1), Some(patch(0, b"", b"12")));
+        assert_eq!(history.rebased(2), Some(patch(20, b"b", b"y")));
+        assert_eq!(history.rebased(3), None);
+        history.record(patch(12, b"x", b"z"), 30.0);
+        assert_eq!(history.rebased(0), None);
+    }
+
+    #[test]
+    fn record_groups_close_edits() {
+        let mut history = History::default();
+        history.record(patch(0, b"a", b"x"), 0.0);
+        history.record(patch(1, b"b", b"y"), 0.5);
+        assert_eq!(history.edits().len(), 1);
+        // Too late to be grouped
+        history.record(patch(2, b"c", b"z"), 2.0);
+        assert_eq!(history.edits().len(), 2);
+        // Not grouped with an edit that was undone and redone
+        assert_eq!(history.undo(), Some(patch(2, b"z", b"c")));
+        assert_eq!(history.redo(), Some(patch(2, b"c", b"z")));
+        history.record(patch(3, b"d", b"w"), 2.1);
+        assert_eq!(history.edits().len(), 3);
+        assert_eq!(history.edits()[0].patch, patch(0, b"ab", b"xy"));
+    }
+}
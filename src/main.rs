@@ -1,21 +1,28 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
+mod document;
+mod error;
+mod error_log;
+mod expression;
+mod history;
+mod time;
 mod tools;
 
 use std::{
-    fs::File,
-    sync::{
-        mpsc::{Receiver, Sender},
-        Arc,
-    },
+    path::PathBuf,
+    sync::mpsc::{Receiver, Sender},
 };
 
+use document::{Document, DocumentHandle, DocumentId, FileData, OpenMode, Patch};
 use eframe::egui;
-use egui::mutex::RwLock;
 use egui_tiles::SimplificationOptions;
-use memmap2::{MmapMut, MmapOptions};
+use error::Error;
+use error_log::ErrorLog;
 use std::future::Future;
-use tools::{string_finder::StringFinder, GaffrieTool};
+use tools::{string_finder::StringFinder, templates::TemplateLibrary, GaffrieTool};
+
+/// Storage key of the structure templates
+const TEMPLATES_KEY: &str = "templates";
 
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> Result<(), eframe::Error> {
@@ -29,14 +36,17 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
         "Gaffrie",
         options,
-        Box::new(|_cc| {
+        Box::new(|cc| {
             #[cfg_attr(not(feature = "serde"), allow(unused_mut))]
             let mut app = MyApp::default();
-            #[cfg(feature = "serde")]
-            if let Some(storage) = _cc.storage {
+            if let Some(storage) = cc.storage {
+                #[cfg(feature = "serde")]
                 if let Some(state) = eframe::get_value(storage, eframe::APP_KEY) {
                     app = state;
                 }
+                if let Some(source) = storage.get_string(TEMPLATES_KEY) {
+                    app.templates.set_source(source);
+                }
             }
             Box::new(app)
         }),
@@ -61,106 +71,392 @@ fn main() {
     })
 }
 
-type ActionsVec = Vec<(
-    String,
-    Box<dyn Fn(&Arc<RwLock<MmapMut>>, &mut egui_tiles::Tree<Pane>)>,
-)>;
+type ActionsVec = Vec<(String, Box<dyn Fn(&Document, &mut egui_tiles::Tree<Pane>)>)>;
+
+type OpenedFile = Result<(PathBuf, OpenMode, FileData), Error>;
 
-#[derive(Clone, Copy)]
+/// Document to save and where to, picked in a save dialog
+type SaveRequest = (DocumentId, PathBuf);
+
+/// A range of bytes in a document. The cursor is the start of the selection,
+/// an empty selection is just a cursor.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Selection {
+    pub offset: usize,
+    pub length: usize,
+}
+
+impl Selection {
+    pub fn new(offset: usize, length: usize) -> Self {
+        Self { offset, length }
+    }
+
+    pub fn end(&self) -> usize {
+        self.offset + self.length
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        (self.offset..self.end()).contains(&offset)
+    }
+}
+
+#[derive(Clone)]
 pub enum Event {
     FileChanged,
+    /// Cursor moved to the offset, collapsing the selection
+    CursorMoved(usize),
+    SelectionChanged(Selection),
+    /// Bytes to point out without moving the selection, like those of the parsed field under
+    /// the mouse. `None` clears them.
+    Highlight(Option<Selection>),
+    /// Bytes to color along with their colors, like the fields of an applied template. Replaces
+    /// the previous ones from the same `source`, an empty list clears them.
+    Overlay {
+        source: usize,
+        colors: Vec<(Selection, egui::Color32)>,
+    },
+    /// Request to modify the file, tools get `FileChanged` once it's applied
+    Patch(Patch),
+    Undo,
+    Redo,
+    /// Bytes to open as a new document, like a decompressed archive entry
+    Open {
+        name: String,
+        bytes: Vec<u8>,
+    },
+    /// Something went wrong, shown to the user and kept in the error log
+    Error(Error),
+    /// A document was opened, closed or saved under a different name. Sent to every tool.
+    DocumentsChanged(Vec<DocumentHandle>),
 }
 
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 struct MyApp {
-    current_file: Arc<RwLock<MmapMut>>,
+    /// Open documents, never empty
+    documents: Vec<Document>,
+    /// Index of the document the side panel and shortcuts act on
+    active: usize,
+    /// Document waiting for the user to confirm discarding its changes
+    closing: Option<DocumentId>,
     tree: egui_tiles::Tree<Pane>,
 
-    file_channel: (Sender<MmapMut>, Receiver<MmapMut>),
+    /// Mode for the files opened next
+    open_mode: OpenMode,
+    file_channel: (Sender<OpenedFile>, Receiver<OpenedFile>),
+    save_channel: (Sender<SaveRequest>, Receiver<SaveRequest>),
+    /// Time of the current frame, in seconds
+    time: f64,
+    errors: ErrorLog,
 
     action_popup_opened: bool,
     action_popup_text: String,
     actions: ActionsVec,
     current_action: usize,
+    /// Index of the document new tools are attached to
+    action_document: usize,
+    /// Structure templates, shared by the template tools
+    templates: TemplateLibrary,
 }
 
 impl Default for MyApp {
     fn default() -> Self {
-        let mmap = MmapOptions::new().map_anon().unwrap();
-        let current_file = Arc::new(RwLock::new(mmap));
         let mut tiles = egui_tiles::Tiles::default();
         let tabs = vec![];
         let root = tiles.insert_tab_tile(tabs);
         let tree = egui_tiles::Tree::new("tools_tree", root, tiles);
+        let templates = TemplateLibrary::default();
+        let library = templates.clone();
         let actions: ActionsVec = vec![
             (
                 "String Finder".to_string(),
-                Box::new(|file, tree| {
-                    let tool = StringFinder::new(file.clone());
+                Box::new(|document, tree| {
+                    let tool = StringFinder::new(document);
                     let boxed_tool = Box::new(tool);
-                    MyApp::add_tool(tree, boxed_tool);
+                    MyApp::add_tool(tree, document.id, boxed_tool);
                 }),
             ),
             (
                 "Entropy Plot".to_string(),
-                Box::new(|file, tree| {
-                    let tool = tools::entropy_plot::EntropyPlot::new(file.clone());
+                Box::new(|document, tree| {
+                    let tool = tools::entropy_plot::EntropyPlot::new(document);
                     let boxed_tool = Box::new(tool);
-                    MyApp::add_tool(tree, boxed_tool);
+                    MyApp::add_tool(tree, document.id, boxed_tool);
                 }),
             ),
             (
                 "Frequency Image".to_string(),
-                Box::new(|file, tree| {
-                    let tool = tools::frequency_image::FrequencyImage::new(file.clone());
+                Box::new(|document, tree| {
+                    let tool = tools::frequency_image::FrequencyImage::new(document);
                     let boxed_tool = Box::new(tool);
-                    MyApp::add_tool(tree, boxed_tool);
+                    MyApp::add_tool(tree, document.id, boxed_tool);
                 }),
             ),
             (
                 "Hex Viewer".to_string(),
-                Box::new(|file, tree| {
-                    let tool = tools::hex_viewer::HexViewer::new(file.clone());
+                Box::new(|document, tree| {
+                    let tool = tools::hex_viewer::HexViewer::new(document);
                     let boxed_tool = Box::new(tool);
-                    MyApp::add_tool(tree, boxed_tool);
+                    MyApp::add_tool(tree, document.id, boxed_tool);
                 }),
             ),
             (
                 "Format Explorer".to_string(),
-                Box::new(|file, tree| {
-                    let tool = tools::format_explorer::FormatExplorer::new(file.clone());
+                Box::new(|document, tree| {
+                    let tool = tools::format_explorer::FormatExplorer::new(document);
+                    let boxed_tool = Box::new(tool);
+                    MyApp::add_tool(tree, document.id, boxed_tool);
+                }),
+            ),
+            (
+                "Edit History".to_string(),
+                Box::new(|document, tree| {
+                    let tool = tools::edit_history::EditHistory::new(document);
+                    let boxed_tool = Box::new(tool);
+                    MyApp::add_tool(tree, document.id, boxed_tool);
+                }),
+            ),
+            (
+                "Search".to_string(),
+                Box::new(|document, tree| {
+                    let tool = tools::search::Search::new(document);
                     let boxed_tool = Box::new(tool);
-                    MyApp::add_tool(tree, boxed_tool);
+                    MyApp::add_tool(tree, document.id, boxed_tool);
+                }),
+            ),
+            (
+                "Binary Diff".to_string(),
+                Box::new(|document, tree| {
+                    let tool = tools::binary_diff::BinaryDiff::new(document);
+                    let boxed_tool = Box::new(tool);
+                    MyApp::add_tool(tree, document.id, boxed_tool);
+                }),
+            ),
+            (
+                "Data Inspector".to_string(),
+                Box::new(|document, tree| {
+                    let tool = tools::data_inspector::DataInspector::new(document);
+                    let boxed_tool = Box::new(tool);
+                    MyApp::add_tool(tree, document.id, boxed_tool);
+                }),
+            ),
+            (
+                "Structure Templates".to_string(),
+                Box::new(move |document, tree| {
+                    let library = library.clone();
+                    let tool =
+                        tools::templates::StructureTemplates::with_library(document, library);
+                    let boxed_tool = Box::new(tool);
+                    MyApp::add_tool(tree, document.id, boxed_tool);
                 }),
             ),
         ];
 
         let file_channel = std::sync::mpsc::channel();
+        let save_channel = std::sync::mpsc::channel();
 
         Self {
-            current_file,
+            documents: vec![Document::default()],
+            active: 0,
+            closing: None,
             tree,
             action_popup_opened: false,
             action_popup_text: String::new(),
             actions,
             current_action: 0,
+            action_document: 0,
+            open_mode: OpenMode::default(),
             file_channel,
+            save_channel,
+            time: 0.0,
+            errors: ErrorLog::default(),
+            templates,
         }
     }
 }
 
 impl MyApp {
-    fn notify_tools(&mut self, event: Event) {
+    fn document_index(&self, id: DocumentId) -> Option<usize> {
+        self.documents.iter().position(|document| document.id == id)
+    }
+
+    /// Forwards an event to the tools attached to the document
+    fn notify_tools(&mut self, document: DocumentId, event: Event) {
         for (_, tile) in self.tree.tiles.iter_mut() {
             match tile {
-                egui_tiles::Tile::Pane(pane) => pane.tool.notify(event),
-                egui_tiles::Tile::Container(_) => {}
+                egui_tiles::Tile::Pane(pane) if pane.document == document => {
+                    pane.tool.notify(event.clone())
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Lets every tool know about the current set of documents
+    fn documents_changed(&mut self) {
+        let handles = self
+            .documents
+            .iter()
+            .map(Document::handle)
+            .collect::<Vec<_>>();
+        for (_, tile) in self.tree.tiles.iter_mut() {
+            if let egui_tiles::Tile::Pane(pane) = tile {
+                pane.tool.notify(Event::DocumentsChanged(handles.clone()));
+            }
+        }
+    }
+
+    /// Applies an event published by one of the tools of a document and forwards it to its tools
+    fn handle_event(&mut self, index: usize, event: Event) {
+        let document = &mut self.documents[index];
+        let id = document.id;
+        match event {
+            Event::FileChanged => {}
+            Event::CursorMoved(offset) => document.selection = Selection::new(offset, 0),
+            Event::SelectionChanged(selection) => document.selection = selection,
+            Event::Highlight(_) | Event::Overlay { .. } => {}
+            Event::Patch(patch) => {
+                if let Err(err) = document.edit(patch, self.time) {
+                    self.errors.push(err, self.time);
+                }
+                self.notify_tools(id, Event::FileChanged);
+                return;
+            }
+            Event::Undo | Event::Redo => {
+                let result = match event {
+                    Event::Undo => document.undo(),
+                    _ => document.redo(),
+                };
+                match result {
+                    Ok(Some(patch)) => {
+                        self.notify_tools(id, Event::FileChanged);
+                        let changed = Selection::new(patch.offset, patch.new.len());
+                        self.handle_event(index, Event::SelectionChanged(changed));
+                    }
+                    Ok(None) => {}
+                    Err(err) => self.errors.push(err, self.time),
+                }
+                return;
+            }
+            Event::Open { name, bytes } => {
+                self.open_derived(name, &bytes);
+                return;
+            }
+            Event::Error(err) => {
+                self.errors.push(err, self.time);
+                return;
             }
+            Event::DocumentsChanged(_) => return,
         }
+        self.notify_tools(id, event);
     }
 
-    fn add_tool(tree: &mut egui_tiles::Tree<Pane>, tool: Box<dyn GaffrieTool>) {
-        let pane = tree.tiles.insert_pane(Pane { tool });
+    /// Opens the file as a new document, reusing the active one if it's blank
+    fn file_loaded(&mut self, file: OpenedFile) {
+        let (path, mode, file) = match file {
+            Ok(file) => file,
+            Err(err) => {
+                self.errors.push(err, self.time);
+                return;
+            }
+        };
+        if !self.documents[self.active].is_blank() {
+            self.documents.push(Document::default());
+            self.active = self.documents.len() - 1;
+        }
+        let document = &mut self.documents[self.active];
+        document.load(path, mode, file);
+        let id = document.id;
+        self.notify_tools(id, Event::FileChanged);
+        self.handle_event(self.active, Event::SelectionChanged(Selection::default()));
+        self.documents_changed();
+    }
+
+    /// Opens bytes made from another document as a new one
+    fn open_derived(&mut self, name: String, bytes: &[u8]) {
+        let file = match FileData::from_bytes(bytes) {
+            Ok(file) => file,
+            Err(err) => {
+                self.errors.push(Error::open(name, err), self.time);
+                return;
+            }
+        };
+        let mut document = Document::default();
+        document.load_derived(name, file);
+        self.documents.push(document);
+        self.active = self.documents.len() - 1;
+        self.documents_changed();
+    }
+
+    fn close_document(&mut self, id: DocumentId) {
+        let Some(index) = self.document_index(id) else {
+            return;
+        };
+        self.documents.remove(index);
+        // The active document moves down when one before it goes away
+        if index < self.active {
+            self.active -= 1;
+        }
+        if self.documents.is_empty() {
+            self.documents.push(Document::default());
+        }
+        self.active = self.active.min(self.documents.len() - 1);
+        let panes = self
+            .tree
+            .tiles
+            .iter()
+            .filter_map(|(tile_id, tile)| match tile {
+                egui_tiles::Tile::Pane(pane) if pane.document == id => Some(*tile_id),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // Containers drop the removed children when the tree is simplified
+        for tile_id in panes {
+            self.tree.tiles.remove(tile_id);
+        }
+        self.documents_changed();
+    }
+
+    fn save(&mut self) {
+        if self.documents[self.active].path.is_none() {
+            self.save_as();
+        } else if let Err(err) = self.documents[self.active].save() {
+            self.errors.push(err, self.time);
+        }
+    }
+
+    fn save_as(&mut self) {
+        let sender = self.save_channel.0.clone();
+        let document = &self.documents[self.active];
+        let id = document.id;
+        let task = rfd::AsyncFileDialog::new()
+            .set_file_name(document.name())
+            .save_file();
+        execute(async move {
+            if let Some(file) = task.await {
+                let _ = sender.send((id, file.path().to_path_buf()));
+            }
+        });
+    }
+
+    fn saved_as(&mut self, id: DocumentId, path: PathBuf) {
+        // The document may have been closed while the dialog was open
+        let Some(index) = self.document_index(id) else {
+            return;
+        };
+        if let Err(err) = self.documents[index].save_as(path) {
+            self.errors.push(err, self.time);
+        }
+        // The file was remapped
+        self.notify_tools(id, Event::FileChanged);
+        self.documents_changed();
+    }
+
+    fn add_tool(
+        tree: &mut egui_tiles::Tree<Pane>,
+        document: DocumentId,
+        tool: Box<dyn GaffrieTool>,
+    ) {
+        let pane = tree.tiles.insert_pane(Pane { tool, document });
         match tree.root {
             Some(root_tileid) => {
                 let root_tile = tree.tiles.get_mut(root_tileid).unwrap();
@@ -182,39 +478,141 @@ impl MyApp {
 
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.time = ctx.input(|i| i.time);
         if let Ok(file) = self.file_channel.1.try_recv() {
-            let mut lock = self.current_file.write();
-            *lock = file;
-            drop(lock);
-            self.notify_tools(Event::FileChanged);
+            self.file_loaded(file);
+        }
+        if let Ok((id, path)) = self.save_channel.1.try_recv() {
+            self.saved_as(id, path);
+        }
+        for index in 0..self.documents.len() {
+            while let Some(event) = self.documents[index].next_event() {
+                self.handle_event(index, event);
+            }
         }
         egui::SidePanel::left("tree").show(ctx, |ui| {
             if ui.button("Select file").clicked() {
                 let sender = self.file_channel.0.clone();
+                let mode = self.open_mode;
                 let task = rfd::AsyncFileDialog::new().pick_file();
                 execute(async move {
                     let file = task.await;
                     if let Some(file) = file {
                         let path = file.path();
 
-                        let file = File::options().write(true).read(true).open(path).unwrap();
-                        let memfile =
-                            unsafe { memmap2::MmapOptions::new().map_mut(&file).unwrap() };
-                        let _ = sender.send(memfile);
+                        let memfile = Document::map(path, mode);
+                        let _ =
+                            sender.send(memfile.map(|memfile| (path.to_path_buf(), mode, memfile)));
+                    }
+                });
+            }
+            egui::ComboBox::from_label("Open mode")
+                .selected_text(self.open_mode.name())
+                .show_ui(ui, |ui| {
+                    for mode in OpenMode::ALL {
+                        ui.selectable_value(&mut self.open_mode, mode, mode.name());
+                    }
+                });
+            ui.separator();
+            let mut close = None;
+            for (index, document) in self.documents.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    let dirty_marker = if document.dirty { " •" } else { "" };
+                    let name = format!("{}{}", document.name(), dirty_marker);
+                    if ui.selectable_label(index == self.active, name).clicked() {
+                        self.active = index;
+                    }
+                    if ui.small_button("×").on_hover_text("Close").clicked() {
+                        close = Some(document.id);
                     }
                 });
             }
+            if let Some(id) = close {
+                if self.documents.iter().any(|d| d.id == id && d.dirty) {
+                    self.closing = Some(id);
+                } else {
+                    self.close_document(id);
+                }
+            }
+            ui.separator();
+            let document = &self.documents[self.active];
+            ui.weak(document.mode.name());
+            let selection = document.selection;
+            ui.horizontal(|ui| {
+                let document = &self.documents[self.active];
+                let can_save = document.dirty && !document.is_read_only();
+                if ui
+                    .add_enabled(can_save, egui::Button::new("Save"))
+                    .clicked()
+                {
+                    self.save();
+                }
+                if ui.button("Save As").clicked() {
+                    self.save_as();
+                }
+            });
+            ui.separator();
+            ui.label(format!("Cursor: {:#x}", selection.offset));
+            if selection.length > 0 {
+                ui.label(format!("Selected: {} bytes", selection.length));
+            }
+            ui.separator();
+            let errors = format!("Errors ({})", self.errors.len());
+            if ui.selectable_label(self.errors.open, errors).clicked() {
+                self.errors.open = !self.errors.open;
+            }
         });
 
+        self.errors.show_panel(ctx);
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            let mut behavior = TreeBehavior {};
+            let mut behavior = TreeBehavior {
+                documents: &self.documents,
+            };
             self.tree.ui(&mut behavior, ui);
         });
 
+        if let Some(id) = self.closing {
+            let name = match self.document_index(id) {
+                Some(index) => self.documents[index].name(),
+                None => String::new(),
+            };
+            egui::Window::new("Unsaved changes")
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(format!("{} has unsaved changes.", name));
+                    ui.horizontal(|ui| {
+                        if ui.button("Discard").clicked() {
+                            self.close_document(id);
+                            self.closing = None;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            self.closing = None;
+                        }
+                    });
+                });
+        }
+
+        let save_shortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::S);
+        if ctx.input_mut(|i| i.consume_shortcut(&save_shortcut)) {
+            self.save();
+        }
+        let redo_modifiers = egui::Modifiers::COMMAND | egui::Modifiers::SHIFT;
+        let redo_shortcut = egui::KeyboardShortcut::new(redo_modifiers, egui::Key::Z);
+        let undo_shortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z);
+        if ctx.input_mut(|i| i.consume_shortcut(&redo_shortcut)) {
+            self.handle_event(self.active, Event::Redo);
+        } else if ctx.input_mut(|i| i.consume_shortcut(&undo_shortcut)) {
+            self.handle_event(self.active, Event::Undo);
+        }
+
         ctx.input(|i| {
             if i.key_pressed(egui::Key::P) && !self.action_popup_opened {
                 self.action_popup_opened = true;
                 self.current_action = 0;
+                self.action_document = self.active;
                 self.action_popup_text.clear();
             }
             if self.action_popup_opened {
@@ -223,8 +621,18 @@ impl eframe::App for MyApp {
                 } else if i.key_pressed(egui::Key::ArrowUp) {
                     self.current_action = self.current_action.saturating_sub(1);
                 }
+                // Tab cycles through the documents the new tool can be attached to
+                if i.key_pressed(egui::Key::Tab) {
+                    let count = self.documents.len();
+                    self.action_document = if i.modifiers.shift {
+                        (self.action_document + count - 1) % count
+                    } else {
+                        (self.action_document + 1) % count
+                    };
+                }
             }
         });
+        let mut tool_added = false;
         if self.action_popup_opened {
             egui::Window::new("action_popup")
                 .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
@@ -233,8 +641,13 @@ impl eframe::App for MyApp {
                 .title_bar(false)
                 .show(ctx, |ui| {
                     let text_edit = egui::TextEdit::singleline(&mut self.action_popup_text)
-                        .hint_text("Enter the name of a tool");
+                        .hint_text("Enter the name of a tool")
+                        .lock_focus(true);
                     let response = ui.add(text_edit);
+                    if self.documents.len() > 1 {
+                        let document = &self.documents[self.action_document];
+                        ui.weak(format!("For {} (Tab to change)", document.name()));
+                    }
                     if response.lost_focus() {
                         self.action_popup_opened = false;
                     }
@@ -273,40 +686,53 @@ impl eframe::App for MyApp {
                         if i.key_pressed(egui::Key::Enter) {
                             self.action_popup_opened = false;
                             let action = &filtered_actions[self.current_action].0 .1;
-                            (action)(&self.current_file, &mut self.tree);
+                            let document = &self.documents[self.action_document];
+                            (action)(document, &mut self.tree);
+                            // Let the new tool catch up with the document selection
+                            let _ = document
+                                .events
+                                .send(Event::SelectionChanged(document.selection));
+                            tool_added = true;
                         }
                     });
                 });
         }
+        if tool_added {
+            self.documents_changed();
+        }
 
         ctx.input(|i| {
-            if !i.raw.dropped_files.is_empty() {
-                let dropped_file = i.raw.dropped_files.first().unwrap();
+            for dropped_file in &i.raw.dropped_files {
                 if let Some(path) = &dropped_file.path {
-                    let file = File::options().write(true).read(true).open(path).unwrap();
-                    let memfile = unsafe { memmap2::MmapOptions::new().map_mut(&file).unwrap() };
-                    let mut lock = self.current_file.write();
-                    *lock = memfile;
-                    drop(lock);
-                    self.notify_tools(Event::FileChanged);
+                    let memfile = Document::map(path, self.open_mode);
+                    self.file_loaded(
+                        memfile.map(|memfile| (path.clone(), self.open_mode, memfile)),
+                    );
                 }
             }
-        })
+        });
+
+        self.errors.show_toasts(ctx, self.time);
     }
 
-    fn save(&mut self, _storage: &mut dyn eframe::Storage) {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        storage.set_string(TEMPLATES_KEY, self.templates.source());
         #[cfg(feature = "serde")]
-        eframe::set_value(_storage, eframe::APP_KEY, &self);
+        eframe::set_value(storage, eframe::APP_KEY, &self);
     }
 }
 
 struct Pane {
     tool: Box<dyn GaffrieTool>,
+    /// Document the tool works on
+    document: DocumentId,
 }
 
-struct TreeBehavior {}
+struct TreeBehavior<'a> {
+    documents: &'a [Document],
+}
 
-impl egui_tiles::Behavior<Pane> for TreeBehavior {
+impl egui_tiles::Behavior<Pane> for TreeBehavior<'_> {
     fn pane_ui(
         &mut self,
         ui: &mut egui::Ui,
@@ -318,7 +744,14 @@ impl egui_tiles::Behavior<Pane> for TreeBehavior {
     }
 
     fn tab_title_for_pane(&mut self, pane: &Pane) -> egui::WidgetText {
-        pane.tool.title().into()
+        // Tell apart the same tools opened for different documents
+        let document = self.documents.iter().find(|d| d.id == pane.document);
+        match document {
+            Some(document) if self.documents.len() > 1 => {
+                format!("{} — {}", pane.tool.title(), document.name()).into()
+            }
+            _ => pane.tool.title().into(),
+        }
     }
 
     fn simplification_options(&self) -> SimplificationOptions {
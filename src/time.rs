@@ -0,0 +1,71 @@
+//! Dates and times the way file formats store them, as text
+
+/// Converts days since the Unix epoch into a year, month and day
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Seconds since 1970 as a UTC date, like `2021-03-04 05:06:07 UTC`. `None` for dates before
+/// year 1 or after year 9999.
+pub fn format_unix_time(seconds: i64) -> Option<String> {
+    let days = seconds.div_euclid(86_400);
+    let time = seconds.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    if !(1..=9999).contains(&year) {
+        return None;
+    }
+    Some(format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    ))
+}
+
+/// MS-DOS date, years start at 1980. `None` if the month or day is out of range.
+pub fn format_dos_date(date: u16) -> Option<String> {
+    let year = 1980 + (date >> 9);
+    let month = (date >> 5) & 0xf;
+    let day = date & 0x1f;
+    if !(1..=12).contains(&month) || day == 0 {
+        return None;
+    }
+    Some(format!("{:04}-{:02}-{:02}", year, month, day))
+}
+
+/// MS-DOS time of day, which counts seconds by two. `None` if a part is out of range.
+pub fn format_dos_time(time: u16) -> Option<String> {
+    let hour = time >> 11;
+    let minute = (time >> 5) & 0x3f;
+    let second = (time & 0x1f) * 2;
+    if hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    Some(format!("{:02}:{:02}:{:02}", hour, minute, second))
+}
+
+pub fn format_dos_date_time(date: u16, time: u16) -> Option<String> {
+    Some(format!(
+        "{} {}",
+        format_dos_date(date)?,
+        format_dos_time(time)?
+    ))
+}
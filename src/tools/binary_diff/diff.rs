@@ -0,0 +1,319 @@
+use std::{collections::HashMap, ops::Range};
+
+/// Bytes compared at once while skipping over equal parts
+const CHUNK: usize = 64;
+/// Equal bytes needed to consider the files to be back in sync after a difference
+const SYNC_LENGTH: usize = 16;
+/// How far past a difference the shift-aware diff looks for the files to get back in sync
+const SEARCH_WINDOW: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffMode {
+    /// Compares bytes at the same offsets, fast but every shift makes the rest differ
+    Aligned,
+    /// Finds inserted and deleted bytes, so shifted content still matches
+    ShiftAware,
+}
+
+impl DiffMode {
+    pub const ALL: [DiffMode; 2] = [DiffMode::Aligned, DiffMode::ShiftAware];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DiffMode::Aligned => "Aligned",
+            DiffMode::ShiftAware => "Shift-aware",
+        }
+    }
+}
+
+/// Bytes differing between two files. One of the ranges is empty for pure insertions and deletions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Difference {
+    pub left: Range<usize>,
+    pub right: Range<usize>,
+}
+
+impl Difference {
+    /// How much the right file is shifted relative to the left one after this difference
+    pub fn shift(&self) -> isize {
+        self.right.end as isize - self.left.end as isize
+    }
+}
+
+/// Differences ordered by offset, both ranges grow monotonically. Gives up with `None` once
+/// `stop` returns true, which is checked after each difference.
+pub fn diff(
+    left: &[u8],
+    right: &[u8],
+    mode: DiffMode,
+    stop: &dyn Fn() -> bool,
+) -> Option<Vec<Difference>> {
+    match mode {
+        DiffMode::Aligned => aligned(left, right, stop),
+        DiffMode::ShiftAware => shift_aware(left, right, stop),
+    }
+}
+
+/// Offset in the right file shown next to `offset` of the left one
+pub fn right_offset(differences: &[Difference], offset: usize) -> isize {
+    // Differences starting right at the offset still have to be visible, so they don't count yet
+    let index = differences.partition_point(|d| d.left.start < offset);
+    let shift = match index.checked_sub(1).map(|index| &differences[index]) {
+        Some(d) if offset < d.left.end => d.right.start as isize - d.left.start as isize,
+        Some(d) => d.shift(),
+        None => 0,
+    };
+    offset as isize + shift
+}
+
+/// Index of the difference covering `offset`, in the left or the right file
+pub fn difference_at(differences: &[Difference], offset: usize, right: bool) -> Option<usize> {
+    let range = |d: &Difference| {
+        if right {
+            d.right.clone()
+        } else {
+            d.left.clone()
+        }
+    };
+    let index = differences.partition_point(|d| range(d).end <= offset);
+    differences
+        .get(index)
+        .filter(|d| range(d).contains(&offset))
+        .map(|_| index)
+}
+
+fn common_prefix(left: &[u8], right: &[u8]) -> usize {
+    let len = left.len().min(right.len());
+    let mut offset = 0;
+    while offset + CHUNK <= len && left[offset..offset + CHUNK] == right[offset..offset + CHUNK] {
+        offset += CHUNK;
+    }
+    offset
+        + left[offset..len]
+            .iter()
+            .zip(&right[offset..len])
+            .take_while(|(a, b)| a == b)
+            .count()
+}
+
+fn aligned(left: &[u8], right: &[u8], stop: &dyn Fn() -> bool) -> Option<Vec<Difference>> {
+    let common = left.len().min(right.len());
+    let mut differences = Vec::new();
+    let mut offset = 0;
+    while offset < common {
+        if stop() {
+            return None;
+        }
+        offset += common_prefix(&left[offset..common], &right[offset..common]);
+        if offset == common {
+            break;
+        }
+        let start = offset;
+        offset += left[offset..common]
+            .iter()
+            .zip(&right[offset..common])
+            .take_while(|(a, b)| a != b)
+            .count();
+        differences.push(Difference {
+            left: start..offset,
+            right: start..offset,
+        });
+    }
+    if left.len() != right.len() {
+        push_merged(&mut differences, common..left.len(), common..right.len());
+    }
+    Some(differences)
+}
+
+fn shift_aware(left: &[u8], right: &[u8], stop: &dyn Fn() -> bool) -> Option<Vec<Difference>> {
+    let mut differences = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    loop {
+        if stop() {
+            return None;
+        }
+        let equal = common_prefix(&left[i..], &right[j..]);
+        i += equal;
+        j += equal;
+        if i == left.len() || j == right.len() {
+            break;
+        }
+        let (di, dj) = resync(&left[i..], &right[j..]);
+        push_merged(&mut differences, i..i + di, j..j + dj);
+        i += di;
+        j += dj;
+    }
+    if i < left.len() || j < right.len() {
+        push_merged(&mut differences, i..left.len(), j..right.len());
+    }
+    Some(differences)
+}
+
+/// Adds a difference, extending the last one instead if they touch
+fn push_merged(differences: &mut Vec<Difference>, left: Range<usize>, right: Range<usize>) {
+    match differences.last_mut() {
+        Some(last) if last.left.end == left.start && last.right.end == right.start => {
+            last.left.end = left.end;
+            last.right.end = right.end;
+        }
+        _ => differences.push(Difference { left, right }),
+    }
+}
+
+/// Finds the closest offsets after which both slices are equal for `SYNC_LENGTH` bytes.
+/// Both slices start with differing bytes.
+fn resync(left: &[u8], right: &[u8]) -> (usize, usize) {
+    let in_sync =
+        |i: usize, j: usize| match (left.get(i..i + SYNC_LENGTH), right.get(j..j + SYNC_LENGTH)) {
+            (Some(left), Some(right)) => left == right,
+            _ => false,
+        };
+    // Most differences are a few replaced bytes, check that before looking for shifts
+    if let Some(d) = (1..SYNC_LENGTH * 4).find(|&d| in_sync(d, d)) {
+        return (d, d);
+    }
+    let fallback = (
+        left.len().min(SEARCH_WINDOW),
+        right.len().min(SEARCH_WINDOW),
+    );
+    if left.len() < SYNC_LENGTH || right.len() < SYNC_LENGTH {
+        return fallback;
+    }
+    // Where each run of `SYNC_LENGTH` bytes starts in the right window, closest first
+    let window = right.len().min(SEARCH_WINDOW + SYNC_LENGTH) - SYNC_LENGTH;
+    let mut starts: HashMap<&[u8], usize> = HashMap::new();
+    for j in (0..=window).rev() {
+        starts.insert(&right[j..j + SYNC_LENGTH], j);
+    }
+    let mut best: Option<(usize, usize)> = None;
+    let window = left.len().min(SEARCH_WINDOW + SYNC_LENGTH) - SYNC_LENGTH;
+    for i in 0..=window {
+        if best.is_some_and(|(bi, bj)| i >= bi + bj) {
+            break;
+        }
+        if let Some(&j) = starts.get(&left[i..i + SYNC_LENGTH]) {
+            if best.filter(|&(bi, bj)| bi + bj <= i + j).is_none() {
+                best = Some((i, j));
+            }
+        }
+    }
+    best.unwrap_or(fallback)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff_all(left: &[u8], right: &[u8], mode: DiffMode) -> Vec<Difference> {
+        diff(left, right, mode, &|| false).unwrap()
+    }
+
+    fn difference(left: Range<usize>, right: Range<usize>) -> Difference {
+        Difference { left, right }
+    }
+
+    /// Bytes that don't repeat within `SYNC_LENGTH`, so shifts can only line up one way
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn identical() {
+        let bytes = pattern(1000);
+        for mode in DiffMode::ALL {
+            assert_eq!(diff_all(&bytes, &bytes, mode), []);
+            assert_eq!(diff_all(&[], &[], mode), []);
+        }
+    }
+
+    #[test]
+    fn aligned_replacements_and_tail() {
+        let left = pattern(300);
+        let mut right = left.clone();
+        right[10] ^= 0xff;
+        right[11] ^= 0xff;
+        right[200] ^= 0xff;
+        right.extend_from_slice(b"tail");
+        assert_eq!(
+            diff_all(&left, &right, DiffMode::Aligned),
+            [
+                difference(10..12, 10..12),
+                difference(200..201, 200..201),
+                difference(300..300, 300..304),
+            ]
+        );
+    }
+
+    #[test]
+    fn aligned_sees_insertion_as_rest_differing() {
+        let left = pattern(300);
+        let mut right = left.clone();
+        right.insert(100, 0xaa);
+        let differences = diff_all(&left, &right, DiffMode::Aligned);
+        assert_eq!(differences.first().unwrap().left.start, 100);
+        assert_eq!(differences.last().unwrap().right.end, 301);
+    }
+
+    #[test]
+    fn shift_aware_insertion_and_deletion() {
+        let left = pattern(1000);
+        let mut right = left.clone();
+        right.splice(100..100, [0xaa; 5]);
+        right.drain(605..615);
+        assert_eq!(
+            diff_all(&left, &right, DiffMode::ShiftAware),
+            [
+                difference(100..100, 100..105),
+                difference(600..610, 605..605)
+            ]
+        );
+    }
+
+    #[test]
+    fn shift_aware_replacement() {
+        let left = pattern(1000);
+        let mut right = left.clone();
+        right[500] ^= 0xff;
+        assert_eq!(
+            diff_all(&left, &right, DiffMode::ShiftAware),
+            [difference(500..501, 500..501)]
+        );
+    }
+
+    #[test]
+    fn shift_aware_different_endings() {
+        let left = pattern(100);
+        let mut right = left[..50].to_vec();
+        right.extend_from_slice(b"something else");
+        assert_eq!(
+            diff_all(&left, &right, DiffMode::ShiftAware),
+            [difference(50..100, 50..64)]
+        );
+    }
+
+    #[test]
+    fn stops_when_asked() {
+        let left = pattern(100);
+        let right = vec![0; 100];
+        for mode in DiffMode::ALL {
+            assert_eq!(diff(&left, &right, mode, &|| true), None);
+        }
+    }
+
+    #[test]
+    fn offsets_across_a_shift() {
+        let differences = [
+            difference(100..100, 100..105),
+            difference(600..610, 605..605),
+        ];
+        assert_eq!(right_offset(&differences, 50), 50);
+        assert_eq!(right_offset(&differences, 100), 100);
+        assert_eq!(right_offset(&differences, 101), 106);
+        assert_eq!(right_offset(&differences, 600), 605);
+        assert_eq!(right_offset(&differences, 700), 695);
+        assert_eq!(difference_at(&differences, 102, true), Some(0));
+        assert_eq!(difference_at(&differences, 102, false), None);
+        assert_eq!(difference_at(&differences, 609, false), Some(1));
+        assert_eq!(difference_at(&differences, 610, false), None);
+    }
+}
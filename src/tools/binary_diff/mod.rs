@@ -0,0 +1,480 @@
+use std::{
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{Receiver, Sender},
+        Arc,
+    },
+};
+
+use egui::{mutex::RwLock, Align2, Color32, FontId, Rect, Sense, Stroke, TextStyle};
+
+use super::{hex_viewer::RowLayout, GaffrieTool};
+use crate::{
+    document::{Document, DocumentHandle, DocumentId, FileData},
+    Event, Selection,
+};
+
+mod diff;
+
+use diff::{DiffMode, Difference};
+
+/// Space between the two hex views, in characters
+const GAP: usize = 3;
+/// Seconds without edits before the files are compared again, so typing doesn't start a
+/// comparison for every byte
+const EDIT_DELAY: f64 = 0.3;
+
+/// What a comparison was made from, to tell when it's outdated
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Compared {
+    other: DocumentId,
+    mode: DiffMode,
+    /// Versions of our file and the other one
+    versions: (usize, usize),
+}
+
+/// Comparison running in the background
+struct RunningDiff {
+    result: Receiver<Vec<Difference>>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Drop for RunningDiff {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+pub struct BinaryDiff {
+    id: DocumentId,
+    file: Arc<RwLock<FileData>>,
+    version: Arc<AtomicUsize>,
+    events: Sender<Event>,
+    /// Documents that can be compared against, all open ones except ours
+    others: Vec<DocumentHandle>,
+    other: Option<DocumentHandle>,
+    mode: DiffMode,
+    differences: Vec<Difference>,
+    /// The last comparison started, which may still be running
+    compared: Option<Compared>,
+    running: Option<RunningDiff>,
+    /// When one of the files was first seen edited since the last comparison
+    edited_at: Option<f64>,
+    /// Difference navigated to last
+    current: Option<usize>,
+    bytes_per_row: usize,
+    visible_rows: Range<usize>,
+    scroll_to_row: Option<usize>,
+}
+
+impl GaffrieTool for BinaryDiff {
+    fn new(document: &Document) -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            id: document.id,
+            file: document.file.clone(),
+            version: document.version.clone(),
+            events: document.events.clone(),
+            others: Vec::new(),
+            other: None,
+            mode: DiffMode::Aligned,
+            differences: Vec::new(),
+            compared: None,
+            running: None,
+            edited_at: None,
+            current: None,
+            bytes_per_row: 16,
+            visible_rows: 0..0,
+            scroll_to_row: None,
+        }
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        self.receive_differences();
+        let mut recompute = false;
+        ui.horizontal(|ui| {
+            let selected = match &self.other {
+                Some(other) => other.name.clone(),
+                None => "Nothing".to_string(),
+            };
+            egui::ComboBox::from_label("Compare with")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for other in &self.others {
+                        let checked = self.other.as_ref().map(|o| o.id) == Some(other.id);
+                        if ui.selectable_label(checked, &other.name).clicked() {
+                            self.other = Some(other.clone());
+                            recompute = true;
+                        }
+                    }
+                });
+            egui::ComboBox::from_label("Mode")
+                .selected_text(self.mode.name())
+                .show_ui(ui, |ui| {
+                    for mode in DiffMode::ALL {
+                        recompute |= ui
+                            .selectable_value(&mut self.mode, mode, mode.name())
+                            .clicked();
+                    }
+                });
+            if self.running.is_some() {
+                ui.spinner();
+            }
+        });
+        if recompute {
+            self.start();
+        }
+        self.start_after_edits(ui);
+        let Some(other) = self.other.clone() else {
+            ui.label("Open another file to compare with");
+            return;
+        };
+        ui.horizontal(|ui| {
+            let count = self.differences.len();
+            if ui
+                .add_enabled(count > 0, egui::Button::new("Previous"))
+                .clicked()
+            {
+                self.navigate(false);
+            }
+            if ui
+                .add_enabled(count > 0, egui::Button::new("Next"))
+                .clicked()
+            {
+                self.navigate(true);
+            }
+            match self.current {
+                _ if count == 0 => ui.label("Files are identical"),
+                Some(current) => ui.label(format!("Difference {} of {}", current + 1, count)),
+                None => ui.label(format!("{} differences", count)),
+            };
+            ui.add(egui::DragValue::new(&mut self.bytes_per_row).clamp_range(1..=48));
+        });
+        let text_style = TextStyle::Monospace;
+        let row_height = ui.text_style_height(&text_style);
+        let font_id = text_style.resolve(ui.style());
+        let layout = RowLayout {
+            char_width: ui.fonts(|f| f.glyph_width(&font_id, 'a')),
+            row_stride: row_height + ui.spacing().item_spacing.y,
+            bytes_per_row: self.bytes_per_row,
+        };
+        let file = self.file.clone();
+        let left = file.read();
+        let right = other.file.read();
+        // Rows go over the left file, the right one may be shifted by up to the largest difference
+        let max_shift = self
+            .differences
+            .iter()
+            .map(|d| d.shift().unsigned_abs())
+            .max()
+            .unwrap_or(0);
+        let total_rows = (left.len().max(right.len()) + max_shift) / self.bytes_per_row + 1;
+        let side_width = layout.width() + GAP as f32 * layout.char_width;
+        let mut scroll_area = egui::ScrollArea::both().auto_shrink([false, false]);
+        if let Some(row) = self.scroll_to_row.take() {
+            scroll_area = scroll_area.vertical_scroll_offset(row as f32 * layout.row_stride);
+        }
+        let mut clicked = None;
+        scroll_area.show_rows(ui, row_height, total_rows, |ui, rows| {
+            self.visible_rows = rows.clone();
+            // The right side keeps the shift of the top row, so it scrolls along with the left one
+            let top = rows.start * self.bytes_per_row;
+            let shift = diff::right_offset(&self.differences, top) - top as isize;
+            for row in rows {
+                let (rect, response) = ui
+                    .allocate_exact_size(egui::vec2(side_width * 2.0, row_height), Sense::click());
+                let start = row * self.bytes_per_row;
+                let left_rect = rect.with_max_x(rect.left() + side_width);
+                let right_rect = rect.with_min_x(rect.left() + side_width);
+                self.paint_side(
+                    ui,
+                    &layout,
+                    &font_id,
+                    left_rect,
+                    &left,
+                    start as isize,
+                    false,
+                );
+                self.paint_side(
+                    ui,
+                    &layout,
+                    &font_id,
+                    right_rect,
+                    &right,
+                    start as isize + shift,
+                    true,
+                );
+                if let Some(pos) = response
+                    .interact_pointer_pos()
+                    .filter(|_| response.clicked())
+                {
+                    let is_right = pos.x >= right_rect.left();
+                    let side_rect = if is_right { right_rect } else { left_rect };
+                    let (offset, _) = layout.byte_at(row, side_rect, pos);
+                    let offset = if is_right {
+                        offset as isize + shift
+                    } else {
+                        offset as isize
+                    };
+                    clicked = Some((is_right, offset));
+                }
+            }
+        });
+        drop(left);
+        drop(right);
+        if let Some((is_right, offset)) = clicked {
+            let (len, events) = if is_right {
+                (other.file.read().len(), &other.events)
+            } else {
+                (self.file.read().len(), &self.events)
+            };
+            if let Ok(offset) = usize::try_from(offset) {
+                if offset < len {
+                    let _ = events.send(Event::CursorMoved(offset));
+                }
+            }
+        }
+    }
+
+    fn title(&self) -> String {
+        "Binary Diff".to_string()
+    }
+
+    fn notify(&mut self, event: Event) {
+        // Edits of either file are noticed through their versions
+        if let Event::DocumentsChanged(documents) = event {
+            self.others = documents
+                .into_iter()
+                .filter(|document| document.id != self.id)
+                .collect();
+            // Picks up renames and closed documents, falling back to the first one
+            let other_id = self.other.as_ref().map(|other| other.id);
+            self.other = self
+                .others
+                .iter()
+                .find(|other| Some(other.id) == other_id)
+                .or(self.others.first())
+                .cloned();
+            if self.other.as_ref().map(|other| other.id) != other_id {
+                self.start();
+            }
+        }
+    }
+}
+
+impl BinaryDiff {
+    fn to_compare(&self) -> Option<Compared> {
+        let other = self.other.as_ref()?;
+        Some(Compared {
+            other: other.id,
+            mode: self.mode,
+            versions: (
+                self.version.load(Ordering::Relaxed),
+                other.version.load(Ordering::Relaxed),
+            ),
+        })
+    }
+
+    /// Compares the files in the background, replacing the comparison that's running
+    fn start(&mut self) {
+        self.compared = self.to_compare();
+        self.edited_at = None;
+        let Some(other) = self.other.clone() else {
+            self.running = None;
+            self.differences.clear();
+            self.current = None;
+            return;
+        };
+        let (sender, result) = std::sync::mpsc::channel();
+        let running = RunningDiff {
+            result,
+            cancel: Arc::new(AtomicBool::new(false)),
+        };
+        let cancel = running.cancel.clone();
+        self.running = Some(running);
+        let file = self.file.clone();
+        let version = self.version.clone();
+        let mode = self.mode;
+        let versions = self.compared.map(|compared| compared.versions);
+        crate::execute(async move {
+            // Edits wait for the files, so they get them back as soon as one is about to happen
+            let stop = || {
+                cancel.load(Ordering::Relaxed)
+                    || Some((
+                        version.load(Ordering::Relaxed),
+                        other.version.load(Ordering::Relaxed),
+                    )) != versions
+            };
+            let left = file.read();
+            let right = other.file.read();
+            if let Some(differences) = diff::diff(&left, &right, mode, &stop) {
+                let _ = sender.send(differences);
+            }
+        });
+    }
+
+    /// Compares again once the files haven't been edited for a moment
+    fn start_after_edits(&mut self, ui: &egui::Ui) {
+        if self.to_compare() == self.compared {
+            return;
+        }
+        let time = ui.input(|i| i.time);
+        let edited_at = *self.edited_at.get_or_insert(time);
+        if time - edited_at >= EDIT_DELAY {
+            self.start();
+        } else {
+            ui.ctx()
+                .request_repaint_after(std::time::Duration::from_secs_f64(EDIT_DELAY));
+        }
+    }
+
+    fn receive_differences(&mut self) {
+        let Some(running) = &self.running else {
+            return;
+        };
+        match running.result.try_recv() {
+            Ok(differences) => {
+                self.differences = differences;
+                self.current = self
+                    .current
+                    .filter(|&current| current < self.differences.len());
+                self.running = None;
+            }
+            // Stopped by an edit, the next comparison replaces it
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => self.running = None,
+            Err(std::sync::mpsc::TryRecvError::Empty) => {}
+        }
+    }
+
+    fn navigate(&mut self, forward: bool) {
+        let count = self.differences.len();
+        if count == 0 {
+            return;
+        }
+        let current = match self.current {
+            Some(current) if forward => (current + 1) % count,
+            Some(current) => (current + count - 1) % count,
+            // Starts from what's visible
+            None => {
+                let top = self.visible_rows.start * self.bytes_per_row;
+                let next = self.differences.partition_point(|d| d.left.end <= top);
+                if forward {
+                    next % count
+                } else {
+                    (next + count - 1) % count
+                }
+            }
+        };
+        self.current = Some(current);
+        let difference = &self.differences[current];
+        // A few rows of context above the difference
+        let row = difference.left.start / self.bytes_per_row;
+        self.scroll_to_row = Some(row.saturating_sub(2));
+        let selection = Selection::new(difference.left.start, difference.left.len());
+        let _ = self.events.send(Event::SelectionChanged(selection));
+        if let Some(other) = &self.other {
+            let selection = Selection::new(difference.right.start, difference.right.len());
+            let _ = other.events.send(Event::SelectionChanged(selection));
+        }
+    }
+
+    /// Paints one row of one of the files, `start` is the offset of its first byte and may be
+    /// outside of the file when the files are shifted
+    #[allow(clippy::too_many_arguments)]
+    fn paint_side(
+        &self,
+        ui: &egui::Ui,
+        layout: &RowLayout,
+        font_id: &FontId,
+        rect: Rect,
+        bytes: &[u8],
+        start: isize,
+        is_right: bool,
+    ) {
+        let painter = ui.painter();
+        let visuals = ui.visuals();
+        let mut text = String::with_capacity(self.bytes_per_row * 3);
+        let mut ascii_text = String::with_capacity(self.bytes_per_row);
+        for index in 0..self.bytes_per_row {
+            let offset = usize::try_from(start + index as isize).ok();
+            let Some((offset, byte)) = offset.and_then(|o| Some((o, *bytes.get(o)?))) else {
+                text.push_str("   ");
+                ascii_text.push(' ');
+                continue;
+            };
+            text.push_str(&format!("{:02x} ", byte));
+            ascii_text.push(if byte.is_ascii_control() {
+                '.'
+            } else {
+                byte as char
+            });
+            let Some(found) = diff::difference_at(&self.differences, offset, is_right) else {
+                continue;
+            };
+            let difference = &self.differences[found];
+            let paired = if is_right {
+                &difference.left
+            } else {
+                &difference.right
+            };
+            let color = if !paired.is_empty() {
+                visuals.warn_fg_color
+            } else if is_right {
+                Color32::from_rgb(0x40, 0xc0, 0x40)
+            } else {
+                visuals.error_fg_color
+            };
+            let hex_rect = Rect::from_min_size(
+                rect.left_top() + egui::vec2(layout.hex_x(index), 0.0),
+                egui::vec2(layout.char_width * 2.0, rect.height()),
+            )
+            .expand2(egui::vec2(layout.char_width * 0.5, 0.0));
+            let ascii_rect = Rect::from_min_size(
+                rect.left_top() + egui::vec2(layout.ascii_x(index), 0.0),
+                egui::vec2(layout.char_width, rect.height()),
+            );
+            let alpha = if self.current == Some(found) {
+                0.6
+            } else {
+                0.25
+            };
+            painter.rect_filled(hex_rect, 0.0, color.gamma_multiply(alpha));
+            painter.rect_filled(ascii_rect, 0.0, color.gamma_multiply(alpha));
+        }
+
+        let in_file = usize::try_from(start).is_ok_and(|start| start < bytes.len());
+        if in_file {
+            painter.text(
+                rect.left_top(),
+                Align2::LEFT_TOP,
+                format!("{:08x}", start),
+                font_id.clone(),
+                visuals.weak_text_color(),
+            );
+        }
+        let color = visuals.text_color();
+        painter.text(
+            rect.left_top() + egui::vec2(layout.hex_x(0), 0.0),
+            Align2::LEFT_TOP,
+            text.trim_end(),
+            font_id.clone(),
+            color,
+        );
+        painter.text(
+            rect.left_top() + egui::vec2(layout.ascii_x(0), 0.0),
+            Align2::LEFT_TOP,
+            ascii_text,
+            font_id.clone(),
+            color,
+        );
+        let separator_x = rect.left() + layout.ascii_x(0) - layout.char_width * 0.5;
+        painter.vline(
+            separator_x,
+            rect.y_range(),
+            Stroke::new(1.0, visuals.widgets.noninteractive.bg_stroke.color),
+        );
+    }
+}
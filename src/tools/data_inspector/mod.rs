@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use egui::mutex::RwLock;
+
+use super::GaffrieTool;
+use crate::{
+    document::{Document, FileData},
+    Event,
+};
+
+mod values;
+
+use values::Values;
+
+/// Enough for the longest interpretation, GUIDs and IPv6 addresses
+const READ_LENGTH: usize = 16;
+
+pub struct DataInspector {
+    file: Arc<RwLock<FileData>>,
+    cursor: usize,
+}
+
+impl GaffrieTool for DataInspector {
+    fn new(document: &Document) -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            file: document.file.clone(),
+            cursor: document.selection.offset,
+        }
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let bytes = {
+            let lock = self.file.read();
+            let start = self.cursor.min(lock.len());
+            let end = (start + READ_LENGTH).min(lock.len());
+            lock[start..end].to_vec()
+        };
+        ui.label(format!("Offset: {:#x}", self.cursor));
+        egui::ScrollArea::both()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                egui::Grid::new("data_inspector")
+                    .striped(true)
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        ui.strong("Type");
+                        ui.strong("Little endian");
+                        ui.strong("Big endian");
+                        ui.end_row();
+                        for row in values::interpret(&bytes) {
+                            ui.label(row.name);
+                            match row.values {
+                                Values::Single(value) => {
+                                    value_label(ui, value);
+                                    ui.label("");
+                                }
+                                Values::Endian { little, big } => {
+                                    value_label(ui, little);
+                                    value_label(ui, big);
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+
+    fn title(&self) -> String {
+        "Data Inspector".to_string()
+    }
+
+    fn notify(&mut self, event: Event) {
+        match event {
+            Event::CursorMoved(offset) => self.cursor = offset,
+            Event::SelectionChanged(selection) => self.cursor = selection.offset,
+            _ => {}
+        }
+    }
+}
+
+/// Shows the value in monospace, clicking it copies it
+fn value_label(ui: &mut egui::Ui, value: Option<String>) {
+    let Some(value) = value else {
+        ui.weak("—");
+        return;
+    };
+    let label =
+        egui::Label::new(egui::RichText::new(&value).monospace()).sense(egui::Sense::click());
+    if ui.add(label).on_hover_text("Click to copy").clicked() {
+        ui.output_mut(|o| o.copied_text = value);
+    }
+}
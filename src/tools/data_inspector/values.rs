@@ -0,0 +1,343 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::time::{format_dos_date_time, format_unix_time};
+
+/// Seconds between 1601-01-01, the FILETIME epoch, and the Unix epoch
+const FILETIME_EPOCH_OFFSET: i64 = 11_644_473_600;
+
+pub enum Values {
+    /// Doesn't depend on byte order
+    Single(Option<String>),
+    Endian {
+        little: Option<String>,
+        big: Option<String>,
+    },
+}
+
+/// One way to read the bytes at the cursor, values are `None` if the bytes don't fit the type
+pub struct Row {
+    pub name: &'static str,
+    pub values: Values,
+}
+
+fn array<const N: usize>(bytes: &[u8]) -> Option<[u8; N]> {
+    bytes.get(..N)?.try_into().ok()
+}
+
+fn single(name: &'static str, value: Option<String>) -> Row {
+    Row {
+        name,
+        values: Values::Single(value),
+    }
+}
+
+fn endian<const N: usize, T>(
+    name: &'static str,
+    bytes: &[u8],
+    from_le: impl Fn([u8; N]) -> Option<T>,
+    from_be: impl Fn([u8; N]) -> Option<T>,
+    format: impl Fn(T) -> Option<String>,
+) -> Row {
+    let bytes = array::<N>(bytes);
+    Row {
+        name,
+        values: Values::Endian {
+            little: bytes.and_then(&from_le).and_then(&format),
+            big: bytes.and_then(&from_be).and_then(&format),
+        },
+    }
+}
+
+macro_rules! number {
+    ($bytes:expr, $name:literal, $type:ty) => {
+        endian(
+            $name,
+            $bytes,
+            |b| Some(<$type>::from_le_bytes(b)),
+            |b| Some(<$type>::from_be_bytes(b)),
+            |value| Some(value.to_string()),
+        )
+    };
+}
+
+pub fn interpret(bytes: &[u8]) -> Vec<Row> {
+    vec![
+        single("i8", bytes.first().map(|&b| (b as i8).to_string())),
+        single("u8", bytes.first().map(|b| b.to_string())),
+        number!(bytes, "i16", i16),
+        number!(bytes, "u16", u16),
+        number!(bytes, "i32", i32),
+        number!(bytes, "u32", u32),
+        number!(bytes, "i64", i64),
+        number!(bytes, "u64", u64),
+        endian(
+            "f16",
+            bytes,
+            |b| Some(f16_to_f32(u16::from_le_bytes(b))),
+            |b| Some(f16_to_f32(u16::from_be_bytes(b))),
+            format_float,
+        ),
+        endian(
+            "f32",
+            bytes,
+            |b| Some(f32::from_le_bytes(b)),
+            |b| Some(f32::from_be_bytes(b)),
+            format_float,
+        ),
+        endian(
+            "f64",
+            bytes,
+            |b| Some(f64::from_le_bytes(b)),
+            |b| Some(f64::from_be_bytes(b)),
+            format_float,
+        ),
+        single(
+            "ULEB128",
+            uleb128(bytes).map(|(value, length)| format_leb128(value, length)),
+        ),
+        single(
+            "SLEB128",
+            sleb128(bytes).map(|(value, length)| format_leb128(value, length)),
+        ),
+        endian(
+            "Unix time (32-bit)",
+            bytes,
+            |b| Some(i32::from_le_bytes(b) as i64),
+            |b| Some(i32::from_be_bytes(b) as i64),
+            format_unix_time,
+        ),
+        endian(
+            "Unix time (64-bit)",
+            bytes,
+            |b| Some(i64::from_le_bytes(b)),
+            |b| Some(i64::from_be_bytes(b)),
+            format_unix_time,
+        ),
+        endian(
+            "FILETIME",
+            bytes,
+            |b| Some(u64::from_le_bytes(b)),
+            |b| Some(u64::from_be_bytes(b)),
+            format_filetime,
+        ),
+        endian(
+            "DOS date/time",
+            bytes,
+            |b: [u8; 4]| {
+                Some([
+                    u16::from_le_bytes([b[0], b[1]]),
+                    u16::from_le_bytes([b[2], b[3]]),
+                ])
+            },
+            |b: [u8; 4]| {
+                Some([
+                    u16::from_be_bytes([b[0], b[1]]),
+                    u16::from_be_bytes([b[2], b[3]]),
+                ])
+            },
+            |[time, date]| format_dos_date_time(date, time),
+        ),
+        Row {
+            name: "GUID",
+            values: Values::Endian {
+                little: array(bytes).map(|b| format_guid(b, true)),
+                big: array(bytes).map(|b| format_guid(b, false)),
+            },
+        },
+        endian(
+            "IPv4",
+            bytes,
+            |b: [u8; 4]| Some(Ipv4Addr::from(u32::from_le_bytes(b))),
+            |b: [u8; 4]| Some(Ipv4Addr::from(b)),
+            |address| Some(address.to_string()),
+        ),
+        single(
+            "IPv6",
+            array::<16>(bytes).map(|b| Ipv6Addr::from(b).to_string()),
+        ),
+        single("UTF-8", utf8_char(bytes).map(format_char)),
+        Row {
+            name: "UTF-16",
+            values: Values::Endian {
+                little: utf16_char(bytes, u16::from_le_bytes).map(format_char),
+                big: utf16_char(bytes, u16::from_be_bytes).map(format_char),
+            },
+        },
+    ]
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let fraction = (bits & 0x3ff) as f32;
+    match exponent {
+        0 => sign * fraction * 2f32.powi(-24),
+        0x1f if fraction == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + fraction / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+/// Very large and very small values are shown in scientific notation instead of hundreds of digits
+fn format_float<T: Copy + Into<f64> + std::fmt::Display + std::fmt::LowerExp>(
+    value: T,
+) -> Option<String> {
+    let wide: f64 = value.into();
+    if wide == 0.0 || !wide.is_finite() || (1e-6..1e16).contains(&wide.abs()) {
+        Some(value.to_string())
+    } else {
+        Some(format!("{:e}", value))
+    }
+}
+
+fn format_leb128(value: impl std::fmt::Display, length: usize) -> String {
+    let plural = if length == 1 { "" } else { "s" };
+    format!("{} ({} byte{})", value, length, plural)
+}
+
+/// Returns the value and how many bytes it took
+fn uleb128(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (index, byte) in bytes.iter().take(10).enumerate() {
+        value |= ((byte & 0x7f) as u64) << (7 * index);
+        if byte & 0x80 == 0 {
+            return Some((value, index + 1));
+        }
+    }
+    None
+}
+
+fn sleb128(bytes: &[u8]) -> Option<(i64, usize)> {
+    let mut value = 0i64;
+    for (index, byte) in bytes.iter().take(10).enumerate() {
+        let shift = 7 * index;
+        value |= ((byte & 0x7f) as i64) << shift;
+        if byte & 0x80 == 0 {
+            if shift + 7 < 64 && byte & 0x40 != 0 {
+                value |= -1 << (shift + 7);
+            }
+            return Some((value, index + 1));
+        }
+    }
+    None
+}
+
+fn format_filetime(ticks: u64) -> Option<String> {
+    // Ticks are 100 nanoseconds long
+    let seconds = (ticks / 10_000_000) as i64 - FILETIME_EPOCH_OFFSET;
+    format_unix_time(seconds)
+}
+
+/// Little endian GUIDs store the first three groups as little endian integers, like Windows does
+fn format_guid(bytes: [u8; 16], little_endian: bool) -> String {
+    let mut bytes = bytes;
+    if little_endian {
+        bytes[0..4].reverse();
+        bytes[4..6].reverse();
+        bytes[6..8].reverse();
+    }
+    let hex = |range: std::ops::Range<usize>| {
+        bytes[range]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>()
+    };
+    format!(
+        "{}-{}-{}-{}-{}",
+        hex(0..4),
+        hex(4..6),
+        hex(6..8),
+        hex(8..10),
+        hex(10..16)
+    )
+}
+
+fn utf8_char(bytes: &[u8]) -> Option<char> {
+    let length = match bytes.first()? {
+        0x00..=0x7f => 1,
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return None,
+    };
+    std::str::from_utf8(bytes.get(..length)?)
+        .ok()?
+        .chars()
+        .next()
+}
+
+/// Decodes the first character, the second unit is only needed for surrogate pairs
+fn utf16_char(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> Option<char> {
+    let units = bytes
+        .chunks_exact(2)
+        .take(2)
+        .map(|unit| from_bytes([unit[0], unit[1]]));
+    char::decode_utf16(units).next()?.ok()
+}
+
+fn format_char(c: char) -> String {
+    format!("{:?} U+{:04X}", c, c as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(name: &str, bytes: &[u8]) -> (Option<String>, Option<String>) {
+        let row = interpret(bytes)
+            .into_iter()
+            .find(|row| row.name == name)
+            .unwrap();
+        match row.values {
+            Values::Single(value) => (value, None),
+            Values::Endian { little, big } => (little, big),
+        }
+    }
+
+    #[test]
+    fn uleb128_values() {
+        assert_eq!(uleb128(&[0x00]), Some((0, 1)));
+        assert_eq!(uleb128(&[0x7f, 0xff]), Some((127, 1)));
+        assert_eq!(uleb128(&[0x80, 0x01]), Some((128, 2)));
+        assert_eq!(uleb128(&[0xe5, 0x8e, 0x26]), Some((624_485, 3)));
+        let mut max = [0xff; 10];
+        max[9] = 0x01;
+        assert_eq!(uleb128(&max), Some((u64::MAX, 10)));
+    }
+
+    #[test]
+    fn uleb128_unterminated() {
+        assert_eq!(uleb128(&[]), None);
+        assert_eq!(uleb128(&[0x80, 0x80]), None);
+        // Longer than any 64-bit value
+        assert_eq!(uleb128(&[0x80; 11]), None);
+    }
+
+    #[test]
+    fn sleb128_values() {
+        assert_eq!(sleb128(&[0x00]), Some((0, 1)));
+        assert_eq!(sleb128(&[0x3f]), Some((63, 1)));
+        assert_eq!(sleb128(&[0x40]), Some((-64, 1)));
+        assert_eq!(sleb128(&[0x7f]), Some((-1, 1)));
+        assert_eq!(sleb128(&[0x80, 0x7f]), Some((-128, 2)));
+        assert_eq!(sleb128(&[0xc0, 0xbb, 0x78]), Some((-123_456, 3)));
+        let mut min = [0x80; 10];
+        min[9] = 0x7f;
+        assert_eq!(sleb128(&min), Some((i64::MIN, 10)));
+        let mut max = [0xff; 10];
+        max[9] = 0x00;
+        assert_eq!(sleb128(&max), Some((i64::MAX, 10)));
+        assert_eq!(sleb128(&[0xff]), None);
+    }
+
+    #[test]
+    fn unix_time_32_is_signed() {
+        let (little, big) = value("Unix time (32-bit)", &(-1i32).to_le_bytes());
+        assert_eq!(little.as_deref(), Some("1969-12-31 23:59:59 UTC"));
+        assert_eq!(big.as_deref(), Some("1969-12-31 23:59:59 UTC"));
+        let (_, big) = value("Unix time (32-bit)", &i32::MIN.to_be_bytes());
+        assert_eq!(big.as_deref(), Some("1901-12-13 20:45:52 UTC"));
+        let (little, _) = value("Unix time (32-bit)", &i32::MAX.to_le_bytes());
+        assert_eq!(little.as_deref(), Some("2038-01-19 03:14:07 UTC"));
+    }
+}
@@ -0,0 +1,145 @@
+use std::sync::{mpsc::Sender, Arc};
+
+use egui::{mutex::RwLock, Vec2b};
+use egui_extras::Column;
+
+use super::GaffrieTool;
+use crate::{document::Document, history::History, Event, Selection};
+
+/// How many bytes of an edit are shown in the list
+const PREVIEW_LENGTH: usize = 8;
+
+pub struct EditHistory {
+    history: Arc<RwLock<History>>,
+    events: Sender<Event>,
+}
+
+impl GaffrieTool for EditHistory {
+    fn new(document: &Document) -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            history: document.history.clone(),
+            events: document.events.clone(),
+        }
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let history = self.history.read();
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(!history.edits().is_empty(), egui::Button::new("Undo"))
+                .clicked()
+            {
+                let _ = self.events.send(Event::Undo);
+            }
+            if ui
+                .add_enabled(!history.undone().is_empty(), egui::Button::new("Redo"))
+                .clicked()
+            {
+                let _ = self.events.send(Event::Redo);
+            }
+        });
+        let applied = history.edits().len();
+        let total = applied + history.undone().len();
+        let table = egui_extras::TableBuilder::new(ui)
+            .striped(true)
+            .resizable(false)
+            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+            .column(Column::auto().at_least(32.0))
+            .column(Column::auto().at_least(64.0))
+            .column(Column::remainder().clip(true))
+            .column(Column::auto())
+            .vscroll(true)
+            .auto_shrink(Vec2b::new(false, true));
+        table
+            .header(20.0, |mut header| {
+                header.col(|ui| {
+                    ui.label("#");
+                });
+                header.col(|ui| {
+                    ui.label("Offset");
+                });
+                header.col(|ui| {
+                    ui.label("Change");
+                });
+                header.col(|_| {});
+            })
+            .body(|body| {
+                body.rows(20.0, total, |mut row| {
+                    let index = row.index();
+                    // Undone edits go after the applied ones, the next one to redo first
+                    let edit = match history.edits().get(index) {
+                        Some(edit) => edit,
+                        None => &history.undone()[total - index - 1],
+                    };
+                    let undone = index >= applied;
+                    let text = |text: String| {
+                        let text = egui::RichText::new(text);
+                        if undone {
+                            text.weak()
+                        } else {
+                            text
+                        }
+                    };
+                    row.col(|ui| {
+                        ui.label(text(index.to_string()));
+                    });
+                    row.col(|ui| {
+                        ui.label(text(format!("{:#x}", edit.patch.offset)));
+                    });
+                    row.col(|ui| {
+                        let change = format!(
+                            "{} → {}",
+                            preview(&edit.patch.old),
+                            preview(&edit.patch.new)
+                        );
+                        ui.add(egui::Label::new(text(change)).wrap(false).truncate(true));
+                    });
+                    row.col(|ui| {
+                        if undone {
+                            return;
+                        }
+                        // Later edits may have moved this one or overwritten it
+                        let rebased = history.rebased(index);
+                        if ui.small_button("Jump").clicked() {
+                            let patch = rebased.as_ref().unwrap_or(&edit.patch);
+                            let selection = Selection::new(patch.offset, patch.new.len());
+                            let _ = self.events.send(Event::SelectionChanged(selection));
+                        }
+                        let revert = ui
+                            .add_enabled(rebased.is_some(), egui::Button::new("Revert").small())
+                            .on_disabled_hover_text("Bytes were changed by a later edit");
+                        if revert.clicked() {
+                            if let Some(patch) = rebased {
+                                let _ = self.events.send(Event::Patch(patch.inverse()));
+                            }
+                        }
+                    });
+                })
+            });
+    }
+
+    fn title(&self) -> String {
+        "Edit History".to_string()
+    }
+
+    fn notify(&mut self, _event: Event) {}
+}
+
+fn preview(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        return "∅".to_string();
+    }
+    let mut text = bytes
+        .iter()
+        .take(PREVIEW_LENGTH)
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if bytes.len() > PREVIEW_LENGTH {
+        text.push_str(&format!(" … ({} bytes)", bytes.len()));
+    }
+    text
+}
@@ -1,24 +1,33 @@
-use std::sync::Arc;
+use std::sync::{mpsc::Sender, Arc};
 
 use egui::{mutex::RwLock, Vec2b};
-use egui_plot::{Line, PlotPoints};
-use memmap2::MmapMut;
+use egui_plot::{Line, PlotPoints, VLine};
 
 use super::GaffrieTool;
+use crate::{
+    document::{Document, FileData},
+    Event,
+};
 
 pub struct EntropyPlot {
-    file: Arc<RwLock<MmapMut>>,
+    file: Arc<RwLock<FileData>>,
+    events: Sender<Event>,
     points: Vec<[f64; 2]>,
+    chunk_size: usize,
+    cursor: usize,
 }
 
 impl GaffrieTool for EntropyPlot {
-    fn new(file_lock: Arc<RwLock<MmapMut>>) -> Self
+    fn new(document: &Document) -> Self
     where
         Self: Sized,
     {
         let mut this = Self {
-            file: file_lock,
+            file: document.file.clone(),
+            events: document.events.clone(),
             points: Vec::new(),
+            chunk_size: 0,
+            cursor: 0,
         };
         this.regenerate_plot();
         this
@@ -32,10 +41,22 @@ impl GaffrieTool for EntropyPlot {
             .allow_zoom(false)
             .allow_scroll(false)
             .allow_boxed_zoom(false);
-        plot.show(ui, |plot_ui| {
+        let response = plot.show(ui, |plot_ui| {
             let plot_points: PlotPoints = PlotPoints::new(self.points.clone());
             plot_ui.line(Line::new(plot_points));
+            if self.chunk_size > 0 {
+                let cursor_x = self.cursor as f64 / self.chunk_size as f64;
+                plot_ui.vline(VLine::new(cursor_x).name("cursor"));
+            }
+            plot_ui.pointer_coordinate()
         });
+        if response.response.clicked() && self.chunk_size > 0 {
+            if let Some(point) = response.inner {
+                let chunk = point.x.max(0.0) as usize;
+                let offset = (chunk * self.chunk_size).min(self.file.read().len());
+                let _ = self.events.send(Event::CursorMoved(offset));
+            }
+        }
     }
 
     fn title(&self) -> String {
@@ -45,6 +66,9 @@ impl GaffrieTool for EntropyPlot {
     fn notify(&mut self, event: crate::Event) {
         match event {
             crate::Event::FileChanged => self.regenerate_plot(),
+            crate::Event::CursorMoved(offset) => self.cursor = offset,
+            crate::Event::SelectionChanged(selection) => self.cursor = selection.offset,
+            _ => {}
         }
     }
 }
@@ -54,6 +78,7 @@ impl EntropyPlot {
         let file = self.file.read();
         let number_of_points = 1000;
         let chunk_size = file.len() / number_of_points;
+        self.chunk_size = chunk_size;
         if chunk_size == 0 {
             self.points = Vec::new();
             return;
@@ -67,7 +92,7 @@ impl EntropyPlot {
         self.points = points;
     }
 
-    fn entropy_of_slice(slice: &[u8]) -> f64 {
+    pub fn entropy_of_slice(slice: &[u8]) -> f64 {
         let mut entropy = 0.0;
         let mut counts = [0; 256];
         for b in slice {
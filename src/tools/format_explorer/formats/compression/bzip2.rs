@@ -0,0 +1,349 @@
+//! bzip2 files, streams of Burrows-Wheeler blocks. Blocks aren't byte-aligned and don't say how
+//! long they are, so they are found by the 48-bit magic each one starts with.
+
+use std::io;
+
+use bzip2_rs::DecoderReader;
+
+use super::{super::trailing_data_ui, slice, Extracted, SHOWN_ITEMS};
+use crate::{
+    error::Error,
+    tools::format_explorer::{formats::Confidence, text_field, FileFormatUi, FormatContext},
+};
+
+/// The digits of pi in BCD, in front of each block
+const BLOCK_MAGIC: u64 = 0x3141_5926_5359;
+/// The digits of the square root of pi in BCD, in front of the combined CRC that ends a stream
+const END_MAGIC: u64 = 0x1772_4538_5090;
+const MAGIC_BITS: u64 = 48;
+/// "BZh" and the block size in hundreds of kB
+const HEADER_SIZE: u64 = 4;
+
+/// Streams read at most
+const MAX_STREAMS: usize = 10_000;
+/// Blocks read at most, over all streams
+const MAX_BLOCKS: usize = 1_000_000;
+
+/// Checks the header, and that a block or the end of the stream comes right after it
+pub fn probe(bytes: &[u8]) -> Confidence {
+    if !is_header(bytes) {
+        return Confidence::None;
+    }
+    match bits(bytes, HEADER_SIZE * 8, MAGIC_BITS as u32) {
+        Some(BLOCK_MAGIC | END_MAGIC) => Confidence::High,
+        _ => Confidence::Low,
+    }
+}
+
+fn is_header(bytes: &[u8]) -> bool {
+    matches!(bytes, [b'B', b'Z', b'h', b'1'..=b'9', ..])
+}
+
+/// `count` bits at bit `offset` as a number, most significant bit first
+fn bits(input: &[u8], offset: u64, count: u32) -> Option<u64> {
+    (offset..offset + count as u64).try_fold(0, |value, bit| {
+        let byte = *input.get(usize::try_from(bit / 8).ok()?)?;
+        Some(value << 1 | (byte >> (7 - bit % 8) & 1) as u64)
+    })
+}
+
+/// The first block or end of stream magic at or after bit `from`, with whether it's the end
+fn find_magic(input: &[u8], from: u64) -> Option<(u64, bool)> {
+    let mask = (1 << MAGIC_BITS) - 1;
+    let mut window = 0u64;
+    let first_byte = usize::try_from(from / 8).ok()?;
+    for (index, &byte) in input.iter().enumerate().skip(first_byte) {
+        window = window << 8 | byte as u64;
+        let end = (index as u64 + 1) * 8;
+        // Magics ending in this byte, the one that starts first first
+        for shift in (0..8).rev() {
+            let Some(start) = (end - shift).checked_sub(MAGIC_BITS) else {
+                continue;
+            };
+            if start < from {
+                continue;
+            }
+            match window >> shift & mask {
+                BLOCK_MAGIC => return Some((start, false)),
+                END_MAGIC => return Some((start, true)),
+                _ => {}
+            }
+        }
+    }
+    None
+}
+
+/// A block, as far as its header tells without decoding it
+struct Block {
+    /// Offset in bits from the start of the file
+    bit_offset: u64,
+    /// Up to the next block or the end of the stream
+    bit_length: u64,
+    /// CRC of the uncompressed data of the block
+    crc: u32,
+    /// Set by old versions of bzip2 for data that sorts badly, newer ones only read it
+    randomized: bool,
+}
+
+/// A header, blocks, and the end of stream marker with the combined CRC of the blocks
+pub struct Stream {
+    offset: u64,
+    /// Maximum size of a block before compression, in hundreds of kB
+    level: u8,
+    blocks: Vec<Block>,
+    /// Bit offset of the end of stream marker and the combined CRC after it
+    end_marker: Option<(u64, u32)>,
+    /// The end of the combined CRC and its padding to a byte, or of the file when there is none
+    end: u64,
+    warnings: Vec<String>,
+}
+
+impl Stream {
+    /// Reads the stream at `offset`, with at most `max_blocks` blocks
+    fn parse(input: &[u8], offset: u64, max_blocks: usize) -> Self {
+        let mut stream = Self {
+            offset,
+            level: input[offset as usize + 3] - b'0',
+            blocks: Vec::new(),
+            end_marker: None,
+            end: input.len() as u64,
+            warnings: Vec::new(),
+        };
+        let mut position = (offset + HEADER_SIZE) * 8;
+        loop {
+            let Some((start, is_end)) = find_magic(input, position) else {
+                stream
+                    .warnings
+                    .push("There is no end of stream marker, the stream is cut off".to_string());
+                break;
+            };
+            if stream.blocks.is_empty() && start != position {
+                stream.warnings.push(format!(
+                    "The first block starts {} bits after the header",
+                    start - position
+                ));
+            }
+            if let Some(last) = stream.blocks.last_mut() {
+                last.bit_length = start - last.bit_offset;
+            }
+            let crc = bits(input, start + MAGIC_BITS, 32);
+            if is_end {
+                match crc {
+                    Some(crc) => {
+                        stream.end_marker = Some((start, crc as u32));
+                        stream.end = (start + MAGIC_BITS + 32).div_ceil(8);
+                    }
+                    None => stream
+                        .warnings
+                        .push("The combined CRC is cut off".to_string()),
+                }
+                break;
+            }
+            if stream.blocks.len() >= max_blocks {
+                stream
+                    .warnings
+                    .push(format!("Only the first {} blocks are listed", max_blocks));
+                break;
+            }
+            let Some(crc) = crc else {
+                stream
+                    .warnings
+                    .push("The last block is cut off".to_string());
+                break;
+            };
+            stream.blocks.push(Block {
+                bit_offset: start,
+                bit_length: input.len() as u64 * 8 - start,
+                crc: crc as u32,
+                randomized: bits(input, start + MAGIC_BITS + 32, 1) == Some(1),
+            });
+            position = start + MAGIC_BITS;
+        }
+        if let Some((_, combined_crc)) = stream.end_marker {
+            // Each block's CRC goes into the combined one after rotating it by a bit
+            let computed = stream
+                .blocks
+                .iter()
+                .fold(0u32, |combined, block| combined.rotate_left(1) ^ block.crc);
+            if computed != combined_crc {
+                stream.warnings.push(format!(
+                    "The combined CRC is {:#010x} but the block CRCs combine to {:#010x}",
+                    combined_crc, computed
+                ));
+            }
+        }
+        stream
+    }
+
+    /// Appends the decompressed data to `bytes`, `input` is the whole file
+    fn extract(&self, input: &[u8], bytes: &mut Vec<u8>) -> Result<(), Error> {
+        let data = slice(input, self.offset, self.end - self.offset)
+            .ok_or_else(|| Error::parse("bzip2", "the stream isn't all in the file"))?;
+        io::copy(&mut DecoderReader::new(data), &mut Extracted(bytes))
+            .map(|_| ())
+            .map_err(|err| Error::parse("bzip2", err.to_string()))
+    }
+}
+
+/// Bytes covering the bits, for highlighting them
+fn byte_span(bit_offset: u64, bit_length: u64) -> (u64, u64) {
+    let start = bit_offset / 8;
+    (start, (bit_offset + bit_length).div_ceil(8) - start)
+}
+
+impl FileFormatUi for Stream {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        context
+            .at(self.offset, self.end - self.offset)
+            .collapsing(ui, name, |ui| {
+                for warning in &self.warnings {
+                    ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+                }
+                let level = format!("{} (blocks of up to {}00 kB)", self.level, self.level);
+                context
+                    .at(self.offset, HEADER_SIZE)
+                    .field(ui, "level", &level);
+                if self.end_marker.is_some()
+                    && ui
+                        .button("Open")
+                        .on_hover_text("Decompress the stream into a new document")
+                        .clicked()
+                {
+                    context.open("decompressed", |input| {
+                        let mut bytes = Vec::new();
+                        self.extract(input, &mut bytes).map(|()| bytes)
+                    });
+                }
+                let title = format!("blocks ({})", self.blocks.len());
+                ui.collapsing(title, |ui| {
+                    for (index, block) in self.blocks.iter().take(SHOWN_ITEMS).enumerate() {
+                        let mut text = format!(
+                            "bit {} of {:#x}, {} bits, CRC {:#010x}",
+                            block.bit_offset % 8,
+                            block.bit_offset / 8,
+                            block.bit_length,
+                            block.crc
+                        );
+                        if block.randomized {
+                            text.push_str(", randomized");
+                        }
+                        let (offset, length) = byte_span(block.bit_offset, block.bit_length);
+                        context
+                            .at(offset, length)
+                            .field(ui, &format!("[{}]", index), &text);
+                    }
+                    if self.blocks.len() > SHOWN_ITEMS {
+                        ui.weak(format!("… {} more", self.blocks.len() - SHOWN_ITEMS));
+                    }
+                });
+                if let Some((bit_offset, crc)) = self.end_marker {
+                    let (offset, length) = byte_span(bit_offset, MAGIC_BITS + 32);
+                    let crc = format!("{:#010x}", crc);
+                    context.at(offset, length).field(ui, "combined CRC", &crc);
+                }
+            });
+    }
+}
+
+pub struct Bzip2Format {
+    streams: Vec<Stream>,
+    /// Where the last stream ends
+    end: u64,
+    size: u64,
+}
+
+impl Bzip2Format {
+    pub fn new(input: &[u8]) -> Result<Self, Error> {
+        if !is_header(input) {
+            return Err(Error::parse("bzip2", "there is no stream header"));
+        }
+        let mut streams = Vec::new();
+        let mut block_count = 0;
+        let mut position = 0;
+        // Streams can follow each other, like the ones parallel compressors write
+        while streams.len() < MAX_STREAMS && is_header(&input[position as usize..]) {
+            let stream = Stream::parse(input, position, MAX_BLOCKS - block_count);
+            block_count += stream.blocks.len();
+            position = stream.end;
+            streams.push(stream);
+        }
+        Ok(Self {
+            streams,
+            end: position,
+            size: input.len() as u64,
+        })
+    }
+
+    fn block_count(&self) -> usize {
+        self.streams.iter().map(|stream| stream.blocks.len()).sum()
+    }
+
+    /// Stream and block counts and where the streams end, for expressions
+    pub fn symbols(&self) -> Vec<(String, u64)> {
+        vec![
+            ("streams".to_string(), self.streams.len() as u64),
+            ("blocks".to_string(), self.block_count() as u64),
+            ("stream_end".to_string(), self.end),
+        ]
+    }
+}
+
+impl FileFormatUi for Bzip2Format {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        ui.collapsing(name, |ui| {
+            if self.streams.len() > 1
+                && ui
+                    .button("Open all")
+                    .on_hover_text("Decompress the streams one after the other, like bunzip2")
+                    .clicked()
+            {
+                context.open("decompressed", |input| {
+                    let mut bytes = Vec::new();
+                    for stream in &self.streams {
+                        stream.extract(input, &mut bytes)?;
+                    }
+                    Ok(bytes)
+                });
+            }
+            text_field(ui, "blocks", &self.block_count().to_string());
+            let title = format!("streams ({})", self.streams.len());
+            ui.collapsing(title, |ui| {
+                for (index, stream) in self.streams.iter_mut().take(SHOWN_ITEMS).enumerate() {
+                    let title = format!("[{}] stream", index);
+                    ui.push_id(index, |ui| stream.ui(ui, &title, context));
+                }
+                if self.streams.len() > SHOWN_ITEMS {
+                    ui.weak(format!("… {} more", self.streams.len() - SHOWN_ITEMS));
+                }
+            });
+            trailing_data_ui(ui, context, "stream", self.end, self.size);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_STREAMS: &[u8] = include_bytes!("fixtures/two_streams.bz2");
+
+    #[test]
+    fn extract_streams() {
+        let bzip2 = Bzip2Format::new(TWO_STREAMS).unwrap();
+        assert_eq!(bzip2.streams.len(), 2);
+        let mut bytes = Vec::new();
+        for stream in &bzip2.streams {
+            stream.extract(TWO_STREAMS, &mut bytes).unwrap();
+        }
+        assert_eq!(bytes, b"The first stream\nand the second one\n");
+    }
+
+    #[test]
+    fn extract_broken_stream() {
+        let mut input = TWO_STREAMS.to_vec();
+        // In the block of the first stream, after its header and CRC
+        input[20] ^= 0xff;
+        let bzip2 = Bzip2Format::new(&input).unwrap();
+        assert!(bzip2.streams[0].extract(&input, &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,624 @@
+//! gzip files, one or more members that each wrap a deflate stream between a header and a
+//! trailer with the CRC-32 and size of the uncompressed data
+
+use miniz_oxide::inflate::{
+    core::{decompress, DecompressorOxide},
+    TINFLStatus,
+};
+
+use super::{
+    super::{hex_bytes, trailing_data_ui, unix_time},
+    slice, too_big, MAX_EXTRACTED, SHOWN_ITEMS,
+};
+use crate::{
+    error::Error,
+    tools::format_explorer::{
+        formats::Confidence, text_field, FileFormat, FileFormatUi, FormatContext, Layout,
+    },
+};
+
+const MAGIC: [u8; 2] = [0x1f, 0x8b];
+const DEFLATE: u8 = 8;
+
+const FLAG_HEADER_CRC: u8 = 0x2;
+const FLAG_EXTRA: u8 = 0x4;
+const FLAG_NAME: u8 = 0x8;
+const FLAG_COMMENT: u8 = 0x10;
+/// Bits the spec reserves, gzip refuses members with any of them set
+const RESERVED_FLAGS: u8 = 0xe0;
+
+const HEADER_FLAGS: [(u64, &str); 5] = [
+    (0x1, "TEXT"),
+    (0x2, "HEADER_CRC"),
+    (0x4, "EXTRA"),
+    (0x8, "NAME"),
+    (0x10, "COMMENT"),
+];
+
+/// Size of the output window of the decompressor, as far back as deflate can copy from
+const WINDOW_SIZE: usize = 1 << 15;
+/// Members read at most
+const MAX_MEMBERS: usize = 100_000;
+/// Bytes decompressed at most while parsing. Members have to be decompressed to find where they
+/// end, this keeps parsing a big file again after every edit quick.
+const MAX_INFLATED: u64 = 64 << 20;
+/// Bytes of an extra subfield shown at most
+const SHOWN_SUBFIELD: usize = 64;
+
+/// Checks the magic, the method and that no reserved flag is set
+pub fn probe(bytes: &[u8]) -> Confidence {
+    match bytes {
+        [0x1f, 0x8b, DEFLATE, flags, ..] if flags & RESERVED_FLAGS == 0 => Confidence::High,
+        [0x1f, 0x8b, ..] => Confidence::Low,
+        _ => Confidence::None,
+    }
+}
+
+fn method_name(method: u8) -> String {
+    let name = match method {
+        DEFLATE => "deflate",
+        _ => "unknown",
+    };
+    name.to_string()
+}
+
+/// Modification time, left at zero when there isn't one like for data from a pipe
+fn modified_time(seconds: u32) -> String {
+    match seconds {
+        0 => "not set".to_string(),
+        _ => unix_time(seconds.into()),
+    }
+}
+
+fn extra_flags_name(flags: u8) -> String {
+    let name = match flags {
+        0 => "none",
+        2 => "best compression",
+        4 => "fastest compression",
+        _ => "unknown",
+    };
+    name.to_string()
+}
+
+fn os_name(os: u8) -> String {
+    let name = match os {
+        0 => "FAT",
+        1 => "Amiga",
+        2 => "VMS",
+        3 => "Unix",
+        4 => "VM/CMS",
+        5 => "Atari TOS",
+        6 => "HPFS",
+        7 => "Macintosh",
+        8 => "Z-System",
+        9 => "CP/M",
+        10 => "TOPS-20",
+        11 => "NTFS",
+        12 => "QDOS",
+        13 => "Acorn RISC OS",
+        255 => "unknown",
+        _ => "reserved",
+    };
+    name.to_string()
+}
+
+/// Names and comments are ISO 8859-1, whose bytes are the first 256 code points
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| char::from(b)).collect()
+}
+
+#[derive(FileFormat)]
+#[format(little_endian)]
+pub struct MemberHeader {
+    #[format(hex)]
+    pub magic: u16,
+    #[format(meaning = method_name)]
+    pub method: u8,
+    #[format(flags = HEADER_FLAGS)]
+    pub flags: u8,
+    #[format(meaning = modified_time)]
+    pub modified: u32,
+    #[format(meaning = extra_flags_name)]
+    pub extra_flags: u8,
+    #[format(meaning = os_name)]
+    pub os: u8,
+}
+
+/// Tagged data in the extra field of a header
+struct Subfield {
+    offset: u64,
+    id: [u8; 2],
+    data: Vec<u8>,
+}
+
+impl Subfield {
+    /// Splits the extra field at `offset` into its subfields
+    fn parse_all(extra: &[u8], offset: u64, warnings: &mut Vec<String>) -> Vec<Self> {
+        let mut subfields = Vec::new();
+        let mut position = 0;
+        while let Some(header) = extra.get(position..position + 4) {
+            let length = u16::from_le_bytes([header[2], header[3]]) as usize;
+            let Some(data) = extra.get(position + 4..position + 4 + length) else {
+                break;
+            };
+            subfields.push(Self {
+                offset: offset + position as u64,
+                id: [header[0], header[1]],
+                data: data.to_vec(),
+            });
+            position += 4 + length;
+        }
+        if position != extra.len() {
+            warnings.push(format!(
+                "The extra field has {} bytes that aren't a subfield",
+                extra.len() - position
+            ));
+        }
+        subfields
+    }
+}
+
+/// A NUL-terminated string at `offset`, with its length without the NUL
+fn zero_terminated(input: &[u8], offset: u64) -> Option<(u64, u64, String)> {
+    let tail = input.get(usize::try_from(offset).ok()?..)?;
+    let length = tail.iter().position(|&b| b == 0)?;
+    Some((offset, length as u64, latin1(&tail[..length])))
+}
+
+/// What decompressing a deflate stream finds out
+struct Inflated {
+    /// Bytes of deflate data, up to the end of the last block
+    length: u64,
+    size: u64,
+    crc32: u32,
+    /// Why the stream stops before its last block
+    error: Option<String>,
+}
+
+/// Decompresses the deflate stream at the start of `input`, handing the output to `output` a
+/// window at a time. It stops early when `output` returns an error, without counting the window
+/// it turned down in the size and CRC-32.
+fn inflate(input: &[u8], mut output: impl FnMut(&[u8]) -> Result<(), String>) -> Inflated {
+    let mut decompressor = Box::<DecompressorOxide>::default();
+    let mut window = vec![0; WINDOW_SIZE];
+    let mut hasher = crc32fast::Hasher::new();
+    let (mut length, mut size, mut position) = (0, 0, 0);
+    let error = loop {
+        // Without flags the output wraps around the window and the input is all there is
+        let (status, read, written) = decompress(
+            &mut decompressor,
+            &input[length..],
+            &mut window,
+            position,
+            0,
+        );
+        length += read;
+        let chunk = &window[position..position + written];
+        if let Err(error) = output(chunk) {
+            break Some(error);
+        }
+        hasher.update(chunk);
+        size += written as u64;
+        position = (position + written) & (WINDOW_SIZE - 1);
+        match status {
+            TINFLStatus::Done => break None,
+            TINFLStatus::HasMoreOutput => {}
+            TINFLStatus::NeedsMoreInput | TINFLStatus::FailedCannotMakeProgress => {
+                break Some("the deflate data is cut off".to_string());
+            }
+            _ => break Some("the deflate data is broken".to_string()),
+        }
+    };
+    Inflated {
+        length: length as u64,
+        size,
+        crc32: hasher.finalize(),
+        error,
+    }
+}
+
+/// A header, its deflate stream and the trailer after it
+pub struct Member {
+    offset: u64,
+    header: MemberHeader,
+    /// Where the extra field is and how long it is, with its length in front
+    extra: Option<(u64, u64)>,
+    subfields: Vec<Subfield>,
+    /// Where the file name and comment are, with their length without the NUL
+    name: Option<(u64, u64, String)>,
+    comment: Option<(u64, u64, String)>,
+    header_crc: Option<(u64, u16)>,
+    data_offset: u64,
+    inflated: Inflated,
+    /// Whether decompressing stopped at the parse budget, the size and CRC-32 then only cover the
+    /// start of the data
+    partial: bool,
+    /// CRC-32 and size modulo 2^32 of the uncompressed data, `None` if the file ends first
+    trailer: Option<(u32, u32)>,
+    /// The end of the trailer, or of the file when the deflate data is broken
+    end: u64,
+    warnings: Vec<String>,
+}
+
+impl Member {
+    /// Reads the header at `offset` and decompresses the data after it to find where it ends,
+    /// stopping after `budget` bytes of output
+    fn parse(input: &[u8], offset: u64, budget: u64) -> Result<Self, String> {
+        let cut_off = || format!("the header at {:#x} is cut off", offset);
+        let bytes = slice(input, offset, MemberHeader::SIZE).ok_or_else(cut_off)?;
+        let (_, header) = MemberHeader::parse(bytes, false).map_err(|_| cut_off())?;
+        if header.magic.to_le_bytes() != MAGIC {
+            return Err(format!("there is no member at {:#x}", offset));
+        }
+        if header.method != DEFLATE {
+            return Err(format!(
+                "the member at {:#x} uses method {}, not deflate",
+                offset, header.method
+            ));
+        }
+        let mut warnings = Vec::new();
+        if header.flags & RESERVED_FLAGS != 0 {
+            warnings.push("Reserved flags are set, gzip refuses such members".to_string());
+        }
+        let mut position = offset + MemberHeader::SIZE;
+        let mut subfields = Vec::new();
+        let extra = if header.flags & FLAG_EXTRA != 0 {
+            let length = slice(input, position, 2).ok_or_else(cut_off)?;
+            let length = u16::from_le_bytes([length[0], length[1]]) as u64;
+            let data = slice(input, position + 2, length).ok_or_else(cut_off)?;
+            subfields = Subfield::parse_all(data, position + 2, &mut warnings);
+            let extra = (position, 2 + length);
+            position += 2 + length;
+            Some(extra)
+        } else {
+            None
+        };
+        let mut text = |flag: u8| -> Result<_, String> {
+            if header.flags & flag == 0 {
+                return Ok(None);
+            }
+            let text = zero_terminated(input, position).ok_or_else(cut_off)?;
+            position += text.1 + 1;
+            Ok(Some(text))
+        };
+        let name = text(FLAG_NAME)?;
+        let comment = text(FLAG_COMMENT)?;
+        let header_crc = if header.flags & FLAG_HEADER_CRC != 0 {
+            let crc = slice(input, position, 2).ok_or_else(cut_off)?;
+            let crc = u16::from_le_bytes([crc[0], crc[1]]);
+            // The low half of the CRC-32 of the header before it
+            let computed = crc32fast::hash(slice(input, offset, position - offset).unwrap()) as u16;
+            if crc != computed {
+                warnings.push(format!(
+                    "The header CRC doesn't match, the header hashes to {:#06x}",
+                    computed
+                ));
+            }
+            let header_crc = (position, crc);
+            position += 2;
+            Some(header_crc)
+        } else {
+            None
+        };
+
+        let data_offset = position;
+        let data = slice(input, data_offset, input.len() as u64 - data_offset).unwrap();
+        let (mut left, mut partial) = (budget, false);
+        let inflated = inflate(data, |chunk| match left.checked_sub(chunk.len() as u64) {
+            Some(rest) => {
+                left = rest;
+                Ok(())
+            }
+            None => {
+                partial = true;
+                Err(String::new())
+            }
+        });
+        let trailer_offset = data_offset + inflated.length;
+        let mut trailer = None;
+        let end = if partial {
+            warnings.push(format!(
+                "Parsing decompresses at most {} MiB of the file, the size and CRC-32 only cover \
+                 the data up to there",
+                MAX_INFLATED >> 20
+            ));
+            input.len() as u64
+        } else if let Some(error) = &inflated.error {
+            warnings.push(format!("Decompressing stops, {}", error));
+            input.len() as u64
+        } else if let Some(bytes) = slice(input, trailer_offset, 8) {
+            let crc32 = u32::from_le_bytes(bytes[..4].try_into().unwrap());
+            let size = u32::from_le_bytes(bytes[4..].try_into().unwrap());
+            if crc32 != inflated.crc32 {
+                warnings.push(format!(
+                    "The CRC-32 is {:#010x} but the trailer says {:#010x}",
+                    inflated.crc32, crc32
+                ));
+            }
+            if size != inflated.size as u32 {
+                warnings.push(format!(
+                    "The data decompresses to {} bytes but the trailer says {} modulo 2^32",
+                    inflated.size, size
+                ));
+            }
+            trailer = Some((crc32, size));
+            trailer_offset + 8
+        } else {
+            warnings.push("The trailer is cut off".to_string());
+            input.len() as u64
+        };
+        Ok(Self {
+            offset,
+            header,
+            extra,
+            subfields,
+            name,
+            comment,
+            header_crc,
+            data_offset,
+            inflated,
+            partial,
+            trailer,
+            end,
+            warnings,
+        })
+    }
+
+    /// The file name without its directories, if the header has one
+    fn file_name(&self) -> Option<&str> {
+        let (_, _, name) = self.name.as_ref()?;
+        name.rsplit(['/', '\\'])
+            .next()
+            .filter(|name| !name.is_empty())
+    }
+
+    /// Appends the decompressed data to `bytes`, `input` is the whole file
+    fn extract(&self, input: &[u8], bytes: &mut Vec<u8>) -> Result<(), Error> {
+        // Where the data of a partly decompressed member ends is only known after this
+        let length = match self.partial {
+            true => (input.len() as u64).saturating_sub(self.data_offset),
+            false => self.inflated.length,
+        };
+        let data = slice(input, self.data_offset, length)
+            .ok_or_else(|| Error::parse("gzip", "the data isn't all in the file"))?;
+        let inflated = inflate(data, |chunk| {
+            if bytes.len() + chunk.len() > MAX_EXTRACTED {
+                return Err(too_big());
+            }
+            bytes.extend_from_slice(chunk);
+            Ok(())
+        });
+        if let Some(error) = inflated.error {
+            return Err(Error::parse("gzip", error));
+        }
+        match self.trailer {
+            Some((crc32, _)) if crc32 != inflated.crc32 => Err(Error::parse(
+                "gzip",
+                format!(
+                    "the CRC-32 is {:#010x} but the trailer says {:#010x}",
+                    inflated.crc32, crc32
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FileFormatUi for Member {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        context
+            .at(self.offset, self.end - self.offset)
+            .collapsing(ui, name, |ui| {
+                for warning in &self.warnings {
+                    ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+                }
+                let header = context.at(self.offset, MemberHeader::SIZE);
+                self.header.ui(ui, "header", &header);
+                if let Some((offset, length)) = self.extra {
+                    let title = format!("extra ({})", self.subfields.len());
+                    context.at(offset, length).collapsing(ui, &title, |ui| {
+                        for subfield in &self.subfields {
+                            let shown = &subfield.data[..subfield.data.len().min(SHOWN_SUBFIELD)];
+                            let mut text = hex_bytes(shown);
+                            if shown.len() < subfield.data.len() {
+                                text.push_str(" …");
+                            }
+                            let length = 4 + subfield.data.len() as u64;
+                            let id = subfield.id.escape_ascii().to_string();
+                            context.at(subfield.offset, length).field(ui, &id, &text);
+                        }
+                    });
+                }
+                if let Some((offset, length, name)) = &self.name {
+                    context
+                        .at(*offset, length + 1)
+                        .field(ui, "name", &format!("{:?}", name));
+                }
+                if let Some((offset, length, comment)) = &self.comment {
+                    context
+                        .at(*offset, length + 1)
+                        .field(ui, "comment", &format!("{:?}", comment));
+                }
+                if let Some((offset, crc)) = self.header_crc {
+                    let crc = format!("{:#06x}", crc);
+                    context.at(offset, 2).field(ui, "header CRC-16", &crc);
+                }
+                ui.horizontal(|ui| {
+                    let length = self.inflated.length;
+                    context.range_link(ui, "compressed data", self.data_offset, length);
+                    if (self.inflated.error.is_none() || self.partial)
+                        && ui
+                            .button("Open")
+                            .on_hover_text("Decompress the member into a new document")
+                            .clicked()
+                    {
+                        let name = self.file_name().unwrap_or("decompressed");
+                        context.open(name, |input| {
+                            let mut bytes = Vec::new();
+                            self.extract(input, &mut bytes).map(|()| bytes)
+                        });
+                    }
+                });
+                let mut size = context.number(self.inflated.size);
+                if self.partial {
+                    size.push_str(" or more");
+                }
+                text_field(ui, "uncompressed size", &size);
+                let crc32 = format!("{:#010x}", self.inflated.crc32);
+                text_field(ui, "uncompressed CRC-32", &crc32);
+                if let Some((crc32, size)) = self.trailer {
+                    let offset = self.data_offset + self.inflated.length;
+                    context.at(offset, 8).collapsing(ui, "trailer", |ui| {
+                        let crc32 = format!("{:#010x}", crc32);
+                        context.at(offset, 4).field(ui, "CRC-32", &crc32);
+                        let size = context.number(size.into());
+                        context.at(offset + 4, 4).field(ui, "size", &size);
+                    });
+                }
+            });
+    }
+}
+
+pub struct GzipFormat {
+    members: Vec<Member>,
+    /// Where the last member ends
+    end: u64,
+    size: u64,
+    warnings: Vec<String>,
+}
+
+impl GzipFormat {
+    pub fn new(input: &[u8]) -> Result<Self, Error> {
+        Self::parse(input, MAX_INFLATED)
+    }
+
+    /// Reads the members, decompressing at most `budget` bytes across all of them
+    fn parse(input: &[u8], budget: u64) -> Result<Self, Error> {
+        let first =
+            Member::parse(input, 0, budget).map_err(|message| Error::parse("gzip", message))?;
+        let mut left = budget.saturating_sub(first.inflated.size);
+        let mut position = first.end;
+        let mut members = vec![first];
+        let mut warnings = Vec::new();
+        // Members can follow each other, gzip decompresses them one after the other
+        while members.len() < MAX_MEMBERS
+            && slice(input, position, 2) == Some(&MAGIC[..])
+            && members
+                .last()
+                .is_some_and(|member| member.trailer.is_some())
+        {
+            match Member::parse(input, position, left) {
+                Ok(member) => {
+                    left = left.saturating_sub(member.inflated.size);
+                    position = member.end;
+                    members.push(member);
+                }
+                Err(message) => {
+                    warnings.push(format!("The member after the last one is bad, {}", message));
+                    break;
+                }
+            }
+        }
+        Ok(Self {
+            members,
+            end: position,
+            size: input.len() as u64,
+            warnings,
+        })
+    }
+
+    fn uncompressed_size(&self) -> u64 {
+        self.members.iter().map(|member| member.inflated.size).sum()
+    }
+
+    /// Member count, sizes and where the data starts and ends, for expressions
+    pub fn symbols(&self) -> Vec<(String, u64)> {
+        vec![
+            ("members".to_string(), self.members.len() as u64),
+            ("compressed_data".to_string(), self.members[0].data_offset),
+            ("uncompressed_size".to_string(), self.uncompressed_size()),
+            ("stream_end".to_string(), self.end),
+        ]
+    }
+}
+
+impl FileFormatUi for GzipFormat {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        ui.collapsing(name, |ui| {
+            for warning in &self.warnings {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+            if self.members.len() > 1 {
+                ui.horizontal(|ui| {
+                    let size = context.number(self.uncompressed_size());
+                    text_field(ui, "uncompressed size", &size);
+                    if ui
+                        .button("Open all")
+                        .on_hover_text("Decompress the members one after the other, like gunzip")
+                        .clicked()
+                    {
+                        let name = self.members[0].file_name().unwrap_or("decompressed");
+                        context.open(name, |input| {
+                            let mut bytes = Vec::new();
+                            for member in &self.members {
+                                member.extract(input, &mut bytes)?;
+                            }
+                            Ok(bytes)
+                        });
+                    }
+                });
+            }
+            let title = format!("members ({})", self.members.len());
+            ui.collapsing(title, |ui| {
+                for (index, member) in self.members.iter_mut().take(SHOWN_ITEMS).enumerate() {
+                    let title = format!("[{}] {}", index, member.file_name().unwrap_or("member"));
+                    ui.push_id(index, |ui| member.ui(ui, &title, context));
+                }
+                if self.members.len() > SHOWN_ITEMS {
+                    ui.weak(format!("… {} more", self.members.len() - SHOWN_ITEMS));
+                }
+            });
+            trailing_data_ui(ui, context, "stream", self.end, self.size);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_MEMBERS: &[u8] = include_bytes!("fixtures/two_members.gz");
+
+    #[test]
+    fn extract_members() {
+        let gzip = GzipFormat::new(TWO_MEMBERS).unwrap();
+        assert_eq!(gzip.members.len(), 2);
+        assert_eq!(gzip.end, TWO_MEMBERS.len() as u64);
+        let mut bytes = Vec::new();
+        for member in &gzip.members {
+            assert!(member.warnings.is_empty());
+            member.extract(TWO_MEMBERS, &mut bytes).unwrap();
+        }
+        assert_eq!(bytes, b"The first member\nand the second one\n");
+    }
+
+    #[test]
+    fn stop_at_the_budget() {
+        // Enough for the first member but not the second
+        let gzip = GzipFormat::parse(TWO_MEMBERS, 20).unwrap();
+        assert_eq!(gzip.members.len(), 2);
+        let [first, second] = &gzip.members[..] else {
+            unreachable!()
+        };
+        assert!(!first.partial && first.trailer.is_some());
+        assert!(second.partial && second.trailer.is_none());
+        assert_eq!(second.warnings.len(), 1);
+        // The window that goes over the budget isn't counted
+        assert!(first.inflated.size + second.inflated.size <= 20);
+        assert_eq!(second.inflated.crc32, crc32fast::hash(&[]));
+        // Opening it still decompresses all of it
+        let mut bytes = Vec::new();
+        second.extract(TWO_MEMBERS, &mut bytes).unwrap();
+        assert_eq!(bytes, b"and the second one\n");
+    }
+}
@@ -0,0 +1,44 @@
+//! Compressed streams, parsed down to their members, frames and blocks. Only gzip is
+//! decompressed while parsing, the others are listed as they are in the file and decompressed
+//! when opened.
+
+use std::io::{self, Write};
+
+pub mod bzip2;
+pub mod gzip;
+pub mod xz;
+pub mod zstd;
+
+/// Members, frames and blocks shown at most, more would make the explorer crawl
+const SHOWN_ITEMS: usize = 10_000;
+/// Data decompresses to at most this much when opened, more is more likely a bomb than
+/// something to look at
+const MAX_EXTRACTED: usize = 1 << 30;
+
+fn too_big() -> String {
+    format!("it decompresses to more than {} MiB", MAX_EXTRACTED >> 20)
+}
+
+/// Collects what a decoder writes, failing once there is more than `MAX_EXTRACTED` bytes of it
+struct Extracted<'a>(&'a mut Vec<u8>);
+
+impl Write for Extracted<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.0.len() + buf.len() > MAX_EXTRACTED {
+            return Err(io::Error::other(too_big()));
+        }
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// `length` bytes at `offset`, `None` if they aren't all in the file
+fn slice(input: &[u8], offset: u64, length: u64) -> Option<&[u8]> {
+    let start = usize::try_from(offset).ok()?;
+    let end = start.checked_add(usize::try_from(length).ok()?)?;
+    input.get(start..end)
+}
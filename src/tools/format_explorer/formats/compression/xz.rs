@@ -0,0 +1,629 @@
+//! xz files, streams of blocks with an index of their sizes between the last block and the
+//! stream footer
+
+use super::{
+    super::{hex_bytes, trailing_data_ui},
+    slice, Extracted, SHOWN_ITEMS,
+};
+use crate::{
+    error::Error,
+    tools::format_explorer::{
+        formats::Confidence, text_field, FileFormat, FileFormatUi, FormatContext, Layout,
+    },
+};
+
+const HEADER_MAGIC: [u8; 6] = [0xfd, b'7', b'z', b'X', b'Z', 0];
+const FOOTER_MAGIC: [u8; 2] = *b"YZ";
+/// Streams are made of 4-byte units, padding included
+const ALIGNMENT: u64 = 4;
+/// The smallest index, for a stream without blocks
+const MIN_INDEX_SIZE: u64 = 8;
+
+/// Streams read at most
+const MAX_STREAMS: usize = 10_000;
+/// Blocks read at most, over all streams
+const MAX_BLOCKS: usize = 1_000_000;
+
+pub fn probe(bytes: &[u8]) -> Confidence {
+    if bytes.starts_with(&HEADER_MAGIC) {
+        Confidence::High
+    } else {
+        Confidence::None
+    }
+}
+
+/// The type of check after the data of each block, from the stream flags
+fn check_name(flags: u16) -> String {
+    let name = match flags {
+        0 => "none",
+        1 => "CRC-32",
+        4 => "CRC-64",
+        10 => "SHA-256",
+        2 | 3 | 5..=9 | 11..=15 => "reserved",
+        _ => "reserved bits set",
+    };
+    name.to_string()
+}
+
+/// Size of the check after the data of each block, which grows with the type
+fn check_size(flags: u16) -> u64 {
+    match flags & 0xf {
+        0 => 0,
+        check => 4 << ((check - 1) / 3),
+    }
+}
+
+/// The footer stores the size of the index in 4-byte units, minus one
+fn index_size(backward_size: u32) -> String {
+    format!("index of {} bytes", (backward_size as u64 + 1) * ALIGNMENT)
+}
+
+fn ascii(bytes: &[u8]) -> String {
+    bytes.escape_ascii().to_string()
+}
+
+fn filter_name(id: u64) -> &'static str {
+    match id {
+        0x03 => "delta",
+        0x04 => "x86 BCJ",
+        0x05 => "PowerPC BCJ",
+        0x06 => "IA-64 BCJ",
+        0x07 => "ARM BCJ",
+        0x08 => "ARM Thumb BCJ",
+        0x09 => "SPARC BCJ",
+        0x0a => "ARM64 BCJ",
+        0x0b => "RISC-V BCJ",
+        0x21 => "LZMA2",
+        _ => "unknown",
+    }
+}
+
+/// Multibyte integers, seven bits a byte with the low bits first. Returns the value and the
+/// number of bytes, `None` if it's cut off, too long or has a needless zero byte.
+fn varint(input: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0;
+    for (index, &b) in input.iter().take(9).enumerate() {
+        value |= ((b & 0x7f) as u64) << (7 * index);
+        if b & 0x80 == 0 {
+            return (b != 0 || index == 0).then_some((value, index + 1));
+        }
+    }
+    None
+}
+
+/// Reads a multibyte integer at `position` and moves past it
+fn next_varint(bytes: &[u8], position: &mut usize) -> Option<u64> {
+    let (value, length) = varint(bytes.get(*position..)?)?;
+    *position += length;
+    Some(value)
+}
+
+fn align(value: u64) -> u64 {
+    value.div_ceil(ALIGNMENT).saturating_mul(ALIGNMENT)
+}
+
+#[derive(FileFormat)]
+#[format(little_endian)]
+pub struct StreamHeader {
+    #[format(display = hex_bytes)]
+    pub magic: [u8; 6],
+    #[format(big_endian, meaning = check_name)]
+    pub flags: u16,
+    #[format(hex)]
+    pub crc32: u32,
+}
+
+#[derive(FileFormat)]
+#[format(little_endian)]
+pub struct StreamFooter {
+    #[format(hex)]
+    pub crc32: u32,
+    #[format(meaning = index_size)]
+    pub backward_size: u32,
+    #[format(big_endian, meaning = check_name)]
+    pub flags: u16,
+    #[format(display = ascii)]
+    pub magic: [u8; 2],
+}
+
+impl StreamFooter {
+    fn index_size(&self) -> u64 {
+        (self.backward_size as u64 + 1) * ALIGNMENT
+    }
+}
+
+/// Reads the footer at `offset`, `None` if its magic or CRC is wrong
+fn footer_at(input: &[u8], offset: u64) -> Option<StreamFooter> {
+    let bytes = slice(input, offset, StreamFooter::SIZE)?;
+    let (_, footer) = StreamFooter::parse(bytes, false).ok()?;
+    (footer.magic == FOOTER_MAGIC && footer.crc32 == crc32fast::hash(&bytes[4..10]))
+        .then_some(footer)
+}
+
+/// Reads the index at `offset`, its records are the unpadded and uncompressed sizes of the
+/// blocks
+fn index_at(input: &[u8], offset: u64, size: u64) -> Result<Vec<(u64, u64)>, String> {
+    let bytes = slice(input, offset, size).ok_or("the index is cut off")?;
+    let (body, crc32) = bytes.split_at(bytes.len() - 4);
+    if u32::from_le_bytes(crc32.try_into().unwrap()) != crc32fast::hash(body) {
+        return Err("the CRC of the index doesn't match".to_string());
+    }
+    let malformed = || "the index is malformed".to_string();
+    if body.first() != Some(&0) {
+        return Err(malformed());
+    }
+    let mut position = 1;
+    let count = next_varint(body, &mut position).ok_or_else(malformed)?;
+    // Each record takes at least two bytes
+    if count > body.len() as u64 / 2 {
+        return Err(malformed());
+    }
+    let records = (0..count)
+        .map(|_| {
+            let unpadded_size = next_varint(body, &mut position)?;
+            Some((unpadded_size, next_varint(body, &mut position)?))
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(malformed)?;
+    if body[position..].iter().any(|&b| b != 0) || body.len() as u64 != align(position as u64) {
+        return Err(malformed());
+    }
+    Ok(records)
+}
+
+struct Filter {
+    id: u64,
+    properties: Vec<u8>,
+}
+
+/// A block header, its compressed data and the check of the uncompressed data
+pub struct Block {
+    offset: u64,
+    header_size: u64,
+    filters: Vec<Filter>,
+    /// Header, data and check without the padding after them, from the index or the header
+    unpadded_size: u64,
+    uncompressed_size: Option<u64>,
+    check: Vec<u8>,
+    warnings: Vec<String>,
+}
+
+impl Block {
+    /// Reads the header at `offset`. Sizes in the header are optional, the index has them all.
+    fn parse(
+        input: &[u8],
+        offset: u64,
+        check_size: u64,
+        record: Option<(u64, u64)>,
+    ) -> Result<Self, String> {
+        let cut_off = || format!("the block header at {:#x} is cut off", offset);
+        let size_byte = *slice(input, offset, 1)
+            .ok_or_else(cut_off)?
+            .first()
+            .unwrap();
+        if size_byte == 0 {
+            return Err(format!("there is no block header at {:#x}", offset));
+        }
+        let header_size = (size_byte as u64 + 1) * ALIGNMENT;
+        let bytes = slice(input, offset, header_size).ok_or_else(cut_off)?;
+        let (body, crc32) = bytes.split_at(bytes.len() - 4);
+        let mut warnings = Vec::new();
+        if u32::from_le_bytes(crc32.try_into().unwrap()) != crc32fast::hash(body) {
+            warnings.push("The CRC of the header doesn't match".to_string());
+        }
+        let malformed = || format!("the block header at {:#x} is malformed", offset);
+        let flags = body[1];
+        if flags & 0x3c != 0 {
+            warnings.push("Reserved header flags are set".to_string());
+        }
+        let mut position = 2;
+        let next = |position: &mut usize| next_varint(body, position).ok_or_else(malformed);
+        let compressed_size = match flags & 0x40 {
+            0 => None,
+            _ => Some(next(&mut position)?),
+        };
+        let header_uncompressed_size = match flags & 0x80 {
+            0 => None,
+            _ => Some(next(&mut position)?),
+        };
+        let mut filters = Vec::new();
+        for _ in 0..(flags & 0x3) + 1 {
+            let id = next(&mut position)?;
+            let length = next(&mut position)? as usize;
+            let properties = body
+                .get(position..position + length)
+                .ok_or_else(malformed)?
+                .to_vec();
+            position += length;
+            filters.push(Filter { id, properties });
+        }
+        if body[position..].iter().any(|&b| b != 0) {
+            warnings.push("The padding of the header isn't zeros".to_string());
+        }
+
+        let header_unpadded_size =
+            compressed_size.map(|size| header_size.saturating_add(size).saturating_add(check_size));
+        let (unpadded_size, uncompressed_size) = match record {
+            Some((unpadded_size, uncompressed_size)) => {
+                if header_unpadded_size.is_some_and(|size| size != unpadded_size) {
+                    warnings.push("The compressed size doesn't match the index".to_string());
+                }
+                if header_uncompressed_size.is_some_and(|size| size != uncompressed_size) {
+                    warnings.push("The uncompressed size doesn't match the index".to_string());
+                }
+                (unpadded_size, Some(uncompressed_size))
+            }
+            None => (
+                header_unpadded_size.ok_or_else(|| {
+                    format!("the block at {:#x} doesn't say how big it is", offset)
+                })?,
+                header_uncompressed_size,
+            ),
+        };
+        if unpadded_size < header_size + check_size {
+            return Err(format!(
+                "the block at {:#x} is smaller than its header",
+                offset
+            ));
+        }
+        let check = slice(input, offset + unpadded_size - check_size, check_size)
+            .map(<[u8]>::to_vec)
+            .unwrap_or_default();
+        if check.is_empty() && check_size > 0 {
+            warnings.push("The block is cut off".to_string());
+        }
+        Ok(Self {
+            offset,
+            header_size,
+            filters,
+            unpadded_size,
+            uncompressed_size,
+            check,
+            warnings,
+        })
+    }
+
+    fn filter_names(&self) -> String {
+        self.filters
+            .iter()
+            .map(|filter| {
+                let name = filter_name(filter.id);
+                if filter.properties.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{} ({})", name, hex_bytes(&filter.properties))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl FileFormatUi for Block {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let length = align(self.unpadded_size);
+        context.at(self.offset, length).collapsing(ui, name, |ui| {
+            for warning in &self.warnings {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+            context
+                .at(self.offset, self.header_size)
+                .field(ui, "filters", &self.filter_names());
+            let check_size = self.check.len() as u64;
+            let data_size = self.unpadded_size - self.header_size - check_size;
+            let data_offset = self.offset + self.header_size;
+            context.range_link(ui, "compressed data", data_offset, data_size);
+            if let Some(size) = self.uncompressed_size {
+                text_field(ui, "uncompressed size", &context.number(size));
+            }
+            if check_size > 0 {
+                context.at(data_offset + data_size, check_size).field(
+                    ui,
+                    "check",
+                    &hex_bytes(&self.check),
+                );
+            }
+        });
+    }
+}
+
+/// A header, blocks, an index and a footer
+pub struct Stream {
+    offset: u64,
+    header: StreamHeader,
+    blocks: Vec<Block>,
+    /// Where the index is and how long it is
+    index: Option<(u64, u64)>,
+    footer: Option<(u64, StreamFooter)>,
+    /// The end of the footer, or of the file when there is none
+    end: u64,
+    warnings: Vec<String>,
+}
+
+impl Stream {
+    /// Reads the stream at `offset`, with at most `max_blocks` blocks
+    fn parse(input: &[u8], offset: u64, max_blocks: usize) -> Result<Self, String> {
+        let bytes = slice(input, offset, StreamHeader::SIZE)
+            .ok_or_else(|| format!("the stream header at {:#x} is cut off", offset))?;
+        let (_, header) = StreamHeader::parse(bytes, false).unwrap();
+        if header.magic != HEADER_MAGIC {
+            return Err(format!("there is no stream at {:#x}", offset));
+        }
+        let mut warnings = Vec::new();
+        if header.crc32 != crc32fast::hash(&bytes[6..8]) {
+            warnings.push("The CRC of the stream header doesn't match".to_string());
+        }
+        let mut stream = Self {
+            offset,
+            header,
+            blocks: Vec::new(),
+            index: None,
+            footer: None,
+            end: input.len() as u64,
+            warnings,
+        };
+        let first_block = offset + StreamHeader::SIZE;
+        let check_size = check_size(stream.header.flags);
+
+        let Some((footer_offset, footer)) = stream.find_footer(input) else {
+            stream
+                .warnings
+                .push("There is no stream footer, the stream is cut off".to_string());
+            // Blocks can still be found while their headers have their size
+            let mut position = first_block;
+            while stream.blocks.len() < max_blocks {
+                let Ok(block) = Block::parse(input, position, check_size, None) else {
+                    break;
+                };
+                position = position.saturating_add(align(block.unpadded_size));
+                stream.blocks.push(block);
+            }
+            return Ok(stream);
+        };
+        let index_offset = footer_offset - footer.index_size();
+        stream.index = Some((index_offset, footer.index_size()));
+        stream.end = footer_offset + StreamFooter::SIZE;
+        stream.footer = Some((footer_offset, footer));
+        let records = match index_at(input, index_offset, footer_offset - index_offset) {
+            Ok(records) => records,
+            Err(message) => {
+                stream
+                    .warnings
+                    .push(format!("The blocks can't be listed, {}", message));
+                return Ok(stream);
+            }
+        };
+        let mut position = first_block;
+        for &record in records.iter().take(max_blocks) {
+            match Block::parse(input, position, check_size, Some(record)) {
+                Ok(block) => {
+                    position = position.saturating_add(align(block.unpadded_size));
+                    stream.blocks.push(block);
+                }
+                Err(message) => {
+                    stream
+                        .warnings
+                        .push(format!("Listing the blocks stops, {}", message));
+                    return Ok(stream);
+                }
+            }
+        }
+        if records.len() > max_blocks {
+            stream.warnings.push(format!(
+                "Only {} of the {} blocks are listed",
+                max_blocks,
+                records.len()
+            ));
+        } else if position != index_offset {
+            stream.warnings.push(format!(
+                "The blocks end at {:#x} but the index is at {:#x}",
+                position, index_offset
+            ));
+        }
+        Ok(stream)
+    }
+
+    /// The first footer after the header whose flags match the header and whose index size
+    /// leaves room for the header. The blocks don't say how big they are, so the footer can't
+    /// be found any other way.
+    fn find_footer(&self, input: &[u8]) -> Option<(u64, StreamFooter)> {
+        let first = self.offset + StreamHeader::SIZE + MIN_INDEX_SIZE;
+        let last = (input.len() as u64).checked_sub(StreamFooter::SIZE)?;
+        (first..=last)
+            .step_by(ALIGNMENT as usize)
+            .filter(|&offset| {
+                let magic_offset = (offset + StreamFooter::SIZE) as usize - FOOTER_MAGIC.len();
+                input[magic_offset..].starts_with(&FOOTER_MAGIC)
+            })
+            .filter_map(|offset| Some((offset, footer_at(input, offset)?)))
+            .find(|(offset, footer)| {
+                footer.flags == self.header.flags
+                    && offset - self.offset - StreamHeader::SIZE >= footer.index_size()
+            })
+    }
+
+    /// Appends the decompressed data to `bytes`, `input` is the whole file
+    fn extract(&self, input: &[u8], bytes: &mut Vec<u8>) -> Result<(), Error> {
+        let mut data = slice(input, self.offset, self.end - self.offset)
+            .ok_or_else(|| Error::parse("xz", "the stream isn't all in the file"))?;
+        lzma_rs::xz_decompress(&mut data, &mut Extracted(bytes)).map_err(|err| match err {
+            lzma_rs::error::Error::IoError(err) => Error::parse("xz", err.to_string()),
+            err => Error::parse("xz", err.to_string()),
+        })
+    }
+
+    fn uncompressed_size(&self) -> u64 {
+        self.blocks
+            .iter()
+            .filter_map(|block| block.uncompressed_size)
+            .sum()
+    }
+}
+
+impl FileFormatUi for Stream {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        context
+            .at(self.offset, self.end - self.offset)
+            .collapsing(ui, name, |ui| {
+                for warning in &self.warnings {
+                    ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+                }
+                let header = context.at(self.offset, StreamHeader::SIZE);
+                self.header.ui(ui, "header", &header);
+                ui.horizontal(|ui| {
+                    let size = context.number(self.uncompressed_size());
+                    text_field(ui, "uncompressed size", &size);
+                    if self.footer.is_some()
+                        && ui
+                            .button("Open")
+                            .on_hover_text("Decompress the stream into a new document")
+                            .clicked()
+                    {
+                        context.open("decompressed", |input| {
+                            let mut bytes = Vec::new();
+                            self.extract(input, &mut bytes).map(|()| bytes)
+                        });
+                    }
+                });
+                let title = format!("blocks ({})", self.blocks.len());
+                ui.collapsing(title, |ui| {
+                    for (index, block) in self.blocks.iter_mut().take(SHOWN_ITEMS).enumerate() {
+                        let title = format!("[{}] block", index);
+                        ui.push_id(index, |ui| block.ui(ui, &title, context));
+                    }
+                    if self.blocks.len() > SHOWN_ITEMS {
+                        ui.weak(format!("… {} more", self.blocks.len() - SHOWN_ITEMS));
+                    }
+                });
+                if let Some((offset, size)) = self.index {
+                    context.range_link(ui, "index", offset, size);
+                }
+                if let Some((offset, footer)) = &mut self.footer {
+                    let record = context.at(*offset, StreamFooter::SIZE);
+                    footer.ui(ui, "footer", &record);
+                }
+            });
+    }
+}
+
+pub struct XzFormat {
+    streams: Vec<Stream>,
+    /// Where the last stream ends, with the padding after it
+    end: u64,
+    size: u64,
+    warnings: Vec<String>,
+}
+
+impl XzFormat {
+    pub fn new(input: &[u8]) -> Result<Self, Error> {
+        let mut streams = Vec::new();
+        let mut warnings = Vec::new();
+        let mut block_count = 0;
+        let mut position = 0;
+        // Streams can follow each other, with zeros in multiples of 4 bytes between them
+        while streams.len() < MAX_STREAMS {
+            match Stream::parse(input, position, MAX_BLOCKS - block_count) {
+                Ok(stream) => {
+                    block_count += stream.blocks.len();
+                    position = stream.end;
+                    streams.push(stream);
+                }
+                Err(message) if streams.is_empty() => return Err(Error::parse("xz", message)),
+                Err(message) => {
+                    warnings.push(format!("The stream after the first is bad, {}", message));
+                    break;
+                }
+            }
+            while slice(input, position, ALIGNMENT) == Some(&[0; ALIGNMENT as usize][..]) {
+                position += ALIGNMENT;
+            }
+            if slice(input, position, HEADER_MAGIC.len() as u64) != Some(&HEADER_MAGIC[..]) {
+                break;
+            }
+        }
+        Ok(Self {
+            streams,
+            end: position,
+            size: input.len() as u64,
+            warnings,
+        })
+    }
+
+    fn block_count(&self) -> usize {
+        self.streams.iter().map(|stream| stream.blocks.len()).sum()
+    }
+
+    /// Stream and block counts, the uncompressed size and where the streams end, for
+    /// expressions
+    pub fn symbols(&self) -> Vec<(String, u64)> {
+        let uncompressed_size = self.streams.iter().map(Stream::uncompressed_size).sum();
+        vec![
+            ("streams".to_string(), self.streams.len() as u64),
+            ("blocks".to_string(), self.block_count() as u64),
+            ("uncompressed_size".to_string(), uncompressed_size),
+            ("stream_end".to_string(), self.end),
+        ]
+    }
+}
+
+impl FileFormatUi for XzFormat {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        ui.collapsing(name, |ui| {
+            for warning in &self.warnings {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+            if self.streams.len() > 1
+                && ui
+                    .button("Open all")
+                    .on_hover_text("Decompress the streams one after the other, like unxz")
+                    .clicked()
+            {
+                context.open("decompressed", |input| {
+                    let mut bytes = Vec::new();
+                    for stream in &self.streams {
+                        stream.extract(input, &mut bytes)?;
+                    }
+                    Ok(bytes)
+                });
+            }
+            let title = format!("streams ({})", self.streams.len());
+            ui.collapsing(title, |ui| {
+                for (index, stream) in self.streams.iter_mut().take(SHOWN_ITEMS).enumerate() {
+                    let title = format!("[{}] stream", index);
+                    ui.push_id(index, |ui| stream.ui(ui, &title, context));
+                }
+                if self.streams.len() > SHOWN_ITEMS {
+                    ui.weak(format!("… {} more", self.streams.len() - SHOWN_ITEMS));
+                }
+            });
+            trailing_data_ui(ui, context, "stream", self.end, self.size);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_STREAMS: &[u8] = include_bytes!("fixtures/two_streams.xz");
+
+    #[test]
+    fn extract_streams() {
+        let xz = XzFormat::new(TWO_STREAMS).unwrap();
+        assert_eq!(xz.streams.len(), 2);
+        let mut bytes = Vec::new();
+        for stream in &xz.streams {
+            stream.extract(TWO_STREAMS, &mut bytes).unwrap();
+        }
+        assert_eq!(bytes, b"The first stream\nand the second one\n");
+    }
+
+    #[test]
+    fn extract_broken_stream() {
+        let mut input = TWO_STREAMS.to_vec();
+        // In the compressed data of the first block
+        input[StreamHeader::SIZE as usize + 16] ^= 0xff;
+        let xz = XzFormat::new(&input).unwrap();
+        assert!(xz.streams[0].extract(&input, &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,505 @@
+//! Zstandard files, frames of blocks after a header with the window and content sizes, with
+//! skippable frames of other data between them
+
+use std::io;
+
+use ruzstd::decoding::StreamingDecoder;
+
+use super::{super::trailing_data_ui, slice, Extracted, SHOWN_ITEMS};
+use crate::{
+    error::Error,
+    tools::format_explorer::{formats::Confidence, FileFormatUi, FormatContext},
+};
+
+const MAGIC: u32 = 0xfd2f_b528;
+/// Skippable frames have any magic from here to `SKIPPABLE_MAGIC | 0xf`
+const SKIPPABLE_MAGIC: u32 = 0x184d_2a50;
+/// Blocks decompress to at most this much, and so are at most this big
+const MAX_BLOCK_SIZE: u64 = 128 << 10;
+const BLOCK_HEADER_SIZE: u64 = 3;
+
+/// Frames read at most
+const MAX_FRAMES: usize = 100_000;
+/// Blocks read at most, over all frames
+const MAX_BLOCKS: usize = 1_000_000;
+
+const RAW: u8 = 0;
+const RLE: u8 = 1;
+const COMPRESSED: u8 = 2;
+
+/// Skippable frames are also used by formats like LZ4, so only a Zstandard frame is sure
+pub fn probe(bytes: &[u8]) -> Confidence {
+    match magic_at(bytes, 0) {
+        Some(MAGIC) => Confidence::High,
+        Some(magic) if magic & !0xf == SKIPPABLE_MAGIC => Confidence::Low,
+        _ => Confidence::None,
+    }
+}
+
+fn magic_at(input: &[u8], offset: u64) -> Option<u32> {
+    let bytes = slice(input, offset, 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// A little-endian number of `length` bytes at `offset`
+fn number_at(input: &[u8], offset: u64, length: u64) -> Option<u64> {
+    let bytes = slice(input, offset, length)?;
+    Some(
+        bytes
+            .iter()
+            .rev()
+            .fold(0, |value, &b| value << 8 | b as u64),
+    )
+}
+
+fn block_type_name(kind: u8) -> &'static str {
+    match kind {
+        RAW => "raw",
+        RLE => "RLE",
+        COMPRESSED => "compressed",
+        _ => "reserved",
+    }
+}
+
+/// The frame header descriptor, which says which fields follow it
+fn descriptor_text(descriptor: u8) -> String {
+    let mut parts = vec![format!("{:#04x}", descriptor)];
+    if descriptor & 0x20 != 0 {
+        parts.push("single segment".to_string());
+    }
+    if descriptor & 0x4 != 0 {
+        parts.push("checksum".to_string());
+    }
+    if descriptor & 0x8 != 0 {
+        parts.push("reserved bit set".to_string());
+    }
+    parts.join(", ")
+}
+
+/// The window descriptor, a power of two and eighths of it
+fn window_size(descriptor: u8) -> u64 {
+    let base = 1u64 << (10 + (descriptor >> 3));
+    base + base / 8 * (descriptor & 0x7) as u64
+}
+
+/// A block header and what follows it
+struct Block {
+    offset: u64,
+    last: bool,
+    kind: u8,
+    /// Size from the header, which for RLE blocks is how many times their byte repeats
+    size: u64,
+}
+
+impl Block {
+    /// Bytes after the header, RLE blocks only have the byte they repeat
+    fn data_size(&self) -> u64 {
+        match self.kind {
+            RLE => 1,
+            _ => self.size,
+        }
+    }
+}
+
+/// A frame header, its blocks and the checksum of the content
+pub struct Frame {
+    offset: u64,
+    descriptor: u8,
+    /// Where the window descriptor is and the size it gives, single segment frames have none
+    window: Option<(u64, u64)>,
+    /// Where the fields are, how long they are and their values
+    dictionary_id: Option<(u64, u64, u64)>,
+    content_size: Option<(u64, u64, u64)>,
+    blocks: Vec<Block>,
+    /// Where the checksum is and its value, the low half of the XXH64 of the content
+    checksum: Option<(u64, u32)>,
+    end: u64,
+    warnings: Vec<String>,
+}
+
+impl Frame {
+    /// Reads the frame at `offset`, with at most `max_blocks` blocks
+    fn parse(input: &[u8], offset: u64, max_blocks: usize) -> Result<Self, String> {
+        let cut_off = || format!("the frame header at {:#x} is cut off", offset);
+        let mut position = offset + 4;
+        let descriptor = number_at(input, position, 1).ok_or_else(cut_off)? as u8;
+        position += 1;
+        let mut warnings = Vec::new();
+        if descriptor & 0x8 != 0 {
+            warnings.push("The reserved bit of the descriptor is set".to_string());
+        }
+        let single_segment = descriptor & 0x20 != 0;
+        let window = if single_segment {
+            None
+        } else {
+            let window = number_at(input, position, 1).ok_or_else(cut_off)? as u8;
+            position += 1;
+            Some((position - 1, window_size(window)))
+        };
+        let mut field = |length: u64| -> Result<_, String> {
+            if length == 0 {
+                return Ok(None);
+            }
+            let value = number_at(input, position, length).ok_or_else(cut_off)?;
+            position += length;
+            Ok(Some((position - length, length, value)))
+        };
+        let dictionary_id = field([0, 1, 2, 4][(descriptor & 0x3) as usize])?;
+        let mut content_size = match descriptor >> 6 {
+            0 if single_segment => field(1)?,
+            0 => None,
+            flag => field(1 << flag)?,
+        };
+        // Two bytes can't hold the sizes one byte can, so they start after them
+        if let Some((_, 2, size)) = &mut content_size {
+            *size += 256;
+        }
+        let window_size = window.map(|(_, size)| size);
+        let window_size = window_size.or(content_size.map(|(_, _, size)| size));
+        let max_block_size = window_size.unwrap_or(MAX_BLOCK_SIZE).min(MAX_BLOCK_SIZE);
+
+        let mut blocks = Vec::new();
+        let end = loop {
+            if blocks.len() >= max_blocks {
+                warnings.push(format!("Only the first {} blocks are listed", max_blocks));
+                break input.len() as u64;
+            }
+            let Some(header) = number_at(input, position, BLOCK_HEADER_SIZE) else {
+                warnings.push("The frame is cut off".to_string());
+                break input.len() as u64;
+            };
+            let block = Block {
+                offset: position,
+                last: header & 0x1 != 0,
+                kind: (header >> 1 & 0x3) as u8,
+                size: header >> 3,
+            };
+            if block.kind > COMPRESSED {
+                warnings.push(format!("Block {} has the reserved type", blocks.len()));
+            } else if block.size > max_block_size {
+                warnings.push(format!(
+                    "Block {} is bigger than the {} bytes blocks can be",
+                    blocks.len(),
+                    max_block_size
+                ));
+            }
+            position = position
+                .saturating_add(BLOCK_HEADER_SIZE)
+                .saturating_add(block.data_size());
+            let last = block.last;
+            blocks.push(block);
+            if position > input.len() as u64 {
+                warnings.push("The last block is cut off".to_string());
+                break input.len() as u64;
+            }
+            if last {
+                break position;
+            }
+        };
+        let checksum = if descriptor & 0x4 == 0 || end != position {
+            None
+        } else if let Some(value) = number_at(input, position, 4) {
+            position += 4;
+            Some((position - 4, value as u32))
+        } else {
+            warnings.push("The checksum is cut off".to_string());
+            None
+        };
+        Ok(Self {
+            offset,
+            descriptor,
+            window,
+            dictionary_id,
+            content_size,
+            blocks,
+            checksum,
+            end: end.max(position).min(input.len() as u64),
+            warnings,
+        })
+    }
+}
+
+impl Frame {
+    /// Whether the frame goes on to its last block
+    fn is_complete(&self) -> bool {
+        self.blocks.last().is_some_and(|block| block.last)
+    }
+
+    /// Appends the decompressed content to `bytes`, `input` is the whole file
+    fn extract(&self, input: &[u8], bytes: &mut Vec<u8>) -> Result<(), Error> {
+        let error = |message: String| Error::parse("Zstandard", message);
+        let data = slice(input, self.offset, self.end - self.offset)
+            .ok_or_else(|| error("the frame isn't all in the file".to_string()))?;
+        let mut decoder = StreamingDecoder::new(data).map_err(|err| error(err.to_string()))?;
+        io::copy(&mut decoder, &mut Extracted(bytes)).map_err(|err| error(err.to_string()))?;
+        let frame = decoder.into_frame_decoder();
+        match (
+            frame.get_checksum_from_data(),
+            frame.get_calculated_checksum(),
+        ) {
+            (Some(checksum), Some(computed)) if checksum != computed => Err(error(format!(
+                "the checksum is {:#010x} but the frame says {:#010x}",
+                computed, checksum
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FileFormatUi for Frame {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        context
+            .at(self.offset, self.end - self.offset)
+            .collapsing(ui, name, |ui| {
+                for warning in &self.warnings {
+                    ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+                }
+                context
+                    .at(self.offset, 4)
+                    .field(ui, "magic", &format!("{:#x}", MAGIC));
+                let descriptor = descriptor_text(self.descriptor);
+                context
+                    .at(self.offset + 4, 1)
+                    .field(ui, "descriptor", &descriptor);
+                if let Some((offset, size)) = self.window {
+                    let size = context.number(size);
+                    context.at(offset, 1).field(ui, "window size", &size);
+                }
+                if let Some((offset, length, id)) = self.dictionary_id {
+                    let id = context.number(id);
+                    context.at(offset, length).field(ui, "dictionary ID", &id);
+                }
+                if let Some((offset, length, size)) = self.content_size {
+                    let size = context.number(size);
+                    context.at(offset, length).field(ui, "content size", &size);
+                }
+                if self.is_complete()
+                    && ui
+                        .button("Open")
+                        .on_hover_text("Decompress the frame into a new document")
+                        .clicked()
+                {
+                    context.open("decompressed", |input| {
+                        let mut bytes = Vec::new();
+                        self.extract(input, &mut bytes).map(|()| bytes)
+                    });
+                }
+                let title = format!("blocks ({})", self.blocks.len());
+                ui.collapsing(title, |ui| {
+                    for (index, block) in self.blocks.iter().take(SHOWN_ITEMS).enumerate() {
+                        let mut text = format!(
+                            "{}, {}",
+                            block_type_name(block.kind),
+                            context.number(block.size)
+                        );
+                        if block.last {
+                            text.push_str(", last");
+                        }
+                        let length = BLOCK_HEADER_SIZE + block.data_size();
+                        context
+                            .at(block.offset, length)
+                            .field(ui, &format!("[{}]", index), &text);
+                    }
+                    if self.blocks.len() > SHOWN_ITEMS {
+                        ui.weak(format!("… {} more", self.blocks.len() - SHOWN_ITEMS));
+                    }
+                });
+                if let Some((offset, checksum)) = self.checksum {
+                    let checksum = format!("{:#010x}", checksum);
+                    context.at(offset, 4).field(ui, "checksum", &checksum);
+                }
+            });
+    }
+}
+
+/// Data that decoders skip, with its own magic
+struct SkippableFrame {
+    offset: u64,
+    magic: u32,
+    size: u32,
+}
+
+impl SkippableFrame {
+    fn end(&self, input_size: u64) -> u64 {
+        (self.offset + 8 + self.size as u64).min(input_size)
+    }
+}
+
+enum Item {
+    Frame(Frame),
+    Skippable(SkippableFrame),
+}
+
+pub struct ZstdFormat {
+    items: Vec<Item>,
+    /// Where the last frame ends
+    end: u64,
+    size: u64,
+    warnings: Vec<String>,
+}
+
+impl ZstdFormat {
+    pub fn new(input: &[u8]) -> Result<Self, Error> {
+        let size = input.len() as u64;
+        let mut items = Vec::new();
+        let mut warnings = Vec::new();
+        let mut block_count = 0;
+        let mut position = 0;
+        // Frames follow each other, zstd decompresses them one after the other
+        while items.len() < MAX_FRAMES {
+            let item = match magic_at(input, position) {
+                Some(MAGIC) => match Frame::parse(input, position, MAX_BLOCKS - block_count) {
+                    Ok(frame) => {
+                        block_count += frame.blocks.len();
+                        Item::Frame(frame)
+                    }
+                    Err(message) if items.is_empty() => {
+                        return Err(Error::parse("Zstandard", message))
+                    }
+                    Err(message) => {
+                        warnings.push(format!("The frame after the last one is bad, {}", message));
+                        break;
+                    }
+                },
+                Some(magic) if magic & !0xf == SKIPPABLE_MAGIC => {
+                    let Some(frame_size) = number_at(input, position + 4, 4) else {
+                        warnings.push(format!("The skippable frame at {:#x} is cut off", position));
+                        break;
+                    };
+                    let frame = SkippableFrame {
+                        offset: position,
+                        magic,
+                        size: frame_size as u32,
+                    };
+                    if frame.end(u64::MAX) > size {
+                        warnings.push(format!("The skippable frame at {:#x} is cut off", position));
+                    }
+                    Item::Skippable(frame)
+                }
+                _ if items.is_empty() => {
+                    return Err(Error::parse("Zstandard", "there is no frame at the start"))
+                }
+                _ => break,
+            };
+            position = match &item {
+                Item::Frame(frame) => frame.end,
+                Item::Skippable(frame) => frame.end(size),
+            };
+            items.push(item);
+        }
+        Ok(Self {
+            items,
+            end: position,
+            size,
+            warnings,
+        })
+    }
+
+    fn frames(&self) -> impl Iterator<Item = &Frame> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Frame(frame) => Some(frame),
+            Item::Skippable(_) => None,
+        })
+    }
+
+    /// Frame and block counts, the content size if the frames have it and where the frames
+    /// end, for expressions
+    pub fn symbols(&self) -> Vec<(String, u64)> {
+        let block_count = self.frames().map(|frame| frame.blocks.len()).sum::<usize>();
+        let mut symbols = vec![
+            ("frames".to_string(), self.items.len() as u64),
+            ("blocks".to_string(), block_count as u64),
+            ("stream_end".to_string(), self.end),
+        ];
+        let content_size = self
+            .frames()
+            .map(|frame| frame.content_size.map(|(_, _, size)| size))
+            .sum::<Option<u64>>();
+        if let Some(size) = content_size {
+            symbols.push(("content_size".to_string(), size));
+        }
+        symbols
+    }
+}
+
+impl FileFormatUi for ZstdFormat {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        ui.collapsing(name, |ui| {
+            for warning in &self.warnings {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+            if self.frames().count() > 1
+                && ui
+                    .button("Open all")
+                    .on_hover_text("Decompress the frames one after the other, like unzstd")
+                    .clicked()
+            {
+                context.open("decompressed", |input| {
+                    let mut bytes = Vec::new();
+                    for frame in self.frames() {
+                        frame.extract(input, &mut bytes)?;
+                    }
+                    Ok(bytes)
+                });
+            }
+            let title = format!("frames ({})", self.items.len());
+            ui.collapsing(title, |ui| {
+                for (index, item) in self.items.iter_mut().take(SHOWN_ITEMS).enumerate() {
+                    match item {
+                        Item::Frame(frame) => {
+                            let title = format!("[{}] frame", index);
+                            ui.push_id(index, |ui| frame.ui(ui, &title, context));
+                        }
+                        Item::Skippable(frame) => {
+                            let length = frame.end(self.size) - frame.offset;
+                            let text = format!(
+                                "magic {:#x}, {} bytes",
+                                frame.magic,
+                                context.number(frame.size.into())
+                            );
+                            context.at(frame.offset, length).field(
+                                ui,
+                                &format!("[{}] skippable frame", index),
+                                &text,
+                            );
+                        }
+                    }
+                }
+                if self.items.len() > SHOWN_ITEMS {
+                    ui.weak(format!("… {} more", self.items.len() - SHOWN_ITEMS));
+                }
+            });
+            trailing_data_ui(ui, context, "stream", self.end, self.size);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_FRAMES: &[u8] = include_bytes!("fixtures/two_frames.zst");
+
+    #[test]
+    fn extract_frames() {
+        let zstd = ZstdFormat::new(TWO_FRAMES).unwrap();
+        assert_eq!(zstd.frames().count(), 2);
+        let mut bytes = Vec::new();
+        for frame in zstd.frames() {
+            assert!(frame.is_complete());
+            frame.extract(TWO_FRAMES, &mut bytes).unwrap();
+        }
+        assert_eq!(bytes, b"The first stream\nand the second one\n");
+    }
+
+    #[test]
+    fn extract_with_a_bad_checksum() {
+        let zstd = ZstdFormat::new(TWO_FRAMES).unwrap();
+        let frame = zstd.frames().next().unwrap();
+        let (offset, _) = frame.checksum.unwrap();
+        let mut input = TWO_FRAMES.to_vec();
+        input[offset as usize] ^= 0xff;
+        let error = frame.extract(&input, &mut Vec::new()).unwrap_err();
+        assert!(error.to_string().contains("checksum"), "{}", error);
+    }
+}
@@ -0,0 +1,329 @@
+use nom::{sequence::Tuple, IResult};
+
+use super::{
+    sections::SectionHeader,
+    segments::{self, ProgramHeader, PT_DYNAMIC},
+    Readers,
+};
+use crate::tools::format_explorer::{FileFormatUi, FormatContext};
+
+const SHT_DYNAMIC: u32 = 6;
+
+const DT_NULL: i64 = 0;
+const DT_NEEDED: i64 = 1;
+const DT_STRTAB: i64 = 5;
+const DT_STRSZ: i64 = 10;
+const DT_SONAME: i64 = 14;
+const DT_RPATH: i64 = 15;
+const DT_RUNPATH: i64 = 29;
+const DT_FLAGS: i64 = 30;
+const DT_FLAGS_1: i64 = 0x6fff_fffb;
+
+pub struct DynamicEntry {
+    pub tag: i64,
+    pub value: u64,
+    /// Value looked up in the dynamic string table, for tags like `DT_NEEDED`
+    pub string: Option<String>,
+    /// Where the entry is in the file
+    pub offset: u64,
+    pub size: u64,
+}
+
+impl DynamicEntry {
+    fn parse(input: &[u8], readers: Readers) -> IResult<&[u8], Self> {
+        let address = |input| readers.address(input);
+        let (tail, (tag, value)) = (address, address).parse(input)?;
+        // Tags are signed, 32-bit ones need sign extension
+        let tag = if readers.is_64 {
+            tag as i64
+        } else {
+            tag as u32 as i32 as i64
+        };
+        Ok((
+            tail,
+            Self {
+                tag,
+                value,
+                string: None,
+                offset: 0,
+                size: 0,
+            },
+        ))
+    }
+
+    /// The value as a string, flag names or a number depending on the tag
+    pub fn value_text(&self) -> String {
+        if let Some(string) = &self.string {
+            return format!("{:?}", string);
+        }
+        match self.tag {
+            DT_FLAGS => super::flag_names(self.value, DYNAMIC_FLAGS),
+            DT_FLAGS_1 => super::flag_names(self.value, DYNAMIC_FLAGS_1),
+            _ => format!("{:#x}", self.value),
+        }
+    }
+}
+
+/// Symbolic `DT_*` name of a dynamic tag
+pub fn dynamic_tag_name(tag: i64) -> String {
+    let name = match tag {
+        DT_NULL => "DT_NULL",
+        DT_NEEDED => "DT_NEEDED",
+        2 => "DT_PLTRELSZ",
+        3 => "DT_PLTGOT",
+        4 => "DT_HASH",
+        DT_STRTAB => "DT_STRTAB",
+        6 => "DT_SYMTAB",
+        7 => "DT_RELA",
+        8 => "DT_RELASZ",
+        9 => "DT_RELAENT",
+        DT_STRSZ => "DT_STRSZ",
+        11 => "DT_SYMENT",
+        12 => "DT_INIT",
+        13 => "DT_FINI",
+        DT_SONAME => "DT_SONAME",
+        DT_RPATH => "DT_RPATH",
+        16 => "DT_SYMBOLIC",
+        17 => "DT_REL",
+        18 => "DT_RELSZ",
+        19 => "DT_RELENT",
+        20 => "DT_PLTREL",
+        21 => "DT_DEBUG",
+        22 => "DT_TEXTREL",
+        23 => "DT_JMPREL",
+        24 => "DT_BIND_NOW",
+        25 => "DT_INIT_ARRAY",
+        26 => "DT_FINI_ARRAY",
+        27 => "DT_INIT_ARRAYSZ",
+        28 => "DT_FINI_ARRAYSZ",
+        DT_RUNPATH => "DT_RUNPATH",
+        DT_FLAGS => "DT_FLAGS",
+        32 => "DT_PREINIT_ARRAY",
+        33 => "DT_PREINIT_ARRAYSZ",
+        34 => "DT_SYMTAB_SHNDX",
+        35 => "DT_RELRSZ",
+        36 => "DT_RELR",
+        37 => "DT_RELRENT",
+        0x6fff_fef5 => "DT_GNU_HASH",
+        0x6fff_fff0 => "DT_VERSYM",
+        0x6fff_fff9 => "DT_RELACOUNT",
+        0x6fff_fffa => "DT_RELCOUNT",
+        DT_FLAGS_1 => "DT_FLAGS_1",
+        0x6fff_fffc => "DT_VERDEF",
+        0x6fff_fffd => "DT_VERDEFNUM",
+        0x6fff_fffe => "DT_VERNEED",
+        0x6fff_ffff => "DT_VERNEEDNUM",
+        _ => return format!("{:#x}", tag),
+    };
+    name.to_string()
+}
+
+const DYNAMIC_FLAGS: [(u64, &str); 5] = [
+    (0x1, "DF_ORIGIN"),
+    (0x2, "DF_SYMBOLIC"),
+    (0x4, "DF_TEXTREL"),
+    (0x8, "DF_BIND_NOW"),
+    (0x10, "DF_STATIC_TLS"),
+];
+
+const DYNAMIC_FLAGS_1: [(u64, &str); 17] = [
+    (0x1, "DF_1_NOW"),
+    (0x2, "DF_1_GLOBAL"),
+    (0x4, "DF_1_GROUP"),
+    (0x8, "DF_1_NODELETE"),
+    (0x10, "DF_1_LOADFLTR"),
+    (0x20, "DF_1_INITFIRST"),
+    (0x40, "DF_1_NOOPEN"),
+    (0x80, "DF_1_ORIGIN"),
+    (0x100, "DF_1_DIRECT"),
+    (0x400, "DF_1_INTERPOSE"),
+    (0x800, "DF_1_NODEFLIB"),
+    (0x1000, "DF_1_NODUMP"),
+    (0x2000, "DF_1_CONFALT"),
+    (0x4000, "DF_1_ENDFILTEE"),
+    (0x20000, "DF_1_NODIRECT"),
+    (0x80_0000, "DF_1_SYMINTPOSE"),
+    (0x800_0000, "DF_1_PIE"),
+];
+
+/// Parses the `.dynamic` section, or the `PT_DYNAMIC` segment if there are no sections
+pub(super) fn parse_dynamic(
+    input: &[u8],
+    readers: Readers,
+    sections: &[SectionHeader],
+    program_headers: &[ProgramHeader],
+) -> Vec<DynamicEntry> {
+    let section = sections.iter().find(|section| section.type_ == SHT_DYNAMIC);
+    let data = match section {
+        Some(section) => section.data(input).map(|data| (section.offset, data)),
+        None => program_headers
+            .iter()
+            .find(|header| header.type_ == PT_DYNAMIC)
+            .and_then(|header| Some((header.offset, header.data(input)?))),
+    };
+    let Some((offset, data)) = data else {
+        return Vec::new();
+    };
+    let entry_size = if readers.is_64 { 16 } else { 8 };
+    let mut entries = Vec::new();
+    for (index, entry) in data.chunks_exact(entry_size).enumerate() {
+        let Ok((_, mut entry)) = DynamicEntry::parse(entry, readers) else {
+            break;
+        };
+        entry.offset = offset + (index * entry_size) as u64;
+        entry.size = entry_size as u64;
+        let tag = entry.tag;
+        entries.push(entry);
+        if tag == DT_NULL {
+            break;
+        }
+    }
+
+    // The section links to its string table, otherwise `DT_STRTAB` has its address
+    let strings = match section {
+        Some(section) => sections
+            .get(section.link as usize)
+            .and_then(|strings| strings.data(input)),
+        None => {
+            let value = |tag| entries.iter().find(|entry| entry.tag == tag);
+            value(DT_STRTAB).and_then(|table| {
+                let start = segments::address_to_offset(program_headers, table.value)?;
+                let size = value(DT_STRSZ).map_or(u64::MAX, |size| size.value);
+                let start = usize::try_from(start).ok()?;
+                let end = usize::try_from(size)
+                    .ok()
+                    .and_then(|size| start.checked_add(size))
+                    .unwrap_or(input.len())
+                    .min(input.len());
+                input.get(start..end)
+            })
+        }
+    };
+    if let Some(strings) = strings {
+        for entry in &mut entries {
+            if matches!(entry.tag, DT_NEEDED | DT_SONAME | DT_RPATH | DT_RUNPATH) {
+                entry.string = Some(super::string_at(strings, entry.value));
+            }
+        }
+    }
+    entries
+}
+
+impl FileFormatUi for Vec<DynamicEntry> {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let title = format!("{} ({})", name, self.len());
+        ui.collapsing(title, |ui| {
+            for entry in self.iter() {
+                context.at(entry.offset, entry.size).field(
+                    ui,
+                    &dynamic_tag_name(entry.tag),
+                    &entry.value_text(),
+                );
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::tests::*, *};
+    use crate::tools::format_explorer::formats::elf::ElfFormat;
+
+    const STRINGS: &[u8] = b"\0libc.so.6\0libx.so\0";
+
+    /// `.dynstr` and `.dynamic`, both loaded by a segment, and the dynamic segment
+    fn builder(kind: Kind) -> Builder {
+        let mut builder = Builder::new(kind);
+        builder.segments.push(Segment {
+            type_: segments::PT_LOAD,
+            flags: 4,
+            sections: 1..=2,
+        });
+        builder.segments.push(Segment {
+            type_: PT_DYNAMIC,
+            flags: 4,
+            sections: 2..=2,
+        });
+        let mut strings = Section::new(".dynstr", 3, STRINGS.to_vec());
+        strings.flags = SHF_ALLOC;
+        builder.sections.push(strings);
+        let strings = builder.offset_of(1);
+        let entry = |tag: i64, value: u64| [kind.address(tag as u64), kind.address(value)].concat();
+        let entries = [
+            entry(DT_NEEDED, 1),
+            entry(DT_SONAME, 11),
+            entry(DT_STRTAB, strings),
+            entry(DT_STRSZ, STRINGS.len() as u64),
+            entry(DT_FLAGS, 0x8 | 0x100),
+            entry(DT_FLAGS_1, 0x800_0001),
+            entry(-1, 0),
+            entry(DT_NULL, 0),
+            // Entries after DT_NULL aren't read
+            entry(DT_NEEDED, 1),
+        ]
+        .concat();
+        let mut dynamic = Section::new(".dynamic", SHT_DYNAMIC, entries);
+        dynamic.flags = SHF_ALLOC;
+        dynamic.link = 1;
+        builder.sections.push(dynamic);
+        builder
+    }
+
+    fn check(kind: Kind, builder: &Builder, entries: &[DynamicEntry]) {
+        let names = entries.iter().map(|entry| dynamic_tag_name(entry.tag));
+        assert_eq!(
+            names.collect::<Vec<_>>()[..6],
+            [
+                "DT_NEEDED",
+                "DT_SONAME",
+                "DT_STRTAB",
+                "DT_STRSZ",
+                "DT_FLAGS",
+                "DT_FLAGS_1"
+            ],
+            "{:?}",
+            kind
+        );
+        assert_eq!(entries.len(), 8);
+        // Tags are signed, 32-bit ones too
+        assert_eq!((entries[6].tag, entries[7].tag), (-1, DT_NULL));
+        let values = entries
+            .iter()
+            .map(DynamicEntry::value_text)
+            .collect::<Vec<_>>();
+        assert_eq!(values[..2], ["\"libc.so.6\"", "\"libx.so\""]);
+        assert_eq!(values[2], format!("{:#x}", builder.offset_of(1)));
+        assert_eq!(values[4], "DF_BIND_NOW | 0x100");
+        assert_eq!(values[5], "DF_1_NOW | DF_1_PIE");
+        let size = if kind.is_64 { 16 } else { 8 };
+        assert_eq!(entries[1].offset, builder.offset_of(2) + size);
+        assert_eq!(entries[1].size, size);
+    }
+
+    #[test]
+    fn dynamic_section() {
+        for kind in KINDS {
+            let builder = builder(kind);
+            let elf = ElfFormat::new(&builder.build()).unwrap();
+            assert!(elf.warnings.is_empty(), "{:?} {:?}", kind, elf.warnings);
+            check(kind, &builder, &elf.dynamic);
+        }
+    }
+
+    #[test]
+    fn dynamic_segment() {
+        for kind in KINDS {
+            let builder = builder(kind);
+            let mut input = builder.build();
+            // Without sections the segment has the entries, DT_STRTAB the strings
+            let shoff = header_field(kind, "e_shoff");
+            let address_size = if kind.is_64 { 8 } else { 4 };
+            input[shoff..shoff + address_size].copy_from_slice(&kind.address(0));
+            let shnum = header_field(kind, "e_shnum");
+            input[shnum..shnum + 2].copy_from_slice(&kind.half(0));
+            let elf = ElfFormat::new(&input).unwrap();
+            assert!(elf.sections.headers.is_empty());
+            check(kind, &builder, &elf.dynamic);
+        }
+    }
+}
@@ -0,0 +1,571 @@
+//! The ELF header and the meanings of its values
+
+use std::fmt::LowerHex;
+
+use nom::IResult;
+
+use super::{hex_bytes, ElfFormat};
+use crate::tools::format_explorer::{FileFormat, FileFormatUi, FormatContext, Layout};
+
+pub(super) const ELF_MAGIC: [u8; 4] = *b"\x7fELF";
+
+const EV_CURRENT: u32 = 1;
+
+pub(super) const EM_MIPS: u16 = 8;
+pub(super) const EM_PPC64: u16 = 21;
+pub(super) const EM_ARM: u16 = 40;
+pub(super) const EM_X86_64: u16 = 62;
+pub(super) const EM_AARCH64: u16 = 183;
+pub(super) const EM_RISCV: u16 = 243;
+pub(super) const EM_LOONGARCH: u16 = 258;
+
+/// `e_ident`, the bytes that say how to read the rest of the file
+#[derive(FileFormat, Clone, Copy)]
+pub struct Identification {
+    #[format(display = magic_text)]
+    pub mag: [u8; 4],
+    #[format(meaning = class_name)]
+    pub class: u8,
+    #[format(meaning = data_name)]
+    pub data: u8,
+    #[format(meaning = version_name)]
+    pub ei_version: u8,
+    #[format(meaning = os_abi_name)]
+    pub os_abi: u8,
+    pub abi_version: u8,
+    pub pad: [u8; 7],
+}
+
+/// Magic bytes with their ASCII, like `7f 45 4c 46 (\x7fELF)`
+fn magic_text(magic: &[u8; 4]) -> String {
+    format!("{} ({})", hex_bytes(magic), magic.escape_ascii())
+}
+
+/// `ElfN_Addr` and `ElfN_Off`, the fields that are as wide as the class
+pub trait Address: Layout + LowerHex + Copy + Into<u64> {}
+
+impl Address for u32 {}
+impl Address for u64 {}
+
+/// `e_flags`, which only mean something for the machine. The machine isn't known while the
+/// flags are parsed, so the header sets it afterwards.
+#[derive(Clone, Copy)]
+pub struct Flags {
+    pub bits: u32,
+    pub machine: u16,
+}
+
+impl Layout for Flags {
+    const SIZE: u64 = 4;
+
+    fn parse(input: &[u8], big_endian: bool) -> IResult<&[u8], Self> {
+        let (tail, bits) = u32::parse(input, big_endian)?;
+        Ok((tail, Self { bits, machine: 0 }))
+    }
+}
+
+impl FileFormatUi for Flags {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let description = flags_description(self.machine, self.bits);
+        context.enum_field(ui, name, self.bits.into(), &description);
+    }
+}
+
+/// `Elf32_Ehdr` or `Elf64_Ehdr`, depending on the address type
+#[derive(FileFormat, Clone, Copy)]
+pub struct FileHeader<A: Address> {
+    pub ident: Identification,
+    #[format(meaning = type_name)]
+    pub type_: u16,
+    #[format(meaning = machine_name)]
+    pub machine: u16,
+    #[format(meaning = version_name)]
+    pub e_version: u32,
+    #[format(hex)]
+    pub entry: A,
+    #[format(hex)]
+    pub ph_offset: A,
+    #[format(hex)]
+    pub sh_offset: A,
+    pub flags: Flags,
+    pub eh_size: u16,
+    pub ph_entry_size: u16,
+    pub ph_entry_num: u16,
+    pub sh_entry_size: u16,
+    pub sh_entry_num: u16,
+    pub sh_str_offset: u16,
+}
+
+impl<A: Address> FileHeader<A> {
+    fn parse_for_machine(input: &[u8], big_endian: bool) -> IResult<&[u8], Self> {
+        let (tail, mut header) = Self::parse(input, big_endian)?;
+        header.flags.machine = header.machine;
+        Ok((tail, header))
+    }
+
+    fn widened(&self) -> FileHeader<u64> {
+        FileHeader {
+            ident: self.ident,
+            type_: self.type_,
+            machine: self.machine,
+            e_version: self.e_version,
+            entry: self.entry.into(),
+            ph_offset: self.ph_offset.into(),
+            sh_offset: self.sh_offset.into(),
+            flags: self.flags,
+            eh_size: self.eh_size,
+            ph_entry_size: self.ph_entry_size,
+            ph_entry_num: self.ph_entry_num,
+            sh_entry_size: self.sh_entry_size,
+            sh_entry_num: self.sh_entry_num,
+            sh_str_offset: self.sh_str_offset,
+        }
+    }
+}
+
+/// The header of a 32-bit or a 64-bit file, which only differ in the width of the addresses
+pub enum Header {
+    Elf32(FileHeader<u32>),
+    Elf64(FileHeader<u64>),
+}
+
+impl Header {
+    pub(super) fn parse(input: &[u8], is_64: bool, big_endian: bool) -> IResult<&[u8], Self> {
+        if is_64 {
+            let (tail, header) = FileHeader::parse_for_machine(input, big_endian)?;
+            Ok((tail, Self::Elf64(header)))
+        } else {
+            let (tail, header) = FileHeader::parse_for_machine(input, big_endian)?;
+            Ok((tail, Self::Elf32(header)))
+        }
+    }
+
+    /// The fields with the addresses and offsets as 64-bit numbers, whatever the class
+    pub fn fields(&self) -> FileHeader<u64> {
+        match self {
+            Self::Elf32(header) => header.widened(),
+            Self::Elf64(header) => *header,
+        }
+    }
+
+    /// Bytes the header takes in the file
+    pub fn size(&self) -> u64 {
+        match self {
+            Self::Elf32(_) => FileHeader::<u32>::SIZE,
+            Self::Elf64(_) => FileHeader::<u64>::SIZE,
+        }
+    }
+}
+
+impl FileFormatUi for Header {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        match self {
+            Self::Elf32(header) => header.ui(ui, name, context),
+            Self::Elf64(header) => header.ui(ui, name, context),
+        }
+    }
+}
+
+pub fn class_name(class: u8) -> String {
+    let name = match class {
+        0 => "ELFCLASSNONE",
+        1 => "ELFCLASS32",
+        2 => "ELFCLASS64",
+        _ => return "unknown".to_string(),
+    };
+    name.to_string()
+}
+
+pub fn data_name(data: u8) -> String {
+    let name = match data {
+        0 => "ELFDATANONE",
+        1 => "ELFDATA2LSB, little endian",
+        2 => "ELFDATA2MSB, big endian",
+        _ => return "unknown".to_string(),
+    };
+    name.to_string()
+}
+
+pub fn version_name(version: u32) -> String {
+    match version {
+        0 => "EV_NONE".to_string(),
+        EV_CURRENT => "EV_CURRENT".to_string(),
+        _ => "unknown".to_string(),
+    }
+}
+
+pub fn os_abi_name(os_abi: u8) -> String {
+    let name = match os_abi {
+        0 => "ELFOSABI_SYSV",
+        1 => "ELFOSABI_HPUX",
+        2 => "ELFOSABI_NETBSD",
+        3 => "ELFOSABI_LINUX",
+        4 => "ELFOSABI_HURD",
+        6 => "ELFOSABI_SOLARIS",
+        7 => "ELFOSABI_AIX",
+        8 => "ELFOSABI_IRIX",
+        9 => "ELFOSABI_FREEBSD",
+        10 => "ELFOSABI_TRU64",
+        11 => "ELFOSABI_MODESTO",
+        12 => "ELFOSABI_OPENBSD",
+        13 => "ELFOSABI_OPENVMS",
+        14 => "ELFOSABI_NSK",
+        15 => "ELFOSABI_AROS",
+        16 => "ELFOSABI_FENIXOS",
+        17 => "ELFOSABI_CLOUDABI",
+        18 => "ELFOSABI_OPENVOS",
+        64 => "ELFOSABI_ARM_AEABI",
+        97 => "ELFOSABI_ARM",
+        255 => "ELFOSABI_STANDALONE",
+        _ => return "unknown".to_string(),
+    };
+    name.to_string()
+}
+
+pub fn type_name(type_: u16) -> String {
+    let name = match type_ {
+        0 => "ET_NONE",
+        1 => "ET_REL, relocatable",
+        2 => "ET_EXEC, executable",
+        3 => "ET_DYN, shared object",
+        4 => "ET_CORE, core dump",
+        0xfe00..=0xfeff => return format!("ET_LOOS+{:#x}", type_ - 0xfe00),
+        0xff00..=0xffff => return format!("ET_LOPROC+{:#x}", type_ - 0xff00),
+        _ => "unknown",
+    };
+    name.to_string()
+}
+
+pub fn machine_name(machine: u16) -> String {
+    let name = match machine {
+        0 => "EM_NONE",
+        1 => "EM_M32",
+        2 => "EM_SPARC",
+        3 => "EM_386",
+        4 => "EM_68K",
+        5 => "EM_88K",
+        6 => "EM_IAMCU",
+        7 => "EM_860",
+        EM_MIPS => "EM_MIPS",
+        9 => "EM_S370",
+        10 => "EM_MIPS_RS3_LE",
+        15 => "EM_PARISC",
+        18 => "EM_SPARC32PLUS",
+        20 => "EM_PPC",
+        EM_PPC64 => "EM_PPC64",
+        22 => "EM_S390",
+        23 => "EM_SPU",
+        36 => "EM_V800",
+        EM_ARM => "EM_ARM",
+        41 => "EM_ALPHA",
+        42 => "EM_SH",
+        43 => "EM_SPARCV9",
+        44 => "EM_TRICORE",
+        45 => "EM_ARC",
+        46 => "EM_H8_300",
+        50 => "EM_IA_64",
+        52 => "EM_COLDFIRE",
+        53 => "EM_68HC12",
+        EM_X86_64 => "EM_X86_64",
+        83 => "EM_AVR",
+        87 => "EM_V850",
+        88 => "EM_M32R",
+        92 => "EM_OPENRISC",
+        93 => "EM_ARC_COMPACT",
+        94 => "EM_XTENSA",
+        105 => "EM_MSP430",
+        106 => "EM_BLACKFIN",
+        140 => "EM_TI_C6000",
+        164 => "EM_HEXAGON",
+        EM_AARCH64 => "EM_AARCH64",
+        190 => "EM_CUDA",
+        191 => "EM_TILEGX",
+        195 => "EM_ARC_COMPACT2",
+        224 => "EM_AMDGPU",
+        EM_RISCV => "EM_RISCV",
+        247 => "EM_BPF",
+        252 => "EM_CSKY",
+        EM_LOONGARCH => "EM_LOONGARCH",
+        _ => "unknown",
+    };
+    name.to_string()
+}
+
+const ARM_FLAGS: [(u64, &str); 4] = [
+    (0x200, "EF_ARM_ABI_FLOAT_SOFT"),
+    (0x400, "EF_ARM_ABI_FLOAT_HARD"),
+    (0x40_0000, "EF_ARM_LE8"),
+    (0x80_0000, "EF_ARM_BE8"),
+];
+
+const RISCV_FLAGS: [(u64, &str); 3] = [
+    (0x1, "EF_RISCV_RVC"),
+    (0x8, "EF_RISCV_RVE"),
+    (0x10, "EF_RISCV_TSO"),
+];
+
+const MIPS_FLAGS: [(u64, &str); 8] = [
+    (0x1, "EF_MIPS_NOREORDER"),
+    (0x2, "EF_MIPS_PIC"),
+    (0x4, "EF_MIPS_CPIC"),
+    (0x8, "EF_MIPS_XGOT"),
+    (0x20, "EF_MIPS_ABI2"),
+    (0x100, "EF_MIPS_32BITMODE"),
+    (0x200, "EF_MIPS_FP64"),
+    (0x400, "EF_MIPS_NAN2008"),
+];
+
+/// Decodes the processor specific `e_flags`, which only mean something for the given machine
+pub fn flags_description(machine: u16, flags: u32) -> String {
+    let flags = flags as u64;
+    // Multi-bit fields are named first, the single bits after them
+    let (mut names, rest) = match machine {
+        EM_ARM => {
+            let version = flags >> 24;
+            let names = if version == 0 {
+                vec![]
+            } else {
+                vec![format!("EABI version {}", version)]
+            };
+            (names, super::flag_names(flags & 0xff_ffff, ARM_FLAGS))
+        }
+        EM_RISCV => {
+            let float_abi = match flags & 0x6 {
+                0x0 => "EF_RISCV_FLOAT_ABI_SOFT",
+                0x2 => "EF_RISCV_FLOAT_ABI_SINGLE",
+                0x4 => "EF_RISCV_FLOAT_ABI_DOUBLE",
+                _ => "EF_RISCV_FLOAT_ABI_QUAD",
+            };
+            (
+                vec![float_abi.to_string()],
+                super::flag_names(flags & !0x6, RISCV_FLAGS),
+            )
+        }
+        EM_MIPS => {
+            let arch = match flags >> 28 {
+                0 => "EF_MIPS_ARCH_1",
+                1 => "EF_MIPS_ARCH_2",
+                2 => "EF_MIPS_ARCH_3",
+                3 => "EF_MIPS_ARCH_4",
+                4 => "EF_MIPS_ARCH_5",
+                5 => "EF_MIPS_ARCH_32",
+                6 => "EF_MIPS_ARCH_64",
+                7 => "EF_MIPS_ARCH_32R2",
+                8 => "EF_MIPS_ARCH_64R2",
+                9 => "EF_MIPS_ARCH_32R6",
+                10 => "EF_MIPS_ARCH_64R6",
+                _ => "unknown architecture",
+            };
+            let mut names = vec![arch.to_string()];
+            match flags & 0xf000 {
+                0x0 => {}
+                0x1000 => names.push("EF_MIPS_ABI_O32".to_string()),
+                0x2000 => names.push("EF_MIPS_ABI_O64".to_string()),
+                0x3000 => names.push("EF_MIPS_ABI_EABI32".to_string()),
+                0x4000 => names.push("EF_MIPS_ABI_EABI64".to_string()),
+                abi => names.push(format!("ABI {:#x}", abi >> 12)),
+            }
+            (names, super::flag_names(flags & 0x0fff_0fff, MIPS_FLAGS))
+        }
+        EM_PPC64 => {
+            let names = match flags & 0x3 {
+                0 => vec![],
+                version => vec![format!("ELFv{} ABI", version)],
+            };
+            (names, super::flag_names(flags & !0x3, []))
+        }
+        EM_LOONGARCH => {
+            let float_abi = match flags & 0x7 {
+                1 => "soft float",
+                2 => "single float",
+                3 => "double float",
+                _ => "unknown float ABI",
+            };
+            let names = vec![
+                float_abi.to_string(),
+                format!("object ABI v{}", (flags >> 6) & 0x3),
+            ];
+            (names, super::flag_names(flags & !0xc7, []))
+        }
+        _ => (vec![], super::flag_names(flags, [])),
+    };
+    if rest != "none" || names.is_empty() {
+        names.push(rest);
+    }
+    names.join(", ")
+}
+
+/// Header size, program header size and section header size of the class
+fn expected_sizes(class: u8) -> (u16, u16, u16) {
+    if class == 2 {
+        (64, 56, 64)
+    } else {
+        (52, 32, 40)
+    }
+}
+
+/// Things in the header that are wrong but don't stop the rest of the file from being parsed
+pub(super) fn warnings(elf: &ElfFormat, string_table: usize) -> Vec<String> {
+    let header = elf.header.fields();
+    let ident = header.ident;
+    let mut warnings = Vec::new();
+    if ident.mag != ELF_MAGIC {
+        warnings.push(format!(
+            "Magic is \"{}\" instead of \"\\x7fELF\", this might not be an ELF file",
+            ident.mag.escape_ascii()
+        ));
+    }
+    if ident.ei_version as u32 != EV_CURRENT {
+        warnings.push(format!("ei_version is {} instead of 1", ident.ei_version));
+    }
+    if header.e_version != EV_CURRENT {
+        warnings.push(format!("e_version is {} instead of 1", header.e_version));
+    }
+    let (header_size, program_header_size, section_header_size) = expected_sizes(ident.class);
+    if header.eh_size != header_size {
+        warnings.push(format!(
+            "eh_size is {} instead of {}",
+            header.eh_size, header_size
+        ));
+    }
+    if !elf.program_headers.is_empty() && header.ph_entry_size != program_header_size {
+        warnings.push(format!(
+            "ph_entry_size is {} instead of {}",
+            header.ph_entry_size, program_header_size
+        ));
+    }
+    let section_count = elf.sections.headers.len();
+    if section_count > 0 && header.sh_entry_size != section_header_size {
+        warnings.push(format!(
+            "sh_entry_size is {} instead of {}",
+            header.sh_entry_size, section_header_size
+        ));
+    }
+    if section_count > 0 && string_table >= section_count {
+        warnings.push(format!(
+            "Section name table {} is past the last section",
+            string_table
+        ));
+    }
+    if ident.pad.iter().any(|&b| b != 0) {
+        warnings.push("Padding after the identification bytes isn't zero".to_string());
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::tests::*, *};
+
+    #[test]
+    fn header_fields() {
+        for kind in KINDS {
+            let mut builder = Builder::new(kind);
+            builder.sections.push(Section::new(".data", 1, vec![1; 4]));
+            let input = builder.build();
+            let elf = ElfFormat::new(&input).unwrap();
+            assert!(elf.warnings.is_empty(), "{:?} {:?}", kind, elf.warnings);
+            let header = elf.header.fields();
+            assert_eq!(elf.header.size(), kind.header_size() as u64);
+            assert_eq!(matches!(elf.header, Header::Elf64(_)), kind.is_64);
+            assert_eq!(
+                class_name(header.ident.class),
+                format!("ELFCLASS{}", if kind.is_64 { 64 } else { 32 })
+            );
+            let data = data_name(header.ident.data);
+            assert_eq!(data.ends_with("big endian"), kind.big_endian);
+            assert_eq!(type_name(header.type_), "ET_DYN, shared object");
+            assert_eq!(
+                (header.machine, header.flags.machine),
+                (kind.machine, kind.machine)
+            );
+            assert_eq!(header.sh_entry_num, 3);
+            // The section header table is at the end
+            let table_size = 3 * kind.section_header_size();
+            assert_eq!(header.sh_offset, (input.len() - table_size) as u64);
+
+            let symbols = elf.symbols();
+            let value = |name: &str| symbols.iter().find(|(symbol, _)| symbol == name).unwrap().1;
+            assert_eq!(value("e_machine"), kind.machine as u64);
+            assert_eq!(value("e_shoff"), header.sh_offset);
+            assert_eq!(value("e_ehsize"), kind.header_size() as u64);
+            assert_eq!(value("e_shstrndx"), 2);
+        }
+    }
+
+    #[test]
+    fn out_of_spec_values() {
+        for kind in KINDS {
+            let mut builder = Builder::new(kind);
+            builder.sections.push(Section::new(".data", 1, vec![1; 4]));
+            let mut input = builder.build();
+            input[..4].copy_from_slice(b"\x7fELG");
+            input[6] = 2;
+            input[9] = 1;
+            input[20..24].copy_from_slice(&kind.word(0));
+            let shnum = header_field(kind, "e_shnum");
+            let (header_size, section_header_size) = (shnum - 8, shnum - 2);
+            input[header_size..header_size + 2].copy_from_slice(&kind.half(48));
+            input[shnum + 2..shnum + 4].copy_from_slice(&kind.half(7));
+            // The entries keep their size, the table has room for more fields than we read
+            let entry_size = kind.section_header_size() as u16;
+            let table = input.len() - 3 * entry_size as usize;
+            let end = input.len();
+            input.splice(
+                table..end,
+                (0..3)
+                    .flat_map(|index| {
+                        let start = table + index * entry_size as usize;
+                        let mut entry = input[start..start + entry_size as usize].to_vec();
+                        entry.extend([0; 8]);
+                        entry
+                    })
+                    .collect::<Vec<_>>(),
+            );
+            input[section_header_size..section_header_size + 2]
+                .copy_from_slice(&kind.half(entry_size + 8));
+            let elf = ElfFormat::new(&input).unwrap();
+            assert_eq!(
+                elf.warnings,
+                [
+                    "Magic is \"\\x7fELG\" instead of \"\\x7fELF\", this might not be an ELF file"
+                        .to_string(),
+                    "ei_version is 2 instead of 1".to_string(),
+                    "e_version is 0 instead of 1".to_string(),
+                    format!("eh_size is 48 instead of {}", kind.header_size()),
+                    format!(
+                        "sh_entry_size is {} instead of {}",
+                        entry_size + 8,
+                        entry_size
+                    ),
+                    "Section name table 7 is past the last section".to_string(),
+                    "Padding after the identification bytes isn't zero".to_string(),
+                ],
+                "{:?}",
+                kind
+            );
+            assert_eq!(elf.sections.headers.len(), 3);
+        }
+    }
+
+    #[test]
+    fn machine_flags() {
+        assert_eq!(
+            flags_description(EM_ARM, 0x0500_0400),
+            "EABI version 5, EF_ARM_ABI_FLOAT_HARD"
+        );
+        assert_eq!(
+            flags_description(EM_RISCV, 0x5),
+            "EF_RISCV_FLOAT_ABI_DOUBLE, EF_RISCV_RVC"
+        );
+        assert_eq!(
+            flags_description(EM_MIPS, 0x7000_1007),
+            "EF_MIPS_ARCH_32R2, EF_MIPS_ABI_O32, EF_MIPS_NOREORDER | EF_MIPS_PIC | EF_MIPS_CPIC"
+        );
+        assert_eq!(flags_description(EM_PPC64, 0x2), "ELFv2 ABI");
+        assert_eq!(flags_description(EM_X86_64, 0), "none");
+        assert_eq!(flags_description(EM_X86_64, 0x10), "0x10");
+        assert_eq!(machine_name(EM_LOONGARCH), "EM_LOONGARCH");
+        assert_eq!(os_abi_name(3), "ELFOSABI_LINUX");
+    }
+}
@@ -0,0 +1,633 @@
+use nom::{
+    error::{Error as NomError, ErrorKind},
+    IResult,
+};
+
+use super::{at, flag_names, hex_bytes, string_at, Confidence, Readers};
+use crate::{
+    error::Error,
+    tools::format_explorer::{FileFormatUi, FormatContext, Layout},
+};
+
+mod dynamic;
+mod header;
+mod notes;
+mod relocations;
+mod sections;
+mod segments;
+mod symbols;
+
+pub use dynamic::DynamicEntry;
+pub use header::{FileHeader, Header, Identification};
+pub use notes::Note;
+pub use relocations::RelocationTable;
+pub use sections::{SectionHeader, SectionTable};
+pub use segments::ProgramHeader;
+pub use symbols::SymbolTable;
+
+/// Checks the magic and that the class and byte order are ones we know
+pub fn probe(bytes: &[u8]) -> Confidence {
+    if !bytes.starts_with(&header::ELF_MAGIC) {
+        return Confidence::None;
+    }
+    match (bytes.get(4), bytes.get(5)) {
+        (Some(1 | 2), Some(1 | 2)) => Confidence::High,
+        _ => Confidence::Low,
+    }
+}
+
+/// Parses `count` entries of `size` bytes each, starting at `offset`. Tables that don't fit in
+/// the file are common in stripped and carved files, so they give a warning instead of failing
+/// the whole file.
+fn table<'a, T>(
+    input: &'a [u8],
+    what: &str,
+    offset: u64,
+    count: u64,
+    size: u16,
+    parse: impl Fn(&'a [u8]) -> IResult<&'a [u8], T>,
+) -> Result<Vec<T>, String> {
+    if count > 0 && size == 0 {
+        return Err(format!("The {} table has entries of size 0", what));
+    }
+    // Checked up front so a bogus count fails before parsing millions of entries
+    let end = offset.saturating_add(count.saturating_mul(size as u64));
+    if count > 0 && end > input.len() as u64 {
+        return Err(format!(
+            "The {} table at {:#x} with {} entries of {} bytes goes past the end of the file",
+            what, offset, count, size
+        ));
+    }
+    (0..count)
+        .map(|index| {
+            let start = offset.saturating_add(index * size as u64);
+            at(input, start)
+                .and_then(&parse)
+                .map(|(_, entry)| entry)
+                .map_err(|_| format!("Entry {} of the {} table is invalid", index, what))
+        })
+        .collect()
+}
+
+/// `e_phnum` value meaning the real count is in the first section header
+const PN_XNUM: u16 = 0xffff;
+/// `e_shstrndx` value meaning the real index is in the first section header
+const SHN_XINDEX: u16 = 0xffff;
+
+pub struct ElfFormat {
+    pub header: Header,
+    pub program_headers: Vec<ProgramHeader>,
+    pub sections: SectionTable,
+    pub symbol_table: SymbolTable,
+    pub dynamic: Vec<DynamicEntry>,
+    pub relocations: Vec<RelocationTable>,
+    pub notes: Vec<Note>,
+    /// Header values that are out of spec
+    pub warnings: Vec<String>,
+}
+
+impl FileFormatUi for ElfFormat {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        ui.collapsing(name, |ui| {
+            for warning in &self.warnings {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+            let header = self.header.fields();
+            self.header
+                .ui(ui, "header", &context.at(0, self.header.size()));
+            let title = format!("program_headers ({})", self.program_headers.len());
+            let entry_size = u64::from(header.ph_entry_size);
+            let table = context.at(
+                header.ph_offset,
+                entry_size.saturating_mul(self.program_headers.len() as u64),
+            );
+            table.collapsing(ui, &title, |ui| {
+                for (index, header) in self.program_headers.iter_mut().enumerate() {
+                    let entry = table.entry(index, entry_size);
+                    header.ui(ui, &format!("[{}]", index), &entry);
+                }
+            });
+            let size = u64::from(header.sh_entry_size) * self.sections.headers.len() as u64;
+            let section_headers = context.at(header.sh_offset, size);
+            self.sections.ui(ui, "section_headers", &section_headers);
+            self.symbol_table.ui(ui, "symbols", context);
+            self.dynamic.ui(ui, "dynamic", context);
+            let title = format!("relocations ({})", self.relocations.len());
+            ui.collapsing(title, |ui| {
+                for table in &mut self.relocations {
+                    let name = table.section_name.clone();
+                    table.ui(ui, &name, context);
+                }
+            });
+            self.notes.ui(ui, "notes", context);
+        });
+    }
+}
+
+impl ElfFormat {
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (_, ident) = Identification::parse(input, false)?;
+        // Offsets of the class and data bytes, to point at them in errors
+        let unsupported =
+            |offset: usize| nom::Err::Failure(NomError::new(&input[offset..], ErrorKind::Switch));
+        let big_endian = match ident.data {
+            1 => false,
+            2 => true,
+            _ => return Err(unsupported(5)),
+        };
+        let is_64 = match ident.class {
+            1 => false,
+            2 => true,
+            _ => return Err(unsupported(4)),
+        };
+        let readers = Readers::new(big_endian, is_64);
+        let (tail, header) = Header::parse(input, is_64, big_endian)?;
+        let FileHeader {
+            machine,
+            ph_offset,
+            sh_offset,
+            ph_entry_size,
+            ph_entry_num,
+            sh_entry_size,
+            sh_entry_num,
+            sh_str_offset,
+            ..
+        } = header.fields();
+        let mut table_warnings = Vec::new();
+        // Counts that don't fit in the header are stored in the first section header
+        let first_section = match sh_offset {
+            0 => None,
+            _ => at(input, sh_offset)
+                .and_then(|input| SectionHeader::parse(input, readers))
+                .map(|(_, first)| first)
+                .map_err(|_| {
+                    table_warnings.push(format!(
+                        "The section header table at {:#x} is past the end of the file",
+                        sh_offset
+                    ))
+                })
+                .ok(),
+        };
+        let section_count = match &first_section {
+            Some(first) if sh_entry_num == 0 => first.size,
+            _ if sh_offset != 0 && first_section.is_none() => 0,
+            _ => sh_entry_num as u64,
+        };
+        let string_table = match &first_section {
+            Some(first) if sh_str_offset == SHN_XINDEX => first.link as usize,
+            _ => sh_str_offset as usize,
+        };
+        let program_header_count = match &first_section {
+            Some(first) if ph_entry_num == PN_XNUM => first.info as u64,
+            _ => ph_entry_num as u64,
+        };
+        let program_headers = table(
+            input,
+            "program header",
+            ph_offset,
+            program_header_count,
+            ph_entry_size,
+            |input| ProgramHeader::parse(input, readers),
+        )
+        .unwrap_or_else(|warning| {
+            table_warnings.push(warning);
+            Vec::new()
+        });
+        let mut section_headers = table(
+            input,
+            "section header",
+            sh_offset,
+            section_count,
+            sh_entry_size,
+            |input| SectionHeader::parse(input, readers),
+        )
+        .unwrap_or_else(|warning| {
+            table_warnings.push(warning);
+            Vec::new()
+        });
+        sections::resolve(&mut section_headers, input, string_table);
+        let symbols = symbols::parse_symbols(input, readers, &section_headers);
+        let dynamic = dynamic::parse_dynamic(input, readers, &section_headers, &program_headers);
+        let relocations = relocations::parse_relocations(input, readers, &section_headers, machine);
+        let notes = notes::parse_notes(input, readers, &section_headers, &program_headers);
+        let mut elf = Self {
+            header,
+            program_headers,
+            sections: SectionTable::new(section_headers, sh_entry_size),
+            symbol_table: SymbolTable::new(symbols),
+            dynamic,
+            relocations,
+            notes,
+            warnings: Vec::new(),
+        };
+        elf.warnings = header::warnings(&elf, string_table);
+        elf.warnings.extend(table_warnings);
+        Ok((tail, elf))
+    }
+
+    /// Header fields under their names from the ELF specification and the file offsets of
+    /// symbols, for use in expressions
+    pub fn symbols(&self) -> Vec<(String, u64)> {
+        let header = self.header.fields();
+        [
+            ("e_type", header.type_ as u64),
+            ("e_machine", header.machine as u64),
+            ("e_version", header.e_version as u64),
+            ("e_entry", header.entry),
+            ("e_phoff", header.ph_offset),
+            ("e_shoff", header.sh_offset),
+            ("e_flags", header.flags.bits as u64),
+            ("e_ehsize", header.eh_size as u64),
+            ("e_phentsize", header.ph_entry_size as u64),
+            ("e_phnum", header.ph_entry_num as u64),
+            ("e_shentsize", header.sh_entry_size as u64),
+            ("e_shnum", header.sh_entry_num as u64),
+            ("e_shstrndx", header.sh_str_offset as u64),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .chain(self.symbol_table.symbols.iter().flat_map(|symbol| {
+            // Demangled names work too, as long as they don't have generics or parameters
+            let offset = symbol.file_offset;
+            let names = std::iter::once(symbol.name.clone()).chain(symbol.demangled.clone());
+            names.filter_map(move |name| Some((name, offset?)))
+        }))
+        .collect()
+    }
+
+    pub fn new(bytes: &[u8]) -> Result<Self, Error> {
+        Self::parse(bytes)
+            .map(|(_, elf)| elf)
+            .map_err(|err| Error::from_nom("ELF", bytes, err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! The files are built by [`Builder`] in each class and byte order, with a header, program
+    //! headers, the sections' contents and the section header table in that order. The other
+    //! modules' tests build theirs with it too.
+
+    use super::*;
+
+    /// Class and byte order of each kind of file, with a machine that uses it
+    pub(super) const KINDS: [Kind; 4] = [
+        Kind {
+            is_64: false,
+            big_endian: false,
+            machine: header::EM_ARM,
+        },
+        Kind {
+            is_64: true,
+            big_endian: false,
+            machine: header::EM_X86_64,
+        },
+        Kind {
+            is_64: false,
+            big_endian: true,
+            machine: EM_PPC,
+        },
+        Kind {
+            is_64: true,
+            big_endian: true,
+            machine: header::EM_PPC64,
+        },
+    ];
+
+    const EM_PPC: u16 = 20;
+    pub(super) const SHF_ALLOC: u64 = 0x2;
+
+    /// Encodes values in the class and byte order of a file
+    #[derive(Clone, Copy, Debug)]
+    pub(super) struct Kind {
+        pub is_64: bool,
+        pub big_endian: bool,
+        pub machine: u16,
+    }
+
+    impl Kind {
+        pub fn half(self, value: u16) -> Vec<u8> {
+            match self.big_endian {
+                true => value.to_be_bytes().to_vec(),
+                false => value.to_le_bytes().to_vec(),
+            }
+        }
+
+        pub fn word(self, value: u32) -> Vec<u8> {
+            match self.big_endian {
+                true => value.to_be_bytes().to_vec(),
+                false => value.to_le_bytes().to_vec(),
+            }
+        }
+
+        /// 4 or 8 bytes depending on the class
+        pub fn address(self, value: u64) -> Vec<u8> {
+            match (self.is_64, self.big_endian) {
+                (true, true) => value.to_be_bytes().to_vec(),
+                (true, false) => value.to_le_bytes().to_vec(),
+                (false, _) => self.word(value as u32),
+            }
+        }
+
+        /// A symbol table entry, whose fields are in another order in 64-bit files
+        pub fn symbol(self, name: u32, value: u64, size: u64, info: u8, section: u16) -> Vec<u8> {
+            let (name, section) = (self.word(name), self.half(section));
+            let (value, size) = (self.address(value), self.address(size));
+            if self.is_64 {
+                [&name[..], &[info, 0], &section, &value, &size].concat()
+            } else {
+                [&name[..], &value, &size, &[info, 0], &section].concat()
+            }
+        }
+
+        pub fn header_size(self) -> usize {
+            if self.is_64 {
+                64
+            } else {
+                52
+            }
+        }
+
+        pub fn program_header_size(self) -> usize {
+            if self.is_64 {
+                56
+            } else {
+                32
+            }
+        }
+
+        pub fn section_header_size(self) -> usize {
+            if self.is_64 {
+                64
+            } else {
+                40
+            }
+        }
+    }
+
+    pub(super) struct Section {
+        pub name: &'static str,
+        pub type_: u32,
+        pub flags: u64,
+        pub link: u32,
+        pub info: u32,
+        pub align: u64,
+        pub entry_size: u64,
+        pub data: Vec<u8>,
+    }
+
+    impl Section {
+        pub fn new(name: &'static str, type_: u32, data: Vec<u8>) -> Self {
+            Self {
+                name,
+                type_,
+                flags: 0,
+                link: 0,
+                info: 0,
+                align: 1,
+                entry_size: 0,
+                data,
+            }
+        }
+    }
+
+    /// A segment over the contents of some of the sections, by their index, aligned like the
+    /// first one
+    pub(super) struct Segment {
+        pub type_: u32,
+        pub flags: u32,
+        pub sections: std::ops::RangeInclusive<usize>,
+    }
+
+    pub(super) struct Builder {
+        pub kind: Kind,
+        /// Sections from index 1, `.shstrtab` is added after them
+        pub sections: Vec<Section>,
+        pub segments: Vec<Segment>,
+    }
+
+    /// Where the contents of each section go, from index 0, and where the file ends
+    fn layout(builder: &Builder, names_size: usize) -> (Vec<usize>, usize) {
+        let start = builder.kind.header_size()
+            + builder.segments.len() * builder.kind.program_header_size();
+        let mut offsets = vec![0];
+        let mut position = start;
+        let sizes = builder.sections.iter().map(|section| section.data.len());
+        for size in sizes.chain([names_size]) {
+            position = position.next_multiple_of(8);
+            offsets.push(position);
+            position += size;
+        }
+        (offsets, position.next_multiple_of(8))
+    }
+
+    impl Builder {
+        pub fn new(kind: Kind) -> Self {
+            Self {
+                kind,
+                sections: Vec::new(),
+                segments: Vec::new(),
+            }
+        }
+
+        /// Where the contents of the section at `index` go in the file, allocated sections are
+        /// also loaded at that address. It moves with the program headers, so add the segments
+        /// first.
+        pub fn offset_of(&self, index: usize) -> u64 {
+            let names_size = self.names().0.len();
+            layout(self, names_size).0[index] as u64
+        }
+
+        /// `.shstrtab` and where each name is in it
+        fn names(&self) -> (Vec<u8>, Vec<u32>) {
+            let mut strings = vec![0];
+            let mut offsets = Vec::new();
+            let names = self.sections.iter().map(|section| section.name);
+            for name in names.chain([".shstrtab"]) {
+                offsets.push(strings.len() as u32);
+                strings.extend(name.as_bytes());
+                strings.push(0);
+            }
+            (strings, offsets)
+        }
+
+        pub fn build(&self) -> Vec<u8> {
+            let kind = self.kind;
+            let (names, name_offsets) = self.names();
+            let (offsets, table_offset) = layout(self, names.len());
+            let section_count = self.sections.len() + 2;
+            let mut file = Vec::new();
+            file.extend(header::ELF_MAGIC);
+            file.extend([1 + kind.is_64 as u8, 1 + kind.big_endian as u8, 1]);
+            file.resize(16, 0);
+            file.extend(kind.half(3));
+            file.extend(kind.half(kind.machine));
+            file.extend(kind.word(1));
+            file.extend(kind.address(0));
+            let ph_offset = if self.segments.is_empty() {
+                0
+            } else {
+                kind.header_size()
+            };
+            file.extend(kind.address(ph_offset as u64));
+            file.extend(kind.address(table_offset as u64));
+            file.extend(kind.word(0));
+            file.extend(kind.half(kind.header_size() as u16));
+            file.extend(kind.half(kind.program_header_size() as u16));
+            file.extend(kind.half(self.segments.len() as u16));
+            file.extend(kind.half(kind.section_header_size() as u16));
+            file.extend(kind.half(section_count as u16));
+            file.extend(kind.half(section_count as u16 - 1));
+
+            let range = |segment: &Segment| {
+                let first = *segment.sections.start();
+                let last = *segment.sections.end();
+                let end = offsets[last] + self.sections[last - 1].data.len();
+                (offsets[first] as u64, (end - offsets[first]) as u64)
+            };
+            for segment in &self.segments {
+                let (offset, size) = range(segment);
+                let mut fields = vec![kind.word(segment.type_)];
+                if kind.is_64 {
+                    fields.push(kind.word(segment.flags));
+                }
+                fields.extend([offset, offset, offset, size, size].map(|v| kind.address(v)));
+                if !kind.is_64 {
+                    fields.push(kind.word(segment.flags));
+                }
+                let first = &self.sections[segment.sections.start() - 1];
+                fields.push(kind.address(first.align));
+                file.extend(fields.concat());
+            }
+
+            let contents = self.sections.iter().map(|section| &section.data);
+            for (index, data) in contents.chain([&names]).enumerate() {
+                file.resize(offsets[index + 1], 0);
+                file.extend(data);
+            }
+            file.resize(table_offset, 0);
+
+            file.extend(vec![0; kind.section_header_size()]);
+            let names_section = Section::new(".shstrtab", 3, names.clone());
+            for (index, section) in self.sections.iter().chain([&names_section]).enumerate() {
+                let offset = offsets[index + 1] as u64;
+                let address = if section.flags & SHF_ALLOC != 0 {
+                    offset
+                } else {
+                    0
+                };
+                file.extend(
+                    [
+                        kind.word(name_offsets[index]),
+                        kind.word(section.type_),
+                        kind.address(section.flags),
+                        kind.address(address),
+                        kind.address(offset),
+                        kind.address(section.data.len() as u64),
+                        kind.word(section.link),
+                        kind.word(section.info),
+                        kind.address(section.align),
+                        kind.address(section.entry_size),
+                    ]
+                    .concat(),
+                );
+            }
+            file
+        }
+    }
+
+    /// Offsets of the header fields that say where the tables are
+    pub(super) fn header_field(kind: Kind, name: &str) -> usize {
+        let address = if kind.is_64 { 8 } else { 4 };
+        let ph_offset = 24 + address;
+        match name {
+            "e_phoff" => ph_offset,
+            "e_shoff" => ph_offset + address,
+            "e_phnum" => ph_offset + 2 * address + 8,
+            "e_shnum" => ph_offset + 2 * address + 12,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn tables_outside_the_file() {
+        for kind in KINDS {
+            let mut builder = Builder::new(kind);
+            builder.sections.push(Section::new(".data", 1, vec![1; 16]));
+            builder.segments.push(Segment {
+                type_: segments::PT_LOAD,
+                flags: 4,
+                sections: 1..=1,
+            });
+            let mut input = builder.build();
+            assert_eq!(probe(&input), Confidence::High);
+            let elf = ElfFormat::new(&input).unwrap();
+            assert!(elf.warnings.is_empty(), "{:?} {:?}", kind, elf.warnings);
+            assert_eq!(elf.program_headers.len(), 1);
+            assert_eq!(elf.sections.headers.len(), 3);
+
+            // A program header count past the end of the file, and a section table offset too
+            let phnum = header_field(kind, "e_phnum");
+            input[phnum..phnum + 2].copy_from_slice(&kind.half(1000));
+            let shoff = header_field(kind, "e_shoff");
+            let address_size = if kind.is_64 { 8 } else { 4 };
+            input[shoff..shoff + address_size].copy_from_slice(&kind.address(0x10_0000));
+            let elf = ElfFormat::new(&input).unwrap();
+            let phoff = kind.header_size();
+            assert_eq!(
+                elf.warnings,
+                [
+                    "The section header table at 0x100000 is past the end of the file".to_string(),
+                    format!(
+                        "The program header table at {:#x} with 1000 entries of {} bytes goes \
+                         past the end of the file",
+                        phoff,
+                        kind.program_header_size()
+                    ),
+                ]
+            );
+            assert!(elf.program_headers.is_empty() && elf.sections.headers.is_empty());
+        }
+    }
+
+    #[test]
+    fn counts_in_the_first_section() {
+        for kind in KINDS {
+            let mut builder = Builder::new(kind);
+            builder.sections.push(Section::new(".data", 1, vec![1; 16]));
+            builder.segments.push(Segment {
+                type_: segments::PT_LOAD,
+                flags: 4,
+                sections: 1..=1,
+            });
+            let mut input = builder.build();
+            let table = ElfFormat::new(&input).unwrap().header.fields().sh_offset as usize;
+            // PN_XNUM and a section count of 0 move the counts to sh_info and sh_size
+            let phnum = header_field(kind, "e_phnum");
+            input[phnum..phnum + 2].copy_from_slice(&kind.half(PN_XNUM));
+            let shnum = header_field(kind, "e_shnum");
+            input[shnum..shnum + 2].copy_from_slice(&kind.half(0));
+            let (size, info) = if kind.is_64 { (32, 44) } else { (20, 28) };
+            let address_size = if kind.is_64 { 8 } else { 4 };
+            input[table + size..table + size + address_size].copy_from_slice(&kind.address(3));
+            input[table + info..table + info + 4].copy_from_slice(&kind.word(1));
+            let elf = ElfFormat::new(&input).unwrap();
+            assert!(elf.warnings.is_empty(), "{:?} {:?}", kind, elf.warnings);
+            assert_eq!(elf.program_headers.len(), 1);
+            assert_eq!(elf.sections.headers.len(), 3);
+            assert_eq!(elf.sections.headers[1].name, ".data");
+        }
+    }
+
+    #[test]
+    fn unknown_class_or_byte_order() {
+        let input = Builder::new(KINDS[0]).build();
+        for offset in [4, 5] {
+            let mut input = input.clone();
+            input[offset] = 3;
+            assert_eq!(probe(&input), Confidence::Low);
+            assert!(ElfFormat::new(&input).is_err());
+        }
+        assert_eq!(probe(b"\x7fEL"), Confidence::None);
+    }
+}
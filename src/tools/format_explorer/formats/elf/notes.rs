@@ -0,0 +1,353 @@
+use nom::{bytes::complete::take, sequence::Tuple, IResult};
+
+use super::{
+    sections::SectionHeader,
+    segments::{ProgramHeader, PT_NOTE},
+    Readers,
+};
+use crate::tools::format_explorer::{text_field, FileFormatUi, FormatContext};
+
+const SHT_NOTE: u32 = 7;
+
+const NT_GNU_ABI_TAG: u32 = 1;
+const NT_GNU_HWCAP: u32 = 2;
+const NT_GNU_BUILD_ID: u32 = 3;
+const NT_GNU_GOLD_VERSION: u32 = 4;
+const NT_GNU_PROPERTY_TYPE_0: u32 = 5;
+
+pub struct Note {
+    /// Where the note starts in the file
+    pub offset: u64,
+    /// Bytes up to the next note
+    pub size: u64,
+    pub owner: String,
+    pub name_size: u32,
+    pub type_: u32,
+    pub descriptor: Vec<u8>,
+    /// Where the descriptor is in the file
+    pub descriptor_offset: u64,
+    /// Section or segment the note was found in
+    pub source: String,
+    /// Readable descriptor for the notes we know, hex bytes for the others
+    pub description: String,
+}
+
+/// Rounds up to the note alignment, 4 or 8 bytes
+fn align(value: usize, alignment: usize) -> usize {
+    value.div_ceil(alignment) * alignment
+}
+
+impl Note {
+    /// Returns the note and the offset of its descriptor in `input`
+    fn parse(input: &[u8], readers: Readers, alignment: usize) -> IResult<&[u8], (Self, usize)> {
+        let (tail, (name_size, descriptor_size, type_)) =
+            (readers.word, readers.word, readers.word).parse(input)?;
+        let (_, name) = take(name_size as usize)(tail)?;
+        // Padding is relative to the start of the note, after the 12-byte header
+        let descriptor_start = align(12 + name_size as usize, alignment).min(input.len());
+        let (_, descriptor) = take(descriptor_size as usize)(&input[descriptor_start..])?;
+        let end = align(descriptor_start + descriptor_size as usize, alignment);
+        let tail = &input[end.min(input.len())..];
+        let owner = String::from_utf8_lossy(name)
+            .trim_end_matches('\0')
+            .to_string();
+        Ok((
+            tail,
+            (
+                Self {
+                    offset: 0,
+                    size: (input.len() - tail.len()) as u64,
+                    owner,
+                    name_size,
+                    type_,
+                    descriptor: descriptor.to_vec(),
+                    descriptor_offset: 0,
+                    source: String::new(),
+                    description: String::new(),
+                },
+                descriptor_start,
+            ),
+        ))
+    }
+
+    pub fn type_name(&self) -> String {
+        let name = match (self.owner.as_str(), self.type_) {
+            ("GNU", NT_GNU_ABI_TAG) => "NT_GNU_ABI_TAG",
+            ("GNU", NT_GNU_HWCAP) => "NT_GNU_HWCAP",
+            ("GNU", NT_GNU_BUILD_ID) => "NT_GNU_BUILD_ID",
+            ("GNU", NT_GNU_GOLD_VERSION) => "NT_GNU_GOLD_VERSION",
+            ("GNU", NT_GNU_PROPERTY_TYPE_0) => "NT_GNU_PROPERTY_TYPE_0",
+            ("Go", 4) => "NT_GO_BUILD_ID",
+            ("FreeBSD", 1) => "NT_FREEBSD_ABI_TAG",
+            ("CORE", 1) => "NT_PRSTATUS",
+            ("CORE", 2) => "NT_FPREGSET",
+            ("CORE", 3) => "NT_PRPSINFO",
+            ("CORE", 6) => "NT_AUXV",
+            ("CORE", 0x4649_4c45) => "NT_FILE",
+            ("CORE", 0x5349_4749) => "NT_SIGINFO",
+            _ => return format!("{:#x}", self.type_),
+        };
+        name.to_string()
+    }
+
+    fn describe(&self, readers: Readers) -> String {
+        let hex = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        };
+        match (self.owner.as_str(), self.type_) {
+            ("GNU", NT_GNU_ABI_TAG) => {
+                let words = self
+                    .descriptor
+                    .chunks_exact(4)
+                    .filter_map(|word| Some((readers.word)(word).ok()?.1))
+                    .collect::<Vec<_>>();
+                let [os, major, minor, subminor, ..] = words[..] else {
+                    return hex(&self.descriptor);
+                };
+                let os = match os {
+                    0 => "Linux".to_string(),
+                    1 => "Hurd".to_string(),
+                    2 => "Solaris".to_string(),
+                    3 => "FreeBSD".to_string(),
+                    os => format!("OS {}", os),
+                };
+                format!("{} {}.{}.{}", os, major, minor, subminor)
+            }
+            ("GNU", NT_GNU_BUILD_ID) | ("Go", 4) => hex(&self.descriptor),
+            ("GNU", NT_GNU_GOLD_VERSION) => String::from_utf8_lossy(&self.descriptor)
+                .trim_end_matches('\0')
+                .to_string(),
+            _ => {
+                // Long descriptors are cut short, the link selects all of it
+                let shown = self.descriptor.len().min(32);
+                let ellipsis = if shown < self.descriptor.len() {
+                    "…"
+                } else {
+                    ""
+                };
+                format!("{}{}", hex(&self.descriptor[..shown]), ellipsis)
+            }
+        }
+    }
+}
+
+/// Parses the notes of the `SHT_NOTE` sections, or of the `PT_NOTE` segments if there are no
+/// sections
+pub(super) fn parse_notes(
+    input: &[u8],
+    readers: Readers,
+    sections: &[SectionHeader],
+    program_headers: &[ProgramHeader],
+) -> Vec<Note> {
+    // (name, offset, contents, alignment) of each place notes are stored
+    let mut sources = sections
+        .iter()
+        .filter(|section| section.type_ == SHT_NOTE)
+        .filter_map(|section| {
+            let data = section.data(input)?;
+            Some((
+                section.name.clone(),
+                section.offset,
+                data,
+                section.address_align,
+            ))
+        })
+        .collect::<Vec<_>>();
+    if sources.is_empty() {
+        sources = program_headers
+            .iter()
+            .enumerate()
+            .filter(|(_, header)| header.type_ == PT_NOTE)
+            .filter_map(|(index, header)| {
+                let data = header.data(input)?;
+                Some((
+                    format!("PT_NOTE [{}]", index),
+                    header.offset,
+                    data,
+                    header.align,
+                ))
+            })
+            .collect();
+    }
+    let mut notes = Vec::new();
+    for (source, offset, data, alignment) in sources {
+        // Only 8-byte alignment changes the padding, anything else means 4
+        let alignment = if alignment == 8 { 8 } else { 4 };
+        let mut rest = data;
+        while !rest.is_empty() {
+            let Ok((tail, (mut note, descriptor_start))) = Note::parse(rest, readers, alignment)
+            else {
+                break;
+            };
+            let position = (data.len() - rest.len()) as u64;
+            note.offset = offset + position;
+            note.descriptor_offset = note.offset + descriptor_start as u64;
+            note.source = source.clone();
+            note.description = note.describe(readers);
+            notes.push(note);
+            rest = tail;
+        }
+    }
+    notes
+}
+
+impl FileFormatUi for Vec<Note> {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let title = format!("{} ({})", name, self.len());
+        ui.collapsing(title, |ui| {
+            for (index, note) in self.iter().enumerate() {
+                let title = format!("[{}] {} {}", index, note.owner, note.type_name());
+                let note_context = context.at(note.offset, note.size);
+                note_context.collapsing(ui, &title, |ui| {
+                    text_field(ui, "source", &note.source);
+                    let mut fields = note_context.fields();
+                    fields
+                        .next(4)
+                        .field(ui, "name_size", &note.name_size.to_string());
+                    let descriptor_size = note.descriptor.len() as u64;
+                    fields
+                        .next(4)
+                        .field(ui, "descriptor_size", &descriptor_size.to_string());
+                    fields.next(4).field(
+                        ui,
+                        "type",
+                        &format!("{} ({:#x})", note.type_name(), note.type_),
+                    );
+                    fields
+                        .next(note.name_size.into())
+                        .field(ui, "owner", &note.owner);
+                    context.at(note.descriptor_offset, descriptor_size).field(
+                        ui,
+                        "value",
+                        &note.description,
+                    );
+                });
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::tests::*, *};
+    use crate::tools::format_explorer::formats::elf::ElfFormat;
+
+    /// A note with its name and descriptor padded to `alignment`
+    fn note(kind: Kind, owner: &[u8], type_: u32, descriptor: &[u8], alignment: usize) -> Vec<u8> {
+        let mut note = [
+            kind.word(owner.len() as u32),
+            kind.word(descriptor.len() as u32),
+            kind.word(type_),
+            owner.to_vec(),
+        ]
+        .concat();
+        note.resize(align(note.len(), alignment), 0);
+        note.extend(descriptor);
+        note.resize(align(note.len(), alignment), 0);
+        note
+    }
+
+    /// Two GNU notes in a section and a core note aligned to 8 bytes in another, each loaded
+    /// by a PT_NOTE segment
+    fn builder(kind: Kind) -> Builder {
+        let mut builder = Builder::new(kind);
+        for sections in [1..=1, 2..=2] {
+            builder.segments.push(Segment {
+                type_: PT_NOTE,
+                flags: 4,
+                sections,
+            });
+        }
+        let abi_tag = [0, 3, 2, 0].map(|word| kind.word(word)).concat();
+        let notes = [
+            note(kind, b"GNU\0", NT_GNU_BUILD_ID, &[0xab, 0xcd, 0xef], 4),
+            note(kind, b"GNU\0", NT_GNU_ABI_TAG, &abi_tag, 4),
+        ]
+        .concat();
+        let mut section = Section::new(".note.gnu", SHT_NOTE, notes);
+        section.align = 4;
+        builder.sections.push(section);
+        let mut section =
+            Section::new(".note.core", SHT_NOTE, note(kind, b"CORE\0", 1, &[7; 4], 8));
+        section.align = 8;
+        builder.sections.push(section);
+        builder
+    }
+
+    fn check(kind: Kind, builder: &Builder, notes: &[Note], sources: [&str; 2]) {
+        let described = notes
+            .iter()
+            .map(|note| {
+                (
+                    note.source.as_str(),
+                    note.type_name(),
+                    note.description.as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            described,
+            [
+                (sources[0], "NT_GNU_BUILD_ID".to_string(), "abcdef"),
+                (sources[0], "NT_GNU_ABI_TAG".to_string(), "Linux 3.2.0"),
+                (sources[1], "NT_PRSTATUS".to_string(), "07070707"),
+            ],
+            "{:?}",
+            kind
+        );
+        let (gnu, core) = (builder.offset_of(1), builder.offset_of(2));
+        let offsets = notes
+            .iter()
+            .map(|note| (note.offset, note.descriptor_offset, note.size));
+        assert_eq!(
+            offsets.collect::<Vec<_>>(),
+            [
+                (gnu, gnu + 16, 20),
+                (gnu + 20, gnu + 36, 32),
+                (core, core + 24, 32)
+            ]
+        );
+        assert_eq!((notes[2].owner.as_str(), notes[2].name_size), ("CORE", 5));
+    }
+
+    #[test]
+    fn note_sections() {
+        for kind in KINDS {
+            let builder = builder(kind);
+            let elf = ElfFormat::new(&builder.build()).unwrap();
+            assert!(elf.warnings.is_empty(), "{:?} {:?}", kind, elf.warnings);
+            check(kind, &builder, &elf.notes, [".note.gnu", ".note.core"]);
+        }
+    }
+
+    #[test]
+    fn note_segments() {
+        for kind in KINDS {
+            let builder = builder(kind);
+            let mut input = builder.build();
+            let shoff = header_field(kind, "e_shoff");
+            let address_size = if kind.is_64 { 8 } else { 4 };
+            input[shoff..shoff + address_size].copy_from_slice(&kind.address(0));
+            let shnum = header_field(kind, "e_shnum");
+            input[shnum..shnum + 2].copy_from_slice(&kind.half(0));
+            let elf = ElfFormat::new(&input).unwrap();
+            check(kind, &builder, &elf.notes, ["PT_NOTE [0]", "PT_NOTE [1]"]);
+        }
+    }
+
+    #[test]
+    fn cut_off_notes() {
+        let kind = KINDS[0];
+        let mut notes = note(kind, b"GNU\0", NT_GNU_BUILD_ID, &[1; 20], 4);
+        notes.truncate(notes.len() - 8);
+        let mut builder = Builder::new(kind);
+        builder
+            .sections
+            .push(Section::new(".note", SHT_NOTE, notes));
+        let elf = ElfFormat::new(&builder.build()).unwrap();
+        assert!(elf.notes.is_empty());
+    }
+}
@@ -0,0 +1,495 @@
+use egui::Vec2b;
+use egui_extras::Column;
+use nom::{sequence::Tuple, IResult};
+
+use super::{
+    header::{EM_AARCH64, EM_ARM, EM_RISCV, EM_X86_64},
+    sections::SectionHeader,
+    symbols, Readers,
+};
+use crate::tools::format_explorer::{FileFormatUi, FormatContext};
+
+const SHT_RELA: u32 = 4;
+const SHT_REL: u32 = 9;
+
+pub struct Relocation {
+    pub offset: u64,
+    pub type_: u32,
+    pub symbol_index: u64,
+    pub symbol: Option<String>,
+    /// Only `SHT_RELA` entries have an explicit addend
+    pub addend: Option<i64>,
+}
+
+impl Relocation {
+    fn parse(input: &[u8], readers: Readers, with_addend: bool) -> IResult<&[u8], Self> {
+        let address = |input| readers.address(input);
+        let (tail, (offset, info)) = (address, address).parse(input)?;
+        let (tail, addend) = if with_addend {
+            let (tail, addend) = address(tail)?;
+            let addend = if readers.is_64 {
+                addend as i64
+            } else {
+                addend as u32 as i32 as i64
+            };
+            (tail, Some(addend))
+        } else {
+            (tail, None)
+        };
+        // 32-bit files pack the symbol and type into a single word differently
+        let (symbol_index, type_) = if readers.is_64 {
+            (info >> 32, info as u32)
+        } else {
+            (info >> 8, info as u32 & 0xff)
+        };
+        Ok((
+            tail,
+            Self {
+                offset,
+                type_,
+                symbol_index,
+                symbol: None,
+                addend,
+            },
+        ))
+    }
+}
+
+/// A `SHT_REL` or `SHT_RELA` section
+pub struct RelocationTable {
+    pub section_name: String,
+    pub section_offset: u64,
+    pub section_size: u64,
+    pub entry_size: u64,
+    /// `e_machine` of the file, the relocation types depend on it
+    pub machine: u16,
+    pub relocations: Vec<Relocation>,
+}
+
+/// Parses every relocation section, resolving symbols through their linked symbol tables
+pub(super) fn parse_relocations(
+    input: &[u8],
+    readers: Readers,
+    sections: &[SectionHeader],
+    machine: u16,
+) -> Vec<RelocationTable> {
+    let tables = sections
+        .iter()
+        .filter(|section| section.type_ == SHT_REL || section.type_ == SHT_RELA);
+    let mut result = Vec::new();
+    for section in tables {
+        let with_addend = section.type_ == SHT_RELA;
+        let word_size = if readers.is_64 { 8 } else { 4 };
+        let minimum_size = if with_addend { 3 } else { 2 } * word_size;
+        let entry_size = (section.entry_size as usize).max(minimum_size);
+        let Some(data) = section.data(input) else {
+            continue;
+        };
+        let relocations = data
+            .chunks_exact(entry_size)
+            .filter_map(|entry| {
+                let (_, mut relocation) = Relocation::parse(entry, readers, with_addend).ok()?;
+                // Symbol 0 is the null symbol, used by relocations like `R_X86_64_RELATIVE`
+                if relocation.symbol_index != 0 {
+                    relocation.symbol = symbols::symbol_name(
+                        input,
+                        readers,
+                        sections,
+                        section.link,
+                        relocation.symbol_index,
+                    );
+                }
+                Some(relocation)
+            })
+            .collect();
+        result.push(RelocationTable {
+            section_name: section.name.clone(),
+            section_offset: section.offset,
+            section_size: section.size,
+            entry_size: entry_size as u64,
+            machine,
+            relocations,
+        });
+    }
+    result
+}
+
+/// Architecture specific `R_*` name of a relocation type
+pub fn relocation_type_name(machine: u16, type_: u32) -> String {
+    let name = match machine {
+        EM_X86_64 => x86_64_relocation(type_),
+        EM_AARCH64 => aarch64_relocation(type_),
+        EM_ARM => arm_relocation(type_),
+        EM_RISCV => riscv_relocation(type_),
+        _ => None,
+    };
+    name.map_or_else(|| type_.to_string(), str::to_string)
+}
+
+fn x86_64_relocation(type_: u32) -> Option<&'static str> {
+    Some(match type_ {
+        0 => "R_X86_64_NONE",
+        1 => "R_X86_64_64",
+        2 => "R_X86_64_PC32",
+        3 => "R_X86_64_GOT32",
+        4 => "R_X86_64_PLT32",
+        5 => "R_X86_64_COPY",
+        6 => "R_X86_64_GLOB_DAT",
+        7 => "R_X86_64_JUMP_SLOT",
+        8 => "R_X86_64_RELATIVE",
+        9 => "R_X86_64_GOTPCREL",
+        10 => "R_X86_64_32",
+        11 => "R_X86_64_32S",
+        12 => "R_X86_64_16",
+        13 => "R_X86_64_PC16",
+        14 => "R_X86_64_8",
+        15 => "R_X86_64_PC8",
+        16 => "R_X86_64_DTPMOD64",
+        17 => "R_X86_64_DTPOFF64",
+        18 => "R_X86_64_TPOFF64",
+        19 => "R_X86_64_TLSGD",
+        20 => "R_X86_64_TLSLD",
+        21 => "R_X86_64_DTPOFF32",
+        22 => "R_X86_64_GOTTPOFF",
+        23 => "R_X86_64_TPOFF32",
+        24 => "R_X86_64_PC64",
+        25 => "R_X86_64_GOTOFF64",
+        26 => "R_X86_64_GOTPC32",
+        27 => "R_X86_64_GOT64",
+        28 => "R_X86_64_GOTPCREL64",
+        29 => "R_X86_64_GOTPC64",
+        30 => "R_X86_64_GOTPLT64",
+        31 => "R_X86_64_PLTOFF64",
+        32 => "R_X86_64_SIZE32",
+        33 => "R_X86_64_SIZE64",
+        34 => "R_X86_64_GOTPC32_TLSDESC",
+        35 => "R_X86_64_TLSDESC_CALL",
+        36 => "R_X86_64_TLSDESC",
+        37 => "R_X86_64_IRELATIVE",
+        38 => "R_X86_64_RELATIVE64",
+        41 => "R_X86_64_GOTPCRELX",
+        42 => "R_X86_64_REX_GOTPCRELX",
+        _ => return None,
+    })
+}
+
+fn aarch64_relocation(type_: u32) -> Option<&'static str> {
+    Some(match type_ {
+        0 => "R_AARCH64_NONE",
+        257 => "R_AARCH64_ABS64",
+        258 => "R_AARCH64_ABS32",
+        259 => "R_AARCH64_ABS16",
+        260 => "R_AARCH64_PREL64",
+        261 => "R_AARCH64_PREL32",
+        262 => "R_AARCH64_PREL16",
+        263 => "R_AARCH64_MOVW_UABS_G0",
+        264 => "R_AARCH64_MOVW_UABS_G0_NC",
+        265 => "R_AARCH64_MOVW_UABS_G1",
+        266 => "R_AARCH64_MOVW_UABS_G1_NC",
+        267 => "R_AARCH64_MOVW_UABS_G2",
+        268 => "R_AARCH64_MOVW_UABS_G2_NC",
+        269 => "R_AARCH64_MOVW_UABS_G3",
+        274 => "R_AARCH64_ADR_PREL_LO21",
+        275 => "R_AARCH64_ADR_PREL_PG_HI21",
+        276 => "R_AARCH64_ADR_PREL_PG_HI21_NC",
+        277 => "R_AARCH64_ADD_ABS_LO12_NC",
+        278 => "R_AARCH64_LDST8_ABS_LO12_NC",
+        279 => "R_AARCH64_TSTBR14",
+        280 => "R_AARCH64_CONDBR19",
+        282 => "R_AARCH64_JUMP26",
+        283 => "R_AARCH64_CALL26",
+        284 => "R_AARCH64_LDST16_ABS_LO12_NC",
+        285 => "R_AARCH64_LDST32_ABS_LO12_NC",
+        286 => "R_AARCH64_LDST64_ABS_LO12_NC",
+        299 => "R_AARCH64_LDST128_ABS_LO12_NC",
+        311 => "R_AARCH64_ADR_GOT_PAGE",
+        312 => "R_AARCH64_LD64_GOT_LO12_NC",
+        1024 => "R_AARCH64_COPY",
+        1025 => "R_AARCH64_GLOB_DAT",
+        1026 => "R_AARCH64_JUMP_SLOT",
+        1027 => "R_AARCH64_RELATIVE",
+        1028 => "R_AARCH64_TLS_DTPMOD",
+        1029 => "R_AARCH64_TLS_DTPREL",
+        1030 => "R_AARCH64_TLS_TPREL",
+        1031 => "R_AARCH64_TLSDESC",
+        1032 => "R_AARCH64_IRELATIVE",
+        _ => return None,
+    })
+}
+
+fn arm_relocation(type_: u32) -> Option<&'static str> {
+    Some(match type_ {
+        0 => "R_ARM_NONE",
+        1 => "R_ARM_PC24",
+        2 => "R_ARM_ABS32",
+        3 => "R_ARM_REL32",
+        4 => "R_ARM_LDR_PC_G0",
+        5 => "R_ARM_ABS16",
+        6 => "R_ARM_ABS12",
+        7 => "R_ARM_THM_ABS5",
+        8 => "R_ARM_ABS8",
+        9 => "R_ARM_SBREL32",
+        10 => "R_ARM_THM_CALL",
+        11 => "R_ARM_THM_PC8",
+        17 => "R_ARM_TLS_DTPMOD32",
+        18 => "R_ARM_TLS_DTPOFF32",
+        19 => "R_ARM_TLS_TPOFF32",
+        20 => "R_ARM_COPY",
+        21 => "R_ARM_GLOB_DAT",
+        22 => "R_ARM_JUMP_SLOT",
+        23 => "R_ARM_RELATIVE",
+        24 => "R_ARM_GOTOFF32",
+        25 => "R_ARM_BASE_PREL",
+        26 => "R_ARM_GOT_BREL",
+        27 => "R_ARM_PLT32",
+        28 => "R_ARM_CALL",
+        29 => "R_ARM_JUMP24",
+        30 => "R_ARM_THM_JUMP24",
+        38 => "R_ARM_TARGET1",
+        40 => "R_ARM_V4BX",
+        41 => "R_ARM_TARGET2",
+        42 => "R_ARM_PREL31",
+        43 => "R_ARM_MOVW_ABS_NC",
+        44 => "R_ARM_MOVT_ABS",
+        47 => "R_ARM_THM_MOVW_ABS_NC",
+        48 => "R_ARM_THM_MOVT_ABS",
+        51 => "R_ARM_THM_JUMP19",
+        102 => "R_ARM_THM_JUMP11",
+        160 => "R_ARM_IRELATIVE",
+        _ => return None,
+    })
+}
+
+fn riscv_relocation(type_: u32) -> Option<&'static str> {
+    Some(match type_ {
+        0 => "R_RISCV_NONE",
+        1 => "R_RISCV_32",
+        2 => "R_RISCV_64",
+        3 => "R_RISCV_RELATIVE",
+        4 => "R_RISCV_COPY",
+        5 => "R_RISCV_JUMP_SLOT",
+        6 => "R_RISCV_TLS_DTPMOD32",
+        7 => "R_RISCV_TLS_DTPMOD64",
+        8 => "R_RISCV_TLS_DTPREL32",
+        9 => "R_RISCV_TLS_DTPREL64",
+        10 => "R_RISCV_TLS_TPREL32",
+        11 => "R_RISCV_TLS_TPREL64",
+        12 => "R_RISCV_TLSDESC",
+        16 => "R_RISCV_BRANCH",
+        17 => "R_RISCV_JAL",
+        18 => "R_RISCV_CALL",
+        19 => "R_RISCV_CALL_PLT",
+        20 => "R_RISCV_GOT_HI20",
+        21 => "R_RISCV_TLS_GOT_HI20",
+        22 => "R_RISCV_TLS_GD_HI20",
+        23 => "R_RISCV_PCREL_HI20",
+        24 => "R_RISCV_PCREL_LO12_I",
+        25 => "R_RISCV_PCREL_LO12_S",
+        26 => "R_RISCV_HI20",
+        27 => "R_RISCV_LO12_I",
+        28 => "R_RISCV_LO12_S",
+        29 => "R_RISCV_TPREL_HI20",
+        30 => "R_RISCV_TPREL_LO12_I",
+        31 => "R_RISCV_TPREL_LO12_S",
+        32 => "R_RISCV_TPREL_ADD",
+        33 => "R_RISCV_ADD8",
+        34 => "R_RISCV_ADD16",
+        35 => "R_RISCV_ADD32",
+        36 => "R_RISCV_ADD64",
+        37 => "R_RISCV_SUB8",
+        38 => "R_RISCV_SUB16",
+        39 => "R_RISCV_SUB32",
+        40 => "R_RISCV_SUB64",
+        43 => "R_RISCV_ALIGN",
+        44 => "R_RISCV_RVC_BRANCH",
+        45 => "R_RISCV_RVC_JUMP",
+        51 => "R_RISCV_RELAX",
+        52 => "R_RISCV_SUB6",
+        53 => "R_RISCV_SET6",
+        54 => "R_RISCV_SET8",
+        55 => "R_RISCV_SET16",
+        56 => "R_RISCV_SET32",
+        57 => "R_RISCV_32_PCREL",
+        58 => "R_RISCV_IRELATIVE",
+        59 => "R_RISCV_PLT32",
+        60 => "R_RISCV_SET_ULEB128",
+        61 => "R_RISCV_SUB_ULEB128",
+        _ => return None,
+    })
+}
+
+impl FileFormatUi for RelocationTable {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let title = format!("{} ({})", name, self.relocations.len());
+        ui.collapsing(title, |ui| {
+            context.range_link(ui, "section bytes", self.section_offset, self.section_size);
+            ui.push_id(name, |ui| {
+                egui_extras::TableBuilder::new(ui)
+                    .striped(true)
+                    .resizable(false)
+                    .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                    .columns(Column::auto(), 3)
+                    .column(Column::remainder().clip(true))
+                    .vscroll(false)
+                    .auto_shrink(Vec2b::new(false, true))
+                    .header(20.0, |mut header| {
+                        for title in ["Offset", "Type", "Addend", "Symbol"] {
+                            header.col(|ui| {
+                                ui.label(title);
+                            });
+                        }
+                    })
+                    .body(|body| {
+                        body.rows(20.0, self.relocations.len(), |mut row| {
+                            let index = row.index();
+                            let relocation = &self.relocations[index];
+                            row.col(|ui| {
+                                let label = egui::Label::new(format!("{:#x}", relocation.offset))
+                                    .sense(egui::Sense::click());
+                                let offset = self.section_offset + index as u64 * self.entry_size;
+                                context.at(offset, self.entry_size).track(
+                                    &ui.add(label),
+                                    &format!("relocation {} of {}", index, self.section_name),
+                                );
+                            });
+                            row.col(|ui| {
+                                ui.label(relocation_type_name(self.machine, relocation.type_));
+                            });
+                            row.col(|ui| match relocation.addend {
+                                Some(addend) if addend < 0 => {
+                                    ui.label(format!("-{:#x}", addend.unsigned_abs()));
+                                }
+                                Some(addend) => {
+                                    ui.label(format!("{:#x}", addend));
+                                }
+                                None => {}
+                            });
+                            row.col(|ui| {
+                                let symbol = match &relocation.symbol {
+                                    Some(symbol) => symbol.clone(),
+                                    None if relocation.symbol_index == 0 => String::new(),
+                                    None => relocation.symbol_index.to_string(),
+                                };
+                                ui.add(egui::Label::new(symbol).wrap(false));
+                            });
+                        });
+                    });
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::tests::*, *};
+    use crate::tools::format_explorer::formats::elf::ElfFormat;
+
+    const SHT_SYMTAB: u32 = 2;
+
+    /// `.symtab` and `.strtab` with `puts` at index 1, and a table of each kind
+    fn builder(kind: Kind) -> Builder {
+        let mut builder = Builder::new(kind);
+        let symbols = [kind.symbol(0, 0, 0, 0, 0), kind.symbol(1, 0, 0, 0x12, 0)].concat();
+        let mut symbols = Section::new(".symtab", SHT_SYMTAB, symbols);
+        symbols.link = 2;
+        builder.sections.push(symbols);
+        builder
+            .sections
+            .push(Section::new(".strtab", 3, b"\0puts\0".to_vec()));
+        let info = |symbol: u64, type_: u32| match kind.is_64 {
+            true => symbol << 32 | type_ as u64,
+            false => symbol << 8 | type_ as u64,
+        };
+        let with_addend = [
+            [
+                kind.address(0x10),
+                kind.address(info(1, 2)),
+                kind.address(-4i64 as u64),
+            ]
+            .concat(),
+            [
+                kind.address(0x20),
+                kind.address(info(0, 8)),
+                kind.address(0x1234),
+            ]
+            .concat(),
+        ]
+        .concat();
+        let mut rela = Section::new(".rela.text", SHT_RELA, with_addend);
+        rela.link = 1;
+        builder.sections.push(rela);
+        // Entries can be larger than the fields, the rest is skipped
+        let word_size = if kind.is_64 { 8 } else { 4 };
+        let mut without_addend =
+            [kind.address(0x30), kind.address(info(1, 1)), vec![0xee; 4]].concat();
+        without_addend.extend(without_addend.clone());
+        let mut rel = Section::new(".rel.dyn", SHT_REL, without_addend);
+        rel.link = 1;
+        rel.entry_size = 2 * word_size + 4;
+        builder.sections.push(rel);
+        builder
+    }
+
+    #[test]
+    fn relocation_tables() {
+        for kind in KINDS {
+            let builder = builder(kind);
+            let elf = ElfFormat::new(&builder.build()).unwrap();
+            assert!(elf.warnings.is_empty(), "{:?} {:?}", kind, elf.warnings);
+            let [rela, rel] = &elf.relocations[..] else {
+                panic!("{:?}: {} relocation tables", kind, elf.relocations.len());
+            };
+            let word_size = if kind.is_64 { 8 } else { 4 };
+            assert_eq!(rela.section_name, ".rela.text");
+            assert_eq!(
+                (rela.section_offset, rela.entry_size),
+                (builder.offset_of(3), 3 * word_size)
+            );
+            assert_eq!(rela.machine, kind.machine);
+            let entries = rela
+                .relocations
+                .iter()
+                .map(|relocation| {
+                    (
+                        relocation.offset,
+                        relocation.type_,
+                        relocation.symbol_index,
+                        relocation.symbol.as_deref(),
+                        relocation.addend,
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                entries,
+                [
+                    (0x10, 2, 1, Some("puts"), Some(-4)),
+                    (0x20, 8, 0, None, Some(0x1234))
+                ],
+                "{:?}",
+                kind
+            );
+
+            assert_eq!(
+                (rel.section_name.as_str(), rel.entry_size),
+                (".rel.dyn", 2 * word_size + 4)
+            );
+            assert_eq!(rel.relocations.len(), 2);
+            let relocation = &rel.relocations[1];
+            assert_eq!((relocation.offset, relocation.type_), (0x30, 1));
+            assert_eq!(
+                (relocation.symbol.as_deref(), relocation.addend),
+                (Some("puts"), None)
+            );
+        }
+    }
+
+    #[test]
+    fn type_names() {
+        assert_eq!(relocation_type_name(EM_X86_64, 2), "R_X86_64_PC32");
+        assert_eq!(relocation_type_name(EM_AARCH64, 257), "R_AARCH64_ABS64");
+        assert_eq!(relocation_type_name(EM_ARM, 2), "R_ARM_ABS32");
+        // Unknown types and machines keep the number
+        assert_eq!(relocation_type_name(EM_X86_64, 1000), "1000");
+        assert_eq!(relocation_type_name(21, 1), "1");
+    }
+}
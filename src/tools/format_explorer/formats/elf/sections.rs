@@ -0,0 +1,464 @@
+use std::cmp::Ordering;
+
+use egui::{Color32, Vec2b};
+use egui_extras::Column;
+use nom::{sequence::Tuple, IResult};
+
+use super::Readers;
+use crate::tools::{
+    entropy_plot::EntropyPlot,
+    format_explorer::{FileFormatUi, FormatContext},
+};
+
+/// Section type without contents in the file, like `.bss`
+const SHT_NOBITS: u32 = 8;
+
+pub struct SectionHeader {
+    pub name: String,
+    /// Offset of the name in the section header string table
+    pub name_offset: u32,
+    pub type_: u32,
+    pub flags: u64,
+    pub address: u64,
+    pub offset: u64,
+    pub size: u64,
+    pub link: u32,
+    pub info: u32,
+    pub address_align: u64,
+    pub entry_size: u64,
+    /// Entropy of the contents, `None` for sections without bytes in the file
+    pub entropy: Option<f64>,
+}
+
+impl SectionHeader {
+    pub(super) fn parse(input: &[u8], readers: Readers) -> IResult<&[u8], Self> {
+        let address = |input| readers.address(input);
+        let (
+            tail,
+            (
+                name_offset,
+                type_,
+                flags,
+                section_address,
+                offset,
+                size,
+                link,
+                info,
+                address_align,
+                entry_size,
+            ),
+        ) = (
+            readers.word,
+            readers.word,
+            address,
+            address,
+            address,
+            address,
+            readers.word,
+            readers.word,
+            address,
+            address,
+        )
+            .parse(input)?;
+        Ok((
+            tail,
+            Self {
+                name: String::new(),
+                name_offset,
+                type_,
+                flags,
+                address: section_address,
+                offset,
+                size,
+                link,
+                info,
+                address_align,
+                entry_size,
+                entropy: None,
+            },
+        ))
+    }
+
+    /// Bytes of the section in the file, empty for `SHT_NOBITS`
+    pub fn file_size(&self) -> u64 {
+        if self.type_ == SHT_NOBITS {
+            0
+        } else {
+            self.size
+        }
+    }
+
+    /// Contents of the section, `None` if they are outside of the file
+    pub fn data<'a>(&self, input: &'a [u8]) -> Option<&'a [u8]> {
+        let start = usize::try_from(self.offset).ok()?;
+        let end = start.checked_add(usize::try_from(self.file_size()).ok()?)?;
+        input.get(start..end)
+    }
+}
+
+/// Symbolic `SHT_*` name of a section type
+pub fn section_type_name(type_: u32) -> String {
+    let name = match type_ {
+        0 => "SHT_NULL",
+        1 => "SHT_PROGBITS",
+        2 => "SHT_SYMTAB",
+        3 => "SHT_STRTAB",
+        4 => "SHT_RELA",
+        5 => "SHT_HASH",
+        6 => "SHT_DYNAMIC",
+        7 => "SHT_NOTE",
+        SHT_NOBITS => "SHT_NOBITS",
+        9 => "SHT_REL",
+        10 => "SHT_SHLIB",
+        11 => "SHT_DYNSYM",
+        14 => "SHT_INIT_ARRAY",
+        15 => "SHT_FINI_ARRAY",
+        16 => "SHT_PREINIT_ARRAY",
+        17 => "SHT_GROUP",
+        18 => "SHT_SYMTAB_SHNDX",
+        19 => "SHT_RELR",
+        0x6fff_fff5 => "SHT_GNU_ATTRIBUTES",
+        0x6fff_fff6 => "SHT_GNU_HASH",
+        0x6fff_fff7 => "SHT_GNU_LIBLIST",
+        0x6fff_fffd => "SHT_GNU_verdef",
+        0x6fff_fffe => "SHT_GNU_verneed",
+        0x6fff_ffff => "SHT_GNU_versym",
+        0x6000_0000..=0x6fff_ffff => return format!("SHT_LOOS+{:#x}", type_ - 0x6000_0000),
+        0x7000_0000..=0x7fff_ffff => return format!("SHT_LOPROC+{:#x}", type_ - 0x7000_0000),
+        0x8000_0000..=0xffff_ffff => return format!("SHT_LOUSER+{:#x}", type_ - 0x8000_0000),
+        _ => return format!("{:#x}", type_),
+    };
+    name.to_string()
+}
+
+/// `SHF_*` flags with the letters `readelf` uses for them
+const SECTION_FLAGS: [(u64, char, &str); 13] = [
+    (0x1, 'W', "SHF_WRITE"),
+    (0x2, 'A', "SHF_ALLOC"),
+    (0x4, 'X', "SHF_EXECINSTR"),
+    (0x10, 'M', "SHF_MERGE"),
+    (0x20, 'S', "SHF_STRINGS"),
+    (0x40, 'I', "SHF_INFO_LINK"),
+    (0x80, 'L', "SHF_LINK_ORDER"),
+    (0x100, 'O', "SHF_OS_NONCONFORMING"),
+    (0x200, 'G', "SHF_GROUP"),
+    (0x400, 'T', "SHF_TLS"),
+    (0x800, 'C', "SHF_COMPRESSED"),
+    (0x20_0000, 'R', "SHF_GNU_RETAIN"),
+    (0x8000_0000, 'E', "SHF_EXCLUDE"),
+];
+
+/// Short form of the flags, like `WA`
+pub fn section_flag_letters(flags: u64) -> String {
+    SECTION_FLAGS
+        .iter()
+        .filter(|(bit, _, _)| flags & bit != 0)
+        .map(|(_, letter, _)| *letter)
+        .collect()
+}
+
+/// Names of the flags, with unknown bits left as a number
+pub fn section_flag_names(flags: u64) -> String {
+    let names = SECTION_FLAGS.iter().map(|&(bit, _, name)| (bit, name));
+    super::flag_names(flags, names)
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum SectionColumn {
+    #[default]
+    Index,
+    Name,
+    Type,
+    Address,
+    Offset,
+    Size,
+    Flags,
+    Entropy,
+}
+
+impl SectionColumn {
+    const ALL: [SectionColumn; 8] = [
+        SectionColumn::Index,
+        SectionColumn::Name,
+        SectionColumn::Type,
+        SectionColumn::Address,
+        SectionColumn::Offset,
+        SectionColumn::Size,
+        SectionColumn::Flags,
+        SectionColumn::Entropy,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            SectionColumn::Index => "#",
+            SectionColumn::Name => "Name",
+            SectionColumn::Type => "Type",
+            SectionColumn::Address => "Address",
+            SectionColumn::Offset => "Offset",
+            SectionColumn::Size => "Size",
+            SectionColumn::Flags => "Flags",
+            SectionColumn::Entropy => "Entropy",
+        }
+    }
+}
+
+/// Section headers with the order they are shown in
+pub struct SectionTable {
+    pub headers: Vec<SectionHeader>,
+    /// `e_shentsize`, the table might have room for more fields than we read
+    entry_size: u64,
+    /// Indices into `headers`, sorted by the chosen column
+    order: Vec<usize>,
+    sort_column: SectionColumn,
+    descending: bool,
+}
+
+impl SectionTable {
+    pub(super) fn new(headers: Vec<SectionHeader>, entry_size: u16) -> Self {
+        Self {
+            order: (0..headers.len()).collect(),
+            headers,
+            entry_size: entry_size.into(),
+            sort_column: SectionColumn::default(),
+            descending: false,
+        }
+    }
+
+    fn compare(&self, a: usize, b: usize) -> Ordering {
+        let (left, right) = (&self.headers[a], &self.headers[b]);
+        match self.sort_column {
+            SectionColumn::Index => a.cmp(&b),
+            SectionColumn::Name => left.name.cmp(&right.name),
+            SectionColumn::Type => {
+                section_type_name(left.type_).cmp(&section_type_name(right.type_))
+            }
+            SectionColumn::Address => left.address.cmp(&right.address),
+            SectionColumn::Offset => left.offset.cmp(&right.offset),
+            SectionColumn::Size => left.size.cmp(&right.size),
+            SectionColumn::Flags => left.flags.cmp(&right.flags),
+            SectionColumn::Entropy => {
+                let entropy = |header: &SectionHeader| header.entropy.unwrap_or(-1.0);
+                entropy(left).total_cmp(&entropy(right))
+            }
+        }
+        .then(a.cmp(&b))
+    }
+
+    fn sort(&mut self) {
+        let mut order = std::mem::take(&mut self.order);
+        order.sort_by(|&a, &b| {
+            let ordering = self.compare(a, b);
+            if self.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+        self.order = order;
+    }
+
+    fn header_button(&mut self, ui: &mut egui::Ui, column: SectionColumn) {
+        let mut label = column.name().to_string();
+        if self.sort_column == column {
+            label.push_str(if self.descending { " 🔽" } else { " 🔼" });
+        }
+        if ui
+            .add(egui::Button::new(label).fill(Color32::TRANSPARENT))
+            .clicked()
+        {
+            self.descending = self.sort_column == column && !self.descending;
+            self.sort_column = column;
+            self.sort();
+        }
+    }
+}
+
+impl FileFormatUi for SectionTable {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let title = format!("{} ({})", name, self.headers.len());
+        context.collapsing(ui, &title, |ui| {
+            egui_extras::TableBuilder::new(ui)
+                .striped(true)
+                .resizable(false)
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .columns(Column::auto(), SectionColumn::ALL.len())
+                .vscroll(false)
+                .auto_shrink(Vec2b::new(false, true))
+                .header(20.0, |mut header| {
+                    for column in SectionColumn::ALL {
+                        header.col(|ui| self.header_button(ui, column));
+                    }
+                })
+                .body(|body| {
+                    body.rows(20.0, self.order.len(), |mut row| {
+                        let index = self.order[row.index()];
+                        let section = &self.headers[index];
+                        row.col(|ui| {
+                            let label =
+                                egui::Label::new(index.to_string()).sense(egui::Sense::click());
+                            context.entry(index, self.entry_size).track(
+                                &ui.add(label),
+                                &format!("section header {} {}", index, section.name),
+                            );
+                        });
+                        row.col(|ui| {
+                            ui.label(&section.name).on_hover_text(format!(
+                                "link: {}, info: {}, align: {:#x}, entry size: {:#x}",
+                                section.link,
+                                section.info,
+                                section.address_align,
+                                section.entry_size
+                            ));
+                        });
+                        row.col(|ui| {
+                            ui.label(section_type_name(section.type_));
+                        });
+                        row.col(|ui| {
+                            ui.label(format!("{:#x}", section.address));
+                        });
+                        row.col(|ui| {
+                            if ui
+                                .link(format!("{:#x}", section.offset))
+                                .on_hover_text("Select the section's bytes")
+                                .clicked()
+                            {
+                                context.select(section.offset, section.file_size());
+                            }
+                        });
+                        row.col(|ui| {
+                            ui.label(format!("{:#x}", section.size));
+                        });
+                        row.col(|ui| {
+                            ui.label(section_flag_letters(section.flags))
+                                .on_hover_text(section_flag_names(section.flags));
+                        });
+                        row.col(|ui| match section.entropy {
+                            Some(entropy) => {
+                                ui.label(format!("{:.3}", entropy));
+                            }
+                            None => {
+                                ui.weak("—");
+                            }
+                        });
+                    });
+                });
+        });
+    }
+}
+
+/// Fills in the names and entropies, which need the rest of the file
+pub(super) fn resolve(headers: &mut [SectionHeader], input: &[u8], string_table: usize) {
+    let strings = headers
+        .get(string_table)
+        .and_then(|table| table.data(input))
+        .unwrap_or_default();
+    for header in headers.iter_mut() {
+        header.name = super::string_at(strings, header.name_offset as u64);
+        header.entropy = header
+            .data(input)
+            .filter(|data| !data.is_empty())
+            .map(EntropyPlot::entropy_of_slice);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::tests::*, *};
+    use crate::tools::format_explorer::formats::elf::ElfFormat;
+
+    fn builder(kind: Kind) -> Builder {
+        let mut builder = Builder::new(kind);
+        let mut text = Section::new(".text", 1, (0..=255).collect());
+        text.flags = SHF_ALLOC | 0x4;
+        text.align = 16;
+        builder.sections.push(text);
+        let mut bss = Section::new(".bss", SHT_NOBITS, vec![0; 64]);
+        bss.flags = SHF_ALLOC | 0x1;
+        builder.sections.push(bss);
+        let mut comment = Section::new(".comment", 1, vec![b'a'; 8]);
+        comment.flags = 0x30;
+        comment.entry_size = 1;
+        builder.sections.push(comment);
+        builder
+    }
+
+    #[test]
+    fn section_headers() {
+        for kind in KINDS {
+            let builder = builder(kind);
+            let input = builder.build();
+            let elf = ElfFormat::new(&input).unwrap();
+            assert!(elf.warnings.is_empty(), "{:?} {:?}", kind, elf.warnings);
+            let headers = &elf.sections.headers;
+            let names = headers
+                .iter()
+                .map(|header| header.name.as_str())
+                .collect::<Vec<_>>();
+            assert_eq!(names, ["", ".text", ".bss", ".comment", ".shstrtab"]);
+            let types = headers.iter().map(|header| section_type_name(header.type_));
+            assert_eq!(
+                types.collect::<Vec<_>>(),
+                [
+                    "SHT_NULL",
+                    "SHT_PROGBITS",
+                    "SHT_NOBITS",
+                    "SHT_PROGBITS",
+                    "SHT_STRTAB"
+                ]
+            );
+            let text = &headers[1];
+            assert_eq!(
+                (text.offset, text.address),
+                (builder.offset_of(1), builder.offset_of(1))
+            );
+            assert_eq!((text.size, text.address_align), (256, 16));
+            assert_eq!(text.data(&input).unwrap(), (0..=255).collect::<Vec<u8>>());
+            assert_eq!(section_flag_letters(text.flags), "AX");
+            assert_eq!(text.entropy, Some(8.0));
+
+            // .bss takes no room in the file whatever its size
+            let bss = &headers[2];
+            assert_eq!((bss.size, bss.file_size()), (64, 0));
+            assert_eq!(bss.data(&input), Some(&[][..]));
+            assert_eq!(bss.entropy, None);
+
+            let comment = &headers[3];
+            assert_eq!((comment.address, comment.entry_size), (0, 1));
+            assert_eq!(section_flag_letters(comment.flags), "MS");
+            assert_eq!(comment.entropy, Some(0.0));
+            assert_eq!(headers[0].entropy, None);
+        }
+    }
+
+    #[test]
+    fn sorting() {
+        let input = builder(KINDS[1]).build();
+        let mut table = ElfFormat::new(&input).unwrap().sections;
+        table.sort_column = SectionColumn::Size;
+        table.sort();
+        // Ties keep the index order
+        assert_eq!(table.order, [0, 3, 4, 2, 1]);
+        table.descending = true;
+        table.sort();
+        assert_eq!(table.order, [1, 2, 4, 3, 0]);
+        table.sort_column = SectionColumn::Name;
+        table.descending = false;
+        table.sort();
+        assert_eq!(table.order, [0, 2, 3, 4, 1]);
+        table.sort_column = SectionColumn::Entropy;
+        table.sort();
+        assert_eq!(table.order, [0, 2, 3, 4, 1]);
+    }
+
+    #[test]
+    fn names_and_flags() {
+        assert_eq!(section_type_name(0x6fff_fff6), "SHT_GNU_HASH");
+        assert_eq!(section_type_name(0x7000_0003), "SHT_LOPROC+0x3");
+        assert_eq!(section_type_name(0x20), "0x20");
+        assert_eq!(section_flag_letters(0x8000_0243), "WAIGE");
+        assert_eq!(
+            section_flag_names(0x1000_0003),
+            "SHF_WRITE | SHF_ALLOC | 0x10000000"
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use nom::{sequence::Tuple, IResult};
+
+use super::Readers;
+use crate::tools::format_explorer::{FileFormatUi, FormatContext};
+
+pub(super) const PT_LOAD: u32 = 1;
+pub(super) const PT_DYNAMIC: u32 = 2;
+pub(super) const PT_NOTE: u32 = 4;
+
+pub struct ProgramHeader {
+    pub type_: u32,
+    pub flags: u32,
+    pub offset: u64,
+    pub vaddr: u64,
+    pub paddr: u64,
+    pub file_size: u64,
+    pub memory_size: u64,
+    pub align: u64,
+    /// From a 64-bit file, where the fields are in another order
+    pub is_64: bool,
+}
+
+impl ProgramHeader {
+    pub(super) fn parse(input: &[u8], readers: Readers) -> IResult<&[u8], Self> {
+        let address = |input| readers.address(input);
+        // The flags moved to keep 64-bit fields aligned
+        let (tail, (type_, flags, offset, vaddr, paddr, file_size, memory_size, align)) =
+            if readers.is_64 {
+                (
+                    readers.word,
+                    readers.word,
+                    address,
+                    address,
+                    address,
+                    address,
+                    address,
+                    address,
+                )
+                    .parse(input)?
+            } else {
+                let (tail, (type_, offset, vaddr, paddr, file_size, memory_size, flags, align)) = (
+                    readers.word,
+                    address,
+                    address,
+                    address,
+                    address,
+                    address,
+                    readers.word,
+                    address,
+                )
+                    .parse(input)?;
+                (
+                    tail,
+                    (
+                        type_,
+                        flags,
+                        offset,
+                        vaddr,
+                        paddr,
+                        file_size,
+                        memory_size,
+                        align,
+                    ),
+                )
+            };
+        Ok((
+            tail,
+            Self {
+                type_,
+                flags,
+                offset,
+                vaddr,
+                paddr,
+                file_size,
+                memory_size,
+                align,
+                is_64: readers.is_64,
+            },
+        ))
+    }
+
+    /// Contents of the segment in the file, `None` if they are outside of it
+    pub fn data<'a>(&self, input: &'a [u8]) -> Option<&'a [u8]> {
+        let start = usize::try_from(self.offset).ok()?;
+        let end = start.checked_add(usize::try_from(self.file_size).ok()?)?;
+        input.get(start..end)
+    }
+
+    /// `R`, `W` and `X` permissions, like `R-X`
+    pub fn permissions(&self) -> String {
+        [(4, 'R'), (2, 'W'), (1, 'X')]
+            .iter()
+            .map(|&(bit, c)| if self.flags & bit != 0 { c } else { '-' })
+            .collect()
+    }
+}
+
+impl FileFormatUi for ProgramHeader {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let title = format!(
+            "{} {} {}",
+            name,
+            segment_type_name(self.type_),
+            self.permissions()
+        );
+        context.collapsing(ui, &title, |ui| {
+            let address_size = if self.is_64 { 8 } else { 4 };
+            let mut fields = context.fields();
+            fields.next(4).field(
+                ui,
+                "type",
+                &format!("{} ({:#x})", segment_type_name(self.type_), self.type_),
+            );
+            let flags = format!("{} ({:#x})", self.permissions(), self.flags);
+            if self.is_64 {
+                fields.next(4).field(ui, "flags", &flags);
+            }
+            fields
+                .next(address_size)
+                .field(ui, "offset", &format!("{:#x}", self.offset));
+            context.range_link(ui, "file bytes", self.offset, self.file_size);
+            for (name, value) in [
+                ("vaddr", self.vaddr),
+                ("paddr", self.paddr),
+                ("file_size", self.file_size),
+                ("memory_size", self.memory_size),
+            ] {
+                fields
+                    .next(address_size)
+                    .field(ui, name, &format!("{:#x}", value));
+            }
+            if !self.is_64 {
+                fields.next(4).field(ui, "flags", &flags);
+            }
+            fields
+                .next(address_size)
+                .field(ui, "align", &format!("{:#x}", self.align));
+        });
+    }
+}
+
+/// Symbolic `PT_*` name of a segment type
+pub fn segment_type_name(type_: u32) -> String {
+    let name = match type_ {
+        0 => "PT_NULL",
+        PT_LOAD => "PT_LOAD",
+        PT_DYNAMIC => "PT_DYNAMIC",
+        3 => "PT_INTERP",
+        PT_NOTE => "PT_NOTE",
+        5 => "PT_SHLIB",
+        6 => "PT_PHDR",
+        7 => "PT_TLS",
+        0x6474_e550 => "PT_GNU_EH_FRAME",
+        0x6474_e551 => "PT_GNU_STACK",
+        0x6474_e552 => "PT_GNU_RELRO",
+        0x6474_e553 => "PT_GNU_PROPERTY",
+        0x6474_e554 => "PT_GNU_SFRAME",
+        0x6000_0000..=0x6fff_ffff => return format!("PT_LOOS+{:#x}", type_ - 0x6000_0000),
+        0x7000_0000..=0x7fff_ffff => return format!("PT_LOPROC+{:#x}", type_ - 0x7000_0000),
+        _ => return format!("{:#x}", type_),
+    };
+    name.to_string()
+}
+
+/// Finds where a virtual address is stored in the file, through the loaded segments
+pub(super) fn address_to_offset(headers: &[ProgramHeader], address: u64) -> Option<u64> {
+    headers
+        .iter()
+        .filter(|header| header.type_ == PT_LOAD)
+        .find_map(|header| {
+            let relative = address.checked_sub(header.vaddr)?;
+            (relative < header.file_size).then(|| header.offset + relative)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::tests::*, *};
+    use crate::tools::format_explorer::formats::elf::ElfFormat;
+
+    #[test]
+    fn program_headers() {
+        for kind in KINDS {
+            let mut builder = Builder::new(kind);
+            let mut text = Section::new(".text", 1, vec![0xaa; 24]);
+            text.align = 16;
+            builder.sections.push(text);
+            builder
+                .sections
+                .push(Section::new(".data", 1, vec![0xbb; 8]));
+            builder.segments.push(Segment {
+                type_: PT_LOAD,
+                flags: 5,
+                sections: 1..=2,
+            });
+            builder.segments.push(Segment {
+                type_: 0x6474_e551,
+                flags: 6,
+                sections: 2..=2,
+            });
+            let input = builder.build();
+            let elf = ElfFormat::new(&input).unwrap();
+            assert!(elf.warnings.is_empty(), "{:?} {:?}", kind, elf.warnings);
+            let [load, stack] = &elf.program_headers[..] else {
+                panic!("{:?}: {} program headers", kind, elf.program_headers.len());
+            };
+            let (text, data) = (builder.offset_of(1), builder.offset_of(2));
+            assert_eq!(load.is_64, kind.is_64);
+            assert_eq!(
+                (load.type_, load.flags, load.permissions()),
+                (PT_LOAD, 5, "R-X".to_string())
+            );
+            assert_eq!((load.offset, load.vaddr, load.paddr), (text, text, text));
+            assert_eq!(
+                (load.file_size, load.memory_size),
+                (data + 8 - text, data + 8 - text)
+            );
+            assert_eq!(load.align, 16);
+            assert_eq!(load.data(&input).unwrap()[..24], [0xaa; 24]);
+            assert_eq!(segment_type_name(stack.type_), "PT_GNU_STACK");
+            assert_eq!(
+                (stack.permissions(), stack.offset),
+                ("RW-".to_string(), data)
+            );
+
+            let headers = &elf.program_headers;
+            assert_eq!(address_to_offset(headers, text + 4), Some(text + 4));
+            assert_eq!(address_to_offset(headers, data + 8), None);
+            assert_eq!(address_to_offset(headers, 0), None);
+        }
+    }
+
+    #[test]
+    fn segments_outside_the_file() {
+        let header = ProgramHeader {
+            type_: PT_LOAD,
+            flags: 0,
+            offset: 0x100,
+            vaddr: 0,
+            paddr: 0,
+            file_size: u64::MAX,
+            memory_size: 0,
+            align: 0,
+            is_64: true,
+        };
+        assert!(header.data(&[0; 0x200]).is_none());
+        assert_eq!(segment_type_name(0x6000_0010), "PT_LOOS+0x10");
+        assert_eq!(segment_type_name(0x1234), "0x1234");
+    }
+}
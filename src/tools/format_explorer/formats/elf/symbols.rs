@@ -0,0 +1,434 @@
+use egui::Vec2b;
+use egui_extras::Column;
+use nom::{number::complete::u8, sequence::Tuple, IResult};
+
+use super::{sections::SectionHeader, Readers};
+use crate::tools::format_explorer::{formats::demangle, FileFormatUi, FormatContext};
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_DYNSYM: u32 = 11;
+
+/// Section indices with a special meaning, from `SHN_LORESERVE` on
+const SHN_UNDEF: u16 = 0;
+const SHN_LORESERVE: u16 = 0xff00;
+
+pub struct Symbol {
+    pub name: String,
+    /// Readable name for Rust and C++ symbols
+    pub demangled: Option<String>,
+    pub value: u64,
+    pub size: u64,
+    pub info: u8,
+    pub other: u8,
+    pub section_index: u16,
+    /// Where the symbol's bytes are, if it points into a section stored in the file
+    pub file_offset: Option<u64>,
+    /// Found in `.dynsym` rather than `.symtab`
+    pub dynamic: bool,
+    /// Where the symbol table entry is in the file
+    pub entry_offset: u64,
+    pub entry_size: u64,
+}
+
+impl Symbol {
+    /// Returns the symbol with its name still unresolved, and the offset of the name
+    fn parse(input: &[u8], readers: Readers) -> IResult<&[u8], (u32, Self)> {
+        let address = |input| readers.address(input);
+        // Like program headers, 64-bit entries reorder the fields for alignment
+        let (tail, (name_offset, info, other, section_index, value, size)) = if readers.is_64 {
+            (readers.word, u8, u8, readers.half, address, address).parse(input)?
+        } else {
+            let (tail, (name_offset, value, size, info, other, section_index)) =
+                (readers.word, address, address, u8, u8, readers.half).parse(input)?;
+            (tail, (name_offset, info, other, section_index, value, size))
+        };
+        Ok((
+            tail,
+            (
+                name_offset,
+                Self {
+                    name: String::new(),
+                    demangled: None,
+                    value,
+                    size,
+                    info,
+                    other,
+                    section_index,
+                    file_offset: None,
+                    dynamic: false,
+                    entry_offset: 0,
+                    entry_size: 0,
+                },
+            ),
+        ))
+    }
+
+    /// Name to show, the demangled one if there is one
+    pub fn display_name(&self) -> &str {
+        self.demangled.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn binding_name(&self) -> String {
+        let binding = self.info >> 4;
+        let name = match binding {
+            0 => "LOCAL",
+            1 => "GLOBAL",
+            2 => "WEAK",
+            10 => "GNU_UNIQUE",
+            _ => return binding.to_string(),
+        };
+        name.to_string()
+    }
+
+    pub fn type_name(&self) -> String {
+        let type_ = self.info & 0xf;
+        let name = match type_ {
+            0 => "NOTYPE",
+            1 => "OBJECT",
+            2 => "FUNC",
+            3 => "SECTION",
+            4 => "FILE",
+            5 => "COMMON",
+            6 => "TLS",
+            10 => "GNU_IFUNC",
+            _ => return type_.to_string(),
+        };
+        name.to_string()
+    }
+
+    pub fn visibility_name(&self) -> &'static str {
+        match self.other & 0x3 {
+            0 => "DEFAULT",
+            1 => "INTERNAL",
+            2 => "HIDDEN",
+            _ => "PROTECTED",
+        }
+    }
+
+    pub fn section_index_name(&self) -> String {
+        match self.section_index {
+            SHN_UNDEF => "UND".to_string(),
+            0xfff1 => "ABS".to_string(),
+            0xfff2 => "COMMON".to_string(),
+            0xffff => "XINDEX".to_string(),
+            index => index.to_string(),
+        }
+    }
+}
+
+/// Size of the table's entries, at least as large as the fields we read
+fn entry_size(table: &SectionHeader, readers: Readers) -> usize {
+    let minimum_size = if readers.is_64 { 24 } else { 16 };
+    (table.entry_size as usize).max(minimum_size)
+}
+
+/// Parses `.symtab` and `.dynsym`, with names from their linked string tables
+pub(super) fn parse_symbols(
+    input: &[u8],
+    readers: Readers,
+    sections: &[SectionHeader],
+) -> Vec<Symbol> {
+    let mut symbols = Vec::new();
+    let tables = sections
+        .iter()
+        .filter(|section| section.type_ == SHT_SYMTAB || section.type_ == SHT_DYNSYM);
+    for table in tables {
+        let entry_size = entry_size(table, readers);
+        // Broken tables are skipped, they shouldn't hide the rest of the file
+        let Some(data) = table.data(input) else {
+            continue;
+        };
+        let strings = sections
+            .get(table.link as usize)
+            .and_then(|strings| strings.data(input))
+            .unwrap_or_default();
+        // The first entry is always the null symbol
+        for (index, entry) in data.chunks_exact(entry_size).enumerate().skip(1) {
+            let Ok((_, (name_offset, mut symbol))) = Symbol::parse(entry, readers) else {
+                continue;
+            };
+            symbol.entry_offset = table.offset + (index * entry_size) as u64;
+            symbol.entry_size = entry_size as u64;
+            symbol.name = super::string_at(strings, name_offset as u64);
+            symbol.demangled = demangle(&symbol.name);
+            symbol.dynamic = table.type_ == SHT_DYNSYM;
+            symbol.file_offset = file_offset(&symbol, sections);
+            symbols.push(symbol);
+        }
+    }
+    symbols
+}
+
+/// Name of the symbol at `index` in the symbol table section `table`, demangled if possible
+pub(super) fn symbol_name(
+    input: &[u8],
+    readers: Readers,
+    sections: &[SectionHeader],
+    table: u32,
+    index: u64,
+) -> Option<String> {
+    let table = sections.get(table as usize)?;
+    let start = usize::try_from(index)
+        .ok()?
+        .checked_mul(entry_size(table, readers))?;
+    let entry = table.data(input)?.get(start..)?;
+    let (_, (name_offset, _)) = Symbol::parse(entry, readers).ok()?;
+    let strings = sections.get(table.link as usize)?.data(input)?;
+    let name = super::string_at(strings, name_offset as u64);
+    Some(demangle(&name).unwrap_or(name))
+}
+
+/// Translates the symbol's address into the file through its section
+fn file_offset(symbol: &Symbol, sections: &[SectionHeader]) -> Option<u64> {
+    if symbol.section_index == SHN_UNDEF || symbol.section_index >= SHN_LORESERVE {
+        return None;
+    }
+    let section = sections.get(symbol.section_index as usize)?;
+    let relative = symbol.value.checked_sub(section.address)?;
+    if relative > section.file_size() {
+        return None;
+    }
+    section.offset.checked_add(relative)
+}
+
+pub struct SymbolTable {
+    pub symbols: Vec<Symbol>,
+    filter: String,
+    /// Indices of the symbols matching the filter
+    matches: Vec<usize>,
+}
+
+impl SymbolTable {
+    pub(super) fn new(symbols: Vec<Symbol>) -> Self {
+        Self {
+            matches: (0..symbols.len()).collect(),
+            symbols,
+            filter: String::new(),
+        }
+    }
+
+    fn apply_filter(&mut self) {
+        let filter = self.filter.to_lowercase();
+        self.matches = self
+            .symbols
+            .iter()
+            .enumerate()
+            .filter(|(_, symbol)| {
+                symbol.name.to_lowercase().contains(&filter)
+                    || symbol
+                        .demangled
+                        .as_ref()
+                        .is_some_and(|name| name.to_lowercase().contains(&filter))
+            })
+            .map(|(index, _)| index)
+            .collect();
+    }
+}
+
+impl FileFormatUi for SymbolTable {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let title = format!("{} ({})", name, self.symbols.len());
+        ui.collapsing(title, |ui| {
+            ui.horizontal(|ui| {
+                let response =
+                    ui.add(egui::TextEdit::singleline(&mut self.filter).hint_text("Filter"));
+                if response.changed() {
+                    self.apply_filter();
+                }
+                ui.label(format!("{} shown", self.matches.len()));
+            });
+            egui_extras::TableBuilder::new(ui)
+                .striped(true)
+                .resizable(false)
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .columns(Column::auto(), 7)
+                .column(Column::remainder().clip(true))
+                .vscroll(false)
+                .auto_shrink(Vec2b::new(false, true))
+                .header(20.0, |mut header| {
+                    for title in [
+                        "Value", "Size", "Type", "Bind", "Vis", "Ndx", "Table", "Name",
+                    ] {
+                        header.col(|ui| {
+                            ui.label(title);
+                        });
+                    }
+                })
+                .body(|body| {
+                    body.rows(20.0, self.matches.len(), |mut row| {
+                        let symbol = &self.symbols[self.matches[row.index()]];
+                        row.col(|ui| {
+                            let label = egui::Label::new(format!("{:#x}", symbol.value))
+                                .sense(egui::Sense::click());
+                            context.at(symbol.entry_offset, symbol.entry_size).track(
+                                &ui.add(label),
+                                &format!("symbol {}", symbol.display_name()),
+                            );
+                        });
+                        row.col(|ui| {
+                            ui.label(symbol.size.to_string());
+                        });
+                        row.col(|ui| {
+                            ui.label(symbol.type_name());
+                        });
+                        row.col(|ui| {
+                            ui.label(symbol.binding_name());
+                        });
+                        row.col(|ui| {
+                            ui.label(symbol.visibility_name());
+                        });
+                        row.col(|ui| {
+                            ui.label(symbol.section_index_name());
+                        });
+                        row.col(|ui| {
+                            ui.label(if symbol.dynamic { ".dynsym" } else { ".symtab" });
+                        });
+                        row.col(|ui| {
+                            let label = egui::Label::new(symbol.display_name()).wrap(false);
+                            match symbol.file_offset {
+                                Some(offset) => {
+                                    let response = ui
+                                        .add(label.sense(egui::Sense::click()))
+                                        .on_hover_text(format!(
+                                            "{}\nClick to select its bytes at {:#x}",
+                                            symbol.name, offset
+                                        ));
+                                    if response.clicked() {
+                                        context.select(offset, symbol.size);
+                                    }
+                                }
+                                None => {
+                                    ui.add(label).on_hover_text(&symbol.name);
+                                }
+                            }
+                        });
+                    });
+                });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::tests::*, *};
+    use crate::tools::format_explorer::formats::elf::ElfFormat;
+
+    const NAMES: &[u8] = b"\0a.c\0_ZN3foo3barEv\0counter\0puts\0";
+
+    /// `.text`, `.symtab` and `.strtab`, and `.dynsym` sharing the string table
+    fn builder(kind: Kind) -> Builder {
+        let mut builder = Builder::new(kind);
+        let mut text = Section::new(".text", 1, vec![0x90; 32]);
+        text.flags = SHF_ALLOC;
+        builder.sections.push(text);
+        let text = builder.offset_of(1);
+        let entry_size = if kind.is_64 { 24 } else { 16 };
+        let symbols = [
+            kind.symbol(0, 0, 0, 0, 0),
+            kind.symbol(1, 0, 0, 0x04, 0xfff1),
+            kind.symbol(5, text + 4, 8, 0x12, 1),
+            kind.symbol(19, text + 16, 4, 0x21, 1),
+            kind.symbol(27, 0, 0, 0x12, SHN_UNDEF),
+        ]
+        .concat();
+        for (name, type_, symbols) in [
+            (".symtab", SHT_SYMTAB, symbols.clone()),
+            (".dynsym", SHT_DYNSYM, symbols[..2 * entry_size].to_vec()),
+        ] {
+            let mut table = Section::new(name, type_, symbols);
+            table.link = 4;
+            table.entry_size = entry_size as u64;
+            builder.sections.push(table);
+        }
+        builder
+            .sections
+            .push(Section::new(".strtab", 3, NAMES.to_vec()));
+        builder
+    }
+
+    #[test]
+    fn symbol_tables() {
+        for kind in KINDS {
+            let builder = builder(kind);
+            let input = builder.build();
+            let elf = ElfFormat::new(&input).unwrap();
+            let symbols = &elf.symbol_table.symbols;
+            let names = symbols
+                .iter()
+                .map(|symbol| symbol.name.as_str())
+                .collect::<Vec<_>>();
+            // The null entries are left out
+            assert_eq!(
+                names,
+                ["a.c", "_ZN3foo3barEv", "counter", "puts", "a.c"],
+                "{:?}",
+                kind
+            );
+            let described = symbols.iter().map(|symbol| {
+                (
+                    symbol.binding_name(),
+                    symbol.type_name(),
+                    symbol.section_index_name(),
+                    symbol.dynamic,
+                )
+            });
+            let expected = [
+                ("LOCAL", "FILE", "ABS", false),
+                ("GLOBAL", "FUNC", "1", false),
+                ("WEAK", "OBJECT", "1", false),
+                ("GLOBAL", "FUNC", "UND", false),
+                ("LOCAL", "FILE", "ABS", true),
+            ]
+            .map(|(binding, type_, section, dynamic)| {
+                (
+                    binding.to_string(),
+                    type_.to_string(),
+                    section.to_string(),
+                    dynamic,
+                )
+            });
+            assert_eq!(described.collect::<Vec<_>>(), expected);
+
+            let text = builder.offset_of(1);
+            let function = &symbols[1];
+            assert_eq!((function.value, function.size), (text + 4, 8));
+            assert!(function.demangled.is_some() && symbols[2].demangled.is_none());
+            assert_eq!(function.visibility_name(), "DEFAULT");
+            let offsets = symbols
+                .iter()
+                .map(|symbol| symbol.file_offset)
+                .collect::<Vec<_>>();
+            assert_eq!(offsets, [None, Some(text + 4), Some(text + 16), None, None]);
+            let entry_size = if kind.is_64 { 24 } else { 16 };
+            assert_eq!(function.entry_offset, builder.offset_of(2) + 2 * entry_size);
+            assert_eq!(function.entry_size, entry_size);
+
+            // Names lead to the symbols' bytes in expressions
+            let expression_symbols = elf.symbols();
+            assert!(expression_symbols.contains(&("counter".to_string(), text + 16)));
+            assert!(!expression_symbols.iter().any(|(name, _)| name == "puts"));
+            assert_eq!(
+                symbol_name(
+                    &input,
+                    Readers::new(kind.big_endian, kind.is_64),
+                    &elf.sections.headers,
+                    2,
+                    3
+                ),
+                Some("counter".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn filter() {
+        let input = builder(KINDS[0]).build();
+        let mut table = ElfFormat::new(&input).unwrap().symbol_table;
+        table.filter = "C".to_string();
+        table.apply_filter();
+        assert_eq!(table.matches, [0, 2, 4]);
+        table.filter = "nothing".to_string();
+        table.apply_filter();
+        assert!(table.matches.is_empty());
+    }
+}
@@ -0,0 +1,541 @@
+//! EXIF metadata, a small TIFF file of tagged values found in JPEG APP1 segments and PNG eXIf
+//! chunks
+
+use std::collections::HashSet;
+
+use super::super::hex_bytes;
+use crate::tools::format_explorer::{FileFormatUi, FormatContext};
+
+/// Directories read at most, they link to each other and could loop
+const MAX_DIRECTORIES: usize = 16;
+/// Tags read at most from a directory
+const MAX_TAGS: usize = 1000;
+/// Items of an array value shown at most
+const SHOWN_VALUES: usize = 16;
+/// Bytes of an undefined value shown at most
+const SHOWN_BYTES: usize = 32;
+
+/// Which directory a tag is in, GPS tags have numbers of their own
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DirectoryKind {
+    Image,
+    Thumbnail,
+    Exif,
+    Gps,
+    Interoperability,
+}
+
+impl DirectoryKind {
+    fn name(self) -> &'static str {
+        match self {
+            DirectoryKind::Image => "IFD0 (image)",
+            DirectoryKind::Thumbnail => "IFD1 (thumbnail)",
+            DirectoryKind::Exif => "Exif IFD",
+            DirectoryKind::Gps => "GPS IFD",
+            DirectoryKind::Interoperability => "Interoperability IFD",
+        }
+    }
+}
+
+const EXIF_POINTER: u16 = 0x8769;
+const GPS_POINTER: u16 = 0x8825;
+const INTEROPERABILITY_POINTER: u16 = 0xa005;
+const THUMBNAIL_OFFSET: u16 = 0x0201;
+const THUMBNAIL_LENGTH: u16 = 0x0202;
+
+fn tag_name(kind: DirectoryKind, tag: u16) -> Option<&'static str> {
+    if kind == DirectoryKind::Gps {
+        return Some(match tag {
+            0x00 => "GPSVersionID",
+            0x01 => "GPSLatitudeRef",
+            0x02 => "GPSLatitude",
+            0x03 => "GPSLongitudeRef",
+            0x04 => "GPSLongitude",
+            0x05 => "GPSAltitudeRef",
+            0x06 => "GPSAltitude",
+            0x07 => "GPSTimeStamp",
+            0x12 => "GPSMapDatum",
+            0x1d => "GPSDateStamp",
+            _ => return None,
+        });
+    }
+    Some(match tag {
+        0x0001 => "InteroperabilityIndex",
+        0x0100 => "ImageWidth",
+        0x0101 => "ImageLength",
+        0x0103 => "Compression",
+        0x010e => "ImageDescription",
+        0x010f => "Make",
+        0x0110 => "Model",
+        0x0112 => "Orientation",
+        0x011a => "XResolution",
+        0x011b => "YResolution",
+        0x0128 => "ResolutionUnit",
+        0x0131 => "Software",
+        0x0132 => "DateTime",
+        0x013b => "Artist",
+        0x0201 => "JPEGInterchangeFormat",
+        0x0202 => "JPEGInterchangeFormatLength",
+        0x0213 => "YCbCrPositioning",
+        0x8298 => "Copyright",
+        0x829a => "ExposureTime",
+        0x829d => "FNumber",
+        0x8769 => "ExifIFDPointer",
+        0x8822 => "ExposureProgram",
+        0x8825 => "GPSInfoIFDPointer",
+        0x8827 => "ISOSpeedRatings",
+        0x9000 => "ExifVersion",
+        0x9003 => "DateTimeOriginal",
+        0x9004 => "DateTimeDigitized",
+        0x9010 => "OffsetTime",
+        0x9101 => "ComponentsConfiguration",
+        0x9201 => "ShutterSpeedValue",
+        0x9202 => "ApertureValue",
+        0x9204 => "ExposureBiasValue",
+        0x9207 => "MeteringMode",
+        0x9209 => "Flash",
+        0x920a => "FocalLength",
+        0x927c => "MakerNote",
+        0x9286 => "UserComment",
+        0xa000 => "FlashpixVersion",
+        0xa001 => "ColorSpace",
+        0xa002 => "PixelXDimension",
+        0xa003 => "PixelYDimension",
+        0xa005 => "InteroperabilityIFDPointer",
+        0xa402 => "ExposureMode",
+        0xa403 => "WhiteBalance",
+        0xa406 => "SceneCaptureType",
+        0xa420 => "ImageUniqueID",
+        0xa430 => "CameraOwnerName",
+        0xa431 => "BodySerialNumber",
+        0xa433 => "LensMake",
+        0xa434 => "LensModel",
+        _ => return None,
+    })
+}
+
+/// Bytes taken by one value of a TIFF type, `None` for unknown types
+fn type_size(kind: u16) -> Option<u64> {
+    match kind {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+
+/// Reads numbers in the byte order of the TIFF header
+#[derive(Clone, Copy)]
+struct Reader<'a> {
+    tiff: &'a [u8],
+    big_endian: bool,
+}
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&self, offset: u64) -> Option<[u8; N]> {
+        let start = usize::try_from(offset).ok()?;
+        let mut bytes: [u8; N] = self
+            .tiff
+            .get(start..start.checked_add(N)?)?
+            .try_into()
+            .ok()?;
+        if !self.big_endian {
+            bytes.reverse();
+        }
+        Some(bytes)
+    }
+
+    fn u16(&self, offset: u64) -> Option<u16> {
+        self.bytes(offset).map(u16::from_be_bytes)
+    }
+
+    fn u32(&self, offset: u64) -> Option<u32> {
+        self.bytes(offset).map(u32::from_be_bytes)
+    }
+
+    fn u64(&self, offset: u64) -> Option<u64> {
+        self.bytes(offset).map(u64::from_be_bytes)
+    }
+
+    /// One value of a TIFF type as text
+    fn value(&self, kind: u16, offset: u64) -> Option<String> {
+        Some(match kind {
+            1 | 7 => self.bytes::<1>(offset)?[0].to_string(),
+            6 => (self.bytes::<1>(offset)?[0] as i8).to_string(),
+            3 => self.u16(offset)?.to_string(),
+            8 => (self.u16(offset)? as i16).to_string(),
+            4 => self.u32(offset)?.to_string(),
+            9 => (self.u32(offset)? as i32).to_string(),
+            5 => format!("{}/{}", self.u32(offset)?, self.u32(offset + 4)?),
+            10 => format!(
+                "{}/{}",
+                self.u32(offset)? as i32,
+                self.u32(offset + 4)? as i32
+            ),
+            11 => f32::from_bits(self.u32(offset)?).to_string(),
+            12 => f64::from_bits(self.u64(offset)?).to_string(),
+            _ => return None,
+        })
+    }
+}
+
+struct Tag {
+    /// Where the 12-byte entry is, from the start of the TIFF header
+    offset: u64,
+    tag: u16,
+    name: Option<&'static str>,
+    /// The first value as a number, for tags pointing at other things
+    number: Option<u32>,
+    value: String,
+}
+
+struct Directory {
+    kind: DirectoryKind,
+    /// From the start of the TIFF header
+    offset: u64,
+    tags: Vec<Tag>,
+}
+
+pub struct Exif {
+    /// Where the TIFF header is in the file, offsets in it are from there
+    offset: u64,
+    directories: Vec<Directory>,
+    /// The JPEG thumbnail pointed at from IFD1
+    thumbnail: Option<(u64, u64)>,
+    warnings: Vec<String>,
+}
+
+impl Exif {
+    /// Reads the TIFF structure in `tiff`, which is at `offset` in the file
+    pub fn parse(tiff: &[u8], offset: u64) -> Self {
+        let mut exif = Self {
+            offset,
+            directories: Vec::new(),
+            thumbnail: None,
+            warnings: Vec::new(),
+        };
+        let big_endian = match tiff.get(..2) {
+            Some(b"MM") => true,
+            Some(b"II") => false,
+            _ => {
+                exif.warnings
+                    .push("The TIFF header has no byte order".to_string());
+                return exif;
+            }
+        };
+        let reader = Reader { tiff, big_endian };
+        if reader.u16(2) != Some(42) {
+            exif.warnings
+                .push("The TIFF header doesn't have the magic number 42".to_string());
+        }
+        let Some(first) = reader.u32(4) else {
+            exif.warnings.push("The TIFF header is cut off".to_string());
+            return exif;
+        };
+        let mut pending = vec![(DirectoryKind::Image, first as u64)];
+        let mut visited = HashSet::new();
+        while let Some((kind, position)) = pending.pop() {
+            if !visited.insert(position) {
+                exif.warnings
+                    .push(format!("The directories loop back to {:#x}", position));
+                continue;
+            }
+            if exif.directories.len() >= MAX_DIRECTORIES {
+                exif.warnings.push(format!(
+                    "There are more than {} directories",
+                    MAX_DIRECTORIES
+                ));
+                break;
+            }
+            let Some(count) = reader.u16(position) else {
+                exif.warnings.push(format!(
+                    "The {} at {:#x} is past the end of the data",
+                    kind.name(),
+                    position
+                ));
+                continue;
+            };
+            let mut tags = Vec::new();
+            for index in 0..(count as usize).min(MAX_TAGS) {
+                let entry = position + 2 + 12 * index as u64;
+                let Some(tag) = exif.read_tag(&reader, kind, entry) else {
+                    exif.warnings.push(format!(
+                        "The {} is cut off after {} tags",
+                        kind.name(),
+                        index
+                    ));
+                    break;
+                };
+                let pointed = match tag.tag {
+                    EXIF_POINTER => Some(DirectoryKind::Exif),
+                    GPS_POINTER => Some(DirectoryKind::Gps),
+                    INTEROPERABILITY_POINTER => Some(DirectoryKind::Interoperability),
+                    _ => None,
+                };
+                if let (Some(pointed), Some(number)) = (pointed, tag.number) {
+                    pending.push((pointed, number as u64));
+                }
+                tags.push(tag);
+            }
+            if kind == DirectoryKind::Thumbnail {
+                let number = |id| tags.iter().find(|tag| tag.tag == id)?.number;
+                if let (Some(start), Some(length)) =
+                    (number(THUMBNAIL_OFFSET), number(THUMBNAIL_LENGTH))
+                {
+                    exif.thumbnail = Some((offset + start as u64, length as u64));
+                }
+            }
+            // IFD0 links to IFD1, which has the thumbnail
+            let next = reader.u32(position + 2 + 12 * count as u64);
+            if let (DirectoryKind::Image, Some(next)) = (kind, next.filter(|&next| next != 0)) {
+                pending.push((DirectoryKind::Thumbnail, next as u64));
+            }
+            exif.directories.push(Directory {
+                kind,
+                offset: position,
+                tags,
+            });
+        }
+        exif.directories.sort_by_key(|directory| directory.offset);
+        exif
+    }
+
+    fn read_tag(&mut self, reader: &Reader, kind: DirectoryKind, entry: u64) -> Option<Tag> {
+        let tag = reader.u16(entry)?;
+        let value_type = reader.u16(entry + 2)?;
+        let count = reader.u32(entry + 4)? as u64;
+        let name = tag_name(kind, tag);
+        let Some(size) = type_size(value_type) else {
+            let value = format!("unknown type {}", value_type);
+            return Some(Tag {
+                offset: entry,
+                tag,
+                name,
+                number: None,
+                value,
+            });
+        };
+        // Values that fit in 4 bytes are in the entry, others are pointed at
+        let total = size.saturating_mul(count);
+        let start = if total <= 4 {
+            entry + 8
+        } else {
+            reader.u32(entry + 8)? as u64
+        };
+        let data = usize::try_from(start)
+            .ok()
+            .and_then(|start| reader.tiff.get(start..start.checked_add(total as usize)?));
+        let Some(data) = data else {
+            let shown = name.map_or_else(|| format!("{:#06x}", tag), str::to_string);
+            self.warnings.push(format!(
+                "The value of {} is past the end of the data",
+                shown
+            ));
+            return Some(Tag {
+                offset: entry,
+                tag,
+                name,
+                number: None,
+                value: format!("{} values at {:#x}", count, start),
+            });
+        };
+        let number = match value_type {
+            3 => reader.u16(start).map(u32::from),
+            4 => reader.u32(start),
+            _ => None,
+        };
+        let value = match value_type {
+            2 => {
+                let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+                format!("{:?}", String::from_utf8_lossy(&data[..end]))
+            }
+            // Versions and comments are text in undefined values
+            7 if matches!(tag, 0x9000 | 0xa000 | 0x0002) && kind != DirectoryKind::Gps => {
+                format!("{:?}", String::from_utf8_lossy(data))
+            }
+            7 => {
+                let shown = &data[..data.len().min(SHOWN_BYTES)];
+                if data.len() > SHOWN_BYTES {
+                    format!("{} … ({} bytes)", hex_bytes(shown), data.len())
+                } else {
+                    hex_bytes(shown)
+                }
+            }
+            _ => {
+                let mut values = (0..count.min(SHOWN_VALUES as u64))
+                    .filter_map(|index| reader.value(value_type, start + index * size))
+                    .collect::<Vec<_>>();
+                if count > SHOWN_VALUES as u64 {
+                    values.push(format!("… ({} values)", count));
+                }
+                values.join(", ")
+            }
+        };
+        Some(Tag {
+            offset: entry,
+            tag,
+            name,
+            number,
+            value,
+        })
+    }
+}
+
+impl FileFormatUi for Exif {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        ui.collapsing(name, |ui| {
+            for warning in &self.warnings {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+            for directory in &self.directories {
+                let offset = self.offset + directory.offset;
+                let length = 2 + 12 * directory.tags.len() as u64 + 4;
+                let title = format!("{} ({} tags)", directory.kind.name(), directory.tags.len());
+                context.at(offset, length).collapsing(ui, &title, |ui| {
+                    for tag in &directory.tags {
+                        let name = match tag.name {
+                            Some(name) => name.to_string(),
+                            None => format!("{:#06x}", tag.tag),
+                        };
+                        context
+                            .at(self.offset + tag.offset, 12)
+                            .field(ui, &name, &tag.value);
+                    }
+                });
+            }
+            if let Some((offset, length)) = self.thumbnail {
+                context.range_link(ui, "thumbnail", offset, length);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! The TIFF structures are built in either byte order: IFD0 with a camera make, an
+    //! orientation and the Exif pointer, an Exif IFD with an exposure time stored after it, and
+    //! IFD1 pointing at a thumbnail.
+
+    use super::*;
+
+    /// Where the structure is in the file, offsets in it are relative to this
+    const OFFSET: u64 = 0x1000;
+
+    fn tiff(big_endian: bool) -> Vec<u8> {
+        let u16 = |value: u16| match big_endian {
+            true => value.to_be_bytes(),
+            false => value.to_le_bytes(),
+        };
+        let u32 = |value: u32| match big_endian {
+            true => value.to_be_bytes(),
+            false => value.to_le_bytes(),
+        };
+        let entry = |tag, kind, count, value: &[u8]| {
+            let mut value = value.to_vec();
+            value.resize(4, 0);
+            [&u16(tag)[..], &u16(kind), &u32(count), &value].concat()
+        };
+        [
+            &(if big_endian { b"MM" } else { b"II" })[..],
+            &u16(42),
+            &u32(8),
+            // IFD0 at 8
+            &u16(3),
+            &entry(0x010f, 2, 4, b"Cam\0"),
+            &entry(0x0112, 3, 1, &u16(6)),
+            &entry(EXIF_POINTER, 4, 1, &u32(50)),
+            &u32(76),
+            // Exif IFD at 50
+            &u16(1),
+            &entry(0x829a, 5, 1, &u32(68)),
+            &u32(0),
+            // The exposure time at 68
+            &u32(1),
+            &u32(100),
+            // IFD1 at 76
+            &u16(2),
+            &entry(THUMBNAIL_OFFSET, 4, 1, &u32(106)),
+            &entry(THUMBNAIL_LENGTH, 4, 1, &u32(4)),
+            &u32(0),
+            // The thumbnail at 106
+            &[0xff, 0xd8, 0xff, 0xd9],
+        ]
+        .concat()
+    }
+
+    fn tags(directory: &Directory) -> Vec<(Option<&str>, &str)> {
+        directory
+            .tags
+            .iter()
+            .map(|tag| (tag.name, tag.value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn directories_in_both_byte_orders() {
+        for big_endian in [false, true] {
+            let exif = Exif::parse(&tiff(big_endian), OFFSET);
+            assert!(exif.warnings.is_empty(), "{:?}", exif.warnings);
+            let directories = exif
+                .directories
+                .iter()
+                .map(|directory| (directory.kind, directory.offset))
+                .collect::<Vec<_>>();
+            assert!(
+                directories
+                    == [
+                        (DirectoryKind::Image, 8),
+                        (DirectoryKind::Exif, 50),
+                        (DirectoryKind::Thumbnail, 76),
+                    ]
+            );
+            assert_eq!(
+                tags(&exif.directories[0]),
+                [
+                    (Some("Make"), "\"Cam\""),
+                    (Some("Orientation"), "6"),
+                    (Some("ExifIFDPointer"), "50"),
+                ]
+            );
+            assert_eq!(exif.directories[0].tags[2].number, Some(50));
+            assert_eq!(
+                tags(&exif.directories[1]),
+                [(Some("ExposureTime"), "1/100")]
+            );
+            assert_eq!(exif.thumbnail, Some((OFFSET + 106, 4)));
+        }
+    }
+
+    #[test]
+    fn broken_structures() {
+        // IFD1 points back at IFD0
+        let mut input = tiff(true);
+        input[46..50].copy_from_slice(&8u32.to_be_bytes());
+        let exif = Exif::parse(&input, OFFSET);
+        assert_eq!(exif.warnings, ["The directories loop back to 0x8"]);
+        assert_eq!(exif.directories.len(), 2);
+
+        // The exposure time is cut off, and IFD1 is past the end
+        let exif = Exif::parse(&tiff(false)[..72], OFFSET);
+        assert_eq!(
+            exif.warnings,
+            [
+                "The IFD1 (thumbnail) at 0x4c is past the end of the data",
+                "The value of ExposureTime is past the end of the data",
+            ]
+        );
+        assert_eq!(exif.directories[1].tags[0].value, "1 values at 0x44");
+
+        let exif = Exif::parse(b"XX\0\0", OFFSET);
+        assert_eq!(exif.warnings, ["The TIFF header has no byte order"]);
+        let exif = Exif::parse(b"II\x2b\0\x08", OFFSET);
+        assert_eq!(
+            exif.warnings,
+            [
+                "The TIFF header doesn't have the magic number 42",
+                "The TIFF header is cut off",
+            ]
+        );
+    }
+}
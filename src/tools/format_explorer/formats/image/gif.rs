@@ -0,0 +1,615 @@
+//! GIF images, a screen descriptor followed by image and extension blocks, whose data is split
+//! in sub-blocks of up to 255 bytes
+
+use super::{super::trailing_data_ui, colors, palette_ui};
+use crate::{
+    error::Error,
+    tools::format_explorer::{
+        formats::Confidence, text_field, FileFormat, FileFormatUi, FormatContext, Layout,
+    },
+};
+
+const EXTENSION: u8 = 0x21;
+const IMAGE: u8 = 0x2c;
+const TRAILER: u8 = 0x3b;
+const PLAIN_TEXT: u8 = 0x01;
+const GRAPHIC_CONTROL: u8 = 0xf9;
+const COMMENT: u8 = 0xfe;
+const APPLICATION: u8 = 0xff;
+
+/// The color table flag of screen and image descriptors
+const COLOR_TABLE: u8 = 0x80;
+/// Blocks read at most
+const MAX_BLOCKS: usize = 100_000;
+/// Blocks shown at most, more would make the explorer crawl
+const SHOWN_BLOCKS: usize = 10_000;
+/// Extension data kept at most, for comments and application data
+const MAX_COLLECTED: usize = 1 << 20;
+/// Characters of comments shown at most
+const SHOWN_TEXT: usize = 2000;
+
+/// Checks the signature and version
+pub fn probe(bytes: &[u8]) -> Confidence {
+    if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Confidence::High
+    } else if bytes.starts_with(b"GIF") {
+        Confidence::Low
+    } else {
+        Confidence::None
+    }
+}
+
+fn signature(signature: &[u8; 6]) -> String {
+    signature.escape_ascii().to_string()
+}
+
+/// Number of colors of a color table, from the size in the low bits of the flags
+fn table_colors(flags: u8) -> usize {
+    2 << (flags & 0x7)
+}
+
+fn screen_flags(flags: &u8) -> String {
+    let mut text = format!("{:#04x}: {}-bit colors", flags, (flags >> 4 & 0x7) + 1);
+    if flags & COLOR_TABLE != 0 {
+        text.push_str(&format!(", global table of {}", table_colors(*flags)));
+        if flags & 0x08 != 0 {
+            text.push_str(", sorted");
+        }
+    }
+    text
+}
+
+fn image_flags(flags: &u8) -> String {
+    let mut properties = Vec::new();
+    if flags & COLOR_TABLE != 0 {
+        properties.push(format!("local table of {}", table_colors(*flags)));
+        if flags & 0x20 != 0 {
+            properties.push("sorted".to_string());
+        }
+    }
+    if flags & 0x40 != 0 {
+        properties.push("interlaced".to_string());
+    }
+    if properties.is_empty() {
+        properties.push("none".to_string());
+    }
+    format!("{:#04x}: {}", flags, properties.join(", "))
+}
+
+fn aspect_ratio(ratio: u8) -> String {
+    match ratio {
+        0 => "not given".to_string(),
+        ratio => format!("{:.3}", (ratio as f64 + 15.0) / 64.0),
+    }
+}
+
+fn control_flags(flags: &u8) -> String {
+    let disposal = match flags >> 2 & 0x7 {
+        0 => "unspecified",
+        1 => "keep",
+        2 => "restore to background",
+        3 => "restore to previous",
+        _ => "unknown",
+    };
+    let mut text = format!("{:#04x}: dispose {}", flags, disposal);
+    if flags & 0x2 != 0 {
+        text.push_str(", waits for input");
+    }
+    if flags & 0x1 != 0 {
+        text.push_str(", transparent");
+    }
+    text
+}
+
+/// Delays are in hundredths of a second
+fn delay(delay: u16) -> String {
+    format!("{} ms", delay as u32 * 10)
+}
+
+#[derive(FileFormat)]
+#[format(little_endian)]
+pub struct ScreenDescriptor {
+    #[format(display = signature)]
+    pub signature: [u8; 6],
+    pub width: u16,
+    pub height: u16,
+    #[format(display = screen_flags)]
+    pub flags: u8,
+    pub background_color: u8,
+    #[format(meaning = aspect_ratio)]
+    pub pixel_aspect_ratio: u8,
+}
+
+#[derive(FileFormat)]
+#[format(little_endian)]
+pub struct ImageDescriptor {
+    #[format(hex)]
+    pub separator: u8,
+    pub left: u16,
+    pub top: u16,
+    pub width: u16,
+    pub height: u16,
+    #[format(display = image_flags)]
+    pub flags: u8,
+}
+
+/// Contents of a graphic control extension, after its label
+#[derive(FileFormat)]
+#[format(little_endian)]
+pub struct GraphicControl {
+    pub block_size: u8,
+    #[format(display = control_flags)]
+    pub flags: u8,
+    #[format(meaning = delay)]
+    pub delay: u16,
+    pub transparent_color: u8,
+}
+
+/// Where sub-blocks starting at `offset` end, after the empty one closing them, and their data
+/// if `collect`. `None` for the end if the file ends first.
+fn sub_blocks(input: &[u8], offset: usize, collect: bool) -> (Option<usize>, Vec<u8>) {
+    let mut data = Vec::new();
+    let mut position = offset;
+    while let Some(&size) = input.get(position) {
+        position += 1;
+        if size == 0 {
+            return (Some(position), data);
+        }
+        let Some(bytes) = input.get(position..position + size as usize) else {
+            break;
+        };
+        if collect && data.len() < MAX_COLLECTED {
+            data.extend_from_slice(bytes);
+        }
+        position += size as usize;
+    }
+    (None, data)
+}
+
+enum BlockKind {
+    Image {
+        descriptor: ImageDescriptor,
+        colors: Vec<[u8; 3]>,
+        minimum_code_size: Option<u8>,
+    },
+    GraphicControl(Option<GraphicControl>),
+    Comment(String),
+    Application {
+        identifier: String,
+        /// Times to play the animation from a NETSCAPE2.0 block, 0 for forever
+        loops: Option<u16>,
+    },
+    PlainText,
+    /// An extension with an unknown label
+    Extension(u8),
+}
+
+impl BlockKind {
+    /// The byte after the introducer of extension blocks
+    fn label(&self) -> Option<u8> {
+        match self {
+            BlockKind::Image { .. } => None,
+            BlockKind::GraphicControl(_) => Some(GRAPHIC_CONTROL),
+            BlockKind::Comment(_) => Some(COMMENT),
+            BlockKind::Application { .. } => Some(APPLICATION),
+            BlockKind::PlainText => Some(PLAIN_TEXT),
+            BlockKind::Extension(label) => Some(*label),
+        }
+    }
+}
+
+pub struct Block {
+    offset: u64,
+    kind: BlockKind,
+    /// Where the sub-blocks are, up to the end of the block
+    data: (u64, u64),
+    warnings: Vec<String>,
+}
+
+impl Block {
+    /// Reads the image block at `offset` in a screen of `width` by `height`
+    fn image(input: &[u8], offset: usize, width: u16, height: u16) -> Option<Self> {
+        let (_, descriptor) = ImageDescriptor::parse(input.get(offset..)?, false).ok()?;
+        let mut warnings = Vec::new();
+        let mut position = offset + ImageDescriptor::SIZE as usize;
+        let mut local_colors = Vec::new();
+        if descriptor.flags & COLOR_TABLE != 0 {
+            let length = 3 * table_colors(descriptor.flags);
+            match input.get(position..position + length) {
+                Some(table) => local_colors = colors(table),
+                None => warnings.push("The local color table is cut off".to_string()),
+            }
+            position += length;
+        }
+        let right = descriptor.left as u32 + descriptor.width as u32;
+        let bottom = descriptor.top as u32 + descriptor.height as u32;
+        if right > width as u32 || bottom > height as u32 {
+            warnings.push("The image goes past the logical screen".to_string());
+        }
+        let minimum_code_size = input.get(position).copied();
+        if minimum_code_size.is_some_and(|size| !(2..=8).contains(&size)) {
+            warnings.push("The LZW code size isn't between 2 and 8".to_string());
+        }
+        let data_start = (position + 1).min(input.len());
+        let (end, _) = sub_blocks(input, data_start, false);
+        if end.is_none() {
+            warnings.push("The image data is cut off".to_string());
+        }
+        let end = end.unwrap_or(input.len());
+        Some(Self {
+            offset: offset as u64,
+            kind: BlockKind::Image {
+                descriptor,
+                colors: local_colors,
+                minimum_code_size,
+            },
+            data: (data_start as u64, (end - data_start) as u64),
+            warnings,
+        })
+    }
+
+    fn extension(input: &[u8], offset: usize) -> Option<Self> {
+        let label = *input.get(offset + 1)?;
+        let data_start = offset + 2;
+        let (end, data) = sub_blocks(input, data_start, true);
+        let mut warnings = Vec::new();
+        if end.is_none() {
+            warnings.push("The extension is cut off".to_string());
+        }
+        let end = end.unwrap_or(input.len());
+        let kind = match label {
+            GRAPHIC_CONTROL => {
+                let control = GraphicControl::parse(&input[data_start..], false)
+                    .ok()
+                    .map(|(_, control)| control)
+                    .filter(|control| control.block_size == 4);
+                if control.is_none() {
+                    warnings.push("The graphic control block isn't 4 bytes".to_string());
+                }
+                BlockKind::GraphicControl(control)
+            }
+            COMMENT => {
+                let text = String::from_utf8_lossy(&data);
+                let shown = text.chars().take(SHOWN_TEXT).collect::<String>();
+                BlockKind::Comment(if shown.len() < text.len() {
+                    format!("{:?} …", shown)
+                } else {
+                    format!("{:?}", shown)
+                })
+            }
+            // An 8-byte name and a 3-byte code come first
+            APPLICATION => {
+                let identifier = data.get(..11).unwrap_or(&data);
+                let loops = match data.get(11..14) {
+                    Some(&[1, low, high]) if identifier == b"NETSCAPE2.0" => {
+                        Some(u16::from_le_bytes([low, high]))
+                    }
+                    _ => None,
+                };
+                BlockKind::Application {
+                    identifier: identifier.escape_ascii().to_string(),
+                    loops,
+                }
+            }
+            PLAIN_TEXT => BlockKind::PlainText,
+            label => BlockKind::Extension(label),
+        };
+        Some(Self {
+            offset: offset as u64,
+            kind,
+            data: (data_start as u64, (end - data_start) as u64),
+            warnings,
+        })
+    }
+
+    fn end(&self) -> u64 {
+        self.data.0 + self.data.1
+    }
+
+    fn title(&self) -> String {
+        match &self.kind {
+            BlockKind::Image { descriptor, .. } => format!(
+                "image {}x{} at ({}, {})",
+                descriptor.width, descriptor.height, descriptor.left, descriptor.top
+            ),
+            BlockKind::GraphicControl(_) => "graphic control".to_string(),
+            BlockKind::Comment(_) => "comment".to_string(),
+            BlockKind::Application { identifier, .. } => format!("application {}", identifier),
+            BlockKind::PlainText => "plain text".to_string(),
+            BlockKind::Extension(label) => format!("extension {:#04x}", label),
+        }
+    }
+}
+
+impl FileFormatUi for Block {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        context.collapsing(ui, name, |ui| {
+            for warning in &self.warnings {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+            if let Some(label) = self.kind.label() {
+                let label = format!("{:#04x}", label);
+                context.at(self.offset + 1, 1).field(ui, "label", &label);
+            }
+            match &mut self.kind {
+                BlockKind::Image {
+                    descriptor,
+                    colors,
+                    minimum_code_size,
+                } => {
+                    let header = context.at(self.offset, ImageDescriptor::SIZE);
+                    descriptor.ui(ui, "image descriptor", &header);
+                    let table = self.offset + ImageDescriptor::SIZE;
+                    if !colors.is_empty() {
+                        let title = format!("local color table ({} colors)", colors.len());
+                        palette_ui(ui, &title, context, table, colors);
+                    }
+                    if let Some(size) = minimum_code_size {
+                        let offset = self.data.0 - 1;
+                        let size = context.number((*size).into());
+                        context.at(offset, 1).field(ui, "LZW code size", &size);
+                    }
+                }
+                BlockKind::GraphicControl(Some(control)) => {
+                    let fields = context.at(self.data.0, GraphicControl::SIZE);
+                    control.ui(ui, "graphic control", &fields);
+                }
+                BlockKind::Comment(text) => text_field(ui, "text", text),
+                BlockKind::Application { identifier, loops } => {
+                    context.at(self.data.0 + 1, 11).field(
+                        ui,
+                        "identifier",
+                        &format!("{:?}", identifier),
+                    );
+                    if let Some(loops) = loops {
+                        let text = match loops {
+                            0 => "forever".to_string(),
+                            loops => loops.to_string(),
+                        };
+                        text_field(ui, "loops", &text);
+                    }
+                }
+                _ => {}
+            }
+            context.range_link(ui, "data", self.data.0, self.data.1);
+        });
+    }
+}
+
+pub struct GifFormat {
+    screen: ScreenDescriptor,
+    global_colors: Vec<[u8; 3]>,
+    blocks: Vec<Block>,
+    /// Where the trailer ends, or where reading the blocks stopped without one
+    end: u64,
+    size: u64,
+    warnings: Vec<String>,
+}
+
+impl GifFormat {
+    pub fn new(input: &[u8]) -> Result<Self, Error> {
+        if !input.starts_with(b"GIF") {
+            return Err(Error::parse("GIF", "the signature doesn't match"));
+        }
+        let (_, screen) = ScreenDescriptor::parse(input, false)
+            .map_err(|err| Error::from_nom("GIF", input, err))?;
+        let mut warnings = Vec::new();
+        let mut position = ScreenDescriptor::SIZE as usize;
+        let mut global_colors = Vec::new();
+        if screen.flags & COLOR_TABLE != 0 {
+            let length = 3 * table_colors(screen.flags);
+            match input.get(position..position + length) {
+                Some(table) => global_colors = colors(table),
+                None => warnings.push("The global color table is cut off".to_string()),
+            }
+            position = (position + length).min(input.len());
+        }
+        let mut blocks = Vec::new();
+        let mut has_trailer = false;
+        while blocks.len() < MAX_BLOCKS {
+            let block = match input.get(position) {
+                None => break,
+                Some(&TRAILER) => {
+                    position += 1;
+                    has_trailer = true;
+                    break;
+                }
+                Some(&IMAGE) => Block::image(input, position, screen.width, screen.height),
+                Some(&EXTENSION) => Block::extension(input, position),
+                Some(&other) => {
+                    warnings.push(format!(
+                        "Unknown block type {:#04x} at {:#x}",
+                        other, position
+                    ));
+                    break;
+                }
+            };
+            let Some(block) = block else {
+                warnings.push(format!("The block at {:#x} is cut off", position));
+                position = input.len();
+                break;
+            };
+            position = block.end() as usize;
+            blocks.push(block);
+        }
+        if !has_trailer {
+            warnings.push("There is no trailer".to_string());
+        }
+        Ok(Self {
+            screen,
+            global_colors,
+            blocks,
+            end: position as u64,
+            size: input.len() as u64,
+            warnings,
+        })
+    }
+
+    /// Screen size, block and frame counts, and where the image ends, for expressions
+    pub fn symbols(&self) -> Vec<(String, u64)> {
+        let frames = self
+            .blocks
+            .iter()
+            .filter(|block| matches!(block.kind, BlockKind::Image { .. }))
+            .count();
+        vec![
+            ("width".to_string(), self.screen.width.into()),
+            ("height".to_string(), self.screen.height.into()),
+            ("blocks".to_string(), self.blocks.len() as u64),
+            ("frames".to_string(), frames as u64),
+            ("image_end".to_string(), self.end),
+        ]
+    }
+}
+
+impl FileFormatUi for GifFormat {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        ui.collapsing(name, |ui| {
+            for warning in &self.warnings {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+            let screen = context.at(0, ScreenDescriptor::SIZE);
+            self.screen.ui(ui, "screen descriptor", &screen);
+            if !self.global_colors.is_empty() {
+                let title = format!("global color table ({} colors)", self.global_colors.len());
+                let offset = ScreenDescriptor::SIZE;
+                palette_ui(ui, &title, context, offset, &self.global_colors);
+            }
+            let title = format!("blocks ({})", self.blocks.len());
+            ui.collapsing(title, |ui| {
+                for (index, block) in self.blocks.iter_mut().take(SHOWN_BLOCKS).enumerate() {
+                    let title = format!("[{}] {}", index, block.title());
+                    let block_context = context.at(block.offset, block.end() - block.offset);
+                    ui.push_id(index, |ui| block.ui(ui, &title, &block_context));
+                }
+                if self.blocks.len() > SHOWN_BLOCKS {
+                    ui.weak(format!("… {} more", self.blocks.len() - SHOWN_BLOCKS));
+                }
+            });
+            trailing_data_ui(ui, context, "image", self.end, self.size);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! The images are built block by block, a 2×2 image with a two-color global table and
+    //! one extension block of each kind before it.
+
+    use super::*;
+
+    /// Offset of the image block
+    const IMAGE_BLOCK: usize = 55;
+
+    fn image() -> Vec<u8> {
+        [
+            &b"GIF89a\x02\x00\x02\x00\x80\x00\x00"[..],
+            &[0, 0, 0, 0xff, 0xff, 0xff],
+            &[EXTENSION, GRAPHIC_CONTROL, 4, 0, 10, 0, 0, 0],
+            &[EXTENSION, COMMENT, 2, b'a', b'b', 2, b'c', b'd', 0],
+            &[EXTENSION, APPLICATION, 11],
+            b"NETSCAPE2.0",
+            &[3, 1, 0, 0, 0],
+            &[IMAGE, 0, 0, 0, 0, 2, 0, 2, 0, 0],
+            &[2, 2, 0x4c, 0x01, 0],
+            &[TRAILER],
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn blocks() {
+        let input = image();
+        assert_eq!(probe(&input), Confidence::High);
+        let gif = GifFormat::new(&input).unwrap();
+        assert!(gif.warnings.is_empty(), "{:?}", gif.warnings);
+        assert_eq!((gif.screen.width, gif.screen.height), (2, 2));
+        assert_eq!(gif.global_colors, [[0, 0, 0], [0xff, 0xff, 0xff]]);
+        assert!(gif.blocks.iter().all(|block| block.warnings.is_empty()));
+        let offsets = gif
+            .blocks
+            .iter()
+            .map(|block| block.offset)
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [19, 27, 36, IMAGE_BLOCK as u64]);
+
+        let BlockKind::GraphicControl(Some(control)) = &gif.blocks[0].kind else {
+            panic!("the graphic control block isn't read");
+        };
+        assert_eq!(control.block_size, 4);
+        assert_eq!(gif.blocks[0].data, (21, 6));
+        let BlockKind::Comment(comment) = &gif.blocks[1].kind else {
+            panic!("the comment isn't read");
+        };
+        assert_eq!(comment, "\"abcd\"");
+        let BlockKind::Application { identifier, loops } = &gif.blocks[2].kind else {
+            panic!("the application block isn't read");
+        };
+        assert_eq!((identifier.as_str(), *loops), ("NETSCAPE2.0", Some(0)));
+        let BlockKind::Image {
+            descriptor,
+            colors,
+            minimum_code_size,
+        } = &gif.blocks[3].kind
+        else {
+            panic!("the image isn't read");
+        };
+        assert_eq!((descriptor.width, descriptor.height), (2, 2));
+        assert!(colors.is_empty());
+        assert_eq!(*minimum_code_size, Some(2));
+        assert_eq!(gif.blocks[3].data, (IMAGE_BLOCK as u64 + 11, 4));
+        assert_eq!(
+            (gif.end, gif.size),
+            (input.len() as u64, input.len() as u64)
+        );
+    }
+
+    #[test]
+    fn sub_block_chains() {
+        let input = [&[0xaa][..], &[3, 1, 2, 3, 1, 4, 0, 0xbb]].concat();
+        assert_eq!(sub_blocks(&input, 1, true), (Some(8), vec![1, 2, 3, 4]));
+        assert_eq!(sub_blocks(&input, 1, false), (Some(8), vec![]));
+        assert_eq!(sub_blocks(&input[..6], 1, true), (None, vec![1, 2, 3]));
+        assert_eq!(sub_blocks(&input[..4], 1, true), (None, vec![]));
+    }
+
+    #[test]
+    fn trailing_data_and_cut_off() {
+        let mut input = image();
+        let end = input.len() as u64;
+        input.extend(b"appended");
+        let gif = GifFormat::new(&input).unwrap();
+        assert!(gif.warnings.is_empty(), "{:?}", gif.warnings);
+        assert_eq!((gif.end, gif.size), (end, input.len() as u64));
+
+        // Cut inside the image data
+        let gif = GifFormat::new(&input[..IMAGE_BLOCK + 13]).unwrap();
+        assert_eq!(gif.warnings, ["There is no trailer"]);
+        assert_eq!(gif.blocks[3].warnings, ["The image data is cut off"]);
+        assert_eq!(gif.end, gif.size);
+
+        // Cut inside the image descriptor
+        let gif = GifFormat::new(&input[..IMAGE_BLOCK + 4]).unwrap();
+        assert_eq!(
+            gif.warnings,
+            [
+                format!("The block at {:#x} is cut off", IMAGE_BLOCK),
+                "There is no trailer".to_string(),
+            ]
+        );
+        assert_eq!(gif.blocks.len(), 3);
+
+        // An image past the screen, with a code size out of range
+        let mut input = image();
+        input[IMAGE_BLOCK + 1] = 1;
+        input[IMAGE_BLOCK + 10] = 12;
+        let gif = GifFormat::new(&input).unwrap();
+        assert_eq!(
+            gif.blocks[3].warnings,
+            [
+                "The image goes past the logical screen",
+                "The LZW code size isn't between 2 and 8",
+            ]
+        );
+    }
+}
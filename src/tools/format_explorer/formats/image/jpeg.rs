@@ -0,0 +1,785 @@
+//! JPEG images, marker segments with the entropy-coded data of a scan after each SOS segment
+
+use super::{super::trailing_data_ui, exif::Exif};
+use crate::{
+    error::Error,
+    tools::format_explorer::{
+        formats::Confidence, text_field, FileFormat, FileFormatUi, FormatContext, Layout,
+    },
+};
+
+const TEM: u8 = 0x01;
+const DHT: u8 = 0xc4;
+const SOI: u8 = 0xd8;
+const EOI: u8 = 0xd9;
+const SOS: u8 = 0xda;
+const DQT: u8 = 0xdb;
+const DRI: u8 = 0xdd;
+const APP0: u8 = 0xe0;
+const APP1: u8 = 0xe1;
+const COM: u8 = 0xfe;
+
+/// Segments read at most
+const MAX_SEGMENTS: usize = 100_000;
+/// Segments shown at most, more would make the explorer crawl
+const SHOWN_SEGMENTS: usize = 10_000;
+/// Characters of comments and XMP shown at most
+const SHOWN_TEXT: usize = 2000;
+/// APPn identifiers are NUL-terminated strings no longer than this
+const MAX_IDENTIFIER: usize = 32;
+const XMP_IDENTIFIER: &str = "http://ns.adobe.com/xap/1.0/";
+
+/// Checks for the SOI marker and the start of the next one
+pub fn probe(bytes: &[u8]) -> Confidence {
+    match bytes {
+        [0xff, SOI, 0xff, ..] => Confidence::High,
+        [0xff, SOI, ..] => Confidence::Low,
+        _ => Confidence::None,
+    }
+}
+
+fn marker_name(marker: u8) -> String {
+    match marker {
+        TEM => "TEM".to_string(),
+        DHT => "DHT".to_string(),
+        0xc8 => "JPG".to_string(),
+        0xcc => "DAC".to_string(),
+        0xc0..=0xcf => format!("SOF{}", marker - 0xc0),
+        0xd0..=0xd7 => format!("RST{}", marker - 0xd0),
+        SOI => "SOI".to_string(),
+        EOI => "EOI".to_string(),
+        SOS => "SOS".to_string(),
+        DQT => "DQT".to_string(),
+        0xdc => "DNL".to_string(),
+        DRI => "DRI".to_string(),
+        0xde => "DHP".to_string(),
+        0xdf => "EXP".to_string(),
+        0xe0..=0xef => format!("APP{}", marker - APP0),
+        0xf0..=0xfd => format!("JPG{}", marker - 0xf0),
+        COM => "COM".to_string(),
+        _ => "reserved".to_string(),
+    }
+}
+
+/// Start of frame markers, the ones in their range that aren't something else
+fn is_frame(marker: u8) -> bool {
+    matches!(marker, 0xc0..=0xcf) && !matches!(marker, DHT | 0xc8 | 0xcc)
+}
+
+/// Process and entropy coding of a start of frame marker, which its low bits encode
+fn frame_kind(marker: u8) -> String {
+    let differential = marker & 0x4 != 0;
+    let process = match marker & 0x3 {
+        0 => "baseline",
+        1 if differential => "sequential",
+        1 => "extended sequential",
+        2 => "progressive",
+        _ => "lossless",
+    };
+    let coding = if marker & 0x8 != 0 {
+        "arithmetic"
+    } else {
+        "Huffman"
+    };
+    let prefix = if differential { "differential " } else { "" };
+    format!("{}{}, {} coding", prefix, process, coding)
+}
+
+/// Markers without a length or data
+fn is_standalone(marker: u8) -> bool {
+    matches!(marker, TEM | 0xd0..=0xd9)
+}
+
+fn be16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+/// Where the next marker is at or after `from`, past the 0xff bytes markers can be padded
+/// with. Entropy-coded data escapes 0xff as 0xff 0x00 and has restart markers in it.
+fn next_marker(input: &[u8], from: usize, in_scan: bool) -> Option<usize> {
+    (from..input.len().saturating_sub(1)).find(|&index| {
+        input[index] == 0xff
+            && match input[index + 1] {
+                0x00 | 0xff => false,
+                0xd0..=0xd7 => !in_scan,
+                _ => true,
+            }
+    })
+}
+
+fn text(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let shown = text.chars().take(SHOWN_TEXT).collect::<String>();
+    if shown.len() < text.len() {
+        format!("{:?} …", shown)
+    } else {
+        format!("{:?}", shown)
+    }
+}
+
+fn jfif_version(version: &u16) -> String {
+    format!("{}.{:02}", version >> 8, version & 0xff)
+}
+
+fn density_unit(unit: u8) -> String {
+    match unit {
+        0 => "aspect ratio",
+        1 => "dots per inch",
+        2 => "dots per cm",
+        _ => "unknown",
+    }
+    .to_string()
+}
+
+/// The APP0 segment of JFIF files, after the `JFIF` identifier
+#[derive(FileFormat)]
+#[format(big_endian)]
+pub struct JfifHeader {
+    #[format(display = jfif_version)]
+    pub version: u16,
+    #[format(meaning = density_unit)]
+    pub units: u8,
+    pub x_density: u16,
+    pub y_density: u16,
+    pub thumbnail_width: u8,
+    pub thumbnail_height: u8,
+}
+
+struct Component {
+    id: u8,
+    /// Horizontal sampling factor in the high nibble, vertical in the low one
+    sampling: u8,
+    table: u8,
+}
+
+struct Frame {
+    precision: u8,
+    height: u16,
+    width: u16,
+    components: Vec<Component>,
+}
+
+struct QuantizationTable {
+    offset: u64,
+    /// 0 for 8-bit values, 1 for 16-bit ones
+    precision: u8,
+    id: u8,
+    /// In zigzag order
+    values: Vec<u16>,
+}
+
+struct HuffmanTable {
+    offset: u64,
+    /// 0 for DC, 1 for AC
+    class: u8,
+    id: u8,
+    /// Number of codes of each length from 1 to 16 bits
+    counts: [u8; 16],
+}
+
+impl HuffmanTable {
+    fn symbols(&self) -> u64 {
+        self.counts.iter().map(|&count| count as u64).sum()
+    }
+}
+
+struct ScanHeader {
+    /// Component selector, and DC and AC tables in the high and low nibbles
+    components: Vec<(u8, u8)>,
+    spectral_start: u8,
+    spectral_end: u8,
+    /// Successive approximation bit positions, high and low nibbles
+    approximation: u8,
+}
+
+/// What a segment holds, for the markers we know
+enum Details {
+    Frame(Frame),
+    Scan(ScanHeader),
+    Quantization(Vec<QuantizationTable>),
+    Huffman(Vec<HuffmanTable>),
+    RestartInterval(u16),
+    Jfif(JfifHeader),
+    Exif(Exif),
+    Text(String),
+    Other,
+}
+
+impl Details {
+    fn parse(marker: u8, data: &[u8], offset: u64, warnings: &mut Vec<String>) -> Self {
+        let cut_off = |warnings: &mut Vec<String>| {
+            warnings.push(format!("The {} segment is cut off", marker_name(marker)));
+        };
+        match marker {
+            _ if is_frame(marker) => {
+                let (Some(&precision), Some(height), Some(width), Some(&count)) =
+                    (data.first(), be16(data, 1), be16(data, 3), data.get(5))
+                else {
+                    cut_off(warnings);
+                    return Details::Other;
+                };
+                let components = data[6..]
+                    .chunks_exact(3)
+                    .take(count as usize)
+                    .map(|component| Component {
+                        id: component[0],
+                        sampling: component[1],
+                        table: component[2],
+                    })
+                    .collect::<Vec<_>>();
+                if data.len() != 6 + 3 * count as usize {
+                    warnings.push(format!(
+                        "The segment is {} bytes for {} components",
+                        data.len() + 2,
+                        count
+                    ));
+                }
+                if height == 0 {
+                    warnings.push("The height is set by a DNL segment after the scan".to_string());
+                }
+                Details::Frame(Frame {
+                    precision,
+                    height,
+                    width,
+                    components,
+                })
+            }
+            SOS => {
+                let count = data.first().copied().unwrap_or_default() as usize;
+                let end = 1 + 2 * count;
+                let Some(&[spectral_start, spectral_end, approximation]) = data.get(end..end + 3)
+                else {
+                    cut_off(warnings);
+                    return Details::Other;
+                };
+                let components = data[1..end]
+                    .chunks_exact(2)
+                    .map(|component| (component[0], component[1]))
+                    .collect();
+                Details::Scan(ScanHeader {
+                    components,
+                    spectral_start,
+                    spectral_end,
+                    approximation,
+                })
+            }
+            DQT => {
+                let mut tables = Vec::new();
+                let mut position = 0;
+                while let Some(&specification) = data.get(position) {
+                    let precision = specification >> 4;
+                    if precision > 1 {
+                        warnings.push(format!("Unknown table precision {}", precision));
+                        break;
+                    }
+                    let size = 1 + precision as usize;
+                    let Some(values) = data.get(position + 1..position + 1 + 64 * size) else {
+                        cut_off(warnings);
+                        break;
+                    };
+                    let values = values
+                        .chunks_exact(size)
+                        .map(|value| match value {
+                            [high, low] => u16::from_be_bytes([*high, *low]),
+                            _ => value[0] as u16,
+                        })
+                        .collect();
+                    tables.push(QuantizationTable {
+                        offset: offset + position as u64,
+                        precision,
+                        id: specification & 0xf,
+                        values,
+                    });
+                    position += 1 + 64 * size;
+                }
+                Details::Quantization(tables)
+            }
+            DHT => {
+                let mut tables = Vec::new();
+                let mut position = 0;
+                while let Some(&class_id) = data.get(position) {
+                    let Some(counts) = data.get(position + 1..position + 17) else {
+                        cut_off(warnings);
+                        break;
+                    };
+                    let table = HuffmanTable {
+                        offset: offset + position as u64,
+                        class: class_id >> 4,
+                        id: class_id & 0xf,
+                        counts: counts.try_into().unwrap(),
+                    };
+                    position += 17 + table.symbols() as usize;
+                    if position > data.len() {
+                        cut_off(warnings);
+                        break;
+                    }
+                    tables.push(table);
+                }
+                Details::Huffman(tables)
+            }
+            DRI => match be16(data, 0) {
+                Some(interval) => Details::RestartInterval(interval),
+                None => {
+                    cut_off(warnings);
+                    Details::Other
+                }
+            },
+            APP0 if data.starts_with(b"JFIF\0") => match JfifHeader::parse(&data[5..], true) {
+                Ok((_, header)) => Details::Jfif(header),
+                Err(_) => {
+                    cut_off(warnings);
+                    Details::Other
+                }
+            },
+            APP1 if data.starts_with(b"Exif\0\0") => {
+                Details::Exif(Exif::parse(&data[6..], offset + 6))
+            }
+            APP1 if data.starts_with(XMP_IDENTIFIER.as_bytes()) => {
+                Details::Text(text(&data[XMP_IDENTIFIER.len() + 1..]))
+            }
+            COM => Details::Text(text(data)),
+            _ => Details::Other,
+        }
+    }
+}
+
+/// NUL-terminated name at the start of APPn segments, like `Exif` or `ICC_PROFILE`
+fn identifier(data: &[u8]) -> Option<String> {
+    let end = data.iter().take(MAX_IDENTIFIER).position(|&b| b == 0)?;
+    let name = &data[..end];
+    let printable = !name.is_empty() && name.iter().all(|b| b.is_ascii_graphic() || *b == b' ');
+    printable.then(|| String::from_utf8_lossy(name).into_owned())
+}
+
+pub struct Segment {
+    /// Where the marker is, from its 0xff byte
+    offset: u64,
+    marker: u8,
+    /// Length of the segment after the marker, counting itself, `None` for markers without one
+    length: Option<u16>,
+    identifier: Option<String>,
+    details: Details,
+    /// Entropy-coded data after a SOS segment, up to the next marker
+    scan: Option<(u64, u64)>,
+    warnings: Vec<String>,
+}
+
+impl Segment {
+    fn new(offset: usize, marker: u8) -> Self {
+        Self {
+            offset: offset as u64,
+            marker,
+            length: None,
+            identifier: None,
+            details: Details::Other,
+            scan: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn title(&self) -> String {
+        let mut title = marker_name(self.marker);
+        if let Some(identifier) = &self.identifier {
+            title.push(' ');
+            title.push_str(identifier);
+        }
+        title
+    }
+
+    /// Bytes from the marker to the end of the segment, without the scan data
+    fn size(&self, file_size: u64) -> u64 {
+        (2 + self.length.unwrap_or_default() as u64).min(file_size - self.offset)
+    }
+}
+
+impl FileFormatUi for Segment {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        context.collapsing(ui, name, |ui| {
+            for warning in &self.warnings {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+            let marker = 0xff00 | self.marker as u64;
+            context
+                .at(self.offset, 2)
+                .enum_field(ui, "marker", marker, &marker_name(self.marker));
+            if let Some(length) = self.length {
+                context
+                    .at(self.offset + 2, 2)
+                    .field(ui, "length", &context.number(length.into()));
+            }
+            let data = self.offset + 4;
+            if let Some(identifier) = &self.identifier {
+                let length = identifier.len() as u64 + 1;
+                context
+                    .at(data, length)
+                    .field(ui, "identifier", &format!("{:?}", identifier));
+            }
+            match &mut self.details {
+                Details::Frame(frame) => {
+                    text_field(ui, "kind", &frame_kind(self.marker));
+                    let precision = context.number(frame.precision.into());
+                    context.at(data, 1).field(ui, "precision", &precision);
+                    let height = context.number(frame.height.into());
+                    context.at(data + 1, 2).field(ui, "height", &height);
+                    let width = context.number(frame.width.into());
+                    context.at(data + 3, 2).field(ui, "width", &width);
+                    for (index, component) in frame.components.iter().enumerate() {
+                        let text = format!(
+                            "{}x{} sampling, quantization table {}",
+                            component.sampling >> 4,
+                            component.sampling & 0xf,
+                            component.table
+                        );
+                        context.at(data + 6 + 3 * index as u64, 3).field(
+                            ui,
+                            &format!("component {}", component.id),
+                            &text,
+                        );
+                    }
+                }
+                Details::Scan(scan) => {
+                    for (index, (selector, tables)) in scan.components.iter().enumerate() {
+                        let text = format!("DC table {}, AC table {}", tables >> 4, tables & 0xf);
+                        context.at(data + 1 + 2 * index as u64, 2).field(
+                            ui,
+                            &format!("component {}", selector),
+                            &text,
+                        );
+                    }
+                    let end = data + 1 + 2 * scan.components.len() as u64;
+                    let spectral = format!("{}..={}", scan.spectral_start, scan.spectral_end);
+                    context
+                        .at(end, 2)
+                        .field(ui, "spectral selection", &spectral);
+                    let approximation = format!(
+                        "high bit {}, low bit {}",
+                        scan.approximation >> 4,
+                        scan.approximation & 0xf
+                    );
+                    context
+                        .at(end + 2, 1)
+                        .field(ui, "successive approximation", &approximation);
+                }
+                Details::Quantization(tables) => {
+                    for table in tables {
+                        let bits = if table.precision == 0 { 8 } else { 16 };
+                        let length = 1 + table.values.len() as u64 * (bits / 8);
+                        let title = format!("table {} ({}-bit, zigzag order)", table.id, bits);
+                        context
+                            .at(table.offset, length)
+                            .collapsing(ui, &title, |ui| {
+                                for row in table.values.chunks(8) {
+                                    let row = row
+                                        .iter()
+                                        .map(|value| format!("{:>5}", value))
+                                        .collect::<String>();
+                                    ui.monospace(row);
+                                }
+                            });
+                    }
+                }
+                Details::Huffman(tables) => {
+                    for table in tables {
+                        let class = if table.class == 0 { "DC" } else { "AC" };
+                        let counts = table.counts.map(|count| count.to_string()).join(" ");
+                        let text = format!("{} codes, by length: {}", table.symbols(), counts);
+                        context.at(table.offset, 17 + table.symbols()).field(
+                            ui,
+                            &format!("{} table {}", class, table.id),
+                            &text,
+                        );
+                    }
+                }
+                Details::RestartInterval(interval) => {
+                    let interval = context.number((*interval).into());
+                    context.at(data, 2).field(ui, "restart interval", &interval);
+                }
+                Details::Jfif(header) => {
+                    header.ui(ui, "JFIF", &context.at(data + 5, JfifHeader::SIZE));
+                }
+                Details::Exif(exif) => exif.ui(ui, "Exif", context),
+                Details::Text(text) => text_field(ui, "text", text),
+                Details::Other => {}
+            }
+            if let Some((offset, length)) = self.scan {
+                context.range_link(ui, "scan data", offset, length);
+            }
+        });
+    }
+}
+
+pub struct JpegFormat {
+    segments: Vec<Segment>,
+    /// Where the EOI marker ends, or the end of the file without one
+    end: u64,
+    size: u64,
+    warnings: Vec<String>,
+}
+
+impl JpegFormat {
+    pub fn new(input: &[u8]) -> Result<Self, Error> {
+        if !input.starts_with(&[0xff, SOI]) {
+            return Err(Error::parse("JPEG", "there is no SOI marker at the start"));
+        }
+        let size = input.len() as u64;
+        let mut segments = vec![Segment::new(0, SOI)];
+        let mut warnings = Vec::new();
+        let mut position = 2;
+        let mut end = None;
+        while segments.len() < MAX_SEGMENTS {
+            let Some(offset) = next_marker(input, position, false) else {
+                warnings.push("There is no EOI marker".to_string());
+                break;
+            };
+            let skipped = &input[position..offset];
+            if skipped.iter().any(|&b| b != 0xff) {
+                warnings.push(format!(
+                    "{} bytes at {:#x} aren't in a segment",
+                    skipped.len(),
+                    position
+                ));
+            }
+            let marker = input[offset + 1];
+            let mut segment = Segment::new(offset, marker);
+            position = offset + 2;
+            if is_standalone(marker) {
+                if marker == SOI {
+                    segment
+                        .warnings
+                        .push("SOI in the middle of the image".to_string());
+                }
+                segments.push(segment);
+                if marker == EOI {
+                    end = Some(position as u64);
+                    break;
+                }
+                continue;
+            }
+            let length = be16(input, position).filter(|&length| length >= 2);
+            let Some(length) = length else {
+                segment
+                    .warnings
+                    .push("The length is cut off or less than 2".to_string());
+                segments.push(segment);
+                break;
+            };
+            segment.length = Some(length);
+            let data_start = position + 2;
+            let data_end = position + length as usize;
+            let data = match input.get(data_start..data_end) {
+                Some(data) => data,
+                None => {
+                    segment
+                        .warnings
+                        .push("The segment goes past the end of the file".to_string());
+                    input.get(data_start..).unwrap_or_default()
+                }
+            };
+            if (APP0..=0xef).contains(&marker) {
+                segment.identifier = identifier(data);
+            }
+            segment.details =
+                Details::parse(marker, data, data_start as u64, &mut segment.warnings);
+            position = data_end.min(input.len());
+            if marker == SOS {
+                let scan_end = next_marker(input, position, true).unwrap_or(input.len());
+                segment.scan = Some((position as u64, (scan_end - position) as u64));
+                position = scan_end;
+            }
+            segments.push(segment);
+        }
+        if !segments.iter().any(|segment| is_frame(segment.marker)) {
+            warnings.push("There is no start of frame segment".to_string());
+        }
+        if !segments.iter().any(|segment| segment.marker == SOS) {
+            warnings.push("There is no scan".to_string());
+        }
+        Ok(Self {
+            segments,
+            end: end.unwrap_or(size),
+            size,
+            warnings,
+        })
+    }
+
+    fn frame(&self) -> Option<&Frame> {
+        self.segments
+            .iter()
+            .find_map(|segment| match &segment.details {
+                Details::Frame(frame) => Some(frame),
+                _ => None,
+            })
+    }
+
+    /// Image size, segment count and where the image ends, for expressions
+    pub fn symbols(&self) -> Vec<(String, u64)> {
+        let mut symbols = vec![
+            ("segments".to_string(), self.segments.len() as u64),
+            ("image_end".to_string(), self.end),
+        ];
+        if let Some(frame) = self.frame() {
+            symbols.push(("width".to_string(), frame.width.into()));
+            symbols.push(("height".to_string(), frame.height.into()));
+        }
+        symbols
+    }
+}
+
+impl FileFormatUi for JpegFormat {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        ui.collapsing(name, |ui| {
+            for warning in &self.warnings {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+            let title = format!("segments ({})", self.segments.len());
+            ui.collapsing(title, |ui| {
+                for (index, segment) in self.segments.iter_mut().take(SHOWN_SEGMENTS).enumerate() {
+                    let title = format!("[{}] {}", index, segment.title());
+                    let segment_context = context.at(segment.offset, segment.size(self.size));
+                    ui.push_id(index, |ui| segment.ui(ui, &title, &segment_context));
+                }
+                if self.segments.len() > SHOWN_SEGMENTS {
+                    ui.weak(format!("… {} more", self.segments.len() - SHOWN_SEGMENTS));
+                }
+            });
+            trailing_data_ui(ui, context, "image", self.end, self.size);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! The images are built segment by segment, a 3×2 grayscale baseline image whose scan
+    //! data is only there for the markers in it.
+
+    use super::*;
+
+    fn segment(marker: u8, data: &[u8]) -> Vec<u8> {
+        let length = (data.len() as u16 + 2).to_be_bytes();
+        [&[0xff, marker][..], &length, data].concat()
+    }
+
+    /// Offset of the scan data
+    const SCAN: usize = 2 + 2 + 67 + 2 + 11 + 2 + 20 + 2 + 8;
+
+    fn image() -> Vec<u8> {
+        let mut huffman = vec![0x00, 0, 1];
+        huffman.extend([0; 14]);
+        huffman.push(0x05);
+        [
+            &[0xff, SOI][..],
+            &segment(DQT, &[&[0x00][..], &[1; 64]].concat()),
+            &segment(0xc0, &[8, 0, 2, 0, 3, 1, 1, 0x11, 0]),
+            &segment(DHT, &huffman),
+            &segment(SOS, &[1, 1, 0x00, 0, 63, 0]),
+            // A stuffed 0xff, fill bytes and a restart marker stay in the scan
+            &[0x12, 0xff, 0x00, 0x34, 0xff, 0xd0, 0x56],
+            &[0xff, 0xff, EOI],
+        ]
+        .concat()
+    }
+
+    fn markers(jpeg: &JpegFormat) -> Vec<u8> {
+        jpeg.segments.iter().map(|segment| segment.marker).collect()
+    }
+
+    #[test]
+    fn segments() {
+        let input = image();
+        let jpeg = JpegFormat::new(&input).unwrap();
+        assert!(jpeg.warnings.is_empty(), "{:?}", jpeg.warnings);
+        assert_eq!(markers(&jpeg), [SOI, DQT, 0xc0, DHT, SOS, EOI]);
+        assert!(jpeg
+            .segments
+            .iter()
+            .all(|segment| segment.warnings.is_empty()));
+        assert_eq!(jpeg.segments[4].scan, Some((SCAN as u64, 8)));
+        assert_eq!(jpeg.segments[5].offset, SCAN as u64 + 8);
+        assert_eq!(
+            (jpeg.end, jpeg.size),
+            (input.len() as u64, input.len() as u64)
+        );
+
+        let Details::Frame(frame) = &jpeg.segments[2].details else {
+            panic!("SOF0 isn't read as a frame");
+        };
+        assert_eq!((frame.precision, frame.width, frame.height), (8, 3, 2));
+        assert_eq!(frame.components.len(), 1);
+        let component = &frame.components[0];
+        assert_eq!(
+            (component.id, component.sampling, component.table),
+            (1, 0x11, 0)
+        );
+        assert_eq!(jpeg.frame().unwrap().width, 3);
+
+        let Details::Huffman(tables) = &jpeg.segments[3].details else {
+            panic!("DHT isn't read as Huffman tables");
+        };
+        assert_eq!(tables.len(), 1);
+        assert_eq!(
+            (tables[0].class, tables[0].id, tables[0].symbols()),
+            (0, 0, 1)
+        );
+        assert_eq!(tables[0].offset, SCAN as u64 - 8 - 20);
+
+        let Details::Quantization(tables) = &jpeg.segments[1].details else {
+            panic!("DQT isn't read as quantization tables");
+        };
+        assert_eq!((tables[0].precision, tables[0].values.len()), (0, 64));
+    }
+
+    #[test]
+    fn broken_segments() {
+        // A frame with fewer components than it says, and Huffman tables with missing symbols
+        let mut huffman = vec![0x10, 0, 3];
+        huffman.extend([0; 14]);
+        huffman.push(0x05);
+        let input = [
+            &[0xff, SOI][..],
+            &segment(0xc2, &[8, 0, 0, 0, 3, 2, 1, 0x11, 0]),
+            &segment(DHT, &huffman),
+            &[0xff, EOI],
+        ]
+        .concat();
+        let jpeg = JpegFormat::new(&input).unwrap();
+        assert_eq!(jpeg.warnings, ["There is no scan"]);
+        assert_eq!(
+            jpeg.segments[1].warnings,
+            [
+                "The segment is 11 bytes for 2 components",
+                "The height is set by a DNL segment after the scan",
+            ]
+        );
+        assert_eq!(jpeg.segments[2].warnings, ["The DHT segment is cut off"]);
+        assert!(matches!(&jpeg.segments[2].details, Details::Huffman(tables) if tables.is_empty()));
+    }
+
+    #[test]
+    fn trailing_data_and_cut_off() {
+        let mut input = image();
+        let end = input.len() as u64;
+        input.extend(b"appended");
+        let jpeg = JpegFormat::new(&input).unwrap();
+        assert!(jpeg.warnings.is_empty(), "{:?}", jpeg.warnings);
+        assert_eq!((jpeg.end, jpeg.size), (end, input.len() as u64));
+
+        // Without EOI the scan runs to the end of the file
+        let jpeg = JpegFormat::new(&input[..SCAN + 4]).unwrap();
+        assert_eq!(jpeg.warnings, ["There is no EOI marker"]);
+        assert_eq!(jpeg.segments.last().unwrap().scan, Some((SCAN as u64, 4)));
+        assert_eq!(jpeg.end, SCAN as u64 + 4);
+
+        // A segment longer than the file
+        let jpeg = JpegFormat::new(&input[..10]).unwrap();
+        assert_eq!(markers(&jpeg), [SOI, DQT]);
+        assert_eq!(
+            jpeg.segments[1].warnings,
+            [
+                "The segment goes past the end of the file",
+                "The DQT segment is cut off"
+            ]
+        );
+        assert!(JpegFormat::new(b"\xff\xd9").is_err());
+    }
+}
@@ -0,0 +1,51 @@
+//! Image formats, parsed down to their chunks, segments and blocks rather than their pixels
+
+use crate::tools::format_explorer::FormatContext;
+
+mod exif;
+pub mod gif;
+pub mod jpeg;
+pub mod png;
+
+/// Colors of a palette in a row
+const PALETTE_COLUMNS: usize = 16;
+/// Side of a palette color in points
+const SWATCH_SIZE: f32 = 14.0;
+
+/// Color swatches of a palette, each tied to its three bytes at `offset`
+fn palette_ui(
+    ui: &mut egui::Ui,
+    title: &str,
+    context: &FormatContext,
+    offset: u64,
+    colors: &[[u8; 3]],
+) {
+    let length = 3 * colors.len() as u64;
+    context.at(offset, length).collapsing(ui, title, |ui| {
+        for (row, chunk) in colors.chunks(PALETTE_COLUMNS).enumerate() {
+            ui.horizontal(|ui| {
+                ui.spacing_mut().item_spacing.x = 2.0;
+                for (column, &[red, green, blue]) in chunk.iter().enumerate() {
+                    let index = row * PALETTE_COLUMNS + column;
+                    let size = egui::vec2(SWATCH_SIZE, SWATCH_SIZE);
+                    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+                    let color = egui::Color32::from_rgb(red, green, blue);
+                    ui.painter().rect_filled(rect, 2.0, color);
+                    let name = format!("[{}] #{:02x}{:02x}{:02x}", index, red, green, blue);
+                    let response = response.on_hover_text(&name);
+                    context
+                        .at(offset + 3 * index as u64, 3)
+                        .track(&response, &name);
+                }
+            });
+        }
+    });
+}
+
+/// Groups bytes in threes, leaving out any incomplete color at the end
+fn colors(bytes: &[u8]) -> Vec<[u8; 3]> {
+    bytes
+        .chunks_exact(3)
+        .map(|color| [color[0], color[1], color[2]])
+        .collect()
+}
@@ -0,0 +1,642 @@
+//! PNG images, a signature followed by chunks that each end with a CRC of their type and data
+
+use miniz_oxide::inflate::decompress_to_vec_zlib_with_limit;
+
+use super::{super::trailing_data_ui, colors, exif::Exif, palette_ui};
+use crate::{
+    error::Error,
+    tools::format_explorer::{
+        formats::Confidence, text_field, FileFormat, FileFormatUi, FormatContext, Layout,
+    },
+};
+
+const SIGNATURE: [u8; 8] = *b"\x89PNG\r\n\x1a\n";
+/// Chunks shown at most, more would make the explorer crawl
+const SHOWN_CHUNKS: usize = 10_000;
+/// Compressed text is decompressed up to this many bytes
+const MAX_TEXT: usize = 1 << 20;
+/// Characters of text shown at most
+const SHOWN_TEXT: usize = 2000;
+
+/// Checks the signature, and that the first chunk is the header
+pub fn probe(bytes: &[u8]) -> Confidence {
+    if !bytes.starts_with(&SIGNATURE) {
+        Confidence::None
+    } else if bytes.get(12..16) == Some(b"IHDR") {
+        Confidence::High
+    } else {
+        Confidence::Low
+    }
+}
+
+/// The letter case of each byte of a chunk type is a property of the chunk
+fn chunk_type(kind: &[u8; 4]) -> String {
+    let mut properties = vec![if kind[0].is_ascii_lowercase() {
+        "ancillary"
+    } else {
+        "critical"
+    }];
+    if kind[1].is_ascii_lowercase() {
+        properties.push("private");
+    }
+    if kind[2].is_ascii_lowercase() {
+        properties.push("reserved bit set");
+    }
+    if kind[3].is_ascii_lowercase() {
+        properties.push("safe to copy");
+    }
+    format!("{} ({})", kind.escape_ascii(), properties.join(", "))
+}
+
+fn color_type_name(color_type: u8) -> String {
+    match color_type {
+        0 => "grayscale",
+        2 => "RGB",
+        3 => "palette",
+        4 => "grayscale with alpha",
+        6 => "RGBA",
+        _ => "unknown",
+    }
+    .to_string()
+}
+
+fn compression_name(method: u8) -> String {
+    match method {
+        0 => "deflate",
+        _ => "unknown",
+    }
+    .to_string()
+}
+
+fn filter_name(method: u8) -> String {
+    match method {
+        0 => "adaptive",
+        _ => "unknown",
+    }
+    .to_string()
+}
+
+fn interlace_name(method: u8) -> String {
+    match method {
+        0 => "none",
+        1 => "Adam7",
+        _ => "unknown",
+    }
+    .to_string()
+}
+
+/// Contents of the IHDR chunk
+#[derive(FileFormat)]
+#[format(big_endian)]
+pub struct ImageHeader {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    #[format(meaning = color_type_name)]
+    pub color_type: u8,
+    #[format(meaning = compression_name)]
+    pub compression: u8,
+    #[format(meaning = filter_name)]
+    pub filter: u8,
+    #[format(meaning = interlace_name)]
+    pub interlace: u8,
+}
+
+impl ImageHeader {
+    /// What's wrong with the header, the spec only allows some bit depths for each color type
+    fn problem(&self) -> Option<String> {
+        let depths: &[u8] = match self.color_type {
+            0 => &[1, 2, 4, 8, 16],
+            3 => &[1, 2, 4, 8],
+            2 | 4 | 6 => &[8, 16],
+            other => return Some(format!("Unknown color type {}", other)),
+        };
+        if !depths.contains(&self.bit_depth) {
+            return Some(format!(
+                "A bit depth of {} isn't allowed for {} images",
+                self.bit_depth,
+                color_type_name(self.color_type)
+            ));
+        }
+        if self.width == 0 || self.height == 0 {
+            return Some("The image is empty".to_string());
+        }
+        None
+    }
+}
+
+/// What a chunk holds, for the types we know
+enum Details {
+    Header(ImageHeader),
+    Palette(Vec<[u8; 3]>),
+    /// tEXt, zTXt and iTXt chunks
+    Text {
+        keyword: String,
+        /// Language and translated keyword of iTXt chunks
+        language: Option<(String, String)>,
+        text: String,
+    },
+    Exif(Exif),
+    Other,
+}
+
+/// Bytes up to the first NUL and the ones after it, like a keyword and the rest of a chunk
+fn split_at_nul(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let end = data.iter().position(|&b| b == 0)?;
+    Some((&data[..end], &data[end + 1..]))
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    decompress_to_vec_zlib_with_limit(data, MAX_TEXT)
+        .map_err(|_| "The compressed text doesn't decompress".to_string())
+}
+
+impl Details {
+    fn parse(kind: &[u8; 4], data: &[u8], offset: u64, warnings: &mut Vec<String>) -> Self {
+        match kind {
+            b"IHDR" => match ImageHeader::parse(data, true) {
+                Ok((_, header)) => {
+                    if data.len() as u64 != ImageHeader::SIZE {
+                        warnings.push(format!("IHDR is {} bytes instead of 13", data.len()));
+                    }
+                    warnings.extend(header.problem());
+                    Details::Header(header)
+                }
+                Err(_) => {
+                    warnings.push("IHDR is too short".to_string());
+                    Details::Other
+                }
+            },
+            b"PLTE" => {
+                let palette = colors(data);
+                if palette.is_empty() || palette.len() > 256 || 3 * palette.len() != data.len() {
+                    warnings.push(format!(
+                        "PLTE is {} bytes, it should be 3 per color for 1 to 256 colors",
+                        data.len()
+                    ));
+                }
+                Details::Palette(palette)
+            }
+            b"tEXt" => match split_at_nul(data) {
+                Some((keyword, text)) => Details::Text {
+                    keyword: latin1(keyword),
+                    language: None,
+                    text: latin1(text),
+                },
+                None => {
+                    warnings.push("The keyword has no NUL after it".to_string());
+                    Details::Other
+                }
+            },
+            b"zTXt" => {
+                let Some((keyword, rest)) = split_at_nul(data) else {
+                    warnings.push("The keyword has no NUL after it".to_string());
+                    return Details::Other;
+                };
+                // The compression method comes before the text
+                let text = rest
+                    .get(1..)
+                    .ok_or_else(|| "The compression method is missing".to_string())
+                    .and_then(inflate)
+                    .unwrap_or_else(|warning| {
+                        warnings.push(warning);
+                        Vec::new()
+                    });
+                Details::Text {
+                    keyword: latin1(keyword),
+                    language: None,
+                    text: latin1(&text),
+                }
+            }
+            b"iTXt" => {
+                // Keyword, compression flag and method, language, translated keyword, text
+                let parsed = split_at_nul(data).and_then(|(keyword, rest)| {
+                    let (&compressed, rest) = rest.split_first()?;
+                    let rest = rest.get(1..)?;
+                    let (language, rest) = split_at_nul(rest)?;
+                    let (translated, text) = split_at_nul(rest)?;
+                    Some((keyword, compressed, language, translated, text))
+                });
+                let Some((keyword, compressed, language, translated, text)) = parsed else {
+                    warnings.push("The iTXt header is cut off".to_string());
+                    return Details::Other;
+                };
+                let text = if compressed != 0 {
+                    inflate(text).unwrap_or_else(|warning| {
+                        warnings.push(warning);
+                        Vec::new()
+                    })
+                } else {
+                    text.to_vec()
+                };
+                let translated = String::from_utf8_lossy(translated).into_owned();
+                Details::Text {
+                    keyword: latin1(keyword),
+                    language: Some((latin1(language), translated)),
+                    text: String::from_utf8_lossy(&text).into_owned(),
+                }
+            }
+            b"eXIf" => Details::Exif(Exif::parse(data, offset)),
+            _ => Details::Other,
+        }
+    }
+}
+
+pub struct Chunk {
+    offset: u64,
+    length: u32,
+    kind: [u8; 4],
+    /// CRC stored after the data, `None` if the file ends first
+    crc32: Option<u32>,
+    /// CRC of the type and data as they are
+    computed_crc32: u32,
+    details: Details,
+    warnings: Vec<String>,
+}
+
+impl Chunk {
+    /// Bytes from the length to the CRC, or to the end of the file for a cut off chunk
+    fn size(&self, file_size: u64) -> u64 {
+        (12 + self.length as u64).min(file_size - self.offset)
+    }
+}
+
+impl FileFormatUi for Chunk {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        context.collapsing(ui, name, |ui| {
+            for warning in &self.warnings {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+            let length = self.length as u64;
+            context
+                .at(self.offset, 4)
+                .field(ui, "length", &context.number(length));
+            context
+                .at(self.offset + 4, 4)
+                .field(ui, "type", &chunk_type(&self.kind));
+            let data_offset = self.offset + 8;
+            context.range_link(ui, "data", data_offset, length);
+            let data = context.at(data_offset, length);
+            match &mut self.details {
+                Details::Header(header) => header.ui(ui, "image header", &data),
+                Details::Palette(palette) => {
+                    let title = format!("palette ({} colors)", palette.len());
+                    palette_ui(ui, &title, context, data_offset, palette);
+                }
+                Details::Text {
+                    keyword,
+                    language,
+                    text,
+                } => {
+                    data.field(ui, "keyword", &format!("{:?}", keyword));
+                    if let Some((language, translated)) = language {
+                        text_field(ui, "language", &format!("{:?}", language));
+                        text_field(ui, "translated keyword", &format!("{:?}", translated));
+                    }
+                    let shown = text.chars().take(SHOWN_TEXT).collect::<String>();
+                    if shown.len() < text.len() {
+                        text_field(ui, "text", &format!("{:?} …", shown));
+                    } else {
+                        text_field(ui, "text", &format!("{:?}", shown));
+                    }
+                }
+                Details::Exif(exif) => exif.ui(ui, "Exif", context),
+                Details::Other => {}
+            }
+            if let Some(crc32) = self.crc32 {
+                let text = if crc32 == self.computed_crc32 {
+                    format!("{:#010x}", crc32)
+                } else {
+                    format!("{:#010x} (⚠ computed {:#010x})", crc32, self.computed_crc32)
+                };
+                context.at(data_offset + length, 4).field(ui, "CRC", &text);
+            }
+        });
+    }
+}
+
+pub struct PngFormat {
+    chunks: Vec<Chunk>,
+    /// Where the IEND chunk ends, or the last chunk that could be read
+    end: u64,
+    size: u64,
+    warnings: Vec<String>,
+}
+
+impl PngFormat {
+    pub fn new(input: &[u8]) -> Result<Self, Error> {
+        if !input.starts_with(&SIGNATURE) {
+            return Err(Error::parse("PNG", "the signature doesn't match"));
+        }
+        let size = input.len() as u64;
+        let mut chunks = Vec::new();
+        let mut warnings = Vec::new();
+        let mut position = SIGNATURE.len() as u64;
+        let mut has_end = false;
+        while position < size {
+            let header = position as usize..position as usize + 8;
+            let Some(header) = input.get(header) else {
+                warnings.push(format!("The file ends inside a chunk at {:#x}", position));
+                break;
+            };
+            let length = u32::from_be_bytes(header[..4].try_into().unwrap());
+            let kind: [u8; 4] = header[4..].try_into().unwrap();
+            let mut chunk_warnings = Vec::new();
+            let data_start = position as usize + 8;
+            let data_end = data_start.saturating_add(length as usize);
+            let data = match input.get(data_start..data_end) {
+                Some(data) => data,
+                None => {
+                    chunk_warnings.push("The chunk goes past the end of the file".to_string());
+                    &input[data_start..]
+                }
+            };
+            let crc32 = input
+                .get(data_end..data_end.saturating_add(4))
+                .map(|crc| u32::from_be_bytes(crc.try_into().unwrap()));
+            let mut hasher = crc32fast::Hasher::new();
+            hasher.update(&kind);
+            hasher.update(data);
+            let computed_crc32 = hasher.finalize();
+            if crc32.is_some_and(|crc32| crc32 != computed_crc32) {
+                chunk_warnings.push(format!(
+                    "The CRC doesn't match, the data hashes to {:#010x}",
+                    computed_crc32
+                ));
+            }
+            let details = Details::parse(&kind, data, data_start as u64, &mut chunk_warnings);
+            chunks.push(Chunk {
+                offset: position,
+                length,
+                kind,
+                crc32,
+                computed_crc32,
+                details,
+                warnings: chunk_warnings,
+            });
+            position = (data_end as u64).saturating_add(4).min(size);
+            if crc32.is_none() {
+                break;
+            }
+            if &kind == b"IEND" {
+                has_end = true;
+                break;
+            }
+        }
+        if !has_end {
+            warnings.push("There is no IEND chunk".to_string());
+        }
+        check_order(&chunks, &mut warnings);
+        Ok(Self {
+            chunks,
+            end: position,
+            size,
+            warnings,
+        })
+    }
+
+    fn header(&self) -> Option<&ImageHeader> {
+        self.chunks.iter().find_map(|chunk| match &chunk.details {
+            Details::Header(header) => Some(header),
+            _ => None,
+        })
+    }
+
+    /// Image size, chunk count and where the image ends, for expressions
+    pub fn symbols(&self) -> Vec<(String, u64)> {
+        let mut symbols = vec![
+            ("chunks".to_string(), self.chunks.len() as u64),
+            ("image_end".to_string(), self.end),
+        ];
+        if let Some(header) = self.header() {
+            symbols.push(("width".to_string(), header.width.into()));
+            symbols.push(("height".to_string(), header.height.into()));
+        }
+        symbols
+    }
+}
+
+/// Checks the chunks are where the spec wants them
+fn check_order(chunks: &[Chunk], warnings: &mut Vec<String>) {
+    if chunks.first().map(|chunk| &chunk.kind) != Some(b"IHDR") {
+        warnings.push("The first chunk isn't IHDR".to_string());
+    }
+    let data = chunks
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| &chunk.kind == b"IDAT")
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    match (data.first(), data.last()) {
+        (None, _) => warnings.push("There is no IDAT chunk".to_string()),
+        (Some(first), Some(last)) if last - first + 1 != data.len() => {
+            warnings.push("The IDAT chunks aren't one after the other".to_string());
+        }
+        _ => {}
+    }
+    let palette = chunks.iter().position(|chunk| &chunk.kind == b"PLTE");
+    let is_indexed = chunks
+        .iter()
+        .any(|chunk| matches!(&chunk.details, Details::Header(header) if header.color_type == 3));
+    match (palette, data.first()) {
+        (None, _) if is_indexed => {
+            warnings.push("The image uses a palette but has no PLTE chunk".to_string());
+        }
+        (Some(palette), Some(&first)) if palette > first => {
+            warnings.push("PLTE comes after the image data".to_string());
+        }
+        _ => {}
+    }
+}
+
+impl FileFormatUi for PngFormat {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        ui.collapsing(name, |ui| {
+            for warning in &self.warnings {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+            context.at(0, SIGNATURE.len() as u64).field(
+                ui,
+                "signature",
+                &SIGNATURE.escape_ascii().to_string(),
+            );
+            let title = format!("chunks ({})", self.chunks.len());
+            ui.collapsing(title, |ui| {
+                for (index, chunk) in self.chunks.iter_mut().take(SHOWN_CHUNKS).enumerate() {
+                    let title = format!("[{}] {}", index, chunk.kind.escape_ascii());
+                    let chunk_context = context.at(chunk.offset, chunk.size(self.size));
+                    ui.push_id(index, |ui| chunk.ui(ui, &title, &chunk_context));
+                }
+                if self.chunks.len() > SHOWN_CHUNKS {
+                    ui.weak(format!("… {} more", self.chunks.len() - SHOWN_CHUNKS));
+                }
+            });
+            trailing_data_ui(ui, context, "image", self.end, self.size);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! The images are built chunk by chunk, a 1×1 RGB image with a text chunk.
+
+    use super::*;
+
+    fn chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(kind);
+        hasher.update(data);
+        [
+            &(data.len() as u32).to_be_bytes()[..],
+            kind,
+            data,
+            &hasher.finalize().to_be_bytes(),
+        ]
+        .concat()
+    }
+
+    fn image() -> Vec<u8> {
+        let header = [0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0];
+        let pixels = stored_zlib(&[0, 0xff, 0, 0]);
+        [
+            &SIGNATURE[..],
+            &chunk(b"IHDR", &header),
+            &chunk(b"tEXt", b"Title\0tiny"),
+            &chunk(b"IDAT", &pixels),
+            &chunk(b"IEND", &[]),
+        ]
+        .concat()
+    }
+
+    /// A zlib stream that stores the data as it is
+    fn stored_zlib(data: &[u8]) -> Vec<u8> {
+        let length = data.len() as u16;
+        let mut stream = vec![0x78, 0x01, 0x01];
+        stream.extend(length.to_le_bytes());
+        stream.extend((!length).to_le_bytes());
+        stream.extend(data);
+        let (mut a, mut b) = (1u32, 0u32);
+        for &byte in data {
+            a = (a + byte as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+        stream.extend((b << 16 | a).to_be_bytes());
+        stream
+    }
+
+    fn symbol(png: &PngFormat, name: &str) -> Option<u64> {
+        png.symbols()
+            .into_iter()
+            .find(|(symbol, _)| symbol == name)
+            .map(|(_, value)| value)
+    }
+
+    #[test]
+    fn chunks() {
+        let input = image();
+        assert_eq!(probe(&input), Confidence::High);
+        let png = PngFormat::new(&input).unwrap();
+        assert!(png.warnings.is_empty(), "{:?}", png.warnings);
+        let kinds = png
+            .chunks
+            .iter()
+            .map(|chunk| &chunk.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, [b"IHDR", b"tEXt", b"IDAT", b"IEND"]);
+        assert!(png.chunks.iter().all(|chunk| chunk.warnings.is_empty()));
+        assert!(png
+            .chunks
+            .iter()
+            .all(|chunk| chunk.crc32 == Some(chunk.computed_crc32)));
+        let Details::Text { keyword, text, .. } = &png.chunks[1].details else {
+            panic!("tEXt isn't read as text");
+        };
+        assert_eq!((keyword.as_str(), text.as_str()), ("Title", "tiny"));
+        assert_eq!(symbol(&png, "width"), Some(1));
+        assert_eq!(symbol(&png, "height"), Some(1));
+        assert_eq!(symbol(&png, "image_end"), Some(input.len() as u64));
+        assert_eq!(png.end, png.size);
+    }
+
+    #[test]
+    fn crc_mismatch() {
+        let mut input = image();
+        // The last byte of the tEXt CRC, after the signature, IHDR and the text
+        let crc_end = SIGNATURE.len() + 25 + 12 + 10;
+        input[crc_end - 1] ^= 0xff;
+        let png = PngFormat::new(&input).unwrap();
+        assert!(png.warnings.is_empty(), "{:?}", png.warnings);
+        let text = &png.chunks[1];
+        assert_ne!(text.crc32, Some(text.computed_crc32));
+        assert_eq!(
+            text.warnings,
+            [format!(
+                "The CRC doesn't match, the data hashes to {:#010x}",
+                text.computed_crc32
+            )]
+        );
+        // The other chunks are still read
+        assert_eq!(png.chunks.len(), 4);
+        assert!(png.chunks[2].warnings.is_empty());
+    }
+
+    #[test]
+    fn trailing_data_and_cut_off() {
+        let mut input = image();
+        let end = input.len() as u64;
+        input.extend(b"appended");
+        let png = PngFormat::new(&input).unwrap();
+        assert!(png.warnings.is_empty(), "{:?}", png.warnings);
+        assert_eq!((png.end, png.size), (end, input.len() as u64));
+
+        // Cut inside IDAT, the chunk is kept and the end is missing
+        let idat = SIGNATURE.len() + 25 + 22;
+        let png = PngFormat::new(&input[..idat + 10]).unwrap();
+        assert_eq!(png.chunks.len(), 3);
+        assert_eq!(png.chunks[2].crc32, None);
+        assert_eq!(
+            png.chunks[2].warnings,
+            ["The chunk goes past the end of the file"]
+        );
+        assert_eq!(png.warnings, ["There is no IEND chunk"]);
+        assert_eq!(png.end, png.size);
+
+        // Cut inside a chunk header
+        let png = PngFormat::new(&input[..idat + 4]).unwrap();
+        assert_eq!(
+            png.warnings,
+            [
+                format!("The file ends inside a chunk at {:#x}", idat),
+                "There is no IEND chunk".to_string(),
+                "There is no IDAT chunk".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn chunk_order() {
+        let header = [0, 0, 0, 1, 0, 0, 0, 1, 8, 3, 0, 0, 0];
+        let input = [
+            &SIGNATURE[..],
+            &chunk(b"IHDR", &header),
+            &chunk(b"IDAT", &[]),
+            &chunk(b"tEXt", b"a\0b"),
+            &chunk(b"IDAT", &[]),
+            &chunk(b"IEND", &[]),
+        ]
+        .concat();
+        let png = PngFormat::new(&input).unwrap();
+        assert_eq!(
+            png.warnings,
+            [
+                "The IDAT chunks aren't one after the other",
+                "The image uses a palette but has no PLTE chunk",
+            ]
+        );
+        assert_eq!(probe(&input[..12]), Confidence::Low);
+    }
+}
@@ -0,0 +1,907 @@
+use egui::Vec2b;
+use egui_extras::Column;
+use nom::{bytes::complete::take, multi::count, sequence::Tuple, IResult};
+
+use super::{flag_names, signature::CodeSignature, string_at, Readers};
+use crate::tools::format_explorer::{FileFormatUi, FormatContext};
+
+/// Set on commands the loader must understand to load the file
+const LC_REQ_DYLD: u32 = 0x8000_0000;
+
+const LC_SEGMENT: u32 = 0x1;
+const LC_SYMTAB: u32 = 0x2;
+const LC_DYSYMTAB: u32 = 0xb;
+const LC_LOAD_DYLIB: u32 = 0xc;
+const LC_ID_DYLIB: u32 = 0xd;
+const LC_LOAD_DYLINKER: u32 = 0xe;
+const LC_ID_DYLINKER: u32 = 0xf;
+const LC_SUB_FRAMEWORK: u32 = 0x12;
+const LC_SUB_UMBRELLA: u32 = 0x13;
+const LC_SUB_CLIENT: u32 = 0x14;
+const LC_SUB_LIBRARY: u32 = 0x15;
+const LC_LOAD_WEAK_DYLIB: u32 = 0x18 | LC_REQ_DYLD;
+const LC_SEGMENT_64: u32 = 0x19;
+const LC_UUID: u32 = 0x1b;
+const LC_RPATH: u32 = 0x1c | LC_REQ_DYLD;
+const LC_CODE_SIGNATURE: u32 = 0x1d;
+const LC_SEGMENT_SPLIT_INFO: u32 = 0x1e;
+const LC_REEXPORT_DYLIB: u32 = 0x1f | LC_REQ_DYLD;
+const LC_LAZY_LOAD_DYLIB: u32 = 0x20;
+const LC_ENCRYPTION_INFO: u32 = 0x21;
+const LC_DYLD_INFO: u32 = 0x22;
+const LC_DYLD_INFO_ONLY: u32 = 0x22 | LC_REQ_DYLD;
+const LC_LOAD_UPWARD_DYLIB: u32 = 0x23 | LC_REQ_DYLD;
+const LC_VERSION_MIN_MACOSX: u32 = 0x24;
+const LC_VERSION_MIN_IPHONEOS: u32 = 0x25;
+const LC_FUNCTION_STARTS: u32 = 0x26;
+const LC_DYLD_ENVIRONMENT: u32 = 0x27;
+const LC_MAIN: u32 = 0x28 | LC_REQ_DYLD;
+const LC_DATA_IN_CODE: u32 = 0x29;
+const LC_SOURCE_VERSION: u32 = 0x2a;
+const LC_DYLIB_CODE_SIGN_DRS: u32 = 0x2b;
+const LC_ENCRYPTION_INFO_64: u32 = 0x2c;
+const LC_LINKER_OPTIMIZATION_HINT: u32 = 0x2e;
+const LC_VERSION_MIN_TVOS: u32 = 0x2f;
+const LC_VERSION_MIN_WATCHOS: u32 = 0x30;
+const LC_BUILD_VERSION: u32 = 0x32;
+const LC_DYLD_EXPORTS_TRIE: u32 = 0x33 | LC_REQ_DYLD;
+const LC_DYLD_CHAINED_FIXUPS: u32 = 0x34 | LC_REQ_DYLD;
+const LC_ATOM_INFO: u32 = 0x36;
+
+/// Section types, in the low byte of the flags, without contents in the file
+const S_ZEROFILL: u32 = 0x1;
+const S_GB_ZEROFILL: u32 = 0xc;
+const S_THREAD_LOCAL_ZEROFILL: u32 = 0x12;
+const SECTION_TYPE: u32 = 0xff;
+
+/// Field names of `dysymtab_command`, which is just a list of indices, counts and offsets
+const DYSYMTAB_FIELDS: [&str; 18] = [
+    "ilocalsym",
+    "nlocalsym",
+    "iextdefsym",
+    "nextdefsym",
+    "iundefsym",
+    "nundefsym",
+    "tocoff",
+    "ntoc",
+    "modtaboff",
+    "nmodtab",
+    "extrefsymoff",
+    "nextrefsyms",
+    "indirectsymoff",
+    "nindirectsyms",
+    "extreloff",
+    "nextrel",
+    "locreloff",
+    "nlocrel",
+];
+
+/// Tables of opcodes `dyld` runs to rebase and bind, before chained fixups replaced them
+const DYLD_INFO_TABLES: [&str; 5] = ["rebase", "bind", "weak_bind", "lazy_bind", "export"];
+
+pub fn command_name(cmd: u32) -> String {
+    let name = match cmd {
+        LC_SEGMENT => "LC_SEGMENT",
+        LC_SYMTAB => "LC_SYMTAB",
+        0x3 => "LC_SYMSEG",
+        0x4 => "LC_THREAD",
+        0x5 => "LC_UNIXTHREAD",
+        0x6 => "LC_LOADFVMLIB",
+        0x7 => "LC_IDFVMLIB",
+        0x8 => "LC_IDENT",
+        0x9 => "LC_FVMFILE",
+        0xa => "LC_PREPAGE",
+        LC_DYSYMTAB => "LC_DYSYMTAB",
+        LC_LOAD_DYLIB => "LC_LOAD_DYLIB",
+        LC_ID_DYLIB => "LC_ID_DYLIB",
+        LC_LOAD_DYLINKER => "LC_LOAD_DYLINKER",
+        LC_ID_DYLINKER => "LC_ID_DYLINKER",
+        0x10 => "LC_PREBOUND_DYLIB",
+        0x11 => "LC_ROUTINES",
+        LC_SUB_FRAMEWORK => "LC_SUB_FRAMEWORK",
+        LC_SUB_UMBRELLA => "LC_SUB_UMBRELLA",
+        LC_SUB_CLIENT => "LC_SUB_CLIENT",
+        LC_SUB_LIBRARY => "LC_SUB_LIBRARY",
+        0x16 => "LC_TWOLEVEL_HINTS",
+        0x17 => "LC_PREBIND_CKSUM",
+        LC_LOAD_WEAK_DYLIB => "LC_LOAD_WEAK_DYLIB",
+        LC_SEGMENT_64 => "LC_SEGMENT_64",
+        0x1a => "LC_ROUTINES_64",
+        LC_UUID => "LC_UUID",
+        LC_RPATH => "LC_RPATH",
+        LC_CODE_SIGNATURE => "LC_CODE_SIGNATURE",
+        LC_SEGMENT_SPLIT_INFO => "LC_SEGMENT_SPLIT_INFO",
+        LC_REEXPORT_DYLIB => "LC_REEXPORT_DYLIB",
+        LC_LAZY_LOAD_DYLIB => "LC_LAZY_LOAD_DYLIB",
+        LC_ENCRYPTION_INFO => "LC_ENCRYPTION_INFO",
+        LC_DYLD_INFO => "LC_DYLD_INFO",
+        LC_DYLD_INFO_ONLY => "LC_DYLD_INFO_ONLY",
+        LC_LOAD_UPWARD_DYLIB => "LC_LOAD_UPWARD_DYLIB",
+        LC_VERSION_MIN_MACOSX => "LC_VERSION_MIN_MACOSX",
+        LC_VERSION_MIN_IPHONEOS => "LC_VERSION_MIN_IPHONEOS",
+        LC_FUNCTION_STARTS => "LC_FUNCTION_STARTS",
+        LC_DYLD_ENVIRONMENT => "LC_DYLD_ENVIRONMENT",
+        LC_MAIN => "LC_MAIN",
+        LC_DATA_IN_CODE => "LC_DATA_IN_CODE",
+        LC_SOURCE_VERSION => "LC_SOURCE_VERSION",
+        LC_DYLIB_CODE_SIGN_DRS => "LC_DYLIB_CODE_SIGN_DRS",
+        LC_ENCRYPTION_INFO_64 => "LC_ENCRYPTION_INFO_64",
+        0x2d => "LC_LINKER_OPTION",
+        LC_LINKER_OPTIMIZATION_HINT => "LC_LINKER_OPTIMIZATION_HINT",
+        LC_VERSION_MIN_TVOS => "LC_VERSION_MIN_TVOS",
+        LC_VERSION_MIN_WATCHOS => "LC_VERSION_MIN_WATCHOS",
+        0x31 => "LC_NOTE",
+        LC_BUILD_VERSION => "LC_BUILD_VERSION",
+        LC_DYLD_EXPORTS_TRIE => "LC_DYLD_EXPORTS_TRIE",
+        LC_DYLD_CHAINED_FIXUPS => "LC_DYLD_CHAINED_FIXUPS",
+        0x35 | LC_REQ_DYLD => "LC_FILESET_ENTRY",
+        LC_ATOM_INFO => "LC_ATOM_INFO",
+        _ => return format!("{:#x}", cmd),
+    };
+    name.to_string()
+}
+
+/// `xxxx.yy.zz` versions of dylibs and SDKs, packed into 16, 8 and 8 bits
+pub fn version(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        version >> 16,
+        (version >> 8) & 0xff,
+        version & 0xff
+    )
+}
+
+/// `a.b.c.d.e` source versions, packed into 24, 10, 10, 10 and 10 bits
+fn source_version(version: u64) -> String {
+    format!(
+        "{}.{}.{}.{}.{}",
+        version >> 40,
+        (version >> 30) & 0x3ff,
+        (version >> 20) & 0x3ff,
+        (version >> 10) & 0x3ff,
+        version & 0x3ff
+    )
+}
+
+fn platform_name(platform: u32) -> String {
+    let name = match platform {
+        1 => "MACOS",
+        2 => "IOS",
+        3 => "TVOS",
+        4 => "WATCHOS",
+        5 => "BRIDGEOS",
+        6 => "MACCATALYST",
+        7 => "IOSSIMULATOR",
+        8 => "TVOSSIMULATOR",
+        9 => "WATCHOSSIMULATOR",
+        10 => "DRIVERKIT",
+        11 => "VISIONOS",
+        12 => "VISIONOSSIMULATOR",
+        _ => return platform.to_string(),
+    };
+    name.to_string()
+}
+
+fn tool_name(tool: u32) -> String {
+    let name = match tool {
+        1 => "CLANG",
+        2 => "SWIFT",
+        3 => "LD",
+        4 => "LLD",
+        _ => return tool.to_string(),
+    };
+    name.to_string()
+}
+
+/// `rwx` style virtual memory protection
+fn protection(protection: u32) -> String {
+    [(1, 'r'), (2, 'w'), (4, 'x')]
+        .iter()
+        .map(|&(bit, c)| if protection & bit != 0 { c } else { '-' })
+        .collect()
+}
+
+const SEGMENT_FLAGS: [(u64, &str); 5] = [
+    (0x1, "SG_HIGHVM"),
+    (0x2, "SG_FVMLIB"),
+    (0x4, "SG_NORELOC"),
+    (0x8, "SG_PROTECTED_VERSION_1"),
+    (0x10, "SG_READ_ONLY"),
+];
+
+fn section_type_name(type_: u32) -> String {
+    let name = match type_ {
+        0x0 => "S_REGULAR",
+        S_ZEROFILL => "S_ZEROFILL",
+        0x2 => "S_CSTRING_LITERALS",
+        0x3 => "S_4BYTE_LITERALS",
+        0x4 => "S_8BYTE_LITERALS",
+        0x5 => "S_LITERAL_POINTERS",
+        0x6 => "S_NON_LAZY_SYMBOL_POINTERS",
+        0x7 => "S_LAZY_SYMBOL_POINTERS",
+        0x8 => "S_SYMBOL_STUBS",
+        0x9 => "S_MOD_INIT_FUNC_POINTERS",
+        0xa => "S_MOD_TERM_FUNC_POINTERS",
+        0xb => "S_COALESCED",
+        S_GB_ZEROFILL => "S_GB_ZEROFILL",
+        0xd => "S_INTERPOSING",
+        0xe => "S_16BYTE_LITERALS",
+        0xf => "S_DTRACE_DOF",
+        0x10 => "S_LAZY_DYLIB_SYMBOL_POINTERS",
+        0x11 => "S_THREAD_LOCAL_REGULAR",
+        S_THREAD_LOCAL_ZEROFILL => "S_THREAD_LOCAL_ZEROFILL",
+        0x13 => "S_THREAD_LOCAL_VARIABLES",
+        0x14 => "S_THREAD_LOCAL_VARIABLE_POINTERS",
+        0x15 => "S_THREAD_LOCAL_INIT_FUNCTION_POINTERS",
+        0x16 => "S_INIT_FUNC_OFFSETS",
+        _ => return format!("{:#x}", type_),
+    };
+    name.to_string()
+}
+
+const SECTION_ATTRIBUTES: [(u64, &str); 10] = [
+    (0x8000_0000, "PURE_INSTRUCTIONS"),
+    (0x4000_0000, "NO_TOC"),
+    (0x2000_0000, "STRIP_STATIC_SYMS"),
+    (0x1000_0000, "NO_DEAD_STRIP"),
+    (0x0800_0000, "LIVE_SUPPORT"),
+    (0x0400_0000, "SELF_MODIFYING_CODE"),
+    (0x0200_0000, "DEBUG"),
+    (0x400, "SOME_INSTRUCTIONS"),
+    (0x200, "EXT_RELOC"),
+    (0x100, "LOC_RELOC"),
+];
+
+pub struct Section {
+    pub name: String,
+    pub segment_name: String,
+    pub address: u64,
+    pub size: u64,
+    pub offset: u32,
+    /// Power of two
+    pub align: u32,
+    pub relocations_offset: u32,
+    pub relocation_count: u32,
+    pub flags: u32,
+    pub reserved1: u32,
+    pub reserved2: u32,
+    /// Only in 64-bit files
+    pub reserved3: Option<u32>,
+}
+
+impl Section {
+    fn parse(input: &[u8], readers: Readers) -> IResult<&[u8], Self> {
+        let word = readers.word;
+        let address = |input| readers.address(input);
+        let (mut tail, (name, segment_name, address, size)) =
+            (take(16usize), take(16usize), address, address).parse(input)?;
+        let (
+            rest,
+            (offset, align, relocations_offset, relocation_count, flags, reserved1, reserved2),
+        ) = (word, word, word, word, word, word, word).parse(tail)?;
+        tail = rest;
+        let reserved3 = if readers.is_64 {
+            let (rest, reserved3) = word(tail)?;
+            tail = rest;
+            Some(reserved3)
+        } else {
+            None
+        };
+        Ok((
+            tail,
+            Self {
+                name: string_at(name, 0),
+                segment_name: string_at(segment_name, 0),
+                address,
+                size,
+                offset,
+                align,
+                relocations_offset,
+                relocation_count,
+                flags,
+                reserved1,
+                reserved2,
+                reserved3,
+            },
+        ))
+    }
+
+    /// Zero filled sections only take up memory, their offset is meaningless
+    pub fn is_zerofill(&self) -> bool {
+        matches!(
+            self.flags & SECTION_TYPE,
+            S_ZEROFILL | S_GB_ZEROFILL | S_THREAD_LOCAL_ZEROFILL
+        )
+    }
+
+    /// Where the byte at `address` is in the file, if it's in the section's contents
+    pub fn file_offset(&self, address: u64) -> Option<u64> {
+        let relative = address.checked_sub(self.address)?;
+        (!self.is_zerofill() && relative < self.size).then(|| self.offset as u64 + relative)
+    }
+}
+
+pub struct Segment {
+    pub name: String,
+    pub vm_address: u64,
+    pub vm_size: u64,
+    pub file_offset: u64,
+    pub file_size: u64,
+    pub max_protection: u32,
+    pub initial_protection: u32,
+    pub flags: u32,
+    pub sections: Vec<Section>,
+}
+
+impl Segment {
+    fn parse(input: &[u8], readers: Readers) -> IResult<&[u8], Self> {
+        let word = readers.word;
+        let address = |input| readers.address(input);
+        let (tail, (name, vm_address, vm_size, file_offset, file_size)) =
+            (take(16usize), address, address, address, address).parse(input)?;
+        let (tail, (max_protection, initial_protection, section_count, flags)) =
+            (word, word, word, word).parse(tail)?;
+        let (tail, sections) = count(
+            |input| Section::parse(input, readers),
+            section_count as usize,
+        )(tail)?;
+        Ok((
+            tail,
+            Self {
+                name: string_at(name, 0),
+                vm_address,
+                vm_size,
+                file_offset,
+                file_size,
+                max_protection,
+                initial_protection,
+                flags,
+                sections,
+            },
+        ))
+    }
+}
+
+pub enum CommandKind {
+    Segment(Segment),
+    SymbolTable {
+        symbol_offset: u32,
+        symbol_count: u32,
+        string_offset: u32,
+        string_size: u32,
+    },
+    DynamicSymbolTable([u32; 18]),
+    Dylib {
+        name: String,
+        timestamp: u32,
+        current_version: u32,
+        compatibility_version: u32,
+    },
+    /// Commands that only hold a path or name, like `LC_LOAD_DYLINKER` and `LC_RPATH`
+    Path(String),
+    Main {
+        entry_offset: u64,
+        stack_size: u64,
+    },
+    Uuid([u8; 16]),
+    /// Points at data in the `__LINKEDIT` segment
+    LinkeditData {
+        data_offset: u32,
+        data_size: u32,
+        signature: Option<CodeSignature>,
+    },
+    VersionMin {
+        version: u32,
+        sdk: u32,
+    },
+    BuildVersion {
+        platform: u32,
+        minimum_os: u32,
+        sdk: u32,
+        /// Tools and their versions
+        tools: Vec<(u32, u32)>,
+    },
+    SourceVersion(u64),
+    /// Offsets and sizes of the rebase, bind, weak bind, lazy bind and export tables
+    DyldInfo([u32; 10]),
+    EncryptionInfo {
+        crypt_offset: u32,
+        crypt_size: u32,
+        crypt_id: u32,
+    },
+    /// Commands we don't decode, or that are too short for their type
+    Other,
+}
+
+pub struct LoadCommand {
+    pub cmd: u32,
+    pub size: u32,
+    /// Where the command starts
+    pub offset: u64,
+    pub kind: CommandKind,
+}
+
+impl LoadCommand {
+    /// Decodes the command in `command`, which includes `cmd` and `cmdsize`
+    fn parse_kind<'a>(
+        command: &'a [u8],
+        cmd: u32,
+        readers: Readers,
+        input: &[u8],
+    ) -> IResult<&'a [u8], CommandKind> {
+        let word = readers.word;
+        let xword = readers.xword;
+        let body = &command[8..];
+        let kind = match cmd {
+            LC_SEGMENT | LC_SEGMENT_64 => {
+                // The command type decides the layout, not the header
+                let readers = Readers {
+                    is_64: cmd == LC_SEGMENT_64,
+                    ..readers
+                };
+                CommandKind::Segment(Segment::parse(body, readers)?.1)
+            }
+            LC_SYMTAB => {
+                let (_, (symbol_offset, symbol_count, string_offset, string_size)) =
+                    (word, word, word, word).parse(body)?;
+                CommandKind::SymbolTable {
+                    symbol_offset,
+                    symbol_count,
+                    string_offset,
+                    string_size,
+                }
+            }
+            LC_DYSYMTAB => {
+                let (_, fields) = count(word, DYSYMTAB_FIELDS.len())(body)?;
+                CommandKind::DynamicSymbolTable(fields.try_into().unwrap())
+            }
+            LC_LOAD_DYLIB | LC_ID_DYLIB | LC_LOAD_WEAK_DYLIB | LC_REEXPORT_DYLIB
+            | LC_LAZY_LOAD_DYLIB | LC_LOAD_UPWARD_DYLIB => {
+                let (_, (name_offset, timestamp, current_version, compatibility_version)) =
+                    (word, word, word, word).parse(body)?;
+                CommandKind::Dylib {
+                    name: string_at(command, name_offset.into()),
+                    timestamp,
+                    current_version,
+                    compatibility_version,
+                }
+            }
+            LC_LOAD_DYLINKER | LC_ID_DYLINKER | LC_DYLD_ENVIRONMENT | LC_RPATH
+            | LC_SUB_FRAMEWORK | LC_SUB_UMBRELLA | LC_SUB_CLIENT | LC_SUB_LIBRARY => {
+                let (_, name_offset) = word(body)?;
+                CommandKind::Path(string_at(command, name_offset.into()))
+            }
+            LC_MAIN => {
+                let (_, (entry_offset, stack_size)) = (xword, xword).parse(body)?;
+                CommandKind::Main {
+                    entry_offset,
+                    stack_size,
+                }
+            }
+            LC_UUID => {
+                let (_, uuid) = take(16usize)(body)?;
+                CommandKind::Uuid(uuid.try_into().unwrap())
+            }
+            LC_CODE_SIGNATURE
+            | LC_SEGMENT_SPLIT_INFO
+            | LC_FUNCTION_STARTS
+            | LC_DATA_IN_CODE
+            | LC_DYLIB_CODE_SIGN_DRS
+            | LC_LINKER_OPTIMIZATION_HINT
+            | LC_DYLD_EXPORTS_TRIE
+            | LC_DYLD_CHAINED_FIXUPS
+            | LC_ATOM_INFO => {
+                let (_, (data_offset, data_size)) = (word, word).parse(body)?;
+                let signature = if cmd == LC_CODE_SIGNATURE {
+                    CodeSignature::parse(input, data_offset.into(), data_size.into())
+                } else {
+                    None
+                };
+                CommandKind::LinkeditData {
+                    data_offset,
+                    data_size,
+                    signature,
+                }
+            }
+            LC_VERSION_MIN_MACOSX
+            | LC_VERSION_MIN_IPHONEOS
+            | LC_VERSION_MIN_TVOS
+            | LC_VERSION_MIN_WATCHOS => {
+                let (_, (version, sdk)) = (word, word).parse(body)?;
+                CommandKind::VersionMin { version, sdk }
+            }
+            LC_BUILD_VERSION => {
+                let (tail, (platform, minimum_os, sdk, tool_count)) =
+                    (word, word, word, word).parse(body)?;
+                // Limited by the command size rather than trusting the count
+                let tools = tail
+                    .chunks_exact(8)
+                    .take(tool_count as usize)
+                    .filter_map(|tool| (word, word).parse(tool).ok().map(|(_, tool)| tool))
+                    .collect();
+                CommandKind::BuildVersion {
+                    platform,
+                    minimum_os,
+                    sdk,
+                    tools,
+                }
+            }
+            LC_SOURCE_VERSION => CommandKind::SourceVersion(xword(body)?.1),
+            LC_DYLD_INFO | LC_DYLD_INFO_ONLY => {
+                let (_, fields) = count(word, 10)(body)?;
+                CommandKind::DyldInfo(fields.try_into().unwrap())
+            }
+            LC_ENCRYPTION_INFO | LC_ENCRYPTION_INFO_64 => {
+                let (_, (crypt_offset, crypt_size, crypt_id)) = (word, word, word).parse(body)?;
+                CommandKind::EncryptionInfo {
+                    crypt_offset,
+                    crypt_size,
+                    crypt_id,
+                }
+            }
+            _ => CommandKind::Other,
+        };
+        Ok((&command[command.len()..], kind))
+    }
+
+    /// Short description for the command's title, like the segment name or dylib path
+    fn summary(&self) -> String {
+        match &self.kind {
+            CommandKind::Segment(segment) => segment.name.clone(),
+            CommandKind::Dylib { name, .. } | CommandKind::Path(name) => name.clone(),
+            CommandKind::Uuid(uuid) => uuid_string(uuid),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Upper case UUID with dashes, like `dwarfdump --uuid` shows it
+pub fn uuid_string(uuid: &[u8; 16]) -> String {
+    let hex: String = uuid.iter().map(|b| format!("{:02X}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Reads the load commands following the header, broken commands end the list with a warning
+pub(super) fn parse_commands(
+    input: &[u8],
+    readers: Readers,
+    start: u64,
+    command_count: u32,
+    warnings: &mut Vec<String>,
+) -> Vec<LoadCommand> {
+    let mut commands = Vec::new();
+    let mut offset = start;
+    for index in 0..command_count {
+        let Ok((_, (cmd, size))) = super::at(input, offset)
+            .and_then(|command| (readers.word, readers.word).parse(command))
+        else {
+            warnings.push(format!(
+                "Load command {} at {:#x} is past the end of the file",
+                index, offset
+            ));
+            break;
+        };
+        if size < 8 {
+            warnings.push(format!(
+                "Load command {} at {:#x} has a size of {}, too small for its header",
+                index, offset, size
+            ));
+            break;
+        }
+        let Some(command) = input.get(offset as usize..offset as usize + size as usize) else {
+            warnings.push(format!(
+                "Load command {} at {:#x} goes past the end of the file",
+                index, offset
+            ));
+            break;
+        };
+        let kind = match LoadCommand::parse_kind(command, cmd, readers, input) {
+            Ok((_, kind)) => kind,
+            Err(_) => {
+                warnings.push(format!(
+                    "{} at {:#x} is too short for its contents",
+                    command_name(cmd),
+                    offset
+                ));
+                CommandKind::Other
+            }
+        };
+        commands.push(LoadCommand {
+            cmd,
+            size,
+            offset,
+            kind,
+        });
+        offset += size as u64;
+    }
+    commands
+}
+
+/// Table of a segment's sections
+/// `context` is tied to the sections following the segment command
+fn section_table(ui: &mut egui::Ui, sections: &[Section], is_64: bool, context: &FormatContext) {
+    let section_size = if is_64 { 80 } else { 68 };
+    egui_extras::TableBuilder::new(ui)
+        .striped(true)
+        .resizable(false)
+        .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+        .columns(Column::auto(), 6)
+        .column(Column::remainder().clip(true))
+        .vscroll(false)
+        .auto_shrink(Vec2b::new(false, true))
+        .header(20.0, |mut header| {
+            for title in [
+                "Name",
+                "Address",
+                "Size",
+                "Offset",
+                "Align",
+                "Type",
+                "Attributes",
+            ] {
+                header.col(|ui| {
+                    ui.label(title);
+                });
+            }
+        })
+        .body(|body| {
+            body.rows(20.0, sections.len(), |mut row| {
+                let index = row.index();
+                let section = &sections[index];
+                row.col(|ui| {
+                    let label = egui::Label::new(&section.name).sense(egui::Sense::click());
+                    let response = ui.add(label).on_hover_text(format!(
+                        "{},{}\nrelocations: {} at {:#x}\nreserved: {:#x} {:#x} {}",
+                        section.segment_name,
+                        section.name,
+                        section.relocation_count,
+                        section.relocations_offset,
+                        section.reserved1,
+                        section.reserved2,
+                        section
+                            .reserved3
+                            .map_or_else(String::new, |reserved| format!("{:#x}", reserved))
+                    ));
+                    context.entry(index, section_size).track(
+                        &response,
+                        &format!("section {},{}", section.segment_name, section.name),
+                    );
+                });
+                row.col(|ui| {
+                    ui.label(format!("{:#x}", section.address));
+                });
+                row.col(|ui| {
+                    ui.label(format!("{:#x}", section.size));
+                });
+                row.col(|ui| {
+                    if section.is_zerofill() {
+                        ui.weak("—");
+                    } else if ui
+                        .link(format!("{:#x}", section.offset))
+                        .on_hover_text("Select the section's bytes")
+                        .clicked()
+                    {
+                        context.select(section.offset.into(), section.size);
+                    }
+                });
+                row.col(|ui| {
+                    ui.label(format!("2^{}", section.align));
+                });
+                row.col(|ui| {
+                    ui.label(section_type_name(section.flags & SECTION_TYPE));
+                });
+                row.col(|ui| {
+                    let attributes =
+                        flag_names((section.flags & !SECTION_TYPE).into(), SECTION_ATTRIBUTES);
+                    ui.add(egui::Label::new(attributes).wrap(false));
+                });
+            });
+        });
+}
+
+impl FileFormatUi for LoadCommand {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let title = format!("{} {} {}", name, command_name(self.cmd), self.summary());
+        let command = context.at(self.offset, self.size.into());
+        command.collapsing(ui, title.trim_end(), |ui| {
+            context.range_link(ui, "bytes", self.offset, self.size.into());
+            let mut fields = command.fields();
+            fields
+                .next(4)
+                .enum_field(ui, "cmd", self.cmd.into(), &command_name(self.cmd));
+            self.size.ui(ui, "cmdsize", &fields.next(4));
+            // Strings of `lc_str` fields follow the fixed part of the command
+            let string_field = |start: u64| {
+                let start = start.min(self.size.into());
+                context.at(self.offset + start, u64::from(self.size) - start)
+            };
+            match &mut self.kind {
+                CommandKind::Segment(segment) => {
+                    let is_64 = self.cmd == LC_SEGMENT_64;
+                    let address_size = if is_64 { 8 } else { 4 };
+                    fields.next(16).field(ui, "segname", &segment.name);
+                    fields.next(address_size).field(
+                        ui,
+                        "vmaddr",
+                        &format!("{:#x}", segment.vm_address),
+                    );
+                    fields.next(address_size).field(
+                        ui,
+                        "vmsize",
+                        &format!("{:#x}", segment.vm_size),
+                    );
+                    fields.next(address_size).field(
+                        ui,
+                        "fileoff",
+                        &format!("{:#x}", segment.file_offset),
+                    );
+                    context.range_link(ui, "file bytes", segment.file_offset, segment.file_size);
+                    segment
+                        .file_size
+                        .ui(ui, "filesize", &fields.next(address_size));
+                    let max_protection = protection(segment.max_protection);
+                    fields.next(4).enum_field(
+                        ui,
+                        "maxprot",
+                        segment.max_protection.into(),
+                        &max_protection,
+                    );
+                    let initial_protection = protection(segment.initial_protection);
+                    fields.next(4).enum_field(
+                        ui,
+                        "initprot",
+                        segment.initial_protection.into(),
+                        &initial_protection,
+                    );
+                    let section_count = segment.sections.len().to_string();
+                    fields.next(4).field(ui, "nsects", &section_count);
+                    let flags = flag_names(segment.flags.into(), SEGMENT_FLAGS);
+                    fields
+                        .next(4)
+                        .enum_field(ui, "flags", segment.flags.into(), &flags);
+                    let title = format!("sections ({})", segment.sections.len());
+                    let sections = fields.next(u64::from(self.size).saturating_sub(if is_64 {
+                        72
+                    } else {
+                        56
+                    }));
+                    sections.collapsing(ui, &title, |ui| {
+                        section_table(ui, &segment.sections, is_64, &sections)
+                    });
+                }
+                CommandKind::SymbolTable {
+                    symbol_offset,
+                    symbol_count,
+                    string_offset,
+                    string_size,
+                } => {
+                    fields
+                        .next(4)
+                        .field(ui, "symoff", &format!("{:#x}", symbol_offset));
+                    symbol_count.ui(ui, "nsyms", &fields.next(4));
+                    fields
+                        .next(4)
+                        .field(ui, "stroff", &format!("{:#x}", string_offset));
+                    context.range_link(
+                        ui,
+                        "string bytes",
+                        (*string_offset).into(),
+                        (*string_size).into(),
+                    );
+                    string_size.ui(ui, "strsize", &fields.next(4));
+                }
+                CommandKind::DynamicSymbolTable(values) => {
+                    for (name, value) in DYSYMTAB_FIELDS.iter().zip(values.iter_mut()) {
+                        value.ui(ui, name, &fields.next(4));
+                    }
+                }
+                CommandKind::Dylib {
+                    name,
+                    timestamp,
+                    current_version,
+                    compatibility_version,
+                } => {
+                    string_field(24).field(ui, "name", name);
+                    fields.skip(4);
+                    timestamp.ui(ui, "timestamp", &fields.next(4));
+                    fields
+                        .next(4)
+                        .field(ui, "current_version", &version(*current_version));
+                    let compatibility_version = version(*compatibility_version);
+                    fields
+                        .next(4)
+                        .field(ui, "compatibility_version", &compatibility_version);
+                }
+                CommandKind::Path(path) => string_field(12).field(ui, "name", path),
+                CommandKind::Main {
+                    entry_offset,
+                    stack_size,
+                } => {
+                    fields
+                        .next(8)
+                        .field(ui, "entryoff", &format!("{:#x}", entry_offset));
+                    context.range_link(ui, "entry point", *entry_offset, 1);
+                    stack_size.ui(ui, "stacksize", &fields.next(8));
+                }
+                CommandKind::Uuid(uuid) => fields.next(16).field(ui, "uuid", &uuid_string(uuid)),
+                CommandKind::LinkeditData {
+                    data_offset,
+                    data_size,
+                    signature,
+                } => {
+                    fields
+                        .next(4)
+                        .field(ui, "dataoff", &format!("{:#x}", data_offset));
+                    context.range_link(ui, "data", (*data_offset).into(), (*data_size).into());
+                    data_size.ui(ui, "datasize", &fields.next(4));
+                    if let Some(signature) = signature {
+                        signature.ui(ui, "code_signature", context);
+                    }
+                }
+                CommandKind::VersionMin {
+                    version: minimum,
+                    sdk,
+                } => {
+                    fields.next(4).field(ui, "version", &version(*minimum));
+                    fields.next(4).field(ui, "sdk", &version(*sdk));
+                }
+                CommandKind::BuildVersion {
+                    platform,
+                    minimum_os,
+                    sdk,
+                    tools,
+                } => {
+                    fields.next(4).enum_field(
+                        ui,
+                        "platform",
+                        (*platform).into(),
+                        &platform_name(*platform),
+                    );
+                    fields.next(4).field(ui, "minos", &version(*minimum_os));
+                    fields.next(4).field(ui, "sdk", &version(*sdk));
+                    fields.next(4).field(ui, "ntools", &tools.len().to_string());
+                    for (tool, tool_version) in tools.iter() {
+                        fields
+                            .next(8)
+                            .field(ui, &tool_name(*tool), &version(*tool_version));
+                    }
+                }
+                CommandKind::SourceVersion(version) => {
+                    fields
+                        .next(8)
+                        .field(ui, "version", &source_version(*version));
+                }
+                CommandKind::DyldInfo(values) => {
+                    for (name, pair) in DYLD_INFO_TABLES.iter().zip(values.chunks_exact(2)) {
+                        context.range_link(ui, name, pair[0].into(), pair[1].into());
+                    }
+                }
+                CommandKind::EncryptionInfo {
+                    crypt_offset,
+                    crypt_size,
+                    crypt_id,
+                } => {
+                    fields
+                        .next(4)
+                        .field(ui, "cryptoff", &format!("{:#x}", crypt_offset));
+                    context.range_link(
+                        ui,
+                        "encrypted bytes",
+                        (*crypt_offset).into(),
+                        (*crypt_size).into(),
+                    );
+                    crypt_size.ui(ui, "cryptsize", &fields.next(4));
+                    crypt_id.ui(ui, "cryptid", &fields.next(4));
+                }
+                CommandKind::Other => {}
+            }
+        });
+    }
+}
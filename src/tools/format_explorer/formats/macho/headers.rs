@@ -0,0 +1,234 @@
+use nom::{sequence::Tuple, IResult};
+
+use super::{flag_names, Readers};
+use crate::tools::format_explorer::{FileFormatUi, FormatContext};
+
+pub(super) const MH_MAGIC: u32 = 0xfeed_face;
+pub(super) const MH_MAGIC_64: u32 = 0xfeed_facf;
+/// Magics of universal binaries, which are always big endian
+pub(super) const FAT_MAGIC: u32 = 0xcafe_babe;
+pub(super) const FAT_MAGIC_64: u32 = 0xcafe_babf;
+
+/// CPU type bits for 64-bit architectures and for 64-bit hardware with 32-bit pointers
+const CPU_ARCH_ABI64: u32 = 0x0100_0000;
+const CPU_ARCH_ABI64_32: u32 = 0x0200_0000;
+/// CPU subtype bits holding capabilities rather than the subtype
+const CPU_SUBTYPE_MASK: u32 = 0xff00_0000;
+
+const CPU_TYPE_X86: u32 = 7;
+const CPU_TYPE_ARM: u32 = 12;
+const CPU_TYPE_POWERPC: u32 = 18;
+
+pub struct MachHeader {
+    pub magic: u32,
+    /// Byte order of the file, the magic is byte swapped in big endian files
+    pub big_endian: bool,
+    pub cpu_type: u32,
+    pub cpu_subtype: u32,
+    pub file_type: u32,
+    pub command_count: u32,
+    pub commands_size: u32,
+    pub flags: u32,
+    /// Only in 64-bit headers
+    pub reserved: Option<u32>,
+}
+
+impl MachHeader {
+    /// Size of the header, the load commands follow it
+    pub fn size(&self) -> u64 {
+        if self.reserved.is_some() {
+            32
+        } else {
+            28
+        }
+    }
+
+    pub(super) fn parse(input: &[u8], readers: Readers, big_endian: bool) -> IResult<&[u8], Self> {
+        let word = readers.word;
+        let (
+            mut tail,
+            (magic, cpu_type, cpu_subtype, file_type, command_count, commands_size, flags),
+        ) = (word, word, word, word, word, word, word).parse(input)?;
+        let reserved = if readers.is_64 {
+            let (rest, reserved) = word(tail)?;
+            tail = rest;
+            Some(reserved)
+        } else {
+            None
+        };
+        Ok((
+            tail,
+            Self {
+                magic,
+                big_endian,
+                cpu_type,
+                cpu_subtype,
+                file_type,
+                command_count,
+                commands_size,
+                flags,
+                reserved,
+            },
+        ))
+    }
+}
+
+pub fn cpu_type_name(cpu_type: u32) -> String {
+    let name = match cpu_type {
+        1 => "VAX",
+        6 => "MC680x0",
+        CPU_TYPE_X86 => "X86",
+        10 => "MC98000",
+        11 => "HPPA",
+        CPU_TYPE_ARM => "ARM",
+        13 => "MC88000",
+        14 => "SPARC",
+        15 => "I860",
+        CPU_TYPE_POWERPC => "POWERPC",
+        0x0100_0007 => "X86_64",
+        0x0100_000c => "ARM64",
+        0x0100_0012 => "POWERPC64",
+        0x0200_000c => "ARM64_32",
+        _ => return format!("{:#x}", cpu_type),
+    };
+    name.to_string()
+}
+
+/// Architecture name as used by `lipo` and `-arch`, like `x86_64` or `arm64e`
+pub fn arch_name(cpu_type: u32, cpu_subtype: u32) -> String {
+    let subtype = cpu_subtype & !CPU_SUBTYPE_MASK;
+    let name = match (cpu_type, subtype) {
+        (CPU_TYPE_X86, _) => "i386",
+        (0x0100_0007, 8) => "x86_64h",
+        (0x0100_0007, _) => "x86_64",
+        (CPU_TYPE_ARM, 5) => "armv4t",
+        (CPU_TYPE_ARM, 6) => "armv6",
+        (CPU_TYPE_ARM, 7) => "armv5",
+        (CPU_TYPE_ARM, 8) => "xscale",
+        (CPU_TYPE_ARM, 9) => "armv7",
+        (CPU_TYPE_ARM, 10) => "armv7f",
+        (CPU_TYPE_ARM, 11) => "armv7s",
+        (CPU_TYPE_ARM, 12) => "armv7k",
+        (CPU_TYPE_ARM, 13) => "armv8",
+        (CPU_TYPE_ARM, 14) => "armv6m",
+        (CPU_TYPE_ARM, 15) => "armv7m",
+        (CPU_TYPE_ARM, 16) => "armv7em",
+        (CPU_TYPE_ARM, _) => "arm",
+        (0x0100_000c, 2) => "arm64e",
+        (0x0100_000c, _) => "arm64",
+        (0x0200_000c, _) => "arm64_32",
+        (CPU_TYPE_POWERPC, _) => "ppc",
+        (0x0100_0012, _) => "ppc64",
+        _ => return cpu_type_name(cpu_type).to_lowercase(),
+    };
+    name.to_string()
+}
+
+/// The subtype with the capability bits, like pointer authentication for arm64e
+fn cpu_subtype_description(cpu_type: u32, cpu_subtype: u32) -> String {
+    let arch = arch_name(cpu_type, cpu_subtype);
+    match cpu_subtype & CPU_SUBTYPE_MASK {
+        0 => arch,
+        capabilities if cpu_type & (CPU_ARCH_ABI64 | CPU_ARCH_ABI64_32) != 0 => {
+            let name = if cpu_type == 0x0100_000c {
+                "PTRAUTH_ABI"
+            } else {
+                "LIB64"
+            };
+            format!("{}, {} {:#x}", arch, name, capabilities)
+        }
+        capabilities => format!("{}, {:#x}", arch, capabilities),
+    }
+}
+
+pub fn file_type_name(file_type: u32) -> String {
+    let name = match file_type {
+        1 => "MH_OBJECT",
+        2 => "MH_EXECUTE",
+        3 => "MH_FVMLIB",
+        4 => "MH_CORE",
+        5 => "MH_PRELOAD",
+        6 => "MH_DYLIB",
+        7 => "MH_DYLINKER",
+        8 => "MH_BUNDLE",
+        9 => "MH_DYLIB_STUB",
+        10 => "MH_DSYM",
+        11 => "MH_KEXT_BUNDLE",
+        12 => "MH_FILESET",
+        13 => "MH_GPU_EXECUTE",
+        14 => "MH_GPU_DYLIB",
+        _ => return file_type.to_string(),
+    };
+    name.to_string()
+}
+
+const HEADER_FLAGS: [(u64, &str); 29] = [
+    (0x1, "NOUNDEFS"),
+    (0x2, "INCRLINK"),
+    (0x4, "DYLDLINK"),
+    (0x8, "BINDATLOAD"),
+    (0x10, "PREBOUND"),
+    (0x20, "SPLIT_SEGS"),
+    (0x40, "LAZY_INIT"),
+    (0x80, "TWOLEVEL"),
+    (0x100, "FORCE_FLAT"),
+    (0x200, "NOMULTIDEFS"),
+    (0x400, "NOFIXPREBINDING"),
+    (0x800, "PREBINDABLE"),
+    (0x1000, "ALLMODSBOUND"),
+    (0x2000, "SUBSECTIONS_VIA_SYMBOLS"),
+    (0x4000, "CANONICAL"),
+    (0x8000, "WEAK_DEFINES"),
+    (0x1_0000, "BINDS_TO_WEAK"),
+    (0x2_0000, "ALLOW_STACK_EXECUTION"),
+    (0x4_0000, "ROOT_SAFE"),
+    (0x8_0000, "SETUID_SAFE"),
+    (0x10_0000, "NO_REEXPORTED_DYLIBS"),
+    (0x20_0000, "PIE"),
+    (0x40_0000, "DEAD_STRIPPABLE_DYLIB"),
+    (0x80_0000, "HAS_TLV_DESCRIPTORS"),
+    (0x100_0000, "NO_HEAP_EXECUTION"),
+    (0x200_0000, "APP_EXTENSION_SAFE"),
+    (0x400_0000, "NLIST_OUTOFSYNC_WITH_DYLDINFO"),
+    (0x800_0000, "SIM_SUPPORT"),
+    (0x8000_0000, "DYLIB_IN_CACHE"),
+];
+
+impl FileFormatUi for MachHeader {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let header = context.at(0, self.size());
+        header.collapsing(ui, name, |ui| {
+            let mut fields = header.fields();
+            let magic = match (self.magic, self.big_endian) {
+                (MH_MAGIC_64, false) => "MH_MAGIC_64, little endian",
+                (MH_MAGIC_64, true) => "MH_MAGIC_64, big endian",
+                (_, false) => "MH_MAGIC, little endian",
+                (_, true) => "MH_MAGIC, big endian",
+            };
+            fields
+                .next(4)
+                .field(ui, "magic", &format!("{:#x} ({})", self.magic, magic));
+            let cpu_type = cpu_type_name(self.cpu_type);
+            fields
+                .next(4)
+                .enum_field(ui, "cputype", self.cpu_type.into(), &cpu_type);
+            let cpu_subtype = cpu_subtype_description(self.cpu_type, self.cpu_subtype);
+            fields
+                .next(4)
+                .enum_field(ui, "cpusubtype", self.cpu_subtype.into(), &cpu_subtype);
+            let file_type = file_type_name(self.file_type);
+            fields
+                .next(4)
+                .enum_field(ui, "filetype", self.file_type.into(), &file_type);
+            self.command_count.ui(ui, "ncmds", &fields.next(4));
+            self.commands_size.ui(ui, "sizeofcmds", &fields.next(4));
+            let flags = flag_names(self.flags.into(), HEADER_FLAGS);
+            fields
+                .next(4)
+                .enum_field(ui, "flags", self.flags.into(), &flags);
+            if let Some(reserved) = &mut self.reserved {
+                reserved.ui(ui, "reserved", &fields.next(4));
+            }
+        });
+    }
+}
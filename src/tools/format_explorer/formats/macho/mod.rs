@@ -0,0 +1,683 @@
+use nom::{
+    error::{Error as NomError, ErrorKind},
+    number::complete::{be_u32, be_u64},
+    sequence::Tuple,
+    IResult,
+};
+
+use super::{at, demangle, flag_names, string_at, Confidence, Readers};
+use crate::{
+    error::Error,
+    tools::format_explorer::{FileFormatUi, FormatContext},
+};
+
+mod commands;
+mod headers;
+mod signature;
+mod symbols;
+
+pub use commands::{CommandKind, LoadCommand, Section};
+pub use headers::MachHeader;
+pub use symbols::SymbolTable;
+
+use headers::{FAT_MAGIC, FAT_MAGIC_64, MH_MAGIC, MH_MAGIC_64};
+
+/// Java class files start with `0xcafebabe` too, followed by a version that's at least 45
+/// where universal binaries have their architecture count
+const MAX_FAT_ARCHS: u32 = 44;
+
+/// Checks for a Mach-O magic in either byte order, or a universal binary whose first slice
+/// has one
+pub fn probe(bytes: &[u8]) -> Confidence {
+    let word = |offset: usize| {
+        let word = bytes.get(offset..offset.checked_add(4)?)?;
+        Some(u32::from_be_bytes(word.try_into().unwrap()))
+    };
+    let is_mach_o = |magic: u32| {
+        [MH_MAGIC, MH_MAGIC_64]
+            .iter()
+            .any(|&mach| magic == mach || magic == mach.swap_bytes())
+    };
+    let magic = word(0);
+    if magic.is_some_and(is_mach_o) {
+        return Confidence::High;
+    }
+    let is_universal = matches!(magic, Some(FAT_MAGIC | FAT_MAGIC_64))
+        && word(4).is_some_and(|count| count <= MAX_FAT_ARCHS);
+    if !is_universal {
+        return Confidence::None;
+    }
+    // The first slice's offset follows its CPU type and subtype, 64 bits in `fat_arch_64`
+    let first_offset = if magic == Some(FAT_MAGIC_64) {
+        word(16)
+            .zip(word(20))
+            .map(|(high, low)| (high as u64) << 32 | low as u64)
+    } else {
+        word(16).map(u64::from)
+    };
+    let first_magic = first_offset
+        .and_then(|offset| usize::try_from(offset).ok())
+        .and_then(word);
+    if first_magic.is_some_and(is_mach_o) {
+        Confidence::High
+    } else {
+        Confidence::Low
+    }
+}
+
+/// A single architecture image, on its own or in a universal binary
+pub struct MachO {
+    pub header: MachHeader,
+    pub commands: Vec<LoadCommand>,
+    pub symbol_table: SymbolTable,
+    /// Header values and load commands that are out of spec
+    pub warnings: Vec<String>,
+}
+
+impl FileFormatUi for MachO {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        ui.collapsing(name, |ui| {
+            for warning in &self.warnings {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+            self.header.ui(ui, "header", context);
+            let title = format!("load_commands ({})", self.commands.len());
+            let commands = context.at(self.header.size(), self.header.commands_size.into());
+            commands.collapsing(ui, &title, |ui| {
+                for (index, command) in self.commands.iter_mut().enumerate() {
+                    ui.push_id(index, |ui| command.ui(ui, &format!("[{}]", index), context));
+                }
+            });
+            self.symbol_table.ui(ui, "symbols", context);
+        });
+    }
+}
+
+impl MachO {
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (_, magic) = be_u32(input)?;
+        let (big_endian, is_64) = match magic {
+            MH_MAGIC => (true, false),
+            MH_MAGIC_64 => (true, true),
+            _ if magic == MH_MAGIC.swap_bytes() => (false, false),
+            _ if magic == MH_MAGIC_64.swap_bytes() => (false, true),
+            _ => return Err(nom::Err::Failure(NomError::new(input, ErrorKind::Tag))),
+        };
+        let readers = Readers::new(big_endian, is_64);
+        let (tail, header) = MachHeader::parse(input, readers, big_endian)?;
+        let mut warnings = Vec::new();
+        let commands = commands::parse_commands(
+            input,
+            readers,
+            header.size(),
+            header.command_count,
+            &mut warnings,
+        );
+
+        // Symbols refer to sections by their index across all segments
+        let sections: Vec<&Section> = commands
+            .iter()
+            .filter_map(|command| match &command.kind {
+                CommandKind::Segment(segment) => Some(&segment.sections),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        let symbols = commands
+            .iter()
+            .find_map(|command| match command.kind {
+                CommandKind::SymbolTable {
+                    symbol_offset,
+                    symbol_count,
+                    string_offset,
+                    string_size,
+                } => {
+                    let strings = at(input, string_offset.into())
+                        .unwrap_or_default()
+                        .get(..string_size as usize)
+                        .unwrap_or_default();
+                    Some(symbols::parse_symbols(
+                        input,
+                        readers,
+                        symbol_offset,
+                        symbol_count,
+                        strings,
+                        &sections,
+                    ))
+                }
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        let mut mach_o = Self {
+            header,
+            commands,
+            symbol_table: SymbolTable::new(symbols, is_64),
+            warnings,
+        };
+        mach_o.find_warnings(input);
+        Ok((tail, mach_o))
+    }
+
+    fn find_warnings(&mut self, input: &[u8]) {
+        let commands_end = self.header.size() + self.header.commands_size as u64;
+        if let Some(last) = self.commands.last() {
+            let end = last.offset + last.size as u64;
+            if end != commands_end {
+                self.warnings.push(format!(
+                    "Load commands end at {:#x} but sizeofcmds says {:#x}",
+                    end, commands_end
+                ));
+            }
+        }
+        let alignment = if self.header.reserved.is_some() { 8 } else { 4 };
+        for command in &self.commands {
+            if command.size % alignment != 0 {
+                self.warnings.push(format!(
+                    "{} at {:#x} has a size that isn't a multiple of {}",
+                    commands::command_name(command.cmd),
+                    command.offset,
+                    alignment
+                ));
+            }
+            let range = match &command.kind {
+                CommandKind::Segment(segment) => Some((
+                    format!("Segment {}", segment.name),
+                    segment.file_offset,
+                    segment.file_size,
+                )),
+                CommandKind::SymbolTable {
+                    string_offset,
+                    string_size,
+                    ..
+                } => Some((
+                    "The string table".to_string(),
+                    (*string_offset).into(),
+                    (*string_size).into(),
+                )),
+                CommandKind::LinkeditData {
+                    data_offset,
+                    data_size,
+                    ..
+                } => Some((
+                    format!("The data of {}", commands::command_name(command.cmd)),
+                    (*data_offset).into(),
+                    (*data_size).into(),
+                )),
+                _ => None,
+            };
+            if let Some((name, offset, size)) = range {
+                if offset.saturating_add(size) > input.len() as u64 {
+                    self.warnings
+                        .push(format!("{} goes past the end of the file", name));
+                }
+            }
+        }
+    }
+
+    /// Header fields under their names from `loader.h`, the entry point and the file offsets
+    /// of symbols, shifted by `base` for slices of universal binaries
+    fn symbols(&self, base: u64) -> Vec<(String, u64)> {
+        let entry_offset = self.commands.iter().find_map(|command| match command.kind {
+            CommandKind::Main { entry_offset, .. } => Some(entry_offset),
+            _ => None,
+        });
+        [
+            ("cputype", self.header.cpu_type as u64),
+            ("cpusubtype", self.header.cpu_subtype as u64),
+            ("filetype", self.header.file_type as u64),
+            ("ncmds", self.header.command_count as u64),
+            ("sizeofcmds", self.header.commands_size as u64),
+            ("flags", self.header.flags as u64),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .chain(entry_offset.map(|offset| ("entryoff".to_string(), base + offset)))
+        .chain(self.symbol_table.symbols.iter().flat_map(|symbol| {
+            let offset = symbol.file_offset.map(|offset| base + offset);
+            let names = std::iter::once(symbol.name.clone()).chain(symbol.demangled.clone());
+            names.filter_map(move |name| Some((name, offset?)))
+        }))
+        .collect()
+    }
+}
+
+/// One architecture of a universal binary
+pub struct FatArch {
+    pub cpu_type: u32,
+    pub cpu_subtype: u32,
+    pub offset: u64,
+    pub size: u64,
+    /// Power of two
+    pub align: u32,
+    /// Each slice is parsed on its own, a broken one doesn't hide the others
+    pub image: Result<MachO, Error>,
+}
+
+impl FatArch {
+    fn parse(input: &[u8], is_64: bool) -> IResult<&[u8], Self> {
+        let (tail, (cpu_type, cpu_subtype)) = (be_u32, be_u32).parse(input)?;
+        let (tail, (offset, size, align)) = if is_64 {
+            // `fat_arch_64` has a reserved field at the end
+            let (tail, (offset, size, align, _reserved)) =
+                (be_u64, be_u64, be_u32, be_u32).parse(tail)?;
+            (tail, (offset, size, align))
+        } else {
+            let (tail, (offset, size, align)) = (be_u32, be_u32, be_u32).parse(tail)?;
+            (tail, (offset.into(), size.into(), align))
+        };
+        Ok((
+            tail,
+            Self {
+                cpu_type,
+                cpu_subtype,
+                offset,
+                size,
+                align,
+                image: Err(Error::parse("Mach-O", "slice not parsed")),
+            },
+        ))
+    }
+
+    pub fn arch_name(&self) -> String {
+        headers::arch_name(self.cpu_type, self.cpu_subtype)
+    }
+}
+
+pub enum MachOFormat {
+    Single(MachO),
+    Universal {
+        magic: u32,
+        architectures: Vec<FatArch>,
+        /// Slices that overlap or go past the end of the file
+        warnings: Vec<String>,
+    },
+}
+
+impl FileFormatUi for MachOFormat {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        match self {
+            MachOFormat::Single(mach_o) => mach_o.ui(ui, name, context),
+            MachOFormat::Universal {
+                magic,
+                architectures,
+                warnings,
+            } => {
+                ui.collapsing(name, |ui| {
+                    for warning in warnings.iter() {
+                        ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+                    }
+                    let magic_name = if *magic == FAT_MAGIC_64 {
+                        "FAT_MAGIC_64"
+                    } else {
+                        "FAT_MAGIC"
+                    };
+                    let mut fields = context.at(0, 8).fields();
+                    fields
+                        .next(4)
+                        .enum_field(ui, "magic", (*magic).into(), magic_name);
+                    let count = architectures.len() as u64;
+                    fields
+                        .next(4)
+                        .field(ui, "nfat_arch", &context.number(count));
+                    let is_64 = *magic == FAT_MAGIC_64;
+                    let (address_size, entry_size) = if is_64 { (8, 32) } else { (4, 20) };
+                    for (index, arch) in architectures.iter_mut().enumerate() {
+                        let title = format!("[{}] {}", index, arch.arch_name());
+                        let entry = fields.next(entry_size);
+                        ui.push_id(index, |ui| {
+                            entry.collapsing(ui, &title, |ui| {
+                                let mut fields = entry.fields();
+                                let cpu_type = headers::cpu_type_name(arch.cpu_type);
+                                fields.next(4).enum_field(
+                                    ui,
+                                    "cputype",
+                                    arch.cpu_type.into(),
+                                    &cpu_type,
+                                );
+                                let subtype = arch.cpu_subtype.into();
+                                fields.next(4).enum_field(
+                                    ui,
+                                    "cpusubtype",
+                                    subtype,
+                                    &arch.arch_name(),
+                                );
+                                fields.next(address_size).field(
+                                    ui,
+                                    "offset",
+                                    &format!("{:#x}", arch.offset),
+                                );
+                                context.range_link(ui, "slice bytes", arch.offset, arch.size);
+                                arch.size.ui(ui, "size", &fields.next(address_size));
+                                let align = format!("2^{}", arch.align);
+                                fields
+                                    .next(4)
+                                    .enum_field(ui, "align", arch.align.into(), &align);
+                                match &mut arch.image {
+                                    Ok(image) => {
+                                        // Offsets in the slice are relative to its start
+                                        let context = context.nested(arch.offset);
+                                        image.ui(ui, "image", &context);
+                                    }
+                                    Err(err) => {
+                                        ui.colored_label(
+                                            ui.visuals().error_fg_color,
+                                            err.to_string(),
+                                        );
+                                    }
+                                }
+                            });
+                        });
+                    }
+                });
+            }
+        }
+    }
+}
+
+impl MachOFormat {
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (tail, (magic, count)) = (be_u32, be_u32).parse(input)?;
+        if magic != FAT_MAGIC && magic != FAT_MAGIC_64 {
+            let (tail, mach_o) = MachO::parse(input)?;
+            return Ok((tail, MachOFormat::Single(mach_o)));
+        }
+        if count > MAX_FAT_ARCHS {
+            return Err(nom::Err::Failure(NomError::new(
+                &input[4..],
+                ErrorKind::Switch,
+            )));
+        }
+        let mut architectures = Vec::new();
+        let mut table = tail;
+        for _ in 0..count {
+            let (rest, mut arch) = FatArch::parse(table, magic == FAT_MAGIC_64)?;
+            table = rest;
+            let slice = at(input, arch.offset)?;
+            let slice = &slice[..usize::try_from(arch.size)
+                .unwrap_or(usize::MAX)
+                .min(slice.len())];
+            arch.image = MachO::parse(slice)
+                .map(|(_, image)| image)
+                .map_err(|err| Error::from_nom("Mach-O", slice, err));
+            architectures.push(arch);
+        }
+        let warnings = fat_warnings(&architectures, input.len() as u64);
+        Ok((
+            table,
+            MachOFormat::Universal {
+                magic,
+                architectures,
+                warnings,
+            },
+        ))
+    }
+
+    /// Header fields and symbols, with those of the first slice for universal binaries
+    pub fn symbols(&self) -> Vec<(String, u64)> {
+        match self {
+            MachOFormat::Single(mach_o) => mach_o.symbols(0),
+            MachOFormat::Universal { architectures, .. } => {
+                let mut symbols = vec![("nfat_arch".to_string(), architectures.len() as u64)];
+                for arch in architectures {
+                    let name = arch.arch_name();
+                    symbols.push((format!("{}_offset", name), arch.offset));
+                    symbols.push((format!("{}_size", name), arch.size));
+                }
+                if let Some((arch, Ok(image))) = architectures
+                    .first()
+                    .map(|arch| (arch, arch.image.as_ref()))
+                {
+                    symbols.extend(image.symbols(arch.offset));
+                }
+                symbols
+            }
+        }
+    }
+
+    pub fn new(bytes: &[u8]) -> Result<Self, Error> {
+        Self::parse(bytes)
+            .map(|(_, mach_o)| mach_o)
+            .map_err(|err| Error::from_nom("Mach-O", bytes, err))
+    }
+}
+
+fn fat_warnings(architectures: &[FatArch], file_size: u64) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for (index, arch) in architectures.iter().enumerate() {
+        let end = arch.offset.saturating_add(arch.size);
+        if end > file_size {
+            warnings.push(format!(
+                "Slice {} ({}) goes past the end of the file",
+                index,
+                arch.arch_name()
+            ));
+        }
+        if arch.align < 64 && arch.offset % (1 << arch.align) != 0 {
+            warnings.push(format!(
+                "Slice {} ({}) isn't aligned to 2^{}",
+                index,
+                arch.arch_name(),
+                arch.align
+            ));
+        }
+        for (other_index, other) in architectures.iter().enumerate().skip(index + 1) {
+            if arch.offset < other.offset.saturating_add(other.size) && other.offset < end {
+                warnings.push(format!("Slices {} and {} overlap", index, other_index));
+            }
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    //! The object files were compiled by rustc for each target from a `no_core` library, and
+    //! `fat.o` combines two of them like `lipo` does. `exec` is a hand-built executable with the
+    //! load commands linkers usually write.
+
+    use super::*;
+
+    const X86_64: &[u8] = include_bytes!("fixtures/x86_64.o");
+    const I386: &[u8] = include_bytes!("fixtures/i386.o");
+    const FAT: &[u8] = include_bytes!("fixtures/fat.o");
+    const EXEC: &[u8] = include_bytes!("fixtures/exec");
+
+    const LC_SEGMENT: u32 = 0x1;
+    const LC_SYMTAB: u32 = 0x2;
+    const LC_DYSYMTAB: u32 = 0xb;
+    const LC_SEGMENT_64: u32 = 0x19;
+    const LC_BUILD_VERSION: u32 = 0x32;
+
+    fn single(bytes: &[u8]) -> MachO {
+        match MachOFormat::new(bytes).unwrap() {
+            MachOFormat::Single(mach_o) => mach_o,
+            MachOFormat::Universal { .. } => panic!("parsed as a universal binary"),
+        }
+    }
+
+    fn cmds(mach_o: &MachO) -> Vec<u32> {
+        mach_o.commands.iter().map(|command| command.cmd).collect()
+    }
+
+    fn symbol<'a>(symbols: &'a [(String, u64)], name: &str) -> Option<&'a u64> {
+        symbols
+            .iter()
+            .find(|(symbol, _)| symbol == name)
+            .map(|(_, value)| value)
+    }
+
+    #[test]
+    fn thin_64_bit_object() {
+        assert_eq!(probe(X86_64), Confidence::High);
+        let mach_o = single(X86_64);
+        assert_eq!(mach_o.header.magic, MH_MAGIC_64);
+        assert!(!mach_o.header.big_endian);
+        assert_eq!(mach_o.header.cpu_type, 0x0100_0007);
+        assert_eq!(mach_o.header.file_type, 1);
+        assert_eq!(
+            cmds(&mach_o),
+            [LC_SEGMENT_64, LC_BUILD_VERSION, LC_SYMTAB, LC_DYSYMTAB]
+        );
+        assert!(mach_o.warnings.is_empty(), "{:?}", mach_o.warnings);
+        let CommandKind::Segment(segment) = &mach_o.commands[0].kind else {
+            panic!("first command isn't a segment");
+        };
+        let sections: Vec<&str> = segment.sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(sections, ["__text", "__cstring", "__data", "__bss"]);
+        let symbols = mach_o.symbols(0);
+        assert_eq!(symbol(&symbols, "_hello_world"), Some(&0x228));
+        assert_eq!(symbol(&symbols, "_GREETING"), Some(&0x244));
+        // Undefined symbols have no place in the file
+        assert_eq!(symbol(&symbols, "_external_function"), None);
+        assert_eq!(symbol(&symbols, "ncmds"), Some(&4));
+    }
+
+    #[test]
+    fn thin_32_bit_object() {
+        let mach_o = single(I386);
+        assert_eq!(mach_o.header.magic, MH_MAGIC);
+        assert_eq!(mach_o.header.reserved, None);
+        assert_eq!(mach_o.header.size(), 28);
+        assert_eq!(
+            cmds(&mach_o),
+            [LC_SEGMENT, LC_BUILD_VERSION, LC_SYMTAB, LC_DYSYMTAB]
+        );
+        assert!(mach_o.warnings.is_empty(), "{:?}", mach_o.warnings);
+        assert_eq!(symbol(&mach_o.symbols(0), "_hello_world"), Some(&0x1e4));
+    }
+
+    #[test]
+    fn universal_binary() {
+        assert_eq!(probe(FAT), Confidence::High);
+        let MachOFormat::Universal {
+            magic,
+            architectures,
+            warnings,
+        } = MachOFormat::new(FAT).unwrap()
+        else {
+            panic!("parsed as a single image");
+        };
+        assert_eq!(magic, FAT_MAGIC);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        let slices: Vec<(String, u64, u64, u32)> = architectures
+            .iter()
+            .map(|arch| (arch.arch_name(), arch.offset, arch.size, arch.align))
+            .collect();
+        assert_eq!(
+            slices,
+            [
+                ("x86_64".to_string(), 0x1000, 0x300, 12),
+                ("arm64".to_string(), 0x4000, 0x230, 14),
+            ]
+        );
+        for arch in &architectures {
+            let image = arch.image.as_ref().unwrap();
+            assert!(image.warnings.is_empty(), "{:?}", image.warnings);
+        }
+        // The first slice's symbols are shifted to where it is in the file
+        let symbols = MachOFormat::new(FAT).unwrap().symbols();
+        assert_eq!(symbol(&symbols, "nfat_arch"), Some(&2));
+        assert_eq!(symbol(&symbols, "arm64_offset"), Some(&0x4000));
+        assert_eq!(symbol(&symbols, "_hello_world"), Some(&(0x1000 + 0x228)));
+    }
+
+    #[test]
+    fn universal_slice_past_the_end() {
+        let MachOFormat::Universal {
+            architectures,
+            warnings,
+            ..
+        } = MachOFormat::new(&FAT[..0x4100]).unwrap()
+        else {
+            panic!("parsed as a single image");
+        };
+        assert_eq!(warnings, ["Slice 1 (arm64) goes past the end of the file"]);
+        // The cut off slice still parses as far as it goes
+        assert!(architectures[1].image.is_ok());
+    }
+
+    #[test]
+    fn java_class_isnt_universal() {
+        let mut class = b"\xca\xfe\xba\xbe\x00\x00\x00\x34".to_vec();
+        class.resize(64, 0);
+        assert_eq!(probe(&class), Confidence::None);
+        assert!(MachOFormat::new(&class).is_err());
+    }
+
+    #[test]
+    fn load_command_walk() {
+        let mach_o = single(EXEC);
+        assert!(mach_o.warnings.is_empty(), "{:?}", mach_o.warnings);
+        assert_eq!(mach_o.commands.len(), 15);
+        // Each command starts where the previous one ends, and the last ends at sizeofcmds
+        let mut offset = mach_o.header.size();
+        for command in &mach_o.commands {
+            assert_eq!(command.offset, offset);
+            offset += command.size as u64;
+        }
+        assert_eq!(offset, mach_o.header.size() + 712);
+        let segments: Vec<&str> = mach_o
+            .commands
+            .iter()
+            .filter_map(|command| match &command.kind {
+                CommandKind::Segment(segment) => Some(segment.name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(segments, ["__PAGEZERO", "__TEXT", "__LINKEDIT"]);
+        assert!(mach_o.commands.iter().any(|command| matches!(
+            &command.kind,
+            CommandKind::Dylib { name, .. } if name == "/usr/lib/libSystem.B.dylib"
+        )));
+        assert!(mach_o.commands.iter().any(|command| matches!(
+            &command.kind,
+            CommandKind::Path(path) if path == "/usr/lib/dyld"
+        )));
+        assert!(mach_o.commands.iter().any(|command| matches!(
+            &command.kind,
+            CommandKind::Uuid(uuid) if uuid[..] == (0..16).collect::<Vec<u8>>()[..]
+        )));
+        let symbols = mach_o.symbols(0);
+        assert_eq!(symbol(&symbols, "entryoff"), Some(&0x1000));
+        assert_eq!(symbol(&symbols, "_main"), Some(&0x1000));
+    }
+
+    #[test]
+    fn truncated_load_command() {
+        // Cut in the middle of the third command, the `__LINKEDIT` segment at 0x100
+        let mach_o = single(&EXEC[..0x120]);
+        assert_eq!(mach_o.commands.len(), 2);
+        assert!(
+            mach_o
+                .warnings
+                .iter()
+                .any(|warning| warning == "Load command 2 at 0x100 goes past the end of the file"),
+            "{:?}",
+            mach_o.warnings
+        );
+        // Cut right after a command, with more of them expected
+        let mach_o = single(&EXEC[..0x100]);
+        assert_eq!(mach_o.commands.len(), 2);
+        assert!(mach_o
+            .warnings
+            .contains(&"Load command 2 at 0x100 is past the end of the file".to_string()));
+    }
+
+    #[test]
+    fn load_command_too_small() {
+        let mut bytes = EXEC.to_vec();
+        // cmdsize of the second command
+        bytes[0x6c..0x70].copy_from_slice(&4u32.to_le_bytes());
+        let mach_o = single(&bytes);
+        assert_eq!(mach_o.commands.len(), 1);
+        assert!(mach_o.warnings.contains(
+            &"Load command 1 at 0x68 has a size of 4, too small for its header".to_string()
+        ));
+    }
+
+    #[test]
+    fn header_cut_off() {
+        assert!(MachOFormat::new(&X86_64[..16]).is_err());
+    }
+}
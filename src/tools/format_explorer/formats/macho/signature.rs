@@ -0,0 +1,276 @@
+use nom::{
+    number::complete::{be_u32, u8},
+    sequence::Tuple,
+    IResult,
+};
+
+use super::{at, flag_names, string_at};
+use crate::tools::format_explorer::{FileFormatUi, FormatContext};
+
+const CSMAGIC_EMBEDDED_SIGNATURE: u32 = 0xfade_0cc0;
+const CSMAGIC_CODEDIRECTORY: u32 = 0xfade_0c02;
+const CSMAGIC_EMBEDDED_ENTITLEMENTS: u32 = 0xfade_7171;
+
+/// Code directories from this version on have a team identifier
+const CS_SUPPORTSTEAMID: u32 = 0x20200;
+
+/// Limit for super blobs with a bogus count
+const MAX_BLOBS: usize = 64;
+
+/// Code signatures are big endian, whatever the byte order of the file
+fn be_word(input: &[u8]) -> IResult<&[u8], u32> {
+    be_u32(input)
+}
+
+pub struct CodeDirectory {
+    pub version: u32,
+    pub flags: u32,
+    pub hash_offset: u32,
+    pub identifier: String,
+    /// Only in version 0x20200 and later
+    pub team_id: Option<String>,
+    pub special_slot_count: u32,
+    pub code_slot_count: u32,
+    pub code_limit: u32,
+    pub hash_size: u8,
+    pub hash_type: u8,
+    pub platform: u8,
+    /// Power of two
+    pub page_size: u8,
+}
+
+impl CodeDirectory {
+    fn parse(blob: &[u8]) -> IResult<&[u8], Self> {
+        let (
+            tail,
+            (
+                _magic,
+                _length,
+                version,
+                flags,
+                hash_offset,
+                identifier_offset,
+                special_slot_count,
+                code_slot_count,
+                code_limit,
+            ),
+        ) = (
+            be_word, be_word, be_word, be_word, be_word, be_word, be_word, be_word, be_word,
+        )
+            .parse(blob)?;
+        let (tail, (hash_size, hash_type, platform, page_size, _spare, _scatter_offset)) =
+            (u8, u8, u8, u8, be_word, be_word).parse(tail)?;
+        let team_id = if version >= CS_SUPPORTSTEAMID {
+            let (_, team_offset) = be_word(tail)?;
+            (team_offset != 0).then(|| string_at(blob, team_offset.into()))
+        } else {
+            None
+        };
+        Ok((
+            tail,
+            Self {
+                version,
+                flags,
+                hash_offset,
+                identifier: string_at(blob, identifier_offset.into()),
+                team_id,
+                special_slot_count,
+                code_slot_count,
+                code_limit,
+                hash_size,
+                hash_type,
+                platform,
+                page_size,
+            },
+        ))
+    }
+}
+
+fn hash_type_name(hash_type: u8) -> String {
+    let name = match hash_type {
+        1 => "SHA1",
+        2 => "SHA256",
+        3 => "SHA256_TRUNCATED",
+        4 => "SHA384",
+        _ => return hash_type.to_string(),
+    };
+    name.to_string()
+}
+
+const CODE_DIRECTORY_FLAGS: [(u64, &str); 11] = [
+    (0x1, "VALID"),
+    (0x2, "ADHOC"),
+    (0x4, "GET_TASK_ALLOW"),
+    (0x100, "HARD"),
+    (0x200, "KILL"),
+    (0x400, "CHECK_EXPIRATION"),
+    (0x800, "RESTRICT"),
+    (0x1000, "ENFORCEMENT"),
+    (0x2000, "REQUIRE_LV"),
+    (0x1_0000, "RUNTIME"),
+    (0x2_0000, "LINKER_SIGNED"),
+];
+
+/// Name of the slot a blob is in
+fn slot_name(slot: u32) -> String {
+    let name = match slot {
+        0 => "CodeDirectory",
+        1 => "Info.plist",
+        2 => "Requirements",
+        3 => "ResourceDirectory",
+        4 => "Application",
+        5 => "Entitlements",
+        6 => "RepSpecific",
+        7 => "DER Entitlements",
+        0x1000..=0x1004 => "Alternate CodeDirectory",
+        0x1_0000 => "CMS Signature",
+        _ => return format!("{:#x}", slot),
+    };
+    name.to_string()
+}
+
+pub enum BlobContents {
+    CodeDirectory(CodeDirectory),
+    /// XML property list
+    Entitlements(String),
+    Other,
+}
+
+pub struct SignatureBlob {
+    pub slot: u32,
+    pub offset: u64,
+    pub magic: u32,
+    pub length: u32,
+    pub contents: BlobContents,
+}
+
+/// The `LC_CODE_SIGNATURE` super blob with the code directory, requirements, entitlements
+/// and CMS signature
+pub struct CodeSignature {
+    /// Where the super blob is in the file
+    pub offset: u64,
+    pub length: u32,
+    pub blobs: Vec<SignatureBlob>,
+}
+
+impl CodeSignature {
+    /// Parses the signature at `offset`, `None` if it isn't an embedded signature
+    pub(super) fn parse(input: &[u8], offset: u64, size: u64) -> Option<Self> {
+        let data = at(input, offset).ok()?;
+        let data = data.get(..usize::try_from(size).ok()?.min(data.len()))?;
+        let (mut index, (magic, length, blob_count)) =
+            (be_word, be_word, be_word).parse(data).ok()?;
+        if magic != CSMAGIC_EMBEDDED_SIGNATURE {
+            return None;
+        }
+        let mut blobs = Vec::new();
+        for _ in 0..(blob_count as usize).min(MAX_BLOBS) {
+            let Ok((tail, (slot, blob_offset))) = (be_word, be_word).parse(index) else {
+                break;
+            };
+            index = tail;
+            let Some(blob) = data.get(blob_offset as usize..) else {
+                continue;
+            };
+            let Ok((_, (magic, length))) = (be_word, be_word).parse(blob) else {
+                continue;
+            };
+            let blob = &blob[..(length as usize).min(blob.len())];
+            let contents = match magic {
+                CSMAGIC_CODEDIRECTORY => CodeDirectory::parse(blob)
+                    .map_or(BlobContents::Other, |(_, directory)| {
+                        BlobContents::CodeDirectory(directory)
+                    }),
+                CSMAGIC_EMBEDDED_ENTITLEMENTS => BlobContents::Entitlements(
+                    String::from_utf8_lossy(blob.get(8..).unwrap_or_default()).into_owned(),
+                ),
+                _ => BlobContents::Other,
+            };
+            blobs.push(SignatureBlob {
+                slot,
+                offset: offset + blob_offset as u64,
+                magic,
+                length,
+                contents,
+            });
+        }
+        Some(Self {
+            offset,
+            length,
+            blobs,
+        })
+    }
+}
+
+/// `context` is tied to the blob the code directory is in
+impl FileFormatUi for CodeDirectory {
+    fn ui(&mut self, ui: &mut egui::Ui, _name: &str, context: &FormatContext) {
+        let mut fields = context.fields();
+        // The magic and length are shown with the blob
+        fields.skip(8);
+        let version = fields.next(4);
+        let flags = fields.next(4);
+        let hash_offset = fields.next(4);
+        let identifier = fields.next(4);
+        let special_slots = fields.next(4);
+        let code_slots = fields.next(4);
+        let code_limit = fields.next(4);
+        let hash_size = fields.next(1);
+        let hash_type = fields.next(1);
+        let platform = fields.next(1);
+        let page_size = fields.next(1);
+        // Spare and scatter offset
+        fields.skip(8);
+        identifier.field(ui, "identifier", &self.identifier);
+        if let Some(team_id) = &self.team_id {
+            fields.next(4).field(ui, "team_id", team_id);
+        }
+        version.field(ui, "version", &format!("{:#x}", self.version));
+        let flag_names = flag_names(self.flags.into(), CODE_DIRECTORY_FLAGS);
+        flags.enum_field(ui, "flags", self.flags.into(), &flag_names);
+        hash_offset.field(ui, "hash_offset", &format!("{:#x}", self.hash_offset));
+        self.special_slot_count
+            .ui(ui, "special_slots", &special_slots);
+        self.code_slot_count.ui(ui, "code_slots", &code_slots);
+        code_limit.field(ui, "code_limit", &format!("{:#x}", self.code_limit));
+        self.hash_size.ui(ui, "hash_size", &hash_size);
+        let hash_type_name = hash_type_name(self.hash_type);
+        hash_type.enum_field(ui, "hash_type", self.hash_type.into(), &hash_type_name);
+        self.platform.ui(ui, "platform", &platform);
+        page_size.field(ui, "page_size", &format!("2^{}", self.page_size));
+    }
+}
+
+impl FileFormatUi for CodeSignature {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let title = format!("{} ({} blobs)", name, self.blobs.len());
+        let super_blob = context.at(self.offset, self.length.into());
+        super_blob.collapsing(ui, &title, |ui| {
+            let mut fields = super_blob.fields();
+            fields.skip(4);
+            self.length.ui(ui, "length", &fields.next(4));
+            for (index, blob) in self.blobs.iter_mut().enumerate() {
+                let title = format!("[{}] {}", index, slot_name(blob.slot));
+                let blob_context = context.at(blob.offset, blob.length.into());
+                ui.push_id(index, |ui| {
+                    blob_context.collapsing(ui, &title, |ui| {
+                        context.range_link(ui, "bytes", blob.offset, blob.length.into());
+                        let mut fields = blob_context.fields();
+                        fields
+                            .next(4)
+                            .field(ui, "magic", &format!("{:#x}", blob.magic));
+                        match &mut blob.contents {
+                            BlobContents::CodeDirectory(directory) => {
+                                directory.ui(ui, "code_directory", &blob_context);
+                            }
+                            BlobContents::Entitlements(entitlements) => {
+                                ui.label(egui::RichText::new(entitlements.as_str()).monospace());
+                            }
+                            BlobContents::Other => {}
+                        }
+                    });
+                });
+            }
+        });
+    }
+}
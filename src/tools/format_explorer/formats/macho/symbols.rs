@@ -0,0 +1,318 @@
+use egui::Vec2b;
+use egui_extras::Column;
+use nom::{number::complete::u8, sequence::Tuple, IResult};
+
+use super::{commands::Section, demangle, string_at, Readers};
+use crate::tools::format_explorer::{FileFormatUi, FormatContext};
+
+/// Bits of `n_type`
+const N_STAB: u8 = 0xe0;
+const N_PEXT: u8 = 0x10;
+const N_TYPE: u8 = 0x0e;
+const N_EXT: u8 = 0x01;
+
+const N_SECT: u8 = 0xe;
+/// `n_sect` of symbols that aren't in any section
+const NO_SECT: u8 = 0;
+
+/// Limit for symbol tables with a bogus count
+const MAX_SYMBOLS: usize = 1 << 20;
+
+pub struct Symbol {
+    pub name: String,
+    /// Readable name for Rust and C++ symbols
+    pub demangled: Option<String>,
+    pub type_: u8,
+    /// 1-based index of the section, counting through all segments
+    pub section: u8,
+    pub description: u16,
+    pub value: u64,
+    /// `segment,section` the symbol is in
+    pub section_name: Option<String>,
+    /// Where the symbol's bytes are, if it points into a section stored in the file
+    pub file_offset: Option<u64>,
+    /// Distance to the next symbol or the end of the section, nlist has no sizes
+    pub size: u64,
+    /// Where the nlist entry is in the file
+    pub entry_offset: u64,
+}
+
+impl Symbol {
+    /// Returns the symbol with its name still unresolved, and the offset of the name
+    fn parse(input: &[u8], readers: Readers) -> IResult<&[u8], (u32, Self)> {
+        let address = |input| readers.address(input);
+        let (tail, (name_offset, type_, section, description, value)) =
+            (readers.word, u8, u8, readers.half, address).parse(input)?;
+        Ok((
+            tail,
+            (
+                name_offset,
+                Self {
+                    name: String::new(),
+                    demangled: None,
+                    type_,
+                    section,
+                    description,
+                    value,
+                    section_name: None,
+                    file_offset: None,
+                    size: 0,
+                    entry_offset: 0,
+                },
+            ),
+        ))
+    }
+
+    /// Name to show, the demangled one if there is one
+    pub fn display_name(&self) -> &str {
+        self.demangled.as_deref().unwrap_or(&self.name)
+    }
+
+    /// `SECT`, `UNDF` and the other `N_TYPE` values, or the stab type of debugging symbols
+    pub fn type_name(&self) -> String {
+        if self.type_ & N_STAB != 0 {
+            return format!("STAB {}", stab_name(self.type_));
+        }
+        let type_ = self.type_ & N_TYPE;
+        let name = match type_ {
+            0x0 => "UNDF",
+            0x2 => "ABS",
+            N_SECT => "SECT",
+            0xc => "PBUD",
+            0xa => "INDR",
+            _ => return format!("{:#x}", type_),
+        };
+        name.to_string()
+    }
+
+    /// External, private external or local
+    pub fn scope_name(&self) -> &'static str {
+        if self.type_ & N_STAB != 0 {
+            ""
+        } else if self.type_ & N_PEXT != 0 {
+            "private extern"
+        } else if self.type_ & N_EXT != 0 {
+            "extern"
+        } else {
+            "local"
+        }
+    }
+}
+
+fn stab_name(type_: u8) -> String {
+    let name = match type_ {
+        0x20 => "GSYM",
+        0x22 => "FNAME",
+        0x24 => "FUN",
+        0x26 => "STSYM",
+        0x28 => "LCSYM",
+        0x2e => "BNSYM",
+        0x30 => "AST",
+        0x3c => "OPT",
+        0x40 => "RSYM",
+        0x44 => "SLINE",
+        0x4e => "ENSYM",
+        0x60 => "SSYM",
+        0x64 => "SO",
+        0x66 => "OSO",
+        0x80 => "LSYM",
+        0x82 => "BINCL",
+        0x84 => "SOL",
+        0x86 => "PARAMS",
+        0x88 => "VERSION",
+        0x8a => "OLEVEL",
+        0xa0 => "PSYM",
+        0xa2 => "EINCL",
+        0xa4 => "ENTRY",
+        0xc0 => "LBRAC",
+        0xc2 => "EXCL",
+        0xe0 => "RBRAC",
+        0xe2 => "BCOMM",
+        0xe4 => "ECOMM",
+        0xe8 => "ECOML",
+        0xfe => "LENG",
+        _ => return format!("{:#x}", type_),
+    };
+    name.to_string()
+}
+
+/// Parses the `LC_SYMTAB` symbols, with their sections resolved
+pub(super) fn parse_symbols(
+    input: &[u8],
+    readers: Readers,
+    symbol_offset: u32,
+    symbol_count: u32,
+    strings: &[u8],
+    sections: &[&Section],
+) -> Vec<Symbol> {
+    let entry_size = if readers.is_64 { 16 } else { 12 };
+    let Some(table) = input.get(symbol_offset as usize..) else {
+        return Vec::new();
+    };
+    let mut symbols: Vec<Symbol> = table
+        .chunks_exact(entry_size)
+        .take((symbol_count as usize).min(MAX_SYMBOLS))
+        .enumerate()
+        .filter_map(|(index, entry)| {
+            let (_, (name_offset, mut symbol)) = Symbol::parse(entry, readers).ok()?;
+            symbol.entry_offset = symbol_offset as u64 + (index * entry_size) as u64;
+            symbol.name = string_at(strings, name_offset.into());
+            // C and C++ names get an extra leading underscore
+            let name = symbol.name.strip_prefix('_').unwrap_or(&symbol.name);
+            symbol.demangled = demangle(name);
+            let section = (symbol.type_ & N_STAB == 0
+                && symbol.type_ & N_TYPE == N_SECT
+                && symbol.section != NO_SECT)
+                .then(|| sections.get(symbol.section as usize - 1))
+                .flatten();
+            if let Some(section) = section {
+                symbol.section_name = Some(format!("{},{}", section.segment_name, section.name));
+                symbol.file_offset = section.file_offset(symbol.value);
+            }
+            Some(symbol)
+        })
+        .collect();
+
+    // Symbols extend to the next symbol in the same section
+    let mut order: Vec<usize> = (0..symbols.len())
+        .filter(|&index| symbols[index].file_offset.is_some())
+        .collect();
+    order.sort_by_key(|&index| (symbols[index].section, symbols[index].value));
+    // Walking backwards, `end` is where the symbols after the current one start
+    let mut next: Option<(u8, u64)> = None;
+    let mut end = 0;
+    for &index in order.iter().rev() {
+        let symbol = &mut symbols[index];
+        match next {
+            Some((section, value)) if section == symbol.section => {
+                if value > symbol.value {
+                    end = value;
+                }
+            }
+            _ => {
+                let section = sections[symbol.section as usize - 1];
+                end = section.address.saturating_add(section.size);
+            }
+        }
+        symbol.size = end.saturating_sub(symbol.value);
+        next = Some((symbol.section, symbol.value));
+    }
+    symbols
+}
+
+pub struct SymbolTable {
+    pub symbols: Vec<Symbol>,
+    /// Size of the nlist entries, 16 bytes in 64-bit files
+    entry_size: u64,
+    filter: String,
+    /// Indices of the symbols matching the filter
+    matches: Vec<usize>,
+}
+
+impl SymbolTable {
+    pub(super) fn new(symbols: Vec<Symbol>, is_64: bool) -> Self {
+        Self {
+            matches: (0..symbols.len()).collect(),
+            symbols,
+            entry_size: if is_64 { 16 } else { 12 },
+            filter: String::new(),
+        }
+    }
+
+    fn apply_filter(&mut self) {
+        let filter = self.filter.to_lowercase();
+        self.matches = self
+            .symbols
+            .iter()
+            .enumerate()
+            .filter(|(_, symbol)| {
+                symbol.name.to_lowercase().contains(&filter)
+                    || symbol
+                        .demangled
+                        .as_ref()
+                        .is_some_and(|name| name.to_lowercase().contains(&filter))
+            })
+            .map(|(index, _)| index)
+            .collect();
+    }
+}
+
+impl FileFormatUi for SymbolTable {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let title = format!("{} ({})", name, self.symbols.len());
+        ui.collapsing(title, |ui| {
+            ui.horizontal(|ui| {
+                let response =
+                    ui.add(egui::TextEdit::singleline(&mut self.filter).hint_text("Filter"));
+                if response.changed() {
+                    self.apply_filter();
+                }
+                ui.label(format!("{} shown", self.matches.len()));
+            });
+            egui_extras::TableBuilder::new(ui)
+                .striped(true)
+                .resizable(false)
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .columns(Column::auto(), 5)
+                .column(Column::remainder().clip(true))
+                .vscroll(false)
+                .auto_shrink(Vec2b::new(false, true))
+                .header(20.0, |mut header| {
+                    for title in ["Value", "Type", "Scope", "Section", "Desc", "Name"] {
+                        header.col(|ui| {
+                            ui.label(title);
+                        });
+                    }
+                })
+                .body(|body| {
+                    body.rows(20.0, self.matches.len(), |mut row| {
+                        let symbol = &self.symbols[self.matches[row.index()]];
+                        row.col(|ui| {
+                            let label = egui::Label::new(format!("{:#x}", symbol.value))
+                                .sense(egui::Sense::click());
+                            context.at(symbol.entry_offset, self.entry_size).track(
+                                &ui.add(label),
+                                &format!("symbol {}", symbol.display_name()),
+                            );
+                        });
+                        row.col(|ui| {
+                            ui.label(symbol.type_name());
+                        });
+                        row.col(|ui| {
+                            ui.label(symbol.scope_name());
+                        });
+                        row.col(|ui| match &symbol.section_name {
+                            Some(section) => {
+                                ui.label(section);
+                            }
+                            None => {
+                                ui.label(symbol.section.to_string());
+                            }
+                        });
+                        row.col(|ui| {
+                            ui.label(format!("{:#06x}", symbol.description));
+                        });
+                        row.col(|ui| {
+                            let label = egui::Label::new(symbol.display_name()).wrap(false);
+                            match symbol.file_offset {
+                                Some(offset) => {
+                                    let response = ui
+                                        .add(label.sense(egui::Sense::click()))
+                                        .on_hover_text(format!(
+                                            "{}\nClick to select its bytes at {:#x}",
+                                            symbol.name, offset
+                                        ));
+                                    if response.clicked() {
+                                        context.select(offset, symbol.size);
+                                    }
+                                }
+                                None => {
+                                    ui.add(label).on_hover_text(&symbol.name);
+                                }
+                            }
+                        });
+                    });
+                });
+        });
+    }
+}
@@ -1 +1,437 @@
+use nom::{
+    error::{Error as NomError, ErrorKind},
+    number::complete::{be_u16, be_u32, be_u64, le_u16, le_u32, le_u64},
+    IResult,
+};
+
+use super::{text_field, FileFormatUi, FormatContext};
+use crate::{error::Error, time};
+
+pub mod compression;
 pub mod elf;
+pub mod image;
+pub mod macho;
+pub mod pe;
+pub mod tar;
+pub mod zip;
+
+/// How well a file matches a format, from its probe
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    None,
+    /// The magic matches but the header around it doesn't make sense
+    Low,
+    High,
+}
+
+/// The formats we can parse
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FormatKind {
+    Elf,
+    Pe,
+    MachO,
+    Zip,
+    Png,
+    Jpeg,
+    Gif,
+    Gzip,
+    Tar,
+    Xz,
+    Zstd,
+    Bzip2,
+}
+
+impl FormatKind {
+    pub const ALL: [FormatKind; 12] = [
+        FormatKind::Elf,
+        FormatKind::Pe,
+        FormatKind::MachO,
+        FormatKind::Zip,
+        FormatKind::Png,
+        FormatKind::Jpeg,
+        FormatKind::Gif,
+        FormatKind::Gzip,
+        FormatKind::Tar,
+        FormatKind::Xz,
+        FormatKind::Zstd,
+        FormatKind::Bzip2,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FormatKind::Elf => "ELF",
+            FormatKind::Pe => "PE/COFF",
+            FormatKind::MachO => "Mach-O",
+            FormatKind::Zip => "ZIP",
+            FormatKind::Png => "PNG",
+            FormatKind::Jpeg => "JPEG",
+            FormatKind::Gif => "GIF",
+            FormatKind::Gzip => "gzip",
+            FormatKind::Tar => "tar",
+            FormatKind::Xz => "xz",
+            FormatKind::Zstd => "Zstandard",
+            FormatKind::Bzip2 => "bzip2",
+        }
+    }
+
+    /// Looks at the magic and header of the file without parsing all of it
+    pub fn probe(&self, bytes: &[u8]) -> Confidence {
+        match self {
+            FormatKind::Elf => elf::probe(bytes),
+            FormatKind::Pe => pe::probe(bytes),
+            FormatKind::MachO => macho::probe(bytes),
+            FormatKind::Zip => zip::probe(bytes),
+            FormatKind::Png => image::png::probe(bytes),
+            FormatKind::Jpeg => image::jpeg::probe(bytes),
+            FormatKind::Gif => image::gif::probe(bytes),
+            FormatKind::Gzip => compression::gzip::probe(bytes),
+            FormatKind::Tar => tar::probe(bytes),
+            FormatKind::Xz => compression::xz::probe(bytes),
+            FormatKind::Zstd => compression::zstd::probe(bytes),
+            FormatKind::Bzip2 => compression::bzip2::probe(bytes),
+        }
+    }
+
+    /// The best match for the file, the first format wins ties
+    pub fn detect(bytes: &[u8]) -> Option<FormatKind> {
+        let mut best = None;
+        let mut best_confidence = Confidence::None;
+        for kind in FormatKind::ALL {
+            let confidence = kind.probe(bytes);
+            if confidence > best_confidence {
+                best = Some(kind);
+                best_confidence = confidence;
+            }
+        }
+        best
+    }
+
+    pub fn parse(&self, bytes: &[u8]) -> Result<Box<dyn FileFormatUi>, Error> {
+        Ok(match self {
+            FormatKind::Elf => Box::new(elf::ElfFormat::new(bytes)?),
+            FormatKind::Pe => Box::new(pe::PeFormat::new(bytes)?),
+            FormatKind::MachO => Box::new(macho::MachOFormat::new(bytes)?),
+            FormatKind::Zip => Box::new(zip::ZipFormat::new(bytes)?),
+            FormatKind::Png => Box::new(image::png::PngFormat::new(bytes)?),
+            FormatKind::Jpeg => Box::new(image::jpeg::JpegFormat::new(bytes)?),
+            FormatKind::Gif => Box::new(image::gif::GifFormat::new(bytes)?),
+            FormatKind::Gzip => Box::new(compression::gzip::GzipFormat::new(bytes)?),
+            FormatKind::Tar => Box::new(tar::TarFormat::new(bytes)?),
+            FormatKind::Xz => Box::new(compression::xz::XzFormat::new(bytes)?),
+            FormatKind::Zstd => Box::new(compression::zstd::ZstdFormat::new(bytes)?),
+            FormatKind::Bzip2 => Box::new(compression::bzip2::Bzip2Format::new(bytes)?),
+        })
+    }
+
+    /// Named values of the file for expressions, empty if it doesn't parse
+    pub fn symbols(&self, bytes: &[u8]) -> Vec<(String, u64)> {
+        let symbols = match self {
+            FormatKind::Elf => elf::ElfFormat::new(bytes).map(|elf| elf.symbols()),
+            FormatKind::Pe => pe::PeFormat::new(bytes).map(|pe| pe.symbols()),
+            FormatKind::MachO => macho::MachOFormat::new(bytes).map(|mach_o| mach_o.symbols()),
+            FormatKind::Zip => zip::ZipFormat::new(bytes).map(|zip| zip.symbols()),
+            FormatKind::Png => image::png::PngFormat::new(bytes).map(|png| png.symbols()),
+            FormatKind::Jpeg => image::jpeg::JpegFormat::new(bytes).map(|jpeg| jpeg.symbols()),
+            FormatKind::Gif => image::gif::GifFormat::new(bytes).map(|gif| gif.symbols()),
+            FormatKind::Gzip => {
+                compression::gzip::GzipFormat::new(bytes).map(|gzip| gzip.symbols())
+            }
+            FormatKind::Tar => tar::TarFormat::new(bytes).map(|tar| tar.symbols()),
+            FormatKind::Xz => compression::xz::XzFormat::new(bytes).map(|xz| xz.symbols()),
+            FormatKind::Zstd => {
+                compression::zstd::ZstdFormat::new(bytes).map(|zstd| zstd.symbols())
+            }
+            FormatKind::Bzip2 => {
+                compression::bzip2::Bzip2Format::new(bytes).map(|bzip2| bzip2.symbols())
+            }
+        };
+        symbols.unwrap_or_default()
+    }
+}
+
+/// Bytes shown for files no probe recognizes
+const UNKNOWN_PREVIEW_SIZE: usize = 16;
+
+/// Shown instead of a parse for files no probe recognizes
+pub struct UnknownFormat {
+    size: u64,
+    start: Vec<u8>,
+}
+
+impl UnknownFormat {
+    pub fn new(bytes: &[u8]) -> Self {
+        Self {
+            size: bytes.len() as u64,
+            start: bytes[..bytes.len().min(UNKNOWN_PREVIEW_SIZE)].to_vec(),
+        }
+    }
+}
+
+impl FileFormatUi for UnknownFormat {
+    fn ui(&mut self, ui: &mut egui::Ui, _name: &str, context: &FormatContext) {
+        ui.label("Unknown format, choose one above to parse the file as it anyway");
+        text_field(ui, "size", &context.number(self.size));
+        let start = format!("{} ({})", hex_bytes(&self.start), self.start.escape_ascii());
+        text_field(ui, "first bytes", &start);
+    }
+}
+
+type Parser<T> = fn(&[u8]) -> IResult<&[u8], T>;
+
+/// Integer parsers for the byte order and word size of the file
+#[derive(Clone, Copy)]
+struct Readers {
+    half: Parser<u16>,
+    word: Parser<u32>,
+    xword: Parser<u64>,
+    is_64: bool,
+}
+
+impl Readers {
+    fn new(big_endian: bool, is_64: bool) -> Self {
+        // Generic nom parsers don't coerce to function pointers, closures do
+        let (half, word, xword): (Parser<_>, Parser<_>, Parser<_>) = if big_endian {
+            (|i| be_u16(i), |i| be_u32(i), |i| be_u64(i))
+        } else {
+            (|i| le_u16(i), |i| le_u32(i), |i| le_u64(i))
+        };
+        Self {
+            half,
+            word,
+            xword,
+            is_64,
+        }
+    }
+
+    /// Addresses and offsets, 4 or 8 bytes depending on the word size
+    fn address<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], u64> {
+        if self.is_64 {
+            (self.xword)(input)
+        } else {
+            let (tail, value) = (self.word)(input)?;
+            Ok((tail, value as u64))
+        }
+    }
+}
+
+/// The file from `offset` on, or an end of file error if it's past the end
+fn at(input: &[u8], offset: u64) -> Result<&[u8], nom::Err<NomError<&[u8]>>> {
+    usize::try_from(offset)
+        .ok()
+        .and_then(|offset| input.get(offset..))
+        .ok_or_else(|| nom::Err::Error(NomError::new(&input[input.len()..], ErrorKind::Eof)))
+}
+
+/// Points out bytes after the logical end of the `what`, like an image or a compressed stream.
+/// Readers ignore them, which makes them the usual place for appended archives and hidden
+/// payloads.
+fn trailing_data_ui(ui: &mut egui::Ui, context: &FormatContext, what: &str, end: u64, size: u64) {
+    if end >= size {
+        return;
+    }
+    let length = size - end;
+    ui.colored_label(
+        ui.visuals().warn_fg_color,
+        format!("⚠ {} bytes after the end of the {}", length, what),
+    );
+    ui.horizontal(|ui| {
+        context.range_link(ui, "trailing data", end, length);
+        if ui
+            .button("Open")
+            .on_hover_text("Open the trailing data as a new document")
+            .clicked()
+        {
+            context.open("trailing data", |bytes| {
+                bytes
+                    .get(end as usize..)
+                    .map(<[u8]>::to_vec)
+                    .ok_or_else(|| Error::parse("trailing data", "it is gone"))
+            });
+        }
+    });
+}
+
+/// NUL-terminated string at `offset` of a string table, empty if it's outside of the table
+fn string_at(strings: &[u8], offset: u64) -> String {
+    let tail = usize::try_from(offset)
+        .ok()
+        .and_then(|offset| strings.get(offset..))
+        .unwrap_or_default();
+    let end = tail.iter().position(|&b| b == 0).unwrap_or(tail.len());
+    String::from_utf8_lossy(&tail[..end]).into_owned()
+}
+
+/// Names of the set flags joined with `|`, with unknown bits left as a number
+pub(super) fn flag_names(
+    value: u64,
+    flags: impl IntoIterator<Item = (u64, &'static str)>,
+) -> String {
+    let mut names = Vec::new();
+    let mut unknown = value;
+    for (bit, name) in flags {
+        if value & bit != 0 {
+            names.push(name.to_string());
+            unknown &= !bit;
+        }
+    }
+    if unknown != 0 {
+        names.push(format!("{:#x}", unknown));
+    }
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join(" | ")
+    }
+}
+
+/// Bytes as space separated hex pairs
+pub(super) fn hex_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Seconds since 1970 as a UTC date, like `2021-03-04 05:06:07 UTC`
+fn unix_time(seconds: u64) -> String {
+    i64::try_from(seconds)
+        .ok()
+        .and_then(time::format_unix_time)
+        .unwrap_or_else(|| format!("{} seconds, past year 9999", seconds))
+}
+
+/// Demangles Rust symbols, then C++ ones
+fn demangle(name: &str) -> Option<String> {
+    // Legacy Rust symbols are valid C++ symbols too, so Rust goes first
+    if let Ok(demangled) = rustc_demangle::try_demangle(name) {
+        // `#` leaves out the hash at the end
+        return Some(format!("{:#}", demangled));
+    }
+    let symbol = cpp_demangle::Symbol::new(name).ok()?;
+    symbol
+        .demangle(&cpp_demangle::DemangleOptions::default())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    //! Detection runs on the fixtures of the other modules' tests, and on magics followed by
+    //! just enough of a header for the formats without one.
+
+    use super::*;
+
+    fn tar_header(name: &[u8]) -> Vec<u8> {
+        let mut block = vec![0; 512];
+        block[..name.len()].copy_from_slice(name);
+        block[257..263].copy_from_slice(b"ustar\0");
+        block
+    }
+
+    /// A file of each format, and how many bytes its probe needs to be sure
+    fn samples() -> Vec<(FormatKind, Vec<u8>, usize)> {
+        let png = [&b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR"[..], &[0; 17]].concat();
+        vec![
+            (FormatKind::Elf, b"\x7fELF\x02\x01\x01".to_vec(), 6),
+            (
+                FormatKind::Pe,
+                include_bytes!("pe/fixtures/pe32.dll").to_vec(),
+                0x84,
+            ),
+            (
+                FormatKind::MachO,
+                include_bytes!("macho/fixtures/x86_64.o").to_vec(),
+                4,
+            ),
+            (
+                FormatKind::MachO,
+                include_bytes!("macho/fixtures/fat.o").to_vec(),
+                0x1004,
+            ),
+            (
+                FormatKind::Zip,
+                include_bytes!("zip/fixtures/comment.zip").to_vec(),
+                4,
+            ),
+            (FormatKind::Png, png, 16),
+            (FormatKind::Jpeg, b"\xff\xd8\xff\xe0".to_vec(), 3),
+            (FormatKind::Gif, b"GIF89a".to_vec(), 6),
+            (
+                FormatKind::Gzip,
+                include_bytes!("compression/fixtures/two_members.gz").to_vec(),
+                4,
+            ),
+            (FormatKind::Tar, tar_header(b"file"), 512),
+            (
+                FormatKind::Xz,
+                include_bytes!("compression/fixtures/two_streams.xz").to_vec(),
+                6,
+            ),
+            (
+                FormatKind::Zstd,
+                include_bytes!("compression/fixtures/two_frames.zst").to_vec(),
+                4,
+            ),
+            (
+                FormatKind::Bzip2,
+                include_bytes!("compression/fixtures/two_streams.bz2").to_vec(),
+                10,
+            ),
+        ]
+    }
+
+    #[test]
+    fn each_format() {
+        for (kind, bytes, _) in samples() {
+            assert_eq!(kind.probe(&bytes), Confidence::High, "{}", kind.name());
+            assert_eq!(FormatKind::detect(&bytes), Some(kind), "{}", kind.name());
+        }
+        assert_eq!(FormatKind::detect(b""), None);
+        assert_eq!(FormatKind::detect(b"plain text"), None);
+    }
+
+    #[test]
+    fn shorter_than_the_magic() {
+        for (kind, bytes, needed) in samples() {
+            for length in 0..needed.min(bytes.len()) {
+                let confidence = kind.probe(&bytes[..length]);
+                assert_ne!(
+                    confidence,
+                    Confidence::High,
+                    "{} at {}",
+                    kind.name(),
+                    length
+                );
+                // Every probe copes with any prefix
+                FormatKind::detect(&bytes[..length]);
+            }
+        }
+        // A truncated magic still looks like the format, just not surely
+        assert_eq!(FormatKind::Gif.probe(b"GIF"), Confidence::Low);
+        assert_eq!(FormatKind::detect(b"\xff\xd8"), Some(FormatKind::Jpeg));
+        assert_eq!(FormatKind::detect(b"\x7fEL"), None);
+    }
+
+    #[test]
+    fn several_matches() {
+        // A self-extractor is a program first, its archive is found from the end
+        let zip = include_bytes!("zip/fixtures/comment.zip");
+        let extractor = [&include_bytes!("pe/fixtures/pe32.dll")[..], zip].concat();
+        assert_eq!(FormatKind::Zip.probe(&extractor), Confidence::Low);
+        assert_eq!(FormatKind::detect(&extractor), Some(FormatKind::Pe));
+        let image = [&b"\xff\xd8\xff\xe0"[..], zip].concat();
+        assert_eq!(FormatKind::detect(&image), Some(FormatKind::Jpeg));
+
+        // A sure match beats a doubtful one whatever the order
+        let tar = tar_header(b"\x7fELF\x03");
+        assert_eq!(FormatKind::Elf.probe(&tar), Confidence::Low);
+        assert_eq!(FormatKind::detect(&tar), Some(FormatKind::Tar));
+
+        // Ties go to the first format in the list
+        let tar = tar_header(b"\x7fELF\x01\x01");
+        assert_eq!(FormatKind::Elf.probe(&tar), Confidence::High);
+        assert_eq!(FormatKind::detect(&tar), Some(FormatKind::Elf));
+        let tar = tar_header(b"PK\x03\x04");
+        assert_eq!(FormatKind::detect(&tar), Some(FormatKind::Zip));
+    }
+}
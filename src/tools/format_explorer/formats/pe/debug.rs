@@ -0,0 +1,189 @@
+use nom::{
+    bytes::complete::{tag, take},
+    sequence::Tuple,
+};
+
+use super::{dword, headers::DataDirectory, string_at, unix_time, word, Image};
+use crate::tools::format_explorer::{text_field, FileFormatUi, FormatContext};
+
+/// Size of a debug directory entry
+const ENTRY_SIZE: usize = 28;
+
+const IMAGE_DEBUG_TYPE_CODEVIEW: u32 = 2;
+
+/// Where to find the PDB with the debug information
+pub struct CodeView {
+    pub guid: String,
+    pub age: u32,
+    pub path: String,
+}
+
+impl CodeView {
+    /// Parses the `RSDS` format used since Visual Studio .NET
+    fn parse(input: &[u8]) -> Option<Self> {
+        let (tail, (_, data1, data2, data3, data4, age)) =
+            (tag(b"RSDS"), dword, word, word, take(8usize), dword)
+                .parse(input)
+                .ok()?;
+        let guid = format!(
+            "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{}",
+            data1,
+            data2,
+            data3,
+            data4[0],
+            data4[1],
+            data4[2..]
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<String>()
+        );
+        Some(Self {
+            guid,
+            age,
+            path: string_at(tail, 0),
+        })
+    }
+}
+
+pub struct DebugEntry {
+    /// Where the entry is in the file
+    pub offset: u64,
+    pub characteristics: u32,
+    pub time_date_stamp: u32,
+    pub version: (u16, u16),
+    pub type_: u32,
+    pub data_size: u32,
+    pub data_rva: u32,
+    pub data_offset: u32,
+    pub codeview: Option<CodeView>,
+}
+
+/// Symbolic `IMAGE_DEBUG_TYPE_*` name of a debug entry type
+pub fn debug_type_name(type_: u32) -> String {
+    let name = match type_ {
+        0 => "UNKNOWN",
+        1 => "COFF",
+        IMAGE_DEBUG_TYPE_CODEVIEW => "CODEVIEW",
+        3 => "FPO",
+        4 => "MISC",
+        5 => "EXCEPTION",
+        6 => "FIXUP",
+        7 => "OMAP_TO_SRC",
+        8 => "OMAP_FROM_SRC",
+        9 => "BORLAND",
+        11 => "CLSID",
+        12 => "VC_FEATURE",
+        13 => "POGO",
+        14 => "ILTCG",
+        15 => "MPX",
+        16 => "REPRO",
+        20 => "EX_DLLCHARACTERISTICS",
+        _ => return type_.to_string(),
+    };
+    name.to_string()
+}
+
+pub(super) fn parse_debug(image: Image, directory: DataDirectory) -> Vec<DebugEntry> {
+    let (Some(entries), Some(offset)) = (
+        image.slice(directory.rva, directory.size),
+        image.offset(directory.rva),
+    ) else {
+        return Vec::new();
+    };
+    entries
+        .chunks_exact(ENTRY_SIZE)
+        .enumerate()
+        .filter_map(|(index, entry)| {
+            let (
+                _,
+                (
+                    characteristics,
+                    time_date_stamp,
+                    major_version,
+                    minor_version,
+                    type_,
+                    data_size,
+                    data_rva,
+                    data_offset,
+                ),
+            ) = (dword, dword, word, word, dword, dword, dword, dword)
+                .parse(entry)
+                .ok()?;
+            let data = image
+                .input
+                .get(data_offset as usize..)
+                .and_then(|data| data.get(..data_size as usize));
+            let codeview = data
+                .filter(|_| type_ == IMAGE_DEBUG_TYPE_CODEVIEW)
+                .and_then(CodeView::parse);
+            Some(DebugEntry {
+                offset: offset + (index * ENTRY_SIZE) as u64,
+                characteristics,
+                time_date_stamp,
+                version: (major_version, minor_version),
+                type_,
+                data_size,
+                data_rva,
+                data_offset,
+                codeview,
+            })
+        })
+        .collect()
+}
+
+impl FileFormatUi for Vec<DebugEntry> {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let title = format!("{} ({})", name, self.len());
+        ui.collapsing(title, |ui| {
+            for (index, entry) in self.iter().enumerate() {
+                let title = format!("[{}] {}", index, debug_type_name(entry.type_));
+                let entry_context = context.at(entry.offset, ENTRY_SIZE as u64);
+                entry_context.collapsing(ui, &title, |ui| {
+                    let mut fields = entry_context.fields();
+                    fields.next(4).field(
+                        ui,
+                        "characteristics",
+                        &format!("{:#x}", entry.characteristics),
+                    );
+                    fields.next(4).enum_field(
+                        ui,
+                        "time_date_stamp",
+                        entry.time_date_stamp.into(),
+                        &unix_time(entry.time_date_stamp.into()),
+                    );
+                    fields.next(4).field(
+                        ui,
+                        "version",
+                        &format!("{}.{}", entry.version.0, entry.version.1),
+                    );
+                    fields.next(4).enum_field(
+                        ui,
+                        "type",
+                        entry.type_.into(),
+                        &debug_type_name(entry.type_),
+                    );
+                    fields
+                        .next(4)
+                        .field(ui, "data_size", &format!("{:#x}", entry.data_size));
+                    fields
+                        .next(4)
+                        .field(ui, "data_rva", &format!("{:#x}", entry.data_rva));
+                    fields
+                        .next(4)
+                        .field(ui, "data_offset", &format!("{:#x}", entry.data_offset));
+                    context.range_link(
+                        ui,
+                        "data",
+                        entry.data_offset.into(),
+                        entry.data_size.into(),
+                    );
+                    if let Some(codeview) = &entry.codeview {
+                        text_field(ui, "pdb_guid", &codeview.guid);
+                        text_field(ui, "pdb_age", &codeview.age.to_string());
+                        text_field(ui, "pdb_path", &codeview.path);
+                    }
+                });
+            }
+        });
+    }
+}
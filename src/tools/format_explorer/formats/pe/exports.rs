@@ -0,0 +1,180 @@
+use egui::Vec2b;
+use egui_extras::Column;
+use nom::sequence::Tuple;
+
+use super::{dword, headers::DataDirectory, unix_time, word, Image};
+use crate::tools::format_explorer::{FileFormatUi, FormatContext};
+
+/// Size of the export directory table
+const DIRECTORY_SIZE: u64 = 40;
+
+pub struct ExportedFunction {
+    pub ordinal: u32,
+    pub rva: u32,
+    pub name: Option<String>,
+    /// `library.function` the export is forwarded to, instead of code in this file
+    pub forwarder: Option<String>,
+    pub offset: Option<u64>,
+    /// Where the function's entry in the export address table is
+    pub slot_offset: Option<u64>,
+}
+
+pub struct ExportTable {
+    /// Where the export directory is in the file
+    pub offset: Option<u64>,
+    pub name: String,
+    pub time_date_stamp: u32,
+    pub version: (u16, u16),
+    pub ordinal_base: u32,
+    pub functions: Vec<ExportedFunction>,
+}
+
+pub(super) fn parse_exports(image: Image, directory: DataDirectory) -> Option<ExportTable> {
+    let (
+        _,
+        (
+            _characteristics,
+            time_date_stamp,
+            major_version,
+            minor_version,
+            name_rva,
+            ordinal_base,
+            function_count,
+            name_count,
+            functions_rva,
+            names_rva,
+            ordinals_rva,
+        ),
+    ) = (
+        dword, dword, word, word, dword, dword, dword, dword, dword, dword, dword,
+    )
+        .parse(image.tail(directory.rva)?)
+        .ok()?;
+    // Checked before reading so bogus counts fail instead of allocating
+    let addresses = image.slice(functions_rva, function_count.checked_mul(4)?)?;
+    let addresses_offset = image.offset(functions_rva);
+    let mut functions = addresses
+        .chunks_exact(4)
+        .enumerate()
+        .map(|(index, address)| {
+            let rva = u32::from_le_bytes(address.try_into().unwrap());
+            // Forwarders point at a string inside the export directory
+            let forwarded = rva
+                .checked_sub(directory.rva)
+                .is_some_and(|relative| relative < directory.size);
+            ExportedFunction {
+                ordinal: ordinal_base.wrapping_add(index as u32),
+                rva,
+                name: None,
+                forwarder: forwarded.then(|| image.string(rva)).flatten(),
+                offset: (!forwarded).then(|| image.offset(rva)).flatten(),
+                slot_offset: addresses_offset.map(|offset| offset + index as u64 * 4),
+            }
+        })
+        .collect::<Vec<_>>();
+    let names = image
+        .slice(names_rva, name_count.checked_mul(4)?)
+        .unwrap_or_default();
+    let ordinals = image
+        .slice(ordinals_rva, name_count.checked_mul(2)?)
+        .unwrap_or_default();
+    for (name, ordinal) in names.chunks_exact(4).zip(ordinals.chunks_exact(2)) {
+        let name_rva = u32::from_le_bytes(name.try_into().unwrap());
+        let index = u16::from_le_bytes([ordinal[0], ordinal[1]]);
+        if let Some(function) = functions.get_mut(index as usize) {
+            function.name = image.string(name_rva);
+        }
+    }
+    // Unused slots of sparse ordinals are zero
+    functions.retain(|function| function.rva != 0);
+    Some(ExportTable {
+        offset: image.offset(directory.rva),
+        name: image.string(name_rva).unwrap_or_default(),
+        time_date_stamp,
+        version: (major_version, minor_version),
+        ordinal_base,
+        functions,
+    })
+}
+
+impl FileFormatUi for ExportTable {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let title = format!("{} ({})", name, self.functions.len());
+        let directory = match self.offset {
+            Some(offset) => context.at(offset, DIRECTORY_SIZE),
+            None => *context,
+        };
+        directory.collapsing(ui, &title, |ui| {
+            let mut fields = directory.fields();
+            fields.skip(4);
+            fields.next(4).enum_field(
+                ui,
+                "time_date_stamp",
+                self.time_date_stamp.into(),
+                &unix_time(self.time_date_stamp.into()),
+            );
+            fields.next(4).field(
+                ui,
+                "version",
+                &format!("{}.{}", self.version.0, self.version.1),
+            );
+            fields.next(4).field(ui, "name", &self.name);
+            self.ordinal_base.ui(ui, "ordinal_base", &fields.next(4));
+            egui_extras::TableBuilder::new(ui)
+                .striped(true)
+                .resizable(false)
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .columns(Column::auto(), 2)
+                .column(Column::remainder().clip(true))
+                .vscroll(false)
+                .auto_shrink(Vec2b::new(false, true))
+                .header(20.0, |mut header| {
+                    for title in ["Ordinal", "RVA", "Name"] {
+                        header.col(|ui| {
+                            ui.label(title);
+                        });
+                    }
+                })
+                .body(|body| {
+                    body.rows(20.0, self.functions.len(), |mut row| {
+                        let function = &self.functions[row.index()];
+                        row.col(|ui| {
+                            let label = egui::Label::new(function.ordinal.to_string())
+                                .sense(egui::Sense::click());
+                            let response = ui.add(label);
+                            if let Some(offset) = function.slot_offset {
+                                context.at(offset, 4).track(
+                                    &response,
+                                    &format!("export address of ordinal {}", function.ordinal),
+                                );
+                            }
+                        });
+                        row.col(|ui| {
+                            ui.label(format!("{:#x}", function.rva));
+                        });
+                        row.col(|ui| {
+                            let mut name = function.name.clone().unwrap_or_default();
+                            if let Some(forwarder) = &function.forwarder {
+                                name = format!("{} → {}", name, forwarder);
+                            }
+                            let label = egui::Label::new(name).wrap(false);
+                            match function.offset {
+                                Some(offset) => {
+                                    let response =
+                                        ui.add(label.sense(egui::Sense::click())).on_hover_text(
+                                            format!("Click to select the code at {:#x}", offset),
+                                        );
+                                    if response.clicked() {
+                                        context.select(offset, 1);
+                                    }
+                                }
+                                None => {
+                                    ui.add(label);
+                                }
+                            }
+                        });
+                    });
+                });
+        });
+    }
+}
@@ -0,0 +1,577 @@
+use egui::Vec2b;
+use egui_extras::Column;
+use nom::{
+    error::{Error as NomError, ErrorKind},
+    number::complete::{le_u64, u8},
+    sequence::Tuple,
+    IResult,
+};
+
+use super::{dword, flag_names, hex_bytes, unix_time, word};
+use crate::tools::format_explorer::{FileFormat, FileFormatUi, FormatContext, Layout};
+
+pub(super) const DOS_MAGIC: [u8; 2] = *b"MZ";
+
+const PE32_MAGIC: u16 = 0x10b;
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+
+#[derive(FileFormat)]
+#[format(little_endian)]
+pub struct DosHeader {
+    #[format(display = magic_text)]
+    pub magic: [u8; 2],
+    pub last_page_size: u16,
+    pub page_count: u16,
+    pub relocation_count: u16,
+    pub header_paragraphs: u16,
+    pub min_alloc: u16,
+    pub max_alloc: u16,
+    pub initial_ss: u16,
+    pub initial_sp: u16,
+    pub checksum: u16,
+    pub initial_ip: u16,
+    pub initial_cs: u16,
+    pub relocation_table_offset: u16,
+    pub overlay: u16,
+    #[format(hidden)]
+    pub reserved: [u16; 4],
+    pub oem_id: u16,
+    pub oem_info: u16,
+    #[format(hidden)]
+    pub reserved2: [u16; 10],
+    /// `e_lfanew`, where the NT headers start
+    #[format(hex)]
+    pub new_header_offset: u32,
+}
+
+/// Magic bytes with their ASCII, like `4d 5a (MZ)`
+fn magic_text(magic: &[u8; 2]) -> String {
+    format!("{} ({})", hex_bytes(magic), magic.escape_ascii())
+}
+
+/// "DanS" and "Rich", the markers around the Rich header
+const RICH_START: u32 = 0x536e_6144;
+const RICH_END: &[u8] = b"Rich";
+
+pub struct RichEntry {
+    pub product: u16,
+    pub build: u16,
+    pub count: u32,
+}
+
+/// Undocumented list of the tools that built the file, left in the DOS stub by Microsoft's
+/// linker and XORed with a checksum
+pub struct RichHeader {
+    pub offset: u64,
+    pub size: u64,
+    pub key: u32,
+    pub entries: Vec<RichEntry>,
+    /// Whether the key matches the checksum of the DOS header and the entries
+    pub checksum_valid: bool,
+}
+
+impl RichHeader {
+    /// Looks for the header in the bytes before the NT headers
+    pub(super) fn find(input: &[u8], stub_end: usize) -> Option<Self> {
+        let stub = input.get(..stub_end)?;
+        let end = (DosHeader::SIZE as usize..stub.len().saturating_sub(7))
+            .step_by(4)
+            .find(|&position| &stub[position..position + 4] == RICH_END)?;
+        let dword =
+            |position: usize| u32::from_le_bytes(stub[position..position + 4].try_into().unwrap());
+        let key = dword(end + 4);
+        let start = (DosHeader::SIZE as usize..end)
+            .step_by(4)
+            .rev()
+            .find(|&position| dword(position) ^ key == RICH_START)?;
+        // Three zero dwords of padding follow the start marker
+        let entries = (start + 16..end)
+            .step_by(8)
+            .filter(|&position| position + 8 <= end)
+            .map(|position| {
+                let id = dword(position) ^ key;
+                RichEntry {
+                    product: (id >> 16) as u16,
+                    build: id as u16,
+                    count: dword(position + 4) ^ key,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Sum of the DOS header without `e_lfanew` and of the entries, each rotated
+        let mut checksum = start as u32;
+        for (index, &byte) in stub[..start].iter().enumerate() {
+            if !(0x3c..0x40).contains(&index) {
+                checksum = checksum.wrapping_add((byte as u32).rotate_left(index as u32));
+            }
+        }
+        for entry in &entries {
+            let id = (entry.product as u32) << 16 | entry.build as u32;
+            checksum = checksum.wrapping_add(id.rotate_left(entry.count));
+        }
+        Some(Self {
+            offset: start as u64,
+            size: (end + 8 - start) as u64,
+            key,
+            entries,
+            checksum_valid: checksum == key,
+        })
+    }
+}
+
+impl FileFormatUi for RichHeader {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let title = format!("{} ({})", name, self.entries.len());
+        let header = context.at(self.offset, self.size);
+        header.collapsing(ui, &title, |ui| {
+            context.range_link(ui, "bytes", self.offset, self.size);
+            let validity = if self.checksum_valid {
+                "valid"
+            } else {
+                "doesn't match the checksum"
+            };
+            // The key follows "Rich" at the end
+            context.at(self.offset + self.size - 4, 4).field(
+                ui,
+                "key",
+                &format!("{:#010x} ({})", self.key, validity),
+            );
+            // Entries follow "DanS" and three padding words
+            let entries = context.at(self.offset + 16, 8 * self.entries.len() as u64);
+            for (index, entry) in self.entries.iter().enumerate() {
+                entries.entry(index, 8).field(
+                    ui,
+                    &format!("product {}", entry.product),
+                    &format!("build {}, used {} times", entry.build, entry.count),
+                );
+            }
+        });
+    }
+}
+
+#[derive(FileFormat)]
+#[format(little_endian)]
+pub struct FileHeader {
+    #[format(meaning = machine_name)]
+    pub machine: u16,
+    pub section_count: u16,
+    #[format(meaning = unix_time)]
+    pub time_date_stamp: u32,
+    #[format(hex)]
+    pub symbol_table_offset: u32,
+    pub symbol_count: u32,
+    pub optional_header_size: u16,
+    #[format(flags = FILE_CHARACTERISTICS)]
+    pub characteristics: u16,
+}
+
+pub fn machine_name(machine: u16) -> String {
+    let name = match machine {
+        0x0 => "IMAGE_FILE_MACHINE_UNKNOWN",
+        0x14c => "IMAGE_FILE_MACHINE_I386",
+        0x166 => "IMAGE_FILE_MACHINE_R4000",
+        0x1a2 => "IMAGE_FILE_MACHINE_SH3",
+        0x1a6 => "IMAGE_FILE_MACHINE_SH4",
+        0x1c0 => "IMAGE_FILE_MACHINE_ARM",
+        0x1c2 => "IMAGE_FILE_MACHINE_THUMB",
+        0x1c4 => "IMAGE_FILE_MACHINE_ARMNT",
+        0x1f0 => "IMAGE_FILE_MACHINE_POWERPC",
+        0x200 => "IMAGE_FILE_MACHINE_IA64",
+        0x266 => "IMAGE_FILE_MACHINE_MIPS16",
+        0xebc => "IMAGE_FILE_MACHINE_EBC",
+        0x5032 => "IMAGE_FILE_MACHINE_RISCV32",
+        0x5064 => "IMAGE_FILE_MACHINE_RISCV64",
+        0x6232 => "IMAGE_FILE_MACHINE_LOONGARCH32",
+        0x6264 => "IMAGE_FILE_MACHINE_LOONGARCH64",
+        0x8664 => "IMAGE_FILE_MACHINE_AMD64",
+        0xa641 => "IMAGE_FILE_MACHINE_ARM64EC",
+        0xaa64 => "IMAGE_FILE_MACHINE_ARM64",
+        _ => "unknown",
+    };
+    name.to_string()
+}
+
+const FILE_CHARACTERISTICS: [(u64, &str); 15] = [
+    (0x1, "RELOCS_STRIPPED"),
+    (0x2, "EXECUTABLE_IMAGE"),
+    (0x4, "LINE_NUMS_STRIPPED"),
+    (0x8, "LOCAL_SYMS_STRIPPED"),
+    (0x10, "AGGRESSIVE_WS_TRIM"),
+    (0x20, "LARGE_ADDRESS_AWARE"),
+    (0x80, "BYTES_REVERSED_LO"),
+    (0x100, "32BIT_MACHINE"),
+    (0x200, "DEBUG_STRIPPED"),
+    (0x400, "REMOVABLE_RUN_FROM_SWAP"),
+    (0x800, "NET_RUN_FROM_SWAP"),
+    (0x1000, "SYSTEM"),
+    (0x2000, "DLL"),
+    (0x4000, "UP_SYSTEM_ONLY"),
+    (0x8000, "BYTES_REVERSED_HI"),
+];
+
+const DLL_CHARACTERISTICS: [(u64, &str); 11] = [
+    (0x20, "HIGH_ENTROPY_VA"),
+    (0x40, "DYNAMIC_BASE"),
+    (0x80, "FORCE_INTEGRITY"),
+    (0x100, "NX_COMPAT"),
+    (0x200, "NO_ISOLATION"),
+    (0x400, "NO_SEH"),
+    (0x800, "NO_BIND"),
+    (0x1000, "APPCONTAINER"),
+    (0x2000, "WDM_DRIVER"),
+    (0x4000, "GUARD_CF"),
+    (0x8000, "TERMINAL_SERVER_AWARE"),
+];
+
+pub fn subsystem_name(subsystem: u16) -> String {
+    let name = match subsystem {
+        0 => "IMAGE_SUBSYSTEM_UNKNOWN",
+        1 => "IMAGE_SUBSYSTEM_NATIVE",
+        2 => "IMAGE_SUBSYSTEM_WINDOWS_GUI",
+        3 => "IMAGE_SUBSYSTEM_WINDOWS_CUI",
+        5 => "IMAGE_SUBSYSTEM_OS2_CUI",
+        7 => "IMAGE_SUBSYSTEM_POSIX_CUI",
+        8 => "IMAGE_SUBSYSTEM_NATIVE_WINDOWS",
+        9 => "IMAGE_SUBSYSTEM_WINDOWS_CE_GUI",
+        10 => "IMAGE_SUBSYSTEM_EFI_APPLICATION",
+        11 => "IMAGE_SUBSYSTEM_EFI_BOOT_SERVICE_DRIVER",
+        12 => "IMAGE_SUBSYSTEM_EFI_RUNTIME_DRIVER",
+        13 => "IMAGE_SUBSYSTEM_EFI_ROM",
+        14 => "IMAGE_SUBSYSTEM_XBOX",
+        16 => "IMAGE_SUBSYSTEM_WINDOWS_BOOT_APPLICATION",
+        _ => "unknown",
+    };
+    name.to_string()
+}
+
+/// Names of the data directories, by index
+const DIRECTORY_NAMES: [&str; 16] = [
+    "export",
+    "import",
+    "resource",
+    "exception",
+    "certificate",
+    "base_relocation",
+    "debug",
+    "architecture",
+    "global_pointer",
+    "tls",
+    "load_config",
+    "bound_import",
+    "import_address_table",
+    "delay_import",
+    "clr_runtime",
+    "reserved",
+];
+
+/// Index of the certificate table, the only directory with a file offset instead of an RVA
+pub(super) const CERTIFICATE_DIRECTORY: usize = 4;
+
+#[derive(Clone, Copy)]
+pub struct DataDirectory {
+    pub rva: u32,
+    pub size: u32,
+    /// Where the directory is in the file, if it is stored in it
+    pub offset: Option<u64>,
+}
+
+pub struct OptionalHeader {
+    pub magic: u16,
+    pub linker_version: (u8, u8),
+    pub code_size: u32,
+    pub initialized_data_size: u32,
+    pub uninitialized_data_size: u32,
+    pub entry_point: u32,
+    pub code_base: u32,
+    /// Only in PE32 files
+    pub data_base: Option<u32>,
+    pub image_base: u64,
+    pub section_alignment: u32,
+    pub file_alignment: u32,
+    pub os_version: (u16, u16),
+    pub image_version: (u16, u16),
+    pub subsystem_version: (u16, u16),
+    pub win32_version: u32,
+    pub image_size: u32,
+    pub headers_size: u32,
+    pub checksum: u32,
+    pub subsystem: u16,
+    pub dll_characteristics: u16,
+    pub stack_reserve: u64,
+    pub stack_commit: u64,
+    pub heap_reserve: u64,
+    pub heap_commit: u64,
+    pub loader_flags: u32,
+    pub directory_count: u32,
+    pub directories: Vec<DataDirectory>,
+}
+
+impl OptionalHeader {
+    /// PE32+, with 64-bit addresses
+    pub fn is_64(&self) -> bool {
+        self.magic == PE32_PLUS_MAGIC
+    }
+
+    pub(super) fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (tail, magic) = word(input)?;
+        let is_64 = match magic {
+            PE32_MAGIC => false,
+            PE32_PLUS_MAGIC => true,
+            _ => return Err(nom::Err::Failure(NomError::new(input, ErrorKind::Switch))),
+        };
+        // Fields that are 8 bytes long in PE32+
+        let address = |input| {
+            if is_64 {
+                le_u64(input)
+            } else {
+                dword(input).map(|(tail, value)| (tail, value as u64))
+            }
+        };
+        let (
+            tail,
+            (
+                linker_major,
+                linker_minor,
+                code_size,
+                initialized_data_size,
+                uninitialized_data_size,
+                entry_point,
+                code_base,
+            ),
+        ) = (u8, u8, dword, dword, dword, dword, dword).parse(tail)?;
+        let (tail, data_base) = if is_64 {
+            (tail, None)
+        } else {
+            dword(tail).map(|(tail, value)| (tail, Some(value)))?
+        };
+        let (
+            tail,
+            (
+                image_base,
+                section_alignment,
+                file_alignment,
+                os_major,
+                os_minor,
+                image_major,
+                image_minor,
+                subsystem_major,
+                subsystem_minor,
+                win32_version,
+                image_size,
+                headers_size,
+                checksum,
+                subsystem,
+                dll_characteristics,
+            ),
+        ) = (
+            address, dword, dword, word, word, word, word, word, word, dword, dword, dword, dword,
+            word, word,
+        )
+            .parse(tail)?;
+        let (
+            tail,
+            (stack_reserve, stack_commit, heap_reserve, heap_commit, loader_flags, directory_count),
+        ) = (address, address, address, address, dword, dword).parse(tail)?;
+        // The count can't be trusted, the directories end with the optional header
+        let mut directories = Vec::new();
+        let mut rest = tail;
+        for _ in 0..directory_count.min(DIRECTORY_NAMES.len() as u32) {
+            let Ok((tail, (rva, size))) = (dword, dword).parse(rest) else {
+                break;
+            };
+            directories.push(DataDirectory {
+                rva,
+                size,
+                offset: None,
+            });
+            rest = tail;
+        }
+        Ok((
+            rest,
+            Self {
+                magic,
+                linker_version: (linker_major, linker_minor),
+                code_size,
+                initialized_data_size,
+                uninitialized_data_size,
+                entry_point,
+                code_base,
+                data_base,
+                image_base,
+                section_alignment,
+                file_alignment,
+                os_version: (os_major, os_minor),
+                image_version: (image_major, image_minor),
+                subsystem_version: (subsystem_major, subsystem_minor),
+                win32_version,
+                image_size,
+                headers_size,
+                checksum,
+                subsystem,
+                dll_characteristics,
+                stack_reserve,
+                stack_commit,
+                heap_reserve,
+                heap_commit,
+                loader_flags,
+                directory_count,
+                directories,
+            },
+        ))
+    }
+
+    /// The directory at `index`, if the file has one there
+    pub(super) fn directory(&self, index: usize) -> Option<DataDirectory> {
+        self.directories
+            .get(index)
+            .copied()
+            .filter(|directory| directory.rva != 0 && directory.size != 0)
+    }
+
+    /// `context` is tied to the directories at the end of the header
+    fn directories_ui(&self, ui: &mut egui::Ui, context: &FormatContext) {
+        let title = format!("data_directories ({})", self.directories.len());
+        context.collapsing(ui, &title, |ui| {
+            egui_extras::TableBuilder::new(ui)
+                .striped(true)
+                .resizable(false)
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .columns(Column::auto(), 4)
+                .vscroll(false)
+                .auto_shrink(Vec2b::new(false, true))
+                .header(20.0, |mut header| {
+                    for title in ["Name", "RVA", "Size", "Offset"] {
+                        header.col(|ui| {
+                            ui.label(title);
+                        });
+                    }
+                })
+                .body(|body| {
+                    body.rows(20.0, self.directories.len(), |mut row| {
+                        let index = row.index();
+                        let directory = self.directories[index];
+                        row.col(|ui| {
+                            let label = egui::Label::new(DIRECTORY_NAMES[index])
+                                .sense(egui::Sense::click());
+                            context.entry(index, 8).track(
+                                &ui.add(label),
+                                &format!("{} directory", DIRECTORY_NAMES[index]),
+                            );
+                        });
+                        row.col(|ui| {
+                            ui.label(format!("{:#x}", directory.rva));
+                        });
+                        row.col(|ui| {
+                            ui.label(format!("{:#x}", directory.size));
+                        });
+                        row.col(|ui| match directory.offset {
+                            Some(offset) => {
+                                if ui
+                                    .link(format!("{:#x}", offset))
+                                    .on_hover_text("Select the directory's bytes")
+                                    .clicked()
+                                {
+                                    context.select(offset, directory.size.into());
+                                }
+                            }
+                            None => {
+                                ui.weak("—");
+                            }
+                        });
+                    });
+                });
+        });
+    }
+}
+
+impl FileFormatUi for OptionalHeader {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let version = |(major, minor): (u16, u16)| format!("{}.{}", major, minor);
+        context.collapsing(ui, name, |ui| {
+            let mut fields = context.fields();
+            let address_size = if self.is_64() { 8 } else { 4 };
+            let kind = if self.is_64() { "PE32+" } else { "PE32" };
+            fields
+                .next(2)
+                .enum_field(ui, "magic", self.magic.into(), kind);
+            let (major, minor) = self.linker_version;
+            fields
+                .next(2)
+                .field(ui, "linker_version", &version((major.into(), minor.into())));
+            self.code_size.ui(ui, "code_size", &fields.next(4));
+            self.initialized_data_size
+                .ui(ui, "initialized_data_size", &fields.next(4));
+            self.uninitialized_data_size
+                .ui(ui, "uninitialized_data_size", &fields.next(4));
+            fields
+                .next(4)
+                .field(ui, "entry_point", &format!("{:#x}", self.entry_point));
+            fields
+                .next(4)
+                .field(ui, "code_base", &format!("{:#x}", self.code_base));
+            if let Some(data_base) = self.data_base {
+                fields
+                    .next(4)
+                    .field(ui, "data_base", &format!("{:#x}", data_base));
+            }
+            fields
+                .next(address_size)
+                .field(ui, "image_base", &format!("{:#x}", self.image_base));
+            fields.next(4).field(
+                ui,
+                "section_alignment",
+                &format!("{:#x}", self.section_alignment),
+            );
+            fields
+                .next(4)
+                .field(ui, "file_alignment", &format!("{:#x}", self.file_alignment));
+            fields
+                .next(4)
+                .field(ui, "os_version", &version(self.os_version));
+            fields
+                .next(4)
+                .field(ui, "image_version", &version(self.image_version));
+            fields
+                .next(4)
+                .field(ui, "subsystem_version", &version(self.subsystem_version));
+            self.win32_version.ui(ui, "win32_version", &fields.next(4));
+            fields
+                .next(4)
+                .field(ui, "image_size", &format!("{:#x}", self.image_size));
+            fields
+                .next(4)
+                .field(ui, "headers_size", &format!("{:#x}", self.headers_size));
+            fields
+                .next(4)
+                .field(ui, "checksum", &format!("{:#x}", self.checksum));
+            fields.next(2).enum_field(
+                ui,
+                "subsystem",
+                self.subsystem.into(),
+                &subsystem_name(self.subsystem),
+            );
+            let characteristics = flag_names(self.dll_characteristics.into(), DLL_CHARACTERISTICS);
+            fields.next(2).enum_field(
+                ui,
+                "dll_characteristics",
+                self.dll_characteristics.into(),
+                &characteristics,
+            );
+            for (name, value) in [
+                ("stack_reserve", self.stack_reserve),
+                ("stack_commit", self.stack_commit),
+                ("heap_reserve", self.heap_reserve),
+                ("heap_commit", self.heap_commit),
+            ] {
+                fields
+                    .next(address_size)
+                    .field(ui, name, &format!("{:#x}", value));
+            }
+            self.loader_flags.ui(ui, "loader_flags", &fields.next(4));
+            self.directory_count
+                .ui(ui, "directory_count", &fields.next(4));
+            let directories = fields.next(8 * self.directories.len() as u64);
+            self.directories_ui(ui, &directories);
+        });
+    }
+}
@@ -0,0 +1,188 @@
+use egui::Vec2b;
+use egui_extras::Column;
+use nom::sequence::Tuple;
+
+use super::{dword, headers::DataDirectory, Image};
+use crate::tools::format_explorer::{FileFormatUi, FormatContext};
+
+/// Size of an import descriptor
+const DESCRIPTOR_SIZE: usize = 20;
+
+/// Limits for broken tables without a terminating entry
+const MAX_LIBRARIES: usize = 4096;
+const MAX_FUNCTIONS: usize = 65536;
+
+pub struct ImportedFunction {
+    /// `None` for imports by ordinal
+    pub name: Option<String>,
+    pub hint: u16,
+    pub ordinal: Option<u16>,
+    /// Address of the import address table slot the loader fills in
+    pub slot_rva: u32,
+}
+
+pub struct ImportedLibrary {
+    pub name: String,
+    pub descriptor_offset: u64,
+    pub time_date_stamp: u32,
+    pub forwarder_chain: u32,
+    pub lookup_table_rva: u32,
+    pub address_table_rva: u32,
+    pub functions: Vec<ImportedFunction>,
+}
+
+/// Reads the names or ordinals of the functions imported from one library
+fn parse_functions(
+    image: Image,
+    lookup_table_rva: u32,
+    address_table_rva: u32,
+) -> Vec<ImportedFunction> {
+    let Some(mut table) = image.tail(lookup_table_rva) else {
+        return Vec::new();
+    };
+    let (entry_size, ordinal_flag) = if image.is_64 {
+        (8, 1 << 63)
+    } else {
+        (4, 1 << 31)
+    };
+    let mut functions = Vec::new();
+    while functions.len() < MAX_FUNCTIONS {
+        let Ok((tail, entry)) = image.address(table) else {
+            break;
+        };
+        if entry == 0 {
+            break;
+        }
+        let slot_rva = address_table_rva.wrapping_add((functions.len() * entry_size) as u32);
+        let function = if entry & ordinal_flag != 0 {
+            ImportedFunction {
+                name: None,
+                hint: 0,
+                ordinal: Some(entry as u16),
+                slot_rva,
+            }
+        } else {
+            // The hint is an index into the library's export names, the name follows it
+            let hint_rva = entry as u32;
+            let hint = image
+                .slice(hint_rva, 2)
+                .map_or(0, |hint| u16::from_le_bytes([hint[0], hint[1]]));
+            ImportedFunction {
+                name: image.string(hint_rva.wrapping_add(2)),
+                hint,
+                ordinal: None,
+                slot_rva,
+            }
+        };
+        functions.push(function);
+        table = tail;
+    }
+    functions
+}
+
+pub(super) fn parse_imports(image: Image, directory: DataDirectory) -> Vec<ImportedLibrary> {
+    let Some(descriptors) = image.tail(directory.rva) else {
+        return Vec::new();
+    };
+    let descriptors_offset = image.offset(directory.rva).unwrap_or_default();
+    let mut libraries = Vec::new();
+    for (index, descriptor) in descriptors
+        .chunks_exact(DESCRIPTOR_SIZE)
+        .take(MAX_LIBRARIES)
+        .enumerate()
+    {
+        let Ok((
+            _,
+            (lookup_table_rva, time_date_stamp, forwarder_chain, name_rva, address_table_rva),
+        )) = (dword, dword, dword, dword, dword).parse(descriptor)
+        else {
+            break;
+        };
+        // The table ends with an all zero descriptor
+        if lookup_table_rva == 0 && name_rva == 0 && address_table_rva == 0 {
+            break;
+        }
+        // Some linkers leave out the lookup table, the address table has the same entries
+        // until the file is loaded
+        let table_rva = if lookup_table_rva != 0 {
+            lookup_table_rva
+        } else {
+            address_table_rva
+        };
+        libraries.push(ImportedLibrary {
+            name: image.string(name_rva).unwrap_or_default(),
+            descriptor_offset: descriptors_offset + (index * DESCRIPTOR_SIZE) as u64,
+            time_date_stamp,
+            forwarder_chain,
+            lookup_table_rva,
+            address_table_rva,
+            functions: parse_functions(image, table_rva, address_table_rva),
+        });
+    }
+    libraries
+}
+
+impl FileFormatUi for ImportedLibrary {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let title = format!("{} ({})", name, self.functions.len());
+        let descriptor = context.at(self.descriptor_offset, DESCRIPTOR_SIZE as u64);
+        descriptor.collapsing(ui, &title, |ui| {
+            context.range_link(
+                ui,
+                "descriptor",
+                self.descriptor_offset,
+                DESCRIPTOR_SIZE as u64,
+            );
+            let mut fields = descriptor.fields();
+            fields.next(4).field(
+                ui,
+                "lookup_table_rva",
+                &format!("{:#x}", self.lookup_table_rva),
+            );
+            self.time_date_stamp
+                .ui(ui, "time_date_stamp", &fields.next(4));
+            self.forwarder_chain
+                .ui(ui, "forwarder_chain", &fields.next(4));
+            fields.next(4).field(ui, "name", &self.name);
+            fields.next(4).field(
+                ui,
+                "address_table_rva",
+                &format!("{:#x}", self.address_table_rva),
+            );
+            egui_extras::TableBuilder::new(ui)
+                .striped(true)
+                .resizable(false)
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .columns(Column::auto(), 2)
+                .column(Column::remainder().clip(true))
+                .vscroll(false)
+                .auto_shrink(Vec2b::new(false, true))
+                .header(20.0, |mut header| {
+                    for title in ["Slot RVA", "Hint", "Name"] {
+                        header.col(|ui| {
+                            ui.label(title);
+                        });
+                    }
+                })
+                .body(|body| {
+                    body.rows(20.0, self.functions.len(), |mut row| {
+                        let function = &self.functions[row.index()];
+                        row.col(|ui| {
+                            ui.label(format!("{:#x}", function.slot_rva));
+                        });
+                        row.col(|ui| {
+                            ui.label(function.hint.to_string());
+                        });
+                        row.col(|ui| {
+                            let name = match (&function.name, function.ordinal) {
+                                (_, Some(ordinal)) => format!("ordinal {}", ordinal),
+                                (Some(name), None) => name.clone(),
+                                (None, None) => "?".to_string(),
+                            };
+                            ui.add(egui::Label::new(name).wrap(false));
+                        });
+                    });
+                });
+        });
+    }
+}
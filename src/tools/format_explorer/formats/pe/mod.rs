@@ -0,0 +1,536 @@
+use nom::{
+    bytes::complete::{tag, take},
+    multi::count,
+    number::complete::{le_u16, le_u32, le_u64},
+    IResult,
+};
+
+use super::{at, flag_names, hex_bytes, string_at, unix_time, Confidence};
+use crate::{
+    error::Error,
+    tools::format_explorer::{text_field, FileFormatUi, FormatContext, Layout},
+};
+
+mod debug;
+mod exports;
+mod headers;
+mod imports;
+mod relocations;
+mod resources;
+mod sections;
+mod tls;
+
+pub use debug::DebugEntry;
+pub use exports::ExportTable;
+pub use headers::{DosHeader, FileHeader, OptionalHeader, RichHeader};
+pub use imports::ImportedLibrary;
+pub use relocations::RelocationBlock;
+pub use resources::ResourceEntry;
+pub use sections::{SectionHeader, SectionTable};
+pub use tls::TlsDirectory;
+
+/// Indices of the data directories we parse
+const EXPORT_DIRECTORY: usize = 0;
+const IMPORT_DIRECTORY: usize = 1;
+const RESOURCE_DIRECTORY: usize = 2;
+const BASE_RELOCATION_DIRECTORY: usize = 5;
+const DEBUG_DIRECTORY: usize = 6;
+const TLS_DIRECTORY: usize = 9;
+
+/// Size of a COFF symbol table entry, the string table follows the symbols
+const COFF_SYMBOL_SIZE: u64 = 18;
+
+/// `WORD` and `DWORD` of the specification, with the error type spelled out so tuples of them
+/// don't need annotations
+fn word(input: &[u8]) -> IResult<&[u8], u16> {
+    le_u16(input)
+}
+
+fn dword(input: &[u8]) -> IResult<&[u8], u32> {
+    le_u32(input)
+}
+
+/// Checks the DOS magic and that `e_lfanew` points at the PE signature
+pub fn probe(bytes: &[u8]) -> Confidence {
+    if !bytes.starts_with(&headers::DOS_MAGIC) {
+        return Confidence::None;
+    }
+    let signature = DosHeader::parse(bytes, false)
+        .ok()
+        .and_then(|(_, header)| at(bytes, header.new_header_offset.into()).ok());
+    if signature.is_some_and(|signature| signature.starts_with(b"PE\0\0")) {
+        Confidence::High
+    } else {
+        // Plain DOS programs, or PE files with a broken DOS header
+        Confidence::Low
+    }
+}
+
+/// Maps relative virtual addresses to the file through the section table
+#[derive(Clone, Copy)]
+struct Image<'a> {
+    input: &'a [u8],
+    sections: &'a [SectionHeader],
+    headers_size: u32,
+    image_base: u64,
+    is_64: bool,
+}
+
+impl<'a> Image<'a> {
+    /// Where the byte at `rva` is stored in the file
+    fn offset(&self, rva: u32) -> Option<u64> {
+        if rva < self.headers_size {
+            return Some(rva.into());
+        }
+        self.sections.iter().find_map(|section| {
+            let relative = rva.checked_sub(section.virtual_address)?;
+            (relative < section.raw_size).then(|| section.raw_offset as u64 + relative as u64)
+        })
+    }
+
+    /// The file from `rva` to its end
+    fn tail(&self, rva: u32) -> Option<&'a [u8]> {
+        self.input.get(usize::try_from(self.offset(rva)?).ok()?..)
+    }
+
+    fn slice(&self, rva: u32, size: u32) -> Option<&'a [u8]> {
+        self.tail(rva)?.get(..size as usize)
+    }
+
+    /// NUL-terminated string at `rva`
+    fn string(&self, rva: u32) -> Option<String> {
+        Some(string_at(self.tail(rva)?, 0))
+    }
+
+    /// Converts an absolute virtual address, like the ones in the TLS directory, to an RVA
+    fn rva(&self, address: u64) -> Option<u32> {
+        u32::try_from(address.checked_sub(self.image_base)?).ok()
+    }
+
+    /// Pointer-sized values, 8 bytes in PE32+ files
+    fn address<'b>(&self, input: &'b [u8]) -> IResult<&'b [u8], u64> {
+        if self.is_64 {
+            le_u64(input)
+        } else {
+            let (tail, value) = dword(input)?;
+            Ok((tail, value as u64))
+        }
+    }
+}
+
+pub struct PeFormat {
+    pub dos_header: DosHeader,
+    /// DOS program that runs instead, usually just printing a message
+    pub stub_size: u64,
+    pub stub_message: Option<String>,
+    pub rich_header: Option<RichHeader>,
+    pub file_header: FileHeader,
+    /// Missing in object files
+    pub optional_header: Option<OptionalHeader>,
+    pub sections: SectionTable,
+    pub imports: Vec<ImportedLibrary>,
+    pub exports: Option<ExportTable>,
+    pub resources: Vec<ResourceEntry>,
+    pub relocations: Vec<RelocationBlock>,
+    pub tls: Option<TlsDirectory>,
+    pub debug: Vec<DebugEntry>,
+    /// Header values that are out of spec
+    pub warnings: Vec<String>,
+}
+
+impl FileFormatUi for PeFormat {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        ui.collapsing(name, |ui| {
+            for warning in &self.warnings {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+            let dos_header = context.at(0, DosHeader::SIZE);
+            self.dos_header.ui(ui, "dos_header", &dos_header);
+            ui.collapsing("dos_stub", |ui| {
+                context.range_link(ui, "bytes", DosHeader::SIZE, self.stub_size);
+                if let Some(message) = &self.stub_message {
+                    text_field(ui, "message", &format!("{:?}", message));
+                }
+            });
+            if let Some(rich_header) = &mut self.rich_header {
+                rich_header.ui(ui, "rich_header", context);
+            }
+            // The headers follow the "PE\0\0" signature
+            let mut headers = context
+                .at(self.dos_header.new_header_offset as u64 + 4, 0)
+                .fields();
+            let file_header = headers.next(FileHeader::SIZE);
+            self.file_header.ui(ui, "file_header", &file_header);
+            let optional_header = headers.next(self.file_header.optional_header_size.into());
+            if let Some(header) = &mut self.optional_header {
+                header.ui(ui, "optional_header", &optional_header);
+            }
+            let size = SectionHeader::SIZE * self.sections.headers.len() as u64;
+            self.sections.ui(ui, "section_headers", &headers.next(size));
+            let title = format!("imports ({})", self.imports.len());
+            ui.collapsing(title, |ui| {
+                for (index, library) in self.imports.iter_mut().enumerate() {
+                    let name = library.name.clone();
+                    ui.push_id(index, |ui| library.ui(ui, &name, context));
+                }
+            });
+            if let Some(exports) = &mut self.exports {
+                exports.ui(ui, "exports", context);
+            }
+            self.resources.ui(ui, "resources", context);
+            self.relocations.ui(ui, "relocations", context);
+            if let Some(tls) = &mut self.tls {
+                tls.ui(ui, "tls", context);
+            }
+            self.debug.ui(ui, "debug", context);
+        });
+    }
+}
+
+/// The text printed by the DOS stub, like "This program cannot be run in DOS mode."
+fn stub_message(stub: &[u8]) -> Option<String> {
+    let start = stub
+        .windows(12)
+        .position(|window| window == b"This program")?;
+    // DOS strings end with `$`
+    let length = stub[start..]
+        .iter()
+        .position(|&b| b == b'$' || b == b'\r' || b == 0)?;
+    Some(String::from_utf8_lossy(&stub[start..start + length]).into_owned())
+}
+
+impl PeFormat {
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (_, dos_header) = DosHeader::parse(input, false)?;
+        let nt_headers = at(input, dos_header.new_header_offset.into())?;
+        let (tail, _) = tag(b"PE\0\0")(nt_headers)?;
+        let (tail, file_header) = FileHeader::parse(tail, false)?;
+        let (tail, optional) = take(file_header.optional_header_size)(tail)?;
+        let mut optional_header = if optional.is_empty() {
+            None
+        } else {
+            Some(OptionalHeader::parse(optional)?.1)
+        };
+        let (tail, mut section_headers) =
+            count(SectionHeader::parse, file_header.section_count.into())(tail)?;
+
+        // Object files can have section names longer than 8 bytes in the string table
+        let string_table = file_header.symbol_table_offset as u64
+            + file_header.symbol_count as u64 * COFF_SYMBOL_SIZE;
+        if file_header.symbol_table_offset != 0 {
+            let strings = at(input, string_table).unwrap_or_default();
+            for section in &mut section_headers {
+                if let Some(name) = sections::resolve_long_name(&section.name, strings) {
+                    section.name = name;
+                }
+            }
+        }
+
+        let image = Image {
+            input,
+            sections: &section_headers,
+            headers_size: optional_header
+                .as_ref()
+                .map_or(0, |header| header.headers_size),
+            image_base: optional_header
+                .as_ref()
+                .map_or(0, |header| header.image_base),
+            is_64: optional_header.as_ref().is_some_and(OptionalHeader::is_64),
+        };
+        let directory = |index| {
+            optional_header
+                .as_ref()
+                .and_then(|header: &OptionalHeader| header.directory(index))
+        };
+        // Broken directories are left out rather than hiding the rest of the file
+        let imports = directory(IMPORT_DIRECTORY)
+            .map(|directory| imports::parse_imports(image, directory))
+            .unwrap_or_default();
+        let exports = directory(EXPORT_DIRECTORY)
+            .and_then(|directory| exports::parse_exports(image, directory));
+        let resources = directory(RESOURCE_DIRECTORY)
+            .map(|directory| resources::parse_resources(image, directory))
+            .unwrap_or_default();
+        let relocations = directory(BASE_RELOCATION_DIRECTORY)
+            .map(|directory| relocations::parse_relocations(image, directory))
+            .unwrap_or_default();
+        let tls = directory(TLS_DIRECTORY).and_then(|directory| tls::parse_tls(image, directory));
+        let debug = directory(DEBUG_DIRECTORY)
+            .map(|directory| debug::parse_debug(image, directory))
+            .unwrap_or_default();
+
+        if let Some(header) = &mut optional_header {
+            for (index, directory) in header.directories.iter_mut().enumerate() {
+                directory.offset = if directory.rva == 0 {
+                    None
+                } else if index == headers::CERTIFICATE_DIRECTORY {
+                    Some(directory.rva.into())
+                } else {
+                    image.offset(directory.rva)
+                };
+            }
+        }
+
+        let stub = input
+            .get(DosHeader::SIZE as usize..dos_header.new_header_offset as usize)
+            .unwrap_or_default();
+        let mut pe = Self {
+            stub_size: stub.len() as u64,
+            stub_message: stub_message(stub),
+            rich_header: RichHeader::find(input, dos_header.new_header_offset as usize),
+            dos_header,
+            file_header,
+            optional_header,
+            sections: SectionTable::new(section_headers, input),
+            imports,
+            exports,
+            resources,
+            relocations,
+            tls,
+            debug,
+            warnings: Vec::new(),
+        };
+        pe.warnings = pe.find_warnings(input);
+        Ok((tail, pe))
+    }
+
+    fn find_warnings(&self, input: &[u8]) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.dos_header.magic != headers::DOS_MAGIC {
+            warnings.push(format!(
+                "DOS magic is \"{}\" instead of \"MZ\"",
+                self.dos_header.magic.escape_ascii()
+            ));
+        }
+        if self
+            .rich_header
+            .as_ref()
+            .is_some_and(|rich_header| !rich_header.checksum_valid)
+        {
+            warnings.push("The Rich header's checksum doesn't match, it was edited".to_string());
+        }
+        for section in &self.sections.headers {
+            if section.raw_offset as u64 + section.raw_size as u64 > input.len() as u64 {
+                warnings.push(format!(
+                    "Section {} goes past the end of the file",
+                    section.name
+                ));
+            }
+        }
+        // Packers and hand-made files share bytes between sections, linkers never do
+        let mut in_file = self
+            .sections
+            .headers
+            .iter()
+            .filter(|section| section.raw_size != 0)
+            .collect::<Vec<_>>();
+        in_file.sort_by_key(|section| section.raw_offset);
+        for pair in in_file.windows(2) {
+            if pair[0].raw_offset as u64 + pair[0].raw_size as u64 > pair[1].raw_offset as u64 {
+                warnings.push(format!(
+                    "Sections {} and {} overlap in the file",
+                    pair[0].name, pair[1].name
+                ));
+            }
+        }
+        warnings
+    }
+
+    /// Header fields under their names from the PE specification and the file offsets of
+    /// exports, for use in expressions
+    pub fn symbols(&self) -> Vec<(String, u64)> {
+        let mut symbols = vec![
+            ("e_lfanew", self.dos_header.new_header_offset as u64),
+            ("Machine", self.file_header.machine as u64),
+            ("NumberOfSections", self.file_header.section_count as u64),
+            ("TimeDateStamp", self.file_header.time_date_stamp as u64),
+            (
+                "PointerToSymbolTable",
+                self.file_header.symbol_table_offset as u64,
+            ),
+            ("NumberOfSymbols", self.file_header.symbol_count as u64),
+            ("Characteristics", self.file_header.characteristics as u64),
+        ];
+        if let Some(header) = &self.optional_header {
+            symbols.extend([
+                ("AddressOfEntryPoint", header.entry_point as u64),
+                ("ImageBase", header.image_base),
+                ("SectionAlignment", header.section_alignment as u64),
+                ("FileAlignment", header.file_alignment as u64),
+                ("SizeOfImage", header.image_size as u64),
+                ("SizeOfHeaders", header.headers_size as u64),
+            ]);
+        }
+        symbols
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .chain(self.exports.iter().flat_map(|exports| {
+                exports
+                    .functions
+                    .iter()
+                    .filter_map(|function| Some((function.name.clone()?, function.offset?)))
+            }))
+            .collect()
+    }
+
+    pub fn new(bytes: &[u8]) -> Result<Self, Error> {
+        Self::parse(bytes)
+            .map(|(_, pe)| pe)
+            .map_err(|err| Error::from_nom("PE", bytes, err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! The DLLs were linked by GNU ld from a few functions, a data export and a forwarder to
+    //! `base.dll`, whose `base_function` they import. `pe32.dll` has a Rich header with a
+    //! valid checksum written over its DOS stub, as ld doesn't write one.
+
+    use super::*;
+
+    const PE32: &[u8] = include_bytes!("fixtures/pe32.dll");
+    const PE32_PLUS: &[u8] = include_bytes!("fixtures/pe32plus.dll");
+
+    /// Offset of the first section header, after the signature and the headers
+    fn section_table(input: &[u8]) -> usize {
+        let pe = PeFormat::new(input).unwrap();
+        let offset = pe.dos_header.new_header_offset as u64 + 4 + FileHeader::SIZE;
+        (offset + pe.file_header.optional_header_size as u64) as usize
+    }
+
+    fn set_dword(bytes: &mut [u8], offset: usize, value: u32) {
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    #[test]
+    fn optional_headers() {
+        assert_eq!(probe(PE32), Confidence::High);
+        let pe = PeFormat::new(PE32).unwrap();
+        assert_eq!(pe.file_header.machine, 0x14c);
+        let header = pe.optional_header.as_ref().unwrap();
+        assert!(!header.is_64());
+        assert_eq!(header.image_base, 0x1000_0000);
+        assert_eq!(header.data_base, Some(0x2000));
+        assert!(pe.warnings.is_empty(), "{:?}", pe.warnings);
+
+        assert_eq!(probe(PE32_PLUS), Confidence::High);
+        let pe = PeFormat::new(PE32_PLUS).unwrap();
+        assert_eq!(pe.file_header.machine, 0x8664);
+        let header = pe.optional_header.as_ref().unwrap();
+        assert!(header.is_64());
+        assert_eq!(header.image_base, 0x1_8000_0000);
+        assert_eq!(header.data_base, None);
+        let names = pe
+            .sections
+            .headers
+            .iter()
+            .map(|section| section.name.as_str());
+        assert_eq!(
+            names.collect::<Vec<_>>(),
+            [".text", ".data", ".edata", ".idata"]
+        );
+        assert_eq!(
+            pe.stub_message.as_deref(),
+            Some("This program cannot be run in DOS mode.")
+        );
+        assert!(pe.rich_header.is_none());
+    }
+
+    #[test]
+    fn imports_and_exports() {
+        for input in [PE32, PE32_PLUS] {
+            let pe = PeFormat::new(input).unwrap();
+            let [library] = &pe.imports[..] else {
+                panic!("{} imported libraries", pe.imports.len());
+            };
+            assert_eq!(library.name, "base.dll");
+            let names = library
+                .functions
+                .iter()
+                .map(|function| function.name.clone());
+            assert_eq!(names.collect::<Vec<_>>(), [Some("base_function".into())]);
+
+            let exports = pe.exports.as_ref().unwrap();
+            assert_eq!(exports.name, "main.dll");
+            assert_eq!(exports.ordinal_base, 1);
+            let functions = exports
+                .functions
+                .iter()
+                .map(|function| {
+                    let name = function.name.as_deref().unwrap_or_default();
+                    (function.ordinal, name, function.forwarder.as_deref())
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                functions,
+                [
+                    (1, "exported_function", None),
+                    (2, "exported_data", None),
+                    (3, "forwarded", Some("base.base_function")),
+                ]
+            );
+            // Forwarded functions have no code in the file to point at
+            assert_eq!(exports.functions[0].offset, Some(0x400));
+            assert_eq!(exports.functions[2].offset, None);
+            let symbols = pe.symbols();
+            assert!(symbols.contains(&("exported_data".to_string(), 0x600)));
+            assert!(!symbols.iter().any(|(name, _)| name == "forwarded"));
+        }
+    }
+
+    #[test]
+    fn rich_header() {
+        let pe = PeFormat::new(PE32).unwrap();
+        let rich_header = pe.rich_header.as_ref().unwrap();
+        assert_eq!(rich_header.offset, 0x40);
+        assert_eq!(rich_header.size, 56);
+        assert!(rich_header.checksum_valid);
+        let entries = rich_header
+            .entries
+            .iter()
+            .map(|entry| (entry.product, entry.build, entry.count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            [
+                (0x104, 30729, 3),
+                (0x105, 30729, 12),
+                (0xff, 30729, 1),
+                (0x102, 30729, 1)
+            ]
+        );
+
+        // The checksum covers the DOS header
+        let mut input = PE32.to_vec();
+        input[0x20] ^= 1;
+        let pe = PeFormat::new(&input).unwrap();
+        assert!(!pe.rich_header.as_ref().unwrap().checksum_valid);
+        assert_eq!(
+            pe.warnings,
+            ["The Rich header's checksum doesn't match, it was edited"]
+        );
+    }
+
+    #[test]
+    fn broken_section_tables() {
+        // The file ends in the middle of the last section header
+        let cut_off = &PE32[..section_table(PE32) + 3 * SectionHeader::SIZE as usize + 20];
+        assert!(PeFormat::new(cut_off).is_err());
+
+        let mut input = PE32_PLUS.to_vec();
+        let table = section_table(PE32_PLUS);
+        // .data starts in .text, .idata is larger than the rest of the file
+        set_dword(&mut input, table + 40 + 20, 0x300);
+        set_dword(&mut input, table + 3 * 40 + 16, 0x1000);
+        let pe = PeFormat::new(&input).unwrap();
+        assert_eq!(
+            pe.warnings,
+            [
+                "Section .idata goes past the end of the file",
+                "Sections .data and .text overlap in the file",
+            ]
+        );
+        // Bytes of .idata that are in the file are still read
+        assert_eq!(pe.imports.len(), 1);
+    }
+}
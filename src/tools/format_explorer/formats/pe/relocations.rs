@@ -0,0 +1,133 @@
+use egui::Vec2b;
+use egui_extras::Column;
+use nom::sequence::Tuple;
+
+use super::{dword, headers::DataDirectory, Image};
+use crate::tools::format_explorer::{FileFormatUi, FormatContext};
+
+/// Size of the page address and block size before the entries
+const BLOCK_HEADER_SIZE: usize = 8;
+
+pub struct Relocation {
+    pub type_: u8,
+    /// Offset from the start of the block's page
+    pub offset: u16,
+}
+
+/// Relocations of one 4 KiB page
+pub struct RelocationBlock {
+    pub page_rva: u32,
+    /// Where the block is in the file
+    pub block_offset: u64,
+    pub block_size: u32,
+    pub relocations: Vec<Relocation>,
+}
+
+/// Symbolic `IMAGE_REL_BASED_*` name of a base relocation type
+pub fn relocation_type_name(type_: u8) -> String {
+    let name = match type_ {
+        0 => "ABSOLUTE",
+        1 => "HIGH",
+        2 => "LOW",
+        3 => "HIGHLOW",
+        4 => "HIGHADJ",
+        5 => "MACHINE_SPECIFIC_5",
+        7 => "MACHINE_SPECIFIC_7",
+        8 => "MACHINE_SPECIFIC_8",
+        9 => "MACHINE_SPECIFIC_9",
+        10 => "DIR64",
+        _ => return type_.to_string(),
+    };
+    name.to_string()
+}
+
+pub(super) fn parse_relocations(image: Image, directory: DataDirectory) -> Vec<RelocationBlock> {
+    let Some(data) = image.slice(directory.rva, directory.size) else {
+        return Vec::new();
+    };
+    let start = image.offset(directory.rva).unwrap_or_default();
+    let mut blocks = Vec::new();
+    let mut position = 0;
+    while let Ok((_, (page_rva, block_size))) = (dword, dword).parse(&data[position..]) {
+        // A block too small for its own header would loop forever
+        if (block_size as usize) < BLOCK_HEADER_SIZE {
+            break;
+        }
+        let end = (position + block_size as usize).min(data.len());
+        let relocations = data[position + BLOCK_HEADER_SIZE..end]
+            .chunks_exact(2)
+            .map(|entry| {
+                let entry = u16::from_le_bytes([entry[0], entry[1]]);
+                Relocation {
+                    type_: (entry >> 12) as u8,
+                    offset: entry & 0xfff,
+                }
+            })
+            .collect();
+        blocks.push(RelocationBlock {
+            page_rva,
+            block_offset: start + position as u64,
+            block_size,
+            relocations,
+        });
+        position = end;
+    }
+    blocks
+}
+
+impl FileFormatUi for RelocationBlock {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let title = format!("{} ({})", name, self.relocations.len());
+        let block = context.at(self.block_offset, self.block_size.into());
+        block.collapsing(ui, &title, |ui| {
+            context.range_link(ui, "block bytes", self.block_offset, self.block_size.into());
+            let entries = context.at(
+                self.block_offset + BLOCK_HEADER_SIZE as u64,
+                2 * self.relocations.len() as u64,
+            );
+            egui_extras::TableBuilder::new(ui)
+                .striped(true)
+                .resizable(false)
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .columns(Column::auto(), 2)
+                .vscroll(false)
+                .auto_shrink(Vec2b::new(false, true))
+                .header(20.0, |mut header| {
+                    for title in ["RVA", "Type"] {
+                        header.col(|ui| {
+                            ui.label(title);
+                        });
+                    }
+                })
+                .body(|body| {
+                    body.rows(20.0, self.relocations.len(), |mut row| {
+                        let index = row.index();
+                        let relocation = &self.relocations[index];
+                        row.col(|ui| {
+                            let rva = self.page_rva.wrapping_add(relocation.offset.into());
+                            let label =
+                                egui::Label::new(format!("{:#x}", rva)).sense(egui::Sense::click());
+                            entries
+                                .entry(index, 2)
+                                .track(&ui.add(label), &format!("relocation of {:#x}", rva));
+                        });
+                        row.col(|ui| {
+                            ui.label(relocation_type_name(relocation.type_));
+                        });
+                    });
+                });
+        });
+    }
+}
+
+impl FileFormatUi for Vec<RelocationBlock> {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let title = format!("{} ({} pages)", name, self.len());
+        ui.collapsing(title, |ui| {
+            for (index, block) in self.iter_mut().enumerate() {
+                let name = format!("page {:#x}", block.page_rva);
+                ui.push_id(index, |ui| block.ui(ui, &name, context));
+            }
+        });
+    }
+}
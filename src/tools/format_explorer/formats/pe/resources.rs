@@ -0,0 +1,181 @@
+use nom::sequence::Tuple;
+
+use super::{dword, headers::DataDirectory, word, Image};
+use crate::tools::format_explorer::{text_field, FileFormatUi, FormatContext};
+
+/// Set in entries pointing to a name rather than an ID, or to a subdirectory rather than data
+const HIGH_BIT: u32 = 0x8000_0000;
+
+/// Windows uses three levels, type, name and language, more than this is a loop
+const MAX_DEPTH: usize = 8;
+/// Directories can share subdirectories, this keeps the tree from blowing up
+const MAX_ENTRIES: usize = 65536;
+
+pub struct ResourceData {
+    pub rva: u32,
+    pub size: u32,
+    pub code_page: u32,
+    pub offset: Option<u64>,
+}
+
+pub struct ResourceEntry {
+    /// The name, or the ID for entries without a name
+    pub name: String,
+    pub children: Vec<ResourceEntry>,
+    /// Only set for leaves
+    pub data: Option<ResourceData>,
+}
+
+/// `RT_*` names of the predefined resource types
+fn type_name(id: u32) -> Option<&'static str> {
+    let name = match id {
+        1 => "RT_CURSOR",
+        2 => "RT_BITMAP",
+        3 => "RT_ICON",
+        4 => "RT_MENU",
+        5 => "RT_DIALOG",
+        6 => "RT_STRING",
+        7 => "RT_FONTDIR",
+        8 => "RT_FONT",
+        9 => "RT_ACCELERATOR",
+        10 => "RT_RCDATA",
+        11 => "RT_MESSAGETABLE",
+        12 => "RT_GROUP_CURSOR",
+        14 => "RT_GROUP_ICON",
+        16 => "RT_VERSION",
+        17 => "RT_DLGINCLUDE",
+        19 => "RT_PLUGPLAY",
+        20 => "RT_VXD",
+        21 => "RT_ANICURSOR",
+        22 => "RT_ANIICON",
+        23 => "RT_HTML",
+        24 => "RT_MANIFEST",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Counted UTF-16 string at `offset` of the resource section
+fn resource_name(resources: &[u8], offset: u32) -> Option<String> {
+    let tail = resources.get(offset as usize..)?;
+    let length = u16::from_le_bytes([*tail.first()?, *tail.get(1)?]) as usize;
+    let units = tail
+        .get(2..2 + length * 2)?
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]));
+    Some(
+        char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect(),
+    )
+}
+
+struct Parser<'a> {
+    image: Image<'a>,
+    /// The resource section from the root directory on, all offsets are relative to it
+    resources: &'a [u8],
+    remaining_entries: usize,
+}
+
+impl Parser<'_> {
+    fn directory(&mut self, offset: u32, depth: usize) -> Vec<ResourceEntry> {
+        let Some(directory) = self.resources.get(offset as usize..) else {
+            return Vec::new();
+        };
+        let Ok((entries, (_, _, _, _, named_count, id_count))) =
+            (dword, dword, word, word, word, word).parse(directory)
+        else {
+            return Vec::new();
+        };
+        let count = named_count as usize + id_count as usize;
+        let mut children = Vec::new();
+        for entry in entries.chunks_exact(8).take(count) {
+            if self.remaining_entries == 0 {
+                break;
+            }
+            self.remaining_entries -= 1;
+            let name = u32::from_le_bytes(entry[..4].try_into().unwrap());
+            let target = u32::from_le_bytes(entry[4..].try_into().unwrap());
+            let name = if name & HIGH_BIT != 0 {
+                resource_name(self.resources, name & !HIGH_BIT).unwrap_or_default()
+            } else {
+                match type_name(name).filter(|_| depth == 0) {
+                    Some(type_name) => format!("{} ({})", type_name, name),
+                    None => name.to_string(),
+                }
+            };
+            let child = if target & HIGH_BIT != 0 {
+                if depth + 1 >= MAX_DEPTH {
+                    continue;
+                }
+                ResourceEntry {
+                    name,
+                    children: self.directory(target & !HIGH_BIT, depth + 1),
+                    data: None,
+                }
+            } else {
+                ResourceEntry {
+                    name,
+                    children: Vec::new(),
+                    data: self.data(target),
+                }
+            };
+            children.push(child);
+        }
+        children
+    }
+
+    fn data(&self, offset: u32) -> Option<ResourceData> {
+        let entry = self.resources.get(offset as usize..)?;
+        let (_, (rva, size, code_page)) = (dword, dword, dword).parse(entry).ok()?;
+        Some(ResourceData {
+            rva,
+            size,
+            code_page,
+            offset: self.image.offset(rva),
+        })
+    }
+}
+
+pub(super) fn parse_resources(image: Image, directory: DataDirectory) -> Vec<ResourceEntry> {
+    let Some(resources) = image.tail(directory.rva) else {
+        return Vec::new();
+    };
+    let mut parser = Parser {
+        image,
+        resources,
+        remaining_entries: MAX_ENTRIES,
+    };
+    parser.directory(0, 0)
+}
+
+impl FileFormatUi for ResourceEntry {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        match &self.data {
+            Some(data) => {
+                ui.collapsing(name, |ui| {
+                    text_field(ui, "rva", &format!("{:#x}", data.rva));
+                    match data.offset {
+                        Some(offset) => context.range_link(ui, "bytes", offset, data.size.into()),
+                        None => text_field(ui, "size", &format!("{:#x}", data.size)),
+                    }
+                    text_field(ui, "code_page", &data.code_page.to_string());
+                });
+            }
+            None => self.children.ui(ui, name, context),
+        }
+    }
+}
+
+impl FileFormatUi for Vec<ResourceEntry> {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let title = format!("{} ({})", name, self.len());
+        ui.collapsing(title, |ui| {
+            for (index, entry) in self.iter_mut().enumerate() {
+                let name = entry.name.clone();
+                // Names repeat, like the language of every resource
+                ui.push_id(index, |ui| entry.ui(ui, &name, context));
+            }
+        });
+    }
+}
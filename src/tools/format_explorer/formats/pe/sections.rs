@@ -0,0 +1,245 @@
+use egui::Vec2b;
+use egui_extras::Column;
+use nom::{bytes::complete::take, sequence::Tuple, IResult};
+
+use super::{dword, flag_names, string_at, word};
+use crate::tools::{
+    entropy_plot::EntropyPlot,
+    format_explorer::{FileFormatUi, FormatContext},
+};
+
+pub struct SectionHeader {
+    pub name: String,
+    pub virtual_size: u32,
+    pub virtual_address: u32,
+    pub raw_size: u32,
+    pub raw_offset: u32,
+    pub relocations_offset: u32,
+    pub line_numbers_offset: u32,
+    pub relocation_count: u16,
+    pub line_number_count: u16,
+    pub characteristics: u32,
+    /// Entropy of the contents, `None` for sections without bytes in the file
+    pub entropy: Option<f64>,
+}
+
+impl SectionHeader {
+    pub const SIZE: u64 = 40;
+
+    pub(super) fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (
+            tail,
+            (
+                name,
+                virtual_size,
+                virtual_address,
+                raw_size,
+                raw_offset,
+                relocations_offset,
+                line_numbers_offset,
+                relocation_count,
+                line_number_count,
+                characteristics,
+            ),
+        ) = (
+            take(8usize),
+            dword,
+            dword,
+            dword,
+            dword,
+            dword,
+            dword,
+            word,
+            word,
+            dword,
+        )
+            .parse(input)?;
+        Ok((
+            tail,
+            Self {
+                name: string_at(name, 0),
+                virtual_size,
+                virtual_address,
+                raw_size,
+                raw_offset,
+                relocations_offset,
+                line_numbers_offset,
+                relocation_count,
+                line_number_count,
+                characteristics,
+                entropy: None,
+            },
+        ))
+    }
+
+    /// Contents of the section, `None` if they are outside of the file
+    pub fn data<'a>(&self, input: &'a [u8]) -> Option<&'a [u8]> {
+        let start = self.raw_offset as usize;
+        input.get(start..start.checked_add(self.raw_size as usize)?)
+    }
+
+    /// `R`, `W` and `X` permissions, like `R-X`
+    pub fn permissions(&self) -> String {
+        [
+            (IMAGE_SCN_MEM_READ, 'R'),
+            (IMAGE_SCN_MEM_WRITE, 'W'),
+            (IMAGE_SCN_MEM_EXECUTE, 'X'),
+        ]
+        .iter()
+        .map(|&(bit, c)| {
+            if self.characteristics & bit != 0 {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+    }
+}
+
+const IMAGE_SCN_MEM_EXECUTE: u32 = 0x2000_0000;
+const IMAGE_SCN_MEM_READ: u32 = 0x4000_0000;
+const IMAGE_SCN_MEM_WRITE: u32 = 0x8000_0000;
+
+/// Bits holding the alignment of object file sections, as a power of two plus one
+const ALIGNMENT_MASK: u32 = 0x00f0_0000;
+
+const SECTION_CHARACTERISTICS: [(u64, &str); 16] = [
+    (0x8, "TYPE_NO_PAD"),
+    (0x20, "CNT_CODE"),
+    (0x40, "CNT_INITIALIZED_DATA"),
+    (0x80, "CNT_UNINITIALIZED_DATA"),
+    (0x200, "LNK_INFO"),
+    (0x800, "LNK_REMOVE"),
+    (0x1000, "LNK_COMDAT"),
+    (0x8000, "GPREL"),
+    (0x0100_0000, "LNK_NRELOC_OVFL"),
+    (0x0200_0000, "MEM_DISCARDABLE"),
+    (0x0400_0000, "MEM_NOT_CACHED"),
+    (0x0800_0000, "MEM_NOT_PAGED"),
+    (0x1000_0000, "MEM_SHARED"),
+    (IMAGE_SCN_MEM_EXECUTE as u64, "MEM_EXECUTE"),
+    (IMAGE_SCN_MEM_READ as u64, "MEM_READ"),
+    (IMAGE_SCN_MEM_WRITE as u64, "MEM_WRITE"),
+];
+
+/// Names of the characteristics, with the alignment of object file sections
+pub fn section_characteristic_names(characteristics: u32) -> String {
+    let names = flag_names(
+        (characteristics & !ALIGNMENT_MASK).into(),
+        SECTION_CHARACTERISTICS,
+    );
+    match (characteristics & ALIGNMENT_MASK) >> 20 {
+        0 => names,
+        alignment => format!("{} | ALIGN_{}BYTES", names, 1u32 << (alignment - 1)),
+    }
+}
+
+/// Resolves `/123` names of object files, which point into the COFF string table
+pub(super) fn resolve_long_name(name: &str, string_table: &[u8]) -> Option<String> {
+    let offset = name.strip_prefix('/')?.parse::<u64>().ok()?;
+    Some(string_at(string_table, offset))
+}
+
+pub struct SectionTable {
+    pub headers: Vec<SectionHeader>,
+}
+
+impl SectionTable {
+    pub(super) fn new(mut headers: Vec<SectionHeader>, input: &[u8]) -> Self {
+        for header in &mut headers {
+            header.entropy = header
+                .data(input)
+                .filter(|data| !data.is_empty())
+                .map(EntropyPlot::entropy_of_slice);
+        }
+        Self { headers }
+    }
+}
+
+impl FileFormatUi for SectionTable {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let title = format!("{} ({})", name, self.headers.len());
+        context.collapsing(ui, &title, |ui| {
+            egui_extras::TableBuilder::new(ui)
+                .striped(true)
+                .resizable(false)
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .columns(Column::auto(), 8)
+                .vscroll(false)
+                .auto_shrink(Vec2b::new(false, true))
+                .header(20.0, |mut header| {
+                    for title in [
+                        "#",
+                        "Name",
+                        "Virtual address",
+                        "Virtual size",
+                        "Offset",
+                        "Size",
+                        "Flags",
+                        "Entropy",
+                    ] {
+                        header.col(|ui| {
+                            ui.label(title);
+                        });
+                    }
+                })
+                .body(|body| {
+                    body.rows(20.0, self.headers.len(), |mut row| {
+                        let index = row.index();
+                        let section = &self.headers[index];
+                        row.col(|ui| {
+                            let label =
+                                egui::Label::new(index.to_string()).sense(egui::Sense::click());
+                            context.entry(index, SectionHeader::SIZE).track(
+                                &ui.add(label),
+                                &format!("section header {} {}", index, section.name),
+                            );
+                        });
+                        row.col(|ui| {
+                            ui.label(&section.name).on_hover_text(format!(
+                                "relocations: {} at {:#x}, line numbers: {} at {:#x}",
+                                section.relocation_count,
+                                section.relocations_offset,
+                                section.line_number_count,
+                                section.line_numbers_offset
+                            ));
+                        });
+                        row.col(|ui| {
+                            ui.label(format!("{:#x}", section.virtual_address));
+                        });
+                        row.col(|ui| {
+                            ui.label(format!("{:#x}", section.virtual_size));
+                        });
+                        row.col(|ui| {
+                            if ui
+                                .link(format!("{:#x}", section.raw_offset))
+                                .on_hover_text("Select the section's bytes")
+                                .clicked()
+                            {
+                                context.select(section.raw_offset.into(), section.raw_size.into());
+                            }
+                        });
+                        row.col(|ui| {
+                            ui.label(format!("{:#x}", section.raw_size));
+                        });
+                        row.col(|ui| {
+                            ui.label(section.permissions()).on_hover_text(format!(
+                                "{:#x}: {}",
+                                section.characteristics,
+                                section_characteristic_names(section.characteristics)
+                            ));
+                        });
+                        row.col(|ui| match section.entropy {
+                            Some(entropy) => {
+                                ui.label(format!("{:.3}", entropy));
+                            }
+                            None => {
+                                ui.weak("—");
+                            }
+                        });
+                    });
+                });
+        });
+    }
+}
@@ -0,0 +1,105 @@
+use nom::sequence::Tuple;
+
+use super::{dword, headers::DataDirectory, Image};
+use crate::tools::format_explorer::{text_field, FileFormatUi, FormatContext};
+
+/// Limit for callback arrays without a terminating zero
+const MAX_CALLBACKS: usize = 1024;
+
+/// Thread local storage template and the callbacks run when threads start, which malware
+/// likes to use to run before the entry point
+pub struct TlsDirectory {
+    pub offset: u64,
+    /// From a PE32+ file, with 8-byte addresses
+    pub is_64: bool,
+    pub raw_data_start: u64,
+    pub raw_data_end: u64,
+    pub index_address: u64,
+    pub callbacks_address: u64,
+    pub zero_fill_size: u32,
+    pub characteristics: u32,
+    /// Virtual addresses of the callbacks, with their file offsets
+    pub callbacks: Vec<(u64, Option<u64>)>,
+}
+
+pub(super) fn parse_tls(image: Image, directory: DataDirectory) -> Option<TlsDirectory> {
+    let address = |input| image.address(input);
+    let (
+        _,
+        (
+            raw_data_start,
+            raw_data_end,
+            index_address,
+            callbacks_address,
+            zero_fill_size,
+            characteristics,
+        ),
+    ) = (address, address, address, address, dword, dword)
+        .parse(image.tail(directory.rva)?)
+        .ok()?;
+    let mut callbacks = Vec::new();
+    if let Some(mut array) = image.rva(callbacks_address).and_then(|rva| image.tail(rva)) {
+        while callbacks.len() < MAX_CALLBACKS {
+            let Ok((tail, callback)) = image.address(array) else {
+                break;
+            };
+            if callback == 0 {
+                break;
+            }
+            let offset = image.rva(callback).and_then(|rva| image.offset(rva));
+            callbacks.push((callback, offset));
+            array = tail;
+        }
+    }
+    Some(TlsDirectory {
+        offset: image.offset(directory.rva)?,
+        is_64: image.is_64,
+        raw_data_start,
+        raw_data_end,
+        index_address,
+        callbacks_address,
+        zero_fill_size,
+        characteristics,
+        callbacks,
+    })
+}
+
+impl FileFormatUi for TlsDirectory {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let address_size = if self.is_64 { 8 } else { 4 };
+        let directory = context.at(self.offset, 4 * address_size + 8);
+        directory.collapsing(ui, name, |ui| {
+            text_field(ui, "offset", &format!("{:#x}", self.offset));
+            let mut fields = directory.fields();
+            for (name, value) in [
+                ("raw_data_start", self.raw_data_start),
+                ("raw_data_end", self.raw_data_end),
+                ("index_address", self.index_address),
+                ("callbacks_address", self.callbacks_address),
+            ] {
+                fields
+                    .next(address_size)
+                    .field(ui, name, &format!("{:#x}", value));
+            }
+            self.zero_fill_size
+                .ui(ui, "zero_fill_size", &fields.next(4));
+            fields.next(4).field(
+                ui,
+                "characteristics",
+                &format!("{:#x}", self.characteristics),
+            );
+            let title = format!("callbacks ({})", self.callbacks.len());
+            ui.collapsing(title, |ui| {
+                for (index, &(address, offset)) in self.callbacks.iter().enumerate() {
+                    let name = format!("[{}] {:#x}", index, address);
+                    match offset {
+                        Some(offset) => context.range_link(ui, &name, offset, 1),
+                        None => {
+                            ui.label(name);
+                        }
+                    }
+                }
+            });
+        });
+    }
+}
@@ -0,0 +1,720 @@
+//! tar archives, 512-byte headers each followed by the data of the entry, in the V7, ustar, GNU
+//! and pax flavors
+
+use super::{trailing_data_ui, unix_time, Confidence};
+use crate::{
+    error::Error,
+    tools::format_explorer::{text_field, FileFormat, FileFormatUi, FormatContext, Layout},
+};
+
+const BLOCK_SIZE: u64 = 512;
+/// Where the magic is in a header, V7 headers have nothing there
+const MAGIC_OFFSET: usize = 257;
+/// Where the checksum is in a header, it's counted as spaces when summing
+const CHECKSUM_RANGE: std::ops::Range<usize> = 148..156;
+/// Magic and version of POSIX headers, GNU ones have `ustar  \0` instead
+const USTAR_MAGIC: &[u8; 8] = b"ustar\x0000";
+const GNU_MAGIC: &[u8; 8] = b"ustar  \0";
+
+/// Entries read at most
+const MAX_ENTRIES: usize = 1_000_000;
+/// Entries shown at most, more would make the explorer crawl
+const SHOWN_ENTRIES: usize = 10_000;
+/// pax and GNU long name data read at most, it's names and a few keywords
+const MAX_EXTENSION: u64 = 1 << 20;
+
+/// Checks the ustar magic, or the checksum of the first header for V7 archives that have none
+pub fn probe(bytes: &[u8]) -> Confidence {
+    let Some(block) = bytes.get(..BLOCK_SIZE as usize) else {
+        return Confidence::None;
+    };
+    if block[MAGIC_OFFSET..].starts_with(b"ustar") {
+        Confidence::High
+    } else if block.iter().any(|&b| b != 0) && has_valid_checksum(block) {
+        Confidence::Low
+    } else {
+        Confidence::None
+    }
+}
+
+/// Numbers are octal text, or for values that don't fit big-endian binary after a set high bit,
+/// which GNU tar started. `None` for empty fields and negative binary values.
+fn number(field: &[u8]) -> Option<u64> {
+    if let Some(&first) = field.first().filter(|&&first| first & 0x80 != 0) {
+        // The next bit is the sign
+        if first & 0x40 != 0 {
+            return None;
+        }
+        return field[1..]
+            .iter()
+            .try_fold((first & 0x3f) as u64, |value, &b| {
+                (value.leading_zeros() >= 8).then_some(value << 8 | b as u64)
+            });
+    }
+    let start = field.iter().position(|&b| b != b' ').unwrap_or(field.len());
+    let digits = field[start..]
+        .iter()
+        .take_while(|b| (b'0'..=b'7').contains(b))
+        .count();
+    if digits == 0 || field[start + digits..].iter().any(|&b| b != b' ' && b != 0) {
+        return None;
+    }
+    field[start..start + digits]
+        .iter()
+        .try_fold(0u64, |value, &digit| {
+            value.checked_mul(8)?.checked_add((digit - b'0').into())
+        })
+}
+
+/// Sums of the header bytes with the checksum counted as spaces. Old tars summed signed
+/// bytes, so both sums are accepted.
+fn has_valid_checksum(block: &[u8]) -> bool {
+    let (unsigned, signed) = block
+        .iter()
+        .enumerate()
+        .fold((0u64, 0i64), |sums, (index, &b)| {
+            let b = if CHECKSUM_RANGE.contains(&index) {
+                b' '
+            } else {
+                b
+            };
+            (sums.0 + b as u64, sums.1 + b as i8 as i64)
+        });
+    number(&block[CHECKSUM_RANGE])
+        .is_some_and(|checksum| checksum == unsigned || checksum as i64 == signed)
+}
+
+/// Text up to the first NUL
+fn text(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+fn quoted(field: &[u8]) -> String {
+    format!("{:?}", text(field))
+}
+
+fn number_text(field: &[u8]) -> String {
+    match number(field) {
+        Some(value) => value.to_string(),
+        None if field.iter().all(|&b| b == 0 || b == b' ') => "empty".to_string(),
+        None => "invalid".to_string(),
+    }
+}
+
+fn octal(field: &[u8]) -> String {
+    number(field).map_or_else(|| number_text(field), |value| format!("{:#o}", value))
+}
+
+fn time(field: &[u8]) -> String {
+    number(field).map_or_else(|| number_text(field), unix_time)
+}
+
+fn type_name(flag: &u8) -> String {
+    let name = match flag {
+        0 | b'0' => "file",
+        b'1' => "hard link",
+        b'2' => "symbolic link",
+        b'3' => "character device",
+        b'4' => "block device",
+        b'5' => "directory",
+        b'6' => "FIFO",
+        b'7' => "contiguous file",
+        b'g' => "pax global header",
+        b'x' => "pax header",
+        b'D' => "GNU directory listing",
+        b'K' => "GNU long link name",
+        b'L' => "GNU long name",
+        b'M' => "GNU multi-volume continuation",
+        b'S' => "GNU sparse file",
+        b'V' => "GNU volume label",
+        b'A'..=b'Z' => "vendor extension",
+        _ => "unknown",
+    };
+    format!("{:?} ({})", char::from(*flag), name)
+}
+
+#[derive(FileFormat)]
+pub struct Header {
+    #[format(display = quoted)]
+    pub name: [u8; 100],
+    #[format(display = octal)]
+    pub mode: [u8; 8],
+    #[format(display = number_text)]
+    pub uid: [u8; 8],
+    #[format(display = number_text)]
+    pub gid: [u8; 8],
+    #[format(display = number_text)]
+    pub size: [u8; 12],
+    #[format(display = time)]
+    pub modified: [u8; 12],
+    #[format(display = octal)]
+    pub checksum: [u8; 8],
+    #[format(display = type_name)]
+    pub type_flag: u8,
+    #[format(display = quoted)]
+    pub link_name: [u8; 100],
+    #[format(display = quoted)]
+    pub magic: [u8; 6],
+    #[format(display = quoted)]
+    pub version: [u8; 2],
+    #[format(display = quoted)]
+    pub user_name: [u8; 32],
+    #[format(display = quoted)]
+    pub group_name: [u8; 32],
+    #[format(display = number_text)]
+    pub device_major: [u8; 8],
+    #[format(display = number_text)]
+    pub device_minor: [u8; 8],
+    #[format(display = quoted)]
+    pub prefix: [u8; 155],
+    #[format(hidden)]
+    pub padding: [u8; 12],
+}
+
+impl Header {
+    fn is_ustar(&self) -> bool {
+        self.magic[..] == USTAR_MAGIC[..6] && self.version[..] == USTAR_MAGIC[6..]
+    }
+
+    fn is_gnu(&self) -> bool {
+        self.magic[..] == GNU_MAGIC[..6] && self.version[..] == GNU_MAGIC[6..]
+    }
+
+    /// The name with the prefix ustar headers can put in front of it. GNU headers have other
+    /// fields where the prefix would be.
+    fn full_name(&self) -> String {
+        let prefix = text(&self.prefix);
+        if self.is_ustar() && !prefix.is_empty() {
+            format!("{}/{}", prefix, text(&self.name))
+        } else {
+            text(&self.name)
+        }
+    }
+
+    /// Links, devices, directories and FIFOs have no data whatever the size says
+    fn has_data(&self) -> bool {
+        !(b'1'..=b'6').contains(&self.type_flag)
+    }
+}
+
+/// Names and sizes that pax and GNU long name entries give the entry after them
+#[derive(Default)]
+struct Overrides {
+    name: Option<String>,
+    link_name: Option<String>,
+    size: Option<u64>,
+}
+
+/// Keywords and values of pax records, which are `<length> <keyword>=<value>\n`
+fn pax_records(data: &[u8]) -> Option<Vec<(String, String)>> {
+    let mut records = Vec::new();
+    let mut rest = data;
+    // Writers pad the records with NULs
+    while rest.first().is_some_and(|&b| b != 0) {
+        let space = rest.iter().position(|&b| b == b' ')?;
+        let length: usize = std::str::from_utf8(&rest[..space]).ok()?.parse().ok()?;
+        let record = rest.get(space + 1..length)?.strip_suffix(b"\n")?;
+        let equals = record.iter().position(|&b| b == b'=')?;
+        records.push((
+            String::from_utf8_lossy(&record[..equals]).into_owned(),
+            String::from_utf8_lossy(&record[equals + 1..]).into_owned(),
+        ));
+        rest = &rest[length..];
+    }
+    Some(records)
+}
+
+/// A header and the data after it
+pub struct Entry {
+    offset: u64,
+    header: Header,
+    /// The name with its prefix, or from a pax or GNU long name entry in front of this one
+    name: String,
+    link_name: String,
+    /// Bytes of data, which a pax entry in front of this one can override
+    size: u64,
+    /// Keywords and values of pax headers
+    records: Vec<(String, String)>,
+    warnings: Vec<String>,
+}
+
+impl Entry {
+    fn data_offset(&self) -> u64 {
+        self.offset + BLOCK_SIZE
+    }
+
+    /// Size of the data rounded up to whole blocks
+    fn data_blocks_size(&self) -> u64 {
+        self.size.div_ceil(BLOCK_SIZE).saturating_mul(BLOCK_SIZE)
+    }
+
+    fn file_name(&self) -> &str {
+        let name = self.name.trim_end_matches('/');
+        name.rsplit('/').next().unwrap_or(name)
+    }
+
+    /// The data, `input` is the whole archive
+    fn extract(&self, input: &[u8]) -> Result<Vec<u8>, Error> {
+        usize::try_from(self.data_offset())
+            .ok()
+            .and_then(|start| input.get(start..start.checked_add(self.size as usize)?))
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| {
+                Error::parse(
+                    "tar",
+                    format!("{}: the data isn't all in the file", self.name),
+                )
+            })
+    }
+
+    /// Applies the overrides and reads the ones in the data of pax and GNU long name entries
+    fn apply(&mut self, input: &[u8], overrides: &mut Overrides) {
+        let data = usize::try_from(self.data_offset()).ok().and_then(|start| {
+            input
+                .get(start..)?
+                .get(..self.size.min(MAX_EXTENSION) as usize)
+        });
+        match self.header.type_flag {
+            b'x' | b'g' => {
+                let Some(records) = data.and_then(pax_records) else {
+                    self.warnings
+                        .push("The pax records are malformed or cut off".to_string());
+                    return;
+                };
+                // Global records could change every entry after them, they're only shown
+                if self.header.type_flag == b'x' {
+                    for (keyword, value) in &records {
+                        match keyword.as_str() {
+                            "path" => overrides.name = Some(value.clone()),
+                            "linkpath" => overrides.link_name = Some(value.clone()),
+                            "size" => overrides.size = value.parse().ok(),
+                            _ => {}
+                        }
+                    }
+                }
+                self.records = records;
+            }
+            b'L' | b'K' => {
+                let Some(data) = data else {
+                    self.warnings.push("The long name is cut off".to_string());
+                    return;
+                };
+                if self.header.type_flag == b'L' {
+                    overrides.name = Some(text(data));
+                } else {
+                    overrides.link_name = Some(text(data));
+                }
+            }
+            _ => {
+                let overrides = std::mem::take(overrides);
+                if let Some(name) = overrides.name {
+                    self.name = name;
+                }
+                if let Some(link_name) = overrides.link_name {
+                    self.link_name = link_name;
+                }
+                if let Some(size) = overrides.size {
+                    self.size = size;
+                }
+            }
+        }
+    }
+}
+
+impl FileFormatUi for Entry {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let length = BLOCK_SIZE + self.data_blocks_size();
+        context.at(self.offset, length).collapsing(ui, name, |ui| {
+            for warning in &self.warnings {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+            text_field(ui, "name", &format!("{:?}", self.name));
+            if !self.link_name.is_empty() {
+                text_field(ui, "link name", &format!("{:?}", self.link_name));
+            }
+            let header = context.at(self.offset, BLOCK_SIZE);
+            self.header.ui(ui, "header", &header);
+            if !self.records.is_empty() {
+                let title = format!("pax records ({})", self.records.len());
+                ui.collapsing(title, |ui| {
+                    for (keyword, value) in &self.records {
+                        text_field(ui, keyword, value);
+                    }
+                });
+            }
+            if self.size > 0 {
+                ui.horizontal(|ui| {
+                    context.range_link(ui, "data", self.data_offset(), self.size);
+                    if ui
+                        .button("Open")
+                        .on_hover_text("Open the data of the entry as a new document")
+                        .clicked()
+                    {
+                        context.open(self.file_name(), |bytes| self.extract(bytes));
+                    }
+                });
+            }
+        });
+    }
+}
+
+pub struct TarFormat {
+    entries: Vec<Entry>,
+    /// Where the end of archive marker is, `None` if the archive ends without one
+    end_marker: Option<u64>,
+    /// Where the archive ends, after the blocks of zeros that pad it to a whole record
+    end: u64,
+    size: u64,
+    warnings: Vec<String>,
+}
+
+impl TarFormat {
+    pub fn new(input: &[u8]) -> Result<Self, Error> {
+        let size = input.len() as u64;
+        let mut entries: Vec<Entry> = Vec::new();
+        let mut warnings = Vec::new();
+        let mut overrides = Overrides::default();
+        let mut end_marker = None;
+        let mut position = 0;
+        let block_at = |position: u64| {
+            usize::try_from(position)
+                .ok()
+                .and_then(|start| input.get(start..start.checked_add(BLOCK_SIZE as usize)?))
+        };
+        let is_zero = |block: &[u8]| block.iter().all(|&b| b == 0);
+        while entries.len() < MAX_ENTRIES {
+            let Some(block) = block_at(position) else {
+                if position < size {
+                    warnings.push(format!("The header at {:#x} is cut off", position));
+                } else {
+                    warnings.push("There is no end of archive marker".to_string());
+                }
+                break;
+            };
+            // Two blocks of zeros end the archive
+            if is_zero(block) {
+                if block_at(position + BLOCK_SIZE).is_some_and(is_zero) {
+                    end_marker = Some(position);
+                    position += 2 * BLOCK_SIZE;
+                } else {
+                    warnings.push(format!(
+                        "The archive ends with a single block of zeros at {:#x}",
+                        position
+                    ));
+                    position += BLOCK_SIZE;
+                }
+                break;
+            }
+            if !has_valid_checksum(block) {
+                if entries.is_empty() {
+                    return Err(Error::parse("tar", "the first header's checksum is wrong"));
+                }
+                warnings.push(format!(
+                    "The header at {:#x} has a wrong checksum, the archive ends before it",
+                    position
+                ));
+                break;
+            }
+            let (_, header) =
+                Header::parse(block, false).map_err(|err| Error::from_nom("tar", block, err))?;
+            let mut entry_warnings = Vec::new();
+            let size_field = number(&header.size);
+            if size_field.is_none() {
+                entry_warnings.push("The size isn't a number".to_string());
+            }
+            let mut entry = Entry {
+                offset: position,
+                name: header.full_name(),
+                link_name: text(&header.link_name),
+                size: size_field.filter(|_| header.has_data()).unwrap_or(0),
+                header,
+                records: Vec::new(),
+                warnings: entry_warnings,
+            };
+            entry.apply(input, &mut overrides);
+            let data_end = entry.data_offset().saturating_add(entry.size);
+            position = entry.data_offset().saturating_add(entry.data_blocks_size());
+            if data_end > size {
+                entry.warnings.push("The data is cut off".to_string());
+                entries.push(entry);
+                position = size;
+                break;
+            }
+            entries.push(entry);
+        }
+        // Archives are padded with zeros to a whole record, 10 KiB by default
+        while block_at(position).is_some_and(is_zero) {
+            position += BLOCK_SIZE;
+        }
+        if entries.iter().any(|entry| entry.header.is_gnu())
+            && entries.iter().any(|entry| entry.header.is_ustar())
+        {
+            warnings.push("GNU and POSIX headers are mixed".to_string());
+        }
+        Ok(Self {
+            entries,
+            end_marker,
+            end: position.min(size),
+            size,
+            warnings,
+        })
+    }
+
+    fn flavor(&self) -> &'static str {
+        let has_type = |types: &[u8]| {
+            self.entries
+                .iter()
+                .any(|entry| types.contains(&entry.header.type_flag))
+        };
+        match self.entries.first().map(|entry| &entry.header) {
+            Some(header) if header.is_gnu() => "GNU",
+            Some(header) if header.is_ustar() && has_type(b"xg") => "pax",
+            Some(header) if header.is_ustar() => "ustar",
+            _ => "V7",
+        }
+    }
+
+    /// Entry count and where the archive ends, for expressions
+    pub fn symbols(&self) -> Vec<(String, u64)> {
+        let mut symbols = vec![
+            ("entries".to_string(), self.entries.len() as u64),
+            ("archive_end".to_string(), self.end),
+        ];
+        if let Some(offset) = self.end_marker {
+            symbols.push(("end_marker".to_string(), offset));
+        }
+        symbols
+    }
+}
+
+impl FileFormatUi for TarFormat {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        ui.collapsing(name, |ui| {
+            for warning in &self.warnings {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+            text_field(ui, "flavor", self.flavor());
+            let title = format!("entries ({})", self.entries.len());
+            ui.collapsing(title, |ui| {
+                for (index, entry) in self.entries.iter_mut().take(SHOWN_ENTRIES).enumerate() {
+                    let title = format!("[{}] {}", index, entry.name);
+                    ui.push_id(index, |ui| entry.ui(ui, &title, context));
+                }
+                if self.entries.len() > SHOWN_ENTRIES {
+                    ui.weak(format!("… {} more", self.entries.len() - SHOWN_ENTRIES));
+                }
+            });
+            if let Some(offset) = self.end_marker {
+                context.range_link(ui, "end of archive", offset, 2 * BLOCK_SIZE);
+            }
+            trailing_data_ui(ui, context, "archive", self.end, self.size);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! The archives are built header by header, in the ustar flavor unless a test needs GNU
+    //! headers.
+
+    use super::*;
+
+    fn header(name: &str, type_flag: u8, size: u64, magic: &[u8; 8]) -> Vec<u8> {
+        let mut block = vec![0; BLOCK_SIZE as usize];
+        block[..name.len()].copy_from_slice(name.as_bytes());
+        block[100..108].copy_from_slice(b"0000644\0");
+        block[124..136].copy_from_slice(format!("{:011o}\0", size).as_bytes());
+        block[156] = type_flag;
+        block[MAGIC_OFFSET..MAGIC_OFFSET + 8].copy_from_slice(magic);
+        block[CHECKSUM_RANGE].fill(b' ');
+        let sum = block.iter().map(|&b| b as u64).sum::<u64>();
+        block[CHECKSUM_RANGE].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+        block
+    }
+
+    /// A header and its data padded to whole blocks
+    fn entry(name: &str, type_flag: u8, data: &[u8], magic: &[u8; 8]) -> Vec<u8> {
+        let mut entry = header(name, type_flag, data.len() as u64, magic);
+        entry.extend(data);
+        entry.resize(entry.len().next_multiple_of(BLOCK_SIZE as usize), 0);
+        entry
+    }
+
+    fn zeros(blocks: usize) -> Vec<u8> {
+        vec![0; blocks * BLOCK_SIZE as usize]
+    }
+
+    fn names(tar: &TarFormat) -> Vec<(&str, &str, u64)> {
+        tar.entries
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.link_name.as_str(), entry.size))
+            .collect()
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(number(b"0000644\0"), Some(0o644));
+        assert_eq!(number(b"  17 \0\0\0"), Some(0o17));
+        assert_eq!(number(b"00000000000\0"), Some(0));
+        assert_eq!(number(b"\0\0\0\0\0\0\0\0"), None);
+        assert_eq!(number(b"        "), None);
+        assert_eq!(number(b"0000648\0"), None);
+        assert_eq!(number(b"12 3\0\0\0\0"), None);
+        assert_eq!(number(b"77777777777777777777777"), None);
+        // GNU base-256, the high bit set and the next one for the sign
+        let mut field = [0; 12];
+        field[0] = 0x80;
+        field[10..].copy_from_slice(&[1, 0]);
+        assert_eq!(number(&field), Some(256));
+        field[0] = 0x81;
+        assert_eq!(number(&field), None);
+        let mut field = [0; 8];
+        field[0] = 0x81;
+        assert_eq!(number(&field), Some(1 << 56));
+        assert_eq!(number(&[0xff; 12]), None);
+        assert_eq!(number_text(&[0xff; 12]), "invalid");
+        assert_eq!(number_text(&[0; 12]), "empty");
+    }
+
+    #[test]
+    fn records() {
+        let records = pax_records(b"18 path=some/file\n10 size=5\n\0\0\0").unwrap();
+        assert_eq!(
+            records,
+            [
+                ("path".to_string(), "some/file".to_string()),
+                ("size".to_string(), "5".to_string()),
+            ]
+        );
+        assert_eq!(pax_records(b"\0\0"), Some(Vec::new()));
+        assert_eq!(
+            pax_records(b"13 a=b c=d e\n"),
+            Some(vec![("a".to_string(), "b c=d e".to_string())])
+        );
+        // A length past the data or in the middle of a record
+        assert_eq!(pax_records(b"99 path=x\n"), None);
+        assert_eq!(pax_records(b"8 path=x\n"), None);
+        // No newline at the end of the record
+        assert_eq!(pax_records(b"9 path=xy"), None);
+        assert_eq!(pax_records(b"x path=y\n"), None);
+        assert_eq!(pax_records(b"8 path\n\0"), None);
+    }
+
+    #[test]
+    fn gnu_long_names() {
+        let long_name = "directory/".repeat(20) + "file";
+        let input = [
+            entry(
+                "././@LongLink",
+                b'L',
+                format!("{}\0", long_name).as_bytes(),
+                GNU_MAGIC,
+            ),
+            entry("././@LongLink", b'K', b"target/of/the/link\0", GNU_MAGIC),
+            header("short", b'2', 0, GNU_MAGIC),
+            entry("after", b'0', b"data", GNU_MAGIC),
+            zeros(2),
+        ]
+        .concat();
+        assert_eq!(probe(&input), Confidence::High);
+        let tar = TarFormat::new(&input).unwrap();
+        assert!(tar.warnings.is_empty(), "{:?}", tar.warnings);
+        assert_eq!(tar.flavor(), "GNU");
+        assert_eq!(
+            names(&tar)[2..],
+            [
+                (long_name.as_str(), "target/of/the/link", 0),
+                ("after", "", 4),
+            ]
+        );
+        assert_eq!(tar.entries[3].extract(&input).unwrap(), b"data");
+
+        // A long name entry whose data is cut off
+        let tar = TarFormat::new(&input[..BLOCK_SIZE as usize + 10]).unwrap();
+        assert_eq!(
+            tar.entries[0].warnings,
+            ["The long name is cut off", "The data is cut off"]
+        );
+    }
+
+    #[test]
+    fn pax_overrides() {
+        let records = b"22 path=from/pax/file\n10 size=5\n";
+        let input = [
+            entry("PaxHeaders/file", b'x', records, USTAR_MAGIC),
+            header("file", b'0', 0, USTAR_MAGIC),
+            b"hello".to_vec(),
+            zeros(1)[5..].to_vec(),
+            entry("plain", b'0', b"!", USTAR_MAGIC),
+            entry("PaxHeaders/bad", b'x', b"99 path=x\n", USTAR_MAGIC),
+            zeros(2),
+        ]
+        .concat();
+        let tar = TarFormat::new(&input).unwrap();
+        assert!(tar.warnings.is_empty(), "{:?}", tar.warnings);
+        assert_eq!(tar.flavor(), "pax");
+        assert_eq!(
+            names(&tar),
+            [
+                ("PaxHeaders/file", "", records.len() as u64),
+                ("from/pax/file", "", 5),
+                ("plain", "", 1),
+                ("PaxHeaders/bad", "", 10),
+            ]
+        );
+        assert_eq!(tar.entries[0].records.len(), 2);
+        assert_eq!(tar.entries[1].extract(&input).unwrap(), b"hello");
+        assert_eq!(
+            tar.entries[3].warnings,
+            ["The pax records are malformed or cut off"]
+        );
+    }
+
+    #[test]
+    fn end_marker_and_padding() {
+        let archive = [entry("file", b'0', b"data", USTAR_MAGIC), zeros(2)].concat();
+        let mut input = archive.clone();
+        // Padded to a 10 KiB record, then something else
+        input.resize(10240, 0);
+        input.extend(b"appended");
+        let tar = TarFormat::new(&input).unwrap();
+        assert!(tar.warnings.is_empty(), "{:?}", tar.warnings);
+        assert_eq!(tar.end_marker, Some(2 * BLOCK_SIZE));
+        assert_eq!((tar.end, tar.size), (10240, input.len() as u64));
+        assert_eq!(
+            tar.symbols(),
+            [
+                ("entries".to_string(), 1),
+                ("archive_end".to_string(), 10240),
+                ("end_marker".to_string(), 2 * BLOCK_SIZE),
+            ]
+        );
+
+        let tar = TarFormat::new(&archive[..3 * BLOCK_SIZE as usize]).unwrap();
+        assert_eq!(
+            tar.warnings,
+            ["The archive ends with a single block of zeros at 0x400"]
+        );
+        assert_eq!((tar.end_marker, tar.end), (None, 3 * BLOCK_SIZE));
+
+        let tar = TarFormat::new(&archive[..2 * BLOCK_SIZE as usize]).unwrap();
+        assert_eq!(tar.warnings, ["There is no end of archive marker"]);
+        let tar = TarFormat::new(&archive[..2 * BLOCK_SIZE as usize + 100]).unwrap();
+        assert_eq!(tar.warnings, ["The header at 0x400 is cut off"]);
+
+        // A broken checksum ends the archive after the first entry, but not at the first one
+        let mut input = archive.clone();
+        input.splice(
+            2 * BLOCK_SIZE as usize..,
+            header("next", b'0', 0, USTAR_MAGIC),
+        );
+        input[2 * BLOCK_SIZE as usize] = b'N';
+        let tar = TarFormat::new(&input).unwrap();
+        assert_eq!(
+            tar.warnings,
+            ["The header at 0x400 has a wrong checksum, the archive ends before it"]
+        );
+        assert!(TarFormat::new(&input[2 * BLOCK_SIZE as usize..]).is_err());
+    }
+}
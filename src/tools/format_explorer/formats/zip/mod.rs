@@ -0,0 +1,925 @@
+//! ZIP archives, and the formats built on them like JAR, APK and Office documents
+
+use miniz_oxide::inflate::TINFLStatus;
+
+use super::Confidence;
+use crate::{
+    error::Error,
+    tools::format_explorer::{text_field, FileFormatUi, FormatContext, Layout},
+};
+
+mod records;
+
+use records::{
+    method_name, CentralHeader, DataDescriptor, EndRecord, ExtraField, LocalHeader, Zip64EndRecord,
+    Zip64Locator, CENTRAL_SIGNATURE, END_SIGNATURE, FLAG_DATA_DESCRIPTOR, FLAG_ENCRYPTED,
+    LOCAL_SIGNATURE, ZIP64_END_SIGNATURE, ZIP64_EXTRA, ZIP64_LOCATOR_SIGNATURE, ZIP64_MARKER,
+};
+
+/// Central directories with more entries than this are cut short
+const MAX_ENTRIES: u64 = 1_000_000;
+/// Entries shown at most, more would make the explorer crawl
+const SHOWN_ENTRIES: usize = 10_000;
+/// Entries decompress to at most this much, a bigger one is more likely a bomb than something
+/// to look at
+const MAX_EXTRACTED: usize = 1 << 30;
+
+/// Little-endian signature at `offset`, `None` past the end of the file
+fn signature(input: &[u8], offset: u64) -> Option<u32> {
+    let bytes = slice(input, offset, 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// `length` bytes at `offset`, `None` if they aren't all in the file
+fn slice(input: &[u8], offset: u64, length: u64) -> Option<&[u8]> {
+    let start = usize::try_from(offset).ok()?;
+    let end = start.checked_add(usize::try_from(length).ok()?)?;
+    input.get(start..end)
+}
+
+fn text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Checks for a local header at the start, or an end record at the end for archives with
+/// something in front of them, like self-extracting ones
+pub fn probe(bytes: &[u8]) -> Confidence {
+    match signature(bytes, 0) {
+        Some(LOCAL_SIGNATURE | END_SIGNATURE) => Confidence::High,
+        _ if find_end(bytes).is_some() => Confidence::Low,
+        _ => Confidence::None,
+    }
+}
+
+/// Offset of the end of central directory record. It's followed by a comment of up to 64 KiB,
+/// so it's searched for backwards from the end of the file.
+fn find_end(bytes: &[u8]) -> Option<u64> {
+    let last = bytes.len().checked_sub(EndRecord::SIZE as usize)?;
+    let first = last.saturating_sub(u16::MAX as usize);
+    (first..=last)
+        .rev()
+        .find(|&offset| {
+            let comment_length = u16::from_le_bytes([bytes[offset + 20], bytes[offset + 21]]);
+            bytes[offset..offset + 4] == END_SIGNATURE.to_le_bytes()
+                && offset + EndRecord::SIZE as usize + comment_length as usize <= bytes.len()
+        })
+        .map(|offset| offset as u64)
+}
+
+/// A file or directory in the archive, from its central directory header and its local header
+pub struct Entry {
+    pub name: String,
+    /// Where the central directory header is and what it says, `None` for entries found by
+    /// walking the local headers
+    pub central: Option<(u64, CentralHeader)>,
+    pub central_extra: Vec<ExtraField>,
+    pub comment: String,
+    pub local_offset: u64,
+    pub local: Option<LocalHeader>,
+    pub local_name: String,
+    pub local_extra: Vec<ExtraField>,
+    pub descriptor: Option<DataDescriptor>,
+    /// Flags, method and CRC from the central directory if there is one
+    pub flags: u16,
+    pub method: u16,
+    pub crc32: u32,
+    /// Sizes with the ZIP64 values in place of the saturated 32-bit ones
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    /// Where the compressed data starts, `None` if the local header can't be read
+    pub data_offset: Option<u64>,
+    /// Headers that don't agree with each other or point outside of the file
+    pub warnings: Vec<String>,
+}
+
+impl Entry {
+    /// Reads the central directory header at `offset` and the local header it points to.
+    /// Returns the entry and the size of the central header, `None` if it doesn't fit in the
+    /// file.
+    fn from_central(input: &[u8], offset: u64, prefix: u64) -> Option<(Self, u64)> {
+        let (_, header) =
+            CentralHeader::parse(slice(input, offset, CentralHeader::SIZE)?, false).ok()?;
+        let mut warnings = Vec::new();
+        let name_offset = offset + CentralHeader::SIZE;
+        let extra_offset = name_offset + header.name_length as u64;
+        let comment_offset = extra_offset + header.extra_length as u64;
+        let size = comment_offset + header.comment_length as u64 - offset;
+        let name = slice(input, name_offset, header.name_length.into()).map_or_else(
+            || {
+                warnings.push("The name goes past the end of the file".to_string());
+                String::new()
+            },
+            text,
+        );
+        let extra = slice(input, extra_offset, header.extra_length.into()).unwrap_or_default();
+        let (central_extra, warning) = ExtraField::parse_all(extra, extra_offset);
+        warnings.extend(warning);
+        let comment = slice(input, comment_offset, header.comment_length.into())
+            .map(text)
+            .unwrap_or_default();
+
+        // ZIP64 values replace the saturated fields, in the order of the fields
+        let mut zip64 = central_extra
+            .iter()
+            .find(|field| field.id == ZIP64_EXTRA)
+            .map(ExtraField::zip64_values)
+            .unwrap_or_default()
+            .into_iter();
+        let mut widen = |name: &str, value: u32| {
+            if value != ZIP64_MARKER {
+                return value as u64;
+            }
+            zip64.next().unwrap_or_else(|| {
+                warnings.push(format!("The {} is saturated but has no ZIP64 value", name));
+                value as u64
+            })
+        };
+        let uncompressed_size = widen("uncompressed size", header.uncompressed_size);
+        let compressed_size = widen("compressed size", header.compressed_size);
+        let local_offset = widen("local header offset", header.local_header_offset);
+
+        let mut entry = Self {
+            name,
+            flags: header.flags,
+            method: header.method,
+            crc32: header.crc32,
+            central: Some((offset, header)),
+            central_extra,
+            comment,
+            local_offset: local_offset.saturating_add(prefix),
+            local: None,
+            local_name: String::new(),
+            local_extra: Vec::new(),
+            descriptor: None,
+            compressed_size,
+            uncompressed_size,
+            data_offset: None,
+            warnings,
+        };
+        entry.read_local(input);
+        entry.compare_headers();
+        Some((entry, size))
+    }
+
+    /// Reads the local header at `offset`, for archives without a usable central directory
+    fn from_local(input: &[u8], offset: u64) -> Self {
+        let mut entry = Self {
+            name: String::new(),
+            central: None,
+            central_extra: Vec::new(),
+            comment: String::new(),
+            local_offset: offset,
+            local: None,
+            local_name: String::new(),
+            local_extra: Vec::new(),
+            descriptor: None,
+            flags: 0,
+            method: 0,
+            crc32: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            data_offset: None,
+            warnings: Vec::new(),
+        };
+        entry.read_local(input);
+        if let Some(local) = &entry.local {
+            entry.name = entry.local_name.clone();
+            entry.flags = local.flags;
+            entry.method = local.method;
+            let (crc32, compressed_size, uncompressed_size) = entry.local_values();
+            entry.crc32 = crc32;
+            entry.compressed_size = compressed_size;
+            entry.uncompressed_size = uncompressed_size;
+        }
+        entry
+    }
+
+    fn read_local(&mut self, input: &[u8]) {
+        let offset = self.local_offset;
+        let header = slice(input, offset, LocalHeader::SIZE)
+            .and_then(|bytes| LocalHeader::parse(bytes, false).ok());
+        let Some((_, header)) = header else {
+            self.warnings.push(format!(
+                "The local header at {:#x} is past the end of the file",
+                offset
+            ));
+            return;
+        };
+        if header.signature != LOCAL_SIGNATURE {
+            self.warnings
+                .push(format!("There is no local header at {:#x}", offset));
+            return;
+        }
+        let name_offset = offset + LocalHeader::SIZE;
+        let extra_offset = name_offset + header.name_length as u64;
+        let data_offset = extra_offset + header.extra_length as u64;
+        self.local_name = slice(input, name_offset, header.name_length.into())
+            .map(text)
+            .unwrap_or_default();
+        let extra = slice(input, extra_offset, header.extra_length.into()).unwrap_or_default();
+        let (local_extra, warning) = ExtraField::parse_all(extra, extra_offset);
+        self.local_extra = local_extra;
+        self.warnings.extend(warning);
+        self.data_offset = Some(data_offset);
+        self.local = Some(header);
+
+        let (_, compressed_size, _) = self.local_values();
+        let compressed_size = if self.central.is_some() {
+            self.compressed_size
+        } else {
+            compressed_size
+        };
+        let data_end = data_offset.saturating_add(compressed_size);
+        if data_end > input.len() as u64 {
+            self.warnings
+                .push("The data goes past the end of the file".to_string());
+        }
+        // Without a central directory the sizes are only known if the local header has them
+        let located = self.central.is_some() || compressed_size != 0;
+        if self.local.as_ref().unwrap().flags & FLAG_DATA_DESCRIPTOR != 0 && located {
+            let zip64 = self.local_extra.iter().any(|field| field.id == ZIP64_EXTRA);
+            self.descriptor = usize::try_from(data_end)
+                .ok()
+                .and_then(|start| input.get(start..))
+                .and_then(|bytes| DataDescriptor::parse(bytes, data_end, zip64));
+        }
+    }
+
+    /// CRC and sizes as the local header has them, from the ZIP64 extra field or the data
+    /// descriptor if it defers to them
+    fn local_values(&self) -> (u32, u64, u64) {
+        let Some(local) = &self.local else {
+            return (0, 0, 0);
+        };
+        if let Some(descriptor) = &self.descriptor {
+            return (
+                descriptor.crc32,
+                descriptor.compressed_size,
+                descriptor.uncompressed_size,
+            );
+        }
+        // Local ZIP64 blocks have both sizes, uncompressed first
+        let zip64 = self
+            .local_extra
+            .iter()
+            .find(|field| field.id == ZIP64_EXTRA)
+            .map(ExtraField::zip64_values)
+            .unwrap_or_default();
+        let saturated =
+            local.compressed_size == ZIP64_MARKER || local.uncompressed_size == ZIP64_MARKER;
+        match zip64.as_slice() {
+            [uncompressed, compressed, ..] if saturated => {
+                (local.crc32, *compressed, *uncompressed)
+            }
+            _ => (
+                local.crc32,
+                local.compressed_size.into(),
+                local.uncompressed_size.into(),
+            ),
+        }
+    }
+
+    /// Notes where the local header says something else than the central directory
+    fn compare_headers(&mut self) {
+        let (Some(local), Some((_, central))) = (&self.local, &self.central) else {
+            return;
+        };
+        let mut differences = Vec::new();
+        let mut compare = |what: &str, local: String, central: String| {
+            if local != central {
+                differences.push(format!(
+                    "The {} is {} in the local header but {} in the central directory",
+                    what, local, central
+                ));
+            }
+        };
+        compare(
+            "name",
+            format!("{:?}", self.local_name),
+            format!("{:?}", self.name),
+        );
+        compare(
+            "version needed",
+            local.version_needed.to_string(),
+            central.version_needed.to_string(),
+        );
+        compare(
+            "flags",
+            format!("{:#x}", local.flags),
+            format!("{:#x}", central.flags),
+        );
+        compare(
+            "method",
+            method_name(local.method),
+            method_name(central.method),
+        );
+        compare(
+            "modification time",
+            format!(
+                "{:#x}",
+                (local.modified_date as u32) << 16 | local.modified_time as u32
+            ),
+            format!(
+                "{:#x}",
+                (central.modified_date as u32) << 16 | central.modified_time as u32
+            ),
+        );
+        // With a data descriptor the local header may have zeros, the descriptor is checked
+        let deferred = local.flags & FLAG_DATA_DESCRIPTOR != 0 && self.descriptor.is_none();
+        if !deferred {
+            let (crc32, compressed_size, uncompressed_size) = self.local_values();
+            compare(
+                "CRC-32",
+                format!("{:#010x}", crc32),
+                format!("{:#010x}", self.crc32),
+            );
+            compare(
+                "compressed size",
+                compressed_size.to_string(),
+                self.compressed_size.to_string(),
+            );
+            compare(
+                "uncompressed size",
+                uncompressed_size.to_string(),
+                self.uncompressed_size.to_string(),
+            );
+        }
+        self.warnings.extend(differences);
+    }
+
+    fn is_directory(&self) -> bool {
+        self.name.ends_with('/') && self.uncompressed_size == 0
+    }
+
+    /// The decompressed contents, `input` is the whole archive
+    fn extract(&self, input: &[u8]) -> Result<Vec<u8>, Error> {
+        let error = |message: String| Error::parse("ZIP", format!("{}: {}", self.name, message));
+        if self.flags & FLAG_ENCRYPTED != 0 {
+            return Err(error("the entry is encrypted".to_string()));
+        }
+        let data = self
+            .data_offset
+            .and_then(|offset| slice(input, offset, self.compressed_size))
+            .ok_or_else(|| error("the data isn't all in the file".to_string()))?;
+        let bytes = match self.method {
+            0 => data.to_vec(),
+            8 => miniz_oxide::inflate::decompress_to_vec_with_limit(data, MAX_EXTRACTED).map_err(
+                |err| match err.status {
+                    TINFLStatus::HasMoreOutput => error(format!(
+                        "it decompresses to more than {} MiB",
+                        MAX_EXTRACTED >> 20
+                    )),
+                    _ => error("the deflate data is broken".to_string()),
+                },
+            )?,
+            method => {
+                return Err(error(format!(
+                    "{} entries can't be decompressed",
+                    method_name(method)
+                )))
+            }
+        };
+        let crc32 = crc32fast::hash(&bytes);
+        if crc32 != self.crc32 {
+            return Err(error(format!(
+                "the CRC-32 is {:#010x} but the headers say {:#010x}",
+                crc32, self.crc32
+            )));
+        }
+        Ok(bytes)
+    }
+
+    /// Bytes from the local header to the end of the data
+    fn span(&self) -> (u64, u64) {
+        let end = match self.data_offset {
+            Some(offset) => offset.saturating_add(self.compressed_size),
+            None => self.local_offset,
+        };
+        (self.local_offset, end - self.local_offset)
+    }
+}
+
+impl FileFormatUi for Entry {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        let (offset, length) = self.span();
+        context.at(offset, length).collapsing(ui, name, |ui| {
+            for warning in &self.warnings {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+            text_field(ui, "method", &method_name(self.method));
+            text_field(ui, "compressed size", &context.number(self.compressed_size));
+            text_field(
+                ui,
+                "uncompressed size",
+                &context.number(self.uncompressed_size),
+            );
+            text_field(ui, "CRC-32", &format!("{:#010x}", self.crc32));
+            if let Some(data_offset) = self.data_offset {
+                ui.horizontal(|ui| {
+                    context.range_link(ui, "data", data_offset, self.compressed_size);
+                    if !self.is_directory()
+                        && ui
+                            .button("Open")
+                            .on_hover_text("Decompress the entry into a new document")
+                            .clicked()
+                    {
+                        let file_name = self.name.rsplit('/').next().unwrap_or_default();
+                        context.open(file_name, |bytes| self.extract(bytes));
+                    }
+                });
+            }
+            if let Some(local) = &mut self.local {
+                let header = context.at(self.local_offset, LocalHeader::SIZE);
+                local.ui(ui, "local header", &header);
+                let name_offset = self.local_offset + LocalHeader::SIZE;
+                context.at(name_offset, local.name_length.into()).field(
+                    ui,
+                    "local name",
+                    &format!("{:?}", self.local_name),
+                );
+                extra_ui(ui, "local extra", &mut self.local_extra, context);
+            }
+            if let Some(descriptor) = &self.descriptor {
+                let title = "data descriptor";
+                context
+                    .at(descriptor.offset, descriptor.size)
+                    .collapsing(ui, title, |ui| {
+                        text_field(ui, "CRC-32", &format!("{:#010x}", descriptor.crc32));
+                        let compressed = context.number(descriptor.compressed_size);
+                        text_field(ui, "compressed size", &compressed);
+                        let uncompressed = context.number(descriptor.uncompressed_size);
+                        text_field(ui, "uncompressed size", &uncompressed);
+                    });
+            }
+            if let Some((offset, central)) = &mut self.central {
+                central.ui(
+                    ui,
+                    "central header",
+                    &context.at(*offset, CentralHeader::SIZE),
+                );
+                let name_offset = *offset + CentralHeader::SIZE;
+                let name_length = central.name_length as u64;
+                context
+                    .at(name_offset, name_length)
+                    .field(ui, "name", &format!("{:?}", self.name));
+                extra_ui(ui, "central extra", &mut self.central_extra, context);
+                if !self.comment.is_empty() {
+                    let comment_offset = name_offset + name_length + central.extra_length as u64;
+                    context
+                        .at(comment_offset, central.comment_length.into())
+                        .field(ui, "comment", &format!("{:?}", self.comment));
+                }
+            }
+        });
+    }
+}
+
+fn extra_ui(ui: &mut egui::Ui, name: &str, fields: &mut [ExtraField], context: &FormatContext) {
+    if fields.is_empty() {
+        return;
+    }
+    ui.collapsing(format!("{} ({})", name, fields.len()), |ui| {
+        for (index, field) in fields.iter_mut().enumerate() {
+            ui.push_id(index, |ui| field.ui(ui, "", context));
+        }
+    });
+}
+
+pub struct ZipFormat {
+    /// Where the end of central directory record is and what it says
+    end: Option<(u64, EndRecord)>,
+    comment: String,
+    zip64_locator: Option<(u64, Zip64Locator)>,
+    zip64_end: Option<(u64, Zip64EndRecord)>,
+    /// Where the central directory is and how long it is, as found
+    central_directory: Option<(u64, u64)>,
+    entries: Vec<Entry>,
+    /// Records that don't agree with each other or point outside of the file
+    warnings: Vec<String>,
+}
+
+impl ZipFormat {
+    pub fn new(input: &[u8]) -> Result<Self, Error> {
+        let mut zip = Self {
+            end: None,
+            comment: String::new(),
+            zip64_locator: None,
+            zip64_end: None,
+            central_directory: None,
+            entries: Vec::new(),
+            warnings: Vec::new(),
+        };
+        let Some(end_offset) = find_end(input) else {
+            // Cut off archives still have their local headers
+            if signature(input, 0) != Some(LOCAL_SIGNATURE) {
+                return Err(Error::parse(
+                    "ZIP",
+                    "there is no end of central directory record",
+                ));
+            }
+            zip.warnings.push(
+                "There is no end of central directory record, the entries come from the local \
+                 headers"
+                    .to_string(),
+            );
+            zip.walk_local_headers(input);
+            zip.check_overlaps();
+            return Ok(zip);
+        };
+        let bytes = slice(input, end_offset, EndRecord::SIZE).unwrap();
+        let (_, end) =
+            EndRecord::parse(bytes, false).map_err(|err| Error::from_nom("ZIP", bytes, err))?;
+        let comment_offset = end_offset + EndRecord::SIZE;
+        zip.comment = slice(input, comment_offset, end.comment_length.into())
+            .map(text)
+            .unwrap_or_default();
+        if end.disk_number != 0 || end.central_directory_disk != 0 {
+            zip.warnings
+                .push("The archive is split over several files, only this one is read".to_string());
+        }
+        zip.read_zip64(input, end_offset, &end);
+
+        let (entry_count, directory_size, directory_offset) = match &zip.zip64_end {
+            Some((_, zip64)) => (
+                zip64.total_entries,
+                zip64.central_directory_size,
+                zip64.central_directory_offset,
+            ),
+            None => (
+                end.total_entries.into(),
+                end.central_directory_size.into(),
+                end.central_directory_offset.into(),
+            ),
+        };
+        // Offsets are from the start of the archive, which isn't the start of the file when
+        // something like a self-extractor comes first
+        let directory_end = zip
+            .zip64_end
+            .as_ref()
+            .map_or(end_offset, |(offset, _)| *offset);
+        let expected_end = directory_offset.saturating_add(directory_size);
+        let prefix = directory_end
+            .checked_sub(expected_end)
+            .filter(|&prefix| {
+                prefix > 0 && signature(input, directory_offset + prefix) == Some(CENTRAL_SIGNATURE)
+            })
+            .unwrap_or(0);
+        if prefix > 0 {
+            zip.warnings.push(format!(
+                "The archive starts after {:#x} bytes of other data, which its offsets don't count",
+                prefix
+            ));
+        } else if expected_end != directory_end {
+            zip.warnings.push(format!(
+                "The central directory should end at {:#x}, but the end record is at {:#x}",
+                expected_end, directory_end
+            ));
+        }
+        zip.end = Some((end_offset, end));
+
+        let start = directory_offset.saturating_add(prefix);
+        let mut position = start;
+        for _ in 0..entry_count.min(MAX_ENTRIES) {
+            if signature(input, position) != Some(CENTRAL_SIGNATURE) {
+                zip.warnings.push(format!(
+                    "There is no central directory header at {:#x}",
+                    position
+                ));
+                break;
+            }
+            let Some((entry, size)) = Entry::from_central(input, position, prefix) else {
+                zip.warnings.push(format!(
+                    "The central directory header at {:#x} goes past the end of the file",
+                    position
+                ));
+                break;
+            };
+            zip.entries.push(entry);
+            position += size;
+        }
+        zip.central_directory = Some((start, position - start));
+        if zip.entries.len() as u64 != entry_count {
+            zip.warnings.push(format!(
+                "The end record counts {} entries but the central directory has {}",
+                entry_count,
+                zip.entries.len()
+            ));
+        } else if position - start != directory_size {
+            zip.warnings.push(format!(
+                "The central directory is {} bytes but the end record says {}",
+                position - start,
+                directory_size
+            ));
+        }
+        zip.check_overlaps();
+        Ok(zip)
+    }
+
+    /// Finds the ZIP64 locator right before the end record and the record it points to
+    fn read_zip64(&mut self, input: &[u8], end_offset: u64, end: &EndRecord) {
+        let locator = end_offset
+            .checked_sub(Zip64Locator::SIZE)
+            .filter(|&offset| signature(input, offset) == Some(ZIP64_LOCATOR_SIGNATURE))
+            .and_then(|offset| {
+                let bytes = slice(input, offset, Zip64Locator::SIZE)?;
+                Some((offset, Zip64Locator::parse(bytes, false).ok()?.1))
+            });
+        let Some((locator_offset, locator)) = locator else {
+            if end.needs_zip64() {
+                self.warnings.push(
+                    "The end record has saturated fields but there is no ZIP64 locator".to_string(),
+                );
+            }
+            return;
+        };
+        // Without extensible data the record is right before the locator, where it ends up
+        // when the archive has something in front of it
+        let mut offset = locator.end_offset;
+        if signature(input, offset) != Some(ZIP64_END_SIGNATURE) {
+            let adjacent = locator_offset.saturating_sub(Zip64EndRecord::SIZE);
+            if signature(input, adjacent) == Some(ZIP64_END_SIGNATURE) {
+                offset = adjacent;
+            } else {
+                self.warnings.push(format!(
+                    "The ZIP64 locator points at {:#x}, where there is no ZIP64 end record",
+                    locator.end_offset
+                ));
+            }
+        }
+        self.zip64_locator = Some((locator_offset, locator));
+        let record = slice(input, offset, Zip64EndRecord::SIZE)
+            .filter(|_| signature(input, offset) == Some(ZIP64_END_SIGNATURE))
+            .and_then(|bytes| Zip64EndRecord::parse(bytes, false).ok());
+        if let Some((_, record)) = record {
+            self.zip64_end = Some((offset, record));
+        }
+    }
+
+    /// Goes from one local header to the next, as far as their sizes tell
+    fn walk_local_headers(&mut self, input: &[u8]) {
+        let mut position = 0;
+        while signature(input, position) == Some(LOCAL_SIGNATURE)
+            && (self.entries.len() as u64) < MAX_ENTRIES
+        {
+            let entry = Entry::from_local(input, position);
+            let Some(data_offset) = entry.data_offset else {
+                self.entries.push(entry);
+                break;
+            };
+            if entry.flags & FLAG_DATA_DESCRIPTOR != 0 && entry.descriptor.is_none() {
+                self.warnings.push(format!(
+                    "{} has its sizes after the data, the entries after it can't be found",
+                    entry.name
+                ));
+                self.entries.push(entry);
+                break;
+            }
+            let descriptor_size = entry.descriptor.as_ref().map_or(0, |d| d.size);
+            position = data_offset
+                .saturating_add(entry.compressed_size)
+                .saturating_add(descriptor_size);
+            self.entries.push(entry);
+        }
+    }
+
+    /// Warns about entries sharing compressed data, which zip bombs do
+    fn check_overlaps(&mut self) {
+        let mut ranges = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                let start = entry.data_offset?;
+                Some((start, start.saturating_add(entry.compressed_size), index))
+            })
+            .filter(|(start, end, _)| end > start)
+            .collect::<Vec<_>>();
+        ranges.sort_unstable();
+        for pair in ranges.windows(2) {
+            let ((_, end, index), (start, _, other)) = (pair[0], pair[1]);
+            if start < end {
+                self.entries[other]
+                    .warnings
+                    .push(format!("The data overlaps that of entry {}", index));
+            }
+        }
+    }
+
+    /// Offsets and sizes of the records, for expressions
+    pub fn symbols(&self) -> Vec<(String, u64)> {
+        let mut symbols = vec![("entries".to_string(), self.entries.len() as u64)];
+        if let Some((offset, size)) = self.central_directory {
+            symbols.push(("central_directory".to_string(), offset));
+            symbols.push(("central_directory_size".to_string(), size));
+        }
+        if let Some((offset, _)) = self.end {
+            symbols.push(("end_of_central_directory".to_string(), offset));
+        }
+        if let Some((offset, _)) = self.zip64_end {
+            symbols.push(("zip64_end_of_central_directory".to_string(), offset));
+        }
+        symbols
+    }
+}
+
+impl FileFormatUi for ZipFormat {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        ui.collapsing(name, |ui| {
+            for warning in &self.warnings {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+            if let Some((offset, end)) = &mut self.end {
+                let record = context.at(*offset, EndRecord::SIZE);
+                end.ui(ui, "end of central directory", &record);
+                if !self.comment.is_empty() {
+                    context
+                        .at(*offset + EndRecord::SIZE, end.comment_length.into())
+                        .field(ui, "comment", &format!("{:?}", self.comment));
+                }
+            }
+            if let Some((offset, locator)) = &mut self.zip64_locator {
+                let record = context.at(*offset, Zip64Locator::SIZE);
+                locator.ui(ui, "ZIP64 locator", &record);
+            }
+            if let Some((offset, zip64)) = &mut self.zip64_end {
+                let record = context.at(*offset, Zip64EndRecord::SIZE);
+                zip64.ui(ui, "ZIP64 end of central directory", &record);
+            }
+            if let Some((offset, size)) = self.central_directory {
+                context.range_link(ui, "central directory", offset, size);
+            }
+            let title = format!("entries ({})", self.entries.len());
+            ui.collapsing(title, |ui| {
+                for (index, entry) in self.entries.iter_mut().take(SHOWN_ENTRIES).enumerate() {
+                    let title = format!("[{}] {}", index, entry.name);
+                    ui.push_id(index, |ui| entry.ui(ui, &title, context));
+                }
+                if self.entries.len() > SHOWN_ENTRIES {
+                    ui.weak(format!("… {} more", self.entries.len() - SHOWN_ENTRIES));
+                }
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! The archives were written by Python's zipfile, `descriptors.zip` to a stream it couldn't
+    //! seek back in and `zip64.zip` with its ZIP64 limit lowered to zero.
+
+    use super::*;
+
+    const COMMENT: &[u8] = include_bytes!("fixtures/comment.zip");
+    const DESCRIPTORS: &[u8] = include_bytes!("fixtures/descriptors.zip");
+    const ZIP64: &[u8] = include_bytes!("fixtures/zip64.zip");
+
+    const HELLO: &[u8] = b"Hello, hello, hello from a deflated entry\n";
+
+    fn names(zip: &ZipFormat) -> Vec<&str> {
+        zip.entries
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn end_record_behind_comment() {
+        assert_eq!(probe(COMMENT), Confidence::High);
+        let zip = ZipFormat::new(COMMENT).unwrap();
+        assert!(zip.warnings.is_empty(), "{:?}", zip.warnings);
+        assert_eq!(zip.comment, "An archive comment");
+        let end_offset = zip.end.as_ref().unwrap().0;
+        assert_eq!(end_offset, (COMMENT.len() - 22 - zip.comment.len()) as u64);
+        assert_eq!(names(&zip), ["hello.txt", "dir/", "dir/stored.bin"]);
+        assert!(zip.entries.iter().all(|entry| entry.warnings.is_empty()));
+        assert!(zip.entries[1].is_directory());
+        assert_eq!(zip.entries[0].extract(COMMENT).unwrap(), HELLO.repeat(4));
+        assert_eq!(
+            zip.entries[2].extract(COMMENT).unwrap(),
+            (0..16).collect::<Vec<u8>>()
+        );
+
+        // A signature in the comment without room for a record after it isn't taken for one
+        let comment = [&b"see "[..], &END_SIGNATURE.to_le_bytes(), b" before"].concat();
+        let mut input = COMMENT[..COMMENT.len() - zip.comment.len()].to_vec();
+        let length = end_offset as usize + 20;
+        input[length..length + 2].copy_from_slice(&(comment.len() as u16).to_le_bytes());
+        input.extend(comment);
+        let zip = ZipFormat::new(&input).unwrap();
+        assert_eq!(zip.end.as_ref().unwrap().0, end_offset);
+        assert_eq!(zip.entries.len(), 3);
+    }
+
+    #[test]
+    fn zip64_records() {
+        let zip = ZipFormat::new(ZIP64).unwrap();
+        assert!(zip.warnings.is_empty(), "{:?}", zip.warnings);
+        let (end_offset, _) = zip.end.as_ref().unwrap();
+        let (locator_offset, locator) = zip.zip64_locator.as_ref().unwrap();
+        assert_eq!(*locator_offset, end_offset - Zip64Locator::SIZE);
+        let (zip64_offset, record) = zip.zip64_end.as_ref().unwrap();
+        assert_eq!(*zip64_offset, locator.end_offset);
+        assert_eq!(record.total_entries, 1);
+        assert_eq!(names(&zip), ["hello.txt"]);
+        let entry = &zip.entries[0];
+        assert!(entry.warnings.is_empty(), "{:?}", entry.warnings);
+        assert_eq!(entry.uncompressed_size, 168);
+        assert_eq!(entry.extract(ZIP64).unwrap(), HELLO.repeat(4));
+
+        // Locators pointing elsewhere fall back to the record right before them, where it is
+        // when something comes before the archive
+        let mut input = ZIP64.to_vec();
+        let pointer = *locator_offset as usize + 8;
+        input[pointer..pointer + 8].copy_from_slice(&0x1234u64.to_le_bytes());
+        let zip = ZipFormat::new(&input).unwrap();
+        assert_eq!(zip.zip64_end.as_ref().unwrap().0, *zip64_offset);
+        assert_eq!(zip.entries.len(), 1);
+        // With nothing right before the locator either, the entries come from the end record
+        let adjacent = *zip64_offset as usize;
+        input[adjacent] = 0;
+        let zip = ZipFormat::new(&input).unwrap();
+        assert!(zip.zip64_end.is_none());
+        assert_eq!(
+            zip.warnings[0],
+            "The ZIP64 locator points at 0x1234, where there is no ZIP64 end record"
+        );
+    }
+
+    #[test]
+    fn data_descriptors() {
+        let zip = ZipFormat::new(DESCRIPTORS).unwrap();
+        assert!(zip.warnings.is_empty(), "{:?}", zip.warnings);
+        assert_eq!(names(&zip), ["first.txt", "second.txt"]);
+        for entry in &zip.entries {
+            assert!(entry.warnings.is_empty(), "{:?}", entry.warnings);
+            // The local header leaves the CRC and sizes to the descriptor
+            let local = entry.local.as_ref().unwrap();
+            assert_eq!((local.crc32, local.compressed_size), (0, 0));
+            let descriptor = entry.descriptor.as_ref().unwrap();
+            assert_eq!(descriptor.crc32, entry.crc32);
+            assert_eq!(descriptor.compressed_size, entry.compressed_size);
+        }
+        assert_eq!(
+            zip.entries[0].extract(DESCRIPTORS).unwrap(),
+            HELLO.repeat(4)
+        );
+        assert_eq!(zip.entries[1].extract(DESCRIPTORS).unwrap(), b"second\n");
+
+        // Without the central directory nothing tells where the deflate data ends
+        let central = zip.central_directory.unwrap().0 as usize;
+        let zip = ZipFormat::new(&DESCRIPTORS[..central]).unwrap();
+        assert_eq!(names(&zip), ["first.txt"]);
+        assert_eq!(
+            zip.warnings[1],
+            "first.txt has its sizes after the data, the entries after it can't be found"
+        );
+    }
+
+    #[test]
+    fn self_extractor_prefix() {
+        let prefix = [&b"MZ"[..], &[0; 0xfe]].concat();
+        let input = [&prefix[..], COMMENT].concat();
+        assert_eq!(probe(&input), Confidence::Low);
+        let zip = ZipFormat::new(&input).unwrap();
+        assert_eq!(
+            zip.warnings,
+            ["The archive starts after 0x100 bytes of other data, which its offsets don't count"]
+        );
+        assert_eq!(zip.entries[0].local_offset, 0x100);
+        assert!(zip.entries.iter().all(|entry| entry.warnings.is_empty()));
+        assert_eq!(zip.entries[0].extract(&input).unwrap(), HELLO.repeat(4));
+    }
+
+    #[test]
+    fn header_mismatches() {
+        let zip = ZipFormat::new(COMMENT).unwrap();
+        let (central_offset, _) = zip.entries[0].central.as_ref().unwrap();
+        let central_offset = *central_offset as usize;
+        let mut input = COMMENT.to_vec();
+        // The name in the local header, and the CRC-32 in the central one
+        input[LocalHeader::SIZE as usize] = b'j';
+        input[central_offset + 16] ^= 0xff;
+        let zip = ZipFormat::new(&input).unwrap();
+        let entry = &zip.entries[0];
+        assert_eq!(entry.warnings.len(), 2, "{:?}", entry.warnings);
+        assert_eq!(
+            entry.warnings[0],
+            "The name is \"jello.txt\" in the local header but \"hello.txt\" in the central \
+             directory"
+        );
+        assert!(entry.warnings[1].starts_with("The CRC-32 is 0x880444f1 in the local header"));
+        let error = entry.extract(&input).unwrap_err().to_string();
+        assert!(
+            error.contains("hello.txt: the CRC-32 is 0x880444f1 but the headers say"),
+            "{}",
+            error
+        );
+
+        // Broken deflate data
+        let data_offset = entry.data_offset.unwrap() as usize;
+        input[data_offset] = 0xff;
+        let zip = ZipFormat::new(&input).unwrap();
+        let error = zip.entries[0].extract(&input).unwrap_err().to_string();
+        assert!(error.contains("the deflate data is broken"), "{}", error);
+    }
+}
@@ -0,0 +1,397 @@
+use nom::{
+    number::complete::{le_u16, le_u32, le_u64, u8},
+    sequence::Tuple,
+    IResult,
+};
+
+use super::super::{hex_bytes, unix_time};
+use crate::{
+    time,
+    tools::format_explorer::{FileFormat, FileFormatUi, FormatContext},
+};
+
+pub const LOCAL_SIGNATURE: u32 = 0x0403_4b50;
+pub const CENTRAL_SIGNATURE: u32 = 0x0201_4b50;
+pub const END_SIGNATURE: u32 = 0x0605_4b50;
+pub const ZIP64_END_SIGNATURE: u32 = 0x0606_4b50;
+pub const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+pub const DESCRIPTOR_SIGNATURE: u32 = 0x0807_4b50;
+
+/// 32-bit sizes and offsets set to this have their value in the ZIP64 extra field
+pub const ZIP64_MARKER: u32 = u32::MAX;
+
+pub const FLAG_ENCRYPTED: u16 = 0x1;
+/// CRC and sizes follow the data instead of being in the local header
+pub const FLAG_DATA_DESCRIPTOR: u16 = 0x8;
+
+const GENERAL_FLAGS: [(u64, &str); 9] = [
+    (0x1, "ENCRYPTED"),
+    (0x2, "COMPRESSION_OPTION_1"),
+    (0x4, "COMPRESSION_OPTION_2"),
+    (0x8, "DATA_DESCRIPTOR"),
+    (0x10, "ENHANCED_DEFLATE"),
+    (0x20, "PATCHED_DATA"),
+    (0x40, "STRONG_ENCRYPTION"),
+    (0x800, "UTF8"),
+    (0x2000, "MASKED_HEADERS"),
+];
+
+#[derive(FileFormat)]
+#[format(little_endian)]
+pub struct LocalHeader {
+    #[format(hex)]
+    pub signature: u32,
+    #[format(meaning = version_name)]
+    pub version_needed: u16,
+    #[format(flags = GENERAL_FLAGS)]
+    pub flags: u16,
+    #[format(meaning = method_name)]
+    pub method: u16,
+    #[format(meaning = dos_time)]
+    pub modified_time: u16,
+    #[format(meaning = dos_date)]
+    pub modified_date: u16,
+    #[format(hex)]
+    pub crc32: u32,
+    pub compressed_size: u32,
+    pub uncompressed_size: u32,
+    pub name_length: u16,
+    pub extra_length: u16,
+}
+
+#[derive(FileFormat)]
+#[format(little_endian)]
+pub struct CentralHeader {
+    #[format(hex)]
+    pub signature: u32,
+    #[format(meaning = made_by_name)]
+    pub version_made_by: u16,
+    #[format(meaning = version_name)]
+    pub version_needed: u16,
+    #[format(flags = GENERAL_FLAGS)]
+    pub flags: u16,
+    #[format(meaning = method_name)]
+    pub method: u16,
+    #[format(meaning = dos_time)]
+    pub modified_time: u16,
+    #[format(meaning = dos_date)]
+    pub modified_date: u16,
+    #[format(hex)]
+    pub crc32: u32,
+    pub compressed_size: u32,
+    pub uncompressed_size: u32,
+    pub name_length: u16,
+    pub extra_length: u16,
+    pub comment_length: u16,
+    pub disk_start: u16,
+    #[format(hex)]
+    pub internal_attributes: u16,
+    #[format(hex)]
+    pub external_attributes: u32,
+    #[format(hex)]
+    pub local_header_offset: u32,
+}
+
+#[derive(FileFormat)]
+#[format(little_endian)]
+pub struct EndRecord {
+    #[format(hex)]
+    pub signature: u32,
+    pub disk_number: u16,
+    pub central_directory_disk: u16,
+    pub disk_entries: u16,
+    pub total_entries: u16,
+    pub central_directory_size: u32,
+    #[format(hex)]
+    pub central_directory_offset: u32,
+    pub comment_length: u16,
+}
+
+impl EndRecord {
+    /// Whether a field is too small for its value, which is then in the ZIP64 record
+    pub fn needs_zip64(&self) -> bool {
+        self.disk_entries == u16::MAX
+            || self.total_entries == u16::MAX
+            || self.central_directory_size == ZIP64_MARKER
+            || self.central_directory_offset == ZIP64_MARKER
+    }
+}
+
+/// Points at the ZIP64 end of central directory record, right before the regular one
+#[derive(FileFormat)]
+#[format(little_endian)]
+pub struct Zip64Locator {
+    #[format(hex)]
+    pub signature: u32,
+    pub end_disk: u32,
+    #[format(hex)]
+    pub end_offset: u64,
+    pub disk_count: u32,
+}
+
+#[derive(FileFormat)]
+#[format(little_endian)]
+pub struct Zip64EndRecord {
+    #[format(hex)]
+    pub signature: u32,
+    /// Size of the rest of the record, without the signature and this field
+    pub record_size: u64,
+    #[format(meaning = made_by_name)]
+    pub version_made_by: u16,
+    #[format(meaning = version_name)]
+    pub version_needed: u16,
+    pub disk_number: u32,
+    pub central_directory_disk: u32,
+    pub disk_entries: u64,
+    pub total_entries: u64,
+    pub central_directory_size: u64,
+    #[format(hex)]
+    pub central_directory_offset: u64,
+}
+
+/// Version of the spec as major and minor, like `2.0`
+fn version_name(version: u16) -> String {
+    let spec = version & 0xff;
+    format!("{}.{}", spec / 10, spec % 10)
+}
+
+/// Version of the spec with the system that made the archive, like `6.3, Unix`
+fn made_by_name(version: u16) -> String {
+    let system = match version >> 8 {
+        0 => "MS-DOS",
+        1 => "Amiga",
+        2 => "OpenVMS",
+        3 => "Unix",
+        4 => "VM/CMS",
+        5 => "Atari ST",
+        6 => "OS/2 HPFS",
+        7 => "Macintosh",
+        8 => "Z-System",
+        9 => "CP/M",
+        10 => "Windows NTFS",
+        11 => "MVS",
+        12 => "VSE",
+        13 => "Acorn RISC",
+        14 => "VFAT",
+        15 => "alternate MVS",
+        16 => "BeOS",
+        17 => "Tandem",
+        18 => "OS/400",
+        19 => "OS X",
+        _ => "unknown system",
+    };
+    format!("{}, {}", version_name(version), system)
+}
+
+pub fn method_name(method: u16) -> String {
+    let name = match method {
+        0 => "stored",
+        1 => "shrunk",
+        2..=5 => "reduced",
+        6 => "imploded",
+        8 => "deflated",
+        9 => "deflate64",
+        10 => "PKWARE implode",
+        12 => "bzip2",
+        14 => "LZMA",
+        18 => "IBM TERSE",
+        19 => "IBM LZ77",
+        93 => "Zstandard",
+        94 => "MP3",
+        95 => "xz",
+        96 => "JPEG",
+        97 => "WavPack",
+        98 => "PPMd",
+        99 => "AES encrypted",
+        _ => "unknown",
+    };
+    name.to_string()
+}
+
+fn dos_time(time: u16) -> String {
+    time::format_dos_time(time).unwrap_or_else(|| "invalid".to_string())
+}
+
+fn dos_date(date: u16) -> String {
+    time::format_dos_date(date).unwrap_or_else(|| "invalid".to_string())
+}
+
+pub const ZIP64_EXTRA: u16 = 0x0001;
+
+/// A tagged block in the extra field of a header
+pub struct ExtraField {
+    pub id: u16,
+    /// Where the block starts in the file, with its tag and size
+    pub offset: u64,
+    pub data: Vec<u8>,
+}
+
+impl ExtraField {
+    /// Splits an extra field at `offset` of the file into its blocks, with a warning if it
+    /// doesn't end with a whole one
+    pub fn parse_all(mut input: &[u8], mut offset: u64) -> (Vec<Self>, Option<String>) {
+        let mut fields = Vec::new();
+        while !input.is_empty() {
+            let header: IResult<&[u8], (u16, u16)> = (le_u16, le_u16).parse(input);
+            let Ok((tail, (id, size))) = header else {
+                break;
+            };
+            let Some(data) = tail.get(..size as usize) else {
+                break;
+            };
+            fields.push(Self {
+                id,
+                offset,
+                data: data.to_vec(),
+            });
+            input = &tail[size as usize..];
+            offset += 4 + size as u64;
+        }
+        let warning = (!input.is_empty()).then(|| {
+            format!(
+                "The extra field at {:#x} ends with {} bytes that aren't a whole block",
+                offset,
+                input.len()
+            )
+        });
+        (fields, warning)
+    }
+
+    fn name(&self) -> &'static str {
+        match self.id {
+            ZIP64_EXTRA => "ZIP64",
+            0x0007 => "AV info",
+            0x000a => "NTFS",
+            0x000d => "Unix",
+            0x0017 => "strong encryption",
+            0x5455 => "extended timestamp",
+            0x5855 => "Info-ZIP Unix (old)",
+            0x6375 => "Info-ZIP Unicode comment",
+            0x7075 => "Info-ZIP Unicode path",
+            0x7855 => "Info-ZIP Unix",
+            0x7875 => "Info-ZIP Unix UID/GID",
+            0x9901 => "AES encryption",
+            0xcafe => "JAR marker",
+            0xd935 => "Android alignment",
+            _ => "unknown",
+        }
+    }
+
+    /// 64-bit values of a ZIP64 block, in the order of the header fields they replace
+    pub fn zip64_values(&self) -> Vec<u64> {
+        self.data
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect()
+    }
+
+    /// What the block says, for the blocks we know
+    fn description(&self) -> Option<String> {
+        let data = self.data.as_slice();
+        match self.id {
+            ZIP64_EXTRA => {
+                let values = self.zip64_values();
+                let values = values.iter().map(|value| value.to_string());
+                Some(values.collect::<Vec<_>>().join(", "))
+            }
+            // Flags say which times follow, central headers only have the modification time
+            0x5455 => {
+                let (&flags, times) = data.split_first()?;
+                let times = times
+                    .chunks_exact(4)
+                    .map(|chunk| unix_time(u32::from_le_bytes(chunk.try_into().unwrap()) as u64));
+                let names = ["modified", "accessed", "created"]
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(bit, _)| flags & 1 << bit != 0)
+                    .map(|(_, name)| name);
+                let text = names
+                    .zip(times)
+                    .map(|(name, time)| format!("{} {}", name, time))
+                    .collect::<Vec<_>>();
+                Some(text.join(", "))
+            }
+            0x7875 => {
+                let id = |input| -> IResult<&[u8], u64> {
+                    let (tail, size) = u8(input)?;
+                    let (tail, bytes) = nom::bytes::complete::take(size)(tail)?;
+                    let mut value = [0; 8];
+                    let length = bytes.len().min(8);
+                    value[..length].copy_from_slice(&bytes[..length]);
+                    Ok((tail, u64::from_le_bytes(value)))
+                };
+                let (_, (_version, uid, gid)) = (u8, id, id).parse(data).ok()?;
+                Some(format!("uid {}, gid {}", uid, gid))
+            }
+            // Version and the CRC-32 of the header's name or comment come first
+            0x7075 | 0x6375 => {
+                let text = data.get(5..)?;
+                Some(format!("{:?}", String::from_utf8_lossy(text)))
+            }
+            // Length of the padding that aligns the data
+            0xd935 => {
+                let (_, alignment) = le_u16::<_, nom::error::Error<_>>(data).ok()?;
+                Some(format!("aligned to {} bytes", alignment))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Bytes of an unknown extra block shown at most
+const SHOWN_BYTES: usize = 32;
+
+impl FileFormatUi for ExtraField {
+    fn ui(&mut self, ui: &mut egui::Ui, _name: &str, context: &FormatContext) {
+        let name = format!("{:#06x} {}", self.id, self.name());
+        let text = self.description().unwrap_or_else(|| {
+            let shown = &self.data[..self.data.len().min(SHOWN_BYTES)];
+            if self.data.len() > SHOWN_BYTES {
+                format!("{} … ({} bytes)", hex_bytes(shown), self.data.len())
+            } else {
+                hex_bytes(shown)
+            }
+        });
+        context
+            .at(self.offset, 4 + self.data.len() as u64)
+            .field(ui, &name, &text);
+    }
+}
+
+/// Checksum, sizes and optional signature written after the data when the local header
+/// doesn't have them
+pub struct DataDescriptor {
+    pub offset: u64,
+    /// Bytes the descriptor takes
+    pub size: u64,
+    pub crc32: u32,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+}
+
+impl DataDescriptor {
+    pub fn parse(input: &[u8], offset: u64, zip64: bool) -> Option<Self> {
+        // The signature is optional
+        let (tail, signed) = match le_u32::<_, nom::error::Error<_>>(input) {
+            Ok((tail, DESCRIPTOR_SIGNATURE)) => (tail, true),
+            _ => (input, false),
+        };
+        let size = |input| -> IResult<&[u8], u64> {
+            if zip64 {
+                le_u64(input)
+            } else {
+                le_u32(input).map(|(tail, size)| (tail, size.into()))
+            }
+        };
+        let (_, (crc32, compressed_size, uncompressed_size)) =
+            (le_u32, size, size).parse(tail).ok()?;
+        let sizes = if zip64 { 16 } else { 8 };
+        Some(Self {
+            offset,
+            size: if signed { 8 } else { 4 } + sizes,
+            crc32,
+            compressed_size,
+            uncompressed_size,
+        })
+    }
+}
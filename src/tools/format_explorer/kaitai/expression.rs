@@ -0,0 +1,365 @@
+//! Kaitai Struct expressions, like `header.len * 2 + (_root.version >= 2 ? 4 : 0)`
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    ShiftLeft,
+    ShiftRight,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnaryOperator {
+    Negate,
+    BitNot,
+    Not,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Int(i128),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    Array(Vec<Expr>),
+    /// A field, parameter or instance of the current object, or `_root`, `_parent`, `_io`,
+    /// `_index` and `_`
+    Name(String),
+    /// `target.name`, with the arguments of method calls like `to_s("UTF-8")`
+    Member {
+        target: Box<Expr>,
+        name: String,
+        arguments: Option<Vec<Expr>>,
+    },
+    Index(Box<Expr>, Box<Expr>),
+    Unary(UnaryOperator, Box<Expr>),
+    Binary(BinaryOperator, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Int(i128),
+    Float(f64),
+    Str(String),
+    Name(String),
+    /// `enum::value` or `type::enum::value`
+    Path(Vec<String>),
+    Symbol(&'static str),
+}
+
+/// Operators and punctuation, longest first so `<=` isn't read as `<`
+const SYMBOLS: [&str; 26] = [
+    "<<", ">>", "<=", ">=", "==", "!=", "+", "-", "*", "/", "%", "&", "|", "^", "~", "<", ">", "?",
+    ":", "(", ")", "[", "]", ",", ".", "!",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let length;
+        if c.is_ascii_digit() {
+            length = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+            let mut word = &rest[..length];
+            // `1.to_s` is a method call on an integer, not a float
+            if let Some(dot) = word.find('.') {
+                if !word[dot + 1..].starts_with(|c: char| c.is_ascii_digit()) {
+                    word = &word[..dot];
+                }
+            }
+            tokens.push(number(word)?);
+            rest = &rest[word.len()..];
+            rest = rest.trim_start();
+            continue;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut end = 0;
+            let mut segments = Vec::new();
+            loop {
+                let segment_length = rest[end..]
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len() - end);
+                segments.push(rest[end..end + segment_length].to_string());
+                end += segment_length;
+                if rest[end..].starts_with("::") {
+                    end += 2;
+                } else {
+                    break;
+                }
+            }
+            length = end;
+            tokens.push(match segments.len() {
+                1 => Token::Name(segments.pop().unwrap()),
+                _ => Token::Path(segments),
+            });
+        } else if c == '"' || c == '\'' {
+            let (value, end) = string(rest)?;
+            tokens.push(Token::Str(value));
+            length = end;
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            tokens.push(Token::Symbol(symbol));
+            length = symbol.len();
+        } else {
+            return Err(format!("unexpected '{}'", c));
+        }
+        rest = rest[length..].trim_start();
+    }
+    Ok(tokens)
+}
+
+fn number(word: &str) -> Result<Token, String> {
+    let lower = word.to_ascii_lowercase().replace('_', "");
+    let (digits, radix) = if let Some(digits) = lower.strip_prefix("0x") {
+        (digits, 16)
+    } else if let Some(digits) = lower.strip_prefix("0o") {
+        (digits, 8)
+    } else if let Some(digits) = lower.strip_prefix("0b") {
+        (digits, 2)
+    } else if lower.contains(['.', 'e']) {
+        return lower
+            .parse()
+            .map(Token::Float)
+            .map_err(|_| format!("invalid number '{}'", word));
+    } else {
+        (lower.as_str(), 10)
+    };
+    i128::from_str_radix(digits, radix)
+        .map(Token::Int)
+        .map_err(|_| format!("invalid number '{}'", word))
+}
+
+/// String literal at the start of `text` and its length, escapes only work in double quotes
+fn string(text: &str) -> Result<(String, usize), String> {
+    let quote = text.chars().next().unwrap();
+    let mut value = String::new();
+    let mut chars = text.char_indices().skip(1);
+    while let Some((index, c)) = chars.next() {
+        if c == quote {
+            return Ok((value, index + 1));
+        }
+        if c != '\\' || quote == '\'' {
+            value.push(c);
+            continue;
+        }
+        let (_, escaped) = chars.next().ok_or("unfinished escape")?;
+        value.push(match escaped {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            other => other,
+        });
+    }
+    Err("unclosed string".to_string())
+}
+
+/// Parses an expression, `resolve_enum` turns `enum::value` paths into numbers
+pub fn parse(text: &str, resolve_enum: &dyn Fn(&[String]) -> Option<i128>) -> Result<Expr, String> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        position: 0,
+        resolve_enum,
+    };
+    let expr = parser.conditional()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(token) => Err(format!("unexpected {:?}", token)),
+    }
+}
+
+/// Binary operators from the loosest to the tightest binding
+const PRECEDENCE: [&[(&str, BinaryOperator)]; 9] = [
+    &[("or", BinaryOperator::Or)],
+    &[("and", BinaryOperator::And)],
+    &[
+        ("==", BinaryOperator::Equal),
+        ("!=", BinaryOperator::NotEqual),
+        ("<=", BinaryOperator::LessEqual),
+        (">=", BinaryOperator::GreaterEqual),
+        ("<", BinaryOperator::Less),
+        (">", BinaryOperator::Greater),
+    ],
+    &[("|", BinaryOperator::BitOr)],
+    &[("^", BinaryOperator::BitXor)],
+    &[("&", BinaryOperator::BitAnd)],
+    &[
+        ("<<", BinaryOperator::ShiftLeft),
+        (">>", BinaryOperator::ShiftRight),
+    ],
+    &[("+", BinaryOperator::Add), ("-", BinaryOperator::Subtract)],
+    &[
+        ("*", BinaryOperator::Multiply),
+        ("/", BinaryOperator::Divide),
+        ("%", BinaryOperator::Remainder),
+    ],
+];
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    resolve_enum: &'a dyn Fn(&[String]) -> Option<i128>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    /// Takes the next token if it is the symbol or keyword `text`
+    fn eat(&mut self, text: &str) -> bool {
+        let matches = match self.peek() {
+            Some(Token::Symbol(symbol)) => *symbol == text,
+            Some(Token::Name(name)) => name == text,
+            _ => false,
+        };
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), String> {
+        if self.eat(text) {
+            Ok(())
+        } else {
+            Err(format!("expected '{}'", text))
+        }
+    }
+
+    fn conditional(&mut self) -> Result<Expr, String> {
+        let condition = self.binary(0)?;
+        if !self.eat("?") {
+            return Ok(condition);
+        }
+        let then = self.conditional()?;
+        self.expect(":")?;
+        let otherwise = self.conditional()?;
+        Ok(Expr::Conditional(
+            Box::new(condition),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+        // `not` binds looser than comparisons but tighter than `and`
+        if level == 2 && self.eat("not") {
+            let operand = self.binary(level)?;
+            return Ok(Expr::Unary(UnaryOperator::Not, Box::new(operand)));
+        }
+        let mut lhs = self.binary(level + 1)?;
+        'operators: loop {
+            for &(text, operator) in PRECEDENCE[level] {
+                if self.eat(text) {
+                    let rhs = self.binary(level + 1)?;
+                    lhs = Expr::Binary(operator, Box::new(lhs), Box::new(rhs));
+                    continue 'operators;
+                }
+            }
+            return Ok(lhs);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        let operator = if self.eat("-") {
+            UnaryOperator::Negate
+        } else if self.eat("~") {
+            UnaryOperator::BitNot
+        } else if self.eat("!") {
+            UnaryOperator::Not
+        } else {
+            return self.postfix();
+        };
+        Ok(Expr::Unary(operator, Box::new(self.unary()?)))
+    }
+
+    fn postfix(&mut self) -> Result<Expr, String> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.eat(".") {
+                let name = match self.tokens.get(self.position) {
+                    Some(Token::Name(name)) => name.clone(),
+                    _ => return Err("expected a name after '.'".to_string()),
+                };
+                self.position += 1;
+                let arguments = if self.eat("(") {
+                    Some(self.list(")")?)
+                } else {
+                    None
+                };
+                expr = Expr::Member {
+                    target: Box::new(expr),
+                    name,
+                    arguments,
+                };
+            } else if self.eat("[") {
+                let index = self.conditional()?;
+                self.expect("]")?;
+                expr = Expr::Index(Box::new(expr), Box::new(index));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    /// Expressions separated by commas up to `end`
+    fn list(&mut self, end: &str) -> Result<Vec<Expr>, String> {
+        let mut items = Vec::new();
+        while !self.eat(end) {
+            items.push(self.conditional()?);
+            if !self.eat(",") {
+                self.expect(end)?;
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let Some(token) = self.tokens.get(self.position).cloned() else {
+            return Err("unexpected end of expression".to_string());
+        };
+        self.position += 1;
+        Ok(match token {
+            Token::Int(value) => Expr::Int(value),
+            Token::Float(value) => Expr::Float(value),
+            Token::Str(value) => Expr::Str(value),
+            Token::Name(name) => match name.as_str() {
+                "true" => Expr::Bool(true),
+                "false" => Expr::Bool(false),
+                _ => Expr::Name(name),
+            },
+            Token::Path(path) => Expr::Int(
+                (self.resolve_enum)(&path)
+                    .ok_or_else(|| format!("unknown enum value '{}'", path.join("::")))?,
+            ),
+            Token::Symbol("(") => {
+                let expr = self.conditional()?;
+                self.expect(")")?;
+                expr
+            }
+            Token::Symbol("[") => Expr::Array(self.list("]")?),
+            Token::Symbol(symbol) => return Err(format!("unexpected '{}'", symbol)),
+        })
+    }
+}
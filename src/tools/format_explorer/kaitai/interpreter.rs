@@ -0,0 +1,1490 @@
+//! Reads a file with a spec into a tree of objects, the way the code generated by the Kaitai
+//! Struct compiler would
+
+use std::{
+    cell::{Cell, RefCell},
+    rc::{Rc, Weak},
+};
+
+use super::{
+    expression::{BinaryOperator, Expr, UnaryOperator},
+    spec::{Attribute, Kind, Repeat, Spec, TypeDef},
+};
+use crate::tools::format_explorer::formats::hex_bytes;
+
+/// Objects nested deeper than this are most likely a spec following itself forever
+const MAX_DEPTH: usize = 128;
+/// Items read from a file at most, so a bogus count doesn't exhaust the memory
+const MAX_ITEMS: u64 = 1_000_000;
+
+/// Bytes of the file an object reads from, with the position of the next read
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) struct Stream {
+    pub start: u64,
+    pub end: u64,
+    pub pos: u64,
+    /// Bits left over from the last byte read by a bit-sized field
+    bits: u64,
+    bit_count: u8,
+}
+
+impl Stream {
+    fn new(start: u64, end: u64) -> Self {
+        Self {
+            start,
+            end,
+            pos: start,
+            bits: 0,
+            bit_count: 0,
+        }
+    }
+}
+
+/// An instance of a type of the spec
+pub(super) struct Object {
+    pub type_index: usize,
+    parent: Weak<Object>,
+    depth: usize,
+    pub start: u64,
+    pub end: Cell<u64>,
+    stream: Cell<Stream>,
+    params: Vec<(String, Value)>,
+    /// Fields read so far, with the instances computed so far
+    pub fields: RefCell<Vec<Field>>,
+    /// Instances being computed, to catch ones that depend on themselves
+    computing: RefCell<Vec<String>>,
+    /// What went wrong reading the fields of this object
+    pub errors: RefCell<Vec<String>>,
+}
+
+impl Object {
+    fn root(self: &Rc<Self>) -> Rc<Object> {
+        let mut object = self.clone();
+        while let Some(parent) = object.parent.upgrade() {
+            object = parent;
+        }
+        object
+    }
+
+    fn field(&self, name: &str) -> Option<Value> {
+        let fields = self.fields.borrow();
+        let field = fields.iter().rev().find(|field| field.name == name);
+        field.map(|field| field.item.value.clone())
+    }
+
+    /// First error in the object or the ones in it
+    pub fn first_error(&self) -> Option<String> {
+        if let Some(error) = self.errors.borrow().first() {
+            return Some(error.clone());
+        }
+        self.fields
+            .borrow()
+            .iter()
+            .find_map(|field| field.item.first_error())
+    }
+}
+
+pub(super) struct Field {
+    pub name: String,
+    pub doc: Option<String>,
+    pub item: Item,
+    pub instance: bool,
+}
+
+/// A value with where it was read from
+#[derive(Clone)]
+pub(super) struct Item {
+    pub value: Value,
+    /// Offset and length in the file, `None` for computed values
+    pub span: Option<(u64, u64)>,
+    /// Name of the value in the enum of the field
+    pub meaning: Option<String>,
+}
+
+impl Item {
+    fn first_error(&self) -> Option<String> {
+        match &self.value {
+            Value::Object(object) => object.first_error(),
+            Value::Array(items) => items.iter().find_map(Item::first_error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(super) enum Value {
+    Int(i128),
+    Float(f64),
+    Bool(bool),
+    Str(Rc<str>),
+    Bytes(Rc<[u8]>),
+    Array(Rc<[Item]>),
+    Object(Rc<Object>),
+    Stream(Stream),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "an integer",
+            Value::Float(_) => "a float",
+            Value::Bool(_) => "a boolean",
+            Value::Str(_) => "a string",
+            Value::Bytes(_) => "bytes",
+            Value::Array(_) => "an array",
+            Value::Object(_) => "an object",
+            Value::Stream(_) => "a stream",
+        }
+    }
+
+    fn int(&self) -> Result<i128> {
+        match self {
+            Value::Int(value) => Ok(*value),
+            other => Err(Stop::error(format!(
+                "expected an integer, got {}",
+                other.kind()
+            ))),
+        }
+    }
+
+    fn bool(&self) -> Result<bool> {
+        match self {
+            Value::Bool(value) => Ok(*value),
+            other => Err(Stop::error(format!(
+                "expected a boolean, got {}",
+                other.kind()
+            ))),
+        }
+    }
+}
+
+/// Why reading stopped
+enum Stop {
+    /// An error in the field being read
+    Error(String),
+    /// An error in an object the field holds, which keeps the message
+    Nested,
+}
+
+impl Stop {
+    fn error(message: impl Into<String>) -> Self {
+        Stop::Error(message.into())
+    }
+}
+
+type Result<T> = std::result::Result<T, Stop>;
+
+/// Names an expression can use besides the members of the object
+struct Scope<'s> {
+    object: &'s Rc<Object>,
+    /// `_index`, the position in a repeated field
+    index: Option<i128>,
+    /// `_`, the last item of a repeated field
+    last: Option<&'s Value>,
+}
+
+/// Reads `data` with `spec`, the object holds what could be read even if something failed
+pub(super) fn parse(spec: &Spec, data: &[u8]) -> Rc<Object> {
+    let interpreter = Interpreter {
+        spec,
+        data,
+        budget: Cell::new(MAX_ITEMS),
+    };
+    let stream = Stream::new(0, data.len() as u64);
+    let (root, _) = interpreter.parse_object(0, None, stream, Vec::new());
+    // Instances are lazy in Kaitai Struct, computing them once everything is read lets them
+    // use fields of their parents that come after them
+    interpreter.compute_instances(&root);
+    root
+}
+
+struct Interpreter<'a> {
+    spec: &'a Spec,
+    data: &'a [u8],
+    /// Items that can still be read
+    budget: Cell<u64>,
+}
+
+impl Interpreter<'_> {
+    fn definition(&self, object: &Object) -> &TypeDef {
+        &self.spec.types[object.type_index]
+    }
+
+    fn parse_object(
+        &self,
+        type_index: usize,
+        parent: Option<&Rc<Object>>,
+        stream: Stream,
+        params: Vec<(String, Value)>,
+    ) -> (Rc<Object>, Result<()>) {
+        let object = Rc::new(Object {
+            type_index,
+            parent: parent.map_or_else(Weak::new, Rc::downgrade),
+            depth: parent.map_or(0, |parent| parent.depth + 1),
+            start: stream.pos,
+            end: Cell::new(stream.pos),
+            stream: Cell::new(stream),
+            params,
+            fields: RefCell::new(Vec::new()),
+            computing: RefCell::new(Vec::new()),
+            errors: RefCell::new(Vec::new()),
+        });
+        if object.depth > MAX_DEPTH {
+            object
+                .errors
+                .borrow_mut()
+                .push("objects are nested too deeply".to_string());
+            return (object, Err(Stop::Nested));
+        }
+        let mut result = Ok(());
+        for attribute in &self.spec.types[type_index].seq {
+            if let Err(stop) = self.parse_attribute(&object, attribute, false) {
+                if let Stop::Error(message) = stop {
+                    let message = format!("{}: {}", attribute.id, message);
+                    object.errors.borrow_mut().push(message);
+                }
+                result = Err(Stop::Nested);
+                break;
+            }
+        }
+        object.end.set(object.stream.get().pos);
+        (object, result)
+    }
+
+    /// Computes the instances of the object and of the objects in it that weren't needed
+    /// while reading
+    fn compute_instances(&self, object: &Rc<Object>) {
+        let definition = self.definition(object);
+        for attribute in &definition.instances {
+            if let Err(Stop::Error(message)) = self.instance(object, &attribute.id) {
+                let message = format!("{}: {}", attribute.id, message);
+                object.errors.borrow_mut().push(message);
+            }
+        }
+        // Fields first, then instances in the order they are declared
+        let position = |field: &Field| {
+            let instances = &definition.instances;
+            let declared = instances
+                .iter()
+                .position(|instance| instance.id == field.name);
+            (field.instance, field.instance.then_some(declared))
+        };
+        object.fields.borrow_mut().sort_by_key(position);
+        let children = object
+            .fields
+            .borrow()
+            .iter()
+            .map(|field| field.item.clone())
+            .collect::<Vec<_>>();
+        for item in children {
+            self.compute_item_instances(&item);
+        }
+    }
+
+    fn compute_item_instances(&self, item: &Item) {
+        match &item.value {
+            Value::Object(object) => self.compute_instances(object),
+            Value::Array(items) => {
+                for item in items.iter() {
+                    self.compute_item_instances(item);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Value of an instance, computed the first time it's needed. `None` if its condition is
+    /// false.
+    fn instance(&self, object: &Rc<Object>, name: &str) -> Result<Option<Value>> {
+        if let Some(field) = object
+            .fields
+            .borrow()
+            .iter()
+            .find(|field| field.instance && field.name == name)
+        {
+            return Ok(Some(field.item.value.clone()));
+        }
+        let definition = self.definition(object);
+        let Some(attribute) = definition
+            .instances
+            .iter()
+            .find(|instance| instance.id == name)
+        else {
+            return Err(Stop::error(format!("unknown name '{}'", name)));
+        };
+        if object.computing.borrow().iter().any(|other| other == name) {
+            return Err(Stop::error(format!("'{}' depends on itself", name)));
+        }
+        object.computing.borrow_mut().push(name.to_string());
+        let saved = object.stream.get();
+        let result = self.read_instance(object, attribute);
+        object.stream.set(saved);
+        object.computing.borrow_mut().retain(|other| other != name);
+        result
+    }
+
+    fn read_instance(&self, object: &Rc<Object>, attribute: &Attribute) -> Result<Option<Value>> {
+        let scope = Scope {
+            object,
+            index: None,
+            last: None,
+        };
+        if let Some(io) = &attribute.io {
+            match self.evaluate(io, &scope)? {
+                Value::Stream(stream) => object.stream.set(Stream::new(stream.start, stream.end)),
+                other => {
+                    return Err(Stop::error(format!(
+                        "io must be a stream, got {}",
+                        other.kind()
+                    )))
+                }
+            }
+        }
+        if let Some(pos) = &attribute.pos {
+            let pos = self.evaluate(pos, &scope)?.int()?;
+            let mut stream = object.stream.get();
+            let length = stream.end - stream.start;
+            if !(0..=length as i128).contains(&pos) {
+                return Err(Stop::error(format!(
+                    "pos {:#x} is outside of the stream",
+                    pos
+                )));
+            }
+            stream = Stream::new(stream.start, stream.end);
+            stream.pos = stream.start + pos as u64;
+            object.stream.set(stream);
+        }
+        self.parse_attribute(object, attribute, true)
+    }
+
+    /// Reads a field or computes an instance and adds it to the object, partial arrays and
+    /// objects are added even if reading them fails
+    fn parse_attribute(
+        &self,
+        object: &Rc<Object>,
+        attribute: &Attribute,
+        instance: bool,
+    ) -> Result<Option<Value>> {
+        let scope = Scope {
+            object,
+            index: None,
+            last: None,
+        };
+        if let Some(condition) = &attribute.condition {
+            if !self.evaluate(condition, &scope)?.bool()? {
+                return Ok(None);
+            }
+        }
+        let mut items = Vec::new();
+        let (result, span) = match &attribute.value {
+            Some(value) => {
+                let value = self.evaluate(value, &scope)?;
+                let meaning = self.meaning(attribute, &value);
+                items.push(Item {
+                    value,
+                    span: None,
+                    meaning,
+                });
+                (Ok(()), None)
+            }
+            None => {
+                let start = object.stream.get().pos;
+                let result = self.read_items(object, attribute, &mut items);
+                let end = object.stream.get().pos;
+                (result, Some((start, end.saturating_sub(start))))
+            }
+        };
+        let item = match attribute.repeat {
+            Repeat::None => items.pop(),
+            _ => Some(Item {
+                value: Value::Array(items.into()),
+                span,
+                meaning: None,
+            }),
+        };
+        let value = item.as_ref().map(|item| item.value.clone());
+        if let Some(item) = item {
+            object.fields.borrow_mut().push(Field {
+                name: attribute.id.clone(),
+                doc: attribute.doc.clone(),
+                item,
+                instance,
+            });
+        }
+        result.map(|_| value)
+    }
+
+    fn read_items(
+        &self,
+        object: &Rc<Object>,
+        attribute: &Attribute,
+        items: &mut Vec<Item>,
+    ) -> Result<()> {
+        match &attribute.repeat {
+            Repeat::None => self.read_item(object, attribute, None, items),
+            Repeat::Eos => {
+                for index in 0.. {
+                    let before = object.stream.get();
+                    if before.pos >= before.end {
+                        break;
+                    }
+                    self.read_item(object, attribute, Some(index), items)?;
+                    if object.stream.get() == before {
+                        return Err(Stop::error("the repeated items don't take any bytes"));
+                    }
+                }
+                Ok(())
+            }
+            Repeat::Count(count) => {
+                let scope = Scope {
+                    object,
+                    index: None,
+                    last: None,
+                };
+                let count = self.evaluate(count, &scope)?.int()?;
+                if count < 0 || count > self.budget.get() as i128 {
+                    return Err(Stop::error(format!("invalid repeat count {}", count)));
+                }
+                for index in 0..count {
+                    self.read_item(object, attribute, Some(index), items)?;
+                }
+                Ok(())
+            }
+            Repeat::Until(condition) => {
+                for index in 0.. {
+                    self.read_item(object, attribute, Some(index), items)?;
+                    let last = items.last().map(|item| &item.value);
+                    let scope = Scope {
+                        object,
+                        index: Some(index),
+                        last,
+                    };
+                    if self.evaluate(condition, &scope)?.bool()? {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Reads one item of the field, objects are added even if they couldn't be read entirely
+    fn read_item(
+        &self,
+        object: &Rc<Object>,
+        attribute: &Attribute,
+        index: Option<i128>,
+        items: &mut Vec<Item>,
+    ) -> Result<()> {
+        let budget = self.budget.get();
+        if budget == 0 {
+            return Err(Stop::error("too many items in the file"));
+        }
+        self.budget.set(budget - 1);
+        let scope = Scope {
+            object,
+            index,
+            last: None,
+        };
+        let size = match &attribute.size {
+            Some(size) => {
+                let size = self.evaluate(size, &scope)?.int()?;
+                let remaining = self.remaining(object);
+                if size < 0 || size > remaining as i128 {
+                    return Err(Stop::error(format!(
+                        "size {} is larger than the {} bytes left",
+                        size, remaining
+                    )));
+                }
+                Some(size as u64)
+            }
+            None if attribute.size_eos => Some(self.remaining(object)),
+            None => None,
+        };
+        if let Some(contents) = &attribute.contents {
+            let (start, bytes) = self.take(object, contents.len() as u64)?;
+            items.push(Item {
+                value: Value::Bytes(bytes.into()),
+                span: Some((start, bytes.len() as u64)),
+                meaning: None,
+            });
+            if bytes != contents.as_slice() {
+                return Err(Stop::error(format!(
+                    "expected {} but found {}",
+                    hex_bytes(contents),
+                    hex_bytes(bytes)
+                )));
+            }
+            return Ok(());
+        }
+        let kind = match &attribute.kind {
+            Kind::Switch { on, cases } => {
+                let on = self.evaluate(on, &scope)?;
+                let mut chosen = None;
+                for (key, kind) in cases {
+                    let matches = match key {
+                        Some(key) => {
+                            let key = self.evaluate(key, &scope)?;
+                            self.binary(BinaryOperator::Equal, &on, &key)?.bool()?
+                        }
+                        None => true,
+                    };
+                    if matches {
+                        chosen = Some(kind);
+                        break;
+                    }
+                }
+                match chosen {
+                    Some(kind) => kind,
+                    // Without a matching case the field is just bytes when it has a size, and
+                    // isn't read at all when it doesn't
+                    None if size.is_some() => &Kind::Bytes,
+                    None => return Ok(()),
+                }
+            }
+            kind => kind,
+        };
+        let item = self.read_kind(object, attribute, kind, size, &scope, items)?;
+        if let Some(item) = item {
+            items.push(item);
+        }
+        Ok(())
+    }
+
+    /// Reads a value of a type that isn't a switch, objects are added to `items` right away
+    fn read_kind(
+        &self,
+        object: &Rc<Object>,
+        attribute: &Attribute,
+        kind: &Kind,
+        size: Option<u64>,
+        scope: &Scope,
+        items: &mut Vec<Item>,
+    ) -> Result<Option<Item>> {
+        let definition = self.definition(object);
+        let order = |big_endian: Option<bool>, size: u8| match big_endian.or(definition.big_endian)
+        {
+            Some(big_endian) => Ok(big_endian),
+            // Single bytes don't have a byte order
+            None if size == 1 => Ok(false),
+            None => Err(Stop::error("the byte order isn't set")),
+        };
+        let (value, span) = match kind {
+            Kind::Bytes | Kind::Str { .. } => {
+                let (start, bytes) = self.read_bytes(object, attribute, size)?;
+                let span = Some((start, bytes.len() as u64));
+                let value = match kind {
+                    Kind::Bytes => Value::Bytes(bytes.into()),
+                    _ => {
+                        let encoding = attribute
+                            .encoding
+                            .as_deref()
+                            .or(definition.encoding.as_deref())
+                            .unwrap_or("UTF-8");
+                        Value::Str(decode(bytes, encoding).into())
+                    }
+                };
+                (value, span)
+            }
+            Kind::Int {
+                size: int_size,
+                signed,
+                big_endian,
+            } => {
+                let big_endian = order(*big_endian, *int_size)?;
+                let (start, bytes) = self.take(object, (*int_size).into())?;
+                let value = if big_endian {
+                    bytes.iter().fold(0u64, |value, &b| value << 8 | b as u64)
+                } else {
+                    bytes
+                        .iter()
+                        .rev()
+                        .fold(0u64, |value, &b| value << 8 | b as u64)
+                };
+                let value = if *signed {
+                    // Sign extension from the top bit of the field
+                    let shift = 64 - 8 * *int_size as u32;
+                    ((value << shift) as i64 >> shift) as i128
+                } else {
+                    value as i128
+                };
+                (Value::Int(value), Some((start, (*int_size).into())))
+            }
+            Kind::Float {
+                size: float_size,
+                big_endian,
+            } => {
+                let big_endian = order(*big_endian, *float_size)?;
+                let (start, bytes) = self.take(object, (*float_size).into())?;
+                let value = match (*float_size, big_endian) {
+                    (4, true) => f32::from_be_bytes(bytes.try_into().unwrap()) as f64,
+                    (4, false) => f32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+                    (_, true) => f64::from_be_bytes(bytes.try_into().unwrap()),
+                    (_, false) => f64::from_le_bytes(bytes.try_into().unwrap()),
+                };
+                (Value::Float(value), Some((start, (*float_size).into())))
+            }
+            Kind::Bits { width, big_endian } => {
+                let big_endian = big_endian.unwrap_or(definition.bits_big_endian);
+                let (start, value) = self.read_bits(object, *width, big_endian)?;
+                let end = object.stream.get().pos;
+                // `b1` is a boolean, unless it's an enum
+                let value = match (width, &attribute.enum_def) {
+                    (1, None) => Value::Bool(value == 1),
+                    _ => Value::Int(value.into()),
+                };
+                (value, Some((start, end.saturating_sub(start).max(1))))
+            }
+            Kind::User { index, arguments } => {
+                let params = &self.spec.types[*index].params;
+                if arguments.len() != params.len() {
+                    return Err(Stop::error(format!(
+                        "{} takes {} arguments but {} were given",
+                        self.spec.types[*index].name,
+                        params.len(),
+                        arguments.len()
+                    )));
+                }
+                let params = params
+                    .iter()
+                    .zip(arguments)
+                    .map(|(name, argument)| Ok((name.clone(), self.evaluate(argument, scope)?)))
+                    .collect::<Result<Vec<_>>>()?;
+                let mut stream = object.stream.get();
+                stream.bit_count = 0;
+                let child_stream = match size {
+                    Some(size) => Stream::new(stream.pos, stream.pos + size),
+                    None => stream,
+                };
+                let (child, result) = self.parse_object(*index, Some(object), child_stream, params);
+                stream.pos = match size {
+                    Some(size) => stream.pos + size,
+                    None => child.stream.get().pos,
+                };
+                object.stream.set(stream);
+                let span = (child.start, stream.pos - child.start);
+                items.push(Item {
+                    value: Value::Object(child),
+                    span: Some(span),
+                    meaning: None,
+                });
+                result?;
+                return Ok(None);
+            }
+            Kind::Switch { .. } => unreachable!("switches are resolved before"),
+        };
+        let meaning = self.meaning(attribute, &value);
+        Ok(Some(Item {
+            value,
+            span,
+            meaning,
+        }))
+    }
+
+    fn meaning(&self, attribute: &Attribute, value: &Value) -> Option<String> {
+        let (Some(enum_def), Value::Int(value)) = (&attribute.enum_def, value) else {
+            return None;
+        };
+        Some(enum_def.name(*value).unwrap_or("unknown").to_string())
+    }
+
+    fn remaining(&self, object: &Object) -> u64 {
+        let stream = object.stream.get();
+        stream.end.saturating_sub(stream.pos)
+    }
+
+    /// Reads `length` bytes from the stream of the object
+    fn take(&self, object: &Object, length: u64) -> Result<(u64, &[u8])> {
+        let mut stream = object.stream.get();
+        stream.bit_count = 0;
+        if length > stream.end.saturating_sub(stream.pos) {
+            return Err(Stop::error(format!(
+                "{} bytes needed at {:#x} but the stream ends at {:#x}",
+                length, stream.pos, stream.end
+            )));
+        }
+        let start = stream.pos;
+        stream.pos += length;
+        object.stream.set(stream);
+        Ok((start, &self.data[start as usize..stream.pos as usize]))
+    }
+
+    /// Bytes of a field, up to its size or its terminator
+    fn read_bytes(
+        &self,
+        object: &Object,
+        attribute: &Attribute,
+        size: Option<u64>,
+    ) -> Result<(u64, &[u8])> {
+        if let Some(size) = size {
+            let (start, bytes) = self.take(object, size)?;
+            // A terminator in sized fields only cuts the value short
+            let bytes = match attribute.terminator {
+                Some(terminator) => match bytes.iter().position(|&b| b == terminator) {
+                    Some(end) if attribute.include => &bytes[..end + 1],
+                    Some(end) => &bytes[..end],
+                    None => bytes,
+                },
+                None => bytes,
+            };
+            return Ok((start, bytes));
+        }
+        let Some(terminator) = attribute.terminator else {
+            return Err(Stop::error("the field has neither a size nor a terminator"));
+        };
+        let mut stream = object.stream.get();
+        stream.bit_count = 0;
+        let start = stream.pos;
+        let rest = &self.data[start as usize..stream.end as usize];
+        let (length, consumed) = match rest.iter().position(|&b| b == terminator) {
+            Some(end) => {
+                let length = if attribute.include { end + 1 } else { end };
+                let consumed = if attribute.consume { end + 1 } else { end };
+                (length, consumed)
+            }
+            None if attribute.eos_error => {
+                return Err(Stop::error(format!(
+                    "terminator {:#04x} not found before the end of the stream",
+                    terminator
+                )))
+            }
+            None => (rest.len(), rest.len()),
+        };
+        stream.pos += consumed as u64;
+        object.stream.set(stream);
+        Ok((start, &rest[..length]))
+    }
+
+    /// Reads a bit-sized integer, returns the offset of its first byte with it
+    fn read_bits(&self, object: &Object, width: u8, big_endian: bool) -> Result<(u64, u64)> {
+        let mut stream = object.stream.get();
+        // The bits left over come from the byte before the position
+        let start = if stream.bit_count > 0 {
+            stream.pos - 1
+        } else {
+            stream.pos
+        };
+        let mut bits = stream.bits as u128;
+        let mut count = stream.bit_count as u32;
+        let width = width as u32;
+        while count < width {
+            if stream.pos >= stream.end {
+                return Err(Stop::error(format!(
+                    "{} bits needed at {:#x} but the stream ends",
+                    width, stream.pos
+                )));
+            }
+            let byte = self.data[stream.pos as usize] as u128;
+            stream.pos += 1;
+            if big_endian {
+                bits = bits << 8 | byte;
+            } else {
+                bits |= byte << count;
+            }
+            count += 8;
+        }
+        let mask = |width: u32| (1u128 << width) - 1;
+        let value = if big_endian {
+            let value = bits >> (count - width) & mask(width);
+            bits &= mask(count - width);
+            value
+        } else {
+            let value = bits & mask(width);
+            bits >>= width;
+            value
+        };
+        stream.bits = bits as u64;
+        stream.bit_count = (count - width) as u8;
+        object.stream.set(stream);
+        Ok((start, value as u64))
+    }
+
+    /// A field, parameter or instance of the object
+    fn member(&self, object: &Rc<Object>, name: &str) -> Result<Value> {
+        match name {
+            "_io" => return Ok(Value::Stream(object.stream.get())),
+            "_root" => return Ok(Value::Object(object.root())),
+            "_parent" => {
+                return object
+                    .parent
+                    .upgrade()
+                    .map(Value::Object)
+                    .ok_or_else(|| Stop::error("the top level object has no parent"))
+            }
+            _ => {}
+        }
+        if let Some(value) = object.field(name) {
+            return Ok(value);
+        }
+        if let Some((_, value)) = object.params.iter().find(|(param, _)| param == name) {
+            return Ok(value.clone());
+        }
+        let definition = self.definition(object);
+        let is_field = definition.seq.iter().any(|field| field.id == name);
+        if is_field {
+            return Err(Stop::error(format!(
+                "'{}' isn't read yet or is absent",
+                name
+            )));
+        }
+        self.instance(object, name)?
+            .ok_or_else(|| Stop::error(format!("'{}' is absent", name)))
+    }
+
+    fn evaluate(&self, expr: &Expr, scope: &Scope) -> Result<Value> {
+        Ok(match expr {
+            Expr::Int(value) => Value::Int(*value),
+            Expr::Float(value) => Value::Float(*value),
+            Expr::Bool(value) => Value::Bool(*value),
+            Expr::Str(value) => Value::Str(value.as_str().into()),
+            Expr::Array(items) => {
+                let values = items
+                    .iter()
+                    .map(|item| self.evaluate(item, scope))
+                    .collect::<Result<Vec<_>>>()?;
+                // Arrays of byte values are byte arrays, to compare them with fields
+                let bytes = values
+                    .iter()
+                    .map(|value| match value {
+                        Value::Int(value) => u8::try_from(*value).ok(),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>();
+                match bytes {
+                    Some(bytes) => Value::Bytes(bytes.into()),
+                    None => Value::Array(
+                        values
+                            .into_iter()
+                            .map(|value| Item {
+                                value,
+                                span: None,
+                                meaning: None,
+                            })
+                            .collect(),
+                    ),
+                }
+            }
+            Expr::Name(name) => match name.as_str() {
+                "_index" => Value::Int(
+                    scope
+                        .index
+                        .ok_or_else(|| Stop::error("_index is only set in repeated fields"))?,
+                ),
+                "_" => scope
+                    .last
+                    .cloned()
+                    .ok_or_else(|| Stop::error("_ is only set in repeat-until"))?,
+                _ => self.member(scope.object, name)?,
+            },
+            Expr::Member {
+                target,
+                name,
+                arguments,
+            } => {
+                let target = self.evaluate(target, scope)?;
+                let arguments = arguments
+                    .iter()
+                    .flatten()
+                    .map(|argument| self.evaluate(argument, scope))
+                    .collect::<Result<Vec<_>>>()?;
+                self.method(&target, name, &arguments)?
+            }
+            Expr::Index(target, index) => {
+                let target = self.evaluate(target, scope)?;
+                let index = self.evaluate(index, scope)?.int()?;
+                let out_of_range = || Stop::error(format!("index {} is out of range", index));
+                let index = usize::try_from(index).map_err(|_| out_of_range())?;
+                match target {
+                    Value::Array(items) => items.get(index).ok_or_else(out_of_range)?.value.clone(),
+                    Value::Bytes(bytes) => {
+                        Value::Int((*bytes.get(index).ok_or_else(out_of_range)?).into())
+                    }
+                    other => return Err(Stop::error(format!("can't index {}", other.kind()))),
+                }
+            }
+            Expr::Unary(operator, operand) => {
+                let operand = self.evaluate(operand, scope)?;
+                match (operator, operand) {
+                    (UnaryOperator::Negate, Value::Int(value)) => Value::Int(-value),
+                    (UnaryOperator::Negate, Value::Float(value)) => Value::Float(-value),
+                    (UnaryOperator::BitNot, Value::Int(value)) => Value::Int(!value),
+                    (UnaryOperator::Not, Value::Bool(value)) => Value::Bool(!value),
+                    (_, other) => {
+                        return Err(Stop::error(format!("invalid operand, {}", other.kind())))
+                    }
+                }
+            }
+            Expr::Binary(BinaryOperator::And, lhs, rhs) => Value::Bool(
+                self.evaluate(lhs, scope)?.bool()? && self.evaluate(rhs, scope)?.bool()?,
+            ),
+            Expr::Binary(BinaryOperator::Or, lhs, rhs) => Value::Bool(
+                self.evaluate(lhs, scope)?.bool()? || self.evaluate(rhs, scope)?.bool()?,
+            ),
+            Expr::Binary(operator, lhs, rhs) => {
+                let lhs = self.evaluate(lhs, scope)?;
+                let rhs = self.evaluate(rhs, scope)?;
+                self.binary(*operator, &lhs, &rhs)?
+            }
+            Expr::Conditional(condition, then, otherwise) => {
+                if self.evaluate(condition, scope)?.bool()? {
+                    self.evaluate(then, scope)?
+                } else {
+                    self.evaluate(otherwise, scope)?
+                }
+            }
+        })
+    }
+
+    fn binary(&self, operator: BinaryOperator, lhs: &Value, rhs: &Value) -> Result<Value> {
+        use BinaryOperator::*;
+        let overflow = || Stop::error("overflow");
+        Ok(match (lhs, rhs) {
+            (Value::Int(a), Value::Int(b)) => {
+                let (a, b) = (*a, *b);
+                match operator {
+                    Add => Value::Int(a.checked_add(b).ok_or_else(overflow)?),
+                    Subtract => Value::Int(a.checked_sub(b).ok_or_else(overflow)?),
+                    Multiply => Value::Int(a.checked_mul(b).ok_or_else(overflow)?),
+                    Divide | Remainder if b == 0 => return Err(Stop::error("division by zero")),
+                    // Division rounds down and remainders take the sign of the divisor, like
+                    // in Python
+                    Divide => {
+                        let quotient = a / b;
+                        let rounded_down = a % b != 0 && (a < 0) != (b < 0);
+                        Value::Int(quotient - rounded_down as i128)
+                    }
+                    Remainder => Value::Int(((a % b) + b) % b),
+                    ShiftLeft => Value::Int(a.checked_shl(shift(b)?).ok_or_else(overflow)?),
+                    ShiftRight => Value::Int(a >> shift(b)?.min(127)),
+                    BitAnd => Value::Int(a & b),
+                    BitOr => Value::Int(a | b),
+                    BitXor => Value::Int(a ^ b),
+                    Equal => Value::Bool(a == b),
+                    NotEqual => Value::Bool(a != b),
+                    Less => Value::Bool(a < b),
+                    LessEqual => Value::Bool(a <= b),
+                    Greater => Value::Bool(a > b),
+                    GreaterEqual => Value::Bool(a >= b),
+                    And | Or => return Err(Stop::error("and/or need booleans")),
+                }
+            }
+            (Value::Int(_) | Value::Float(_), Value::Int(_) | Value::Float(_)) => {
+                let float = |value: &Value| match value {
+                    Value::Int(value) => *value as f64,
+                    Value::Float(value) => *value,
+                    _ => unreachable!(),
+                };
+                let (a, b) = (float(lhs), float(rhs));
+                match operator {
+                    Add => Value::Float(a + b),
+                    Subtract => Value::Float(a - b),
+                    Multiply => Value::Float(a * b),
+                    Divide => Value::Float(a / b),
+                    Equal => Value::Bool(a == b),
+                    NotEqual => Value::Bool(a != b),
+                    Less => Value::Bool(a < b),
+                    LessEqual => Value::Bool(a <= b),
+                    Greater => Value::Bool(a > b),
+                    GreaterEqual => Value::Bool(a >= b),
+                    _ => return Err(Stop::error("invalid operation on floats")),
+                }
+            }
+            (Value::Str(a), Value::Str(b)) => match operator {
+                Add => Value::Str(format!("{}{}", a, b).into()),
+                _ => Value::Bool(compare(operator, a.cmp(b))?),
+            },
+            (Value::Bytes(a), Value::Bytes(b)) => Value::Bool(compare(operator, a.cmp(b))?),
+            (Value::Bool(a), Value::Bool(b)) => Value::Bool(compare(operator, a.cmp(b))?),
+            (Value::Object(a), Value::Object(b)) => match operator {
+                Equal => Value::Bool(Rc::ptr_eq(a, b)),
+                NotEqual => Value::Bool(!Rc::ptr_eq(a, b)),
+                _ => return Err(Stop::error("objects can only be compared for equality")),
+            },
+            (a, b) => {
+                return Err(Stop::error(format!(
+                    "can't combine {} and {}",
+                    a.kind(),
+                    b.kind()
+                )))
+            }
+        })
+    }
+
+    /// Properties and methods of values, like `.length` or `.to_s("ASCII")`
+    fn method(&self, target: &Value, name: &str, arguments: &[Value]) -> Result<Value> {
+        let unknown = || {
+            Stop::error(format!(
+                "{} has no property or method '{}'",
+                target.kind(),
+                name
+            ))
+        };
+        Ok(match (target, name) {
+            (Value::Object(object), _) => self.member(object, name)?,
+            (Value::Int(value), "to_s") => Value::Str(value.to_string().into()),
+            (Value::Int(value), "to_i") => Value::Int(*value),
+            (Value::Float(value), "to_i") => Value::Int(*value as i128),
+            (Value::Bool(value), "to_i") => Value::Int(*value as i128),
+            (Value::Str(text), "length") => Value::Int(text.chars().count() as i128),
+            (Value::Str(text), "reverse") => {
+                Value::Str(text.chars().rev().collect::<String>().into())
+            }
+            (Value::Str(text), "to_i") => {
+                let radix = match arguments.first() {
+                    Some(radix) => u32::try_from(radix.int()?).map_err(|_| unknown())?,
+                    None => 10,
+                };
+                let value = i128::from_str_radix(text.trim(), radix)
+                    .map_err(|_| Stop::error(format!("'{}' isn't a number", text)))?;
+                Value::Int(value)
+            }
+            (Value::Str(text), "substring") => {
+                let bound = |index: usize| -> Result<usize> {
+                    let value = arguments.get(index).ok_or_else(unknown)?.int()?;
+                    Ok(usize::try_from(value).unwrap_or(0))
+                };
+                let (from, to) = (bound(0)?, bound(1)?);
+                Value::Str(
+                    text.chars()
+                        .skip(from)
+                        .take(to.saturating_sub(from))
+                        .collect::<String>()
+                        .into(),
+                )
+            }
+            (Value::Bytes(bytes), "length" | "size") => Value::Int(bytes.len() as i128),
+            (Value::Bytes(bytes), "first") => {
+                Value::Int((*bytes.first().ok_or_else(unknown)?).into())
+            }
+            (Value::Bytes(bytes), "last") => {
+                Value::Int((*bytes.last().ok_or_else(unknown)?).into())
+            }
+            (Value::Bytes(bytes), "min") => {
+                Value::Int((*bytes.iter().min().ok_or_else(unknown)?).into())
+            }
+            (Value::Bytes(bytes), "max") => {
+                Value::Int((*bytes.iter().max().ok_or_else(unknown)?).into())
+            }
+            (Value::Bytes(bytes), "to_s") => {
+                let encoding = match arguments.first() {
+                    Some(Value::Str(encoding)) => encoding.to_string(),
+                    _ => "UTF-8".to_string(),
+                };
+                Value::Str(decode(bytes, &encoding).into())
+            }
+            (Value::Array(items), "length" | "size") => Value::Int(items.len() as i128),
+            (Value::Array(items), "first") => items.first().ok_or_else(unknown)?.value.clone(),
+            (Value::Array(items), "last") => items.last().ok_or_else(unknown)?.value.clone(),
+            (Value::Array(items), "min" | "max") => {
+                let mut values = items.iter().map(|item| item.value.int());
+                let first = values.next().ok_or_else(unknown)??;
+                let value = values.try_fold(first, |best, value| {
+                    let value = value?;
+                    Ok(if name == "min" {
+                        best.min(value)
+                    } else {
+                        best.max(value)
+                    })
+                })?;
+                Value::Int(value)
+            }
+            (Value::Stream(stream), "size") => Value::Int((stream.end - stream.start).into()),
+            (Value::Stream(stream), "pos") => Value::Int((stream.pos - stream.start).into()),
+            (Value::Stream(stream), "eof") => {
+                Value::Bool(stream.pos >= stream.end && stream.bit_count == 0)
+            }
+            _ => return Err(unknown()),
+        })
+    }
+}
+
+fn shift(amount: i128) -> Result<u32> {
+    u32::try_from(amount).map_err(|_| Stop::error(format!("invalid shift {}", amount)))
+}
+
+fn compare(operator: BinaryOperator, ordering: std::cmp::Ordering) -> Result<bool> {
+    use std::cmp::Ordering::*;
+    Ok(match operator {
+        BinaryOperator::Equal => ordering == Equal,
+        BinaryOperator::NotEqual => ordering != Equal,
+        BinaryOperator::Less => ordering == Less,
+        BinaryOperator::LessEqual => ordering != Greater,
+        BinaryOperator::Greater => ordering == Greater,
+        BinaryOperator::GreaterEqual => ordering != Less,
+        _ => return Err(Stop::error("invalid operation")),
+    })
+}
+
+/// Text of a string field, unknown encodings are read as UTF-8
+fn decode(bytes: &[u8], encoding: &str) -> String {
+    let normalized = encoding.to_ascii_uppercase().replace(['-', '_'], "");
+    match normalized.as_str() {
+        "UTF16LE" | "UTF16BE" => {
+            let units = bytes.chunks_exact(2).map(|pair| {
+                let pair = [pair[0], pair[1]];
+                if normalized == "UTF16LE" {
+                    u16::from_le_bytes(pair)
+                } else {
+                    u16::from_be_bytes(pair)
+                }
+            });
+            char::decode_utf16(units)
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect()
+        }
+        "ISO88591" | "LATIN1" => bytes.iter().map(|&b| b as char).collect(),
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(spec: &str, data: &[u8]) -> Rc<Object> {
+        parse(&Spec::load(spec).unwrap(), data)
+    }
+
+    fn int(object: &Object, name: &str) -> i128 {
+        match object.field(name) {
+            Some(Value::Int(value)) => value,
+            _ => panic!("{} isn't an integer", name),
+        }
+    }
+
+    fn boolean(object: &Object, name: &str) -> bool {
+        match object.field(name) {
+            Some(Value::Bool(value)) => value,
+            _ => panic!("{} isn't a boolean", name),
+        }
+    }
+
+    fn string(object: &Object, name: &str) -> String {
+        match object.field(name) {
+            Some(Value::Str(text)) => text.to_string(),
+            _ => panic!("{} isn't a string", name),
+        }
+    }
+
+    fn child(object: &Object, name: &str) -> Rc<Object> {
+        match object.field(name) {
+            Some(Value::Object(child)) => child,
+            _ => panic!("{} isn't an object", name),
+        }
+    }
+
+    fn items(object: &Object, name: &str) -> Rc<[Item]> {
+        match object.field(name) {
+            Some(Value::Array(items)) => items,
+            _ => panic!("{} isn't an array", name),
+        }
+    }
+
+    fn item_object(item: &Item) -> Rc<Object> {
+        match &item.value {
+            Value::Object(object) => object.clone(),
+            _ => panic!("the item isn't an object"),
+        }
+    }
+
+    /// Names of the fields and instances, in the order they are shown
+    fn names(object: &Object) -> Vec<String> {
+        let fields = object.fields.borrow();
+        fields.iter().map(|field| field.name.clone()).collect()
+    }
+
+    const CHUNKS: &str = "
+meta:
+  id: chunks
+  endian: be
+seq:
+  - id: chunks
+    type: chunk
+    repeat: until
+    repeat-until: _.kind == 0 or _index >= 3
+  - id: rest
+    size-eos: true
+types:
+  chunk:
+    seq:
+      - id: kind
+        type: u1
+      - id: len
+        type: u1
+      - id: body
+        size: len
+";
+
+    #[test]
+    fn repeat_until() {
+        let root = read(CHUNKS, &[1, 2, 0xaa, 0xbb, 2, 0, 0, 1, 0xcc, 0xdd]);
+        assert_eq!(root.first_error(), None);
+        let chunks = items(&root, "chunks");
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].span, Some((0, 4)));
+        assert_eq!(chunks[2].span, Some((6, 3)));
+        assert_eq!(int(&item_object(&chunks[1]), "kind"), 2);
+        assert_eq!(int(&item_object(&chunks[2]), "len"), 1);
+        assert_eq!(root.fields.borrow()[0].item.span, Some((0, 9)));
+        // `_index` stops it after the fourth chunk when none has kind 0
+        let root = read(CHUNKS, &[1, 0, 2, 0, 3, 0, 4, 0, 5, 0]);
+        assert_eq!(items(&root, "chunks").len(), 4);
+        assert_eq!(root.fields.borrow()[1].item.span, Some((8, 2)));
+    }
+
+    #[test]
+    fn repeat_until_past_the_end() {
+        let root = read(CHUNKS, &[1, 1, 0xaa, 2, 5, 0xbb]);
+        // The chunks read so far are kept along with the error
+        let chunks = items(&root, "chunks");
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(int(&item_object(&chunks[0]), "kind"), 1);
+        assert!(root
+            .first_error()
+            .is_some_and(|error| error.starts_with("body: ")));
+        assert_eq!(names(&root), ["chunks"]);
+    }
+
+    const SWITCH: &str = "
+meta:
+  id: switch
+  endian: le
+seq:
+  - id: records
+    type: record
+    repeat: eos
+types:
+  record:
+    seq:
+      - id: kind
+        type: u1
+        enum: kind
+      - id: body
+        type:
+          switch-on: kind
+          cases:
+            'kind::byte': u1
+            'kind::word': u2be
+            'kind::pair': pair
+      - id: sized
+        size: 1
+        type:
+          switch-on: kind.to_i + 1
+          cases:
+            2: u1
+  pair:
+    seq:
+      - id: first
+        type: u1
+      - id: second
+        type: s1
+enums:
+  kind:
+    1: byte
+    2: word
+    3: pair
+";
+
+    #[test]
+    fn switch_on() {
+        let root = read(
+            SWITCH,
+            &[1, 0x7f, 0xee, 2, 0x12, 0x34, 0xee, 3, 5, 0xff, 0xee],
+        );
+        assert_eq!(root.first_error(), None);
+        let records = items(&root, "records");
+        assert_eq!(records.len(), 3);
+        let byte = item_object(&records[0]);
+        assert_eq!(
+            byte.fields.borrow()[0].item.meaning.as_deref(),
+            Some("byte")
+        );
+        assert_eq!(int(&byte, "body"), 0x7f);
+        assert_eq!(int(&byte, "sized"), 0xee);
+        let word = item_object(&records[1]);
+        assert_eq!(int(&word, "body"), 0x1234);
+        // No case matches, so the sized field is kept as bytes
+        assert!(matches!(word.field("sized"), Some(Value::Bytes(bytes)) if *bytes == [0xee]));
+        let pair = child(&item_object(&records[2]), "body");
+        assert_eq!((int(&pair, "first"), int(&pair, "second")), (5, -1));
+    }
+
+    #[test]
+    fn switch_without_a_matching_case() {
+        let root = read(SWITCH, &[1, 0x7f, 0xee, 9, 0]);
+        assert_eq!(root.first_error(), None);
+        let unknown = item_object(&items(&root, "records")[1]);
+        assert_eq!(
+            unknown.fields.borrow()[0].item.meaning.as_deref(),
+            Some("unknown")
+        );
+        // The body has no size so it's left out, the sized field is read as bytes
+        assert_eq!(names(&unknown), ["kind", "sized"]);
+        assert!(matches!(unknown.field("sized"), Some(Value::Bytes(bytes)) if *bytes == [0]));
+    }
+
+    const INSTANCES: &str = "
+meta:
+  id: instances
+  endian: le
+seq:
+  - id: header
+    type: header
+  - id: entries
+    type: entry(_index)
+    repeat: expr
+    repeat-expr: header.count
+instances:
+  at_end:
+    pos: _io.size - 2
+    type: u2
+  total:
+    value: entries[0].value + entries[1].value
+  loops:
+    value: loops + 1
+  hidden:
+    pos: 0
+    type: u4
+    if: false
+types:
+  header:
+    seq:
+      - id: count
+        type: u1
+    instances:
+      last_entry:
+        value: _parent.entries[count - 1].value
+  entry:
+    params:
+      - id: position
+        type: u1
+    seq:
+      - id: raw
+        type: u1
+    instances:
+      value:
+        value: raw * 10 + position
+      raw_again:
+        pos: 1 + position
+        type: u1
+";
+
+    #[test]
+    fn instances() {
+        let root = read(INSTANCES, &[2, 3, 4, 0x34, 0x12]);
+        // Fields first, then the instances in the order they are declared. `loops` fails and
+        // `hidden` is skipped by its condition, so neither is there.
+        assert_eq!(names(&root), ["header", "entries", "at_end", "total"]);
+        let at_end = &root.fields.borrow()[2];
+        assert!(at_end.instance);
+        assert_eq!(at_end.item.span, Some((3, 2)));
+        assert_eq!(int(&root, "at_end"), 0x1234);
+        assert_eq!(int(&root, "total"), 30 + 41);
+        // The header can look at entries that come after it
+        assert_eq!(int(&child(&root, "header"), "last_entry"), 41);
+        let second = item_object(&items(&root, "entries")[1]);
+        assert_eq!(int(&second, "value"), 41);
+        assert_eq!(int(&second, "raw_again"), 4);
+        let errors = root.errors.borrow();
+        assert_eq!(*errors, ["loops: 'loops' depends on itself"]);
+    }
+
+    /// Specs adapted from the Kaitai Struct format gallery
+    #[test]
+    fn gallery_vlq_base128_le() {
+        let spec = Spec::load(include_str!("fixtures/vlq_base128_le.ksy")).unwrap();
+        let root = parse(&spec, &[0xac, 0x02]);
+        assert_eq!(root.first_error(), None);
+        assert_eq!(int(&root, "len"), 2);
+        assert_eq!(int(&root, "value"), 300);
+        assert_eq!(int(&root, "value_signed"), 300);
+        let root = parse(&spec, &[0x7f]);
+        assert_eq!(int(&root, "value"), 0x7f);
+        assert_eq!(int(&root, "value_signed"), -1);
+        let group = item_object(&items(&root, "groups")[0]);
+        assert!(!boolean(&group, "has_next"));
+    }
+
+    #[test]
+    fn gallery_gif() {
+        let spec = Spec::load(include_str!("fixtures/gif.ksy")).unwrap();
+        let image = [
+            b"GIF89a".as_slice(),
+            // 1x1, global color table of 2 colors
+            &[1, 0, 1, 0, 0x80, 0, 0],
+            &[0, 0, 0, 0xff, 0xff, 0xff],
+            // Graphic control extension
+            &[0x21, 0xf9, 4, 1, 10, 0, 0, 0],
+            // Comment
+            &[0x21, 0xfe, 3, b'h', b'i', b'!', 0],
+            // Image descriptor and data
+            &[0x2c, 0, 0, 0, 0, 1, 0, 1, 0, 0],
+            &[2, 2, 0x44, 1, 0],
+            &[0x3b],
+        ]
+        .concat();
+        let root = parse(&spec, &image);
+        assert_eq!(root.first_error(), None);
+        assert_eq!(string(&child(&root, "hdr"), "version"), "89a");
+        let screen = child(&root, "logical_screen_descriptor");
+        assert!(boolean(&screen, "has_color_table"));
+        assert_eq!(int(&screen, "color_table_size"), 2);
+        let colors = items(&child(&root, "global_color_table"), "entries");
+        assert_eq!(colors.len(), 2);
+        assert_eq!(int(&item_object(&colors[1]), "blue"), 0xff);
+        let blocks = items(&root, "blocks");
+        let meanings = blocks
+            .iter()
+            .map(|block| item_object(block).fields.borrow()[0].item.meaning.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            meanings,
+            [
+                "extension",
+                "extension",
+                "local_image_descriptor",
+                "end_of_file"
+            ]
+            .map(|name| Some(name.to_string()))
+        );
+        let control = child(&child(&item_object(&blocks[0]), "body"), "body");
+        assert_eq!(int(&control, "delay_time"), 10);
+        assert!(boolean(&control, "transparent_color_flag"));
+        let comment = child(&child(&item_object(&blocks[1]), "body"), "body");
+        let entries = items(&comment, "entries");
+        assert_eq!(entries.len(), 2);
+        assert!(
+            matches!(item_object(&entries[0]).field("bytes"), Some(Value::Bytes(bytes)) if *bytes == *b"hi!")
+        );
+        let descriptor = child(&item_object(&blocks[2]), "body");
+        assert_eq!(int(&descriptor, "width"), 1);
+        assert!(!boolean(&descriptor, "has_color_table"));
+        let data = child(&descriptor, "image_data");
+        assert_eq!(int(&data, "lzw_min_code_size"), 2);
+    }
+
+    #[test]
+    fn gallery_dos_datetime() {
+        let spec = Spec::load(include_str!("fixtures/dos_datetime.ksy")).unwrap();
+        // 2024-03-17 13:45:30
+        let root = parse(&spec, &[0xaf, 0x6d, 0x71, 0x58]);
+        assert_eq!(root.first_error(), None);
+        let time = child(&root, "time");
+        assert_eq!(
+            (
+                int(&time, "hour"),
+                int(&time, "minute"),
+                int(&time, "second")
+            ),
+            (13, 45, 30)
+        );
+        assert_eq!(string(&time, "padded_hour"), "13");
+        let date = child(&root, "date");
+        assert_eq!(
+            (int(&date, "year"), int(&date, "month"), int(&date, "day")),
+            (2024, 3, 17)
+        );
+        assert_eq!(string(&date, "padded_month"), "03");
+        assert_eq!(string(&date, "padded_year"), "2024");
+    }
+}
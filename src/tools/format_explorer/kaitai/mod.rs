@@ -0,0 +1,132 @@
+//! Formats described by Kaitai Struct specs (`.ksy` files) loaded at runtime
+
+use std::rc::Rc;
+
+use super::{formats::hex_bytes, text_field, FileFormatUi, FormatContext};
+
+mod expression;
+mod interpreter;
+mod spec;
+mod yaml;
+
+use interpreter::{Item, Object, Value};
+pub use spec::Spec;
+
+/// Items of a repeated field shown at most, more would make the explorer crawl
+const SHOWN_ITEMS: usize = 1000;
+/// Bytes of a field shown at most
+const SHOWN_BYTES: usize = 32;
+
+/// A file read with a spec
+pub struct KaitaiFormat {
+    spec: Rc<Spec>,
+    root: Rc<Object>,
+}
+
+impl KaitaiFormat {
+    pub fn new(spec: Rc<Spec>, bytes: &[u8]) -> Self {
+        let root = interpreter::parse(&spec, bytes);
+        Self { spec, root }
+    }
+
+    /// What went wrong reading the file, the fields before it are still there
+    pub fn error(&self) -> Option<String> {
+        self.root.first_error()
+    }
+
+    fn object_ui(
+        &self,
+        ui: &mut egui::Ui,
+        title: &str,
+        object: &Object,
+        context: &FormatContext,
+    ) -> egui::Response {
+        let span = context.at(object.start, object.end.get() - object.start);
+        span.collapsing(ui, title, |ui| {
+            for error in object.errors.borrow().iter() {
+                ui.colored_label(ui.visuals().error_fg_color, format!("⚠ {}", error));
+            }
+            for field in object.fields.borrow().iter() {
+                let response = self.item_ui(ui, &field.name, &field.item, context);
+                if let Some(doc) = &field.doc {
+                    response.on_hover_text(doc);
+                }
+            }
+        })
+        .header_response
+    }
+
+    fn item_ui(
+        &self,
+        ui: &mut egui::Ui,
+        name: &str,
+        item: &Item,
+        context: &FormatContext,
+    ) -> egui::Response {
+        match &item.value {
+            Value::Object(object) => {
+                let type_name = &self.spec.types[object.type_index].name;
+                self.object_ui(ui, &format!("{}: {}", name, type_name), object, context)
+            }
+            Value::Array(items) => {
+                let title = format!("{} ({})", name, items.len());
+                let collapsing = |ui: &mut egui::Ui| {
+                    for (index, item) in items.iter().take(SHOWN_ITEMS).enumerate() {
+                        self.item_ui(ui, &format!("[{}]", index), item, context);
+                    }
+                    if items.len() > SHOWN_ITEMS {
+                        ui.weak(format!("… {} more", items.len() - SHOWN_ITEMS));
+                    }
+                };
+                match item.span {
+                    Some((offset, length)) => {
+                        context
+                            .at(offset, length)
+                            .collapsing(ui, &title, collapsing)
+                            .header_response
+                    }
+                    None => ui.collapsing(title, collapsing).header_response,
+                }
+            }
+            value => {
+                let text = value_text(value, context);
+                let text = match &item.meaning {
+                    Some(meaning) => format!("{} ({})", text, meaning),
+                    None => text,
+                };
+                ui.scope(|ui| match item.span {
+                    Some((offset, length)) => context.at(offset, length).field(ui, name, &text),
+                    None => text_field(ui, name, &text),
+                })
+                .response
+            }
+        }
+    }
+}
+
+/// Text of a value that isn't an object or an array
+fn value_text(value: &Value, context: &FormatContext) -> String {
+    match value {
+        Value::Int(value) => match u64::try_from(*value) {
+            Ok(value) => context.number(value),
+            Err(_) => value.to_string(),
+        },
+        Value::Float(value) => value.to_string(),
+        Value::Bool(value) => value.to_string(),
+        Value::Str(text) => format!("{:?}", text),
+        Value::Bytes(bytes) if bytes.len() > SHOWN_BYTES => format!(
+            "{} … ({} bytes)",
+            hex_bytes(&bytes[..SHOWN_BYTES]),
+            bytes.len()
+        ),
+        Value::Bytes(bytes) => hex_bytes(bytes),
+        Value::Stream(stream) => format!("stream {:#x}..{:#x}", stream.start, stream.end),
+        Value::Array(_) | Value::Object(_) => unreachable!("shown as collapsing sections"),
+    }
+}
+
+impl FileFormatUi for KaitaiFormat {
+    fn ui(&mut self, ui: &mut egui::Ui, _name: &str, context: &FormatContext) {
+        self.object_ui(ui, &self.spec.title, &self.root, context);
+    }
+}
@@ -0,0 +1,528 @@
+//! What a `.ksy` file declares, checked and with type and enum names resolved
+
+use std::rc::Rc;
+
+use super::{
+    expression::{self, Expr},
+    yaml::{self, Yaml},
+};
+
+/// A loaded Kaitai Struct spec, ready to parse files
+pub struct Spec {
+    /// `meta/id`
+    pub id: String,
+    /// `meta/title`, or the id
+    pub title: String,
+    /// Every type of the spec, the top level one first
+    pub(super) types: Vec<TypeDef>,
+}
+
+pub(super) struct TypeDef {
+    pub name: String,
+    /// Type this one is declared in, where names are looked up next
+    pub parent: Option<usize>,
+    /// Default byte order of the numbers, `None` if the spec doesn't say
+    pub big_endian: Option<bool>,
+    pub bits_big_endian: bool,
+    pub encoding: Option<String>,
+    pub params: Vec<String>,
+    pub seq: Vec<Attribute>,
+    pub instances: Vec<Attribute>,
+    pub types: Vec<(String, usize)>,
+    pub enums: Vec<(String, Rc<EnumDef>)>,
+}
+
+/// Names of the values of an enum
+pub(super) struct EnumDef {
+    pub values: Vec<(i128, String)>,
+}
+
+impl EnumDef {
+    pub fn name(&self, value: i128) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(number, _)| *number == value)
+            .map(|(_, name)| name.as_str())
+    }
+}
+
+/// A field of `seq` or an instance
+pub(super) struct Attribute {
+    pub id: String,
+    pub doc: Option<String>,
+    pub kind: Kind,
+    /// Fixed bytes the field must hold, like a magic number
+    pub contents: Option<Vec<u8>>,
+    pub size: Option<Expr>,
+    /// The field goes on to the end of the stream
+    pub size_eos: bool,
+    pub terminator: Option<u8>,
+    /// Whether the terminator is stepped over, and whether it's part of the value
+    pub consume: bool,
+    pub include: bool,
+    /// Whether reaching the end of the stream before the terminator is an error
+    pub eos_error: bool,
+    pub encoding: Option<String>,
+    pub enum_def: Option<Rc<EnumDef>>,
+    pub repeat: Repeat,
+    pub condition: Option<Expr>,
+    /// Where an instance is read, relative to the start of the stream
+    pub pos: Option<Expr>,
+    /// Stream an instance is read from, the one of the object by default
+    pub io: Option<Expr>,
+    /// Value of a computed instance, which isn't read from the file
+    pub value: Option<Expr>,
+}
+
+pub(super) enum Repeat {
+    None,
+    /// Until the end of the stream
+    Eos,
+    Count(Expr),
+    /// Until the expression is true for the last item, `_`
+    Until(Expr),
+}
+
+pub(super) enum Kind {
+    /// No type, the raw bytes
+    Bytes,
+    Int {
+        size: u8,
+        signed: bool,
+        big_endian: Option<bool>,
+    },
+    Float {
+        size: u8,
+        big_endian: Option<bool>,
+    },
+    /// Bit-sized integer, `b1` to `b64`
+    Bits {
+        width: u8,
+        big_endian: Option<bool>,
+    },
+    Str {
+        zero_terminated: bool,
+    },
+    User {
+        index: usize,
+        arguments: Vec<Expr>,
+    },
+    /// Type picked by the value of `on`, `None` cases match anything
+    Switch {
+        on: Expr,
+        cases: Vec<(Option<Expr>, Kind)>,
+    },
+}
+
+impl Spec {
+    /// Reads a spec from the text of a `.ksy` file
+    pub fn load(text: &str) -> Result<Self, String> {
+        let document = yaml::parse(text).map_err(|err| err.to_string())?;
+        if !matches!(document, Yaml::Map(_)) {
+            return Err("the spec isn't a mapping".to_string());
+        }
+        if document
+            .get("meta")
+            .and_then(|meta| meta.get("imports"))
+            .is_some()
+        {
+            return Err("imports aren't supported".to_string());
+        }
+        let id = document
+            .get("meta")
+            .and_then(|meta| meta.get("id"))
+            .and_then(Yaml::as_str)
+            .ok_or("meta/id is missing")?
+            .to_string();
+        let title = document
+            .get("meta")
+            .and_then(|meta| meta.get("title"))
+            .and_then(Yaml::as_str)
+            .unwrap_or(&id)
+            .to_string();
+        let mut loader = Loader {
+            types: Vec::new(),
+            yamls: Vec::new(),
+        };
+        loader.declare(&document, &id, None)?;
+        for index in 0..loader.types.len() {
+            loader.define(index)?;
+        }
+        Ok(Self {
+            id,
+            title,
+            types: loader.types,
+        })
+    }
+}
+
+/// Builds the types in two passes, so fields can use types declared after them
+struct Loader<'a> {
+    types: Vec<TypeDef>,
+    /// Declaration of each type
+    yamls: Vec<&'a Yaml>,
+}
+
+impl<'a> Loader<'a> {
+    /// Adds the type and the ones nested in it, with their enums but not their fields yet
+    fn declare(
+        &mut self,
+        yaml: &'a Yaml,
+        name: &str,
+        parent: Option<usize>,
+    ) -> Result<usize, String> {
+        let meta = yaml.get("meta");
+        let inherited = parent.map(|parent| &self.types[parent]);
+        let big_endian = match meta.and_then(|meta| meta.get("endian")) {
+            None => inherited.and_then(|parent| parent.big_endian),
+            Some(Yaml::Scalar(endian)) if endian == "le" => Some(false),
+            Some(Yaml::Scalar(endian)) if endian == "be" => Some(true),
+            Some(_) => return Err(format!("{}: unsupported meta/endian", name)),
+        };
+        let bits_big_endian = match meta.and_then(|meta| meta.get("bit-endian")) {
+            None => match inherited {
+                Some(parent) => parent.bits_big_endian,
+                None => true,
+            },
+            Some(endian) => endian.as_str() != Some("le"),
+        };
+        let encoding = meta
+            .and_then(|meta| meta.get("encoding"))
+            .and_then(Yaml::as_str)
+            .map(str::to_string)
+            .or_else(|| inherited.and_then(|parent| parent.encoding.clone()));
+        let mut enums = Vec::new();
+        for (enum_name, values) in yaml.get("enums").map_or(&[][..], Yaml::entries) {
+            let values = values
+                .entries()
+                .iter()
+                .map(|(key, value)| {
+                    let number = integer(key)
+                        .ok_or_else(|| format!("{}: invalid enum key '{}'", enum_name, key))?;
+                    let name = match value {
+                        Yaml::Scalar(name) => name.clone(),
+                        _ => value
+                            .get("id")
+                            .and_then(Yaml::as_str)
+                            .ok_or_else(|| format!("{}: enum value without id", enum_name))?
+                            .to_string(),
+                    };
+                    Ok((number, name))
+                })
+                .collect::<Result<_, String>>()?;
+            enums.push((enum_name.clone(), Rc::new(EnumDef { values })));
+        }
+        let index = self.types.len();
+        self.types.push(TypeDef {
+            name: name.to_string(),
+            parent,
+            big_endian,
+            bits_big_endian,
+            encoding,
+            params: Vec::new(),
+            seq: Vec::new(),
+            instances: Vec::new(),
+            types: Vec::new(),
+            enums,
+        });
+        self.yamls.push(yaml);
+        for (type_name, declaration) in yaml.get("types").map_or(&[][..], Yaml::entries) {
+            let nested = self.declare(declaration, type_name, Some(index))?;
+            self.types[index].types.push((type_name.clone(), nested));
+        }
+        Ok(index)
+    }
+
+    /// Reads the parameters, fields and instances of a declared type
+    fn define(&mut self, index: usize) -> Result<(), String> {
+        let yaml = self.yamls[index];
+        let name = self.types[index].name.clone();
+        let params = match yaml.get("params") {
+            None => Vec::new(),
+            Some(Yaml::List(params)) => params
+                .iter()
+                .map(|param| {
+                    param
+                        .get("id")
+                        .and_then(Yaml::as_str)
+                        .map(str::to_string)
+                        .ok_or_else(|| format!("{}: parameter without id", name))
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err(format!("{}: params isn't a list", name)),
+        };
+        let seq = match yaml.get("seq") {
+            None => Vec::new(),
+            Some(Yaml::List(fields)) => fields
+                .iter()
+                .enumerate()
+                .map(|(position, field)| {
+                    let id = field
+                        .get("id")
+                        .and_then(Yaml::as_str)
+                        .map_or_else(|| format!("_unnamed{}", position), str::to_string);
+                    self.attribute(index, &id, field)
+                        .map_err(|err| format!("{}.{}: {}", name, id, err))
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err(format!("{}: seq isn't a list", name)),
+        };
+        let instances = yaml
+            .get("instances")
+            .map_or(&[][..], Yaml::entries)
+            .iter()
+            .map(|(id, instance)| {
+                self.attribute(index, id, instance)
+                    .map_err(|err| format!("{}.{}: {}", name, id, err))
+            })
+            .collect::<Result<_, _>>()?;
+        let definition = &mut self.types[index];
+        definition.params = params;
+        definition.seq = seq;
+        definition.instances = instances;
+        Ok(())
+    }
+
+    fn attribute(&self, scope: usize, id: &str, yaml: &Yaml) -> Result<Attribute, String> {
+        let text = |key: &str| yaml.get(key).and_then(Yaml::as_str);
+        let flag = |key: &str, default: bool| match text(key) {
+            Some(value) => value == "true",
+            None => default,
+        };
+        let expr = |key: &str| -> Result<Option<Expr>, String> {
+            match yaml.get(key) {
+                None => Ok(None),
+                Some(Yaml::Scalar(text)) => self.expression(scope, text).map(Some),
+                Some(_) => Err(format!("{} isn't an expression", key)),
+            }
+        };
+        if yaml.get("process").is_some() {
+            return Err("process isn't supported".to_string());
+        }
+        let kind = match yaml.get("type") {
+            None => Kind::Bytes,
+            Some(Yaml::Scalar(name)) => self.kind(scope, name)?,
+            Some(switch @ Yaml::Map(_)) => {
+                let on = switch
+                    .get("switch-on")
+                    .and_then(Yaml::as_str)
+                    .ok_or("switch-on is missing")?;
+                let cases = switch
+                    .get("cases")
+                    .map_or(&[][..], Yaml::entries)
+                    .iter()
+                    .map(|(key, value)| {
+                        let key = match key.as_str() {
+                            "_" => None,
+                            key => Some(self.expression(scope, key)?),
+                        };
+                        let name = value.as_str().ok_or("case types must be names")?;
+                        Ok((key, self.kind(scope, name)?))
+                    })
+                    .collect::<Result<_, String>>()?;
+                Kind::Switch {
+                    on: self.expression(scope, on)?,
+                    cases,
+                }
+            }
+            Some(_) => return Err("invalid type".to_string()),
+        };
+        let contents = match yaml.get("contents") {
+            None => None,
+            Some(Yaml::Scalar(text)) => Some(text.as_bytes().to_vec()),
+            Some(Yaml::List(items)) => {
+                let mut bytes = Vec::new();
+                for item in items {
+                    let item = item.as_str().ok_or("invalid contents")?;
+                    match integer(item) {
+                        Some(byte) => bytes.push(
+                            u8::try_from(byte).map_err(|_| format!("{} isn't a byte", byte))?,
+                        ),
+                        None => bytes.extend_from_slice(item.as_bytes()),
+                    }
+                }
+                Some(bytes)
+            }
+            Some(_) => return Err("invalid contents".to_string()),
+        };
+        let repeat = match text("repeat") {
+            None => Repeat::None,
+            Some("eos") => Repeat::Eos,
+            Some("expr") => Repeat::Count(expr("repeat-expr")?.ok_or("repeat-expr is missing")?),
+            Some("until") => Repeat::Until(expr("repeat-until")?.ok_or("repeat-until is missing")?),
+            Some(other) => return Err(format!("unknown repeat '{}'", other)),
+        };
+        let zero_terminated = matches!(
+            kind,
+            Kind::Str {
+                zero_terminated: true
+            }
+        );
+        let terminator = match text("terminator") {
+            Some(value) => Some(
+                integer(value)
+                    .and_then(|byte| u8::try_from(byte).ok())
+                    .ok_or("terminator isn't a byte")?,
+            ),
+            None => zero_terminated.then_some(0),
+        };
+        let enum_def = match text("enum") {
+            Some(name) => Some(
+                self.find_enum(
+                    scope,
+                    &name.split("::").map(str::to_string).collect::<Vec<_>>(),
+                )
+                .ok_or_else(|| format!("unknown enum '{}'", name))?,
+            ),
+            None => None,
+        };
+        Ok(Attribute {
+            id: id.to_string(),
+            doc: text("doc").map(|doc| doc.trim().to_string()),
+            kind,
+            contents,
+            size: expr("size")?,
+            size_eos: flag("size-eos", false),
+            terminator,
+            consume: flag("consume", true),
+            include: flag("include", false),
+            eos_error: flag("eos-error", true),
+            encoding: text("encoding").map(str::to_string),
+            enum_def,
+            repeat,
+            condition: expr("if")?,
+            pos: expr("pos")?,
+            io: expr("io")?,
+            value: expr("value")?,
+        })
+    }
+
+    /// Built-in type like `u4le` or `strz`, or a user type with its arguments like `chunk(4)`
+    fn kind(&self, scope: usize, name: &str) -> Result<Kind, String> {
+        let (base, big_endian) = if let Some(base) = name.strip_suffix("le") {
+            (base, Some(false))
+        } else if let Some(base) = name.strip_suffix("be") {
+            (base, Some(true))
+        } else {
+            (name, None)
+        };
+        let number = |prefix: char| {
+            base.strip_prefix(prefix)
+                .and_then(|digits| digits.parse::<u8>().ok())
+        };
+        if let Some(size @ (1 | 2 | 4 | 8)) = number('u') {
+            return Ok(Kind::Int {
+                size,
+                signed: false,
+                big_endian,
+            });
+        }
+        if let Some(size @ (1 | 2 | 4 | 8)) = number('s') {
+            return Ok(Kind::Int {
+                size,
+                signed: true,
+                big_endian,
+            });
+        }
+        if let Some(size @ (4 | 8)) = number('f') {
+            return Ok(Kind::Float { size, big_endian });
+        }
+        if let Some(width @ 1..=64) = number('b') {
+            return Ok(Kind::Bits { width, big_endian });
+        }
+        match name {
+            "str" => {
+                return Ok(Kind::Str {
+                    zero_terminated: false,
+                })
+            }
+            "strz" => {
+                return Ok(Kind::Str {
+                    zero_terminated: true,
+                })
+            }
+            _ => {}
+        }
+        let (path, arguments) = match name.split_once('(') {
+            Some((path, arguments)) => {
+                let arguments = arguments.strip_suffix(')').ok_or("unclosed arguments")?;
+                // Parsed as an array literal so commas in nested calls are handled
+                match self.expression(scope, &format!("[{}]", arguments))? {
+                    Expr::Array(arguments) => (path.trim(), arguments),
+                    _ => unreachable!(),
+                }
+            }
+            None => (name, Vec::new()),
+        };
+        let path = path.split("::").map(str::to_string).collect::<Vec<_>>();
+        let index = self
+            .find_type(scope, &path)
+            .ok_or_else(|| format!("unknown type '{}'", name))?;
+        Ok(Kind::User { index, arguments })
+    }
+
+    /// Looks a type up in `scope` and the types around it, then follows the rest of the path
+    fn find_type(&self, scope: usize, path: &[String]) -> Option<usize> {
+        let (first, rest) = path.split_first()?;
+        let mut current = Some(scope);
+        let mut found = None;
+        while let Some(index) = current {
+            let definition = &self.types[index];
+            if definition.name == *first && definition.parent.is_none() {
+                found = Some(index);
+                break;
+            }
+            if let Some((_, nested)) = definition.types.iter().find(|(name, _)| name == first) {
+                found = Some(*nested);
+                break;
+            }
+            current = definition.parent;
+        }
+        rest.iter().try_fold(found?, |index, name| {
+            self.types[index]
+                .types
+                .iter()
+                .find(|(nested, _)| nested == name)
+                .map(|(_, nested)| *nested)
+        })
+    }
+
+    fn find_enum(&self, scope: usize, path: &[String]) -> Option<Rc<EnumDef>> {
+        let (name, types) = path.split_last()?;
+        let scopes = if types.is_empty() {
+            std::iter::successors(Some(scope), |&index| self.types[index].parent).collect()
+        } else {
+            vec![self.find_type(scope, types)?]
+        };
+        scopes.into_iter().find_map(|index| {
+            self.types[index]
+                .enums
+                .iter()
+                .find(|(enum_name, _)| enum_name == name)
+                .map(|(_, definition)| definition.clone())
+        })
+    }
+
+    fn expression(&self, scope: usize, text: &str) -> Result<Expr, String> {
+        let resolve_enum = |path: &[String]| {
+            let (value, enum_path) = path.split_last()?;
+            let definition = self.find_enum(scope, enum_path)?;
+            let found = definition.values.iter().find(|(_, name)| name == value);
+            found.map(|(number, _)| *number)
+        };
+        expression::parse(text, &resolve_enum)
+            .map_err(|err| format!("invalid expression '{}': {}", text, err))
+    }
+}
+
+/// Integer in any of the bases of the spec, like `0x7f` or `-1`
+fn integer(text: &str) -> Option<i128> {
+    match expression::parse(text, &|_| None).ok()? {
+        Expr::Int(value) => Some(value),
+        Expr::Unary(expression::UnaryOperator::Negate, operand) => match *operand {
+            Expr::Int(value) => Some(-value),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,756 @@
+//! The part of YAML that Kaitai Struct specs are written in: block and flow mappings and
+//! sequences, plain and quoted scalars, block scalars and comments. Anchors, tags and multiple
+//! documents aren't supported.
+
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Yaml {
+    Null,
+    /// Every scalar, numbers and booleans included, the spec decides how to read them
+    Scalar(String),
+    List(Vec<Yaml>),
+    /// Keys in the order they are written
+    Map(Vec<(String, Yaml)>),
+}
+
+impl Yaml {
+    /// Value of `key` if this is a mapping that has it
+    pub fn get(&self, key: &str) -> Option<&Yaml> {
+        match self {
+            Yaml::Map(entries) => entries
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Yaml::Scalar(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Entries of a mapping, an empty list for anything else
+    pub fn entries(&self) -> &[(String, Yaml)] {
+        match self {
+            Yaml::Map(entries) => entries,
+            _ => &[],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct YamlError {
+    /// One-based line number
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for YamlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Parses a YAML document
+pub fn parse(text: &str) -> Result<Yaml, YamlError> {
+    let mut parser = Parser {
+        lines: text
+            .lines()
+            .map(|line| line.trim_end_matches('\r').to_string())
+            .collect(),
+        position: 0,
+    };
+    // A leading document marker is allowed
+    parser.skip_blank();
+    if parser
+        .lines
+        .get(parser.position)
+        .is_some_and(|line| line.trim_end() == "---")
+    {
+        parser.position += 1;
+    }
+    let Some(indent) = parser.next_indent() else {
+        return Ok(Yaml::Null);
+    };
+    let document = parser.block(indent)?;
+    if parser.next_indent().is_some() {
+        return Err(parser.error("unexpected indentation"));
+    }
+    Ok(document)
+}
+
+struct Parser {
+    lines: Vec<String>,
+    position: usize,
+}
+
+impl Parser {
+    fn error(&self, message: impl Into<String>) -> YamlError {
+        YamlError {
+            line: self.position + 1,
+            message: message.into(),
+        }
+    }
+
+    /// Steps over empty and comment lines
+    fn skip_blank(&mut self) {
+        while let Some(line) = self.lines.get(self.position) {
+            let content = line.trim_start();
+            if !content.is_empty() && !content.starts_with('#') {
+                break;
+            }
+            self.position += 1;
+        }
+    }
+
+    /// Indentation of the next line with content
+    fn next_indent(&mut self) -> Option<usize> {
+        self.skip_blank();
+        let line = self.lines.get(self.position)?;
+        Some(line.len() - line.trim_start_matches(' ').len())
+    }
+
+    /// Content of the current line without the indentation and the comment
+    fn content(&self) -> &str {
+        strip_comment(self.lines[self.position].trim_start_matches(' '))
+    }
+
+    /// A mapping, sequence or scalar whose first line is indented by `indent`
+    fn block(&mut self, indent: usize) -> Result<Yaml, YamlError> {
+        let content = self.content().to_string();
+        if content == "-" || content.starts_with("- ") {
+            self.sequence(indent)
+        } else if split_key(&content).is_some() {
+            self.mapping(indent)
+        } else {
+            self.position += 1;
+            self.scalar_value(&content, indent)
+        }
+    }
+
+    fn sequence(&mut self, indent: usize) -> Result<Yaml, YamlError> {
+        let mut items = Vec::new();
+        while self.next_indent() == Some(indent) {
+            let content = self.content();
+            if content == "-" {
+                self.position += 1;
+                items.push(self.nested(indent, false)?);
+            } else if let Some(rest) = content.strip_prefix("- ") {
+                // The item continues as if it started at the column after the dash
+                let column = indent + 2 + (rest.len() - rest.trim_start().len());
+                let rest = rest.trim_start().to_string();
+                self.lines[self.position] = format!("{}{}", " ".repeat(column), rest);
+                items.push(self.block(column)?);
+            } else {
+                break;
+            }
+        }
+        Ok(Yaml::List(items))
+    }
+
+    fn mapping(&mut self, indent: usize) -> Result<Yaml, YamlError> {
+        let mut entries: Vec<(String, Yaml)> = Vec::new();
+        while self.next_indent() == Some(indent) {
+            let content = self.content().to_string();
+            if content == "-" || content.starts_with("- ") {
+                break;
+            }
+            let Some((key, value)) = split_key(&content) else {
+                return Err(self.error("expected a `key: value` pair"));
+            };
+            let key = scalar(key).map_err(|message| self.error(message))?;
+            if entries.iter().any(|(name, _)| *name == key) {
+                return Err(self.error(format!("duplicate key '{}'", key)));
+            }
+            self.position += 1;
+            let value = if value.is_empty() {
+                self.nested(indent, true)?
+            } else {
+                self.scalar_value(value, indent)?
+            };
+            entries.push((key, value));
+        }
+        Ok(Yaml::Map(entries))
+    }
+
+    /// Value on the lines after a key or a dash, a sequence may start at the same indentation
+    /// as the key
+    fn nested(&mut self, indent: usize, allow_sequence: bool) -> Result<Yaml, YamlError> {
+        match self.next_indent() {
+            Some(next) if next > indent => self.block(next),
+            Some(next) if next == indent && allow_sequence => {
+                let content = self.content();
+                if content == "-" || content.starts_with("- ") {
+                    self.sequence(indent)
+                } else {
+                    Ok(Yaml::Null)
+                }
+            }
+            _ => Ok(Yaml::Null),
+        }
+    }
+
+    /// A value written on the line of its key, which may go on over the next lines
+    fn scalar_value(&mut self, first: &str, indent: usize) -> Result<Yaml, YamlError> {
+        if first.starts_with('|') || first.starts_with('>') {
+            return Ok(Yaml::Scalar(self.block_scalar(first, indent)));
+        }
+        if first.starts_with('[') || first.starts_with('{') {
+            // Flow collections may be split over lines until their brackets close
+            let mut text = first.to_string();
+            while !balanced(&text) {
+                if self.position >= self.lines.len() {
+                    return Err(self.error("unclosed bracket"));
+                }
+                text.push(' ');
+                text.push_str(strip_comment(self.lines[self.position].trim()));
+                self.position += 1;
+            }
+            let mut flow = Flow {
+                text: &text,
+                position: 0,
+            };
+            let value = flow.value().map_err(|message| self.error(message))?;
+            flow.skip_spaces();
+            if flow.position < text.len() {
+                return Err(self.error("unexpected text after the flow collection"));
+            }
+            return Ok(value);
+        }
+        let mut text = first.to_string();
+        if !first.starts_with('"') && !first.starts_with('\'') {
+            // Plain scalars continue on more indented lines, joined by spaces
+            while let Some(next) = self.next_indent() {
+                if next <= indent {
+                    break;
+                }
+                text.push(' ');
+                text.push_str(self.content().trim());
+                self.position += 1;
+            }
+        }
+        match text.as_str() {
+            "~" | "null" => Ok(Yaml::Null),
+            _ => scalar(&text)
+                .map(Yaml::Scalar)
+                .map_err(|message| self.error(message)),
+        }
+    }
+
+    /// Lines after `|` or `>` indented more than the key, kept as they are or folded
+    fn block_scalar(&mut self, header: &str, indent: usize) -> String {
+        let folded = header.starts_with('>');
+        let chomping = header[1..].trim();
+        let mut lines = Vec::new();
+        let mut content_indent = None;
+        while let Some(line) = self.lines.get(self.position) {
+            let spaces = line.len() - line.trim_start_matches(' ').len();
+            if line.trim().is_empty() {
+                lines.push(String::new());
+                self.position += 1;
+                continue;
+            }
+            if spaces <= indent {
+                break;
+            }
+            let content_indent = *content_indent.get_or_insert(spaces);
+            lines.push(line[content_indent.min(spaces)..].to_string());
+            self.position += 1;
+        }
+        // Trailing blank lines belong to what follows
+        while lines.last().is_some_and(|line| line.is_empty()) {
+            lines.pop();
+            self.position -= 1;
+        }
+        let mut text = if folded {
+            lines
+                .split(|line| line.is_empty())
+                .map(|paragraph| paragraph.join(" "))
+                .collect::<Vec<_>>()
+                .join("\n")
+        } else {
+            lines.join("\n")
+        };
+        if !chomping.starts_with('-') && !text.is_empty() {
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Cuts a comment off the end of a line, `#` only starts one after a space and outside of
+/// quotes
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    let mut chars = line.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match quote {
+            // Escaped quotes don't end the string, `\"` in double quotes and `''` in single ones
+            Some('"') if c == '\\' => {
+                chars.next();
+            }
+            Some('\'') if c == '\'' && chars.peek().is_some_and(|&(_, next)| next == '\'') => {
+                chars.next();
+            }
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if (c == '"' || c == '\'') && " [{,:".contains(previous) => quote = Some(c),
+            None if c == '#' && previous.is_whitespace() => return line[..index].trim_end(),
+            None => {}
+        }
+        previous = c;
+    }
+    line.trim_end()
+}
+
+/// Splits `key: value` at the colon, `None` if the line isn't a mapping entry
+fn split_key(line: &str) -> Option<(&str, &str)> {
+    let end = if line.starts_with('"') || line.starts_with('\'') {
+        quoted_end(line)?
+    } else if line.starts_with('[') || line.starts_with('{') {
+        return None;
+    } else {
+        0
+    };
+    let colon = end
+        + line[end..]
+            .find(": ")
+            .or_else(|| line[end..].ends_with(':').then(|| line.len() - end - 1))?;
+    Some((line[..colon].trim(), line[colon + 1..].trim()))
+}
+
+/// Index after the closing quote of a quoted scalar at the start of `text`
+fn quoted_end(text: &str) -> Option<usize> {
+    let quote = text.chars().next()?;
+    let mut chars = text.char_indices().skip(1);
+    while let Some((index, c)) = chars.next() {
+        if quote == '"' && c == '\\' {
+            chars.next();
+        } else if c == quote {
+            // Two single quotes are an escaped one
+            if quote == '\'' && text[index + 1..].starts_with('\'') {
+                chars.next();
+            } else {
+                return Some(index + 1);
+            }
+        }
+    }
+    None
+}
+
+/// Whether the brackets of a flow collection are all closed
+fn balanced(text: &str) -> bool {
+    let mut depth = 0i32;
+    let mut quote = None;
+    let mut escaped = false;
+    for c in text.chars() {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '[' | '{' => depth += 1,
+                ']' | '}' => depth -= 1,
+                _ => {}
+            },
+        }
+    }
+    depth <= 0 && quote.is_none()
+}
+
+/// Text of a plain or quoted scalar
+fn scalar(text: &str) -> Result<String, String> {
+    let text = text.trim();
+    match text.chars().next() {
+        Some(quote @ ('"' | '\'')) => {
+            let end = quoted_end(text).ok_or("unclosed quote")?;
+            if !text[end..].trim().is_empty() {
+                return Err("unexpected text after the quoted string".to_string());
+            }
+            let inner = &text[1..end - 1];
+            if quote == '\'' {
+                Ok(inner.replace("''", "'"))
+            } else {
+                unescape(inner)
+            }
+        }
+        _ => Ok(text.to_string()),
+    }
+}
+
+/// Resolves the escapes of a double-quoted scalar
+fn unescape(text: &str) -> Result<String, String> {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        let escaped = match chars.next().ok_or("unfinished escape")? {
+            '0' => '\0',
+            'a' => '\x07',
+            'b' => '\x08',
+            't' => '\t',
+            'n' => '\n',
+            'v' => '\x0b',
+            'f' => '\x0c',
+            'r' => '\r',
+            'e' => '\x1b',
+            ' ' => ' ',
+            '"' => '"',
+            '/' => '/',
+            '\\' => '\\',
+            kind @ ('x' | 'u' | 'U') => {
+                let digits = match kind {
+                    'x' => 2,
+                    'u' => 4,
+                    _ => 8,
+                };
+                let hex = chars.by_ref().take(digits).collect::<String>();
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == digits)
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| format!("invalid escape '\\{}{}'", kind, hex))?
+            }
+            other => return Err(format!("unknown escape '\\{}'", other)),
+        };
+        result.push(escaped);
+    }
+    Ok(result)
+}
+
+/// Parser for `[a, b]` and `{a: b}` written on one line
+struct Flow<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl Flow<'_> {
+    fn rest(&self) -> &str {
+        &self.text[self.position..]
+    }
+
+    fn skip_spaces(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_spaces();
+        if self.rest().starts_with(c) {
+            self.position += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn value(&mut self) -> Result<Yaml, String> {
+        self.skip_spaces();
+        if self.eat('[') {
+            let mut items = Vec::new();
+            while !self.eat(']') {
+                items.push(self.value()?);
+                if !self.eat(',') && !self.rest().trim_start().starts_with(']') {
+                    return Err("expected ',' or ']'".to_string());
+                }
+            }
+            Ok(Yaml::List(items))
+        } else if self.eat('{') {
+            let mut entries = Vec::new();
+            while !self.eat('}') {
+                let key = self.scalar(true)?;
+                if !self.eat(':') {
+                    return Err("expected ':' in a flow mapping".to_string());
+                }
+                entries.push((key, self.value()?));
+                if !self.eat(',') && !self.rest().trim_start().starts_with('}') {
+                    return Err("expected ',' or '}'".to_string());
+                }
+            }
+            Ok(Yaml::Map(entries))
+        } else {
+            let text = self.scalar(false)?;
+            Ok(match text.as_str() {
+                "~" | "null" => Yaml::Null,
+                _ => Yaml::Scalar(text),
+            })
+        }
+    }
+
+    /// Quoted scalar, or plain one up to the next `,`, closing bracket, or `: ` for keys
+    fn scalar(&mut self, key: bool) -> Result<String, String> {
+        self.skip_spaces();
+        let text = self.text;
+        let rest = &text[self.position..];
+        if rest.starts_with('"') || rest.starts_with('\'') {
+            let end = quoted_end(rest).ok_or("unclosed quote")?;
+            let text = scalar(&rest[..end])?;
+            self.position += end;
+            return Ok(text);
+        }
+        let mut end = rest.len();
+        for (index, c) in rest.char_indices() {
+            let ends_key = key && c == ':' && rest[index + 1..].starts_with([' ', ',', '}']);
+            if c == ',' || c == ']' || c == '}' || ends_key {
+                end = index;
+                break;
+            }
+        }
+        self.position += end;
+        Ok(rest[..end].trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(value: &str) -> Yaml {
+        Yaml::Scalar(value.to_string())
+    }
+
+    fn map(entries: &[(&str, Yaml)]) -> Yaml {
+        Yaml::Map(
+            entries
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn block_collections() {
+        let document = parse(
+            "---\n\
+             # comment\n\
+             seq:\n\
+             - id: magic  # trailing comment\n\
+             \x20 contents: [0x89, 'P', \"NG\"]\n\
+             - id: body\n\
+             \x20 size-eos: true\n\
+             empty:\n\
+             nothing: ~\n",
+        )
+        .unwrap();
+        let expected = map(&[
+            (
+                "seq",
+                Yaml::List(vec![
+                    map(&[
+                        ("id", text("magic")),
+                        (
+                            "contents",
+                            Yaml::List(vec![text("0x89"), text("P"), text("NG")]),
+                        ),
+                    ]),
+                    map(&[("id", text("body")), ("size-eos", text("true"))]),
+                ]),
+            ),
+            ("empty", Yaml::Null),
+            ("nothing", Yaml::Null),
+        ]);
+        assert_eq!(document, expected);
+    }
+
+    #[test]
+    fn flow_collections() {
+        let document = parse(
+            "valid: {min: 1, max: 12}\n\
+             nested: [{a: [1, 2]}, [], {}]\n\
+             split: [\n\
+             \x20 one,  # comment\n\
+             \x20 \"two, three\"\n\
+             \x20 ]\n\
+             keys: {'a: b': 1, \"c\": d, e:f: g}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            document.get("valid"),
+            Some(&map(&[("min", text("1")), ("max", text("12"))]))
+        );
+        assert_eq!(
+            document.get("nested"),
+            Some(&Yaml::List(vec![
+                map(&[("a", Yaml::List(vec![text("1"), text("2")]))]),
+                Yaml::List(vec![]),
+                map(&[]),
+            ]))
+        );
+        assert_eq!(
+            document.get("split"),
+            Some(&Yaml::List(vec![text("one"), text("two, three")]))
+        );
+        assert_eq!(
+            document.get("keys"),
+            Some(&map(&[
+                ("a: b", text("1")),
+                ("c", text("d")),
+                ("e:f", text("g")),
+            ]))
+        );
+    }
+
+    #[test]
+    fn scalars() {
+        let document = parse(
+            "'quoted: key': value\n\
+             \"escapes\": \"tab\\there \\x41\\u00e9 \\\"q\\\"\"\n\
+             single: 'it''s # not a comment'\n\
+             double: \"a \\\" # b\"  # comment\n\
+             plain: a value\n\
+             \x20 that goes on\n\
+             \x20 over lines\n\
+             hash: a#b\n\
+             expression: _.type == \"IEND\" or _io.eof\n",
+        )
+        .unwrap();
+        assert_eq!(document.get("quoted: key"), Some(&text("value")));
+        assert_eq!(document.get("escapes"), Some(&text("tab\there Aé \"q\"")));
+        assert_eq!(document.get("single"), Some(&text("it's # not a comment")));
+        assert_eq!(document.get("double"), Some(&text("a \" # b")));
+        assert_eq!(
+            document.get("plain"),
+            Some(&text("a value that goes on over lines"))
+        );
+        assert_eq!(document.get("hash"), Some(&text("a#b")));
+        assert_eq!(
+            document.get("expression"),
+            Some(&text("_.type == \"IEND\" or _io.eof"))
+        );
+    }
+
+    #[test]
+    fn block_scalars() {
+        let document = parse(
+            "literal: |\n\
+             \x20 first line\n\
+             \x20   indented\n\
+             \n\
+             \x20 after a blank line\n\
+             folded: >-\n\
+             \x20 one\n\
+             \x20 two\n\
+             \n\
+             \x20 three\n\
+             next: value\n",
+        )
+        .unwrap();
+        assert_eq!(
+            document.get("literal"),
+            Some(&text("first line\n  indented\n\nafter a blank line\n"))
+        );
+        assert_eq!(document.get("folded"), Some(&text("one two\nthree")));
+        assert_eq!(document.get("next"), Some(&text("value")));
+    }
+
+    #[test]
+    fn errors() {
+        let error = |text: &str| parse(text).unwrap_err();
+        assert_eq!(
+            error("a: 1\nb: 2\na: 3\n"),
+            YamlError {
+                line: 3,
+                message: "duplicate key 'a'".to_string()
+            }
+        );
+        assert_eq!(error("a: [1, 2\n").message, "unclosed bracket");
+        assert_eq!(error("a: 'open\n").message, "unclosed quote");
+        assert_eq!(error("a: \"\\q\"\n").message, "unknown escape '\\q'");
+        assert_eq!(
+            error("a:\n    b: 1\n  c: 2\n"),
+            YamlError {
+                line: 3,
+                message: "unexpected indentation".to_string()
+            }
+        );
+        assert_eq!(parse("# only a comment\n"), Ok(Yaml::Null));
+    }
+
+    /// Specs adapted from the Kaitai Struct format gallery, with the keys this parser has to
+    /// get through to load them
+    #[test]
+    fn gallery_specs() {
+        let gif = parse(include_str!("fixtures/gif.ksy")).unwrap();
+        let meta = gif.get("meta").unwrap();
+        assert_eq!(meta.get("endian"), Some(&text("le")));
+        assert_eq!(
+            meta.get("xref").and_then(|xref| xref.get("pronom")),
+            Some(&Yaml::List(vec![text("fmt/3"), text("fmt/4")]))
+        );
+        assert_eq!(
+            meta.get("xref").and_then(|xref| xref.get("loc")),
+            Some(&text("fdd000133"))
+        );
+        let cases = gif
+            .get("types")
+            .and_then(|types| types.get("extension"))
+            .and_then(|extension| extension.get("seq"))
+            .and_then(|seq| match seq {
+                Yaml::List(fields) => fields.get(1),
+                _ => None,
+            })
+            .and_then(|body| body.get("type"))
+            .and_then(|kind| kind.get("cases"))
+            .unwrap();
+        assert_eq!(
+            cases.entries().first(),
+            Some(&(
+                "extension_label::application".to_string(),
+                text("ext_application")
+            ))
+        );
+        assert_eq!(cases.get("_"), Some(&text("subblocks")));
+        let enums = gif.get("enums").unwrap();
+        assert_eq!(
+            enums
+                .get("block_type")
+                .and_then(|values| values.get("0x3b")),
+            Some(&text("end_of_file"))
+        );
+
+        let vlq = parse(include_str!("fixtures/vlq_base128_le.ksy")).unwrap();
+        assert_eq!(
+            vlq.get("-webide-representation"),
+            Some(&text("{value:dec}"))
+        );
+        let value = vlq
+            .get("instances")
+            .and_then(|instances| instances.get("value"))
+            .and_then(|value| value.get("value"))
+            .and_then(Yaml::as_str)
+            .unwrap();
+        assert!(value.starts_with("groups[0].value + (len >= 2 ? (groups[1].value << 7) : 0) + "));
+        assert!(!value.ends_with('\n'));
+
+        let dos_datetime = parse(include_str!("fixtures/dos_datetime.ksy")).unwrap();
+        assert_eq!(
+            dos_datetime
+                .get("doc-ref")
+                .map(|doc_ref| matches!(doc_ref, Yaml::List(items) if items.len() == 2)),
+            Some(true)
+        );
+        let month = dos_datetime
+            .get("types")
+            .and_then(|types| types.get("date"))
+            .and_then(|date| date.get("seq"))
+            .and_then(|seq| match seq {
+                Yaml::List(fields) => fields.get(1),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(
+            month.get("valid"),
+            Some(&map(&[("min", text("1")), ("max", text("12"))]))
+        );
+    }
+}
@@ -1,97 +1,793 @@
-use std::sync::Arc;
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+};
 
 use egui::mutex::RwLock;
-use memmap2::MmapMut;
+use nom::{bytes::complete::take, IResult};
 
 use super::GaffrieTool;
+use crate::{
+    document::{Document, FileData},
+    error::Error,
+    Event, Selection,
+};
 
 mod formats;
+mod kaitai;
 
-pub trait FileFormatUi {
-    fn ui(&mut self, ui: &mut egui::Ui, name: &str);
+use formats::{FormatKind, UnknownFormat};
+pub use gaffrie_derive::FileFormat;
+
+/// Named values of the format the file of a document is in, like header fields. They are worked
+/// out once for each version of the bytes, parsing the whole file again for every go-to
+/// expression would be slow.
+#[derive(Clone, Default)]
+pub struct SymbolCache(Rc<RefCell<Option<(usize, Symbols)>>>);
+
+pub type Symbols = Rc<[(String, u64)]>;
+
+impl SymbolCache {
+    /// Symbols of `bytes`, which are at `version` of the document
+    pub fn get(&self, version: usize, bytes: &[u8]) -> Symbols {
+        let mut cached = self.0.borrow_mut();
+        match &*cached {
+            Some((cached_version, symbols)) if *cached_version == version => symbols.clone(),
+            _ => {
+                let symbols: Symbols = FormatKind::detect(bytes)
+                    .map_or_else(Vec::new, |kind| kind.symbols(bytes))
+                    .into();
+                *cached = Some((version, symbols.clone()));
+                symbols
+            }
+        }
+    }
 }
 
-impl FileFormatUi for () {
-    fn ui(&mut self, _ui: &mut egui::Ui, _name: &str) {}
+/// What the fields drawn in a frame have in common and found out about the cursor and mouse
+#[derive(Default)]
+struct FieldTracking {
+    /// Offset of the cursor in the hex view
+    cursor: u64,
+    /// Open and scroll to the fields covering the cursor, it just moved
+    reveal: bool,
+    /// Bytes of the field under the mouse, as offset and length
+    hovered: Cell<Option<(u64, u64)>>,
+    /// Innermost field covering the cursor
+    covering: RefCell<Option<String>>,
 }
 
-impl FileFormatUi for u8 {
-    fn ui(&mut self, ui: &mut egui::Ui, name: &str) {
+/// What parsed formats can do beyond drawing themselves
+#[derive(Clone, Copy)]
+pub struct FormatContext<'a> {
+    events: &'a Sender<Event>,
+    file: &'a RwLock<FileData>,
+    /// Show numeric fields in hexadecimal rather than decimal
+    hex: bool,
+    /// Where the parsed bytes start in the file, for formats nested in others like the slices
+    /// of universal binaries
+    base: u64,
+    /// Bytes of the field about to be drawn in the file, as offset and length
+    span: Option<(u64, u64)>,
+    tracking: &'a FieldTracking,
+}
+
+impl<'a> FormatContext<'a> {
+    /// Context for a format embedded at `offset` of the current one
+    pub fn nested(&self, offset: u64) -> Self {
+        Self {
+            base: self.base.saturating_add(offset),
+            ..*self
+        }
+    }
+
+    /// Context for a field stored at `offset` of the current format, hovering it points out
+    /// its bytes
+    pub fn at(&self, offset: u64, length: u64) -> Self {
+        Self {
+            span: Some((self.base.saturating_add(offset), length)),
+            ..*self
+        }
+    }
+
+    /// Context for entry `index` of the table the context is tied to, with entries of `size`
+    /// bytes
+    pub fn entry(&self, index: usize, size: u64) -> Self {
+        let start = self.span.map_or(self.base, |(offset, _)| offset);
+        Self {
+            span: Some((
+                start.saturating_add((index as u64).saturating_mul(size)),
+                size,
+            )),
+            ..*self
+        }
+    }
+
+    /// Lays out the fields of the structure the context is tied to, one after the other
+    pub fn fields(&self) -> Fields<'a> {
+        Fields {
+            context: *self,
+            offset: self.span.map_or(self.base, |(offset, _)| offset),
+        }
+    }
+
+    /// Whether the cursor of the hex view is in the bytes of the field
+    pub fn covers_cursor(&self) -> bool {
+        let cursor = self.tracking.cursor;
+        self.span
+            .is_some_and(|(start, length)| cursor >= start && cursor - start < length.max(1))
+    }
+
+    /// Formats a number in the base chosen for numeric fields
+    pub fn number(&self, value: u64) -> String {
+        if self.hex {
+            format!("{:#x}", value)
+        } else {
+            value.to_string()
+        }
+    }
+
+    /// Shows a field whose value is already formatted, tied to the bytes from [`Self::at`]
+    pub fn field(&self, ui: &mut egui::Ui, name: &str, text: &str) {
+        if self.span.is_none() {
+            text_field(ui, name, text);
+            return;
+        }
+        // Reserved so the background ends up behind the text
+        let background = ui.painter().add(egui::Shape::Noop);
+        let response = ui
+            .horizontal(|ui| {
+                ui.label(name);
+                ui.label(": ");
+                ui.label(text);
+            })
+            .response
+            .interact(egui::Sense::click());
+        if self.covers_cursor() {
+            let color = ui.visuals().selection.bg_fill.gamma_multiply(0.4);
+            ui.painter().set(
+                background,
+                egui::Shape::rect_filled(response.rect, 2.0, color),
+            );
+        }
+        self.track(&response, name);
+    }
+
+    /// Ties a widget showing the field to its bytes: hovering points them out, clicking
+    /// selects them, and the cursor landing in them reveals the widget
+    pub fn track(&self, response: &egui::Response, name: &str) {
+        let Some((offset, length)) = self.span else {
+            return;
+        };
+        if self.covers_cursor() {
+            *self.tracking.covering.borrow_mut() = Some(name.to_string());
+            if self.tracking.reveal {
+                response.scroll_to_me(None);
+            }
+        }
+        if response.hovered() {
+            self.tracking.hovered.set(Some((offset, length)));
+        }
+        if response.clicked() {
+            let selection = Selection::new(offset as usize, length as usize);
+            let _ = self.events.send(Event::SelectionChanged(selection));
+        }
+        response.clone().on_hover_text(format!(
+            "{:#x}..{:#x}, click to select",
+            offset,
+            offset.saturating_add(length)
+        ));
+    }
+
+    /// Collapsing section for a structure tied to its bytes, it opens when the cursor moves
+    /// into them
+    pub fn collapsing<R>(
+        &self,
+        ui: &mut egui::Ui,
+        title: &str,
+        add_contents: impl FnOnce(&mut egui::Ui) -> R,
+    ) -> egui::CollapsingResponse<R> {
+        let covers_cursor = self.covers_cursor();
+        let mut header = egui::CollapsingHeader::new(title);
+        if covers_cursor && self.tracking.reveal {
+            header = header.open(Some(true));
+        }
+        let outer = self.tracking.covering.take();
+        let response = header.show(ui, add_contents);
+        if self.tracking.covering.borrow().is_none() {
+            // None of the fields inside covers the cursor, or they aren't shown
+            *self.tracking.covering.borrow_mut() = if covers_cursor {
+                Some(title.to_string())
+            } else {
+                outer
+            };
+        }
+        if let Some((offset, length)) = self.span {
+            if response.header_response.hovered() {
+                self.tracking.hovered.set(Some((offset, length)));
+            }
+        }
+        response
+    }
+
+    /// Shows a field with a numeric value and what it means, like `62 (EM_X86_64)`
+    pub fn enum_field(&self, ui: &mut egui::Ui, name: &str, value: u64, meaning: &str) {
+        self.field(ui, name, &format!("{} ({})", self.number(value), meaning));
+    }
+
+    /// Selects the bytes in the other tools, `offset` is relative to the current format
+    pub fn select(&self, offset: u64, length: u64) {
+        let offset = self.base.saturating_add(offset);
+        let selection = Selection::new(offset as usize, length as usize);
+        let _ = self.events.send(Event::SelectionChanged(selection));
+    }
+
+    /// Opens bytes made from those of the format, like a decompressed archive entry, as a new
+    /// document. `derive` gets the bytes from the start of the format on.
+    pub fn open(&self, name: &str, derive: impl FnOnce(&[u8]) -> Result<Vec<u8>, Error>) {
+        let lock = self.file.read();
+        let bytes = usize::try_from(self.base)
+            .ok()
+            .and_then(|base| lock.get(base..))
+            .unwrap_or_default();
+        let event = match derive(bytes) {
+            Ok(bytes) => Event::Open {
+                name: name.to_string(),
+                bytes,
+            },
+            Err(err) => Event::Error(err),
+        };
+        let _ = self.events.send(event);
+    }
+
+    /// Link showing a range of the file, clicking it selects the bytes
+    pub fn range_link(&self, ui: &mut egui::Ui, name: &str, offset: u64, length: u64) {
         ui.horizontal(|ui| {
             ui.label(name);
             ui.label(": ");
-            ui.label(self.to_string());
+            let start = self.base.saturating_add(offset);
+            let text = format!("{:#x}..{:#x}", start, start.saturating_add(length));
+            let response = ui.link(text).on_hover_text("Select these bytes");
+            if response.hovered() {
+                self.tracking.hovered.set(Some((start, length)));
+            }
+            if response.clicked() {
+                self.select(offset, length);
+            }
         });
     }
 }
 
-impl FileFormatUi for u16 {
-    fn ui(&mut self, ui: &mut egui::Ui, name: &str) {
-        ui.horizontal(|ui| {
-            ui.label(name);
-            ui.label(": ");
-            ui.label(self.to_string());
-        });
+/// Contexts for fields stored one after the other
+pub struct Fields<'a> {
+    context: FormatContext<'a>,
+    /// Where the next field starts in the file
+    offset: u64,
+}
+
+impl<'a> Fields<'a> {
+    /// Context for the next field, `length` bytes long
+    pub fn next(&mut self, length: u64) -> FormatContext<'a> {
+        let context = FormatContext {
+            span: Some((self.offset, length)),
+            ..self.context
+        };
+        self.offset = self.offset.saturating_add(length);
+        context
+    }
+
+    /// Steps over bytes that aren't shown, like padding
+    pub fn skip(&mut self, length: u64) {
+        self.offset = self.offset.saturating_add(length);
     }
 }
 
-impl FileFormatUi for u32 {
-    fn ui(&mut self, ui: &mut egui::Ui, name: &str) {
-        ui.horizontal(|ui| {
-            ui.label(name);
-            ui.label(": ");
-            ui.label(self.to_string());
-        });
+/// Shows a field whose value is already formatted
+pub fn text_field(ui: &mut egui::Ui, name: &str, text: &str) {
+    ui.horizontal(|ui| {
+        ui.label(name);
+        ui.label(": ");
+        ui.label(text);
+    });
+}
+
+pub trait FileFormatUi {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext);
+}
+
+impl FileFormatUi for () {
+    fn ui(&mut self, _ui: &mut egui::Ui, _name: &str, _context: &FormatContext) {}
+}
+
+macro_rules! number_ui {
+    ($($type:ty),*) => {
+        $(
+            impl FileFormatUi for $type {
+                fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+                    context.field(ui, name, &context.number(u64::from(*self)));
+                }
+            }
+        )*
+    };
+}
+
+number_ui!(u8, u16, u32, u64);
+
+impl<const N: usize> FileFormatUi for [u8; N] {
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str, context: &FormatContext) {
+        context.field(ui, name, &formats::hex_bytes(self));
     }
 }
 
-impl FileFormatUi for u64 {
-    fn ui(&mut self, ui: &mut egui::Ui, name: &str) {
-        ui.horizontal(|ui| {
-            ui.label(name);
-            ui.label(": ");
-            ui.label(self.to_string());
-        });
+/// Values stored in a fixed number of bytes, which `#[derive(FileFormat)]` builds structures
+/// from
+pub trait Layout: Sized {
+    /// Number of bytes the value takes in the file
+    const SIZE: u64;
+
+    /// Reads the value, `big_endian` is the byte order of the numbers in it
+    fn parse(input: &[u8], big_endian: bool) -> IResult<&[u8], Self>;
+}
+
+macro_rules! number_layout {
+    ($($type:ty),*) => {
+        $(
+            impl Layout for $type {
+                const SIZE: u64 = std::mem::size_of::<$type>() as u64;
+
+                fn parse(input: &[u8], big_endian: bool) -> IResult<&[u8], Self> {
+                    let (tail, bytes) = take(Self::SIZE)(input)?;
+                    let bytes = bytes.try_into().unwrap();
+                    let value = if big_endian {
+                        <$type>::from_be_bytes(bytes)
+                    } else {
+                        <$type>::from_le_bytes(bytes)
+                    };
+                    Ok((tail, value))
+                }
+            }
+        )*
+    };
+}
+
+number_layout!(u8, u16, u32, u64);
+
+impl<T: Layout, const N: usize> Layout for [T; N] {
+    const SIZE: u64 = T::SIZE * N as u64;
+
+    fn parse(input: &[u8], big_endian: bool) -> IResult<&[u8], Self> {
+        let mut input = input;
+        let mut items = Vec::with_capacity(N);
+        for _ in 0..N {
+            let (tail, item) = T::parse(input, big_endian)?;
+            items.push(item);
+            input = tail;
+        }
+        match items.try_into() {
+            Ok(items) => Ok((input, items)),
+            Err(_) => unreachable!("parsed {} items", N),
+        }
     }
 }
 
+/// A format the file can be shown as
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Builtin(FormatKind),
+    /// Index of a loaded Kaitai Struct spec
+    Kaitai(usize),
+}
+
+/// A `.ksy` file picked in the dialog, with its name
+type SpecFile = (String, Vec<u8>);
+
 pub struct FormatExplorer {
-    file: Arc<RwLock<MmapMut>>,
+    file: Arc<RwLock<FileData>>,
+    events: Sender<Event>,
     parsed: Box<dyn FileFormatUi>,
+    error: Option<Error>,
+    hex: bool,
+    /// Format chosen by hand, `None` to use the detected one
+    format: Option<Format>,
+    detected: Option<FormatKind>,
+    specs: Vec<Rc<kaitai::Spec>>,
+    spec_channel: (Sender<SpecFile>, Receiver<SpecFile>),
+    tracking: FieldTracking,
+    /// Bytes last pointed out in the other tools
+    pointed_out: Option<(u64, u64)>,
 }
 
 impl GaffrieTool for FormatExplorer {
-    fn new(file_lock: Arc<RwLock<MmapMut>>) -> Self
+    fn new(document: &Document) -> Self
     where
         Self: Sized,
     {
         let mut this = Self {
-            file: file_lock,
+            file: document.file.clone(),
+            events: document.events.clone(),
             parsed: Box::new(()),
+            error: None,
+            hex: false,
+            format: None,
+            detected: None,
+            specs: Vec::new(),
+            spec_channel: mpsc::channel(),
+            tracking: FieldTracking {
+                cursor: document.selection.offset as u64,
+                ..Default::default()
+            },
+            pointed_out: None,
         };
         this.file_changed();
         this
     }
 
     fn ui(&mut self, ui: &mut egui::Ui) {
-        self.parsed.ui(ui, "file");
+        if let Some(err) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, err.to_string());
+        }
+        if let Ok((name, bytes)) = self.spec_channel.1.try_recv() {
+            self.spec_loaded(&name, &bytes);
+        }
+        let previous_format = self.format;
+        ui.horizontal(|ui| {
+            let automatic = match self.detected {
+                Some(kind) => format!("Automatic ({})", kind.name()),
+                None => "Automatic".to_string(),
+            };
+            let selected = self
+                .format
+                .map_or_else(|| automatic.clone(), |format| self.format_name(format));
+            egui::ComboBox::from_id_source("format_kind")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.format, None, automatic);
+                    for kind in FormatKind::ALL {
+                        let format = Format::Builtin(kind);
+                        ui.selectable_value(&mut self.format, Some(format), kind.name());
+                    }
+                    for index in 0..self.specs.len() {
+                        let format = Format::Kaitai(index);
+                        let name = self.format_name(format);
+                        ui.selectable_value(&mut self.format, Some(format), name);
+                    }
+                });
+            if ui
+                .button("Load .ksy…")
+                .on_hover_text("Read the file with a Kaitai Struct spec")
+                .clicked()
+            {
+                self.pick_spec();
+            }
+            ui.checkbox(&mut self.hex, "Hexadecimal numbers");
+        });
+        if self.format != previous_format {
+            self.error = None;
+            self.file_changed();
+        }
+        let cursor = self.tracking.cursor;
+        let text = match self.tracking.covering.borrow().as_deref() {
+            Some(field) => format!("Cursor at {:#x}: {}", cursor, field),
+            None => format!("Cursor at {:#x}: no field", cursor),
+        };
+        ui.label(text);
+
+        // Moving the cursor by clicking a field shouldn't scroll the fields away
+        if ui.rect_contains_pointer(ui.max_rect()) {
+            self.tracking.reveal = false;
+        }
+        self.tracking.hovered.set(None);
+        self.tracking.covering.replace(None);
+        let context = FormatContext {
+            events: &self.events,
+            file: &self.file,
+            hex: self.hex,
+            base: 0,
+            span: None,
+            tracking: &self.tracking,
+        };
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| self.parsed.ui(ui, "file", &context));
+        self.tracking.reveal = false;
+
+        let hovered = self.tracking.hovered.get();
+        if hovered != self.pointed_out {
+            self.pointed_out = hovered;
+            let selection =
+                hovered.map(|(offset, length)| Selection::new(offset as usize, length as usize));
+            let _ = self.events.send(Event::Highlight(selection));
+        }
     }
 
     fn title(&self) -> String {
         "Format Explorer".to_string()
     }
 
-    fn notify(&mut self, event: crate::Event) {
+    fn notify(&mut self, event: Event) {
         match event {
-            crate::Event::FileChanged => self.file_changed(),
+            Event::FileChanged => self.file_changed(),
+            Event::CursorMoved(offset) => self.cursor_moved(offset),
+            Event::SelectionChanged(selection) => self.cursor_moved(selection.offset),
+            _ => {}
         }
     }
 }
 
 impl FormatExplorer {
+    fn cursor_moved(&mut self, offset: usize) {
+        self.tracking.cursor = offset as u64;
+        self.tracking.reveal = true;
+    }
+
+    fn format_name(&self, format: Format) -> String {
+        match format {
+            Format::Builtin(kind) => kind.name().to_string(),
+            Format::Kaitai(index) => format!("{} (.ksy)", self.specs[index].title),
+        }
+    }
+
+    fn pick_spec(&self) {
+        let sender = self.spec_channel.0.clone();
+        let task = rfd::AsyncFileDialog::new()
+            .add_filter("Kaitai Struct", &["ksy"])
+            .pick_file();
+        crate::execute(async move {
+            if let Some(file) = task.await {
+                let bytes = file.read().await;
+                let _ = sender.send((file.file_name(), bytes));
+            }
+        });
+    }
+
+    fn spec_loaded(&mut self, name: &str, bytes: &[u8]) {
+        let spec = std::str::from_utf8(bytes)
+            .map_err(|_| "the file isn't UTF-8".to_string())
+            .and_then(kaitai::Spec::load);
+        match spec {
+            Ok(spec) => {
+                self.specs.push(Rc::new(spec));
+                self.format = Some(Format::Kaitai(self.specs.len() - 1));
+                self.error = None;
+                self.file_changed();
+            }
+            Err(message) => {
+                let err = Error::parse("Kaitai Struct spec", format!("{}: {}", name, message));
+                let _ = self.events.send(Event::Error(err.clone()));
+                self.error = Some(err);
+            }
+        }
+    }
+
     fn file_changed(&mut self) {
         let lock = self.file.read();
-        let elf = formats::elf::ElfFormat::new(&*lock);
-        self.parsed = Box::new(elf);
+        self.detected = FormatKind::detect(&lock);
+        let kind = match self.format {
+            Some(Format::Builtin(kind)) => Some(kind),
+            Some(Format::Kaitai(index)) => {
+                let spec = self.specs[index].clone();
+                let parsed = kaitai::KaitaiFormat::new(spec.clone(), &lock);
+                let error = parsed.error().map(|message| {
+                    Error::parse("Kaitai Struct", format!("{}: {}", spec.id, message))
+                });
+                // Only report once, not on every edit of an already broken file
+                if let (Some(err), None) = (&error, &self.error) {
+                    let _ = self.events.send(Event::Error(err.clone()));
+                }
+                self.parsed = Box::new(parsed);
+                self.error = error;
+                return;
+            }
+            None => self.detected,
+        };
+        let Some(kind) = kind else {
+            self.parsed = Box::new(UnknownFormat::new(&lock));
+            self.error = None;
+            return;
+        };
+        match kind.parse(&lock) {
+            Ok(parsed) => {
+                self.parsed = parsed;
+                self.error = None;
+            }
+            Err(err) => {
+                // Only report once, not on every edit of an already broken file
+                if self.error.is_none() {
+                    let _ = self.events.send(Event::Error(err.clone()));
+                }
+                self.parsed = Box::new(());
+                self.error = Some(err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! The derive is tried on a structure using each of its options, shown in a headless egui
+    //! frame to read back the text of its fields.
+
+    use super::*;
+
+    fn machine_name(machine: u16) -> String {
+        match machine {
+            62 => "x86-64".to_string(),
+            _ => "unknown".to_string(),
+        }
+    }
+
+    fn magic_text(magic: &u32) -> String {
+        String::from_utf8_lossy(&magic.to_be_bytes()).into_owned()
+    }
+
+    const FLAGS: [(u64, &str); 2] = [(0x1, "READ"), (0x4, "EXEC")];
+
+    #[derive(FileFormat)]
+    struct Header {
+        #[format(meaning = machine_name)]
+        machine: u16,
+        #[format(hex)]
+        offset: u32,
+        #[format(flags = FLAGS)]
+        flags: u16,
+        #[format(big_endian, display = magic_text)]
+        magic: u32,
+        #[format(hidden)]
+        reserved: [u8; 2],
+        type_: u8,
+        #[format(name = "entries")]
+        count: u16,
+    }
+
+    #[derive(FileFormat)]
+    #[format(big_endian)]
+    struct BigEndian {
+        first: u16,
+        #[format(little_endian)]
+        second: u16,
+    }
+
+    #[derive(FileFormat)]
+    #[format(little_endian)]
+    struct LittleEndian {
+        first: u32,
+    }
+
+    const HEADER: [u8; 17] = [
+        0x3e, 0x00, 0x34, 0x12, 0x00, 0x00, 0x15, 0x00, 0x7f, b'E', b'L', b'F', 0xff, 0xff, 0x03,
+        0x02, 0x00,
+    ];
+
+    /// Texts drawn for `value` in a frame, and the field the cursor at `cursor` falls in
+    fn shown(value: &mut impl FileFormatUi, cursor: u64) -> (Vec<String>, Option<String>) {
+        let (events, _receiver) = mpsc::channel();
+        let file = RwLock::new(FileData::empty().unwrap());
+        let tracking = FieldTracking {
+            cursor,
+            reveal: true,
+            ..Default::default()
+        };
+        let context = FormatContext {
+            events: &events,
+            file: &file,
+            hex: false,
+            base: 0,
+            span: None,
+            tracking: &tracking,
+        };
+        let ctx = egui::Context::default();
+        let output = ctx.run(Default::default(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                value.ui(ui, "header", &context.at(0, Header::SIZE));
+            });
+        });
+        let texts = output
+            .shapes
+            .into_iter()
+            .filter_map(|clipped| match clipped.shape {
+                egui::Shape::Text(text) => Some(text.galley.text().to_string()),
+                _ => None,
+            })
+            .collect();
+        (texts, tracking.covering.take())
+    }
+
+    /// Value shown for the field `name`, drawn as the name, a colon and the value
+    fn field<'a>(texts: &'a [String], name: &str) -> Option<&'a str> {
+        let index = texts.iter().position(|text| text == name)?;
+        assert_eq!(texts[index + 1], ": ");
+        Some(&texts[index + 2])
+    }
+
+    #[test]
+    fn derived_layout() {
+        assert_eq!(Header::SIZE, 17);
+        assert_eq!(BigEndian::SIZE, 4);
+        let (tail, header) = Header::parse(&[&HEADER[..], b"tail"].concat(), false)
+            .map(|(tail, header)| (tail.to_vec(), header))
+            .unwrap();
+        assert_eq!(tail, b"tail");
+        assert_eq!(header.machine, 62);
+        assert_eq!(header.offset, 0x1234);
+        assert_eq!(header.flags, 0x15);
+        assert_eq!(header.magic, 0x7f454c46);
+        assert_eq!(header.reserved, [0xff; 2]);
+        assert_eq!(header.type_, 3);
+        assert_eq!(header.count, 2);
+        assert!(Header::parse(&HEADER[..16], false).is_err());
+
+        // The byte order passed at runtime applies to the fields without one of their own
+        let (_, header) = Header::parse(&HEADER, true).unwrap();
+        assert_eq!(header.machine, 0x3e00);
+        assert_eq!(header.offset, 0x34120000);
+        assert_eq!(header.magic, 0x7f454c46);
+        assert_eq!(header.count, 0x200);
+
+        // That of the structure applies whatever is passed
+        for big_endian in [false, true] {
+            let (_, value) = BigEndian::parse(&[1, 2, 3, 4], big_endian).unwrap();
+            assert_eq!((value.first, value.second), (0x102, 0x403));
+            let (_, value) = LittleEndian::parse(&[1, 2, 3, 4], big_endian).unwrap();
+            assert_eq!(value.first, 0x4030201);
+        }
+    }
+
+    #[test]
+    fn derived_ui() {
+        let (_, mut header) = Header::parse(&HEADER, false).unwrap();
+        let (texts, covering) = shown(&mut header, 3);
+        assert_eq!(texts[0], "header");
+        assert_eq!(field(&texts, "machine"), Some("62 (x86-64)"));
+        assert_eq!(field(&texts, "offset"), Some("0x1234"));
+        assert_eq!(field(&texts, "flags"), Some("21 (READ | EXEC | 0x10)"));
+        assert_eq!(field(&texts, "magic"), Some("\u{7f}ELF"));
+        assert_eq!(field(&texts, "reserved"), None);
+        assert_eq!(field(&texts, "type"), Some("3"));
+        assert_eq!(field(&texts, "entries"), Some("2"));
+        assert_eq!(covering.as_deref(), Some("offset"));
+
+        // Hidden fields still take their bytes
+        let (_, covering) = shown(&mut header, 13);
+        assert_eq!(covering.as_deref(), Some("header"));
+        let (_, covering) = shown(&mut header, 15);
+        assert_eq!(covering.as_deref(), Some("entries"));
+    }
+
+    #[test]
+    fn chosen_format() {
+        // A program with an archive appended is detected as the program
+        let bytes = [
+            &include_bytes!("formats/pe/fixtures/pe32.dll")[..],
+            include_bytes!("formats/zip/fixtures/comment.zip"),
+        ]
+        .concat();
+        let mut document = Document::default();
+        document.load_derived("extractor".into(), FileData::from_bytes(&bytes).unwrap());
+        let mut explorer = FormatExplorer::new(&document);
+        assert_eq!(explorer.detected, Some(FormatKind::Pe));
+        assert!(explorer.error.is_none());
+
+        // But it can be read as the archive
+        explorer.format = Some(Format::Builtin(FormatKind::Zip));
+        explorer.file_changed();
+        assert_eq!(explorer.detected, Some(FormatKind::Pe));
+        assert!(explorer.error.is_none());
+
+        // A format it isn't reports the error, only once
+        explorer.format = Some(Format::Builtin(FormatKind::Png));
+        explorer.file_changed();
+        explorer.file_changed();
+        assert!(explorer.error.is_some());
+        assert!(matches!(document.next_event(), Some(Event::Error(_))));
+        assert!(document.next_event().is_none());
+
+        // Going back to the detected one clears it
+        explorer.format = None;
+        explorer.file_changed();
+        assert!(explorer.error.is_none());
     }
 }
@@ -1,23 +1,30 @@
-use std::sync::Arc;
+use std::sync::{mpsc::Sender, Arc};
 
 use egui::{mutex::RwLock, Color32};
-use memmap2::MmapMut;
 
 use super::GaffrieTool;
+use crate::{
+    document::{Document, FileData},
+    Event, Selection,
+};
 
 pub struct FrequencyImage {
-    file: Arc<RwLock<MmapMut>>,
+    file: Arc<RwLock<FileData>>,
+    events: Sender<Event>,
     texture: Option<egui::TextureHandle>,
+    cursor: usize,
 }
 
 impl GaffrieTool for FrequencyImage {
-    fn new(file_lock: Arc<RwLock<MmapMut>>) -> Self
+    fn new(document: &Document) -> Self
     where
         Self: Sized,
     {
         let mut this = Self {
-            file: file_lock,
+            file: document.file.clone(),
+            events: document.events.clone(),
             texture: None,
+            cursor: 0,
         };
         this.reload_image();
         this
@@ -36,8 +43,40 @@ impl GaffrieTool for FrequencyImage {
                 self.texture.as_ref().unwrap()
             }
         };
-        let image = egui::Image::new(texture).shrink_to_fit();
-        ui.add(image);
+        let image = egui::Image::new(texture)
+            .shrink_to_fit()
+            .sense(egui::Sense::click());
+        let response = ui.add(image);
+        let pixel_size = response.rect.size() / 256.0;
+
+        // Outline the byte pair under the cursor
+        if let Some([first, second]) = self.pair_at(self.cursor) {
+            let pixel = egui::Rect::from_min_size(
+                response.rect.min + egui::vec2(second as f32, first as f32) * pixel_size,
+                pixel_size,
+            );
+            ui.painter()
+                .rect_stroke(pixel.expand(2.0), 0.0, egui::Stroke::new(1.0, Color32::RED));
+        }
+
+        // Pixel rows are the first byte of a pair, columns are the second one
+        let pair_under = |pos: egui::Pos2| {
+            let pixel = (pos - response.rect.min) / pixel_size;
+            [pixel.y as u8, pixel.x as u8]
+        };
+        if response.clicked() {
+            if let Some(pos) = response.interact_pointer_pos() {
+                if let Some(offset) = self.find_pair(pair_under(pos)) {
+                    let _ = self
+                        .events
+                        .send(Event::SelectionChanged(Selection::new(offset, 2)));
+                }
+            }
+        }
+        if let Some(pos) = response.hover_pos() {
+            let [first, second] = pair_under(pos);
+            response.on_hover_text(format!("{:02x} {:02x}", first, second));
+        }
     }
 
     fn title(&self) -> String {
@@ -49,11 +88,30 @@ impl GaffrieTool for FrequencyImage {
             crate::Event::FileChanged => {
                 self.reload_image();
             }
+            crate::Event::CursorMoved(offset) => self.cursor = offset,
+            crate::Event::SelectionChanged(selection) => self.cursor = selection.offset,
+            _ => {}
         }
     }
 }
 
 impl FrequencyImage {
+    fn pair_at(&self, offset: usize) -> Option<[u8; 2]> {
+        let file = self.file.read();
+        let pair = file.get(offset..offset.checked_add(2)?)?;
+        Some([pair[0], pair[1]])
+    }
+
+    /// Finds the next occurrence of `pair` after the cursor, wrapping around the end of the file
+    fn find_pair(&self, pair: [u8; 2]) -> Option<usize> {
+        let file = self.file.read();
+        let position = |slice: &[u8]| slice.windows(2).position(|window| window == pair);
+        let start = (self.cursor + 1).min(file.len());
+        position(&file[start..])
+            .map(|p| p + start)
+            .or_else(|| position(&file[..]))
+    }
+
     pub fn reload_image(&mut self) {
         if let Some(texture) = &mut self.texture {
             let file = self.file.read();
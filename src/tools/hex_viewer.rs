@@ -1,95 +1,690 @@
-use std::sync::Arc;
+use std::{
+    ops::Range,
+    sync::mpsc::Sender,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
-use egui::{mutex::RwLock, FontSelection, TextStyle};
-use memmap2::MmapMut;
+use egui::{
+    mutex::RwLock, Align2, FontId, Key, KeyboardShortcut, Modifiers, Pos2, Rect, Sense, Stroke,
+    TextStyle,
+};
 
-use super::GaffrieTool;
+use super::{format_explorer::SymbolCache, templates, GaffrieTool};
+use crate::{
+    document::{Document, FileData, Patch},
+    expression, Event, Selection,
+};
+
+const OFFSET_LENGTH: usize = 8;
+/// How long the row jumped to stays highlighted, in seconds
+const HIGHLIGHT_DURATION: f64 = 1.5;
+
+const GO_TO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::G);
+const BACK_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::ALT, Key::ArrowLeft);
+const FORWARD_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::ALT, Key::ArrowRight);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum EditMode {
+    Overwrite,
+    Insert,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum Column {
+    Hex,
+    Ascii,
+}
 
 pub struct HexViewer {
-    file: Arc<RwLock<MmapMut>>,
-    text: String,
-    ascii_text: String,
+    file: Arc<RwLock<FileData>>,
+    version: Arc<AtomicUsize>,
+    symbols: SymbolCache,
+    events: Sender<Event>,
     bytes_per_row: usize,
+    selection: Selection,
+    /// Offset where the current mouse drag started
+    drag_anchor: Option<usize>,
+    visible_rows: Range<usize>,
+    scroll_to_row: Option<usize>,
+    mode: EditMode,
+    column: Column,
+    /// High nibble of the byte under the cursor was typed, the next digit goes into the low one
+    low_nibble: bool,
+    /// Keyboard goes to the viewer after clicking into it, until clicking somewhere else
+    focused: bool,
+    go_to_text: String,
+    go_to_error: Option<String>,
+    /// Offsets jumped away from, the last one is where going back leads
+    back: Vec<usize>,
+    forward: Vec<usize>,
+    /// Row jumped to and when, it's highlighted for a moment
+    highlight: Option<(usize, f64)>,
+    /// Bytes another tool points out, like the parsed field under the mouse
+    pointed_out: Option<Selection>,
+    /// Colored bytes by the tool that sent them, like the fields of an applied template, each
+    /// sorted by offset. Later ones are drawn over earlier ones.
+    overlays: Vec<(usize, Vec<(Selection, egui::Color32)>)>,
+}
+
+/// Positions of the columns of a single row, in points
+pub(super) struct RowLayout {
+    pub char_width: f32,
+    pub row_stride: f32,
+    pub bytes_per_row: usize,
+}
+
+impl RowLayout {
+    pub fn hex_x(&self, index: usize) -> f32 {
+        (OFFSET_LENGTH + 2 + index * 3) as f32 * self.char_width
+    }
+
+    pub fn ascii_x(&self, index: usize) -> f32 {
+        self.hex_x(self.bytes_per_row) + (1 + index) as f32 * self.char_width
+    }
+
+    pub fn width(&self) -> f32 {
+        self.ascii_x(self.bytes_per_row)
+    }
+
+    /// Finds which byte of the file is under `pos`, `row_rect` is the rect of `row`
+    pub fn byte_at(&self, row: usize, row_rect: Rect, pos: Pos2) -> (usize, Column) {
+        let row_delta = ((pos.y - row_rect.top()) / self.row_stride).floor();
+        let row = (row as f32 + row_delta).max(0.0) as usize;
+        let x = pos.x - row_rect.left();
+        let (column, index) = if x >= self.ascii_x(0) {
+            let index = (x - self.ascii_x(0)) / self.char_width;
+            (Column::Ascii, index as usize)
+        } else {
+            let index = (x - self.hex_x(0)).max(0.0) / (self.char_width * 3.0);
+            (Column::Hex, index as usize)
+        };
+        (
+            row * self.bytes_per_row + index.min(self.bytes_per_row - 1),
+            column,
+        )
+    }
+}
+
+/// Edits typed during a single frame. They can't be read back from the file until
+/// the app applies them, so reads go through the pending patches.
+struct PendingEdits<'a> {
+    file: &'a [u8],
+    patches: Vec<Patch>,
+}
+
+impl<'a> PendingEdits<'a> {
+    fn len(&self) -> usize {
+        self.patches.iter().fold(self.file.len(), |len, patch| {
+            len - patch.old.len() + patch.new.len()
+        })
+    }
+
+    fn read(&self, mut offset: usize) -> Option<u8> {
+        for patch in self.patches.iter().rev() {
+            if offset >= patch.new_end() {
+                offset = offset - patch.new.len() + patch.old.len();
+            } else if offset >= patch.offset {
+                return Some(patch.new[offset - patch.offset]);
+            }
+        }
+        self.file.get(offset).copied()
+    }
+
+    fn replace(&mut self, offset: usize, length: usize, new: Vec<u8>) {
+        let old = (offset..offset + length)
+            .filter_map(|offset| self.read(offset))
+            .collect();
+        let patch = Patch { offset, old, new };
+        if let Some(last) = self.patches.last_mut() {
+            if last.merge(&patch) {
+                return;
+            }
+        }
+        self.patches.push(patch);
+    }
 }
 
 impl GaffrieTool for HexViewer {
-    fn new(file_lock: Arc<RwLock<MmapMut>>) -> Self
+    fn new(document: &Document) -> Self
     where
         Self: Sized,
     {
         Self {
-            file: file_lock,
-            text: String::new(),
-            ascii_text: String::new(),
+            file: document.file.clone(),
+            version: document.version.clone(),
+            symbols: document.symbols.clone(),
+            events: document.events.clone(),
             bytes_per_row: 16,
+            selection: Selection::default(),
+            drag_anchor: None,
+            visible_rows: 0..0,
+            scroll_to_row: None,
+            mode: EditMode::Overwrite,
+            column: Column::Hex,
+            low_nibble: false,
+            focused: false,
+            go_to_text: String::new(),
+            go_to_error: None,
+            back: Vec::new(),
+            forward: Vec::new(),
+            highlight: None,
+            pointed_out: None,
+            overlays: Vec::new(),
         }
     }
 
     fn ui(&mut self, ui: &mut egui::Ui) {
         let text_style = TextStyle::Monospace;
-        let row_height_sans_spacing = ui.text_style_height(&text_style) - 4.0;
-        let fontid = &ui.style().text_styles[&text_style];
-        let width = ui.fonts(|f| f.glyph_width(fontid, 'a'));
-        let total_rows = self.file.read().len().div_ceil(self.bytes_per_row);
-        ui.add(egui::DragValue::new(&mut self.bytes_per_row).clamp_range(1..=48));
-        egui::ScrollArea::vertical().show_rows(
-            ui,
-            row_height_sans_spacing,
-            total_rows,
-            |ui, row| {
-                let mut offsets = Vec::new();
-                let offset_length = 8;
-                for r in row.clone() {
-                    offsets.push(format!("{:08x}", r * self.bytes_per_row));
+        let row_height = ui.text_style_height(&text_style);
+        let font_id = text_style.resolve(ui.style());
+        let layout = RowLayout {
+            char_width: ui.fonts(|f| f.glyph_width(&font_id, 'a')),
+            row_stride: row_height + ui.spacing().item_spacing.y,
+            bytes_per_row: self.bytes_per_row,
+        };
+        // One extra row past the end, so there is a place to put the cursor for appending
+        let total_rows = self.file.read().len() / self.bytes_per_row + 1;
+        let writable = self.file.read().is_writable();
+        let time = ui.input(|i| i.time);
+        let mut focus_go_to = false;
+        if self.focused {
+            let (go_to, back, forward) = ui.input_mut(|i| {
+                (
+                    i.consume_shortcut(&GO_TO_SHORTCUT),
+                    i.consume_shortcut(&BACK_SHORTCUT),
+                    i.consume_shortcut(&FORWARD_SHORTCUT),
+                )
+            });
+            focus_go_to = go_to;
+            if back {
+                self.go_back(time);
+            } else if forward {
+                self.go_forward(time);
+            }
+        }
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut self.bytes_per_row).clamp_range(1..=48));
+            if ui
+                .add_enabled(!self.back.is_empty(), egui::Button::new("◀"))
+                .on_hover_text("Back (Alt+Left)")
+                .clicked()
+            {
+                self.go_back(time);
+            }
+            if ui
+                .add_enabled(!self.forward.is_empty(), egui::Button::new("▶"))
+                .on_hover_text("Forward (Alt+Right)")
+                .clicked()
+            {
+                self.go_forward(time);
+            }
+            let go_to = ui
+                .add(
+                    egui::TextEdit::singleline(&mut self.go_to_text)
+                        .hint_text("Go to (Ctrl+G)")
+                        .desired_width(160.0),
+                )
+                .on_hover_text(
+                    "Offset or expression, like 0x400 + e_phoff or end - 0x100.\n\
+                     A leading + or - moves relative to the cursor.",
+                );
+            if focus_go_to {
+                self.focused = false;
+                go_to.request_focus();
+            }
+            if go_to.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                self.go_to(time);
+            }
+            if let Some(err) = &self.go_to_error {
+                ui.colored_label(ui.visuals().error_fg_color, err);
+            }
+            if !writable {
+                ui.weak("RO").on_hover_text("File is opened read-only");
+                return;
+            }
+            let mode_label = match self.mode {
+                EditMode::Overwrite => "OVR",
+                EditMode::Insert => "INS",
+            };
+            if ui
+                .selectable_label(self.mode == EditMode::Insert, mode_label)
+                .on_hover_text("Toggle insert mode (Insert)")
+                .clicked()
+            {
+                self.toggle_mode();
+            }
+        });
+        let highlight = self.highlight.and_then(|(row, start)| {
+            let fade = 1.0 - (time - start) / HIGHLIGHT_DURATION;
+            (fade > 0.0).then_some((row, fade as f32))
+        });
+        if highlight.is_some() {
+            ui.ctx().request_repaint();
+        } else {
+            self.highlight = None;
+        }
+        if self.focused {
+            self.handle_input(ui);
+        }
+        let mut scroll_area = egui::ScrollArea::vertical().auto_shrink([false, false]);
+        if let Some(row) = self.scroll_to_row.take() {
+            scroll_area = scroll_area.vertical_scroll_offset(row as f32 * layout.row_stride);
+        }
+        let output = scroll_area.show_rows(ui, row_height, total_rows, |ui, rows| {
+            self.visible_rows = rows.clone();
+            for row in rows {
+                let (rect, response) = ui.allocate_exact_size(
+                    egui::vec2(layout.width(), row_height),
+                    Sense::click_and_drag(),
+                );
+                if let Some((_, fade)) = highlight.filter(|(highlighted, _)| *highlighted == row) {
+                    let color = ui.visuals().selection.bg_fill.gamma_multiply(fade * 0.5);
+                    ui.painter().rect_filled(rect, 0.0, color);
                 }
-                let offsets = offsets.join("\n");
-                self.text.clear();
-                self.ascii_text.clear();
-                let lock = self.file.read();
-                let file_range_start = row.start * self.bytes_per_row;
-                let file_range_end = (row.end * self.bytes_per_row).min(lock.len());
-                let visible_file_range = &lock[file_range_start..file_range_end];
-                for chunk in visible_file_range.chunks(self.bytes_per_row) {
-                    let chunk_len = chunk.len();
-                    for (index, byte) in chunk.iter().enumerate() {
-                        self.text.push_str(&format!("{:02x}", byte));
-                        if !byte.is_ascii_control() {
-                            self.ascii_text.push(*byte as char);
-                        } else {
-                            self.ascii_text.push('.');
-                        }
-                        if index < chunk_len - 1 {
-                            self.text.push(' ');
-                        }
+                self.paint_row(ui, &layout, &font_id, row, rect);
+                if let Some(pos) = response.interact_pointer_pos() {
+                    let len = self.file.read().len();
+                    let (offset, column) = layout.byte_at(row, rect, pos);
+                    let offset = offset.min(len);
+                    if response.drag_started() || response.clicked() {
+                        self.focused = true;
+                        self.drag_anchor = Some(offset);
+                        self.column = column;
+                        self.move_cursor(offset);
+                    } else if let Some(anchor) = self.drag_anchor {
+                        let start = anchor.min(offset);
+                        let end = (anchor.max(offset) + 1).min(len);
+                        self.selection = Selection::new(start, end.saturating_sub(start));
                     }
-                    self.text.push('\n');
-                    self.ascii_text.push('\n');
                 }
-                drop(lock);
-                ui.horizontal(|ui| {
-                    ui.add(
-                        egui::TextEdit::multiline(&mut offsets.as_str())
-                            .font(FontSelection::Style(text_style.clone()))
-                            .desired_width(width * (offset_length + 1) as f32),
-                    );
-                    ui.add(
-                        egui::TextEdit::multiline(&mut self.text.as_str())
-                            .font(FontSelection::Style(text_style.clone()))
-                            .desired_width(width * (self.bytes_per_row * 3) as f32),
-                    );
-                    ui.add(
-                        egui::TextEdit::multiline(&mut self.ascii_text.as_str())
-                            .font(FontSelection::Style(text_style))
-                            .desired_width(width * (self.bytes_per_row + 1) as f32 + 4.0),
-                    )
-                });
-            },
-        );
+                if response.drag_released() {
+                    self.drag_anchor = None;
+                    if self.selection.length > 1 {
+                        let _ = self.events.send(Event::SelectionChanged(self.selection));
+                    }
+                }
+            }
+        });
+        let clicked_elsewhere = ui.input(|i| {
+            i.pointer.any_pressed()
+                && i.pointer
+                    .interact_pos()
+                    .filter(|pos| output.inner_rect.contains(*pos))
+                    .is_none()
+        });
+        if clicked_elsewhere {
+            self.focused = false;
+        }
     }
 
     fn title(&self) -> String {
         "Hex Viewer".to_string()
     }
 
-    fn notify(&mut self, _event: crate::Event) {}
+    fn notify(&mut self, event: Event) {
+        match event {
+            Event::CursorMoved(offset) => {
+                // Our own cursor coming back shouldn't interrupt typing a byte
+                if offset != self.selection.offset {
+                    self.low_nibble = false;
+                }
+                self.selection = Selection::new(offset, 0);
+                self.reveal(offset);
+            }
+            Event::SelectionChanged(selection) => {
+                self.selection = selection;
+                self.low_nibble = false;
+                self.reveal(selection.offset);
+            }
+            Event::Highlight(selection) => {
+                if let Some(selection) = selection {
+                    self.reveal(selection.offset);
+                }
+                self.pointed_out = selection;
+            }
+            Event::Overlay { source, colors } => {
+                self.overlays.retain(|(other, _)| *other != source);
+                if !colors.is_empty() {
+                    self.overlays.push((source, colors));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl HexViewer {
+    /// Scrolls to the row containing `offset` if it's not visible already
+    fn reveal(&mut self, offset: usize) {
+        let row = offset / self.bytes_per_row;
+        if !self.visible_rows.contains(&row) {
+            self.scroll_to_row = Some(row);
+        }
+    }
+
+    fn move_cursor(&mut self, offset: usize) {
+        self.selection = Selection::new(offset, 0);
+        self.low_nibble = false;
+        self.reveal(offset);
+        let _ = self.events.send(Event::CursorMoved(offset));
+    }
+
+    /// Evaluates the go-to box and jumps to the result
+    fn go_to(&mut self, time: f64) {
+        let lock = self.file.read();
+        let version = self.version.load(Ordering::Relaxed);
+        let symbols = self.symbols.get(version, &lock);
+        let result = expression::evaluate_offset(
+            &self.go_to_text,
+            self.selection.offset,
+            lock.len(),
+            &symbols,
+        );
+        drop(lock);
+        match result {
+            Ok(offset) => {
+                self.go_to_error = None;
+                self.back.push(self.selection.offset);
+                self.forward.clear();
+                self.show_target(offset, time);
+            }
+            Err(err) => self.go_to_error = Some(err.to_string()),
+        }
+    }
+
+    fn go_back(&mut self, time: f64) {
+        if let Some(offset) = self.back.pop() {
+            self.forward.push(self.selection.offset);
+            self.show_target(offset, time);
+        }
+    }
+
+    fn go_forward(&mut self, time: f64) {
+        if let Some(offset) = self.forward.pop() {
+            self.back.push(self.selection.offset);
+            self.show_target(offset, time);
+        }
+    }
+
+    fn show_target(&mut self, offset: usize, time: f64) {
+        let offset = offset.min(self.file.read().len());
+        self.move_cursor(offset);
+        self.highlight = Some((offset / self.bytes_per_row, time));
+        self.focused = true;
+    }
+
+    fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            EditMode::Overwrite => EditMode::Insert,
+            EditMode::Insert => EditMode::Overwrite,
+        };
+    }
+
+    fn handle_input(&mut self, ui: &egui::Ui) {
+        // Typing into the viewer shouldn't trigger plain key shortcuts elsewhere,
+        // the ones with modifiers are left for the app
+        let events = ui.input_mut(|i| {
+            let events = i.events.clone();
+            i.events.retain(|event| match event {
+                egui::Event::Text(_) | egui::Event::Paste(_) => false,
+                egui::Event::Key { modifiers, .. } => modifiers.command || modifiers.alt,
+                _ => true,
+            });
+            events
+        });
+        let file = self.file.clone();
+        let lock = file.read();
+        let writable = lock.is_writable();
+        let mut edits = PendingEdits {
+            file: &lock,
+            patches: Vec::new(),
+        };
+        let old_cursor = self.selection.offset;
+        let mut cursor = old_cursor;
+        let page = self.visible_rows.len().max(1) * self.bytes_per_row;
+        for event in events {
+            match event {
+                egui::Event::Text(text) | egui::Event::Paste(text) if writable => {
+                    for c in text.chars() {
+                        cursor = self.type_char(&mut edits, cursor, c);
+                    }
+                }
+                egui::Event::Key {
+                    key, pressed: true, ..
+                } => {
+                    let row_start = cursor - cursor % self.bytes_per_row;
+                    let new_cursor = match key {
+                        Key::ArrowLeft => cursor.saturating_sub(1),
+                        Key::ArrowRight => cursor + 1,
+                        Key::ArrowUp => cursor.saturating_sub(self.bytes_per_row),
+                        Key::ArrowDown => cursor + self.bytes_per_row,
+                        Key::PageUp => cursor.saturating_sub(page),
+                        Key::PageDown => cursor + page,
+                        Key::Home => row_start,
+                        Key::End => row_start + self.bytes_per_row - 1,
+                        Key::Backspace | Key::Delete
+                            if writable && self.mode == EditMode::Insert =>
+                        {
+                            self.delete(&mut edits, cursor, key == Key::Backspace)
+                        }
+                        Key::Backspace => cursor.saturating_sub(1),
+                        Key::Insert => {
+                            self.toggle_mode();
+                            cursor
+                        }
+                        Key::Escape => {
+                            self.focused = false;
+                            cursor
+                        }
+                        Key::Tab => {
+                            self.column = match self.column {
+                                Column::Hex => Column::Ascii,
+                                Column::Ascii => Column::Hex,
+                            };
+                            self.low_nibble = false;
+                            cursor
+                        }
+                        _ => continue,
+                    }
+                    .min(edits.len());
+                    if new_cursor != cursor {
+                        self.low_nibble = false;
+                    }
+                    cursor = new_cursor;
+                }
+                _ => {}
+            }
+        }
+        let patches = edits.patches;
+        drop(lock);
+        for patch in patches {
+            let _ = self.events.send(Event::Patch(patch));
+        }
+        if cursor != old_cursor {
+            let low_nibble = self.low_nibble;
+            self.move_cursor(cursor);
+            self.low_nibble = low_nibble;
+        }
+    }
+
+    /// Writes a typed character at the cursor, returns where the cursor ends up
+    fn type_char(&mut self, edits: &mut PendingEdits, cursor: usize, c: char) -> usize {
+        let appending = cursor >= edits.len();
+        let insert = self.mode == EditMode::Insert || appending;
+        match self.column {
+            Column::Hex => {
+                let Some(digit) = c.to_digit(16) else {
+                    return cursor;
+                };
+                let digit = digit as u8;
+                if self.low_nibble {
+                    let old = edits.read(cursor).unwrap_or(0);
+                    edits.replace(cursor, 1, vec![(old & 0xf0) | digit]);
+                    self.low_nibble = false;
+                    cursor + 1
+                } else {
+                    if insert {
+                        edits.replace(cursor, 0, vec![digit << 4]);
+                    } else {
+                        let old = edits.read(cursor).unwrap_or(0);
+                        edits.replace(cursor, 1, vec![(digit << 4) | (old & 0x0f)]);
+                    }
+                    self.low_nibble = true;
+                    cursor
+                }
+            }
+            Column::Ascii => {
+                let Ok(byte) = u8::try_from(c) else {
+                    return cursor;
+                };
+                edits.replace(cursor, if insert { 0 } else { 1 }, vec![byte]);
+                cursor + 1
+            }
+        }
+    }
+
+    /// Removes the selection, or a single byte before (backspace) or at the cursor
+    fn delete(&mut self, edits: &mut PendingEdits, cursor: usize, backspace: bool) -> usize {
+        if self.selection.length > 0 && self.selection.offset == cursor {
+            edits.replace(cursor, self.selection.length, Vec::new());
+            self.selection = Selection::new(cursor, 0);
+            cursor
+        } else if backspace {
+            if cursor > 0 {
+                edits.replace(cursor - 1, 1, Vec::new());
+            }
+            cursor.saturating_sub(1)
+        } else {
+            if cursor < edits.len() {
+                edits.replace(cursor, 1, Vec::new());
+            }
+            cursor
+        }
+    }
+
+    fn paint_row(
+        &self,
+        ui: &egui::Ui,
+        layout: &RowLayout,
+        font_id: &FontId,
+        row: usize,
+        rect: Rect,
+    ) {
+        let lock = self.file.read();
+        let start = (row * self.bytes_per_row).min(lock.len());
+        let end = (start + self.bytes_per_row).min(lock.len());
+        let chunk = &lock[start..end];
+
+        let painter = ui.painter();
+        let visuals = ui.visuals();
+        // Cursor may sit one past the last byte, so it goes up to the row width
+        for index in 0..self.bytes_per_row {
+            let offset = row * self.bytes_per_row + index;
+            let hex_rect = Rect::from_min_size(
+                rect.left_top() + egui::vec2(layout.hex_x(index), 0.0),
+                egui::vec2(layout.char_width * 2.0, rect.height()),
+            );
+            let ascii_rect = Rect::from_min_size(
+                rect.left_top() + egui::vec2(layout.ascii_x(index), 0.0),
+                egui::vec2(layout.char_width, rect.height()),
+            );
+            let color = self
+                .overlays
+                .iter()
+                .rev()
+                .find_map(|(_, colors)| templates::color_at(colors, offset));
+            if let Some(color) = color {
+                if index < chunk.len() {
+                    painter.rect_filled(
+                        hex_rect.expand2(egui::vec2(layout.char_width * 0.5, 0.0)),
+                        0.0,
+                        color,
+                    );
+                    painter.rect_filled(ascii_rect, 0.0, color);
+                }
+            }
+            if self
+                .pointed_out
+                .is_some_and(|pointed_out| pointed_out.contains(offset))
+                && index < chunk.len()
+            {
+                let color = visuals.selection.bg_fill.gamma_multiply(0.4);
+                painter.rect_filled(
+                    hex_rect.expand2(egui::vec2(layout.char_width * 0.5, 0.0)),
+                    0.0,
+                    color,
+                );
+                painter.rect_filled(ascii_rect, 0.0, color);
+            }
+            if self.selection.contains(offset) && index < chunk.len() {
+                painter.rect_filled(
+                    hex_rect.expand2(egui::vec2(layout.char_width * 0.5, 0.0)),
+                    0.0,
+                    visuals.selection.bg_fill,
+                );
+                painter.rect_filled(ascii_rect, 0.0, visuals.selection.bg_fill);
+            }
+            if offset == self.selection.offset {
+                let (active, inactive) = match self.column {
+                    Column::Hex => (hex_rect, ascii_rect),
+                    Column::Ascii => (ascii_rect, hex_rect),
+                };
+                let active = if self.low_nibble {
+                    active.with_min_x(active.min.x + layout.char_width)
+                } else {
+                    active
+                };
+                let color = visuals.strong_text_color();
+                painter.rect_stroke(active, 0.0, Stroke::new(2.0, color));
+                painter.rect_stroke(inactive, 0.0, Stroke::new(1.0, color));
+            }
+        }
+
+        let mut text = String::with_capacity(chunk.len() * 3);
+        let mut ascii_text = String::with_capacity(chunk.len());
+        for (index, byte) in chunk.iter().enumerate() {
+            text.push_str(&format!("{:02x}", byte));
+            if index < chunk.len() - 1 {
+                text.push(' ');
+            }
+            if !byte.is_ascii_control() {
+                ascii_text.push(*byte as char);
+            } else {
+                ascii_text.push('.');
+            }
+        }
+        drop(lock);
+
+        let color = visuals.text_color();
+        painter.text(
+            rect.left_top(),
+            Align2::LEFT_TOP,
+            format!("{:08x}", row * self.bytes_per_row),
+            font_id.clone(),
+            visuals.weak_text_color(),
+        );
+        painter.text(
+            rect.left_top() + egui::vec2(layout.hex_x(0), 0.0),
+            Align2::LEFT_TOP,
+            text,
+            font_id.clone(),
+            color,
+        );
+        painter.text(
+            rect.left_top() + egui::vec2(layout.ascii_x(0), 0.0),
+            Align2::LEFT_TOP,
+            ascii_text,
+            font_id.clone(),
+            color,
+        );
+        let separator_x = rect.left() + layout.ascii_x(0) - layout.char_width * 0.5;
+        painter.vline(
+            separator_x,
+            rect.y_range(),
+            Stroke::new(1.0, visuals.widgets.noninteractive.bg_stroke.color),
+        );
+    }
 }
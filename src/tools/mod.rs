@@ -1,16 +1,18 @@
+pub mod binary_diff;
+pub mod data_inspector;
+pub mod edit_history;
 pub mod entropy_plot;
 pub mod format_explorer;
 pub mod frequency_image;
 pub mod hex_viewer;
+pub mod search;
 pub mod string_finder;
+pub mod templates;
 
-use egui::mutex::RwLock;
-use memmap2::MmapMut;
-
-use crate::Event;
+use crate::{document::Document, Event};
 
 pub trait GaffrieTool {
-    fn new(file_lock: std::sync::Arc<RwLock<MmapMut>>) -> Self
+    fn new(document: &Document) -> Self
     where
         Self: Sized;
     fn ui(&mut self, ui: &mut egui::Ui);
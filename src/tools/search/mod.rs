@@ -0,0 +1,380 @@
+use std::{
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{Receiver, Sender},
+        Arc,
+    },
+};
+
+use egui::{mutex::RwLock, Vec2b};
+use egui_extras::Column;
+use regex::bytes::Regex;
+
+use super::GaffrieTool;
+use crate::{
+    document::{Document, FileData},
+    Event, Selection,
+};
+
+mod pattern;
+
+use pattern::{Query, SearchKind, TextEncoding};
+
+/// Bytes searched while holding the file lock, so edits don't wait for the whole search
+const CHUNK_SIZE: usize = 16 * 1024 * 1024;
+/// Chunks overlap by this much, longer matches crossing a chunk boundary are cut short
+const CHUNK_OVERLAP: usize = 64 * 1024;
+/// Searching stops after this many hits
+const MAX_HITS: usize = 100_000;
+/// Bytes of a hit shown in the table
+const PREVIEW_LENGTH: usize = 16;
+
+enum SearchUpdate {
+    Hits(Vec<Range<usize>>),
+    Done,
+}
+
+/// Search running in the background
+struct RunningSearch {
+    updates: Receiver<SearchUpdate>,
+    cancel: Arc<AtomicBool>,
+    /// Bytes searched so far
+    progress: Arc<AtomicUsize>,
+    total: usize,
+}
+
+impl Drop for RunningSearch {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+pub struct Search {
+    file: Arc<RwLock<FileData>>,
+    events: Sender<Event>,
+    text: String,
+    kind: SearchKind,
+    encoding: TextEncoding,
+    case_insensitive: bool,
+    error: Option<String>,
+    hits: Vec<Range<usize>>,
+    running: Option<RunningSearch>,
+    /// The file was edited after the search
+    stale: bool,
+    cursor: usize,
+    scroll_to_row: Option<usize>,
+}
+
+impl GaffrieTool for Search {
+    fn new(document: &Document) -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            file: document.file.clone(),
+            events: document.events.clone(),
+            text: String::new(),
+            kind: SearchKind::Hex,
+            encoding: TextEncoding::Utf8,
+            case_insensitive: false,
+            error: None,
+            hits: Vec::new(),
+            running: None,
+            stale: false,
+            cursor: 0,
+            scroll_to_row: None,
+        }
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        self.receive_hits();
+        let mut start = false;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("search_kind")
+                .selected_text(self.kind.name())
+                .show_ui(ui, |ui| {
+                    for kind in SearchKind::ALL {
+                        ui.selectable_value(&mut self.kind, kind, kind.name());
+                    }
+                });
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.text)
+                    .hint_text(self.kind.hint())
+                    .font(egui::TextStyle::Monospace),
+            );
+            start |= response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            start |= ui.button("Find").clicked();
+            if self.running.is_some() && ui.button("Stop").clicked() {
+                self.running = None;
+            }
+        });
+        ui.horizontal(|ui| {
+            if self.kind == SearchKind::Text {
+                egui::ComboBox::from_id_source("search_encoding")
+                    .selected_text(self.encoding.name())
+                    .show_ui(ui, |ui| {
+                        for encoding in TextEncoding::ALL {
+                            ui.selectable_value(&mut self.encoding, encoding, encoding.name());
+                        }
+                    });
+            }
+            if self.kind != SearchKind::Hex {
+                ui.checkbox(&mut self.case_insensitive, "Ignore case");
+            }
+        });
+        if start {
+            self.start();
+        }
+
+        if let Some(err) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, err);
+        }
+        if let Some(running) = &self.running {
+            let progress = running.progress.load(Ordering::Relaxed) as f32;
+            ui.add(
+                egui::ProgressBar::new(progress / running.total.max(1) as f32)
+                    .text(format!("{} hits so far", self.hits.len())),
+            );
+            ui.ctx().request_repaint();
+        } else {
+            let limit = if self.hits.len() >= MAX_HITS {
+                " (stopped at the limit)"
+            } else {
+                ""
+            };
+            ui.label(format!("{} hits{}", self.hits.len(), limit));
+        }
+        if self.stale {
+            ui.weak("The file was edited since the search, offsets may be outdated");
+        }
+
+        let file = self.file.clone();
+        let file = file.read();
+        let mut table = egui_extras::TableBuilder::new(ui)
+            .striped(true)
+            .resizable(false)
+            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+            .column(Column::auto().at_least(64.0))
+            .column(Column::auto().at_least(32.0))
+            .column(Column::remainder().clip(true))
+            .vscroll(true)
+            .auto_shrink(Vec2b::new(false, true));
+        if let Some(row) = self.scroll_to_row.take() {
+            table = table.scroll_to_row(row, Some(egui::Align::Center));
+        }
+        table
+            .header(20.0, |mut header| {
+                header.col(|ui| {
+                    ui.label("Offset");
+                });
+                header.col(|ui| {
+                    ui.label("Length");
+                });
+                header.col(|ui| {
+                    ui.label("Bytes");
+                });
+            })
+            .body(|body| {
+                body.rows(20.0, self.hits.len(), |mut row| {
+                    let hit = self.hits[row.index()].clone();
+                    row.col(|ui| {
+                        let selected = hit.contains(&self.cursor);
+                        if ui
+                            .selectable_label(selected, format!("{:#x}", hit.start))
+                            .clicked()
+                        {
+                            let selection = Selection::new(hit.start, hit.len());
+                            let _ = self.events.send(Event::SelectionChanged(selection));
+                        }
+                    });
+                    row.col(|ui| {
+                        ui.label(hit.len().to_string());
+                    });
+                    row.col(|ui| {
+                        let end = hit.end.min(hit.start + PREVIEW_LENGTH).min(file.len());
+                        let bytes = file.get(hit.start..end).unwrap_or_default();
+                        let preview = bytes
+                            .iter()
+                            .map(|b| format!("{:02x}", b))
+                            .collect::<Vec<_>>()
+                            .join(" ");
+                        ui.add(
+                            egui::Label::new(egui::RichText::new(preview).monospace())
+                                .wrap(false)
+                                .truncate(true),
+                        );
+                    });
+                })
+            });
+    }
+
+    fn title(&self) -> String {
+        "Search".to_string()
+    }
+
+    fn notify(&mut self, event: Event) {
+        match event {
+            Event::FileChanged => self.stale = !self.hits.is_empty() || self.running.is_some(),
+            Event::CursorMoved(offset) => self.cursor_moved(offset),
+            Event::SelectionChanged(selection) => self.cursor_moved(selection.offset),
+            _ => {}
+        }
+    }
+}
+
+impl Search {
+    fn start(&mut self) {
+        let query = Query {
+            text: &self.text,
+            kind: self.kind,
+            encoding: self.encoding,
+            case_insensitive: self.case_insensitive,
+        };
+        let regex = match query.compile() {
+            Ok(regex) => regex,
+            Err(err) => {
+                self.error = Some(err);
+                return;
+            }
+        };
+        self.error = None;
+        self.hits.clear();
+        self.stale = false;
+        let (sender, updates) = std::sync::mpsc::channel();
+        let running = RunningSearch {
+            updates,
+            cancel: Arc::new(AtomicBool::new(false)),
+            progress: Arc::new(AtomicUsize::new(0)),
+            total: self.file.read().len(),
+        };
+        let file = self.file.clone();
+        let cancel = running.cancel.clone();
+        let progress = running.progress.clone();
+        // Replacing the previous search cancels it
+        self.running = Some(running);
+        crate::execute(async move {
+            search(&file, &regex, &cancel, &progress, &sender);
+            let _ = sender.send(SearchUpdate::Done);
+        });
+    }
+
+    fn receive_hits(&mut self) {
+        let Some(running) = &self.running else {
+            return;
+        };
+        let mut done = false;
+        while let Ok(update) = running.updates.try_recv() {
+            match update {
+                SearchUpdate::Hits(hits) => self.hits.extend(hits),
+                SearchUpdate::Done => done = true,
+            }
+        }
+        if done {
+            self.running = None;
+        }
+    }
+
+    fn cursor_moved(&mut self, offset: usize) {
+        self.cursor = offset;
+        let index = self.hits.partition_point(|hit| hit.end <= offset);
+        if self
+            .hits
+            .get(index)
+            .is_some_and(|hit| hit.contains(&offset))
+        {
+            self.scroll_to_row = Some(index);
+        }
+    }
+}
+
+/// Finds all matches chunk by chunk, sending them as they are found
+fn search(
+    file: &RwLock<FileData>,
+    regex: &Regex,
+    cancel: &AtomicBool,
+    progress: &AtomicUsize,
+    sender: &Sender<SearchUpdate>,
+) {
+    let mut found = 0;
+    let mut start = 0;
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
+        let lock = file.read();
+        if start >= lock.len() {
+            return;
+        }
+        let end = (start + CHUNK_SIZE).min(lock.len());
+        let haystack = &lock[start..(end + CHUNK_OVERLAP).min(lock.len())];
+        // Matches starting in the overlap are found again with the next chunk
+        let hits = regex
+            .find_iter(haystack)
+            .map(|hit| start + hit.start()..start + hit.end())
+            .take_while(|hit| hit.start < end)
+            .filter(|hit| !hit.is_empty())
+            .take(MAX_HITS - found)
+            .collect::<Vec<_>>();
+        drop(lock);
+        found += hits.len();
+        // A hit running into the overlap was already reported whole, so its tail isn't searched
+        // again
+        let next = hits.last().map_or(end, |hit| hit.end.max(end));
+        if sender.send(SearchUpdate::Hits(hits)).is_err() || found >= MAX_HITS {
+            return;
+        }
+        progress.store(next, Ordering::Relaxed);
+        start = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search_all(bytes: &[u8], pattern: &str) -> Vec<Range<usize>> {
+        let file = RwLock::new(FileData::from_bytes(bytes).unwrap());
+        let regex = Regex::new(pattern).unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        search(
+            &file,
+            &regex,
+            &AtomicBool::new(false),
+            &AtomicUsize::new(0),
+            &sender,
+        );
+        drop(sender);
+        receiver
+            .into_iter()
+            .flat_map(|update| match update {
+                SearchUpdate::Hits(hits) => hits,
+                SearchUpdate::Done => Vec::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hit_across_chunks_is_reported_once() {
+        let mut bytes = vec![0xff; CHUNK_SIZE + 1024];
+        bytes[CHUNK_SIZE - 16..CHUNK_SIZE + 16].fill(0);
+        bytes[CHUNK_SIZE + 100] = 0;
+        let hits = search_all(&bytes, r"(?-u)\x00+");
+        assert_eq!(
+            hits,
+            [
+                CHUNK_SIZE - 16..CHUNK_SIZE + 16,
+                CHUNK_SIZE + 100..CHUNK_SIZE + 101
+            ]
+        );
+    }
+
+    #[test]
+    fn hit_in_overlap_is_found_with_next_chunk() {
+        let mut bytes = vec![0xff; CHUNK_SIZE + 1024];
+        bytes[CHUNK_SIZE + 10..CHUNK_SIZE + 14].copy_from_slice(b"GAFF");
+        let hits = search_all(&bytes, "GAFF");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0], CHUNK_SIZE + 10..CHUNK_SIZE + 14);
+    }
+}
@@ -0,0 +1,286 @@
+use std::fmt::Write;
+
+use regex::bytes::{Regex, RegexBuilder};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchKind {
+    /// Hex bytes with `??` wildcards, like `4D 5A ?? ??`
+    Hex,
+    Text,
+    Regex,
+}
+
+impl SearchKind {
+    pub const ALL: [SearchKind; 3] = [SearchKind::Hex, SearchKind::Text, SearchKind::Regex];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SearchKind::Hex => "Hex",
+            SearchKind::Text => "Text",
+            SearchKind::Regex => "Regex",
+        }
+    }
+
+    pub fn hint(&self) -> &'static str {
+        match self {
+            SearchKind::Hex => "4D 5A ?? ?? 0?",
+            SearchKind::Text => "Text to find",
+            SearchKind::Regex => r"MZ.{2}\x00+PE",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextEncoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl TextEncoding {
+    pub const ALL: [TextEncoding; 3] = [
+        TextEncoding::Utf8,
+        TextEncoding::Utf16Le,
+        TextEncoding::Utf16Be,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => "ASCII/UTF-8",
+            TextEncoding::Utf16Le => "UTF-16LE",
+            TextEncoding::Utf16Be => "UTF-16BE",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Query<'a> {
+    pub text: &'a str,
+    pub kind: SearchKind,
+    pub encoding: TextEncoding,
+    pub case_insensitive: bool,
+}
+
+impl Query<'_> {
+    /// Every kind of search is turned into a byte regex
+    pub fn compile(&self) -> Result<Regex, String> {
+        if self.text.is_empty() {
+            return Err("Nothing to search for".to_string());
+        }
+        // UTF-16 patterns spell out the case variants themselves
+        let (pattern, unicode, fold_case) = match (self.kind, self.encoding) {
+            (SearchKind::Hex, _) => (hex_pattern(self.text)?, false, false),
+            (SearchKind::Text, TextEncoding::Utf8) => (regex::escape(self.text), true, true),
+            (SearchKind::Text, encoding) => (
+                utf16_pattern(self.text, encoding, self.case_insensitive),
+                false,
+                false,
+            ),
+            (SearchKind::Regex, _) => (self.text.to_string(), false, true),
+        };
+        RegexBuilder::new(&pattern)
+            .unicode(unicode)
+            .case_insensitive(self.case_insensitive && fold_case)
+            .dot_matches_new_line(true)
+            .build()
+            .map_err(|err| err.to_string())
+    }
+}
+
+/// Raw byte in a regex with Unicode disabled
+fn byte(value: u8) -> String {
+    format!(r"\x{:02x}", value)
+}
+
+fn hex_pattern(text: &str) -> Result<String, String> {
+    let digits = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<Vec<_>>();
+    if digits.len() % 2 != 0 {
+        return Err("Hex pattern has an odd number of digits".to_string());
+    }
+    let nibble = |c: char| match c {
+        '?' => Ok(None),
+        c => c
+            .to_digit(16)
+            .map(|d| Some(d as u8))
+            .ok_or_else(|| format!("'{}' is not a hex digit", c)),
+    };
+    let mut pattern = String::new();
+    for pair in digits.chunks(2) {
+        match (nibble(pair[0])?, nibble(pair[1])?) {
+            (Some(high), Some(low)) => pattern.push_str(&byte(high << 4 | low)),
+            (None, None) => pattern.push('.'),
+            (Some(high), None) => {
+                let _ = write!(pattern, "[{}-{}]", byte(high << 4), byte(high << 4 | 0xf));
+            }
+            (None, Some(low)) => {
+                pattern.push('[');
+                for high in 0..16 {
+                    pattern.push_str(&byte(high << 4 | low));
+                }
+                pattern.push(']');
+            }
+        }
+    }
+    Ok(pattern)
+}
+
+fn utf16_pattern(text: &str, encoding: TextEncoding, case_insensitive: bool) -> String {
+    let units = |c: char| {
+        let mut buffer = [0; 2];
+        c.encode_utf16(&mut buffer)
+            .iter()
+            .flat_map(|unit| match encoding {
+                TextEncoding::Utf16Be => unit.to_be_bytes(),
+                _ => unit.to_le_bytes(),
+            })
+            .map(byte)
+            .collect::<String>()
+    };
+    let mut pattern = String::new();
+    for c in text.chars() {
+        // The regex can only fold the case of single bytes, so the variants are spelled out
+        let mut variants = vec![c];
+        if case_insensitive {
+            variants.extend(c.to_lowercase().chain(c.to_uppercase()));
+            variants.sort_unstable();
+            variants.dedup();
+        }
+        let variants = variants.into_iter().map(units).collect::<Vec<_>>();
+        let _ = write!(pattern, "(?:{})", variants.join("|"));
+    }
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(
+        text: &str,
+        kind: SearchKind,
+        encoding: TextEncoding,
+        case_insensitive: bool,
+    ) -> Regex {
+        Query {
+            text,
+            kind,
+            encoding,
+            case_insensitive,
+        }
+        .compile()
+        .unwrap()
+    }
+
+    fn hex(text: &str) -> Regex {
+        query(text, SearchKind::Hex, TextEncoding::Utf8, false)
+    }
+
+    fn utf16(text: &str, encoding: TextEncoding) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| match encoding {
+                TextEncoding::Utf16Be => unit.to_be_bytes(),
+                _ => unit.to_le_bytes(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hex_wildcards() {
+        assert_eq!(hex_pattern("4d 5A").unwrap(), r"\x4d\x5a");
+        let regex = hex("4D 5A ?? ??");
+        assert!(regex.is_match(b"MZ\x00\xff"));
+        assert!(regex.is_match(b"MZ\n\x80"));
+        assert!(!regex.is_match(b"MZ\x00"));
+        assert!(!regex.is_match(b"MY\x00\x00"));
+
+        let high = hex("4?");
+        assert!((0x40..=0x4f).all(|byte| high.is_match(&[byte])));
+        assert!(!high.is_match(&[0x3f]) && !high.is_match(&[0x50]));
+        let low = hex("?5");
+        assert!((0..16).all(|high| low.is_match(&[high << 4 | 5])));
+        assert!(!low.is_match(&[0x06]) && !low.is_match(&[0x54]));
+
+        // Bytes, not letters
+        let regex = query("41", SearchKind::Hex, TextEncoding::Utf8, true);
+        assert!(regex.is_match(b"A"));
+        assert!(!regex.is_match(b"a"));
+    }
+
+    #[test]
+    fn invalid_hex() {
+        assert_eq!(
+            hex_pattern("4D 5"),
+            Err("Hex pattern has an odd number of digits".to_string())
+        );
+        assert_eq!(hex_pattern("4G"), Err("'G' is not a hex digit".to_string()));
+        assert_eq!(hex_pattern("0x"), Err("'x' is not a hex digit".to_string()));
+        let empty = Query {
+            text: "",
+            kind: SearchKind::Hex,
+            encoding: TextEncoding::Utf8,
+            case_insensitive: false,
+        };
+        assert_eq!(empty.compile().unwrap_err(), "Nothing to search for");
+    }
+
+    #[test]
+    fn utf8_text() {
+        let regex = query("a.b", SearchKind::Text, TextEncoding::Utf8, false);
+        assert!(regex.is_match(b"xa.by"));
+        assert!(!regex.is_match(b"axb") && !regex.is_match(b"A.B"));
+        let regex = query("straße", SearchKind::Text, TextEncoding::Utf8, true);
+        assert!(regex.is_match("STRAẞE".as_bytes()));
+    }
+
+    #[test]
+    fn utf16_text() {
+        for encoding in [TextEncoding::Utf16Le, TextEncoding::Utf16Be] {
+            let other = match encoding {
+                TextEncoding::Utf16Le => TextEncoding::Utf16Be,
+                _ => TextEncoding::Utf16Le,
+            };
+            let sensitive = query("Gaff😀", SearchKind::Text, encoding, false);
+            assert!(sensitive.is_match(&utf16("xGaff😀", encoding)));
+            assert!(!sensitive.is_match(&utf16("gaff😀", encoding)));
+            assert!(!sensitive.is_match(&utf16("Gaff😀", other)));
+
+            let insensitive = query("Gaff😀", SearchKind::Text, encoding, true);
+            assert!(insensitive.is_match(&utf16("gAFf😀", encoding)));
+            assert!(insensitive.is_match(&utf16("GAFF😀", encoding)));
+            assert!(!insensitive.is_match(&utf16("gAFf😀", other)));
+            assert!(!insensitive.is_match(b"gaff"));
+            let accented = query("é", SearchKind::Text, encoding, true);
+            assert!(accented.is_match(&utf16("É", encoding)));
+        }
+        assert_eq!(
+            utf16_pattern("a", TextEncoding::Utf16Le, true),
+            r"(?:\x41\x00|\x61\x00)"
+        );
+    }
+
+    #[test]
+    fn byte_regex() {
+        let regex = query(
+            r"MZ.{2}\x00+PE",
+            SearchKind::Regex,
+            TextEncoding::Utf8,
+            false,
+        );
+        assert!(regex.is_match(b"MZ\n\xff\x00\x00PE"));
+        let regex = query(r"\xff", SearchKind::Regex, TextEncoding::Utf8, false);
+        assert!(regex.is_match(&[0xff]));
+        let regex = query("pe", SearchKind::Regex, TextEncoding::Utf16Le, true);
+        assert!(regex.is_match(b"PE"));
+        assert!(Query {
+            text: "(",
+            kind: SearchKind::Regex,
+            encoding: TextEncoding::Utf8,
+            case_insensitive: false,
+        }
+        .compile()
+        .is_err());
+    }
+}
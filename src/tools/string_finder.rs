@@ -1,11 +1,13 @@
-use std::sync::Arc;
+use std::sync::{mpsc::Sender, Arc};
 
 use egui::{mutex::RwLock, Color32, Vec2b};
 use egui_extras::Column;
-use memmap2::MmapMut;
 
 use super::GaffrieTool;
-use crate::Event;
+use crate::{
+    document::{Document, FileData},
+    Event, Selection,
+};
 
 pub struct FoundString {
     address: usize,
@@ -23,19 +25,31 @@ pub enum StringsSorting {
 }
 
 pub struct StringFinder {
-    file: Arc<RwLock<MmapMut>>,
+    file: Arc<RwLock<FileData>>,
+    events: Sender<Event>,
     strings: Vec<FoundString>,
     current_sorting: StringsSorting,
     string_min_length: usize,
+    cursor: usize,
+    scroll_to_row: Option<usize>,
+}
+
+impl FoundString {
+    fn contains(&self, offset: usize) -> bool {
+        (self.address..self.address + self.length).contains(&offset)
+    }
 }
 
 impl GaffrieTool for StringFinder {
-    fn new(file_lock: Arc<RwLock<MmapMut>>) -> Self {
+    fn new(document: &Document) -> Self {
         let mut this = Self {
-            file: file_lock,
+            file: document.file.clone(),
+            events: document.events.clone(),
             strings: Vec::new(),
             current_sorting: StringsSorting::default(),
             string_min_length: 5,
+            cursor: 0,
+            scroll_to_row: None,
         };
         this.find_strings();
         this
@@ -49,7 +63,7 @@ impl GaffrieTool for StringFinder {
         {
             self.find_strings();
         }
-        let table = egui_extras::TableBuilder::new(ui)
+        let mut table = egui_extras::TableBuilder::new(ui)
             .striped(true)
             .resizable(false)
             .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
@@ -58,6 +72,9 @@ impl GaffrieTool for StringFinder {
             .column(Column::remainder().clip(true))
             .vscroll(true)
             .auto_shrink(Vec2b::new(false, true));
+        if let Some(row) = self.scroll_to_row.take() {
+            table = table.scroll_to_row(row, Some(egui::Align::Center));
+        }
         let mut address_button_label = "Address".to_string();
         match self.current_sorting {
             StringsSorting::AddressAsc => {
@@ -116,7 +133,14 @@ impl GaffrieTool for StringFinder {
                 body.rows(20.0, self.strings.len(), |mut row| {
                     let string = &self.strings[row.index()];
                     row.col(|ui| {
-                        ui.label(string.address.to_string());
+                        let selected = string.contains(self.cursor);
+                        if ui
+                            .selectable_label(selected, string.address.to_string())
+                            .clicked()
+                        {
+                            let selection = Selection::new(string.address, string.length);
+                            let _ = self.events.send(Event::SelectionChanged(selection));
+                        }
                     });
                     row.col(|ui| {
                         ui.label(string.length.to_string());
@@ -137,6 +161,9 @@ impl GaffrieTool for StringFinder {
             Event::FileChanged => {
                 self.find_strings();
             }
+            Event::CursorMoved(offset) => self.cursor_moved(offset),
+            Event::SelectionChanged(selection) => self.cursor_moved(selection.offset),
+            _ => {}
         }
     }
 }
@@ -167,6 +194,11 @@ impl StringFinder {
         self.sort_strings();
     }
 
+    fn cursor_moved(&mut self, offset: usize) {
+        self.cursor = offset;
+        self.scroll_to_row = self.strings.iter().position(|s| s.contains(offset));
+    }
+
     fn sort_strings(&mut self) {
         match self.current_sorting {
             StringsSorting::AddressAsc => {
@@ -0,0 +1,383 @@
+//! Reading the bytes of a file with a template
+
+use super::language::{FieldDef, FieldType, Primitive, StructDef};
+use crate::expression;
+
+/// Structures nested deeper than this are taken for a structure containing itself
+const MAX_DEPTH: usize = 64;
+/// Values read at most across the whole structure, more is most likely a wrong length. Nested
+/// arrays would multiply a limit per array.
+const MAX_ITEMS: usize = 1_000_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+    Text(String),
+    Struct(Vec<Field>),
+    Array(Vec<Field>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub type_name: String,
+    pub offset: usize,
+    pub length: usize,
+    pub value: Value,
+}
+
+impl Field {
+    /// Fields that aren't made of other fields, arrays of numbers count as a single one
+    pub fn leaves(&self) -> Vec<&Field> {
+        match &self.value {
+            Value::Struct(fields) => fields.iter().flat_map(Field::leaves).collect(),
+            Value::Array(items) if items.iter().any(|item| item.is_struct()) => {
+                items.iter().flat_map(Field::leaves).collect()
+            }
+            _ => vec![self],
+        }
+    }
+
+    pub fn is_struct(&self) -> bool {
+        matches!(self.value, Value::Struct(_))
+    }
+
+    pub fn end(&self) -> usize {
+        self.offset + self.length
+    }
+
+    /// Value of an integer field, `path` goes into nested structures like `header.count`
+    fn lookup(fields: &[Field], path: &str) -> Option<i128> {
+        let (name, rest) = match path.split_once('.') {
+            Some((name, rest)) => (name, Some(rest)),
+            None => (path, None),
+        };
+        let field = fields.iter().find(|field| field.name == name)?;
+        match (&field.value, rest) {
+            (Value::Unsigned(value), None) => Some(*value as i128),
+            (Value::Signed(value), None) => Some(*value as i128),
+            (Value::Struct(fields), Some(rest)) => Self::lookup(fields, rest),
+            _ => None,
+        }
+    }
+}
+
+struct Decoder<'a> {
+    structs: &'a [StructDef],
+    bytes: &'a [u8],
+    position: usize,
+    /// Values that can still be read
+    budget: usize,
+}
+
+/// Reads structure `index` at `offset`. When the bytes run out or an array length can't be
+/// worked out, the fields read so far come with the reason.
+pub fn apply(
+    structs: &[StructDef],
+    index: usize,
+    bytes: &[u8],
+    offset: usize,
+) -> (Field, Option<String>) {
+    let mut decoder = Decoder {
+        structs,
+        bytes,
+        position: offset,
+        budget: MAX_ITEMS,
+    };
+    let mut fields = Vec::new();
+    let result = decoder.read_struct(index, false, 0, &mut fields);
+    let root = Field {
+        name: structs[index].name.clone(),
+        type_name: structs[index].name.clone(),
+        offset,
+        length: decoder.position - offset,
+        value: Value::Struct(fields),
+    };
+    (root, result.err())
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, name: &str, length: usize) -> Result<&'a [u8], String> {
+        let start = self.position;
+        let all = self.bytes;
+        let bytes = start
+            .checked_add(length)
+            .and_then(|end| all.get(start..end))
+            .ok_or_else(|| {
+                format!(
+                    "{}: needs {} bytes at {:#x}, the file ends at {:#x}",
+                    name,
+                    length,
+                    start,
+                    self.bytes.len()
+                )
+            })?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn read_struct(
+        &mut self,
+        index: usize,
+        big_endian: bool,
+        depth: usize,
+        fields: &mut Vec<Field>,
+    ) -> Result<(), String> {
+        let structs = self.structs;
+        let structure = &structs[index];
+        if depth >= MAX_DEPTH {
+            return Err(format!("{} is nested too deeply", structure.name));
+        }
+        let big_endian = structure.big_endian.unwrap_or(big_endian);
+        for definition in &structure.fields {
+            let big_endian = definition.big_endian.unwrap_or(big_endian);
+            let Some(count) = &definition.count else {
+                self.read_item(definition, &definition.name, big_endian, depth, fields)?;
+                continue;
+            };
+            let count = expression::evaluate(count, |name| Field::lookup(fields, name))
+                .map_err(|err| format!("{}: {}", definition.name, err))?;
+            if !(0..=self.budget as i128).contains(&count) {
+                return Err(format!(
+                    "{}: {} is not a usable length",
+                    definition.name, count
+                ));
+            }
+            let count = count as usize;
+            let start = self.position;
+            let type_name = format!("{}[{}]", definition.type_name, count);
+            if definition.kind == FieldType::Primitive(Primitive::Char) {
+                let bytes = self.take(&definition.name, count)?;
+                let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+                fields.push(Field {
+                    name: definition.name.clone(),
+                    type_name,
+                    offset: start,
+                    length: count,
+                    value: Value::Text(String::from_utf8_lossy(&bytes[..end]).into_owned()),
+                });
+                continue;
+            }
+            let mut items = Vec::new();
+            let result = (0..count).try_for_each(|index| {
+                let name = format!("{}[{}]", definition.name, index);
+                self.read_item(definition, &name, big_endian, depth, &mut items)
+            });
+            fields.push(Field {
+                name: definition.name.clone(),
+                type_name,
+                offset: start,
+                length: self.position - start,
+                value: Value::Array(items),
+            });
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Reads a single value of the type of `definition`
+    fn read_item(
+        &mut self,
+        definition: &FieldDef,
+        name: &str,
+        big_endian: bool,
+        depth: usize,
+        fields: &mut Vec<Field>,
+    ) -> Result<(), String> {
+        self.budget = self
+            .budget
+            .checked_sub(1)
+            .ok_or_else(|| format!("{}: the template reads too many values", name))?;
+        let start = self.position;
+        let primitive = match definition.kind {
+            FieldType::Primitive(primitive) => primitive,
+            FieldType::Struct(index) => {
+                let mut inner = Vec::new();
+                let result = self.read_struct(index, big_endian, depth + 1, &mut inner);
+                fields.push(Field {
+                    name: name.to_string(),
+                    type_name: definition.type_name.clone(),
+                    offset: start,
+                    length: self.position - start,
+                    value: Value::Struct(inner),
+                });
+                return result;
+            }
+        };
+        let value = match primitive.size() {
+            Some(size) => {
+                let bytes = self.take(name, size)?;
+                number(primitive, bytes, big_endian)
+            }
+            None => {
+                let rest = &self.bytes[start.min(self.bytes.len())..];
+                let Some(end) = rest.iter().position(|b| *b == 0) else {
+                    return Err(format!("{}: no zero byte ends the string", name));
+                };
+                self.position += end + 1;
+                Value::Text(String::from_utf8_lossy(&rest[..end]).into_owned())
+            }
+        };
+        fields.push(Field {
+            name: name.to_string(),
+            type_name: definition.type_name.clone(),
+            offset: start,
+            length: self.position - start,
+            value,
+        });
+        Ok(())
+    }
+}
+
+/// Decodes a fixed-size value, `bytes` is as long as the type
+fn number(primitive: Primitive, bytes: &[u8], big_endian: bool) -> Value {
+    let mut buffer = [0; 8];
+    if big_endian {
+        buffer[8 - bytes.len()..].copy_from_slice(bytes);
+    } else {
+        buffer[..bytes.len()].copy_from_slice(bytes);
+    }
+    let raw = if big_endian {
+        u64::from_be_bytes(buffer)
+    } else {
+        u64::from_le_bytes(buffer)
+    };
+    match primitive {
+        Primitive::U8 | Primitive::U16 | Primitive::U32 | Primitive::U64 => Value::Unsigned(raw),
+        Primitive::I8 => Value::Signed(raw as u8 as i8 as i64),
+        Primitive::I16 => Value::Signed(raw as u16 as i16 as i64),
+        Primitive::I32 => Value::Signed(raw as u32 as i32 as i64),
+        Primitive::I64 => Value::Signed(raw as i64),
+        Primitive::F32 => Value::Float(f32::from_bits(raw as u32) as f64),
+        Primitive::F64 => Value::Float(f64::from_bits(raw)),
+        Primitive::Char => Value::Text((raw as u8 as char).to_string()),
+        Primitive::CString => unreachable!("strings have no fixed size"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::language::parse;
+    use super::*;
+
+    fn decode(template: &str, bytes: &[u8]) -> (Field, Option<String>) {
+        apply(&parse(template).unwrap(), 0, bytes, 0)
+    }
+
+    fn value<'a>(root: &'a Field, name: &str) -> &'a Value {
+        let Value::Struct(fields) = &root.value else {
+            unreachable!()
+        };
+        &fields
+            .iter()
+            .find(|field| field.name == name)
+            .unwrap()
+            .value
+    }
+
+    #[test]
+    fn endianness() {
+        let bytes = [0x12, 0x34, 0x12, 0x34, 0x12, 0x34, 0xff, 0xfe];
+        let template = "be struct A { u16 big; le u16 little; Inner inner; i16 negative; };\n\
+                        struct Inner { u16 value; }";
+        let (root, error) = decode(template, &bytes);
+        assert_eq!(error, None);
+        assert_eq!(root.length, 8);
+        assert_eq!(value(&root, "big"), &Value::Unsigned(0x1234));
+        assert_eq!(value(&root, "little"), &Value::Unsigned(0x3412));
+        // The structure takes the byte order of the field it is in
+        let inner = Value::Struct(vec![Field {
+            name: "value".to_string(),
+            type_name: "u16".to_string(),
+            offset: 4,
+            length: 2,
+            value: Value::Unsigned(0x1234),
+        }]);
+        assert_eq!(value(&root, "inner"), &inner);
+        assert_eq!(value(&root, "negative"), &Value::Signed(-2));
+        let (root, _) = decode(
+            "struct A { u16 little; be u16 big; f32 float; }",
+            &[1, 0, 0, 1, 0, 0, 0x80, 0x3f],
+        );
+        assert_eq!(value(&root, "little"), &Value::Unsigned(1));
+        assert_eq!(value(&root, "big"), &Value::Unsigned(1));
+        assert_eq!(value(&root, "float"), &Value::Float(1.0));
+    }
+
+    #[test]
+    fn arrays() {
+        let template =
+            "struct A { u8 header; u8 count; u16 items[count * 2 - 1]; char name[4]; }\n\
+                        struct B { A a; u8 rest[a.count]; }";
+        let bytes = [7, 2, 1, 0, 2, 0, 3, 0, b'a', b'b', 0, b'c', 9, 9];
+        let (root, error) = decode(template, &bytes);
+        assert_eq!(error, None);
+        let Value::Array(items) = value(&root, "items") else {
+            panic!("items isn't an array");
+        };
+        let items = items.iter().map(|item| &item.value).collect::<Vec<_>>();
+        assert_eq!(
+            items,
+            [1, 2, 3].map(Value::Unsigned).iter().collect::<Vec<_>>()
+        );
+        // Char arrays are text up to the first zero byte
+        assert_eq!(value(&root, "name"), &Value::Text("ab".to_string()));
+        assert_eq!(root.length, 12);
+        // Lengths can refer to fields of nested structures
+        let (root, error) = apply(&parse(template).unwrap(), 1, &bytes, 0);
+        assert_eq!(error, None);
+        assert_eq!(root.length, 14);
+        let (_, error) = decode("struct A { u8 count; u8 items[size]; }", &bytes);
+        assert!(error.unwrap().starts_with("items: "));
+    }
+
+    #[test]
+    fn cstrings() {
+        let (root, error) = decode("struct A { cstring a; cstring b; u8 c; }", b"ab\0\0\x05");
+        assert_eq!(error, None);
+        assert_eq!(value(&root, "a"), &Value::Text("ab".to_string()));
+        assert_eq!(value(&root, "b"), &Value::Text(String::new()));
+        assert_eq!(value(&root, "c"), &Value::Unsigned(5));
+        let (_, error) = decode("struct A { cstring a; }", b"abc");
+        assert_eq!(error.unwrap(), "a: no zero byte ends the string");
+    }
+
+    #[test]
+    fn cut_off() {
+        let (root, error) = decode("struct A { u16 a; u32 b; }", &[1, 0, 2, 0]);
+        assert_eq!(
+            error.unwrap(),
+            "b: needs 4 bytes at 0x2, the file ends at 0x4"
+        );
+        // What was read before the end is kept
+        assert_eq!(value(&root, "a"), &Value::Unsigned(1));
+        assert_eq!(root.length, 2);
+        let (root, error) = decode("struct A { u16 a[3]; }", &[1, 0, 2, 0, 3]);
+        assert_eq!(
+            error.unwrap(),
+            "a[2]: needs 2 bytes at 0x4, the file ends at 0x5"
+        );
+        let Value::Array(items) = value(&root, "a") else {
+            panic!("a isn't an array");
+        };
+        assert_eq!(items.len(), 2);
+    }
+
+    #[test]
+    fn limits() {
+        let (_, error) = decode("struct A { A a; }", &[]);
+        assert_eq!(error.unwrap(), "A is nested too deeply");
+        let (_, error) = decode("struct A { u8 a[2000000]; }", &[]);
+        assert_eq!(error.unwrap(), "a: 2000000 is not a usable length");
+        // Each array is small, the arrays of empty structures inside each other aren't
+        let template =
+            "struct A { B b[1000]; }\nstruct B { C c[1000]; }\nstruct C { D d[1000]; }\n\
+                        struct D { }";
+        let (_, error) = decode(template, &[]);
+        assert!(error
+            .unwrap()
+            .ends_with(": the template reads too many values"));
+    }
+}
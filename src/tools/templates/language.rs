@@ -0,0 +1,415 @@
+//! Template declarations, C-like structures laid over the bytes of a file:
+//!
+//! ```text
+//! be struct Header {
+//!     char magic[4];
+//!     u16 count;
+//!     le u32 size;
+//!     Entry entries[count];
+//!     cstring name;
+//! };
+//! ```
+//!
+//! Fields follow each other without padding. Numbers are little-endian unless the structure or
+//! the field is marked `be`, and a mark on a field of a structure type applies to the fields
+//! inside. Array lengths are expressions over the fields before them.
+
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Primitive {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    /// A single byte of text, arrays of them are strings
+    Char,
+    /// Text up to and including a zero byte
+    CString,
+}
+
+impl Primitive {
+    /// Type names, with the C ones as aliases
+    const NAMES: [(&'static str, Primitive); 22] = [
+        ("u8", Primitive::U8),
+        ("u16", Primitive::U16),
+        ("u32", Primitive::U32),
+        ("u64", Primitive::U64),
+        ("i8", Primitive::I8),
+        ("i16", Primitive::I16),
+        ("i32", Primitive::I32),
+        ("i64", Primitive::I64),
+        ("f32", Primitive::F32),
+        ("f64", Primitive::F64),
+        ("char", Primitive::Char),
+        ("cstring", Primitive::CString),
+        ("uint8_t", Primitive::U8),
+        ("uint16_t", Primitive::U16),
+        ("uint32_t", Primitive::U32),
+        ("uint64_t", Primitive::U64),
+        ("int8_t", Primitive::I8),
+        ("int16_t", Primitive::I16),
+        ("int32_t", Primitive::I32),
+        ("int64_t", Primitive::I64),
+        ("float", Primitive::F32),
+        ("double", Primitive::F64),
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .find(|(primitive, _)| *primitive == name)
+            .map(|(_, primitive)| *primitive)
+    }
+
+    /// Number of bytes the value takes, `None` if it depends on the data
+    pub fn size(self) -> Option<usize> {
+        match self {
+            Primitive::U8 | Primitive::I8 | Primitive::Char => Some(1),
+            Primitive::U16 | Primitive::I16 => Some(2),
+            Primitive::U32 | Primitive::I32 | Primitive::F32 => Some(4),
+            Primitive::U64 | Primitive::I64 | Primitive::F64 => Some(8),
+            Primitive::CString => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    Primitive(Primitive),
+    /// Index of the structure in the declarations
+    Struct(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldDef {
+    pub name: String,
+    /// Type as written in the template
+    pub type_name: String,
+    pub kind: FieldType,
+    /// Byte order the field is marked with, if any
+    pub big_endian: Option<bool>,
+    /// Expression for the number of items of an array
+    pub count: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructDef {
+    pub name: String,
+    pub big_endian: Option<bool>,
+    pub fields: Vec<FieldDef>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TemplateError {
+    /// One-based line number
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Symbol(char),
+    /// Text between the brackets of an array
+    Count(String),
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, TemplateError> {
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut chars = text.char_indices().peekable();
+    let error = |line, message: String| TemplateError { line, message };
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            '/' if text[start..].starts_with("//") => {
+                while chars.next_if(|(_, c)| *c != '\n').is_some() {}
+            }
+            '/' if text[start..].starts_with("/*") => {
+                let start_line = line;
+                chars.next();
+                loop {
+                    match chars.next() {
+                        Some((index, '*')) if text[index..].starts_with("*/") => {
+                            chars.next();
+                            break;
+                        }
+                        Some((_, '\n')) => line += 1,
+                        Some(_) => {}
+                        None => return Err(error(start_line, "unclosed comment".to_string())),
+                    }
+                }
+            }
+            '{' | '}' | ';' => tokens.push((Token::Symbol(c), line)),
+            '[' => {
+                let Some(length) = text[start..].find(']') else {
+                    return Err(error(line, "missing ']'".to_string()));
+                };
+                let count = &text[start + 1..start + length];
+                if count.contains(['\n', '[']) {
+                    return Err(error(line, "missing ']'".to_string()));
+                }
+                tokens.push((Token::Count(count.trim().to_string()), line));
+                while chars
+                    .next_if(|(index, _)| *index <= start + length)
+                    .is_some()
+                {}
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut end = start + 1;
+                while let Some((index, c)) =
+                    chars.next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_')
+                {
+                    end = index + c.len_utf8();
+                }
+                tokens.push((Token::Word(text[start..end].to_string()), line));
+            }
+            c => return Err(error(line, format!("unexpected '{}'", c))),
+        }
+    }
+    Ok(tokens)
+}
+
+/// A field before the structure types it refers to are known
+struct PendingField {
+    field: FieldDef,
+    line: usize,
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+}
+
+impl Parser {
+    fn line(&self) -> usize {
+        match self.tokens.get(self.position) {
+            Some((_, line)) => *line,
+            None => self.tokens.last().map_or(1, |(_, line)| *line),
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> TemplateError {
+        TemplateError {
+            line: self.line(),
+            message: message.into(),
+        }
+    }
+
+    /// Takes the next token if it is the word `keyword`
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let matches = matches!(self.tokens.get(self.position), Some((Token::Word(word), _)) if word == keyword);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        let matches =
+            matches!(self.tokens.get(self.position), Some((Token::Symbol(c), _)) if *c == symbol);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), TemplateError> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", symbol)))
+        }
+    }
+
+    fn word(&mut self, what: &str) -> Result<String, TemplateError> {
+        match self.tokens.get(self.position) {
+            Some((Token::Word(word), _)) => {
+                let word = word.clone();
+                self.position += 1;
+                Ok(word)
+            }
+            _ => Err(self.error(format!("expected {}", what))),
+        }
+    }
+
+    /// `be` or `le` in front of a structure or a field
+    fn endianness(&mut self) -> Option<bool> {
+        if self.keyword("be") {
+            Some(true)
+        } else if self.keyword("le") {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    fn declaration(&mut self) -> Result<(StructDef, Vec<PendingField>), TemplateError> {
+        let big_endian = self.endianness();
+        if !self.keyword("struct") {
+            return Err(self.error("expected 'struct'"));
+        }
+        let name = self.word("a structure name")?;
+        self.expect('{')?;
+        let mut fields: Vec<PendingField> = Vec::new();
+        while !self.eat('}') {
+            let line = self.line();
+            let big_endian = self.endianness();
+            let type_name = self.word("a type or '}'")?;
+            let name = self.word("a field name")?;
+            if fields.iter().any(|pending| pending.field.name == name) {
+                return Err(self.error(format!("'{}' is declared twice", name)));
+            }
+            let count = match self.tokens.get(self.position) {
+                Some((Token::Count(count), _)) if count.is_empty() => {
+                    return Err(self.error("arrays need a length"));
+                }
+                Some((Token::Count(count), _)) => {
+                    let count = count.clone();
+                    self.position += 1;
+                    Some(count)
+                }
+                _ => None,
+            };
+            self.expect(';')?;
+            let kind = match Primitive::from_name(&type_name) {
+                Some(primitive) => FieldType::Primitive(primitive),
+                // Resolved once every structure is declared
+                None => FieldType::Struct(usize::MAX),
+            };
+            let field = FieldDef {
+                name,
+                type_name,
+                kind,
+                big_endian,
+                count,
+            };
+            fields.push(PendingField { field, line });
+        }
+        self.eat(';');
+        let structure = StructDef {
+            name,
+            big_endian,
+            fields: Vec::new(),
+        };
+        Ok((structure, fields))
+    }
+}
+
+/// Parses the structures declared in `text`
+pub fn parse(text: &str) -> Result<Vec<StructDef>, TemplateError> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        position: 0,
+    };
+    let mut declarations = Vec::new();
+    while parser.position < parser.tokens.len() {
+        let line = parser.line();
+        let (structure, fields) = parser.declaration()?;
+        if declarations
+            .iter()
+            .any(|(other, _): &(StructDef, _)| other.name == structure.name)
+        {
+            let message = format!("structure '{}' is declared twice", structure.name);
+            return Err(TemplateError { line, message });
+        }
+        declarations.push((structure, fields));
+    }
+    let names = declarations
+        .iter()
+        .map(|(structure, _)| structure.name.clone())
+        .collect::<Vec<_>>();
+    declarations
+        .into_iter()
+        .map(|(mut structure, fields)| {
+            for PendingField { mut field, line } in fields {
+                if field.kind == FieldType::Struct(usize::MAX) {
+                    let Some(index) = names.iter().position(|name| *name == field.type_name) else {
+                        let message = format!("unknown type '{}'", field.type_name);
+                        return Err(TemplateError { line, message });
+                    };
+                    field.kind = FieldType::Struct(index);
+                }
+                structure.fields.push(field);
+            }
+            Ok(structure)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> TemplateError {
+        parse(text).unwrap_err()
+    }
+
+    #[test]
+    fn declarations() {
+        let structs = parse(
+            "// A header\n\
+             be struct Header {\n\
+                 char magic[4];\n\
+                 le uint16_t count;\n\
+                 /* the entries */ Entry entries[count * 2];\n\
+             };\n\
+             struct Entry { cstring name; }",
+        )
+        .unwrap();
+        assert_eq!(structs.len(), 2);
+        assert_eq!(structs[0].big_endian, Some(true));
+        assert_eq!(structs[1].big_endian, None);
+        let fields = &structs[0].fields;
+        assert_eq!(fields[0].kind, FieldType::Primitive(Primitive::Char));
+        assert_eq!(fields[0].count.as_deref(), Some("4"));
+        assert_eq!(fields[1].kind, FieldType::Primitive(Primitive::U16));
+        assert_eq!(fields[1].big_endian, Some(false));
+        // Entry is declared after the structure using it
+        assert_eq!(fields[2].kind, FieldType::Struct(1));
+        assert_eq!(fields[2].type_name, "Entry");
+        assert_eq!(fields[2].count.as_deref(), Some("count * 2"));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let missing_semicolon = error("struct A {\n    u8 a;\n    u8 b\n};");
+        assert_eq!(missing_semicolon.line, 4);
+        assert_eq!(missing_semicolon.message, "expected ';'");
+        assert_eq!(error("struct A {\n  u8 a[];\n}").line, 2);
+        assert_eq!(error("struct A {\n  u8 a[4;\n}").message, "missing ']'");
+        assert_eq!(error("\n\n/* open\n struct A {}").line, 3);
+        assert_eq!(error("struct A {\n  u8 a = 1;\n}").line, 2);
+        assert_eq!(error("union A {}").message, "expected 'struct'");
+        assert_eq!(
+            error("struct A {\n  u8 a;").message,
+            "expected a type or '}'"
+        );
+    }
+
+    #[test]
+    fn names() {
+        let twice = error("struct A { u8 a; }\n\nstruct A { u8 b; }");
+        assert_eq!(twice.line, 3);
+        assert_eq!(twice.message, "structure 'A' is declared twice");
+        let field_twice = error("struct A {\n  u8 a;\n  u16 a;\n}");
+        assert_eq!(field_twice.message, "'a' is declared twice");
+        let unknown = error("struct A {\n  u8 a;\n  B b;\n}");
+        assert_eq!(unknown.line, 3);
+        assert_eq!(unknown.message, "unknown type 'B'");
+    }
+}
@@ -0,0 +1,409 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::Sender,
+        Arc,
+    },
+};
+
+use egui::{mutex::RwLock, Color32};
+
+use super::GaffrieTool;
+use crate::{
+    document::{Document, FileData},
+    Event, Selection,
+};
+
+mod decode;
+mod language;
+
+use decode::{Field, Value};
+use language::{StructDef, TemplateError};
+
+/// Items of an array shown at most, more would make the tool crawl
+const SHOWN_ITEMS: usize = 1000;
+/// Arrays of numbers up to this long are shown on a single line
+const INLINE_ITEMS: usize = 16;
+
+/// Colors the fields take in turn, they are toned down behind the bytes in the hex view
+const PALETTE: [Color32; 6] = [
+    Color32::from_rgb(230, 90, 90),
+    Color32::from_rgb(90, 190, 90),
+    Color32::from_rgb(90, 130, 230),
+    Color32::from_rgb(220, 180, 60),
+    Color32::from_rgb(180, 100, 220),
+    Color32::from_rgb(60, 190, 200),
+];
+/// Opacity of the colors in the hex view
+const OVERLAY_OPACITY: f32 = 0.35;
+
+/// What the templates look like until the user writes their own
+const EXAMPLE: &str = "\
+// Fields follow each other without padding, numbers are little-endian
+// unless the structure or the field is marked `be`
+struct Entry {
+    u32 offset;
+    u16 length;
+    be u16 id;
+};
+
+struct Example {
+    char magic[4];
+    u32 version;
+    u16 count;
+    Entry entries[count];
+    cstring name;
+};
+";
+
+/// Template declarations shared by the template tools of every document, saved with the
+/// session
+#[derive(Clone)]
+pub struct TemplateLibrary(Rc<RefCell<String>>);
+
+impl Default for TemplateLibrary {
+    fn default() -> Self {
+        Self(Rc::new(RefCell::new(EXAMPLE.to_string())))
+    }
+}
+
+impl TemplateLibrary {
+    pub fn source(&self) -> String {
+        self.0.borrow().clone()
+    }
+
+    pub fn set_source(&self, source: String) {
+        *self.0.borrow_mut() = source;
+    }
+}
+
+/// A template read at an offset of the file
+struct Applied {
+    root: Field,
+    /// Why reading stopped before the end of the template
+    error: Option<String>,
+    /// Bytes of every field and their colors, by offset
+    colors: Vec<(Selection, Color32)>,
+}
+
+pub struct StructureTemplates {
+    /// Tells the colors of several template tools on a document apart in the hex view
+    id: usize,
+    file: Arc<RwLock<FileData>>,
+    events: Sender<Event>,
+    library: TemplateLibrary,
+    /// Library text the declarations were parsed from, to notice edits
+    parsed_source: Option<String>,
+    structs: Result<Vec<StructDef>, TemplateError>,
+    /// Name of the structure to apply
+    selected: Option<String>,
+    cursor: usize,
+    /// Where the template is applied, `None` until it is
+    offset: Option<usize>,
+    /// Apply the template wherever the cursor moves
+    follow_cursor: bool,
+    applied: Option<Applied>,
+    hex: bool,
+    editing: bool,
+    /// Bytes last pointed out in the other tools
+    pointed_out: Option<Selection>,
+}
+
+impl GaffrieTool for StructureTemplates {
+    fn new(document: &Document) -> Self
+    where
+        Self: Sized,
+    {
+        Self::with_library(document, TemplateLibrary::default())
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        if self.parsed_source.as_deref() != Some(self.library.0.borrow().as_str()) {
+            self.parse();
+        }
+        let previous = self.selected.clone();
+        let mut apply = false;
+        ui.horizontal(|ui| {
+            let names = match &self.structs {
+                Ok(structs) => structs.iter().map(|s| s.name.clone()).collect(),
+                Err(_) => Vec::new(),
+            };
+            egui::ComboBox::from_id_source("template_struct")
+                .selected_text(self.selected.as_deref().unwrap_or("No structure"))
+                .show_ui(ui, |ui| {
+                    for name in names {
+                        let text = name.clone();
+                        ui.selectable_value(&mut self.selected, Some(name), text);
+                    }
+                });
+            apply = ui
+                .add_enabled(
+                    self.selected.is_some(),
+                    egui::Button::new("Apply at cursor"),
+                )
+                .on_hover_text(format!("Read the structure at {:#x}", self.cursor))
+                .clicked();
+            ui.checkbox(&mut self.follow_cursor, "Follow cursor");
+            if ui
+                .add_enabled(self.offset.is_some(), egui::Button::new("Clear"))
+                .clicked()
+            {
+                self.offset = None;
+                self.update();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.toggle_value(&mut self.editing, "Edit templates");
+            ui.checkbox(&mut self.hex, "Hexadecimal numbers");
+        });
+        if apply {
+            self.offset = Some(self.cursor);
+        }
+        if apply || self.selected != previous {
+            self.update();
+        }
+        if self.editing {
+            egui::ScrollArea::vertical()
+                .id_source("template_source")
+                .max_height(ui.available_height() / 2.0)
+                .show(ui, |ui| {
+                    let mut source = self.library.0.borrow_mut();
+                    ui.add(
+                        egui::TextEdit::multiline(&mut *source)
+                            .font(egui::TextStyle::Monospace)
+                            .code_editor()
+                            .desired_rows(12)
+                            .desired_width(f32::INFINITY),
+                    );
+                });
+        }
+        if let Err(err) = &self.structs {
+            ui.colored_label(ui.visuals().error_fg_color, err.to_string());
+        }
+        ui.separator();
+
+        let Some(applied) = &self.applied else {
+            ui.weak("Pick a structure and apply it at the cursor");
+            return;
+        };
+        if let Some(err) = &applied.error {
+            ui.colored_label(ui.visuals().error_fg_color, format!("⚠ {}", err));
+        }
+        let mut hovered = None;
+        egui::ScrollArea::vertical()
+            .id_source("template_fields")
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                let title = format!("{} at {:#x}", applied.root.name, applied.root.offset);
+                self.field_ui(ui, &title, &applied.root, applied, &mut hovered);
+            });
+        if hovered != self.pointed_out {
+            self.pointed_out = hovered;
+            let _ = self.events.send(Event::Highlight(hovered));
+        }
+    }
+
+    fn title(&self) -> String {
+        "Structure Templates".to_string()
+    }
+
+    fn notify(&mut self, event: Event) {
+        match event {
+            Event::FileChanged => self.update(),
+            Event::CursorMoved(offset) => self.cursor_moved(offset),
+            Event::SelectionChanged(selection) => self.cursor_moved(selection.offset),
+            _ => {}
+        }
+    }
+}
+
+impl Drop for StructureTemplates {
+    fn drop(&mut self) {
+        // Closing the tool takes its colors out of the hex view
+        if self.applied.is_some() {
+            let _ = self.events.send(Event::Overlay {
+                source: self.id,
+                colors: Vec::new(),
+            });
+        }
+    }
+}
+
+impl StructureTemplates {
+    /// Tool editing and applying the templates of `library`
+    pub fn with_library(document: &Document, library: TemplateLibrary) -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            file: document.file.clone(),
+            events: document.events.clone(),
+            library,
+            parsed_source: None,
+            structs: Ok(Vec::new()),
+            selected: None,
+            cursor: document.selection.offset,
+            offset: None,
+            follow_cursor: false,
+            applied: None,
+            hex: false,
+            editing: false,
+            pointed_out: None,
+        }
+    }
+
+    fn cursor_moved(&mut self, offset: usize) {
+        self.cursor = offset;
+        if self.follow_cursor && self.selected.is_some() && self.offset != Some(offset) {
+            self.offset = Some(offset);
+            self.update();
+        }
+    }
+
+    fn parse(&mut self) {
+        let source = self.library.source();
+        self.structs = language::parse(&source);
+        self.parsed_source = Some(source);
+        if let Ok(structs) = &self.structs {
+            let exists = |name: &String| structs.iter().any(|s| s.name == *name);
+            if !self.selected.as_ref().is_some_and(exists) {
+                self.selected = structs.last().map(|s| s.name.clone());
+            }
+        }
+        // A broken template keeps showing what was read with the last good one
+        if self.structs.is_ok() {
+            self.update();
+        }
+    }
+
+    /// Reads the template again and colors its bytes in the hex view
+    fn update(&mut self) {
+        let had_colors = self.applied.is_some();
+        self.applied = None;
+        if let (Some(offset), Some(name), Ok(structs)) =
+            (self.offset, &self.selected, &self.structs)
+        {
+            if let Some(index) = structs.iter().position(|s| s.name == *name) {
+                let (root, error) = decode::apply(structs, index, &self.file.read(), offset);
+                let colors = root
+                    .leaves()
+                    .into_iter()
+                    .filter(|field| field.length > 0)
+                    .enumerate()
+                    .map(|(index, field)| {
+                        let selection = Selection::new(field.offset, field.length);
+                        (selection, PALETTE[index % PALETTE.len()])
+                    })
+                    .collect();
+                self.applied = Some(Applied {
+                    root,
+                    error,
+                    colors,
+                });
+            }
+        }
+        let colors = match &self.applied {
+            Some(applied) => applied
+                .colors
+                .iter()
+                .map(|(selection, color)| (*selection, color.gamma_multiply(OVERLAY_OPACITY)))
+                .collect(),
+            None if had_colors => Vec::new(),
+            None => return,
+        };
+        let _ = self.events.send(Event::Overlay {
+            source: self.id,
+            colors,
+        });
+    }
+
+    fn field_ui(
+        &self,
+        ui: &mut egui::Ui,
+        name: &str,
+        field: &Field,
+        applied: &Applied,
+        hovered: &mut Option<Selection>,
+    ) {
+        let selection = Selection::new(field.offset, field.length);
+        let nested = match &field.value {
+            Value::Struct(fields) => Some(fields),
+            Value::Array(items)
+                if items.len() > INLINE_ITEMS || items.iter().any(Field::is_struct) =>
+            {
+                Some(items)
+            }
+            _ => None,
+        };
+        let response = match nested {
+            Some(fields) => {
+                let title = format!("{}: {}", name, field.type_name);
+                egui::CollapsingHeader::new(title)
+                    .id_source((field.offset, &field.name, &field.type_name))
+                    .default_open(field.is_struct())
+                    .show(ui, |ui| {
+                        for item in fields.iter().take(SHOWN_ITEMS) {
+                            self.field_ui(ui, &item.name, item, applied, hovered);
+                        }
+                        if fields.len() > SHOWN_ITEMS {
+                            ui.weak(format!("… {} more", fields.len() - SHOWN_ITEMS));
+                        }
+                    })
+                    .header_response
+            }
+            None => {
+                let color = color_at(&applied.colors, field.offset)
+                    .filter(|_| field.length > 0)
+                    .unwrap_or(Color32::TRANSPARENT);
+                let text = self.value_text(&field.value);
+                ui.horizontal(|ui| {
+                    ui.colored_label(color, "■");
+                    ui.label(format!("{}: {}", name, field.type_name));
+                    ui.label("=");
+                    ui.monospace(text);
+                })
+                .response
+                .interact(egui::Sense::click())
+            }
+        };
+        if response.hovered() {
+            *hovered = Some(selection);
+        }
+        let response = response.on_hover_text(format!(
+            "{:#x}..{:#x}, click to select",
+            field.offset,
+            field.end()
+        ));
+        if response.clicked() {
+            let _ = self.events.send(Event::SelectionChanged(selection));
+        }
+    }
+
+    fn value_text(&self, value: &Value) -> String {
+        match value {
+            Value::Unsigned(value) if self.hex => format!("{:#x}", value),
+            Value::Unsigned(value) => value.to_string(),
+            Value::Signed(value) => value.to_string(),
+            Value::Float(value) => value.to_string(),
+            Value::Text(text) => format!("{:?}", text),
+            Value::Array(items) => {
+                let values = items
+                    .iter()
+                    .map(|item| self.value_text(&item.value))
+                    .collect::<Vec<_>>();
+                format!("[{}]", values.join(", "))
+            }
+            Value::Struct(_) => unreachable!("shown as collapsing sections"),
+        }
+    }
+}
+
+/// Color of the field covering `offset`, `colors` are sorted and don't overlap
+pub fn color_at(colors: &[(Selection, Color32)], offset: usize) -> Option<Color32> {
+    let index = colors.partition_point(|(selection, _)| selection.end() <= offset);
+    colors
+        .get(index)
+        .filter(|(selection, _)| selection.contains(offset))
+        .map(|(_, color)| *color)
+}